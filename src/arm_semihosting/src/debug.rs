@@ -88,6 +88,16 @@ pub fn exit(status: ExitStatus) {
 ///
 pub fn report_exception(reason: Exception) {
     let code = reason as usize;
+
+    // In AArch64 state, the parameter is a pointer to a parameter block
+    // containing the reason code and a subcode.
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        let block = [code, 0];
+        syscall1!(REPORT_EXCEPTION, block.as_ptr());
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
     unsafe {
         syscall1!(REPORT_EXCEPTION, code);
     }
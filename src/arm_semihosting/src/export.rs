@@ -22,7 +22,23 @@ fn interrupt_free<R>(f: impl FnOnce() -> R) -> R {
     ret
 }
 
-#[cfg(not(arm))]
+#[cfg(target_arch = "aarch64")]
+fn interrupt_free<R>(f: impl FnOnce() -> R) -> R {
+    use core::arch::asm;
+    let daif_old: u64;
+    unsafe { asm!("mrs {}, daif", out(reg) daif_old) };
+    unsafe { asm!("msr daifset, #2") };
+
+    let ret = f();
+
+    if daif_old & 0x80 == 0 {
+        unsafe { asm!("msr daifclr, #2") };
+    }
+
+    ret
+}
+
+#[cfg(not(any(arm, target_arch = "aarch64")))]
 fn interrupt_free<R>(_: impl FnOnce() -> R) -> R {
     unreachable!();
 }
@@ -1,4 +1,4 @@
-//! Semihosting for ARM Cortex-A processors (forked from [`cortex-m-semihosting`])
+//! Semihosting for ARM Cortex-A processors in AArch32 and AArch64 states (forked from [`cortex-m-semihosting`])
 //!
 //! [`cortex-m-semihosting`]: https://github.com/rust-embedded/cortex-m-semihosting
 //!
@@ -95,7 +95,18 @@ pub unsafe fn syscall1(_nr: usize, _arg: usize) -> usize {
         #[cfg(all(not(thumb), arm, feature = "no-semihosting"))]
         () => 0,
 
-        #[cfg(not(arm))]
+        #[cfg(all(target_arch = "aarch64", not(feature = "no-semihosting")))]
+        () => {
+            use core::arch::asm;
+            let mut nr = _nr;
+            asm!("hlt 0xf000", inout("x0") nr, in("x1") _arg);
+            nr
+        }
+
+        #[cfg(all(target_arch = "aarch64", feature = "no-semihosting"))]
+        () => 0,
+
+        #[cfg(not(any(arm, target_arch = "aarch64")))]
         () => unimplemented!(),
     }
 }
@@ -0,0 +1,293 @@
+//! Integration with [`defmt`], a highly efficient logging framework for
+//! resource-constrained devices.
+//!
+//! This module provides the building blocks for a [global logger][1] and a
+//! [timestamp provider][2]. They are instantiated for a specific kernel by the
+//! [`defmt_global_logger!`][3] and [`defmt_timestamp!`][4] macros because
+//! `defmt` requires them to be defined exactly once in an application.
+//!
+//! The application must depend on `defmt` directly because the expansions of
+//! these macros refer to it.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! type System = r3_kernel::System<SystemTraits>;
+//!
+//! fn write_uart(bytes: &[u8]) {
+//!     // Output `bytes` to a serial port or an RTT channel
+//! }
+//!
+//! r3::defmt_global_logger!(System, write_uart);
+//! r3::defmt_timestamp!(System);
+//! ```
+//!
+//! The output can be decoded by `defmt-print`, `probe-run`, or `r3_test_runner`.
+//!
+//! [1]: https://defmt.ferrous-systems.com/global-logger.html
+//! [2]: https://defmt.ferrous-systems.com/timestamps.html
+//! [3]: crate::defmt_global_logger
+//! [4]: crate::defmt_timestamp
+use core::cell::{Cell, UnsafeCell};
+
+use crate::kernel::{prelude::*, traits};
+
+/// The state of the [`defmt` global logger][1] defined by
+/// [`defmt_global_logger!`][2].
+///
+/// A log frame is written while holding [CPU Lock][3], so frames from
+/// different contexts are never interleaved. Frames can't be logged from
+/// [unmanaged interrupt handlers][4] because they are not masked by CPU Lock.
+///
+/// [1]: defmt::Logger
+/// [2]: crate::defmt_global_logger
+/// [3]: crate#system-states
+/// [4]: crate#interrupt-handling-framework
+pub struct LoggerState {
+    encoder: UnsafeCell<defmt::Encoder>,
+    /// `true` if a frame is being written.
+    taken: Cell<bool>,
+    /// `true` if CPU Lock was already active when the current frame was
+    /// started.
+    cpu_lock_was_active: Cell<bool>,
+    /// The timestamp of the current frame in microseconds. Captured by
+    /// [`Self::acquire`] because [`Kernel::time`][1] is unavailable while CPU
+    /// Lock is active.
+    ///
+    /// [1]: crate::kernel::Kernel::time
+    timestamp: Cell<u64>,
+}
+
+/// The state of the `defmt` global logger. `defmt` allows only one global
+/// logger in an application, so [`defmt_global_logger!`][1] uses this
+/// instance, through which [`defmt_timestamp!`][2] finds the timestamp of the
+/// current frame.
+///
+/// [1]: crate::defmt_global_logger
+/// [2]: crate::defmt_timestamp
+#[doc(hidden)]
+pub static GLOBAL_LOGGER_STATE: LoggerState = LoggerState::new();
+
+// Safety: The fields are only accessed while holding CPU Lock
+unsafe impl Sync for LoggerState {}
+
+impl LoggerState {
+    /// Construct a `LoggerState`.
+    pub const fn new() -> Self {
+        Self {
+            encoder: UnsafeCell::new(defmt::Encoder::new()),
+            taken: Cell::new(false),
+            cpu_lock_was_active: Cell::new(false),
+            timestamp: Cell::new(0),
+        }
+    }
+
+    /// Implements [`defmt::Logger::acquire`].
+    ///
+    /// This method also captures the timestamp of the frame. If the current
+    /// system time is unavailable in the current context (e.g., in an
+    /// interrupt handler), the frame reuses the timestamp of the previous
+    /// frame, so timestamps never go backward.
+    #[inline]
+    pub fn acquire<System: traits::KernelTime>(&self, write: fn(&[u8])) {
+        // `Kernel::time` fails while CPU Lock is active, so read it first
+        let time = System::time();
+
+        // `acquire_cpu_lock` fails if CPU Lock is already active, in which
+        // case we must not release it in `release`
+        let cpu_lock_was_active = System::acquire_cpu_lock().is_err();
+
+        if self.taken.get() {
+            // `defmt` prohibits reentrancy
+            panic!("the `defmt` logger was taken reentrantly");
+        }
+        self.taken.set(true);
+        self.cpu_lock_was_active.set(cpu_lock_was_active);
+        if let Ok(time) = time {
+            self.timestamp
+                .set(self.timestamp.get().max(time.as_micros()));
+        }
+
+        // Safety: We hold CPU Lock, so we have exclusive access to `encoder`
+        unsafe { (*self.encoder.get()).start_frame(write) };
+    }
+
+    /// Implements [`defmt::Logger::release`].
+    ///
+    /// # Safety
+    ///
+    /// Must be paired with a preceding call to [`Self::acquire`].
+    #[inline]
+    pub unsafe fn release<System: traits::KernelBase>(&self, write: fn(&[u8])) {
+        // Safety: We hold CPU Lock, so we have exclusive access to `encoder`
+        unsafe { (*self.encoder.get()).end_frame(write) };
+        self.taken.set(false);
+
+        if !self.cpu_lock_was_active.get() {
+            // Safety: CPU Lock was acquired by `acquire`
+            unsafe { System::release_cpu_lock().unwrap() };
+        }
+    }
+
+    /// Implements [`defmt::Logger::write`].
+    ///
+    /// # Safety
+    ///
+    /// Must be called between [`Self::acquire`] and [`Self::release`].
+    #[inline]
+    pub unsafe fn write(&self, bytes: &[u8], write: fn(&[u8])) {
+        // Safety: We hold CPU Lock, so we have exclusive access to `encoder`
+        unsafe { (*self.encoder.get()).write(bytes, write) };
+    }
+
+    /// Get the timestamp of the current frame in microseconds. Returns `None`
+    /// if no frame is being written.
+    ///
+    /// This method must be called while CPU Lock is active.
+    #[inline]
+    pub fn timestamp_micros(&self) -> Option<u64> {
+        self.taken.get().then(|| self.timestamp.get())
+    }
+}
+
+impl Default for LoggerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the timestamp for a `defmt` log frame in microseconds. Used by
+/// [`defmt_timestamp!`][1].
+///
+/// If the frame is written by [`defmt_global_logger!`][2], this function
+/// returns the timestamp captured when the frame was started. Otherwise, it
+/// returns the current system time, or `0` if the system time is unavailable
+/// in the current context (e.g., in an interrupt handler or while CPU Lock is
+/// active).
+///
+/// # Example
+///
+#[doc = crate::tests::doc_test!(
+/// ```rust
+/// use r3::{
+///     defmt_logger::{timestamp_micros, GLOBAL_LOGGER_STATE},
+///     kernel::StaticTask,
+///     time::Duration,
+/// };
+///
+/// struct Objects;
+///
+/// const fn configure_app<C>(cfg: &mut Cfg<C>) -> Objects
+/// where
+///     C: ~const traits::CfgTask<System = System>,
+/// {
+///     StaticTask::define()
+///         .start(task1_body)
+///         .priority(1)
+///         .active(true)
+///         .finish(cfg);
+///     Objects
+/// }
+///
+/// fn task1_body() {
+///     // Emulate what `defmt` does in `defmt_global_logger!`'s frames
+///     let frame_timestamp = || {
+///         GLOBAL_LOGGER_STATE.acquire::<System>(|_| {});
+///         let timestamp = timestamp_micros::<System>();
+///         unsafe { GLOBAL_LOGGER_STATE.release::<System>(|_| {}) };
+///         timestamp
+///     };
+///
+///     System::sleep(Duration::from_millis(10)).unwrap();
+///     let t1 = frame_timestamp();
+///     assert!(t1 >= 10_000, "{t1}");
+///
+///     System::sleep(Duration::from_millis(10)).unwrap();
+///     let t2 = frame_timestamp();
+///     assert!(t2 >= t1 + 10_000, "{t1} {t2}");
+///
+///     // The system time is unavailable while CPU Lock is active, so the
+///     // previous timestamp is reused
+///     System::acquire_cpu_lock().unwrap();
+///     let t3 = frame_timestamp();
+///     unsafe { System::release_cpu_lock().unwrap() };
+///     assert_eq!(t3, t2);
+/// #   exit(0);
+/// }
+/// ```
+)]
+///
+/// [1]: crate::defmt_timestamp
+/// [2]: crate::defmt_global_logger
+#[inline]
+pub fn timestamp_micros<System: traits::KernelTime>() -> u64 {
+    GLOBAL_LOGGER_STATE
+        .timestamp_micros()
+        .unwrap_or_else(|| System::time().map_or(0, |time| time.as_micros()))
+}
+
+/// Define a [`defmt` global logger][1] that writes encoded frames by the
+/// specified function (`fn(&[u8])`) while holding CPU Lock.
+///
+/// See [the module-level documentation](crate::defmt_logger) for an example.
+///
+/// [1]: defmt::global_logger
+#[macro_export]
+#[doc(cfg(feature = "defmt"))]
+macro_rules! defmt_global_logger {
+    ($System:ty, $write:expr $(,)?) => {
+        const _: () = {
+            use $crate::defmt_logger::GLOBAL_LOGGER_STATE as STATE;
+            const WRITE: fn(&[u8]) = $write;
+
+            #[::defmt::global_logger]
+            struct Logger;
+
+            unsafe impl ::defmt::Logger for Logger {
+                #[inline]
+                fn acquire() {
+                    STATE.acquire::<$System>(WRITE)
+                }
+
+                #[inline]
+                unsafe fn flush() {}
+
+                #[inline]
+                unsafe fn release() {
+                    // Safety: Upheld by the caller
+                    unsafe { STATE.release::<$System>(WRITE) }
+                }
+
+                #[inline]
+                unsafe fn write(bytes: &[u8]) {
+                    // Safety: Upheld by the caller
+                    unsafe { STATE.write(bytes, WRITE) }
+                }
+            }
+        };
+    };
+}
+
+/// Define a [`defmt` timestamp provider][1] that uses the current
+/// [system time][2] in microseconds.
+///
+/// When used with [`defmt_global_logger!`][4], frames logged in contexts where
+/// [`Kernel::time`][3] is unavailable reuse the timestamp of the previous
+/// frame. Otherwise, they have a timestamp of zero.
+///
+/// See [the module-level documentation](crate::defmt_logger) for an example.
+///
+/// [1]: defmt::timestamp
+/// [2]: crate#kernel-timing
+/// [3]: crate::kernel::Kernel::time
+/// [4]: crate::defmt_global_logger
+#[macro_export]
+#[doc(cfg(feature = "defmt"))]
+macro_rules! defmt_timestamp {
+    ($System:ty $(,)?) => {
+        ::defmt::timestamp!(
+            "{=u64:us}",
+            $crate::defmt_logger::timestamp_micros::<$System>()
+        );
+    };
+}
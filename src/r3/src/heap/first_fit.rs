@@ -0,0 +1,288 @@
+//! The first-fit allocation algorithm
+use core::{alloc::Layout, mem, ptr::NonNull};
+
+use super::{FreeSpace, HeapAlgorithm};
+use crate::utils::Init;
+
+/// A simple first-fit allocator with an address-ordered free list.
+///
+/// Adjacent free blocks are coalesced on deallocation. The execution time of
+/// allocation and deallocation is linear in the number of free blocks, which
+/// makes this algorithm unsuitable for use with [`HeapLock::CpuLock`][1] in a
+/// system with strict timing requirements. It has a very small code size and
+/// no per-allocation header, though.
+///
+/// [1]: super::HeapLock::CpuLock
+pub struct FirstFit {
+    /// The first free block, sorted by address.
+    first_free: Option<NonNull<FreeBlock>>,
+}
+
+/// The header of a free block.
+///
+/// The sizes and starting addresses of all memory blocks are multiples of
+/// [`GRANULARITY`].
+struct FreeBlock {
+    /// The size of the block, including the header.
+    size: usize,
+    next: Option<NonNull<FreeBlock>>,
+}
+
+const GRANULARITY: usize = mem::size_of::<FreeBlock>();
+
+// `FreeBlock` must be storable at any `GRANULARITY`-aligned address
+const _: () = assert!(GRANULARITY.is_power_of_two());
+const _: () = assert!(mem::align_of::<FreeBlock>() <= GRANULARITY);
+
+impl Init for FirstFit {
+    const INIT: Self = Self { first_free: None };
+}
+
+// Safety: `FirstFit` logically owns the memory blocks it points to
+unsafe impl Send for FirstFit {}
+
+impl FirstFit {
+    /// Get the size and alignment of the memory block to allocate for the
+    /// specified layout.
+    #[inline]
+    fn block_layout(layout: Layout) -> Option<(usize, usize)> {
+        let size = layout.size().max(1).checked_add(GRANULARITY - 1)? & !(GRANULARITY - 1);
+        Some((size, layout.align().max(GRANULARITY)))
+    }
+
+    /// Insert the memory block `[start, start + size)` to the free list,
+    /// merging it with adjacent free blocks.
+    ///
+    /// # Safety
+    ///
+    /// The memory block must be owned by `self`, must not overlap with any
+    /// free blocks, and must be aligned to `GRANULARITY`.
+    unsafe fn insert_free_block(&mut self, start: usize, size: usize) {
+        let end = start + size;
+
+        // Find the free blocks immediately before and after the new one
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut next = self.first_free;
+        while let Some(block) = next {
+            if block.as_ptr() as usize > start {
+                break;
+            }
+            prev = next;
+            // Safety: `block` is a valid free block
+            next = unsafe { block.as_ref().next };
+        }
+
+        // Merge with the next block if they are adjacent
+        let (size, next) = match next {
+            // Safety: `block` is a valid free block
+            Some(block) if block.as_ptr() as usize == end => unsafe {
+                let block = block.as_ref();
+                (size + block.size, block.next)
+            },
+            _ => (size, next),
+        };
+
+        // Merge with the previous block if they are adjacent
+        if let Some(mut prev) = prev {
+            // Safety: `prev` is a valid free block
+            let prev = unsafe { prev.as_mut() };
+            if prev as *mut FreeBlock as usize + prev.size == start {
+                prev.size += size;
+                prev.next = next;
+                return;
+            }
+        }
+
+        // Safety: The memory block is owned by `self` and aligned suitably
+        let new_block = unsafe {
+            let ptr = start as *mut FreeBlock;
+            ptr.write(FreeBlock { size, next });
+            NonNull::new_unchecked(ptr)
+        };
+
+        match prev {
+            // Safety: `prev` is a valid free block
+            Some(mut prev) => unsafe { prev.as_mut().next = Some(new_block) },
+            None => self.first_free = Some(new_block),
+        }
+    }
+}
+
+unsafe impl HeapAlgorithm for FirstFit {
+    unsafe fn init(&mut self, pool: NonNull<[u8]>) {
+        let unaligned_start = pool.as_ptr() as *mut u8 as usize;
+        let start = (unaligned_start + GRANULARITY - 1) & !(GRANULARITY - 1);
+        let end = (unaligned_start + pool.len()) & !(GRANULARITY - 1);
+        if end > start {
+            // Safety: We own the pool, which is not in the free list yet
+            unsafe { self.insert_free_block(start, end - start) };
+        }
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = Self::block_layout(layout)?;
+
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut cur = self.first_free;
+        while let Some(mut block_ptr) = cur {
+            // Safety: `block_ptr` is a valid free block
+            let block = unsafe { block_ptr.as_mut() };
+            let block_start = block_ptr.as_ptr() as usize;
+            let block_end = block_start + block.size;
+
+            // Since `align >= GRANULARITY`, the padding is either zero or large
+            // enough to form a free block
+            let Some(alloc_start) = block_start.checked_add(align - 1).map(|x| x & !(align - 1))
+            else {
+                break;
+            };
+            let alloc_end = alloc_start.checked_add(size);
+
+            if let Some(alloc_end) = alloc_end.filter(|&x| x <= block_end) {
+                // Carve out the allocation. The space after the allocation
+                // remains free.
+                let next = block.next;
+                let next = if alloc_end < block_end {
+                    // Safety: `alloc_end` is in the block being split and is
+                    // aligned to `GRANULARITY`
+                    unsafe {
+                        let ptr = alloc_end as *mut FreeBlock;
+                        ptr.write(FreeBlock {
+                            size: block_end - alloc_end,
+                            next,
+                        });
+                        Some(NonNull::new_unchecked(ptr))
+                    }
+                } else {
+                    next
+                };
+
+                if alloc_start > block_start {
+                    // The padding before the allocation remains free
+                    block.size = alloc_start - block_start;
+                    block.next = next;
+                } else {
+                    match prev {
+                        // Safety: `prev` is a valid free block
+                        Some(mut prev) => unsafe { prev.as_mut().next = next },
+                        None => self.first_free = next,
+                    }
+                }
+
+                // Safety: `alloc_start` is inside the pool, so it's non-null
+                return Some(unsafe { NonNull::new_unchecked(alloc_start as *mut u8) });
+            }
+
+            prev = cur;
+            cur = block.next;
+        }
+
+        None
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        // `block_layout` succeeded when this block was allocated
+        let (size, _) = Self::block_layout(layout).unwrap();
+        // Safety: The block was allocated by `self`, so it's suitably aligned
+        // and doesn't overlap with free blocks
+        unsafe { self.insert_free_block(ptr.as_ptr() as usize, size) };
+    }
+
+    fn free_space(&self) -> FreeSpace {
+        let mut free_space = FreeSpace::default();
+        let mut cur = self.first_free;
+        while let Some(block) = cur {
+            // Safety: `block` is a valid free block
+            let block = unsafe { block.as_ref() };
+            free_space.free += block.size;
+            free_space.num_free_blocks += 1;
+            free_space.largest_free_block = free_space.largest_free_block.max(block.size);
+            cur = block.next;
+        }
+        free_space
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use core::ptr::slice_from_raw_parts_mut;
+    use std::{vec, vec::Vec};
+
+    #[repr(align(64))]
+    struct Pool([u8; 4096]);
+
+    fn with_first_fit(f: impl FnOnce(&mut FirstFit, NonNull<[u8]>)) {
+        let mut pool = std::boxed::Box::new(Pool([0; 4096]));
+        let pool =
+            NonNull::new(slice_from_raw_parts_mut(pool.0.as_mut_ptr(), pool.0.len())).unwrap();
+        let mut ff = FirstFit::INIT;
+        unsafe { ff.init(pool) };
+        f(&mut ff, pool);
+    }
+
+    #[test]
+    fn fill_and_free() {
+        with_first_fit(|ff, pool| {
+            let layout = Layout::from_size_align(100, 4).unwrap();
+            let mut blocks = Vec::new();
+            while let Some(ptr) = ff.allocate(layout) {
+                let addr = ptr.as_ptr() as usize;
+                assert!(addr >= pool.as_ptr() as *mut u8 as usize);
+                assert!(addr + 100 <= pool.as_ptr() as *mut u8 as usize + pool.len());
+                blocks.push(ptr);
+            }
+            assert!(blocks.len() >= 4096 / 112);
+
+            // Free every other block to fragment the pool
+            for ptr in blocks.iter().step_by(2) {
+                unsafe { ff.deallocate(*ptr, layout) };
+            }
+            let fs = ff.free_space();
+            assert!(fs.num_free_blocks >= (blocks.len() + 1) / 2);
+            assert!(fs.largest_free_block < 112 * 2);
+
+            for ptr in blocks.iter().skip(1).step_by(2) {
+                unsafe { ff.deallocate(*ptr, layout) };
+            }
+            let fs = ff.free_space();
+            assert_eq!(fs.num_free_blocks, 1);
+            assert_eq!(fs.free, 4096);
+            assert_eq!(fs.largest_free_block, 4096);
+        });
+    }
+
+    #[test]
+    fn alignment() {
+        with_first_fit(|ff, _| {
+            let mut blocks = vec![];
+            for &align in &[1, 2, 8, 32, 64, 128, 256] {
+                let layout = Layout::from_size_align(3, align).unwrap();
+                let ptr = ff.allocate(layout).unwrap();
+                assert_eq!(ptr.as_ptr() as usize % align, 0);
+                blocks.push((ptr, layout));
+            }
+            for (ptr, layout) in blocks.into_iter().rev() {
+                unsafe { ff.deallocate(ptr, layout) };
+            }
+            assert_eq!(ff.free_space().num_free_blocks, 1);
+            assert_eq!(ff.free_space().free, 4096);
+        });
+    }
+
+    #[test]
+    fn too_large() {
+        with_first_fit(|ff, _| {
+            assert!(ff
+                .allocate(Layout::from_size_align(4097, 1).unwrap())
+                .is_none());
+            assert!(ff
+                .allocate(Layout::from_size_align(4096, 1).unwrap())
+                .is_some());
+            assert!(ff
+                .allocate(Layout::from_size_align(1, 1).unwrap())
+                .is_none());
+        });
+    }
+}
@@ -0,0 +1,582 @@
+//! Dynamic memory allocation backed by a hunk.
+//!
+//! [`StaticHeap`] manages a memory pool reserved as a [hunk][1] at
+//! configuration time and implements [`GlobalAlloc`], so it can be used as
+//! the global allocator (`#[global_allocator]`) or as an allocator for
+//! individual components.
+//!
+//! [1]: crate::kernel::Hunk
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    hunk::Hunk,
+    kernel::{self, mutex, prelude::*, traits, Cfg, LockMutexError, MutexProtocol},
+    utils::Init,
+};
+
+mod first_fit;
+#[cfg(feature = "tlsf")]
+mod tlsf;
+pub use self::first_fit::FirstFit;
+#[cfg(feature = "tlsf")]
+#[doc(cfg(feature = "tlsf"))]
+pub use self::tlsf::Tlsf;
+
+/// An allocation algorithm used by [`StaticHeap`].
+///
+/// # Safety
+///
+/// The implementation must behave like a memory allocator, i.e., the returned
+/// memory blocks must be within the memory pool passed to [`Self::init`],
+/// satisfy the requested layout, and must not overlap with each other.
+pub unsafe trait HeapAlgorithm: Init + Send + 'static {
+    /// Take the ownership of the specified memory pool. Called exactly once
+    /// before any other methods are called.
+    ///
+    /// # Safety
+    ///
+    /// `pool` must be valid for reads and writes and must outlive `self`.
+    unsafe fn init(&mut self, pool: NonNull<[u8]>);
+
+    /// Allocate a memory block. Returns `None` on failure.
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Deallocate a memory block.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via `self` with
+    /// the specified layout.
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Shrink or grow a previously allocated memory block. Returns `None` on
+    /// failure, in which case the original memory block is left intact.
+    ///
+    /// The default implementation allocates a new memory block and copies
+    /// the contents.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via `self` with
+    /// the layout `layout`. `new_size`, when rounded up to the nearest multiple
+    /// of `layout.align()`, must not overflow `isize`.
+    unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        // Safety: Upheld by the caller
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = self.allocate(new_layout)?;
+        // Safety: The two memory blocks are live and distinct; `ptr` is valid
+        // for `layout.size()` bytes
+        unsafe {
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), layout.size().min(new_size));
+            self.deallocate(ptr, layout);
+        }
+        Some(new_ptr)
+    }
+
+    /// Examine the free space in the memory pool.
+    fn free_space(&self) -> FreeSpace;
+}
+
+/// Describes the free space of a memory pool. Returned by
+/// [`HeapAlgorithm::free_space`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSpace {
+    /// The total size of free memory blocks, in bytes.
+    pub free: usize,
+    /// The number of free memory blocks.
+    pub num_free_blocks: usize,
+    /// The size of the largest free memory block, in bytes.
+    pub largest_free_block: usize,
+}
+
+/// Usage statistics of a [`StaticHeap`]. Returned by [`GenericHeap::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// The size of the memory pool, in bytes.
+    pub capacity: usize,
+    /// The total size of live allocations as requested by their [`Layout`]s,
+    /// in bytes.
+    pub used: usize,
+    /// The highest value [`Self::used`] has ever reached.
+    pub peak_used: usize,
+    /// The number of live allocations.
+    pub num_allocations: usize,
+    /// The number of allocation requests that could not be fulfilled,
+    /// including the ones rejected because of a bad context.
+    pub num_failures: usize,
+    /// The free space in the memory pool.
+    pub free_space: FreeSpace,
+}
+
+impl HeapStats {
+    /// Get the fraction of the free space that is unusable for a single
+    /// allocation as large as the total free space, in units of 1/1000.
+    ///
+    /// Returns `0` when the free space consists of a single free memory block
+    /// or when there is no free space at all.
+    pub fn fragmentation_permille(&self) -> u32 {
+        let FreeSpace {
+            free,
+            largest_free_block,
+            ..
+        } = self.free_space;
+        if free == 0 {
+            0
+        } else {
+            ((free - largest_free_block) as u64 * 1000 / free as u64) as u32
+        }
+    }
+}
+
+/// Specifies how [`StaticHeap`] protects its internal state from concurrent
+/// access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapLock {
+    /// Use a [mutex][1] with the specified protocol. The heap can be used only
+    /// in a task context or a [boot context][2]. Allocation requests made in
+    /// other contexts will fail, and deallocation requests will leak the
+    /// memory blocks.
+    ///
+    /// If a task exits while holding the mutex (i.e., [the mutex is
+    /// abandoned][3]), the heap state may have been left inconsistent, so the
+    /// heap becomes permanently *poisoned*: all subsequent allocation
+    /// requests and [`GenericHeap::stats`] fail, and deallocation requests
+    /// leak the memory blocks.
+    ///
+    /// [1]: crate::kernel::Mutex
+    /// [2]: crate#contexts
+    /// [3]: crate::kernel::Mutex#robustness
+    Mutex(MutexProtocol),
+    /// Use [CPU Lock][1]. The heap can be used in any context, including an
+    /// interrupt context and a context where CPU Lock is already active.
+    /// Interrupts will be masked for the duration of each heap operation,
+    /// so using an algorithm with a bounded execution time (such as
+    /// [`Tlsf`][2]) is strongly recommended.
+    ///
+    /// [1]: crate#system-states
+    /// [2]: crate::heap::Tlsf
+    CpuLock,
+}
+
+/// The definer (static builder) for [`StaticHeap`][].
+#[doc = include_str!("../common.md")]
+#[must_use = "must call `finish()` to complete registration"]
+pub struct Definer<System, Algorithm> {
+    size: Option<usize>,
+    align: usize,
+    lock: HeapLock,
+    _phantom: PhantomData<(System, Algorithm)>,
+}
+
+/// A memory allocator managing a memory pool provided by `Pool`.
+///
+/// # Example
+///
+/// See [`StaticHeap`].
+pub struct GenericHeap<Pool, State, Mutex> {
+    pool: Pool,
+    pool_len: usize,
+    state: State,
+    /// `None` if [`HeapLock::CpuLock`] is in use.
+    mutex: Option<Mutex>,
+}
+
+/// A defined (statically created) [`GenericHeap`].
+///
+/// The memory pool is reserved as a [hunk][1] by [`Definer::finish`] and
+/// handed over to `Algorithm` on first use.
+///
+/// [1]: crate::kernel::Hunk
+///
+/// # Example
+///
+#[doc = crate::tests::doc_test!(
+/// ```rust
+/// use core::alloc::{GlobalAlloc, Layout};
+/// use r3::{kernel::StaticTask, heap::{StaticHeap, HeapLock}};
+///
+/// struct Objects {
+///     heap: StaticHeap<System>,
+/// }
+///
+/// const fn configure_app<C>(cfg: &mut Cfg<C>) -> Objects
+/// where
+///     C: ~const traits::CfgTask<System = System> +
+///        ~const traits::CfgMutex,
+/// {
+///     StaticTask::define()
+///         .start(task1_body)
+///         .priority(2)
+///         .active(true)
+///         .finish(cfg);
+///
+///     let heap = StaticHeap::define()
+///         .size(4096)
+///         .lock(HeapLock::CpuLock)
+///         .finish(cfg);
+///
+///     Objects { heap }
+/// }
+///
+/// // `#[global_allocator]
+/// // static ALLOCATOR: StaticHeap<System> = COTTAGE.heap;`
+///
+/// fn task1_body() {
+///     let heap = &COTTAGE.heap;
+///     let layout = Layout::new::<[u32; 16]>();
+///     unsafe {
+///         let ptr = heap.alloc(layout);
+///         assert!(!ptr.is_null());
+///
+///         let stats = heap.stats().unwrap();
+///         assert_eq!(stats.used, 64);
+///         assert_eq!(stats.num_allocations, 1);
+///
+///         heap.dealloc(ptr, layout);
+///     }
+///
+///     let stats = heap.stats().unwrap();
+///     assert_eq!(stats.used, 0);
+///     assert_eq!(stats.peak_used, 64);
+/// #   exit(0);
+/// }
+/// ```
+)]
+pub type StaticHeap<System, Algorithm = FirstFit> = GenericHeap<
+    kernel::Hunk<System>,
+    Hunk<System, HeapCell<Algorithm>>,
+    mutex::StaticMutex<System>,
+>;
+
+/// The internal state of [`StaticHeap`].
+#[doc(hidden)]
+pub struct HeapCell<Algorithm>(UnsafeCell<HeapState<Algorithm>>);
+
+struct HeapState<Algorithm> {
+    algorithm: Algorithm,
+    is_initialized: bool,
+    /// Set when a task was terminated while holding the heap's mutex.
+    is_poisoned: bool,
+    used: usize,
+    peak_used: usize,
+    num_allocations: usize,
+    num_failures: usize,
+}
+
+impl<Algorithm: Init> Init for HeapCell<Algorithm> {
+    const INIT: Self = Self(UnsafeCell::new(HeapState {
+        algorithm: Algorithm::INIT,
+        is_initialized: false,
+        is_poisoned: false,
+        used: 0,
+        peak_used: 0,
+        num_allocations: 0,
+        num_failures: 0,
+    }));
+}
+
+// Safety: `HeapState` is only accessed while holding the lock
+unsafe impl<Algorithm: Send> Sync for HeapCell<Algorithm> {}
+
+impl<Pool: Clone, State: Clone, Mutex: Clone> Clone for GenericHeap<Pool, State, Mutex> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            pool_len: self.pool_len,
+            state: self.state.clone(),
+            mutex: self.mutex.clone(),
+        }
+    }
+}
+
+impl<Pool: Copy, State: Copy, Mutex: Copy> Copy for GenericHeap<Pool, State, Mutex> {}
+
+impl<System, Algorithm> StaticHeap<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    /// Construct a `Definer` to define a heap in [a configuration
+    /// function](crate#static-configuration).
+    pub const fn define() -> Definer<System, Algorithm> {
+        Definer {
+            size: None,
+            align: 16,
+            lock: HeapLock::Mutex(MutexProtocol::None),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<System, Algorithm> Definer<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    /// \[**Required**\] Specify the size of the memory pool, in bytes.
+    pub const fn size(self, size: usize) -> Self {
+        Self {
+            size: Some(size),
+            ..self
+        }
+    }
+
+    /// Specify the alignment of the memory pool. Defaults to `16` when
+    /// unspecified.
+    pub const fn align(self, align: usize) -> Self {
+        Self { align, ..self }
+    }
+
+    /// Specify how the heap is protected from concurrent access. Defaults to
+    /// `HeapLock::Mutex(MutexProtocol::None)` when unspecified.
+    pub const fn lock(self, lock: HeapLock) -> Self {
+        Self { lock, ..self }
+    }
+}
+
+/// # Finalization
+///
+/// The following method completes the definition of a heap.
+impl<System, Algorithm> Definer<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    /// Complete the definition of a heap, returning a reference to the heap.
+    pub const fn finish<C: ~const traits::CfgMutex<System = System>>(
+        self,
+        cfg: &mut Cfg<C>,
+    ) -> StaticHeap<System, Algorithm> {
+        let Some(size) = self.size else {
+            panic!("`size` is not specified");
+        };
+        assert!(self.align.is_power_of_two(), "`align` is not power of two");
+
+        let pool = kernel::Hunk::define()
+            .len(size)
+            .align(self.align)
+            .finish(cfg);
+
+        let mutex = match self.lock {
+            HeapLock::Mutex(protocol) => {
+                Some(mutex::StaticMutex::define().protocol(protocol).finish(cfg))
+            }
+            HeapLock::CpuLock => None,
+        };
+
+        GenericHeap {
+            pool,
+            pool_len: size,
+            state: Hunk::<_, HeapCell<Algorithm>>::define().finish(cfg),
+            mutex,
+        }
+    }
+}
+
+/// The proof of exclusive access to `HeapState`. Releases the lock when
+/// dropped.
+struct HeapGuard<'a, System: traits::KernelMutex, Algorithm> {
+    state: &'a mut HeapState<Algorithm>,
+    unlock: Unlock<System>,
+}
+
+enum Unlock<System: traits::KernelMutex> {
+    /// The lock was not taken because the system is still booting.
+    None,
+    Mutex(mutex::StaticMutex<System>),
+    CpuLock,
+}
+
+impl<System: traits::KernelMutex, Algorithm> Drop for HeapGuard<'_, System, Algorithm> {
+    #[inline]
+    fn drop(&mut self) {
+        match self.unlock {
+            Unlock::None => {}
+            Unlock::Mutex(mutex) => mutex.unlock().unwrap(),
+            // Safety: CPU Lock was acquired by `GenericHeap::lock`
+            Unlock::CpuLock => unsafe { System::release_cpu_lock().unwrap() },
+        }
+    }
+}
+
+impl<System, Algorithm> StaticHeap<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    /// Acquire the lock protecting the heap state, returning `None` if the
+    /// current context does not allow it.
+    fn lock(&self) -> Option<HeapGuard<'_, System, Algorithm>> {
+        let unlock = if !System::is_boot_complete() {
+            // The boot phase is single-threaded, and CPU Lock is active
+            Unlock::None
+        } else if let Some(mutex) = self.mutex {
+            match mutex.lock() {
+                Ok(()) => Unlock::Mutex(mutex),
+                Err(LockMutexError::Abandoned) => {
+                    // The previous owner was terminated while manipulating the
+                    // heap, so the heap state might be inconsistent. Poison
+                    // the heap permanently, but make the mutex consistent so
+                    // that later calls see the poison flag instead of
+                    // `Abandoned`.
+                    // Safety: We hold the lock
+                    unsafe { (*self.state.0.get()).is_poisoned = true };
+                    mutex.mark_consistent().unwrap();
+                    mutex.unlock().unwrap();
+                    return None;
+                }
+                Err(_) => return None,
+            }
+        } else if System::acquire_cpu_lock().is_ok() {
+            Unlock::CpuLock
+        } else {
+            // CPU Lock is already active, which is all we need
+            debug_assert!(System::has_cpu_lock());
+            Unlock::None
+        };
+
+        // Safety: We hold the lock, so we have exclusive access to the state
+        let state = unsafe { &mut *self.state.0.get() };
+
+        if state.is_poisoned {
+            // Release the lock by dropping the guard
+            drop(HeapGuard { state, unlock });
+            return None;
+        }
+
+        if !state.is_initialized {
+            let pool = NonNull::new(ptr::slice_from_raw_parts_mut(
+                self.pool.as_ptr(),
+                self.pool_len,
+            ))
+            .unwrap();
+            // Safety: The hunk is valid for the rest of the program's lifetime,
+            // and we are the only one who accesses it
+            unsafe { state.algorithm.init(pool) };
+            state.is_initialized = true;
+        }
+
+        Some(HeapGuard { state, unlock })
+    }
+
+    /// Get the usage statistics of the heap. Returns `None` if the current
+    /// context does not allow access to the heap.
+    ///
+    /// The execution time of this method is linear in the number of free
+    /// memory blocks.
+    pub fn stats(&self) -> Option<HeapStats> {
+        let guard = self.lock()?;
+        let state = &*guard.state;
+        Some(HeapStats {
+            capacity: self.pool_len,
+            used: state.used,
+            peak_used: state.peak_used,
+            num_allocations: state.num_allocations,
+            num_failures: state.num_failures,
+            free_space: state.algorithm.free_space(),
+        })
+    }
+
+    /// Reset [`HeapStats::peak_used`] to the current usage. Returns `false`
+    /// if the current context does not allow access to the heap.
+    pub fn reset_peak_used(&self) -> bool {
+        if let Some(mut guard) = self.lock() {
+            guard.state.peak_used = guard.state.used;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<Algorithm> HeapState<Algorithm> {
+    #[inline]
+    fn record_allocation(&mut self, size: usize) {
+        self.used += size;
+        self.peak_used = self.peak_used.max(self.used);
+        self.num_allocations += 1;
+    }
+}
+
+unsafe impl<System, Algorithm> GlobalAlloc for StaticHeap<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(guard) = self.lock() else {
+            // We can't even update `num_failures` here
+            return ptr::null_mut();
+        };
+        let state = &mut *guard.state;
+        if let Some(ptr) = state.algorithm.allocate(layout) {
+            state.record_allocation(layout.size());
+            ptr.as_ptr()
+        } else {
+            state.num_failures += 1;
+            ptr::null_mut()
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // If we can't lock the heap, there's nothing we can do but leak the
+        // memory block
+        if let Some(guard) = self.lock() {
+            let state = &mut *guard.state;
+            // Safety: Upheld by the caller
+            unsafe {
+                state
+                    .algorithm
+                    .deallocate(NonNull::new_unchecked(ptr), layout)
+            };
+            state.used -= layout.size();
+            state.num_allocations -= 1;
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Some(guard) = self.lock() else {
+            return ptr::null_mut();
+        };
+        let state = &mut *guard.state;
+        // Safety: Upheld by the caller
+        if let Some(new_ptr) = unsafe {
+            state
+                .algorithm
+                .reallocate(NonNull::new_unchecked(ptr), layout, new_size)
+        } {
+            state.used -= layout.size();
+            state.num_allocations -= 1;
+            state.record_allocation(new_size);
+            new_ptr.as_ptr()
+        } else {
+            state.num_failures += 1;
+            ptr::null_mut()
+        }
+    }
+}
+
+impl<System, Algorithm> fmt::Debug for StaticHeap<System, Algorithm>
+where
+    System: traits::KernelMutex + traits::KernelStatic,
+    Algorithm: HeapAlgorithm,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticHeap")
+            .field("pool", &self.pool)
+            .field("pool_len", &self.pool_len)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
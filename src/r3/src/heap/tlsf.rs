@@ -0,0 +1,209 @@
+//! The TLSF allocation algorithm
+use core::{alloc::Layout, ptr::NonNull};
+use rlsf::int::BinInteger;
+
+use super::{FreeSpace, HeapAlgorithm};
+use crate::utils::Init;
+
+/// The [TLSF] (Two-Level Segregated Fit) allocation algorithm provided by
+/// [`rlsf`].
+///
+/// Allocation and deallocation complete in constant time, which makes this
+/// algorithm suitable for use with [`HeapLock::CpuLock`][1]. Reallocation and
+/// [`HeapAlgorithm::free_space`] take linear time.
+///
+/// The generic parameters are passed to [`rlsf::Tlsf`] and determine the
+/// maximum block size (`(GRANULARITY << FLLEN) - GRANULARITY`, where
+/// [`GRANULARITY`][2] is `size_of::<usize>() * 4`) and the size of the
+/// allocator state. The default values allow a block size of up to 256 MiB on
+/// a 32-bit target.
+///
+/// [TLSF]: http://www.gii.upv.es/tlsf/
+/// [1]: super::HeapLock::CpuLock
+/// [2]: rlsf::GRANULARITY
+pub struct Tlsf<FLBitmap = u32, SLBitmap = u16, const FLLEN: usize = 24, const SLLEN: usize = 16> {
+    tlsf: rlsf::Tlsf<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    /// The memory pool inserted by [`HeapAlgorithm::init`], used by
+    /// [`rlsf::Tlsf::iter_blocks`].
+    pool: Option<NonNull<[u8]>>,
+}
+
+impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Init
+    for Tlsf<FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    const INIT: Self = Self {
+        tlsf: rlsf::Tlsf::new(),
+        pool: None,
+    };
+}
+
+// Safety: `Tlsf` logically owns the memory pool
+unsafe impl<FLBitmap: Send, SLBitmap: Send, const FLLEN: usize, const SLLEN: usize> Send
+    for Tlsf<FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+}
+
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> HeapAlgorithm
+    for Tlsf<FLBitmap, SLBitmap, FLLEN, SLLEN>
+where
+    FLBitmap: BinInteger + Send + 'static,
+    SLBitmap: BinInteger + Send + 'static,
+{
+    unsafe fn init(&mut self, pool: NonNull<[u8]>) {
+        // Safety: Upheld by the caller
+        if let Some(len) = unsafe { self.tlsf.insert_free_block_ptr(pool) } {
+            // `iter_blocks` requires the pool length returned by
+            // `insert_free_block_ptr`
+            self.pool = NonNull::new(core::ptr::slice_from_raw_parts_mut(
+                pool.as_ptr() as *mut u8,
+                len.get(),
+            ));
+        }
+    }
+
+    #[inline]
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.tlsf.allocate(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        // Safety: Upheld by the caller
+        unsafe { self.tlsf.deallocate(ptr, layout.align()) }
+    }
+
+    #[inline]
+    unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        // Safety: Upheld by the caller
+        unsafe {
+            self.tlsf.reallocate(
+                ptr,
+                Layout::from_size_align_unchecked(new_size, layout.align()),
+            )
+        }
+    }
+
+    fn free_space(&self) -> FreeSpace {
+        let mut free_space = FreeSpace::default();
+        if let Some(pool) = self.pool {
+            // Safety: `pool` was inserted to `self.tlsf` by `init`
+            for block in unsafe { self.tlsf.iter_blocks(pool) } {
+                if !block.is_occupied() {
+                    let size = block.max_payload_size();
+                    free_space.free += size;
+                    free_space.num_free_blocks += 1;
+                    free_space.largest_free_block = free_space.largest_free_block.max(size);
+                }
+            }
+        }
+        free_space
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use core::ptr::slice_from_raw_parts_mut;
+    use std::{vec, vec::Vec};
+
+    #[repr(align(64))]
+    struct Pool([u8; 4096]);
+
+    fn with_tlsf(f: impl FnOnce(&mut Tlsf, NonNull<[u8]>)) {
+        let mut pool = std::boxed::Box::new(Pool([0; 4096]));
+        let pool =
+            NonNull::new(slice_from_raw_parts_mut(pool.0.as_mut_ptr(), pool.0.len())).unwrap();
+        let mut tlsf = Tlsf::INIT;
+        unsafe { tlsf.init(pool) };
+        f(&mut tlsf, pool);
+    }
+
+    #[test]
+    fn fill_and_free() {
+        with_tlsf(|tlsf, pool| {
+            let initial = tlsf.free_space();
+            assert_eq!(initial.num_free_blocks, 1);
+            assert!(initial.free > 4096 / 2);
+
+            let layout = Layout::from_size_align(100, 4).unwrap();
+            let mut blocks = Vec::new();
+            while let Some(ptr) = tlsf.allocate(layout) {
+                let addr = ptr.as_ptr() as usize;
+                assert!(addr >= pool.as_ptr() as *mut u8 as usize);
+                assert!(addr + 100 <= pool.as_ptr() as *mut u8 as usize + pool.len());
+                blocks.push(ptr);
+            }
+            assert!(blocks.len() >= 4096 / 256);
+
+            // Free every other block to fragment the pool
+            for ptr in blocks.iter().step_by(2) {
+                unsafe { tlsf.deallocate(*ptr, layout) };
+            }
+            let fs = tlsf.free_space();
+            assert!(fs.num_free_blocks >= blocks.len() / 2);
+            assert!(fs.largest_free_block < initial.largest_free_block);
+
+            for ptr in blocks.iter().skip(1).step_by(2) {
+                unsafe { tlsf.deallocate(*ptr, layout) };
+            }
+            assert_eq!(tlsf.free_space(), initial);
+        });
+    }
+
+    #[test]
+    fn alignment() {
+        with_tlsf(|tlsf, _| {
+            let initial = tlsf.free_space();
+            let mut blocks = vec![];
+            for &align in &[1, 2, 8, 32, 64, 128, 256] {
+                let layout = Layout::from_size_align(3, align).unwrap();
+                let ptr = tlsf.allocate(layout).unwrap();
+                assert_eq!(ptr.as_ptr() as usize % align, 0);
+                blocks.push((ptr, layout));
+            }
+            for (ptr, layout) in blocks.into_iter().rev() {
+                unsafe { tlsf.deallocate(ptr, layout) };
+            }
+            assert_eq!(tlsf.free_space(), initial);
+        });
+    }
+
+    #[test]
+    fn reallocate() {
+        with_tlsf(|tlsf, _| {
+            let initial = tlsf.free_space();
+            let layout = Layout::from_size_align(16, 8).unwrap();
+            let ptr = tlsf.allocate(layout).unwrap();
+            unsafe { ptr.as_ptr().write_bytes(0x5a, 16) };
+
+            let ptr = unsafe { tlsf.reallocate(ptr, layout, 512) }.unwrap();
+            let contents = unsafe { core::slice::from_raw_parts(ptr.as_ptr(), 16) };
+            assert!(contents.iter().all(|&x| x == 0x5a));
+
+            let layout = Layout::from_size_align(512, 8).unwrap();
+            unsafe { tlsf.deallocate(ptr, layout) };
+            assert_eq!(tlsf.free_space(), initial);
+        });
+    }
+
+    #[test]
+    fn too_large() {
+        with_tlsf(|tlsf, _| {
+            assert!(tlsf
+                .allocate(Layout::from_size_align(4097, 1).unwrap())
+                .is_none());
+            // TLSF rounds the request up to the next size class, so it can't
+            // necessarily use the whole pool in one allocation
+            let largest = tlsf.free_space().largest_free_block;
+            let layout = Layout::from_size_align(largest / 2, 1).unwrap();
+            let ptr = tlsf.allocate(layout).unwrap();
+            unsafe { tlsf.deallocate(ptr, layout) };
+        });
+    }
+}
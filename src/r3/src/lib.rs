@@ -50,6 +50,15 @@ pub mod _changelog_ {}
 
 pub mod bind;
 
+#[cfg(feature = "defmt")]
+#[doc(cfg(feature = "defmt"))]
+pub mod defmt_logger;
+#[cfg(feature = "heap")]
+#[doc(cfg(feature = "heap"))]
+pub mod heap;
+#[cfg(feature = "log")]
+#[doc(cfg(feature = "log"))]
+pub mod logger;
 #[cfg(feature = "sync")]
 #[doc(cfg(feature = "sync"))]
 pub mod sync;
@@ -0,0 +1,567 @@
+//! A [`log`] backend that buffers records in memory and outputs them from a
+//! dedicated task.
+//!
+//! [`StaticLogger`] formats log records into a lock-free ring buffer, so it
+//! can be used from any context, including interrupt handlers and
+//! [CPU Lock][1]-protected critical sections, without blocking on the output
+//! device. A low-priority *drain task* created along with the logger forwards
+//! the buffered records to a user-supplied [sink function][2].
+//!
+//! The drain task sleeps until a record is logged, so an idle logger doesn't
+//! prevent a tickless system from sleeping. A task can't be woken up while
+//! CPU Lock is active, so a record logged in such a context is output when the
+//! next record is logged outside one, unless [a wake-up interrupt line][3] is
+//! specified.
+//!
+//! [1]: crate#system-states
+//! [2]: Definer::sink
+//! [3]: Definer::wake_interrupt_line
+use core::{
+    fmt::{self, Write},
+    sync::atomic::AtomicU32,
+};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{
+    hunk::Hunk,
+    kernel::{
+        self, prelude::*, task, traits, Cfg, InterruptLine, InterruptNum, InterruptPriority,
+        StaticInterruptHandler, UnparkError,
+    },
+};
+
+mod ring;
+
+/// The maximum length of a formatted log record, in bytes. Longer records are
+/// truncated.
+pub const MAX_RECORD_LEN: usize = 128;
+
+/// The maximum number of per-module filters that can be specified by
+/// [`Definer::module_level`].
+pub const MAX_MODULE_FILTERS: usize = 8;
+
+/// The definer (static builder) for [`StaticLogger`][].
+#[doc = include_str!("../common.md")]
+#[must_use = "must call `finish()` to complete registration"]
+pub struct Definer<System> {
+    capacity: usize,
+    sink: Option<fn(&[u8])>,
+    drain_priority: Option<usize>,
+    drain_stack_size: Option<usize>,
+    wake_line: Option<(InterruptNum, InterruptPriority)>,
+    filters: Filters,
+    _phantom: core::marker::PhantomData<System>,
+}
+
+/// Log level filters.
+#[derive(Clone, Copy)]
+struct Filters {
+    default: LevelFilter,
+    modules: [(&'static str, LevelFilter); MAX_MODULE_FILTERS],
+    num_modules: usize,
+}
+
+impl Filters {
+    /// Get the filter applicable to the specified target. The most specific
+    /// module filter wins.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let mut best: Option<(&str, LevelFilter)> = None;
+        for &(module, level) in &self.modules[..self.num_modules] {
+            let is_match = target
+                .strip_prefix(module)
+                .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"));
+            if is_match && best.map_or(true, |(best, _)| best.len() < module.len()) {
+                best = Some((module, level));
+            }
+        }
+        best.map_or(self.default, |(_, level)| level)
+    }
+
+    /// Get the most verbose level allowed by any of the filters.
+    fn max_level(&self) -> LevelFilter {
+        self.modules[..self.num_modules]
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// A defined (statically created) logger.
+///
+/// `StaticLogger` implements [`Log`]. To use it as the global logger, store it
+/// in a `static` and pass it to [`StaticLogger::install`] or
+/// [`log::set_logger_racy`].
+///
+/// # Example
+///
+#[doc = crate::tests::doc_test!(
+/// ```rust
+/// use r3::{kernel::StaticTask, logger::StaticLogger};
+///
+/// struct Objects {
+///     logger: StaticLogger<System>,
+/// }
+///
+/// const fn configure_app<C>(cfg: &mut Cfg<C>) -> Objects
+/// where
+///     C: ~const traits::CfgTask<System = System> + ~const traits::CfgInterruptLine,
+/// {
+///     StaticTask::define()
+///         .start(task1_body)
+///         .priority(1)
+///         .active(true)
+///         .finish(cfg);
+///
+///     let logger = StaticLogger::define()
+///         .capacity(1024)
+///         .sink(write_uart)
+///         .drain_priority(3)
+///         .max_level(log::LevelFilter::Info)
+///         .module_level("noisy_driver", log::LevelFilter::Error)
+///         .finish(cfg);
+///
+///     Objects { logger }
+/// }
+///
+/// static LOGGER: StaticLogger<System> = COTTAGE.logger;
+///
+/// fn task1_body() {
+///     // An application would call `LOGGER.install()` here, but `r3_port_std`
+///     // has already installed its own logger. Pass records directly instead.
+///     let logger: &dyn log::Log = &LOGGER;
+///     for level in [log::Level::Info, log::Level::Debug] {
+///         logger.log(&log::Record::builder()
+///             .level(level)
+///             .target("app")
+///             .args(format_args!("hello from a task"))
+///             .build());
+///     }
+/// }
+///
+/// fn write_uart(bytes: &[u8]) {
+///     // The drain task calls this function
+///     assert_eq!(bytes, b"[INFO  app] hello from a task\n");
+/// #   exit(0);
+/// }
+/// ```
+)]
+pub struct StaticLogger<System: traits::KernelBase + traits::KernelStatic> {
+    buffer: kernel::Hunk<System>,
+    /// The length of `buffer`, in words.
+    buffer_len: usize,
+    state: Hunk<System, ring::RingState>,
+    drain_task: task::StaticTask<System>,
+    critical_section: ring::CriticalSection,
+    /// The interrupt line to pend to wake up the drain task when it can't be
+    /// unparked directly, and the function to pend it.
+    wake_line: Option<(InterruptNum, fn(InterruptNum))>,
+    filters: Filters,
+}
+
+impl<System: traits::KernelBase + traits::KernelStatic> Clone for StaticLogger<System> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<System: traits::KernelBase + traits::KernelStatic> Copy for StaticLogger<System> {}
+
+// Safety: `StaticLogger` only consists of object handles and function
+// pointers. `System` is merely a marker type.
+unsafe impl<System: traits::KernelBase + traits::KernelStatic> Send for StaticLogger<System> {}
+unsafe impl<System: traits::KernelBase + traits::KernelStatic> Sync for StaticLogger<System> {}
+
+impl<System: traits::KernelBase + traits::KernelStatic> StaticLogger<System> {
+    /// Construct a `Definer` to define a logger in [a configuration
+    /// function](crate#static-configuration).
+    pub const fn define() -> Definer<System> {
+        Definer {
+            capacity: 1024,
+            sink: None,
+            drain_priority: None,
+            drain_stack_size: None,
+            wake_line: None,
+            filters: Filters {
+                default: LevelFilter::Trace,
+                modules: [("", LevelFilter::Off); MAX_MODULE_FILTERS],
+                num_modules: 0,
+            },
+            _phantom: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<System: traits::KernelBase + traits::KernelStatic> Definer<System> {
+    /// Specify the capacity of the ring buffer, in bytes. Must be a power of
+    /// two not less than `4`. Defaults to `1024` when unspecified.
+    ///
+    /// Each record occupies four bytes plus its length rounded up to a
+    /// multiple of four.
+    pub const fn capacity(self, capacity: usize) -> Self {
+        assert!(
+            capacity.is_power_of_two() && capacity >= 4,
+            "`capacity` must be a power of two not less than 4"
+        );
+        Self { capacity, ..self }
+    }
+
+    /// \[**Required**\] Specify the function to output formatted log records.
+    ///
+    /// The function is called by the drain task and may block. Each call
+    /// receives a single record, which ends with `\n` unless truncated.
+    pub const fn sink(self, sink: fn(&[u8])) -> Self {
+        Self {
+            sink: Some(sink),
+            ..self
+        }
+    }
+
+    /// \[**Required**\] Specify the priority of the drain task. It should
+    /// usually be the lowest priority (i.e., the largest value) in the system
+    /// so that log output doesn't disturb time-critical tasks.
+    pub const fn drain_priority(self, priority: usize) -> Self {
+        Self {
+            drain_priority: Some(priority),
+            ..self
+        }
+    }
+
+    /// Specify the stack size of the drain task.
+    pub const fn drain_stack_size(self, stack_size: usize) -> Self {
+        Self {
+            drain_stack_size: Some(stack_size),
+            ..self
+        }
+    }
+
+    /// Specify an interrupt line used to wake up the drain task when a record
+    /// is logged while CPU Lock is active. The logger configures the interrupt
+    /// line with the specified priority, enables it, and registers an
+    /// interrupt handler that unparks the drain task. The interrupt line must
+    /// not be used for other purposes, and `priority` must be in the managed
+    /// range.
+    ///
+    /// When unspecified, such a record is output when the next record is
+    /// logged while CPU Lock is inactive.
+    pub const fn wake_interrupt_line(
+        self,
+        line: InterruptNum,
+        priority: InterruptPriority,
+    ) -> Self {
+        Self {
+            wake_line: Some((line, priority)),
+            ..self
+        }
+    }
+
+    /// Specify the maximum log level of records whose targets don't match
+    /// any of the filters specified by [`Self::module_level`]. Defaults to
+    /// [`LevelFilter::Trace`] when unspecified.
+    pub const fn max_level(self, level: LevelFilter) -> Self {
+        Self {
+            filters: Filters {
+                default: level,
+                ..self.filters
+            },
+            ..self
+        }
+    }
+
+    /// Specify the maximum log level of records whose targets are `module` or
+    /// its submodules. When multiple filters match a target, the one with the
+    /// longest module path is applied.
+    ///
+    /// Up to [`MAX_MODULE_FILTERS`] filters can be specified.
+    pub const fn module_level(self, module: &'static str, level: LevelFilter) -> Self {
+        let mut filters = self.filters;
+        assert!(
+            filters.num_modules < MAX_MODULE_FILTERS,
+            "too many module filters"
+        );
+        filters.modules[filters.num_modules] = (module, level);
+        filters.num_modules += 1;
+        Self { filters, ..self }
+    }
+}
+
+/// # Finalization
+///
+/// The following method completes the definition of a logger.
+impl<System: traits::KernelInterruptLine + traits::KernelStatic> Definer<System> {
+    /// Complete the definition of a logger, returning a reference to the
+    /// logger.
+    pub const fn finish<
+        C: ~const traits::CfgTask<System = System> + ~const traits::CfgInterruptLine,
+    >(
+        self,
+        cfg: &mut Cfg<C>,
+    ) -> StaticLogger<System> {
+        let buffer_len = self.capacity / 4;
+        let buffer = kernel::Hunk::define()
+            .len(self.capacity)
+            .align(core::mem::align_of::<AtomicU32>())
+            .finish(cfg);
+        let state = Hunk::<_, ring::RingState>::define().finish(cfg);
+        let Some(sink) = self.sink else {
+            panic!("`sink` is not specified");
+        };
+        let Some(drain_priority) = self.drain_priority else {
+            panic!("`drain_priority` is not specified");
+        };
+
+        let drain = DrainTask {
+            buffer,
+            buffer_len,
+            state,
+            sink,
+        };
+        let drain_task = task::StaticTask::define()
+            .start(move || drain.run())
+            .priority(drain_priority)
+            .active(true);
+        let drain_task = if let Some(stack_size) = self.drain_stack_size {
+            drain_task.stack_size(stack_size)
+        } else {
+            drain_task
+        };
+        let drain_task = drain_task.finish(cfg);
+
+        let wake_line = if let Some((line, priority)) = self.wake_line {
+            StaticInterruptHandler::define()
+                .line(line)
+                .start(move || {
+                    let _ = drain_task.unpark();
+                })
+                .finish(cfg);
+            InterruptLine::define()
+                .line(line)
+                .priority(priority)
+                .enabled(true)
+                .finish(cfg);
+            Some((line, pend_interrupt_line::<System> as fn(InterruptNum)))
+        } else {
+            None
+        };
+
+        StaticLogger {
+            buffer,
+            buffer_len,
+            state,
+            drain_task,
+            critical_section: cpu_lock_critical_section::<System>,
+            wake_line,
+            filters: self.filters,
+        }
+    }
+}
+
+/// Use CPU Lock to create a critical section. Used by [`ring`] on targets
+/// without compare-and-swap operations.
+fn cpu_lock_critical_section<System: traits::KernelBase>(f: &mut dyn FnMut()) {
+    if System::acquire_cpu_lock().is_ok() {
+        f();
+        // Safety: We acquired CPU Lock
+        unsafe { System::release_cpu_lock().unwrap() };
+    } else {
+        // CPU Lock is already active
+        f();
+    }
+}
+
+/// Pend the specified interrupt line. Used to wake up the drain task while CPU
+/// Lock is active.
+fn pend_interrupt_line<System: traits::KernelInterruptLine>(line: InterruptNum) {
+    let _ = InterruptLine::<System>::from_num(line).pend();
+}
+
+/// Get the buffer of a logger as a slice.
+#[inline]
+fn buffer_slice<System: traits::KernelStatic>(
+    buffer: kernel::Hunk<System>,
+    buffer_len: usize,
+) -> &'static [AtomicU32] {
+    // Safety: The hunk is large enough and suitably aligned to store
+    // `[AtomicU32; buffer_len]`, and a zero-initialized hunk is a valid
+    // `[AtomicU32]`. We only access it through atomic operations.
+    unsafe { &*core::ptr::slice_from_raw_parts(buffer.as_ptr().cast(), buffer_len) }
+}
+
+impl<System: traits::KernelBase + traits::KernelStatic> StaticLogger<System> {
+    /// Register `self` as the global logger by [`log::set_logger`] and set
+    /// the global maximum log level by [`log::set_max_level`].
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn install(&'static self) -> Result<(), log::SetLoggerError> {
+        log::set_logger(self)?;
+        log::set_max_level(self.max_level());
+        Ok(())
+    }
+
+    /// Get the most verbose level that this logger accepts.
+    pub fn max_level(&self) -> LevelFilter {
+        self.filters.max_level()
+    }
+
+    /// Get the number of records that have been dropped because the buffer was
+    /// full.
+    pub fn num_dropped(&self) -> usize {
+        self.state.num_dropped()
+    }
+}
+
+impl<System: traits::KernelBase + traits::KernelStatic> Log for StaticLogger<System> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.filters.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut line = LineBuf::new();
+        let _ = writeln!(
+            line,
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let buffer = buffer_slice(self.buffer, self.buffer_len);
+        if self
+            .state
+            .push(buffer, line.as_bytes(), self.critical_section)
+        {
+            // This fails if CPU Lock is active, in which case we pend the
+            // wake-up interrupt line instead. Without one, the drain task
+            // will find the record when it's woken up for the next record.
+            if let (Err(UnparkError::BadContext), Some((line, pend))) =
+                (self.drain_task.unpark(), self.wake_line)
+            {
+                pend(line);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The state of a drain task.
+struct DrainTask<System: traits::KernelStatic> {
+    buffer: kernel::Hunk<System>,
+    buffer_len: usize,
+    state: Hunk<System, ring::RingState>,
+    sink: fn(&[u8]),
+}
+
+impl<System: traits::KernelStatic> Clone for DrainTask<System> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<System: traits::KernelStatic> Copy for DrainTask<System> {}
+
+// Safety: `DrainTask` only consists of object handles and a function pointer.
+// `System` is merely a marker type.
+unsafe impl<System: traits::KernelStatic> Send for DrainTask<System> {}
+
+impl<System: traits::KernelBase + traits::KernelStatic> DrainTask<System> {
+    /// The entry point of a drain task.
+    fn run(self) {
+        let buffer = buffer_slice(self.buffer, self.buffer_len);
+        let mut out = [0u8; MAX_RECORD_LEN];
+        let mut num_dropped_reported = 0;
+        loop {
+            // Safety: The drain task is the only consumer of the buffer
+            while let Some(len) = unsafe { self.state.pop(buffer, &mut out) } {
+                (self.sink)(&out[..len.min(out.len())]);
+            }
+
+            let num_dropped = self.state.num_dropped();
+            if num_dropped != num_dropped_reported {
+                let mut line = LineBuf::new();
+                let _ = writeln!(
+                    line,
+                    "[{} log records dropped]",
+                    num_dropped.wrapping_sub(num_dropped_reported)
+                );
+                (self.sink)(line.as_bytes());
+                num_dropped_reported = num_dropped;
+            }
+
+            // Wait for new records. `StaticLogger::log` unparks this task
+            // after committing a record.
+            let _ = System::park();
+        }
+    }
+}
+
+/// A fixed-capacity buffer to format a log record in. The output is truncated
+/// if it doesn't fit.
+struct LineBuf {
+    buf: [u8; MAX_RECORD_LEN],
+    len: usize,
+}
+
+impl LineBuf {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_RECORD_LEN],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl fmt::Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let num_bytes = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..][..num_bytes].copy_from_slice(&s.as_bytes()[..num_bytes]);
+        self.len += num_bytes;
+        if num_bytes < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let mut filters = Filters {
+            default: LevelFilter::Info,
+            modules: [("", LevelFilter::Off); MAX_MODULE_FILTERS],
+            num_modules: 3,
+        };
+        filters.modules[0] = ("a", LevelFilter::Warn);
+        filters.modules[1] = ("a::b", LevelFilter::Trace);
+        filters.modules[2] = ("c", LevelFilter::Off);
+
+        assert_eq!(filters.level_for("x"), LevelFilter::Info);
+        assert_eq!(filters.level_for("a"), LevelFilter::Warn);
+        assert_eq!(filters.level_for("ab"), LevelFilter::Info);
+        assert_eq!(filters.level_for("a::c"), LevelFilter::Warn);
+        assert_eq!(filters.level_for("a::b"), LevelFilter::Trace);
+        assert_eq!(filters.level_for("a::b::c"), LevelFilter::Trace);
+        assert_eq!(filters.level_for("c::d"), LevelFilter::Off);
+        assert_eq!(filters.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn line_buf_truncate() {
+        let mut line = LineBuf::new();
+        assert!(write!(line, "{}", "x".repeat(MAX_RECORD_LEN + 1)).is_err());
+        assert_eq!(line.as_bytes().len(), MAX_RECORD_LEN);
+    }
+}
@@ -0,0 +1,309 @@
+//! The lock-free multi-producer single-consumer ring buffer storing formatted
+//! log records
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use crate::utils::Init;
+
+/// The header word of a record. `0` means the record has been reserved but is
+/// not committed yet.
+const HDR_COMMITTED: u32 = 1 << 31;
+/// Indicates the record is a padding inserted to fill the space at the end of
+/// the buffer. The padding's length is measured in words.
+const HDR_PADDING: u32 = 1 << 30;
+const HDR_LEN_MASK: u32 = HDR_PADDING - 1;
+
+/// The indices of a ring buffer. The buffer itself (`[AtomicU32]`, whose
+/// length is a power of two) is stored separately.
+///
+/// The positions are measured in words and wrap around at `usize::MAX`. A
+/// buffer element is zero unless it's part of a committed record.
+pub(super) struct RingState {
+    /// The end of reserved records.
+    write_pos: AtomicUsize,
+    /// The start of the oldest unconsumed record.
+    read_pos: AtomicUsize,
+    /// The number of records that were dropped because the buffer was full.
+    num_dropped: AtomicUsize,
+}
+
+impl Init for RingState {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        write_pos: AtomicUsize::new(0),
+        read_pos: AtomicUsize::new(0),
+        num_dropped: AtomicUsize::new(0),
+    };
+}
+
+/// A critical section used to reserve space in a buffer on targets without
+/// compare-and-swap operations.
+pub(super) type CriticalSection = fn(&mut dyn FnMut());
+
+impl RingState {
+    /// Append a record. Returns `false` and increments the drop counter if
+    /// there's no room for it.
+    ///
+    /// This method is lock-free on targets supporting compare-and-swap
+    /// operations. On other targets, `critical_section` is used to reserve the
+    /// buffer space.
+    pub(super) fn push(
+        &self,
+        buf: &[AtomicU32],
+        data: &[u8],
+        #[allow(unused_variables)] critical_section: CriticalSection,
+    ) -> bool {
+        let cap = buf.len();
+        debug_assert!(cap.is_power_of_two());
+        let payload_words = (data.len() + 3) / 4;
+        let needed = 1 + payload_words;
+
+        let reserve = |write_pos: usize| -> Option<(usize, usize)> {
+            let read_pos = self.read_pos.load(Ordering::Acquire);
+            let i = write_pos % cap;
+            // A record can't wrap around, so insert a padding if necessary
+            let pad = if i + needed > cap { cap - i } else { 0 };
+            let total = pad + needed;
+            if needed > cap || write_pos.wrapping_sub(read_pos) + total > cap {
+                None
+            } else {
+                Some((write_pos.wrapping_add(total), pad))
+            }
+        };
+
+        let Some((start, pad)) = self.reserve(reserve, critical_section) else {
+            self.num_dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        };
+
+        let mut i = start % cap;
+        if pad > 0 {
+            buf[i].store(HDR_COMMITTED | HDR_PADDING | pad as u32, Ordering::Release);
+            i = 0;
+        }
+
+        // Write the payload
+        for (word, chunk) in buf[i + 1..i + 1 + payload_words].iter().zip(data.chunks(4)) {
+            let mut bytes = [0; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            word.store(u32::from_le_bytes(bytes), Ordering::Relaxed);
+        }
+
+        // Commit the record
+        buf[i].store(HDR_COMMITTED | data.len() as u32, Ordering::Release);
+
+        true
+    }
+
+    /// Update `write_pos` by `f`, returning the old value and the value
+    /// returned by `f`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    fn reserve(
+        &self,
+        f: impl Fn(usize) -> Option<(usize, usize)>,
+        _: CriticalSection,
+    ) -> Option<(usize, usize)> {
+        let mut write_pos = self.write_pos.load(Ordering::Relaxed);
+        loop {
+            let (new_write_pos, pad) = f(write_pos)?;
+            match self.write_pos.compare_exchange_weak(
+                write_pos,
+                new_write_pos,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some((write_pos, pad)),
+                Err(x) => write_pos = x,
+            }
+        }
+    }
+
+    /// Update `write_pos` by `f`, returning the old value and the value
+    /// returned by `f`.
+    #[cfg(not(target_has_atomic = "ptr"))]
+    #[inline]
+    fn reserve(
+        &self,
+        f: impl Fn(usize) -> Option<(usize, usize)>,
+        critical_section: CriticalSection,
+    ) -> Option<(usize, usize)> {
+        let mut result = None;
+        critical_section(&mut || {
+            let write_pos = self.write_pos.load(Ordering::Relaxed);
+            if let Some((new_write_pos, pad)) = f(write_pos) {
+                self.write_pos.store(new_write_pos, Ordering::Relaxed);
+                result = Some((write_pos, pad));
+            }
+        });
+        result
+    }
+
+    /// Remove the oldest record and copy it to `out`, truncating it if `out`
+    /// is too short. Returns the record's length, or `None` if there's no
+    /// committed record to read.
+    ///
+    /// # Safety
+    ///
+    /// This method must not be called concurrently for the same ring buffer.
+    pub(super) unsafe fn pop(&self, buf: &[AtomicU32], out: &mut [u8]) -> Option<usize> {
+        let cap = buf.len();
+        loop {
+            let read_pos = self.read_pos.load(Ordering::Relaxed);
+            let i = read_pos % cap;
+            let hdr = buf[i].load(Ordering::Acquire);
+            if hdr & HDR_COMMITTED == 0 {
+                return None;
+            }
+
+            let len = (hdr & HDR_LEN_MASK) as usize;
+            if hdr & HDR_PADDING != 0 {
+                // Skip the padding
+                buf[i].store(0, Ordering::Relaxed);
+                self.read_pos
+                    .store(read_pos.wrapping_add(len), Ordering::Release);
+                continue;
+            }
+
+            let payload_words = (len + 3) / 4;
+            let mut out_i = 0;
+            for word in &buf[i + 1..i + 1 + payload_words] {
+                let bytes = word.swap(0, Ordering::Relaxed).to_le_bytes();
+                let num_bytes = bytes.len().min(out.len() - out_i).min(len - out_i);
+                out[out_i..][..num_bytes].copy_from_slice(&bytes[..num_bytes]);
+                out_i += num_bytes;
+            }
+            buf[i].store(0, Ordering::Relaxed);
+
+            // Release the space. `Release` ensures the zeroing above happens
+            // before the producers reuse the space.
+            self.read_pos
+                .store(read_pos.wrapping_add(1 + payload_words), Ordering::Release);
+
+            return Some(len);
+        }
+    }
+
+    /// Get the number of records dropped so far.
+    #[inline]
+    pub(super) fn num_dropped(&self) -> usize {
+        self.num_dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::{sync::Arc, thread, vec::Vec};
+
+    fn no_critical_section(f: &mut dyn FnMut()) {
+        f()
+    }
+
+    fn new_buf(len: usize) -> Vec<AtomicU32> {
+        (0..len).map(|_| AtomicU32::new(0)).collect()
+    }
+
+    #[test]
+    fn push_pop() {
+        let state = RingState::INIT;
+        let buf = new_buf(16);
+        let mut out = [0u8; 64];
+
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, None);
+
+        for round in 0..20 {
+            let msg = std::format!("round {round}");
+            assert!(state.push(&buf, msg.as_bytes(), no_critical_section));
+            assert!(state.push(&buf, b"", no_critical_section));
+
+            let len = unsafe { state.pop(&buf, &mut out) }.unwrap();
+            assert_eq!(&out[..len], msg.as_bytes());
+            assert_eq!(unsafe { state.pop(&buf, &mut out) }, Some(0));
+            assert_eq!(unsafe { state.pop(&buf, &mut out) }, None);
+        }
+
+        assert!(buf.iter().all(|x| x.load(Ordering::Relaxed) == 0));
+        assert_eq!(state.num_dropped(), 0);
+    }
+
+    #[test]
+    fn overflow() {
+        let state = RingState::INIT;
+        let buf = new_buf(8);
+        let mut out = [0u8; 64];
+
+        // Each record occupies 3 words
+        assert!(state.push(&buf, b"12345678", no_critical_section));
+        assert!(state.push(&buf, b"abcdefgh", no_critical_section));
+        assert!(!state.push(&buf, b"ABCDEFGH", no_critical_section));
+        assert!(!state.push(&buf, &[0; 64], no_critical_section));
+        assert_eq!(state.num_dropped(), 2);
+
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, Some(8));
+        assert_eq!(&out[..8], b"12345678");
+
+        // This one needs a padding
+        assert!(state.push(&buf, b"ABCDEFGH", no_critical_section));
+
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, Some(8));
+        assert_eq!(&out[..8], b"abcdefgh");
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, Some(8));
+        assert_eq!(&out[..8], b"ABCDEFGH");
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, None);
+    }
+
+    #[test]
+    fn truncate() {
+        let state = RingState::INIT;
+        let buf = new_buf(8);
+        let mut out = [0u8; 3];
+        assert!(state.push(&buf, b"hello", no_critical_section));
+        assert_eq!(unsafe { state.pop(&buf, &mut out) }, Some(5));
+        assert_eq!(&out, b"hel");
+        assert!(buf.iter().all(|x| x.load(Ordering::Relaxed) == 0));
+    }
+
+    #[test]
+    fn concurrent_producers() {
+        const NUM_PRODUCERS: usize = 4;
+        const NUM_RECORDS: usize = 2000;
+
+        let state = Arc::new(RingState::INIT);
+        let buf = Arc::new(new_buf(64));
+
+        let producers: Vec<_> = (0..NUM_PRODUCERS)
+            .map(|producer_i| {
+                let (state, buf) = (Arc::clone(&state), Arc::clone(&buf));
+                thread::spawn(move || {
+                    for i in 0..NUM_RECORDS {
+                        let msg = std::format!("{producer_i}:{i}");
+                        while !state.push(&buf, msg.as_bytes(), no_critical_section) {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0; NUM_PRODUCERS];
+        let mut out = [0u8; 64];
+        while next.iter().any(|&x| x < NUM_RECORDS) {
+            let Some(len) = (unsafe { state.pop(&buf, &mut out) }) else {
+                thread::yield_now();
+                continue;
+            };
+            let msg = std::str::from_utf8(&out[..len]).unwrap();
+            let (producer_i, i) = msg.split_once(':').unwrap();
+            let producer_i: usize = producer_i.parse().unwrap();
+            let i: usize = i.parse().unwrap();
+            // Records from the same producer must be in order
+            assert_eq!(next[producer_i], i);
+            next[producer_i] += 1;
+        }
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+    }
+}
@@ -99,6 +99,7 @@ define_result_code! {
     /// [1]: crate#stability
     #[doc = include_str!("../common.md")]
     #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     #[repr(i8)]
     #[non_exhaustive]
     pub enum ResultCode {
@@ -223,6 +224,14 @@ macro_rules! define_error {
             }
         }
 
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for $name {
+            #[inline]
+            fn format(&self, f: defmt::Formatter<'_>) {
+                ResultCode::from(*self).format(f)
+            }
+        }
+
         impl From<Result<(), $name>> for ResultCode {
             #[inline]
             fn from(x: Result<(), $name>) -> Self {
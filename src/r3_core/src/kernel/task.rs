@@ -9,7 +9,7 @@ use super::{
 };
 use crate::{
     closure::{Closure, IntoClosureConst},
-    utils::{Init, PhantomInvariant},
+    utils::{Frozen, Init, PhantomInvariant},
 };
 
 // ----------------------------------------------------------------------------
@@ -325,6 +325,11 @@ pub struct TaskDefiner<System> {
     stack_size: Option<usize>,
     priority: Option<usize>,
     active: bool,
+    privileged: bool,
+    memory_regions: [MemoryRegion<System>; MAX_MEMORY_REGIONS],
+    num_memory_regions: usize,
+    extended_context: ExtendedContext,
+    affinity: CpuAffinity,
 }
 
 impl<System: raw::KernelBase> TaskDefiner<System> {
@@ -335,6 +340,11 @@ impl<System: raw::KernelBase> TaskDefiner<System> {
             stack_size: None,
             priority: None,
             active: false,
+            privileged: true,
+            memory_regions: [MemoryRegion::new(0, 0, MemoryAccess::ReadOnly); MAX_MEMORY_REGIONS],
+            num_memory_regions: 0,
+            extended_context: ExtendedContext::DISABLED,
+            affinity: CpuAffinity::ALL,
         }
     }
 
@@ -379,12 +389,69 @@ impl<System: raw::KernelBase> TaskDefiner<System> {
         Self { active, ..self }
     }
 
+    /// Specify whether the task runs in a privileged mode. Defaults to `true`.
+    ///
+    /// An unprivileged task can only access its own stack and the memory
+    /// regions granted by [`Self::memory_region`]. A kernel might choose to
+    /// ignore this if memory protection is not supported.
+    pub const fn privileged(self, privileged: bool) -> Self {
+        Self { privileged, ..self }
+    }
+
+    /// Grant the task access to the specified memory region while it's
+    /// running in an unprivileged mode (see [`Self::privileged`]).
+    ///
+    /// A task can have up to [`MemoryProtection::MAX_REGIONS`] memory regions.
+    /// A kernel might impose additional restrictions on the number, size, and
+    /// alignment of memory regions.
+    pub const fn memory_region(mut self, region: MemoryRegion<System>) -> Self {
+        assert!(
+            self.num_memory_regions < MAX_MEMORY_REGIONS,
+            "too many memory regions"
+        );
+        self.memory_regions[self.num_memory_regions] = region;
+        self.num_memory_regions += 1;
+        self
+    }
+
+    /// Specify whether the task uses the processor's extended context, such as
+    /// vector registers, which is too large to be saved for every task.
+    /// Defaults to `false`.
+    ///
+    /// A kernel might reserve space for the extended context in the task's
+    /// stack only if this is `true` and disallow other tasks from using the
+    /// associated processor features. A kernel might choose to ignore this if
+    /// the processor doesn't have such a state.
+    pub const fn extended_context(self, extended_context: bool) -> Self {
+        Self {
+            extended_context: ExtendedContext(extended_context),
+            ..self
+        }
+    }
+
+    /// Specify the set of processors the task is allowed to run on. Defaults
+    /// to [`CpuAffinity::ALL`].
+    ///
+    /// This only matters for a kernel supporting symmetric multiprocessing. A
+    /// kernel might reject an affinity that excludes all processors of the
+    /// system.
+    pub const fn affinity(self, affinity: CpuAffinity) -> Self {
+        Self { affinity, ..self }
+    }
+
     /// Complete the definition of a task, returning a reference to the
     /// task.
     pub const fn finish<C: ~const raw_cfg::CfgTask<System = System>>(
         self,
         cfg: &mut Cfg<C>,
     ) -> StaticTask<System> {
+        // Move the memory regions out of line so that tasks without any don't
+        // pay for them at runtime
+        let memory_protection = MemoryProtection {
+            privileged: self.privileged,
+            regions: Frozen::leak_slice(&self.memory_regions[..self.num_memory_regions]),
+        };
+
         let id = cfg.raw().task_define(
             raw_cfg::TaskDescriptor {
                 phantom: Init::INIT,
@@ -397,7 +464,10 @@ impl<System: raw::KernelBase> TaskDefiner<System> {
                     .expect("`priority` (task entry point) is not specified"),
                 stack_size: self.stack_size,
             },
-            (),
+            (
+                memory_protection,
+                (self.extended_context, (self.affinity, ())),
+            ),
         );
         unsafe { TaskRef::from_id(id) }
     }
@@ -443,3 +513,248 @@ impl<System: cfg::KernelStatic> Clone for StackHunk<System> {
 }
 
 impl<System: cfg::KernelStatic> Copy for StackHunk<System> {}
+
+/// Specifies the memory access permissions of a task when included in the
+/// task's property [`Bag`]. Constructed by [`TaskDefiner::privileged`] and
+/// [`TaskDefiner::memory_region`].
+///
+/// A kernel might choose to ignore this if memory protection is not supported.
+///
+/// [`Bag`]: crate::bag::Bag
+pub struct MemoryProtection<System: 'static> {
+    privileged: bool,
+    regions: &'static [Frozen<MemoryRegion<System>>],
+}
+
+const MAX_MEMORY_REGIONS: usize = 8;
+
+impl<System: 'static> MemoryProtection<System> {
+    /// The maximum number of memory regions that can be granted to a task.
+    pub const MAX_REGIONS: usize = MAX_MEMORY_REGIONS;
+
+    /// A privileged task, which can access any memory.
+    pub const PRIVILEGED: Self = Self {
+        privileged: true,
+        regions: &[],
+    };
+
+    /// Get a flag indicating whether the task runs in a privileged mode.
+    #[inline]
+    pub const fn is_privileged(&self) -> bool {
+        self.privileged
+    }
+
+    /// Get the memory regions granted to the task.
+    #[inline]
+    pub fn regions(&self) -> impl Iterator<Item = MemoryRegion<System>> + '_ {
+        self.regions.iter().map(Frozen::get)
+    }
+}
+
+impl<System: 'static> Clone for MemoryProtection<System> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<System: 'static> Copy for MemoryProtection<System> {}
+
+impl<System: 'static> fmt::Debug for MemoryProtection<System> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryProtection")
+            .field("privileged", &self.privileged)
+            .field("regions", &self.regions)
+            .finish()
+    }
+}
+
+/// Specifies whether a task uses the processor's extended context when included
+/// in the task's property [`Bag`]. Constructed by
+/// [`TaskDefiner::extended_context`].
+///
+/// A kernel might choose to ignore this if the processor doesn't have such a
+/// state.
+///
+/// [`Bag`]: crate::bag::Bag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtendedContext(bool);
+
+impl ExtendedContext {
+    /// The task doesn't use the extended context.
+    pub const DISABLED: Self = Self(false);
+
+    /// The task uses the extended context.
+    pub const ENABLED: Self = Self(true);
+
+    /// Get a flag indicating whether the task uses the extended context.
+    #[inline]
+    pub const fn is_enabled(self) -> bool {
+        self.0
+    }
+}
+
+/// A set of processors that a task is allowed to run on when included in the
+/// task's property [`Bag`]. Constructed by [`TaskDefiner::affinity`].
+///
+/// The processors are identified by indices in range `0..`[`Self::MAX_CPUS`].
+/// A kernel might choose to ignore this if it doesn't support symmetric
+/// multiprocessing.
+///
+/// [`Bag`]: crate::bag::Bag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuAffinity(u32);
+
+impl CpuAffinity {
+    /// The maximum number of processors that can be represented by
+    /// `CpuAffinity`.
+    pub const MAX_CPUS: usize = 32;
+
+    /// The task can run on any processor.
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Construct a `CpuAffinity` only containing the specified processor.
+    ///
+    /// Panics if `cpu` is not less than [`Self::MAX_CPUS`].
+    #[inline]
+    pub const fn only(cpu: usize) -> Self {
+        assert!(cpu < Self::MAX_CPUS, "processor index out of range");
+        Self(1 << cpu)
+    }
+
+    /// Construct a `CpuAffinity` from a bit mask. The bit `i` indicates
+    /// whether the processor `i` is included.
+    #[inline]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Get the bit mask representation of the set.
+    #[inline]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Get a flag indicating whether the set contains the specified processor.
+    #[inline]
+    pub const fn contains(self, cpu: usize) -> bool {
+        cpu < Self::MAX_CPUS && (self.0 & (1 << cpu)) != 0
+    }
+}
+
+/// A memory region that an unprivileged task is allowed to access. Passed to
+/// [`TaskDefiner::memory_region`].
+pub struct MemoryRegion<System> {
+    start: MemoryRegionStart,
+    len: usize,
+    access: MemoryAccess,
+    _phantom: PhantomInvariant<System>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum MemoryRegionStart {
+    Address(usize),
+    HunkOffset(usize),
+}
+
+/// The access permissions of a [`MemoryRegion`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryAccess {
+    /// The region can be read.
+    ReadOnly,
+    /// The region can be read and written.
+    ReadWrite,
+    /// The region can be read and executed.
+    ReadExecute,
+}
+
+impl MemoryAccess {
+    /// Get a flag indicating whether the region can be written.
+    #[inline]
+    pub const fn is_writable(self) -> bool {
+        matches!(self, Self::ReadWrite)
+    }
+
+    /// Get a flag indicating whether the region can be executed.
+    #[inline]
+    pub const fn is_executable(self) -> bool {
+        matches!(self, Self::ReadExecute)
+    }
+}
+
+impl<System> MemoryRegion<System> {
+    /// Construct a `MemoryRegion` covering the `len` bytes starting at the
+    /// absolute address `start`.
+    pub const fn new(start: usize, len: usize, access: MemoryAccess) -> Self {
+        Self {
+            start: MemoryRegionStart::Address(start),
+            len,
+            access,
+            _phantom: Init::INIT,
+        }
+    }
+
+    /// Get the size of the region in bytes.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get a flag indicating whether the region is empty.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the access permissions of the region.
+    #[inline]
+    pub const fn access(&self) -> MemoryAccess {
+        self.access
+    }
+}
+
+impl<System: cfg::KernelStatic> MemoryRegion<System> {
+    /// Construct a `MemoryRegion` covering the first `len` bytes of the
+    /// specified [`Hunk`].
+    ///
+    /// [`Hunk`]: crate::kernel::Hunk
+    pub const fn from_hunk(hunk: super::Hunk<System>, len: usize, access: MemoryAccess) -> Self {
+        Self {
+            start: MemoryRegionStart::HunkOffset(hunk.offset()),
+            len,
+            access,
+            _phantom: Init::INIT,
+        }
+    }
+
+    /// Get a raw pointer to the region.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut [u8] {
+        let start = match self.start {
+            MemoryRegionStart::Address(address) => address as *mut u8,
+            MemoryRegionStart::HunkOffset(offset) => {
+                super::Hunk::<System>::from_offset(offset).as_ptr()
+            }
+        };
+        core::ptr::slice_from_raw_parts_mut(start, self.len)
+    }
+}
+
+impl<System> Clone for MemoryRegion<System> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<System> Copy for MemoryRegion<System> {}
+
+impl<System> fmt::Debug for MemoryRegion<System> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryRegion")
+            .field("start", &self.start)
+            .field("len", &self.len)
+            .field("access", &self.access)
+            .finish()
+    }
+}
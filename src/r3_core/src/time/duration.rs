@@ -201,6 +201,13 @@ impl fmt::Debug for Duration {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Duration {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=i32}us", self.micros)
+    }
+}
+
 impl ops::Add for Duration {
     type Output = Self;
 
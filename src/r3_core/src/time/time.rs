@@ -162,6 +162,13 @@ impl fmt::Debug for Time {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Time {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=u64}us", self.micros)
+    }
+}
+
 impl ops::Add<Duration> for Time {
     type Output = Self;
 
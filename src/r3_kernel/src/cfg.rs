@@ -1,5 +1,8 @@
 //! Static configuration mechanism for the kernel
-use r3_core::{kernel::Hunk, utils::ConstAllocator};
+use r3_core::{
+    kernel::{task::CpuAffinity, Hunk},
+    utils::ConstAllocator,
+};
 
 use crate::{
     utils::{ComptimeVec, Frozen, FIXED_PRIO_BITMAP_MAX_LEN},
@@ -31,7 +34,7 @@ macro_rules! build {
                 utils::ConstAllocator,
             },
             cfg::{self, CfgBuilder, MiddleCfg},
-            EventGroupCb, InterruptAttr, InterruptLineInit, KernelCfg1,
+            CpuState, EventGroupCb, InterruptAttr, InterruptLineInit, KernelCfg1,
             KernelCfg2, Port, State, TaskAttr, TaskCb, TimeoutRef, TimerAttr,
             TimerCb, SemaphoreCb, MutexCb, PortThreading, readyqueue,
             arrayvec::ArrayVec,
@@ -200,6 +203,7 @@ macro_rules! build {
             Init::INIT;
 
         // Instantiate the global state
+        type CpuStates = [CpuState<$Traits>; <$Traits as PortThreading>::NUM_CPUS];
         type KernelState = State<$Traits>;
         static KERNEL_STATE: KernelState = State::INIT;
 
@@ -227,6 +231,7 @@ macro_rules! build {
         // Safety: We are `build!`, so it's okay to `impl` this
         unsafe impl KernelCfg2 for $Traits {
             type TimeoutHeap = TimeoutHeap;
+            type CpuStates = CpuStates;
 
             #[inline(always)]
             fn state() -> &'static KernelState {
@@ -390,6 +395,15 @@ impl<Traits: KernelTraits> CfgBuilder<Traits> {
     /// Apply post-processing before [`r3_core::kernel::Cfg`] is finalized.
     #[doc(hidden)]
     pub const fn finalize_in_cfg(cfg: &mut r3_core::kernel::Cfg<Self>) {
+        assert!(
+            Traits::NUM_CPUS >= 1 && Traits::NUM_CPUS <= CpuAffinity::MAX_CPUS,
+            "`NUM_CPUS` must be in range `1..=CpuAffinity::MAX_CPUS`"
+        );
+        assert!(
+            Traits::NUM_CPUS == 1 || cfg!(target_has_atomic = "ptr"),
+            "multiprocessor systems require atomic compare-and-swap operations"
+        );
+
         // Create hunks for task stacks.
         let mut i = 0;
         let mut tasks = &mut cfg.raw().tasks;
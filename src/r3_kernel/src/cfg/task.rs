@@ -3,7 +3,7 @@ use r3_core::{
     closure::Closure,
     kernel::{
         raw_cfg::{CfgTask, TaskDescriptor},
-        task::StackHunk,
+        task::{CpuAffinity, ExtendedContext, MemoryProtection, StackHunk},
     },
     utils::Init,
 };
@@ -36,11 +36,43 @@ unsafe impl<Traits: KernelTraits> const CfgTask for CfgBuilder<Traits> {
             stack = task::StackHunk::from_hunk(hunk.hunk(), stack_size);
         }
 
+        let memory_protection =
+            if let Some(memory_protection) = properties.get::<MemoryProtection<Self::System>>() {
+                *memory_protection
+            } else {
+                MemoryProtection::PRIVILEGED
+            };
+
+        let extended_context = if let Some(extended_context) = properties.get::<ExtendedContext>() {
+            *extended_context
+        } else {
+            ExtendedContext::DISABLED
+        };
+
+        let affinity = if let Some(affinity) = properties.get::<CpuAffinity>() {
+            *affinity
+        } else {
+            CpuAffinity::ALL
+        };
+
+        let all_cpus = if Traits::NUM_CPUS >= CpuAffinity::MAX_CPUS {
+            u32::MAX
+        } else {
+            (1 << Traits::NUM_CPUS) - 1
+        };
+        assert!(
+            affinity.bits() & all_cpus != 0,
+            "task's `affinity` must include at least one processor of the system"
+        );
+
         self.tasks.push(CfgBuilderTask {
             start,
             stack,
             priority,
             active,
+            memory_protection,
+            extended_context,
+            affinity,
         });
 
         unsafe { NonZeroUsize::new_unchecked(self.tasks.len()) }
@@ -53,6 +85,9 @@ pub struct CfgBuilderTask<Traits: KernelTraits> {
     pub(super) stack: task::StackHunk<Traits>,
     priority: usize,
     active: bool,
+    memory_protection: MemoryProtection<crate::System<Traits>>,
+    extended_context: ExtendedContext,
+    affinity: CpuAffinity,
 }
 
 impl<Traits: KernelTraits> Clone for CfgBuilderTask<Traits> {
@@ -62,6 +97,9 @@ impl<Traits: KernelTraits> Clone for CfgBuilderTask<Traits> {
             stack: self.stack,
             priority: self.priority,
             active: self.active,
+            memory_protection: self.memory_protection,
+            extended_context: self.extended_context,
+            affinity: self.affinity,
         }
     }
 }
@@ -104,6 +142,9 @@ impl<Traits: KernelTraits> CfgBuilderTask<Traits> {
             stack: self.stack,
             priority: Traits::to_task_priority(self.priority)
                 .expect("task's `priority` must be less than `num_task_priority_levels`"),
+            memory_protection: self.memory_protection,
+            extended_context: self.extended_context,
+            affinity: self.affinity,
         }
     }
 }
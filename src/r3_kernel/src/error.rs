@@ -62,6 +62,94 @@ macro_rules! define_suberror {
     ( @into enum $($_:tt)* ) => {};
 }
 
+/// Define an error type for a service specific to this kernel in the same
+/// way as `r3_core` defines the error types of the standard kernel services.
+/// The variants share the discriminants of [`ResultCode`][1], so the
+/// conversion to it is cost-free.
+///
+/// [1]: errors::ResultCode
+macro_rules! define_error {
+    (
+        mod $mod_name:ident {}
+        $( #[$meta:meta] )*
+        $vis:vis enum $name:ident {
+            $(
+                $( #[$vmeta:meta] )*
+                $vname:ident
+            ),* $(,)*
+        }
+    ) => {
+        $( #[$meta] )*
+        ///
+        /// See [`ResultCode`](r3_core::kernel::ResultCode) for all result
+        /// codes and generic descriptions.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[repr(i8)]
+        $vis enum $name {
+            $(
+                $( #[$vmeta] )*
+                $vname = r3_core::kernel::ResultCode::$vname as i8
+            ),*
+        }
+
+        impl core::fmt::Debug for $name {
+            #[inline]
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&r3_core::kernel::ResultCode::from(*self), f)
+            }
+        }
+
+        impl From<Result<(), $name>> for r3_core::kernel::ResultCode {
+            #[inline]
+            fn from(x: Result<(), $name>) -> Self {
+                match x {
+                    Ok(()) => Self::Success,
+                    Err(e) => Self::from(e),
+                }
+            }
+        }
+
+        impl From<$name> for r3_core::kernel::ResultCode {
+            #[inline]
+            fn from(x: $name) -> Self {
+                match x {
+                    $( $name::$vname => Self::$vname ),*
+                }
+            }
+        }
+
+        #[cfg(test)]
+        mod $mod_name {
+            use super::*;
+            use r3_core::kernel::ResultCode;
+
+            #[test]
+            fn to_result_code() {
+                $(
+                    assert_eq!(
+                        ResultCode::$vname as i8,
+                        $name::$vname as i8,
+                    );
+                    assert_eq!(
+                        ResultCode::$vname,
+                        ResultCode::from($name::$vname),
+                    );
+                )*
+            }
+
+            #[test]
+            fn is_failure() {
+                // All error values represent failure
+                $(
+                    assert!(ResultCode::from(Err($name::$vname)).is_err());
+                )*
+            }
+        }
+    };
+}
+
+pub(crate) use define_error;
+
 define_suberror! {
     /// `BadContext`
     #[into(errors::ActivateTaskError)]
@@ -91,6 +179,7 @@ define_suberror! {
     #[into(errors::SleepError)]
     #[into(errors::StartTimerError)]
     #[into(errors::StopTimerError)]
+    #[into(crate::TaskInfoError)]
     #[into(errors::TimeError)]
     #[into(errors::TryLockMutexError)]
     #[into(errors::UnlockMutexError)]
@@ -126,6 +215,7 @@ define_suberror! {
     #[into(errors::SignalSemaphoreError)]
     #[into(errors::StartTimerError)]
     #[into(errors::StopTimerError)]
+    #[into(crate::TaskInfoError)]
     #[into(errors::TryLockMutexError)]
     #[into(errors::UnlockMutexError)]
     #[into(errors::UnparkError)]
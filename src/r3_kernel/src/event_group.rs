@@ -11,7 +11,9 @@ use r3_core::{
 
 use crate::{
     error::NoAccessError,
-    klock, state, task, timeout,
+    klock, state,
+    syscall::{self, regs, Syscall},
+    task, timeout,
     wait::{WaitPayload, WaitQueue},
     KernelTraits, Port, System,
 };
@@ -41,11 +43,17 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelEventGroup for Sys
         this: EventGroupId,
         bits: EventGroupBits,
     ) -> Result<(), UpdateEventGroupError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
-        set(event_group_cb, lock, bits);
-        Ok(())
+        syscall::call::<Traits, _>(
+            Syscall::EventGroupSet,
+            regs![this.get(), bits as usize],
+            || {
+                let lock = klock::lock_cpu::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+                set(event_group_cb, lock, bits);
+                Ok(())
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -53,21 +61,29 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelEventGroup for Sys
         this: EventGroupId,
         bits: EventGroupBits,
     ) -> Result<(), UpdateEventGroupError> {
-        let mut lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
-        event_group_cb.bits.replace_with(&mut *lock, |b| *b & !bits);
-        Ok(())
+        syscall::call::<Traits, _>(
+            Syscall::EventGroupClear,
+            regs![this.get(), bits as usize],
+            || {
+                let mut lock = klock::lock_cpu::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+                event_group_cb.bits.replace_with(&mut *lock, |b| *b & !bits);
+                Ok(())
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_event_group_get(
         this: EventGroupId,
     ) -> Result<EventGroupBits, GetEventGroupError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
-        Ok(event_group_cb.bits.get(&*lock))
+        syscall::call::<Traits, _>(Syscall::EventGroupGet, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+            Ok(event_group_cb.bits.get(&*lock))
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -76,12 +92,18 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelEventGroup for Sys
         bits: EventGroupBits,
         flags: EventGroupWaitFlags,
     ) -> Result<EventGroupBits, WaitEventGroupError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+        syscall::call::<Traits, _>(
+            Syscall::EventGroupWait,
+            regs![this.get(), bits as usize, flags.bits() as usize],
+            || {
+                let lock = klock::lock_cpu::<Traits>()?;
+                state::expect_waitable_context::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let event_group_cb = unsafe { Self::event_group_cb(this) }?;
 
-        wait(event_group_cb, lock, bits, flags)
+                wait(event_group_cb, lock, bits, flags)
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -91,13 +113,24 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelEventGroup for Sys
         flags: EventGroupWaitFlags,
         timeout: Duration,
     ) -> Result<EventGroupBits, WaitEventGroupTimeoutError> {
-        let time32 = timeout::time32_from_duration(timeout)?;
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+        syscall::call::<Traits, _>(
+            Syscall::EventGroupWaitTimeout,
+            regs![
+                this.get(),
+                bits as usize,
+                flags.bits() as usize,
+                syscall::encode_duration(timeout)
+            ],
+            || {
+                let time32 = timeout::time32_from_duration(timeout)?;
+                let lock = klock::lock_cpu::<Traits>()?;
+                state::expect_waitable_context::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let event_group_cb = unsafe { Self::event_group_cb(this) }?;
 
-        wait_timeout(event_group_cb, lock, bits, flags, time32)
+                wait_timeout(event_group_cb, lock, bits, flags, time32)
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -106,11 +139,17 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelEventGroup for Sys
         bits: EventGroupBits,
         flags: EventGroupWaitFlags,
     ) -> Result<EventGroupBits, PollEventGroupError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let event_group_cb = unsafe { Self::event_group_cb(this) }?;
+        syscall::call::<Traits, _>(
+            Syscall::EventGroupPoll,
+            regs![this.get(), bits as usize, flags.bits() as usize],
+            || {
+                let lock = klock::lock_cpu::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let event_group_cb = unsafe { Self::event_group_cb(this) }?;
 
-        poll(event_group_cb, lock, bits, flags)
+                poll(event_group_cb, lock, bits, flags)
+            },
+        )
     }
 }
 
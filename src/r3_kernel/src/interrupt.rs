@@ -9,7 +9,11 @@ use r3_core::{
     utils::Init,
 };
 
-use crate::{klock, KernelTraits, PortInterrupts, System};
+use crate::{
+    klock,
+    syscall::{self, regs, Syscall},
+    KernelTraits, PortInterrupts, System,
+};
 
 unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelInterruptLine for System<Traits> {
     const RAW_MANAGED_INTERRUPT_PRIORITY_RANGE: core::ops::Range<InterruptPriority> =
@@ -23,52 +27,68 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelInterruptLine for
         this: InterruptNum,
         value: InterruptPriority,
     ) -> Result<(), SetInterruptLinePriorityError> {
-        let mut _lock = klock::lock_cpu::<Traits>()?;
-
-        // Deny a non-task context
-        if !Traits::is_task_context() {
-            return Err(SetInterruptLinePriorityError::BadContext);
-        }
+        syscall::call::<Traits, _>(
+            Syscall::InterruptLineSetPriority,
+            regs![this, value as usize],
+            || {
+                let mut _lock = klock::lock_cpu::<Traits>()?;
+
+                // Deny a non-task context
+                if !Traits::is_task_context() {
+                    return Err(SetInterruptLinePriorityError::BadContext);
+                }
 
-        // Safety: (1) We are the kernel, so it's okay to call `Port`'s methods.
-        //         (2) CPU Lock active
-        unsafe { Traits::set_interrupt_line_priority(this, value) }
+                // Safety: (1) We are the kernel, so it's okay to call `Port`'s methods.
+                //         (2) CPU Lock active
+                unsafe { Traits::set_interrupt_line_priority(this, value) }
+            },
+        )
     }
 
     #[inline]
     unsafe fn raw_interrupt_line_enable(
         this: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        // Safety: We are the kernel, so it's okay to call `Port`'s methods
-        unsafe { Traits::enable_interrupt_line(this) }
+        syscall::call::<Traits, _>(Syscall::InterruptLineEnable, regs![this], || {
+            // Safety: We are the kernel, so it's okay to call `Port`'s methods
+            unsafe { Traits::enable_interrupt_line(this) }
+        })
     }
 
     #[inline]
     unsafe fn raw_interrupt_line_disable(
         this: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        // Safety: We are the kernel, so it's okay to call `Port`'s methods
-        unsafe { Traits::disable_interrupt_line(this) }
+        syscall::call::<Traits, _>(Syscall::InterruptLineDisable, regs![this], || {
+            // Safety: We are the kernel, so it's okay to call `Port`'s methods
+            unsafe { Traits::disable_interrupt_line(this) }
+        })
     }
 
     #[inline]
     unsafe fn raw_interrupt_line_pend(this: InterruptNum) -> Result<(), PendInterruptLineError> {
-        // Safety: We are the kernel, so it's okay to call `Port`'s methods
-        unsafe { Traits::pend_interrupt_line(this) }
+        syscall::call::<Traits, _>(Syscall::InterruptLinePend, regs![this], || {
+            // Safety: We are the kernel, so it's okay to call `Port`'s methods
+            unsafe { Traits::pend_interrupt_line(this) }
+        })
     }
 
     #[inline]
     unsafe fn raw_interrupt_line_clear(this: InterruptNum) -> Result<(), ClearInterruptLineError> {
-        // Safety: We are the kernel, so it's okay to call `Port`'s methods
-        unsafe { Traits::clear_interrupt_line(this) }
+        syscall::call::<Traits, _>(Syscall::InterruptLineClear, regs![this], || {
+            // Safety: We are the kernel, so it's okay to call `Port`'s methods
+            unsafe { Traits::clear_interrupt_line(this) }
+        })
     }
 
     #[inline]
     unsafe fn raw_interrupt_line_is_pending(
         this: InterruptNum,
     ) -> Result<bool, QueryInterruptLineError> {
-        // Safety: We are the kernel, so it's okay to call `Port`'s methods
-        unsafe { Traits::is_interrupt_line_pending(this) }
+        syscall::call::<Traits, _>(Syscall::InterruptLineIsPending, regs![this], || {
+            // Safety: We are the kernel, so it's okay to call `Port`'s methods
+            unsafe { Traits::is_interrupt_line_pending(this) }
+        })
     }
 }
 
@@ -1,11 +1,21 @@
 //! Kernel state locking mechanism
-use core::{fmt, ops};
+//!
+//! In a multiprocessor system ([`PortThreading::NUM_CPUS`]` > 1`), a CPU Lock
+//! state as seen by the kernel and the application consists of two parts: the
+//! processor-local CPU Lock state controlled by the port (which masks
+//! interrupts on the current processor) and [`CpuLockSpinlock`] (which
+//! excludes the other processors). Use the wrapper functions in this module
+//! instead of calling `PortThreading::{enter,leave}_cpu_lock` directly.
+use core::{
+    fmt, ops,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use tokenlock::UnsyncTokenLock;
 
 use crate::{
     error::BadContextError,
     utils::{intrusive_list::CellLike, Init},
-    PortThreading,
+    KernelTraits, PortThreading,
 };
 
 pub(super) struct CpuLockTag<Traits>(Traits);
@@ -28,7 +38,7 @@ impl<Traits, T> CpuLockCell<Traits, T> {
     }
 }
 
-impl<Traits: PortThreading, T: ?Sized> CpuLockCell<Traits, T> {
+impl<Traits: KernelTraits, T: ?Sized> CpuLockCell<Traits, T> {
     /// Clone the contents and apply debug formatting.
     ///
     /// `CpuLockCell` needs to acquire CPU Lock when doing debug formatting and
@@ -59,7 +69,7 @@ impl<Traits: PortThreading, T: ?Sized> CpuLockCell<Traits, T> {
             f: F,
         }
 
-        impl<Traits: PortThreading, T: Clone, F: Fn(T, &mut fmt::Formatter) -> fmt::Result>
+        impl<Traits: KernelTraits, T: Clone, F: Fn(T, &mut fmt::Formatter) -> fmt::Result>
             fmt::Debug for DebugFmtWith<'_, Traits, T, F>
         {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -91,7 +101,7 @@ impl<Traits: PortThreading, T: ?Sized> CpuLockCell<Traits, T> {
             f: F,
         }
 
-        impl<Traits: PortThreading, T: ?Sized, F: Fn(&T, &mut fmt::Formatter) -> fmt::Result>
+        impl<Traits: KernelTraits, T: ?Sized, F: Fn(&T, &mut fmt::Formatter) -> fmt::Result>
             fmt::Debug for DebugFmtWithRef<'_, Traits, T, F>
         {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -109,7 +119,7 @@ impl<Traits: PortThreading, T: ?Sized> CpuLockCell<Traits, T> {
     }
 }
 
-impl<Traits: PortThreading, T: fmt::Debug> fmt::Debug for CpuLockCell<Traits, T> {
+impl<Traits: KernelTraits, T: fmt::Debug> fmt::Debug for CpuLockCell<Traits, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.debug_fmt_with_ref(|x, f| x.fmt(f)).fmt(f)
     }
@@ -132,7 +142,7 @@ impl<Traits, T> ops::DerefMut for CpuLockCell<Traits, T> {
     }
 }
 
-impl<'a, Element: Clone, Traits: PortThreading> CellLike<&'a mut CpuLockGuard<Traits>>
+impl<'a, Element: Clone, Traits: KernelTraits> CellLike<&'a mut CpuLockGuard<Traits>>
     for CpuLockCell<Traits, Element>
 {
     type Target = Element;
@@ -174,9 +184,9 @@ impl<'a, Element: Clone, Traits: PortThreading> CellLike<CpuLockTokenRefMut<'a,
 
 /// Attempt to enter a CPU Lock state and get an RAII guard.
 /// Return `BadContext` if the kernel is already in a CPU Lock state.
-pub(super) fn lock_cpu<Traits: PortThreading>() -> Result<CpuLockGuard<Traits>, BadContextError> {
+pub(super) fn lock_cpu<Traits: KernelTraits>() -> Result<CpuLockGuard<Traits>, BadContextError> {
     // Safety: `try_enter_cpu_lock` is only meant to be called by the kernel
-    if unsafe { Traits::try_enter_cpu_lock() } {
+    if unsafe { try_enter_cpu_lock::<Traits>() } {
         // Safety: We just entered a CPU Lock state. This also means there are
         //         no instances of `CpuLockGuard` existing at this point.
         Ok(unsafe { assume_cpu_lock() })
@@ -189,9 +199,11 @@ pub(super) fn lock_cpu<Traits: PortThreading>() -> Result<CpuLockGuard<Traits>,
 ///
 /// # Safety
 ///
-/// The system must be really in a CPU Lock state. There must be no instances of
-/// `CpuLockGuard` existing at the point of the call.
-pub(super) unsafe fn assume_cpu_lock<Traits: PortThreading>() -> CpuLockGuard<Traits> {
+/// The system must be really in a CPU Lock state, including
+/// [`CpuLockSpinlock`] being held by the current processor in a
+/// multiprocessor system. There must be no instances of `CpuLockGuard`
+/// existing at the point of the call.
+pub(super) unsafe fn assume_cpu_lock<Traits: KernelTraits>() -> CpuLockGuard<Traits> {
     debug_assert!(Traits::is_cpu_lock_active());
 
     CpuLockGuard {
@@ -204,40 +216,166 @@ pub(super) unsafe fn assume_cpu_lock<Traits: PortThreading>() -> CpuLockGuard<Tr
 /// RAII guard for a CPU Lock state.
 ///
 /// [`CpuLockToken`] can be borrowed from this type.
-pub(super) struct CpuLockGuard<Traits: PortThreading> {
+pub(super) struct CpuLockGuard<Traits: KernelTraits> {
     token: CpuLockToken<Traits>,
 }
 
-impl<Traits: PortThreading> CpuLockGuard<Traits> {
+impl<Traits: KernelTraits> CpuLockGuard<Traits> {
     /// Construct a [`CpuLockTokenRefMut`] by borrowing `self`.
     pub(super) fn borrow_mut(&mut self) -> CpuLockTokenRefMut<'_, Traits> {
         self.token.borrow_mut()
     }
 }
 
-impl<Traits: PortThreading> Drop for CpuLockGuard<Traits> {
+impl<Traits: KernelTraits> Drop for CpuLockGuard<Traits> {
     fn drop(&mut self) {
         // Safety: CPU Lock is currently active, and it's us (the kernel) who
         // are currently controlling the CPU Lock state
         unsafe {
-            Traits::leave_cpu_lock();
+            leave_cpu_lock::<Traits>();
         }
     }
 }
 
-impl<Traits: PortThreading> ops::Deref for CpuLockGuard<Traits> {
+impl<Traits: KernelTraits> ops::Deref for CpuLockGuard<Traits> {
     type Target = CpuLockToken<Traits>;
     fn deref(&self) -> &Self::Target {
         &self.token
     }
 }
 
-impl<Traits: PortThreading> ops::DerefMut for CpuLockGuard<Traits> {
+impl<Traits: KernelTraits> ops::DerefMut for CpuLockGuard<Traits> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.token
     }
 }
 
+/// Activate CPU Lock, also acquiring [`CpuLockSpinlock`] in a multiprocessor
+/// system.
+///
+/// Precondition: CPU Lock inactive
+pub(super) unsafe fn enter_cpu_lock<Traits: KernelTraits>() {
+    // Safety: CPU Lock inactive
+    unsafe { Traits::enter_cpu_lock() };
+    // Safety: The processor-local CPU Lock is active
+    unsafe { acquire_cpu_lock_spinlock::<Traits>() };
+}
+
+/// Activate CPU Lock, also acquiring [`CpuLockSpinlock`] in a multiprocessor
+/// system. Return `true` iff CPU Lock was inactive before the call.
+pub(super) unsafe fn try_enter_cpu_lock<Traits: KernelTraits>() -> bool {
+    if unsafe { Traits::try_enter_cpu_lock() } {
+        // Safety: The processor-local CPU Lock is active
+        unsafe { acquire_cpu_lock_spinlock::<Traits>() };
+        true
+    } else {
+        false
+    }
+}
+
+/// Deactivate CPU Lock, also releasing [`CpuLockSpinlock`] in a multiprocessor
+/// system.
+///
+/// Precondition: CPU Lock active
+pub(super) unsafe fn leave_cpu_lock<Traits: KernelTraits>() {
+    // Safety: The processor-local CPU Lock is active, and the spinlock is held
+    //         by the current processor
+    unsafe { release_cpu_lock_spinlock::<Traits>() };
+    // Safety: CPU Lock active
+    unsafe { Traits::leave_cpu_lock() };
+}
+
+/// Acquire [`CpuLockSpinlock`] if the system has more than one processor.
+///
+/// Precondition: The processor-local CPU Lock active, the spinlock not held by
+/// the current processor
+#[inline]
+pub(super) unsafe fn acquire_cpu_lock_spinlock<Traits: KernelTraits>() {
+    if Traits::NUM_CPUS > 1 {
+        Traits::state()
+            .cpu_lock_spinlock
+            .lock(Traits::current_cpu());
+    }
+}
+
+/// Release [`CpuLockSpinlock`] if the system has more than one processor.
+///
+/// Precondition: The processor-local CPU Lock active, the spinlock held by the
+/// current processor
+#[inline]
+pub(super) unsafe fn release_cpu_lock_spinlock<Traits: KernelTraits>() {
+    if Traits::NUM_CPUS > 1 {
+        let spinlock = &Traits::state().cpu_lock_spinlock;
+        debug_assert!(spinlock.is_held_by(Traits::current_cpu()));
+        spinlock.unlock();
+    }
+}
+
+/// The spinlock that serializes accesses to the kernel state by multiple
+/// processors. Only used if [`PortThreading::NUM_CPUS`]` > 1`.
+///
+/// The spinlock records the owning processor so that the ownership can be
+/// handed over from a task exiting on the processor to the dispatcher running
+/// next on the same processor (see [`PortThreading::exit_and_dispatch`]).
+pub(super) struct CpuLockSpinlock {
+    /// `0` if unlocked, `i + 1` if held by the processor `i`.
+    owner: AtomicUsize,
+}
+
+impl Init for CpuLockSpinlock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        owner: AtomicUsize::new(0),
+    };
+}
+
+impl fmt::Debug for CpuLockSpinlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.owner.load(Ordering::Relaxed) {
+            0 => f.write_str("CpuLockSpinlock(< unlocked >)"),
+            owner => write!(f, "CpuLockSpinlock(< held by processor {} >)", owner - 1),
+        }
+    }
+}
+
+impl CpuLockSpinlock {
+    /// Acquire the spinlock on behalf of the processor `cpu`.
+    #[inline]
+    pub(super) fn lock(&self, cpu: usize) {
+        #[cfg(target_has_atomic = "ptr")]
+        while self
+            .owner
+            .compare_exchange_weak(0, cpu + 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.owner.load(Ordering::Relaxed) != 0 {
+                core::hint::spin_loop();
+            }
+        }
+
+        // `CfgBuilder` rejects multiprocessor systems on targets without
+        // compare-and-swap operations
+        #[cfg(not(target_has_atomic = "ptr"))]
+        {
+            let _ = cpu;
+            unreachable!();
+        }
+    }
+
+    /// Release the spinlock.
+    #[inline]
+    pub(super) fn unlock(&self) {
+        self.owner.store(0, Ordering::Release);
+    }
+
+    /// Get a flag indicating whether the spinlock is held by the processor
+    /// `cpu`.
+    #[inline]
+    pub(super) fn is_held_by(&self, cpu: usize) -> bool {
+        self.owner.load(Ordering::Relaxed) == cpu + 1
+    }
+}
+
 /// Borrowed version of [`CpuLockGuard`]. This is equivalent to
 /// `&'a mut CpuLockGuard` but does not consume memory.
 ///
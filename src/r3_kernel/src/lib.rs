@@ -62,8 +62,15 @@ pub mod _changelog_ {}
 pub mod utils;
 
 #[cfg(feature = "priority_boost")]
-use core::sync::atomic::{AtomicBool, Ordering};
-use core::{fmt, marker::PhantomData, mem::forget, num::NonZeroUsize, ops::Range};
+use core::sync::atomic::AtomicBool;
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::forget,
+    num::NonZeroUsize,
+    ops::Range,
+    sync::atomic::{self, Ordering},
+};
 
 use r3_core::{
     kernel::{
@@ -74,7 +81,10 @@ use r3_core::{
     utils::Init,
 };
 
-use crate::utils::{binary_heap::VecLike, BinUInteger};
+use crate::{
+    syscall::{regs, Syscall},
+    utils::{binary_heap::VecLike, BinUInteger},
+};
 
 #[macro_use]
 pub mod cfg;
@@ -85,12 +95,15 @@ mod klock;
 mod mutex;
 mod semaphore;
 mod state;
+pub mod syscall;
 mod task;
 mod timeout;
 mod timer;
 mod wait;
+pub mod watchdog;
 
 // Some of these re-exports are for our macros, the others are really public
+pub use wait::WaitTarget;
 pub use {event_group::*, interrupt::*, mutex::*, semaphore::*, task::*, timeout::*, timer::*};
 
 /// Numeric value used to identify various kinds of kernel objects.
@@ -148,40 +161,52 @@ unsafe impl<Traits: KernelTraits> raw::KernelBase for System<Traits> {
 
     #[inline]
     fn raw_acquire_cpu_lock() -> Result<(), r3_core::kernel::CpuLockError> {
-        // Safety: `try_enter_cpu_lock` is only meant to be called by
-        //         the kernel
-        if unsafe { Traits::try_enter_cpu_lock() } {
-            Ok(())
-        } else {
-            Err(r3_core::kernel::CpuLockError::BadContext)
-        }
+        syscall::call::<Traits, _>(Syscall::AcquireCpuLock, regs![], || {
+            // Safety: `try_enter_cpu_lock` is only meant to be called by
+            //         the kernel
+            if unsafe { klock::try_enter_cpu_lock::<Traits>() } {
+                Ok(())
+            } else {
+                Err(r3_core::kernel::CpuLockError::BadContext)
+            }
+        })
     }
 
     #[inline]
     unsafe fn raw_release_cpu_lock() -> Result<(), r3_core::kernel::CpuLockError> {
-        if !Traits::is_cpu_lock_active() {
-            Err(r3_core::kernel::CpuLockError::BadContext)
-        } else {
-            // Safety: CPU Lock active
-            unsafe { Traits::leave_cpu_lock() };
-            Ok(())
-        }
+        syscall::call::<Traits, _>(Syscall::ReleaseCpuLock, regs![], || {
+            if !Traits::is_cpu_lock_active() {
+                Err(r3_core::kernel::CpuLockError::BadContext)
+            } else {
+                // Safety: CPU Lock active
+                unsafe { klock::leave_cpu_lock::<Traits>() };
+                Ok(())
+            }
+        })
     }
 
     #[inline]
     fn raw_has_cpu_lock() -> bool {
-        Traits::is_cpu_lock_active()
+        syscall::call::<Traits, _>(Syscall::HasCpuLock, regs![], Traits::is_cpu_lock_active)
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_unboost_priority() -> Result<(), r3_core::kernel::BoostPriorityError> {
-        state::unboost_priority::<Traits>()
+        syscall::call::<Traits, _>(
+            Syscall::UnboostPriority,
+            regs![],
+            state::unboost_priority::<Traits>,
+        )
     }
 
     #[inline]
     #[cfg(feature = "priority_boost")]
     fn raw_is_priority_boost_active() -> bool {
-        Traits::state().priority_boost.load(Ordering::Relaxed)
+        syscall::call::<Traits, _>(
+            Syscall::IsPriorityBoostActive,
+            regs![],
+            state::is_priority_boost_active::<Traits>,
+        )
     }
 
     #[inline]
@@ -192,42 +217,63 @@ unsafe impl<Traits: KernelTraits> raw::KernelBase for System<Traits> {
 
     #[inline]
     fn raw_is_task_context() -> bool {
-        Traits::is_task_context()
+        syscall::call::<Traits, _>(Syscall::IsTaskContext, regs![], Traits::is_task_context)
     }
 
     #[inline]
     fn raw_is_interrupt_context() -> bool {
-        Traits::is_interrupt_context()
+        syscall::call::<Traits, _>(
+            Syscall::IsInterruptContext,
+            regs![],
+            Traits::is_interrupt_context,
+        )
     }
 
     #[inline]
     fn raw_is_boot_complete() -> bool {
-        Traits::is_scheduler_active()
+        syscall::call::<Traits, _>(
+            Syscall::IsBootComplete,
+            regs![],
+            Traits::is_scheduler_active,
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_set_time(time: Time) -> Result<(), r3_core::kernel::TimeError> {
-        timeout::set_system_time::<Traits>(time)
+        let [lo, hi] = syscall::encode_time(time);
+        syscall::call::<Traits, _>(Syscall::SetTime, regs![lo, hi], || {
+            timeout::set_system_time::<Traits>(time)
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_exit_task() -> Result<!, r3_core::kernel::ExitTaskError> {
-        // Safety: Just forwarding the function call
-        unsafe { task::exit_current_task::<Traits>() }
+        syscall::call::<Traits, _>(Syscall::ExitTask, regs![], || {
+            // Safety: Just forwarding the function call
+            unsafe { task::exit_current_task::<Traits>() }
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_park() -> Result<(), r3_core::kernel::ParkError> {
-        task::park_current_task::<Traits>()
+        syscall::call::<Traits, _>(Syscall::Park, regs![], task::park_current_task::<Traits>)
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_park_timeout(timeout: Duration) -> Result<(), r3_core::kernel::ParkTimeoutError> {
-        task::park_current_task_timeout::<Traits>(timeout)
+        syscall::call::<Traits, _>(
+            Syscall::ParkTimeout,
+            regs![syscall::encode_duration(timeout)],
+            || task::park_current_task_timeout::<Traits>(timeout),
+        )
     }
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_sleep(timeout: Duration) -> Result<(), r3_core::kernel::SleepError> {
-        task::put_current_task_on_sleep_timeout::<Traits>(timeout)
+        syscall::call::<Traits, _>(
+            Syscall::Sleep,
+            regs![syscall::encode_duration(timeout)],
+            || task::put_current_task_on_sleep_timeout::<Traits>(timeout),
+        )
     }
 
     type RawDebugPrinter = KernelDebugPrinter<Traits>;
@@ -246,42 +292,52 @@ unsafe impl<Traits: KernelTraits> raw::KernelBase for System<Traits> {
 
     #[inline]
     fn raw_task_current() -> Result<Self::RawTaskId, r3_core::kernel::GetCurrentTaskError> {
-        Self::task_current()
+        syscall::call::<Traits, _>(Syscall::TaskCurrent, regs![], Self::task_current)
     }
 
     #[inline]
     unsafe fn raw_task_activate(
         this: Self::RawTaskId,
     ) -> Result<(), r3_core::kernel::ActivateTaskError> {
-        Self::task_activate(this)
+        syscall::call::<Traits, _>(Syscall::TaskActivate, regs![this.get()], || {
+            Self::task_activate(this)
+        })
     }
 
     #[inline]
     unsafe fn raw_task_interrupt(
         this: Self::RawTaskId,
     ) -> Result<(), r3_core::kernel::InterruptTaskError> {
-        Self::task_interrupt(this)
+        syscall::call::<Traits, _>(Syscall::TaskInterrupt, regs![this.get()], || {
+            Self::task_interrupt(this)
+        })
     }
 
     #[inline]
     unsafe fn raw_task_unpark_exact(
         this: Self::RawTaskId,
     ) -> Result<(), r3_core::kernel::UnparkExactError> {
-        Self::task_unpark_exact(this)
+        syscall::call::<Traits, _>(Syscall::TaskUnparkExact, regs![this.get()], || {
+            Self::task_unpark_exact(this)
+        })
     }
 
     #[inline]
     unsafe fn raw_task_priority(
         this: Self::RawTaskId,
     ) -> Result<usize, r3_core::kernel::GetTaskPriorityError> {
-        Self::task_priority(this)
+        syscall::call::<Traits, _>(Syscall::TaskPriority, regs![this.get()], || {
+            Self::task_priority(this)
+        })
     }
 
     #[inline]
     unsafe fn raw_task_effective_priority(
         this: Self::RawTaskId,
     ) -> Result<usize, r3_core::kernel::GetTaskPriorityError> {
-        Self::task_effective_priority(this)
+        syscall::call::<Traits, _>(Syscall::TaskEffectivePriority, regs![this.get()], || {
+            Self::task_effective_priority(this)
+        })
     }
 }
 
@@ -291,7 +347,11 @@ unsafe impl<Traits: KernelTraits> raw::KernelTaskSetPriority for System<Traits>
         this: Self::RawTaskId,
         priority: usize,
     ) -> Result<(), r3_core::kernel::SetTaskPriorityError> {
-        Self::task_set_priority(this, priority)
+        syscall::call::<Traits, _>(
+            Syscall::TaskSetPriority,
+            regs![this.get(), priority],
+            || Self::task_set_priority(this, priority),
+        )
     }
 }
 
@@ -300,7 +360,11 @@ unsafe impl<Traits: KernelTraits> raw::KernelTaskSetPriority for System<Traits>
 unsafe impl<Traits: KernelTraits> raw::KernelBoostPriority for System<Traits> {
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_boost_priority() -> Result<(), r3_core::kernel::BoostPriorityError> {
-        state::boost_priority::<Traits>()
+        syscall::call::<Traits, _>(
+            Syscall::BoostPriority,
+            regs![],
+            state::boost_priority::<Traits>,
+        )
     }
 }
 
@@ -309,7 +373,7 @@ unsafe impl<Traits: KernelTraits> raw::KernelBoostPriority for System<Traits> {
 unsafe impl<Traits: KernelTraits> raw::KernelTime for System<Traits> {
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_time() -> Result<Time, r3_core::kernel::TimeError> {
-        timeout::system_time::<Traits>()
+        syscall::call::<Traits, _>(Syscall::Time, regs![], timeout::system_time::<Traits>)
     }
 }
 
@@ -318,7 +382,11 @@ unsafe impl<Traits: KernelTraits> raw::KernelAdjustTime for System<Traits> {
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     fn raw_adjust_time(delta: Duration) -> Result<(), r3_core::kernel::AdjustTimeError> {
-        timeout::adjust_system_and_event_time::<Traits>(delta)
+        syscall::call::<Traits, _>(
+            Syscall::AdjustTime,
+            regs![syscall::encode_duration(delta)],
+            || timeout::adjust_system_and_event_time::<Traits>(delta),
+        )
     }
 }
 
@@ -416,11 +484,26 @@ pub unsafe trait PortThreading: KernelCfg1 + KernelStatic<System<Self>> {
     /// [`StackHunk`]: r3_core::kernel::task::StackHunk
     const STACK_ALIGN: usize = core::mem::size_of::<usize>();
 
+    /// The number of processors that run the kernel. Must be in range
+    /// `1..=`[`CpuAffinity::MAX_CPUS`]. Defaults to `1`.
+    ///
+    /// If this is greater than `1`, the kernel operates in the [symmetric
+    /// multiprocessing mode][1]. The port must override
+    /// [`Self::current_cpu`] and [`Self::yield_cpu_on`] in this case. The target must support atomic
+    /// compare-and-swap operations.
+    ///
+    /// [1]: crate#symmetric-multiprocessing
+    /// [`CpuAffinity::MAX_CPUS`]: r3_core::kernel::task::CpuAffinity::MAX_CPUS
+    const NUM_CPUS: usize = 1;
+
     /// Transfer the control to the dispatcher, discarding the current
     /// (startup) context. `*state.`[`running_task_ptr`]`()` is `None` at this
     /// point. The dispatcher should call [`PortToKernel::choose_running_task`]
     /// to find the next task to run and transfer the control to that task.
     ///
+    /// In a multiprocessor system, this is called on every processor, by
+    /// [`PortToKernel::boot`] or [`PortToKernel::boot_secondary`].
+    ///
     /// Precondition: CPU Lock active, a boot context
     ///
     /// [`running_task_ptr`]: State::running_task_ptr
@@ -452,6 +535,11 @@ pub unsafe trait PortThreading: KernelCfg1 + KernelStatic<System<Self>> {
     /// already been removed from `*state.`[`running_task_ptr`]`()`) and proceed
     /// to the dispatcher.
     ///
+    /// In a multiprocessor system, the kernel doesn't let the other processors
+    /// access the kernel state (and thus reactivate `task`) until the next
+    /// call to [`PortToKernel::choose_running_task`] on the current processor.
+    /// The port must not use `task`'s stack after making that call.
+    ///
     /// Precondition: CPU Lock active
     ///
     /// [`running_task_ptr`]: State::running_task_ptr
@@ -459,6 +547,9 @@ pub unsafe trait PortThreading: KernelCfg1 + KernelStatic<System<Self>> {
 
     /// Disable all kernel-managed interrupts (this state is called *CPU Lock*).
     ///
+    /// In a multiprocessor system, this only affects the current processor.
+    /// The kernel excludes the other processors by a spinlock.
+    ///
     /// Precondition: CPU Lock inactive
     unsafe fn enter_cpu_lock();
 
@@ -516,6 +607,46 @@ pub unsafe trait PortThreading: KernelCfg1 + KernelStatic<System<Self>> {
     /// Return a flag indicating whether [`Self::dispatch_first_task`][] was
     /// called.
     fn is_scheduler_active() -> bool;
+
+    /// Get the gate through which the current context must call kernel
+    /// services, or `None` if it can access the kernel state directly. The
+    /// kernel calls this before executing every kernel service.
+    ///
+    /// A port that runs tasks with reduced privileges should return a gate in
+    /// such tasks. The gate must execute [`syscall::dispatch`]`::<Self>` with
+    /// the given parameters in a privileged context on behalf of the current
+    /// task, e.g., by a trap instruction, and return to the caller's privilege
+    /// level afterwards. The caller's own code must not observe elevated
+    /// privileges at any point. Only scalar values pass through the gate, and
+    /// `dispatch` validates them. The default implementation returns `None`.
+    #[inline(always)]
+    fn syscall_gate() -> Option<syscall::Gate> {
+        None
+    }
+
+    /// Get the index of the current processor, which is in range
+    /// `0..`[`Self::NUM_CPUS`]. The default implementation returns `0`.
+    ///
+    /// The kernel only calls this when a task can't migrate to another
+    /// processor, e.g., when CPU Lock is active.
+    #[inline]
+    fn current_cpu() -> usize {
+        0
+    }
+
+    /// Make the processor `cpu`, which is not the current one, call the
+    /// dispatcher as if [`Self::yield_cpu`] was called on that processor.
+    /// This is usually implemented by an inter-processor interrupt.
+    ///
+    /// This method is only called if [`Self::NUM_CPUS`]` > 1`. The default
+    /// implementation panics.
+    ///
+    /// Precondition: CPU Lock active
+    #[inline]
+    unsafe fn yield_cpu_on(cpu: usize) {
+        let _ = cpu;
+        unimplemented!("`yield_cpu_on` is required by a multiprocessor system");
+    }
 }
 
 /// Implemented by a port. This trait contains items related to controlling
@@ -696,10 +827,23 @@ pub trait PortToKernel {
     /// Should be called for exactly once by the port before calling into any
     /// user (application) or kernel code.
     ///
+    /// In a multiprocessor system, only one processor should call this method.
+    /// The other processors should call [`Self::boot_secondary`].
+    ///
     /// Precondition: CPU Lock active, Preboot phase
     // TODO: Explain phases
     unsafe fn boot() -> !;
 
+    /// Wait until [`Self::boot`] completes on another processor and start
+    /// scheduling tasks on the current processor by calling
+    /// [`PortThreading::dispatch_first_task`].
+    ///
+    /// This is only meant to be called by a processor other than the one that
+    /// calls `boot` in a multiprocessor system.
+    ///
+    /// Precondition: CPU Lock active, Preboot phase
+    unsafe fn boot_secondary() -> !;
+
     /// Determine the next task to run and store it in [`State::running_task_ptr`].
     ///
     /// Precondition: CPU Lock active / Postcondition: CPU Lock active
@@ -752,6 +896,11 @@ impl<Traits: KernelTraits> PortToKernel for Traits {
         // Safety: This is the intended place to call startup hooks.
         unsafe { (Traits::STARTUP_HOOK)() };
 
+        // Release the other processors waiting in `boot_secondary`. They
+        // haven't accessed the kernel state so far, which is why we didn't
+        // need to acquire the spinlock.
+        Traits::state().boot_complete.store(true, Ordering::Release);
+
         forget(lock);
 
         // Safety: CPU Lock is active, Startup phase
@@ -760,16 +909,38 @@ impl<Traits: KernelTraits> PortToKernel for Traits {
         }
     }
 
+    unsafe fn boot_secondary() -> ! {
+        while !Traits::state().boot_complete.load(Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+
+        // Safety: CPU Lock is active, Startup phase
+        unsafe {
+            Traits::dispatch_first_task();
+        }
+    }
+
     #[inline(always)]
     unsafe fn choose_running_task() {
+        // The spinlock might have been handed over by `exit_current_task`
+        // (see `PortThreading::exit_and_dispatch`)
+        let cpu = Traits::current_cpu();
+        if Traits::NUM_CPUS > 1 && !Traits::state().cpu_lock_spinlock.is_held_by(cpu) {
+            // Safety: The processor-local CPU Lock is active
+            unsafe { klock::acquire_cpu_lock_spinlock::<Traits>() };
+        }
+
         // Safety: The precondition of this method includes CPU Lock being
         // active
         let mut lock = unsafe { klock::assume_cpu_lock::<Traits>() };
 
         task::choose_next_running_task(lock.borrow_mut());
 
-        // Post-condition: CPU Lock active
+        // Post-condition: CPU Lock active. Only the processor-local part
+        // remains because the port will deactivate it by itself.
         forget(lock);
+        // Safety: The spinlock is held by the current processor
+        unsafe { klock::release_cpu_lock_spinlock::<Traits>() };
     }
 
     #[inline(always)]
@@ -791,6 +962,10 @@ pub unsafe trait KernelCfg2: Port + Sized + KernelStatic<System<Self>> {
     #[doc(hidden)]
     type TimeoutHeap: VecLike<Element = timeout::TimeoutRef<Self>> + Init + fmt::Debug + 'static;
 
+    /// The storage of [`CpuState`]s, one for each processor.
+    #[doc(hidden)]
+    type CpuStates: AsRef<[CpuState<Self>]> + Init + fmt::Debug + Send + Sync + 'static;
+
     /// The table of combined second-level interrupt handlers.
     ///
     /// A port should generate first-level interrupt handlers that call them.
@@ -868,24 +1043,26 @@ pub struct State<
     TaskReadyQueue: 'static = <Traits as KernelCfg1>::TaskReadyQueue,
     TaskPriority: 'static = <Traits as KernelCfg1>::TaskPriority,
     TimeoutHeap: 'static = <Traits as KernelCfg2>::TimeoutHeap,
+    CpuStates: 'static = <Traits as KernelCfg2>::CpuStates,
 > {
-    /// The currently or recently running task. Can be in a Running, Waiting, or
-    /// Ready state. The last two only can be observed momentarily around a
-    /// call to `yield_cpu` or in an interrupt handler.
-    ///
-    /// It must refer to an element of [`KernelCfg2::task_cb_pool`].
-    running_task:
-        klock::CpuLockCell<Traits, Option<&'static TaskCb<Traits, PortTaskState, TaskPriority>>>,
+    /// The per-processor states. The element at index `i` belongs to the
+    /// processor `i`.
+    cpus: CpuStates,
 
-    /// The task ready queue.
-    task_ready_queue: TaskReadyQueue,
+    /// Serializes accesses to the kernel state by multiple processors. Only
+    /// used if [`PortThreading::NUM_CPUS`]` > 1`.
+    cpu_lock_spinlock: klock::CpuLockSpinlock,
 
-    #[cfg(feature = "priority_boost")]
-    /// `true` if Priority Boost is active.
-    priority_boost: AtomicBool,
+    /// `true` if [`PortToKernel::boot`] has completed the initialization.
+    boot_complete: atomic::AtomicBool,
+
+    /// The task ready queue, shared by all processors.
+    task_ready_queue: TaskReadyQueue,
 
     /// The global state of the timekeeping system.
     timeout: timeout::TimeoutGlobals<Traits, TimeoutHeap>,
+
+    _phantom: PhantomData<(PortTaskState, TaskPriority)>,
 }
 
 impl<
@@ -894,14 +1071,16 @@ impl<
         TaskReadyQueue: 'static + Init,
         TaskPriority: 'static,
         TimeoutHeap: 'static + Init,
-    > Init for State<Traits, PortTaskState, TaskReadyQueue, TaskPriority, TimeoutHeap>
+        CpuStates: 'static + Init,
+    > Init for State<Traits, PortTaskState, TaskReadyQueue, TaskPriority, TimeoutHeap, CpuStates>
 {
     const INIT: Self = Self {
-        running_task: klock::CpuLockCell::new(None),
+        cpus: Init::INIT,
+        cpu_lock_spinlock: Init::INIT,
+        boot_complete: atomic::AtomicBool::new(false),
         task_ready_queue: Init::INIT,
-        #[cfg(feature = "priority_boost")]
-        priority_boost: AtomicBool::new(false),
         timeout: Init::INIT,
+        _phantom: PhantomData,
     };
 }
 
@@ -911,37 +1090,45 @@ impl<
         TaskReadyQueue: 'static + fmt::Debug,
         TaskPriority: 'static + fmt::Debug,
         TimeoutHeap: 'static + fmt::Debug,
-    > fmt::Debug for State<Traits, PortTaskState, TaskReadyQueue, TaskPriority, TimeoutHeap>
+        CpuStates: 'static + fmt::Debug,
+    > fmt::Debug
+    for State<Traits, PortTaskState, TaskReadyQueue, TaskPriority, TimeoutHeap, CpuStates>
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("State")
-            .field("running_task", &self.running_task.get_and_debug_fmt())
+            .field("cpus", &self.cpus)
+            .field("cpu_lock_spinlock", &self.cpu_lock_spinlock)
+            .field("boot_complete", &self.boot_complete)
             .field("task_ready_queue", &self.task_ready_queue)
-            .field(
-                "priority_boost",
-                match () {
-                    #[cfg(feature = "priority_boost")]
-                    () => &self.priority_boost,
-                    #[cfg(not(feature = "priority_boost"))]
-                    () => &(),
-                },
-            )
             .field("timeout", &self.timeout)
             .finish()
     }
 }
 
 impl<Traits: KernelCfg2> State<Traits> {
+    /// Get the per-processor states of all processors.
+    #[inline]
+    fn cpu_states(&self) -> &[CpuState<Traits>] {
+        self.cpus.as_ref()
+    }
+
+    /// Get the state of the current processor.
+    #[inline]
+    fn cpu_state(&self) -> &CpuState<Traits> {
+        &self.cpu_states()[Traits::current_cpu()]
+    }
+
     /// Get the currently running task.
     #[inline]
     fn running_task(
         &self,
         lock: klock::CpuLockTokenRefMut<Traits>,
     ) -> Option<&'static TaskCb<Traits>> {
-        *self.running_task.read(&*lock)
+        *self.cpu_state().running_task.read(&*lock)
     }
 
-    /// Get a pointer to the variable storing the currently running task.
+    /// Get a pointer to the variable storing the currently running task of
+    /// the current processor.
     ///
     /// Reading the variable is safe as long as the read is free of data race.
     /// Note that only the dispatcher (that calls
@@ -955,7 +1142,59 @@ impl<Traits: KernelCfg2> State<Traits> {
     /// Writing the variable is not allowed.
     #[inline]
     pub fn running_task_ptr(&self) -> *mut Option<&'static TaskCb<Traits>> {
-        self.running_task.as_ptr()
+        self.cpu_state().running_task.as_ptr()
+    }
+}
+
+/// Per-processor kernel state.
+pub struct CpuState<
+    Traits: PortThreading,
+    PortTaskState: 'static = <Traits as PortThreading>::PortTaskState,
+    TaskPriority: 'static = <Traits as KernelCfg1>::TaskPriority,
+> {
+    /// The currently or recently running task of the processor. Can be in a
+    /// Running, Waiting, or Ready state. The last two only can be observed
+    /// momentarily around a call to `yield_cpu` or in an interrupt handler.
+    ///
+    /// It must refer to an element of [`KernelCfg2::task_cb_pool`].
+    running_task:
+        klock::CpuLockCell<Traits, Option<&'static TaskCb<Traits, PortTaskState, TaskPriority>>>,
+
+    #[cfg(feature = "priority_boost")]
+    /// `true` if Priority Boost is active on the processor.
+    priority_boost: AtomicBool,
+}
+
+impl<Traits: PortThreading, PortTaskState: 'static, TaskPriority: 'static> Init
+    for CpuState<Traits, PortTaskState, TaskPriority>
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        running_task: klock::CpuLockCell::new(None),
+        #[cfg(feature = "priority_boost")]
+        priority_boost: AtomicBool::new(false),
+    };
+}
+
+impl<
+        Traits: KernelTraits,
+        PortTaskState: 'static + fmt::Debug,
+        TaskPriority: 'static + fmt::Debug,
+    > fmt::Debug for CpuState<Traits, PortTaskState, TaskPriority>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CpuState")
+            .field("running_task", &self.running_task.get_and_debug_fmt())
+            .field(
+                "priority_boost",
+                match () {
+                    #[cfg(feature = "priority_boost")]
+                    () => &self.priority_boost,
+                    #[cfg(not(feature = "priority_boost"))]
+                    () => &(),
+                },
+            )
+            .finish()
     }
 }
 
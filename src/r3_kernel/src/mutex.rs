@@ -11,7 +11,9 @@ use r3_core::{
 
 use crate::{
     error::{LockMutexPrecheckError, NoAccessError},
-    klock, state, task, timeout,
+    klock, state,
+    syscall::{self, regs, Syscall},
+    task, timeout,
     wait::{WaitPayload, WaitQueue},
     Id, KernelCfg1, KernelTraits, PortThreading, System,
 };
@@ -40,30 +42,36 @@ unsafe impl<Traits: KernelTraits> raw::KernelMutex for System<Traits> {
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_mutex_is_locked(this: MutexId) -> Result<bool, QueryMutexError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-        Ok(mutex_cb.owning_task.get(&*lock).is_some())
+        syscall::call::<Traits, _>(Syscall::MutexIsLocked, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let mutex_cb = unsafe { Self::mutex_cb(this)? };
+            Ok(mutex_cb.owning_task.get(&*lock).is_some())
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_mutex_unlock(this: MutexId) -> Result<(), UnlockMutexError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-
-        unlock_mutex(mutex_cb, lock)
+        syscall::call::<Traits, _>(Syscall::MutexUnlock, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            state::expect_waitable_context::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let mutex_cb = unsafe { Self::mutex_cb(this)? };
+
+            unlock_mutex(mutex_cb, lock)
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_mutex_lock(this: MutexId) -> Result<(), LockMutexError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-
-        lock_mutex(mutex_cb, lock)
+        syscall::call::<Traits, _>(Syscall::MutexLock, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            state::expect_waitable_context::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let mutex_cb = unsafe { Self::mutex_cb(this)? };
+
+            lock_mutex(mutex_cb, lock)
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -71,36 +79,46 @@ unsafe impl<Traits: KernelTraits> raw::KernelMutex for System<Traits> {
         this: MutexId,
         timeout: Duration,
     ) -> Result<(), LockMutexTimeoutError> {
-        let time32 = timeout::time32_from_duration(timeout)?;
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-
-        lock_mutex_timeout(mutex_cb, lock, time32)
+        syscall::call::<Traits, _>(
+            Syscall::MutexLockTimeout,
+            regs![this.get(), syscall::encode_duration(timeout)],
+            || {
+                let time32 = timeout::time32_from_duration(timeout)?;
+                let lock = klock::lock_cpu::<Traits>()?;
+                state::expect_waitable_context::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let mutex_cb = unsafe { Self::mutex_cb(this)? };
+
+                lock_mutex_timeout(mutex_cb, lock, time32)
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_mutex_try_lock(this: MutexId) -> Result<(), TryLockMutexError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_task_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-
-        try_lock_mutex(mutex_cb, lock)
+        syscall::call::<Traits, _>(Syscall::MutexTryLock, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            state::expect_task_context::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let mutex_cb = unsafe { Self::mutex_cb(this)? };
+
+            try_lock_mutex(mutex_cb, lock)
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_mutex_mark_consistent(this: MutexId) -> Result<(), MarkConsistentMutexError> {
-        let mut lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let mutex_cb = unsafe { Self::mutex_cb(this)? };
-
-        if mutex_cb.inconsistent.replace(&mut *lock, false) {
-            Ok(())
-        } else {
-            Err(MarkConsistentMutexError::BadObjectState)
-        }
+        syscall::call::<Traits, _>(Syscall::MutexMarkConsistent, regs![this.get()], || {
+            let mut lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let mutex_cb = unsafe { Self::mutex_cb(this)? };
+
+            if mutex_cb.inconsistent.replace(&mut *lock, false) {
+                Ok(())
+            } else {
+                Err(MarkConsistentMutexError::BadObjectState)
+            }
+        })
     }
 }
 
@@ -11,7 +11,9 @@ use r3_core::{
 
 use crate::{
     error::NoAccessError,
-    klock, state, task, timeout,
+    klock, state,
+    syscall::{self, regs, Syscall},
+    task, timeout,
     wait::{WaitPayload, WaitQueue},
     Id, KernelTraits, Port, System,
 };
@@ -37,19 +39,23 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelSemaphore for Syst
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_semaphore_drain(this: SemaphoreId) -> Result<(), DrainSemaphoreError> {
-        let mut lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
-        semaphore_cb.value.replace(&mut *lock, 0);
-        Ok(())
+        syscall::call::<Traits, _>(Syscall::SemaphoreDrain, regs![this.get()], || {
+            let mut lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+            semaphore_cb.value.replace(&mut *lock, 0);
+            Ok(())
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_semaphore_get(this: SemaphoreId) -> Result<SemaphoreValue, GetSemaphoreError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
-        Ok(semaphore_cb.value.get(&*lock))
+        syscall::call::<Traits, _>(Syscall::SemaphoreGet, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+            Ok(semaphore_cb.value.get(&*lock))
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -57,10 +63,12 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelSemaphore for Syst
         this: SemaphoreId,
         count: SemaphoreValue,
     ) -> Result<(), SignalSemaphoreError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
-        signal(semaphore_cb, lock, count)
+        syscall::call::<Traits, _>(Syscall::SemaphoreSignal, regs![this.get(), count], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+            signal(semaphore_cb, lock, count)
+        })
     }
 
     unsafe fn raw_semaphore_signal_one(this: SemaphoreId) -> Result<(), SignalSemaphoreError> {
@@ -69,12 +77,14 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelSemaphore for Syst
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_semaphore_wait_one(this: SemaphoreId) -> Result<(), WaitSemaphoreError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+        syscall::call::<Traits, _>(Syscall::SemaphoreWaitOne, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            state::expect_waitable_context::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
 
-        wait_one(semaphore_cb, lock)
+            wait_one(semaphore_cb, lock)
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -82,22 +92,30 @@ unsafe impl<Traits: KernelTraits> r3_core::kernel::raw::KernelSemaphore for Syst
         this: SemaphoreId,
         timeout: Duration,
     ) -> Result<(), WaitSemaphoreTimeoutError> {
-        let time32 = timeout::time32_from_duration(timeout)?;
-        let lock = klock::lock_cpu::<Traits>()?;
-        state::expect_waitable_context::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+        syscall::call::<Traits, _>(
+            Syscall::SemaphoreWaitOneTimeout,
+            regs![this.get(), syscall::encode_duration(timeout)],
+            || {
+                let time32 = timeout::time32_from_duration(timeout)?;
+                let lock = klock::lock_cpu::<Traits>()?;
+                state::expect_waitable_context::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
 
-        wait_one_timeout(semaphore_cb, lock, time32)
+                wait_one_timeout(semaphore_cb, lock, time32)
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_semaphore_poll_one(this: SemaphoreId) -> Result<(), PollSemaphoreError> {
-        let lock = klock::lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
+        syscall::call::<Traits, _>(Syscall::SemaphorePollOne, regs![this.get()], || {
+            let lock = klock::lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let semaphore_cb = unsafe { Self::semaphore_cb(this)? };
 
-        poll_one(semaphore_cb, lock)
+            poll_one(semaphore_cb, lock)
+        })
     }
 }
 
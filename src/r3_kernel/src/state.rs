@@ -4,7 +4,42 @@ use r3_core::kernel::{traits::KernelBase as _, BoostPriorityError};
 
 use crate::{error::BadContextError, KernelTraits, System};
 #[cfg(feature = "priority_boost")]
-use crate::{klock, task};
+use crate::{klock, task, CpuState};
+
+/// Call the given closure with the state of the current processor.
+///
+/// In a multiprocessor system, the current task is prevented from migrating to
+/// another processor while the closure is running.
+#[cfg(feature = "priority_boost")]
+#[inline]
+pub(super) fn with_current_cpu_state<Traits: KernelTraits, R>(
+    f: impl FnOnce(&CpuState<Traits>) -> R,
+) -> R {
+    if Traits::NUM_CPUS == 1 {
+        return f(&Traits::state().cpu_states()[0]);
+    }
+
+    // Disable dispatching on the current processor. The spinlock isn't
+    // necessary because we don't touch the kernel state shared with the other
+    // processors.
+    // Safety: The previous state is restored before returning
+    let entered = unsafe { Traits::try_enter_cpu_lock() };
+    let result = f(Traits::state().cpu_state());
+    if entered {
+        // Safety: We entered CPU Lock above
+        unsafe { Traits::leave_cpu_lock() };
+    }
+    result
+}
+
+/// Implements `Kernel::is_priority_boost_active`.
+#[cfg(feature = "priority_boost")]
+#[inline]
+pub(super) fn is_priority_boost_active<Traits: KernelTraits>() -> bool {
+    with_current_cpu_state::<Traits, _>(|cpu_state| {
+        cpu_state.priority_boost.load(Ordering::Relaxed)
+    })
+}
 
 /// If the current context is not a task context, return `Err(BadContext)`.
 pub(super) fn expect_task_context<Traits: KernelTraits>() -> Result<(), BadContextError> {
@@ -33,9 +68,9 @@ pub(super) fn boost_priority<Traits: KernelTraits>() -> Result<(), BoostPriority
     {
         Err(BoostPriorityError::BadContext)
     } else {
-        Traits::state()
-            .priority_boost
-            .store(true, Ordering::Relaxed);
+        with_current_cpu_state::<Traits, _>(|cpu_state| {
+            cpu_state.priority_boost.store(true, Ordering::Relaxed)
+        });
         Ok(())
     }
 }
@@ -50,6 +85,7 @@ pub(super) fn unboost_priority<Traits: KernelTraits>() -> Result<(), BoostPriori
         // `drop_in_place(&mut lock)` doesn't get emitted twice
         let lock = klock::lock_cpu()?;
         Traits::state()
+            .cpu_state()
             .priority_boost
             .store(false, Ordering::Relaxed);
 
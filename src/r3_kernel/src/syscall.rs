@@ -0,0 +1,585 @@
+//! Numbered kernel service calls
+//!
+//! A port that runs tasks with reduced privileges can't let them call kernel
+//! services directly. Instead, it provides a gate through
+//! [`PortThreading::syscall_gate`], and the kernel encodes every kernel service
+//! called in such a context as a kernel service number and a few words of
+//! scalar arguments. The gate passes them to [`dispatch`] in a privileged
+//! context, which validates the number and the arguments, executes the kernel
+//! service, and encodes the result in the same words.
+//!
+//! No pointers are passed through a gate. Object IDs are checked against the
+//! object pools before they reach the kernel services, so an invalid ID is
+//! reported as `NoAccess` instead of causing an undefined behavior.
+//!
+//! [`PortThreading::syscall_gate`]: crate::PortThreading::syscall_gate
+use core::mem::{size_of, transmute_copy};
+use r3_core::{
+    kernel::{
+        raw::{
+            KernelAdjustTime, KernelBase, KernelEventGroup, KernelInterruptLine, KernelMutex,
+            KernelSemaphore, KernelTaskSetPriority, KernelTimer,
+        },
+        EventGroupWaitFlags, ResultCode, TaskRef,
+    },
+    time::{Duration, Time},
+};
+
+use crate::{Id, KernelCfg2, KernelTraits, System, TaskInfo, TaskState, WaitTarget};
+
+/// The number of words used to pass arguments to and return a result from a
+/// kernel service.
+pub const NUM_REGS: usize = 4;
+
+/// The words passed to [`dispatch`]. They hold the arguments on entry and the
+/// result on return.
+pub type Regs = [usize; NUM_REGS];
+
+/// A gate returned by [`PortThreading::syscall_gate`]. It must call
+/// [`dispatch`] with the same parameters in a privileged context on behalf of
+/// the current task.
+///
+/// [`PortThreading::syscall_gate`]: crate::PortThreading::syscall_gate
+pub type Gate = unsafe fn(num: usize, regs: &mut Regs);
+
+/// Kernel service numbers. The discriminants are contiguous from zero and only
+/// meaningful between the kernel and the port of the same build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub(crate) enum Syscall {
+    AcquireCpuLock,
+    ReleaseCpuLock,
+    HasCpuLock,
+    UnboostPriority,
+    IsPriorityBoostActive,
+    IsTaskContext,
+    IsInterruptContext,
+    IsBootComplete,
+    SetTime,
+    ExitTask,
+    Park,
+    ParkTimeout,
+    Sleep,
+    TaskCurrent,
+    TaskActivate,
+    TaskInterrupt,
+    TaskUnparkExact,
+    TaskPriority,
+    TaskEffectivePriority,
+    TaskSetPriority,
+    TaskInfo,
+    BoostPriority,
+    Time,
+    AdjustTime,
+    EventGroupSet,
+    EventGroupClear,
+    EventGroupGet,
+    EventGroupWait,
+    EventGroupWaitTimeout,
+    EventGroupPoll,
+    SemaphoreDrain,
+    SemaphoreGet,
+    SemaphoreSignal,
+    SemaphoreWaitOne,
+    SemaphoreWaitOneTimeout,
+    SemaphorePollOne,
+    MutexIsLocked,
+    MutexUnlock,
+    MutexLock,
+    MutexLockTimeout,
+    MutexTryLock,
+    MutexMarkConsistent,
+    TimerStart,
+    TimerStop,
+    TimerSetDelay,
+    TimerSetPeriod,
+    InterruptLineSetPriority,
+    InterruptLineEnable,
+    InterruptLineDisable,
+    InterruptLinePend,
+    InterruptLineClear,
+    InterruptLineIsPending,
+}
+
+impl Syscall {
+    #[inline]
+    fn from_usize(num: usize) -> Option<Self> {
+        if num <= Self::InterruptLineIsPending as usize {
+            // Safety: The discriminants are contiguous from zero
+            Some(unsafe { core::mem::transmute::<usize, Self>(num) })
+        } else {
+            None
+        }
+    }
+}
+
+/// Construct [`Regs`] from up to [`NUM_REGS`] words.
+pub(crate) macro regs($($x:expr),* $(,)?) {{
+    #[allow(unused_mut)]
+    let mut regs: Regs = [0; NUM_REGS];
+    let mut _i = 0;
+    $( regs[_i] = $x; _i += 1; )*
+    regs
+}}
+
+/// Execute a kernel service. If the current context must go through a gate,
+/// `num` and `regs` are passed to it, and the result is decoded from the
+/// returned words. Otherwise, `f` is called directly.
+#[inline(always)]
+pub(crate) fn call<Traits: KernelTraits, R: Ret>(
+    num: Syscall,
+    regs: Regs,
+    f: impl FnOnce() -> R,
+) -> R {
+    if let Some(gate) = Traits::syscall_gate() {
+        let mut regs = regs;
+        // Safety: `gate` calls `dispatch`, which encodes a value of type `R`
+        // for `num`
+        unsafe {
+            gate(num as usize, &mut regs);
+            R::decode(&regs)
+        }
+    } else {
+        f()
+    }
+}
+
+/// Execute the kernel service `num` on behalf of the current task. `regs`
+/// holds the arguments on entry and receives the result. A port calls this
+/// from its gate (see [`PortThreading::syscall_gate`]).
+///
+/// `num` and `regs` may be any values. An unknown kernel service number is
+/// rejected with `NotSupported`, and an invalid object ID with `NoAccess`.
+///
+/// # Safety
+///
+/// This function must be called in a privileged context on behalf of the
+/// current task, where [`PortThreading::syscall_gate`] returns `None`. The
+/// port must treat the call as if the task called the kernel service itself,
+/// e.g., the call may block the task or never return.
+///
+/// [`PortThreading::syscall_gate`]: crate::PortThreading::syscall_gate
+pub unsafe extern "C" fn dispatch<Traits: KernelTraits>(num: usize, regs: &mut Regs) {
+    let a = *regs;
+    // Safety: Upheld by the caller
+    *regs = unsafe { dispatch_inner::<Traits>(num, a) };
+}
+
+/// The body of [`dispatch`].
+///
+/// # Safety
+///
+/// See [`dispatch`].
+unsafe fn dispatch_inner<Traits: KernelTraits>(num: usize, a: Regs) -> Regs {
+    type Sys<Traits> = System<Traits>;
+
+    let Some(num) = Syscall::from_usize(num) else {
+        return encode_code(ResultCode::NotSupported);
+    };
+
+    // Validate an object ID. `get_cb` takes a zero-based index.
+    macro_rules! id {
+        ($get_cb:ident, $x:expr) => {{
+            let x: usize = $x;
+            if Traits::$get_cb(x.wrapping_sub(1)).is_none() {
+                return encode_code(ResultCode::NoAccess);
+            }
+            // `x - 1` didn't wrap around, so `x` is non-zero
+            Id::new(x).unwrap()
+        }};
+    }
+
+    // Safety: Object IDs are validated by `id!`, so the kernel services
+    // don't reach `bad_id`. The other preconditions of the raw kernel
+    // services only concern the calling task itself, which made the request.
+    unsafe {
+        match num {
+            Syscall::AcquireCpuLock => Sys::<Traits>::raw_acquire_cpu_lock().encode(),
+            Syscall::ReleaseCpuLock => Sys::<Traits>::raw_release_cpu_lock().encode(),
+            Syscall::HasCpuLock => Sys::<Traits>::raw_has_cpu_lock().encode(),
+            Syscall::UnboostPriority => Sys::<Traits>::raw_unboost_priority().encode(),
+            Syscall::IsPriorityBoostActive => {
+                Sys::<Traits>::raw_is_priority_boost_active().encode()
+            }
+            Syscall::IsTaskContext => Sys::<Traits>::raw_is_task_context().encode(),
+            Syscall::IsInterruptContext => Sys::<Traits>::raw_is_interrupt_context().encode(),
+            Syscall::IsBootComplete => Sys::<Traits>::raw_is_boot_complete().encode(),
+            Syscall::SetTime => Sys::<Traits>::raw_set_time(decode_time(a[0], a[1])).encode(),
+            Syscall::ExitTask => Sys::<Traits>::raw_exit_task().encode(),
+            Syscall::Park => Sys::<Traits>::raw_park().encode(),
+            Syscall::ParkTimeout => Sys::<Traits>::raw_park_timeout(decode_duration(a[0])).encode(),
+            Syscall::Sleep => Sys::<Traits>::raw_sleep(decode_duration(a[0])).encode(),
+            Syscall::TaskCurrent => Sys::<Traits>::raw_task_current().encode(),
+            Syscall::TaskActivate => {
+                Sys::<Traits>::raw_task_activate(id!(get_task_cb, a[0])).encode()
+            }
+            Syscall::TaskInterrupt => {
+                Sys::<Traits>::raw_task_interrupt(id!(get_task_cb, a[0])).encode()
+            }
+            Syscall::TaskUnparkExact => {
+                Sys::<Traits>::raw_task_unpark_exact(id!(get_task_cb, a[0])).encode()
+            }
+            Syscall::TaskPriority => {
+                Sys::<Traits>::raw_task_priority(id!(get_task_cb, a[0])).encode()
+            }
+            Syscall::TaskEffectivePriority => {
+                Sys::<Traits>::raw_task_effective_priority(id!(get_task_cb, a[0])).encode()
+            }
+            Syscall::TaskSetPriority => {
+                Sys::<Traits>::raw_task_set_priority(id!(get_task_cb, a[0]), a[1]).encode()
+            }
+            Syscall::TaskInfo => {
+                Sys::<Traits>::task_info(TaskRef::from_id(id!(get_task_cb, a[0]))).encode()
+            }
+            #[cfg(feature = "priority_boost")]
+            Syscall::BoostPriority => {
+                <Sys<Traits> as r3_core::kernel::raw::KernelBoostPriority>::raw_boost_priority()
+                    .encode()
+            }
+            #[cfg(feature = "system_time")]
+            Syscall::Time => <Sys<Traits> as r3_core::kernel::raw::KernelTime>::raw_time().encode(),
+            Syscall::AdjustTime => Sys::<Traits>::raw_adjust_time(decode_duration(a[0])).encode(),
+            Syscall::EventGroupSet => {
+                Sys::<Traits>::raw_event_group_set(id!(get_event_group_cb, a[0]), a[1] as u32)
+                    .encode()
+            }
+            Syscall::EventGroupClear => {
+                Sys::<Traits>::raw_event_group_clear(id!(get_event_group_cb, a[0]), a[1] as u32)
+                    .encode()
+            }
+            Syscall::EventGroupGet => {
+                Sys::<Traits>::raw_event_group_get(id!(get_event_group_cb, a[0])).encode()
+            }
+            Syscall::EventGroupWait => Sys::<Traits>::raw_event_group_wait(
+                id!(get_event_group_cb, a[0]),
+                a[1] as u32,
+                decode_wait_flags(a[2]),
+            )
+            .encode(),
+            Syscall::EventGroupWaitTimeout => Sys::<Traits>::raw_event_group_wait_timeout(
+                id!(get_event_group_cb, a[0]),
+                a[1] as u32,
+                decode_wait_flags(a[2]),
+                decode_duration(a[3]),
+            )
+            .encode(),
+            Syscall::EventGroupPoll => Sys::<Traits>::raw_event_group_poll(
+                id!(get_event_group_cb, a[0]),
+                a[1] as u32,
+                decode_wait_flags(a[2]),
+            )
+            .encode(),
+            Syscall::SemaphoreDrain => {
+                Sys::<Traits>::raw_semaphore_drain(id!(get_semaphore_cb, a[0])).encode()
+            }
+            Syscall::SemaphoreGet => {
+                Sys::<Traits>::raw_semaphore_get(id!(get_semaphore_cb, a[0])).encode()
+            }
+            Syscall::SemaphoreSignal => {
+                Sys::<Traits>::raw_semaphore_signal(id!(get_semaphore_cb, a[0]), a[1]).encode()
+            }
+            Syscall::SemaphoreWaitOne => {
+                Sys::<Traits>::raw_semaphore_wait_one(id!(get_semaphore_cb, a[0])).encode()
+            }
+            Syscall::SemaphoreWaitOneTimeout => Sys::<Traits>::raw_semaphore_wait_one_timeout(
+                id!(get_semaphore_cb, a[0]),
+                decode_duration(a[1]),
+            )
+            .encode(),
+            Syscall::SemaphorePollOne => {
+                Sys::<Traits>::raw_semaphore_poll_one(id!(get_semaphore_cb, a[0])).encode()
+            }
+            Syscall::MutexIsLocked => {
+                Sys::<Traits>::raw_mutex_is_locked(id!(get_mutex_cb, a[0])).encode()
+            }
+            Syscall::MutexUnlock => {
+                Sys::<Traits>::raw_mutex_unlock(id!(get_mutex_cb, a[0])).encode()
+            }
+            Syscall::MutexLock => Sys::<Traits>::raw_mutex_lock(id!(get_mutex_cb, a[0])).encode(),
+            Syscall::MutexLockTimeout => Sys::<Traits>::raw_mutex_lock_timeout(
+                id!(get_mutex_cb, a[0]),
+                decode_duration(a[1]),
+            )
+            .encode(),
+            Syscall::MutexTryLock => {
+                Sys::<Traits>::raw_mutex_try_lock(id!(get_mutex_cb, a[0])).encode()
+            }
+            Syscall::MutexMarkConsistent => {
+                Sys::<Traits>::raw_mutex_mark_consistent(id!(get_mutex_cb, a[0])).encode()
+            }
+            Syscall::TimerStart => Sys::<Traits>::raw_timer_start(id!(get_timer_cb, a[0])).encode(),
+            Syscall::TimerStop => Sys::<Traits>::raw_timer_stop(id!(get_timer_cb, a[0])).encode(),
+            Syscall::TimerSetDelay => Sys::<Traits>::raw_timer_set_delay(
+                id!(get_timer_cb, a[0]),
+                decode_optional_duration(a[1], a[2]),
+            )
+            .encode(),
+            Syscall::TimerSetPeriod => Sys::<Traits>::raw_timer_set_period(
+                id!(get_timer_cb, a[0]),
+                decode_optional_duration(a[1], a[2]),
+            )
+            .encode(),
+            // The port validates interrupt line numbers and priorities
+            Syscall::InterruptLineSetPriority => {
+                Sys::<Traits>::raw_interrupt_line_set_priority(a[0], a[1] as i16).encode()
+            }
+            Syscall::InterruptLineEnable => Sys::<Traits>::raw_interrupt_line_enable(a[0]).encode(),
+            Syscall::InterruptLineDisable => {
+                Sys::<Traits>::raw_interrupt_line_disable(a[0]).encode()
+            }
+            Syscall::InterruptLinePend => Sys::<Traits>::raw_interrupt_line_pend(a[0]).encode(),
+            Syscall::InterruptLineClear => Sys::<Traits>::raw_interrupt_line_clear(a[0]).encode(),
+            Syscall::InterruptLineIsPending => {
+                Sys::<Traits>::raw_interrupt_line_is_pending(a[0]).encode()
+            }
+            #[allow(unreachable_patterns)]
+            _ => encode_code(ResultCode::NotSupported),
+        }
+    }
+}
+
+/// Encode a result code, which is negative for an error.
+#[inline]
+fn encode_code(code: ResultCode) -> Regs {
+    regs![code as i8 as isize as usize]
+}
+
+#[inline]
+pub(crate) fn encode_duration(x: Duration) -> usize {
+    x.as_micros() as usize
+}
+
+#[inline]
+fn decode_duration(x: usize) -> Duration {
+    Duration::from_micros(x as i32)
+}
+
+/// Encode `Option<Duration>` in two words.
+#[inline]
+pub(crate) fn encode_optional_duration(x: Option<Duration>) -> [usize; 2] {
+    match x {
+        Some(x) => [1, encode_duration(x)],
+        None => [0, 0],
+    }
+}
+
+#[inline]
+fn decode_optional_duration(tag: usize, x: usize) -> Option<Duration> {
+    (tag != 0).then(|| decode_duration(x))
+}
+
+/// Encode `Time` in two words, in case `usize` is 32 bits wide.
+#[inline]
+pub(crate) fn encode_time(x: Time) -> [usize; 2] {
+    let x = x.as_micros();
+    [x as u32 as usize, (x >> 32) as u32 as usize]
+}
+
+#[inline]
+fn decode_time(lo: usize, hi: usize) -> Time {
+    Time::from_micros(lo as u32 as u64 | (hi as u32 as u64) << 32)
+}
+
+/// Decode [`EventGroupWaitFlags`], ignoring undefined bits.
+#[inline]
+fn decode_wait_flags(x: usize) -> EventGroupWaitFlags {
+    EventGroupWaitFlags::from_bits_truncate(x as u8)
+}
+
+/// The result type of a kernel service.
+pub(crate) trait Ret: Sized {
+    fn encode(self) -> Regs;
+
+    /// # Safety
+    ///
+    /// `regs` must have been produced by [`Self::encode`].
+    unsafe fn decode(regs: &Regs) -> Self;
+}
+
+impl Ret for bool {
+    #[inline]
+    fn encode(self) -> Regs {
+        regs![self as usize]
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        regs[0] != 0
+    }
+}
+
+/// A successful result is indicated by a non-negative `regs[0]`. An error is
+/// indicated by a negative `regs[0]` holding the result code.
+impl<T: Value, E: Into<ResultCode>> Ret for Result<T, E> {
+    #[inline]
+    fn encode(self) -> Regs {
+        match self {
+            Ok(x) => {
+                let mut regs = regs![];
+                x.encode(&mut regs);
+                regs
+            }
+            Err(e) => encode_code(e.into()),
+        }
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        let code = regs[0] as isize;
+        if code >= 0 {
+            // Safety: Upheld by the caller
+            Ok(unsafe { T::decode(regs) })
+        } else {
+            // Every error type shares the discriminants of `ResultCode`, and
+            // `code` was produced from `E` by `encode`
+            assert!(size_of::<E>() == 1);
+            // Safety: See above
+            Err(unsafe { transmute_copy::<i8, E>(&(code as i8)) })
+        }
+    }
+}
+
+/// The successful result of a kernel service. Encoded in `regs`, leaving
+/// `regs[0]` non-negative.
+pub(crate) trait Value: Sized {
+    fn encode(self, regs: &mut Regs);
+
+    /// # Safety
+    ///
+    /// `regs` must have been produced by [`Self::encode`].
+    unsafe fn decode(regs: &Regs) -> Self;
+}
+
+impl Value for () {
+    #[inline]
+    fn encode(self, _: &mut Regs) {}
+
+    #[inline]
+    unsafe fn decode(_: &Regs) -> Self {}
+}
+
+impl Value for ! {
+    #[inline]
+    fn encode(self, _: &mut Regs) {
+        self
+    }
+
+    #[inline]
+    unsafe fn decode(_: &Regs) -> Self {
+        // Safety: A value of type `!` is never encoded
+        unsafe { core::hint::unreachable_unchecked() }
+    }
+}
+
+impl Value for bool {
+    #[inline]
+    fn encode(self, regs: &mut Regs) {
+        regs[1] = self as usize;
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        regs[1] != 0
+    }
+}
+
+impl Value for usize {
+    #[inline]
+    fn encode(self, regs: &mut Regs) {
+        regs[1] = self;
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        regs[1]
+    }
+}
+
+impl Value for u32 {
+    #[inline]
+    fn encode(self, regs: &mut Regs) {
+        regs[1] = self as usize;
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        regs[1] as u32
+    }
+}
+
+impl Value for Id {
+    #[inline]
+    fn encode(self, regs: &mut Regs) {
+        regs[1] = self.get();
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        // Safety: `regs[1]` was produced from an `Id`
+        unsafe { Id::new_unchecked(regs[1]) }
+    }
+}
+
+impl Value for Time {
+    #[inline]
+    fn encode(self, regs: &mut Regs) {
+        [regs[1], regs[2]] = encode_time(self);
+    }
+
+    #[inline]
+    unsafe fn decode(regs: &Regs) -> Self {
+        decode_time(regs[1], regs[2])
+    }
+}
+
+/// `regs[0]` holds the state and the kind of the wait target, and `regs[3]`
+/// holds the wait target's object ID.
+impl Value for TaskInfo {
+    fn encode(self, regs: &mut Regs) {
+        let state = match self.state {
+            TaskState::Dormant => 0,
+            TaskState::Ready => 1,
+            TaskState::Running => 2,
+            TaskState::Waiting => 3,
+        };
+        let (wait_kind, wait_id) = match self.wait_target {
+            None => (0, 0),
+            Some(WaitTarget::EventGroup(id)) => (1, id.get()),
+            Some(WaitTarget::Semaphore(id)) => (2, id.get()),
+            Some(WaitTarget::Mutex(id)) => (3, id.get()),
+            Some(WaitTarget::Park) => (4, 0),
+            Some(WaitTarget::Sleep) => (5, 0),
+        };
+        *regs = [
+            state | wait_kind << 4,
+            self.base_priority,
+            self.effective_priority,
+            wait_id,
+        ];
+    }
+
+    unsafe fn decode(regs: &Regs) -> Self {
+        let state = match regs[0] & 0xf {
+            0 => TaskState::Dormant,
+            1 => TaskState::Ready,
+            2 => TaskState::Running,
+            _ => TaskState::Waiting,
+        };
+        // Safety: The ID was produced from an `Id` if the kind uses it
+        let wait_id = || unsafe { Id::new_unchecked(regs[3]) };
+        let wait_target = match regs[0] >> 4 {
+            0 => None,
+            1 => Some(WaitTarget::EventGroup(wait_id())),
+            2 => Some(WaitTarget::Semaphore(wait_id())),
+            3 => Some(WaitTarget::Mutex(wait_id())),
+            4 => Some(WaitTarget::Park),
+            _ => Some(WaitTarget::Sleep),
+        };
+        TaskInfo {
+            state,
+            base_priority: regs[1],
+            effective_priority: regs[2],
+            wait_target,
+        }
+    }
+}
@@ -6,17 +6,21 @@ use num_traits::ToPrimitive;
 use r3_core::{
     closure::ClosureEnv,
     kernel::{
-        raw::KernelBase, ActivateTaskError, ExitTaskError, GetCurrentTaskError,
-        GetTaskPriorityError, Hunk, InterruptTaskError, ParkError, ParkTimeoutError,
-        SetTaskPriorityError, SleepError, UnparkExactError, WaitTimeoutError,
+        raw::KernelBase,
+        task::{CpuAffinity, ExtendedContext, MemoryProtection, TaskHandle},
+        ActivateTaskError, ExitTaskError, GetCurrentTaskError, GetTaskPriorityError, Hunk,
+        InterruptTaskError, ParkError, ParkTimeoutError, SetTaskPriorityError, SleepError, TaskRef,
+        UnparkExactError, WaitTimeoutError,
     },
     time::Duration,
     utils::Init,
 };
 
 use crate::{
-    error::NoAccessError, klock, mutex, state, timeout, wait, Id, KernelCfg1, KernelTraits,
-    PortThreading, System,
+    error::{define_error, NoAccessError},
+    klock, mutex, state,
+    syscall::{self, regs, Syscall},
+    timeout, wait, Id, KernelCfg1, KernelTraits, PortThreading, System,
 };
 
 #[doc(hidden)]
@@ -127,6 +131,106 @@ impl<Traits: KernelTraits> System<Traits> {
     }
 }
 
+define_error! {
+    mod task_info_error {}
+    /// Error type for [`System::task_info`].
+    pub enum TaskInfoError {
+        /// The task ID doesn't refer to any task.
+        NoAccess,
+        /// CPU Lock is active.
+        BadContext,
+    }
+}
+
+/// Task introspection
+impl<Traits: KernelTraits> System<Traits> {
+    /// Get a snapshot of the state of the specified task.
+    ///
+    /// This is an extension specific to this kernel and is mainly intended for
+    /// diagnostic purposes, such as [`watchdog`][1].
+    ///
+    /// [1]: crate::watchdog
+    #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
+    pub fn task_info(task: TaskRef<'_, Self>) -> Result<TaskInfo, TaskInfoError> {
+        syscall::call::<Traits, _>(Syscall::TaskInfo, regs![task.id().get()], || {
+            let mut lock = klock::lock_cpu::<Traits>()?;
+            Self::task_info_inner(lock.borrow_mut(), task)
+        })
+    }
+
+    /// [`task_info`](Self::task_info) without acquiring CPU Lock. This allows
+    /// a debugger-like tool to examine a system that is stuck in a CPU Lock
+    /// state.
+    ///
+    /// The returned information might be inconsistent if the system was
+    /// stopped in the middle of a kernel operation.
+    ///
+    /// # Safety
+    ///
+    /// The kernel state must not be accessed by anyone else during the call.
+    /// In other words, every processor must be stopped, e.g., by a debugger or
+    /// by a simulator running on the host.
+    pub unsafe fn task_info_unchecked(task: TaskRef<'_, Self>) -> Result<TaskInfo, TaskInfoError> {
+        // Safety: No one else accesses the kernel state, so we can act as if
+        //         we were holding CPU Lock (upheld by the caller)
+        let mut token = unsafe { klock::CpuLockToken::<Traits>::new_unchecked() };
+        Self::task_info_inner(token.borrow_mut(), task)
+    }
+
+    pub(crate) fn task_info_inner(
+        mut lock: klock::CpuLockTokenRefMut<'_, Traits>,
+        task: TaskRef<'_, Self>,
+    ) -> Result<TaskInfo, TaskInfoError> {
+        // Unlike other services, return `NoAccess` for an invalid ID instead
+        // of treating it as a violation of object safety (`bad_id`). This
+        // function is meant to be usable by a debugger-like tool enumerating
+        // task IDs.
+        let task_cb = Traits::get_task_cb(task.id().get() - 1).ok_or(TaskInfoError::NoAccess)?;
+
+        let state = match *task_cb.st.read(&*lock) {
+            TaskSt::Dormant => TaskState::Dormant,
+            TaskSt::Ready | TaskSt::PendingActivation => TaskState::Ready,
+            TaskSt::Running => TaskState::Running,
+            TaskSt::Waiting => TaskState::Waiting,
+        };
+
+        Ok(TaskInfo {
+            state,
+            base_priority: task_cb.base_priority.read(&*lock).to_usize().unwrap(),
+            effective_priority: task_cb.effective_priority.read(&*lock).to_usize().unwrap(),
+            wait_target: wait::wait_target(lock.borrow_mut(), task_cb),
+        })
+    }
+}
+
+/// A snapshot of the state of a task, returned by [`System::task_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TaskInfo {
+    /// The task's state.
+    pub state: TaskState,
+    /// The task's base priority. Unspecified if the task is in the Dormant
+    /// state.
+    pub base_priority: usize,
+    /// The task's effective priority, which may be elevated by a mutex locking
+    /// protocol. Unspecified if the task is in the Dormant state.
+    pub effective_priority: usize,
+    /// The condition the task is waiting for. `None` unless the task is in the
+    /// Waiting state.
+    pub wait_target: Option<wait::WaitTarget>,
+}
+
+/// The state of a task. See [`r3_core::kernel::Task`] for the descriptions of
+/// the task states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TaskState {
+    Dormant,
+    Ready,
+    Running,
+    Waiting,
+}
+
 // FIXME: Since we don't want to say "task stack is guaranteed to be a hunk" in
 //        a public interface, we should rename this type
 /// [`Hunk`] for a task stack.
@@ -341,6 +445,17 @@ pub struct TaskAttr<
 
     /// The initial base priority of the task.
     pub priority: TaskPriority,
+
+    /// The memory access permissions of the task. A port may enforce these
+    /// permissions if it supports memory protection.
+    pub memory_protection: MemoryProtection<System<Traits>>,
+
+    /// Indicates whether the task uses the processor's extended context. A
+    /// port may reserve space for saving it in the task's stack.
+    pub extended_context: ExtendedContext,
+
+    /// The set of processors the task can run on.
+    pub affinity: CpuAffinity,
 }
 
 impl<Traits: KernelTraits, TaskPriority: fmt::Debug> fmt::Debug for TaskAttr<Traits, TaskPriority> {
@@ -350,6 +465,9 @@ impl<Traits: KernelTraits, TaskPriority: fmt::Debug> fmt::Debug for TaskAttr<Tra
             .field("entry_param", &self.entry_param)
             .field("stack", &self.stack)
             .field("priority", &self.priority)
+            .field("memory_protection", &self.memory_protection)
+            .field("extended_context", &self.extended_context)
+            .field("affinity", &self.affinity)
             .finish()
     }
 }
@@ -390,7 +508,7 @@ pub(super) unsafe fn exit_current_task<Traits: KernelTraits>() -> Result<!, Exit
     //       kernel-owned CPU Lock.
     let mut lock = unsafe {
         if !Traits::is_cpu_lock_active() {
-            Traits::enter_cpu_lock();
+            klock::enter_cpu_lock::<Traits>();
         }
         klock::assume_cpu_lock::<Traits>()
     };
@@ -399,6 +517,7 @@ pub(super) unsafe fn exit_current_task<Traits: KernelTraits>() -> Result<!, Exit
     {
         // If Priority Boost is active, deactivate it.
         Traits::state()
+            .cpu_state()
             .priority_boost
             .store(false, Ordering::Release);
     }
@@ -414,8 +533,14 @@ pub(super) unsafe fn exit_current_task<Traits: KernelTraits>() -> Result<!, Exit
     running_task.st.replace(&mut *lock, TaskSt::Dormant);
 
     // Erase `running_task`
-    Traits::state().running_task.replace(&mut *lock, None);
+    Traits::state()
+        .cpu_state()
+        .running_task
+        .replace(&mut *lock, None);
 
+    // In a multiprocessor system, the spinlock is handed over to the next call
+    // to `choose_running_task` on this processor so that the other processors
+    // don't reactivate the task while the port is still using its stack.
     core::mem::forget(lock);
 
     // Safety: (1) The user of `exit_task` acknowledges that all preexisting
@@ -501,11 +626,18 @@ pub(super) unsafe fn make_ready<Traits: KernelTraits>(
 /// Relinquish CPU Lock. After that, if there's a higher-priority task than
 /// `running_task`, call `Port::yield_cpu`.
 ///
+/// In a multiprocessor system, this also requests the other processors to
+/// reschedule if they have such tasks.
+///
 /// System services that transition a task into the Ready state should call
 /// this before returning to the caller.
 pub(super) fn unlock_cpu_and_check_preemption<Traits: KernelTraits>(
     mut lock: klock::CpuLockGuard<Traits>,
 ) {
+    if Traits::NUM_CPUS > 1 {
+        preempt_other_cpus(lock.borrow_mut());
+    }
+
     // If Priority Boost is active, treat the currently running task as the
     // highest-priority task.
     if System::<Traits>::raw_is_priority_boost_active() {
@@ -520,24 +652,16 @@ pub(super) fn unlock_cpu_and_check_preemption<Traits: KernelTraits>(
         return;
     }
 
-    let prev_task_priority =
-        if let Some(running_task) = Traits::state().running_task(lock.borrow_mut()) {
-            if *running_task.st.read(&*lock) == TaskSt::Running {
-                running_task
-                    .effective_priority
-                    .read(&*lock)
-                    .to_usize()
-                    .unwrap()
-            } else {
-                usize::MAX
-            }
-        } else {
-            usize::MAX
-        };
+    let running_task = Traits::state().running_task(lock.borrow_mut());
+    let prev_task_priority = running_task_priority(lock.borrow_mut(), running_task);
 
     let has_preempting_task = Traits::state()
         .task_ready_queue
-        .has_ready_task_in_priority_range(lock.borrow_mut().into(), ..prev_task_priority);
+        .has_ready_task_in_priority_range(
+            lock.borrow_mut().into(),
+            Traits::current_cpu(),
+            ..prev_task_priority,
+        );
 
     // Relinquish CPU Lock
     drop(lock);
@@ -548,6 +672,73 @@ pub(super) fn unlock_cpu_and_check_preemption<Traits: KernelTraits>(
     }
 }
 
+/// Request each processor other than the current one to reschedule if there's
+/// a task that should preempt its running task.
+fn preempt_other_cpus<Traits: KernelTraits>(mut lock: klock::CpuLockTokenRefMut<'_, Traits>) {
+    let current_cpu = Traits::current_cpu();
+    for (cpu, cpu_state) in Traits::state().cpu_states().iter().enumerate() {
+        if cpu == current_cpu {
+            continue;
+        }
+
+        // If Priority Boost is active on the processor, treat its running task
+        // as the highest-priority task.
+        #[cfg(feature = "priority_boost")]
+        if cpu_state.priority_boost.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let running_task = *cpu_state.running_task.read(&*lock);
+        let prev_task_priority = running_task_priority(lock.borrow_mut(), running_task);
+
+        if Traits::state()
+            .task_ready_queue
+            .has_ready_task_in_priority_range(lock.borrow_mut().into(), cpu, ..prev_task_priority)
+        {
+            // Safety: CPU Lock active, `cpu` is not the current processor
+            unsafe { Traits::yield_cpu_on(cpu) };
+        }
+    }
+}
+
+/// Get the effective priority of `running_task` if it's in the Running state.
+/// Otherwise, return `usize::MAX`.
+fn running_task_priority<Traits: KernelTraits>(
+    lock: klock::CpuLockTokenRefMut<'_, Traits>,
+    running_task: Option<&'static TaskCb<Traits>>,
+) -> usize {
+    if let Some(running_task) = running_task {
+        if *running_task.st.read(&*lock) == TaskSt::Running {
+            return running_task
+                .effective_priority
+                .read(&*lock)
+                .to_usize()
+                .unwrap();
+        }
+    }
+    usize::MAX
+}
+
+/// Get a flag indicating whether `task_cb`, which is in the Ready state, can be
+/// chosen to run by the processor `cpu`. That's the case if `cpu` is allowed
+/// by the task's affinity and the task is not the running task of another
+/// processor (which can happen momentarily after the task is woken up before
+/// the processor yields).
+pub(super) fn is_task_schedulable_on<Traits: KernelTraits>(
+    lock: klock::CpuLockTokenRefMut<'_, Traits>,
+    task_cb: &'static TaskCb<Traits>,
+    cpu: usize,
+) -> bool {
+    task_cb.attr.affinity.contains(cpu)
+        && Traits::state()
+            .cpu_states()
+            .iter()
+            .enumerate()
+            .all(|(i, cpu_state)| {
+                i == cpu || ptr_from_option_ref(*cpu_state.running_task.read(&*lock)) != task_cb
+            })
+}
+
 /// Implements `PortToKernel::choose_running_task`.
 #[inline]
 pub(super) fn choose_next_running_task<Traits: KernelTraits>(
@@ -570,19 +761,7 @@ pub(super) fn choose_next_running_task<Traits: KernelTraits>(
 
     // The priority of `running_task`
     let prev_running_task = Traits::state().running_task(lock.borrow_mut());
-    let prev_task_priority = if let Some(running_task) = prev_running_task {
-        if *running_task.st.read(&*lock) == TaskSt::Running {
-            running_task
-                .effective_priority
-                .read(&*lock)
-                .to_usize()
-                .unwrap()
-        } else {
-            usize::MAX // (2) see the discussion below
-        }
-    } else {
-        usize::MAX // (1) see the discussion below
-    };
+    let prev_task_priority = running_task_priority(lock.borrow_mut(), prev_running_task);
 
     // Decide the next task to run
     //
@@ -593,9 +772,11 @@ pub(super) fn choose_next_running_task<Traits: KernelTraits>(
     // schedulable task or not. That is, even if there was not such a task, we
     // would still want to assign `None` to `running_task`. Therefore,
     // `pop_front_task` is designed to return `SwitchTo(None)` in this case.
-    let decision = Traits::state()
-        .task_ready_queue
-        .pop_front_task(lock.borrow_mut().into(), prev_task_priority);
+    let decision = Traits::state().task_ready_queue.pop_front_task(
+        lock.borrow_mut().into(),
+        Traits::current_cpu(),
+        prev_task_priority,
+    );
 
     let next_running_task = match decision {
         readyqueue::ScheduleDecision::SwitchTo(task) => task,
@@ -621,6 +802,7 @@ pub(super) fn choose_next_running_task<Traits: KernelTraits>(
     }
 
     // `prev_running_task` now loses the control of the processor.
+    let mut released_ready_task = false;
     if let Some(running_task) = prev_running_task {
         debug_assert_ne!(
             ptr_from_option_ref(prev_running_task),
@@ -631,20 +813,29 @@ pub(super) fn choose_next_running_task<Traits: KernelTraits>(
                 // Transition `prev_running_task` into Ready state.
                 // Safety: The previous state is Running, so this is safe
                 unsafe { make_ready(lock.borrow_mut(), running_task) };
+                released_ready_task = true;
             }
             TaskSt::Waiting => {
                 // `prev_running_task` stays in Waiting state.
             }
             TaskSt::Ready => {
                 // `prev_running_task` stays in Ready state.
+                released_ready_task = true;
             }
             _ => unreachable!(),
         }
     }
 
     Traits::state()
+        .cpu_state()
         .running_task
         .replace(&mut *lock, next_running_task);
+
+    // In a multiprocessor system, `prev_running_task` is now available to the
+    // other processors.
+    if Traits::NUM_CPUS > 1 && released_ready_task {
+        preempt_other_cpus(lock);
+    }
 }
 
 #[inline]
@@ -679,13 +870,13 @@ pub(super) fn wait_until_woken_up<Traits: KernelTraits>(
         //         (2) We currently have CPU Lock.
         //         (3) We will re-acquire a CPU Lock before returning from this
         //             function.
-        unsafe { Traits::leave_cpu_lock() };
+        unsafe { klock::leave_cpu_lock::<Traits>() };
 
         // Safety: CPU Lock inactive
         unsafe { Traits::yield_cpu() };
 
         // Re-acquire a CPU Lock
-        unsafe { Traits::enter_cpu_lock() };
+        unsafe { klock::enter_cpu_lock::<Traits>() };
 
         if *running_task.st.read(&*lock) == TaskSt::Running {
             break;
@@ -3,7 +3,7 @@
 //! **This module is exempt from the API stability guarantee.**
 use crate::{
     klock::{CpuLockCell, CpuLockTokenRefMut},
-    task::TaskCb,
+    task::{is_task_schedulable_on, TaskCb},
     utils::{
         intrusive_list::{Ident, ListAccessorCell, Static, StaticLink, StaticListHead},
         Init, PrioBitmap,
@@ -21,8 +21,14 @@ pub trait Queue<Traits>: Send + Sync + fmt::Debug + Init + 'static + private::Se
     type PerTaskData: Send + Sync + fmt::Debug + Init + 'static;
 
     /// Return a flag indicating whether there's a task in Ready state whose
-    /// priority is in the specified range.
-    fn has_ready_task_in_priority_range(&self, ctx: Ctx<'_, Traits>, range: RangeTo<usize>) -> bool
+    /// priority is in the specified range and which can be scheduled on the
+    /// processor `cpu`.
+    fn has_ready_task_in_priority_range(
+        &self,
+        ctx: Ctx<'_, Traits>,
+        cpu: usize,
+        range: RangeTo<usize>,
+    ) -> bool
     where
         Traits: KernelTraits;
 
@@ -41,8 +47,8 @@ pub trait Queue<Traits>: Send + Sync + fmt::Debug + Init + 'static + private::Se
     where
         Traits: KernelTraits;
 
-    /// Choose the next task to schedule based on `prev_task_priority`, the
-    /// priority of the current task (more precisely, the task that would run
+    /// Choose the next task to schedule on the processor `cpu` based on
+    /// `prev_task_priority`, the priority of the current task (more precisely, the task that would run
     /// after the ongoing scheduling decision if preemption was not requested by
     /// this decision). If there's no such current task, `prev_task_priority`
     /// should be `usize::MAX`, in which case this method will return
@@ -61,7 +67,10 @@ pub trait Queue<Traits>: Send + Sync + fmt::Debug + Init + 'static + private::Se
     ///
     ///  2. If the ready queue is empty, return `SwitchTo(None)`.
     ///
-    ///  3. Pop a task from the front of the ready queue.
+    ///  3. Pop a task from the front of the ready queue. In a multiprocessor
+    ///     system, tasks that can't be scheduled on `cpu` (because of their
+    ///     affinity or because they are still the running task of another
+    ///     processor) are skipped over and left in the queue.
     ///
     ///  4. If the popped task `t` is the imaginary task inserted in step 1,
     ///     return `Keep`. Otherwise, return `SwitchTo(t)`.
@@ -90,6 +99,7 @@ pub trait Queue<Traits>: Send + Sync + fmt::Debug + Init + 'static + private::Se
     fn pop_front_task(
         &self,
         ctx: Ctx<'_, Traits>,
+        cpu: usize,
         prev_task_priority: usize,
     ) -> ScheduleDecision<&'static TaskCb<Traits>>
     where
@@ -261,9 +271,16 @@ where
     #[inline]
     fn has_ready_task_in_priority_range(
         &self,
-        Ctx { lock }: Ctx<'_, Traits>,
+        Ctx { mut lock }: Ctx<'_, Traits>,
+        cpu: usize,
         range: RangeTo<usize>,
     ) -> bool {
+        if Traits::NUM_CPUS > 1 {
+            return self
+                .find_schedulable_task(lock.borrow_mut(), cpu, range)
+                .is_some();
+        }
+
         let highest_task_priority = self.bitmap.read(&*lock).find_set().unwrap_or(usize::MAX);
         highest_task_priority < range.end
     }
@@ -293,8 +310,13 @@ where
     fn pop_front_task(
         &self,
         Ctx { mut lock }: Ctx<'_, Traits>,
+        cpu: usize,
         prev_task_priority: usize,
     ) -> ScheduleDecision<&'static TaskCb<Traits>> {
+        if Traits::NUM_CPUS > 1 {
+            return self.pop_front_schedulable_task(lock, cpu, prev_task_priority);
+        }
+
         // The priority of the next task to run
         //
         // Consider the case where `prev_task_priority == usize::MAX`, i.e.,
@@ -379,6 +401,88 @@ where
     }
 }
 
+/// The multiprocessor versions of the [`Queue`] methods, which skip over the
+/// tasks that can't be scheduled on a given processor.
+impl<Traits: KernelTraits, Bitmap: PrioBitmap, const LEN: usize>
+    BitmapQueue<
+        Traits,
+        <Traits as PortThreading>::PortTaskState,
+        <Traits as KernelCfg1>::TaskPriority,
+        Bitmap,
+        LEN,
+    >
+where
+    Traits: KernelCfg1<TaskReadyQueue = Self>,
+{
+    /// Find the first task in the queue that has a priority in the specified
+    /// range and can be scheduled on the processor `cpu`. Returns the task and
+    /// its effective priority.
+    fn find_schedulable_task(
+        &self,
+        mut lock: CpuLockTokenRefMut<'_, Traits>,
+        cpu: usize,
+        range: RangeTo<usize>,
+    ) -> Option<(&'static TaskCb<Traits>, usize)> {
+        let mut bitmap = *self.bitmap.read(&*lock);
+
+        while let Some(pri) = bitmap.find_set() {
+            if pri >= range.end {
+                break;
+            }
+
+            let Ok(mut cur) = list_accessor!(&self.queues[pri], lock.borrow_mut()).front();
+
+            while let Some(Ident(task_cb)) = cur {
+                if is_task_schedulable_on(lock.borrow_mut(), task_cb, cpu) {
+                    return Some((task_cb, pri));
+                }
+
+                cur = {
+                    let accessor = list_accessor!(&self.queues[pri], lock.borrow_mut());
+                    // Safety: `task_cb` is still linked, so it shouldn't return
+                    //         `ItemError::Unlinked`.
+                    unsafe { accessor.next(Ident(task_cb)).unwrap_unchecked() }
+                };
+            }
+
+            bitmap.clear(pri);
+        }
+
+        None
+    }
+
+    /// The multiprocessor version of [`Queue::pop_front_task`].
+    fn pop_front_schedulable_task(
+        &self,
+        mut lock: CpuLockTokenRefMut<'_, Traits>,
+        cpu: usize,
+        prev_task_priority: usize,
+    ) -> ScheduleDecision<&'static TaskCb<Traits>> {
+        let Some((task_cb, pri)) =
+            self.find_schedulable_task(lock.borrow_mut(), cpu, ..prev_task_priority)
+        else {
+            return if prev_task_priority == usize::MAX {
+                ScheduleDecision::SwitchTo(None)
+            } else {
+                ScheduleDecision::Keep
+            };
+        };
+
+        // Remove the task from the ready queue
+        let mut accessor = list_accessor!(&self.queues[pri], lock.borrow_mut());
+        // Safety: `task_cb` is definitely linked to this list, so `remove`
+        //         shouldn't return `ItemError::NotLinked`.
+        unsafe { accessor.remove(Ident(task_cb)).unwrap_unchecked() };
+
+        // Update `bitmap` accordingly
+        if accessor.is_empty() {
+            self.bitmap.write(&mut *lock).clear(pri);
+        }
+
+        ScheduleDecision::SwitchTo(Some(task_cb))
+    }
+}
+
 impl<
         Traits: KernelTraits,
         PortTaskState: 'static,
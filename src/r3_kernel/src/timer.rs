@@ -10,6 +10,7 @@ use r3_core::{
 use crate::{
     error::NoAccessError,
     klock::{assume_cpu_lock, lock_cpu, CpuLockCell, CpuLockGuard, CpuLockTokenRefMut},
+    syscall::{self, regs, Syscall},
     timeout,
     utils::pin::static_pin,
     Id, KernelCfg2, KernelTraits, System,
@@ -34,20 +35,24 @@ unsafe impl<Traits: KernelTraits> traits::KernelTimer for System<Traits> {
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_timer_start(this: TimerId) -> Result<(), StartTimerError> {
-        let mut lock = lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let timer_cb = unsafe { Self::timer_cb(this)? };
-        start_timer(lock.borrow_mut(), timer_cb);
-        Ok(())
+        syscall::call::<Traits, _>(Syscall::TimerStart, regs![this.get()], || {
+            let mut lock = lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let timer_cb = unsafe { Self::timer_cb(this)? };
+            start_timer(lock.borrow_mut(), timer_cb);
+            Ok(())
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
     unsafe fn raw_timer_stop(this: TimerId) -> Result<(), StopTimerError> {
-        let mut lock = lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let timer_cb = unsafe { Self::timer_cb(this)? };
-        stop_timer(lock.borrow_mut(), timer_cb);
-        Ok(())
+        syscall::call::<Traits, _>(Syscall::TimerStop, regs![this.get()], || {
+            let mut lock = lock_cpu::<Traits>()?;
+            // Safety: The caller is responsible for providing a valid object ID
+            let timer_cb = unsafe { Self::timer_cb(this)? };
+            stop_timer(lock.borrow_mut(), timer_cb);
+            Ok(())
+        })
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -55,16 +60,23 @@ unsafe impl<Traits: KernelTraits> traits::KernelTimer for System<Traits> {
         this: TimerId,
         delay: Option<Duration>,
     ) -> Result<(), SetTimerDelayError> {
-        let time32 = if let Some(x) = delay {
-            timeout::time32_from_duration(x)?
-        } else {
-            timeout::BAD_DURATION32
-        };
-        let mut lock = lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let timer_cb = unsafe { Self::timer_cb(this)? };
-        set_timer_delay(lock.borrow_mut(), timer_cb, time32);
-        Ok(())
+        let [tag, delay_micros] = syscall::encode_optional_duration(delay);
+        syscall::call::<Traits, _>(
+            Syscall::TimerSetDelay,
+            regs![this.get(), tag, delay_micros],
+            || {
+                let time32 = if let Some(x) = delay {
+                    timeout::time32_from_duration(x)?
+                } else {
+                    timeout::BAD_DURATION32
+                };
+                let mut lock = lock_cpu::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let timer_cb = unsafe { Self::timer_cb(this)? };
+                set_timer_delay(lock.borrow_mut(), timer_cb, time32);
+                Ok(())
+            },
+        )
     }
 
     #[cfg_attr(not(feature = "inline_syscall"), inline(never))]
@@ -72,16 +84,23 @@ unsafe impl<Traits: KernelTraits> traits::KernelTimer for System<Traits> {
         this: TimerId,
         period: Option<Duration>,
     ) -> Result<(), SetTimerPeriodError> {
-        let time32 = if let Some(x) = period {
-            timeout::time32_from_duration(x)?
-        } else {
-            timeout::BAD_DURATION32
-        };
-        let mut lock = lock_cpu::<Traits>()?;
-        // Safety: The caller is responsible for providing a valid object ID
-        let timer_cb = unsafe { Self::timer_cb(this)? };
-        set_timer_period(lock.borrow_mut(), timer_cb, time32);
-        Ok(())
+        let [tag, period_micros] = syscall::encode_optional_duration(period);
+        syscall::call::<Traits, _>(
+            Syscall::TimerSetPeriod,
+            regs![this.get(), tag, period_micros],
+            || {
+                let time32 = if let Some(x) = period {
+                    timeout::time32_from_duration(x)?
+                } else {
+                    timeout::BAD_DURATION32
+                };
+                let mut lock = lock_cpu::<Traits>()?;
+                // Safety: The caller is responsible for providing a valid object ID
+                let timer_cb = unsafe { Self::timer_cb(this)? };
+                set_timer_period(lock.borrow_mut(), timer_cb, time32);
+                Ok(())
+            },
+        )
     }
 }
 
@@ -11,7 +11,7 @@ use crate::{
     task::{TaskCb, TaskSt},
     timeout,
     utils::intrusive_list::{self, HandleInconsistencyUnchecked, ListAccessorCell},
-    KernelTraits, Port, PortThreading,
+    Id, KernelTraits, Port, PortThreading,
 };
 
 // Type definitions and trait implementations for wait lists
@@ -638,6 +638,76 @@ pub(super) fn with_current_wait_payload<Traits: KernelTraits, R>(
     f(wait)
 }
 
+/// Identifies the condition a task is waiting for. Part of [`TaskInfo`].
+///
+/// The kernel objects are identified by their raw IDs, which can be converted
+/// to object handles by [`r3_core::kernel::task::TaskHandle::from_id`] and its
+/// counterparts.
+///
+/// [`TaskInfo`]: crate::TaskInfo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WaitTarget {
+    /// The task is waiting on the event group with the specified raw ID.
+    EventGroup(Id),
+    /// The task is waiting on the semaphore with the specified raw ID.
+    Semaphore(Id),
+    /// The task is waiting to lock the mutex with the specified raw ID.
+    Mutex(Id),
+    /// The task is waiting for a park token.
+    Park,
+    /// The task is sleeping.
+    Sleep,
+}
+
+/// Get the condition the given task is waiting for. Returns `None` if the task
+/// is not in the Waiting state.
+pub(super) fn wait_target<Traits: KernelTraits>(
+    lock: CpuLockTokenRefMut<'_, Traits>,
+    task_cb: &TaskCb<Traits>,
+) -> Option<WaitTarget> {
+    let wait_ref = task_cb.wait.current_wait.get(&*lock)?;
+
+    // Safety: `wait_ref` must point to an existing `Wait`
+    let wait = unsafe { &*wait_ref.0.as_ptr() };
+
+    // Find the object containing `wait_queue`. Calculate the object ID from
+    // the position in the object pool.
+    fn find_by_wait_queue<'a, Traits: KernelTraits>(
+        wait: &Wait<Traits>,
+        mut pool_wait_queues: impl Iterator<Item = &'a WaitQueue<Traits>>,
+    ) -> Option<Id> {
+        let wait_queue = wait.wait_queue?;
+        let i = pool_wait_queues.position(|x| core::ptr::eq(x, wait_queue))?;
+        Id::new(i + 1)
+    }
+
+    match wait.payload {
+        WaitPayload::EventGroupBits { .. } => find_by_wait_queue(
+            wait,
+            Traits::event_group_cb_pool()
+                .iter()
+                .map(|cb| &cb.wait_queue),
+        )
+        .map(WaitTarget::EventGroup),
+        WaitPayload::Semaphore => find_by_wait_queue(
+            wait,
+            Traits::semaphore_cb_pool().iter().map(|cb| &cb.wait_queue),
+        )
+        .map(WaitTarget::Semaphore),
+        WaitPayload::Mutex(mutex_cb) => {
+            // Safety: `mutex_cb` refers to an element of
+            // `Traits::mutex_cb_pool()`
+            let offset =
+                unsafe { <*const _>::offset_from(mutex_cb, Traits::mutex_cb_pool().as_ptr()) };
+            Id::new(offset as usize + 1).map(WaitTarget::Mutex)
+        }
+        WaitPayload::Park => Some(WaitTarget::Park),
+        WaitPayload::Sleep => Some(WaitTarget::Sleep),
+        WaitPayload::__Nonexhaustive => None,
+    }
+}
+
 /// Reposition the given task's wait object within the wait queue. This is
 /// necessary after changing the task's priority because some wait queues are
 /// configured to sort wait objects by task priority
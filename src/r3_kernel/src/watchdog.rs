@@ -0,0 +1,392 @@
+//! Software watchdog supervising task liveness
+//!
+//! [`StaticWatchdog`] detects tasks that stopped making progress. A task
+//! [registers][1] itself with a deadline and then [feeds][2] the watchdog
+//! periodically. A monitor driven by a [timer][3] counts down the remaining
+//! time of each registered task and calls the user-supplied handler when a
+//! task misses its deadline. The handler receives the task's [state and wait
+//! condition][4], which helps pinpointing the cause.
+//!
+//! The handler is called in an interrupt context and is called only once per
+//! missed deadline. Typical responses include:
+//!
+//!  - Resetting the system.
+//!  - [Interrupting][5] the task, causing its ongoing blocking operation to
+//!    fail with `Interrupted`, so that it can exit and be [re-activated][6] by
+//!    another task.
+//!
+//! A task must [unregister][7] itself before exiting because a Dormant task
+//! can't feed the watchdog. A watchdog can't be used by a task running with
+//! reduced privileges (e.g., an unprivileged task of `r3_port_arm_m`).
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use r3::kernel::StaticTask;
+//! use r3_kernel::watchdog::{MissedDeadline, StaticWatchdog};
+//!
+//! struct Objects {
+//!     watchdog: StaticWatchdog<SystemTraits>,
+//! }
+//!
+//! const fn configure_app(cfg: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+//!     let watchdog = StaticWatchdog::define()
+//!         .period(Duration::from_millis(10))
+//!         .on_missed_deadline(on_missed_deadline)
+//!         .finish(cfg);
+//!     /* ... */
+//!     Objects { watchdog }
+//! }
+//!
+//! fn task_body() {
+//!     COTTAGE.watchdog.register(Duration::from_millis(100)).unwrap();
+//!     loop {
+//!         do_work();
+//!         COTTAGE.watchdog.feed().unwrap();
+//!     }
+//! }
+//!
+//! fn on_missed_deadline(missed: &MissedDeadline<SystemTraits>) {
+//!     panic!("{missed:?}");
+//! }
+//! ```
+//!
+//! [1]: StaticWatchdog::register
+//! [2]: StaticWatchdog::feed
+//! [3]: r3_core::kernel::Timer
+//! [4]: crate::TaskInfo
+//! [5]: r3_core::kernel::task::TaskMethods::interrupt
+//! [6]: r3_core::kernel::task::TaskMethods::activate
+//! [7]: StaticWatchdog::unregister
+use core::fmt;
+use r3_core::{
+    hunk::Hunk,
+    kernel::{raw_cfg, task::TaskHandle, timer::StaticTimer, Cfg, TaskRef},
+    time::Duration,
+    utils::Init,
+};
+
+use crate::{
+    error::define_error, klock, task::TaskId, KernelTraits, PortThreading, System, TaskInfo,
+};
+
+/// The default value of [`WatchdogDefiner::capacity`].
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// A defined (statically created) software watchdog.
+pub struct StaticWatchdog<Traits: KernelTraits> {
+    slots: Hunk<System<Traits>, [klock::CpuLockCell<Traits, Slot>]>,
+    period_micros: u32,
+    on_missed_deadline: fn(&MissedDeadline<Traits>),
+}
+
+impl<Traits: KernelTraits> Clone for StaticWatchdog<Traits> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Traits: KernelTraits> Copy for StaticWatchdog<Traits> {}
+
+impl<Traits: KernelTraits> fmt::Debug for StaticWatchdog<Traits> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticWatchdog")
+            .field("slots", &&*self.slots)
+            .field("period_micros", &self.period_micros)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The registration of a task.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// The registered task. `None` if this slot is vacant.
+    task: Option<TaskId>,
+    /// The deadline in microseconds.
+    deadline_micros: u32,
+    /// The remaining time until the deadline in microseconds.
+    remaining_micros: u32,
+    /// `true` if the deadline was missed and the handler has already been
+    /// called.
+    missed: bool,
+}
+
+impl Init for Slot {
+    const INIT: Self = Self {
+        task: None,
+        deadline_micros: 0,
+        remaining_micros: 0,
+        missed: false,
+    };
+}
+
+/// Describes a task that missed the deadline. Passed to the handler specified
+/// by [`WatchdogDefiner::on_missed_deadline`].
+#[non_exhaustive]
+pub struct MissedDeadline<Traits: KernelTraits> {
+    /// The task that missed the deadline.
+    pub task: TaskRef<'static, System<Traits>>,
+    /// The deadline specified by [`StaticWatchdog::register`].
+    pub deadline: Duration,
+    /// The state of the task at the point of detection.
+    pub info: TaskInfo,
+}
+
+impl<Traits: KernelTraits> fmt::Debug for MissedDeadline<Traits> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MissedDeadline")
+            .field("task", &self.task)
+            .field("deadline", &self.deadline)
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+define_error! {
+    mod register_error {}
+    /// Error type for [`StaticWatchdog::register`].
+    pub enum RegisterError {
+        /// The current context is not a task context, CPU Lock is active, or
+        /// the current task is unprivileged.
+        BadContext,
+        /// The deadline is not positive.
+        BadParam,
+        /// All slots are in use.
+        QueueOverflow,
+    }
+}
+
+define_error! {
+    mod feed_error {}
+    /// Error type for [`StaticWatchdog::feed`] and
+    /// [`StaticWatchdog::unregister`].
+    pub enum FeedError {
+        /// The current context is not a task context, CPU Lock is active, or
+        /// the current task is unprivileged.
+        BadContext,
+        /// The current task is not registered.
+        BadObjectState,
+    }
+}
+
+impl<Traits: KernelTraits> StaticWatchdog<Traits> {
+    /// Construct a `WatchdogDefiner` to define a watchdog in [a configuration
+    /// function](r3_core#static-configuration).
+    pub const fn define() -> WatchdogDefiner<Traits> {
+        WatchdogDefiner {
+            capacity: DEFAULT_CAPACITY,
+            period: None,
+            on_missed_deadline: None,
+        }
+    }
+
+    /// Register the current task with the specified deadline. The task must
+    /// call [`Self::feed`] at least once in every `deadline`.
+    ///
+    /// If the current task is already registered, its deadline is updated.
+    /// In both cases, the countdown starts over.
+    pub fn register(&self, deadline: Duration) -> Result<(), RegisterError> {
+        if !Self::is_accessible() {
+            return Err(RegisterError::BadContext);
+        }
+
+        if !deadline.is_positive() {
+            return Err(RegisterError::BadParam);
+        }
+        let deadline_micros = deadline.as_micros() as u32;
+
+        let (mut lock, task) = Self::lock_current().ok_or(RegisterError::BadContext)?;
+
+        let slot = self
+            .slots
+            .iter()
+            .find(|slot| slot.read(&*lock).task == Some(task))
+            .or_else(|| {
+                self.slots
+                    .iter()
+                    .find(|slot| slot.read(&*lock).task.is_none())
+            })
+            .ok_or(RegisterError::QueueOverflow)?;
+
+        slot.replace(
+            &mut *lock,
+            Slot {
+                task: Some(task),
+                deadline_micros,
+                remaining_micros: deadline_micros,
+                missed: false,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Unregister the current task.
+    pub fn unregister(&self) -> Result<(), FeedError> {
+        if !Self::is_accessible() {
+            return Err(FeedError::BadContext);
+        }
+
+        let (mut lock, task) = Self::lock_current().ok_or(FeedError::BadContext)?;
+        let slot = self
+            .find_slot(&lock, task)
+            .ok_or(FeedError::BadObjectState)?;
+        slot.replace(&mut *lock, Slot::INIT);
+        Ok(())
+    }
+
+    /// Reset the countdown of the current task.
+    pub fn feed(&self) -> Result<(), FeedError> {
+        if !Self::is_accessible() {
+            return Err(FeedError::BadContext);
+        }
+
+        let (mut lock, task) = Self::lock_current().ok_or(FeedError::BadContext)?;
+        let slot = self
+            .find_slot(&lock, task)
+            .ok_or(FeedError::BadObjectState)?;
+        let slot = slot.write(&mut *lock);
+        slot.remaining_micros = slot.deadline_micros;
+        slot.missed = false;
+        Ok(())
+    }
+
+    /// Get a flag indicating whether the current context can use a watchdog.
+    /// A watchdog is identified by its address, which can't be passed to the
+    /// kernel through a syscall gate, so unprivileged tasks can't use it.
+    fn is_accessible() -> bool {
+        Traits::syscall_gate().is_none()
+    }
+
+    /// Acquire CPU Lock and get the current task. Returns `None` if the current
+    /// context is not a task context or CPU Lock is already active.
+    fn lock_current() -> Option<(klock::CpuLockGuard<Traits>, TaskId)> {
+        let task = System::<Traits>::task_current().ok()?;
+        let lock = klock::lock_cpu::<Traits>().ok()?;
+        Some((lock, task))
+    }
+
+    fn find_slot(
+        &self,
+        lock: &klock::CpuLockGuard<Traits>,
+        task: TaskId,
+    ) -> Option<&'static klock::CpuLockCell<Traits, Slot>> {
+        let slots: &'static [_] = Hunk::as_ref(self.slots);
+        slots
+            .iter()
+            .find(|slot| slot.read(&**lock).task == Some(task))
+    }
+
+    /// The timer callback. Advance the countdowns and report missed deadlines.
+    fn monitor(self) {
+        for slot in self.slots.iter() {
+            // Update the slot and take a snapshot of the task. Release CPU
+            // Lock before calling the handler.
+            let missed = {
+                let Ok(mut lock) = klock::lock_cpu::<Traits>() else { continue };
+                let Slot {
+                    task: Some(task),
+                    deadline_micros,
+                    remaining_micros,
+                    missed,
+                } = *slot.read(&*lock) else { continue };
+
+                let remaining_micros = remaining_micros.saturating_sub(self.period_micros);
+                slot.write(&mut *lock).remaining_micros = remaining_micros;
+                if remaining_micros > 0 || missed {
+                    continue;
+                }
+
+                // Safety: `task` was obtained from `task_current`
+                let task = unsafe { TaskRef::from_id(task) };
+
+                // Don't mark the slot until the snapshot is taken so that the
+                // missed deadline is reported in the next period if this fails
+                let Ok(info) = System::<Traits>::task_info_inner(lock.borrow_mut(), task)
+                    else { continue };
+                slot.write(&mut *lock).missed = true;
+
+                MissedDeadline {
+                    task,
+                    deadline: Duration::from_micros(deadline_micros as i32),
+                    info,
+                }
+            };
+
+            (self.on_missed_deadline)(&missed);
+        }
+    }
+}
+
+/// The definer (static builder) for [`StaticWatchdog`].
+#[must_use = "must call `finish()` to complete registration"]
+pub struct WatchdogDefiner<Traits: KernelTraits> {
+    capacity: usize,
+    period: Option<Duration>,
+    on_missed_deadline: Option<fn(&MissedDeadline<Traits>)>,
+}
+
+impl<Traits: KernelTraits> WatchdogDefiner<Traits> {
+    /// Specify the maximum number of tasks that can be registered at the same
+    /// time. Defaults to [`DEFAULT_CAPACITY`] when unspecified.
+    pub const fn capacity(self, capacity: usize) -> Self {
+        Self { capacity, ..self }
+    }
+
+    /// \[**Required**\] Specify the interval at which the monitor checks the
+    /// registered tasks. This determines the precision of deadline
+    /// detection: a missed deadline is detected up to one period late.
+    pub const fn period(self, period: Duration) -> Self {
+        assert!(period.is_positive(), "`period` must be positive");
+        Self {
+            period: Some(period),
+            ..self
+        }
+    }
+
+    /// \[**Required**\] Specify the function to call when a task misses its
+    /// deadline. It will be called in an interrupt context.
+    pub const fn on_missed_deadline(self, handler: fn(&MissedDeadline<Traits>)) -> Self {
+        Self {
+            on_missed_deadline: Some(handler),
+            ..self
+        }
+    }
+
+    /// Complete the definition of a watchdog, returning a reference to the
+    /// watchdog.
+    pub const fn finish<C: ~const raw_cfg::CfgTimer<System = System<Traits>>>(
+        self,
+        cfg: &mut Cfg<C>,
+    ) -> StaticWatchdog<Traits> {
+        let Some(period) = self.period else {
+            panic!("`period` is not specified");
+        };
+        let Some(on_missed_deadline) = self.on_missed_deadline else {
+            panic!("`on_missed_deadline` is not specified");
+        };
+
+        let watchdog = StaticWatchdog {
+            // Safety: `CpuLockCell<_, Slot>` is zero-initializable because
+            // `CpuLockKeyhole` is a ZST and all-zero `Slot` is `Slot::INIT`.
+            // (Slice hunks don't support `DefaultInitTag` yet.)
+            slots: unsafe {
+                Hunk::<_, [_]>::define()
+                    .len(self.capacity)
+                    .zeroed_unchecked()
+                    .finish(cfg)
+            },
+            period_micros: period.as_micros() as u32,
+            on_missed_deadline,
+        };
+
+        StaticTimer::define()
+            .start(move || watchdog.monitor())
+            .delay(period)
+            .period(period)
+            .active(true)
+            .finish(cfg);
+
+        watchdog
+    }
+}
@@ -0,0 +1,63 @@
+/* This will be provided by the user (see `memory.x`) or by a Board Support Crate */
+INCLUDE memory.x
+ENTRY(start);
+
+SECTIONS
+{
+  .text :
+  {
+    *(.text .text.*);
+    . = ALIGN(8);
+    __etext = .;
+  } > RAM
+
+  .rodata : ALIGN(8)
+  {
+    *(.rodata .rodata.*);
+    . = ALIGN(8);
+  } > RAM
+
+  .data : ALIGN(8)
+  {
+    *(.data .data.*);
+    . = ALIGN(8);
+  } > RAM
+
+  /* LMA of .data */
+  __sidata = LOADADDR(.data);
+
+  /* ### .bss */
+  .bss : ALIGN(8)
+  {
+    __sbss = .;
+    *(.bss .bss.*);
+    . = ALIGN(8);
+    __ebss = .;
+  } > RAM
+
+  /* The main stack (`SP_EL1`), used by the startup code and interrupt
+     handlers */
+  . = ALIGN(16);
+  . += 8192;
+  _stack_start = .;
+
+  /* Place the heap right after the main stack */
+  . = ALIGN(8);
+  __sheap = .;
+
+  /* ## .got */
+  /* Dynamic relocations are unsupported. This section is only used to detect relocatable code in
+     the input files and raise an error if relocatable code is found */
+  .got (NOLOAD) :
+  {
+    KEEP(*(.got .got.*));
+  }
+
+  /* ## Discarded sections */
+  /DISCARD/ :
+  {
+    /* Unused exception related info that only wastes space */
+    *(.eh_frame);
+    *(.eh_frame_hdr);
+  }
+}
\ No newline at end of file
@@ -0,0 +1,123 @@
+//! AArch64 system registers and maintenance instructions
+#![allow(non_snake_case)]
+use core::arch::asm;
+
+/// Define a function to read a 64-bit system register.
+macro_rules! sys_reg_read {
+    (
+        $(#[$meta:meta])*
+        fn $name:ident() = $reg:literal
+    ) => {
+        $(#[$meta])*
+        #[inline(always)]
+        pub fn $name() -> u64 {
+            let value;
+            unsafe {
+                asm!(
+                    concat!("mrs {}, ", $reg),
+                    lateout(reg) value,
+                    options(nomem, nostack, preserves_flags),
+                )
+            };
+            value
+        }
+    };
+}
+
+/// Define a function to write a 64-bit system register.
+macro_rules! sys_reg_write {
+    (
+        $(#[$meta:meta])*
+        fn $name:ident() = $reg:literal
+    ) => {
+        $(#[$meta])*
+        #[inline(always)]
+        pub fn $name(value: u64) {
+            unsafe {
+                asm!(
+                    concat!("msr ", $reg, ", {}"),
+                    in(reg) value,
+                    options(nostack, preserves_flags),
+                )
+            };
+        }
+    };
+}
+
+sys_reg_write!(
+    /// Write System Control Register (EL1).
+    fn set_SCTLR_EL1() = "sctlr_el1"
+);
+sys_reg_read!(
+    /// Read Cache Level ID Register.
+    fn CLIDR_EL1() = "clidr_el1"
+);
+sys_reg_write!(
+    /// Write Cache Size Selection Register.
+    fn set_CSSELR_EL1() = "csselr_el1"
+);
+sys_reg_read!(
+    /// Read Current Cache Size ID Register.
+    fn CCSIDR_EL1() = "ccsidr_el1"
+);
+sys_reg_read!(
+    /// Read AArch64 Memory Model Feature Register 0.
+    fn ID_AA64MMFR0_EL1() = "id_aa64mmfr0_el1"
+);
+sys_reg_write!(
+    /// Write Memory Attribute Indirection Register (EL1).
+    fn set_MAIR_EL1() = "mair_el1"
+);
+sys_reg_write!(
+    /// Write Translation Control Register (EL1).
+    fn set_TCR_EL1() = "tcr_el1"
+);
+sys_reg_write!(
+    /// Write Translation Table Base Register 0 (EL1).
+    fn set_TTBR0_EL1() = "ttbr0_el1"
+);
+sys_reg_write!(
+    /// Write Vector Base Address Register (EL1).
+    fn set_VBAR_EL1() = "vbar_el1"
+);
+
+/// `SCTLR_EL1.M`: MMU enable
+pub const SCTLR_M: u64 = 1 << 0;
+/// `SCTLR_EL1.C`: Data cache enable
+pub const SCTLR_C: u64 = 1 << 2;
+/// `SCTLR_EL1.SA`: Stack alignment check enable
+pub const SCTLR_SA: u64 = 1 << 3;
+/// `SCTLR_EL1.I`: Instruction cache enable
+pub const SCTLR_I: u64 = 1 << 12;
+/// The bits of `SCTLR_EL1` that are `RES1` in Armv8.0
+pub const SCTLR_RES1: u64 = (1 << 29) | (1 << 28) | (1 << 23) | (1 << 22) | (1 << 20) | (1 << 11);
+
+/// Invalidate a data or unified cache line by set/way (`DC ISW`).
+#[inline(always)]
+pub fn dc_isw(set_way: u64) {
+    unsafe { asm!("dc isw, {}", in(reg) set_way, options(nostack, preserves_flags)) };
+}
+
+/// Invalidate all instruction caches to PoU (`IC IALLU`).
+#[inline(always)]
+pub fn ic_iallu() {
+    unsafe { asm!("ic iallu", options(nostack, preserves_flags)) };
+}
+
+/// Invalidate all stage 1 translations used at EL1 (`TLBI VMALLE1`).
+#[inline(always)]
+pub fn tlbi_vmalle1() {
+    unsafe { asm!("tlbi vmalle1", options(nostack, preserves_flags)) };
+}
+
+/// Data Synchronization Barrier (full system)
+#[inline(always)]
+pub fn dsb_sy() {
+    unsafe { asm!("dsb sy", options(nostack, preserves_flags)) };
+}
+
+/// Instruction Synchronization Barrier
+#[inline(always)]
+pub fn isb() {
+    unsafe { asm!("isb", options(nostack, preserves_flags)) };
+}
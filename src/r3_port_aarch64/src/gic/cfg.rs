@@ -0,0 +1,110 @@
+//! The public interface of the GIC driver.
+pub use r3_portkit::arm_gic::{
+    Gic, GicOptions, GicVersion, InterruptLineTriggerMode, SetInterruptLineTriggerModeError,
+};
+
+/// Implement [`PortInterrupts`], [`InterruptController`], and [`Gic`] on
+/// the given kernel trait type using the General Interrupt Controller (GIC) on
+/// the target. Supports GICv2 and GICv3 (see [`GicOptions::GIC_VERSION`]).
+/// **Requires [`GicOptions`].**
+///
+/// [`PortInterrupts`]: r3_kernel::PortInterrupts
+/// [`InterruptController`]: crate::InterruptController
+///
+/// # Safety
+///
+///  - The target must really include a GIC.
+///  - `GicOptions` should be configured correctly and the memory-mapped
+///    registers should be accessible.
+///
+#[macro_export]
+macro_rules! use_gic {
+    (unsafe impl PortInterrupts for $Traits:ty) => {
+        const _: () = {
+            use $crate::{
+                core::ops::Range,
+                r3_core::kernel::{
+                    ClearInterruptLineError, EnableInterruptLineError, InterruptNum,
+                    InterruptPriority, PendInterruptLineError, QueryInterruptLineError,
+                    SetInterruptLinePriorityError,
+                },
+                r3_kernel::PortInterrupts,
+                r3_portkit::arm_gic::{self, GicRegs},
+                Gic, InterruptController,
+            };
+
+            const _: () = arm_gic::validate_options::<$Traits>();
+
+            unsafe impl Gic for $Traits {
+                #[inline(always)]
+                fn gic_regs() -> GicRegs {
+                    unsafe { GicRegs::from_system_traits::<Self>() }
+                }
+            }
+
+            unsafe impl PortInterrupts for $Traits {
+                const MANAGED_INTERRUPT_PRIORITY_RANGE: Range<InterruptPriority> = 0..255;
+
+                #[inline]
+                unsafe fn set_interrupt_line_priority(
+                    line: InterruptNum,
+                    priority: InterruptPriority,
+                ) -> Result<(), SetInterruptLinePriorityError> {
+                    Self::gic_regs().set_interrupt_line_priority(line, priority)
+                }
+
+                #[inline]
+                unsafe fn enable_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), EnableInterruptLineError> {
+                    Self::gic_regs().enable_interrupt_line(line)
+                }
+
+                #[inline]
+                unsafe fn disable_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), EnableInterruptLineError> {
+                    Self::gic_regs().disable_interrupt_line(line)
+                }
+
+                #[inline]
+                unsafe fn pend_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), PendInterruptLineError> {
+                    Self::gic_regs().pend_interrupt_line(line)
+                }
+
+                #[inline]
+                unsafe fn clear_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), ClearInterruptLineError> {
+                    Self::gic_regs().clear_interrupt_line(line)
+                }
+
+                #[inline]
+                unsafe fn is_interrupt_line_pending(
+                    line: InterruptNum,
+                ) -> Result<bool, QueryInterruptLineError> {
+                    Self::gic_regs().is_interrupt_line_pending(line)
+                }
+            }
+
+            impl InterruptController for $Traits {
+                #[inline]
+                unsafe fn init() {
+                    Self::gic_regs().init()
+                }
+
+                #[inline]
+                unsafe fn acknowledge_interrupt() -> Option<InterruptNum> {
+                    Self::gic_regs().acknowledge_interrupt()
+                }
+
+                #[inline]
+                unsafe fn end_interrupt(num: InterruptNum) {
+                    Self::gic_regs().end_interrupt(num);
+                }
+            }
+        };
+    };
+}
@@ -0,0 +1,427 @@
+use core::ops::Range;
+
+/// Generate [startup code]. **Requires [`StartupOptions`] and [`EntryPoint`] to
+/// be implemented.**
+///
+/// This macro produces an entry point function whose symbol name is `start`.
+/// You should specify it as an entry point in your linker script (the provided
+/// linker scripts automatically do this for you).
+///
+/// [startup code]: crate#startup-code
+/// [`EntryPoint`]: crate::EntryPoint
+#[macro_export]
+macro_rules! use_startup {
+    (unsafe $Traits:ty) => {
+        #[no_mangle]
+        #[naked]
+        pub unsafe extern "C" fn start() {
+            ::core::arch::asm!(
+                "b {}",
+                sym $crate::startup::imp::start::<$Traits>,
+                options(noreturn),
+            );
+        }
+
+        impl $crate::startup::imp::Level2TranslationTables for $Traits {
+            const LEVEL2_TRANSLATION_TABLES: &'static [$crate::startup::imp::TranslationTable] =
+                &$crate::startup::imp::level2_translation_tables::<
+                    $Traits,
+                    { $crate::startup::imp::num_level2_translation_tables::<$Traits>() },
+                >();
+        }
+    };
+}
+
+/// The options for [`use_startup!`].
+pub trait StartupOptions {
+    /// The memory map.
+    ///
+    /// Note that the kernel code and the startup code don't support relocation,
+    /// so you need to make sure they are covered by an identical mapping.
+    ///
+    /// The startup code builds stage 1 translation tables for the 4KiB
+    /// translation granule and a 39-bit virtual address space (`TTBR0_EL1`
+    /// only), mapping each section by the largest blocks its alignment allows
+    /// (see [`MemoryMapSection::new`]). Level 2 translation tables are
+    /// allocated statically based on this memory map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use r3_port_aarch64::MemoryMapSection;
+    ///
+    /// // QEMU `virt` machine
+    /// const MEMORY_MAP: &'static [MemoryMapSection] = &[
+    ///     // RAM (128MiB)
+    ///     MemoryMapSection::new(0x4000_0000..0x4800_0000, 0x4000_0000).with_executable(true),
+    ///     // GIC, UART, etc.
+    ///     MemoryMapSection::new(0x0800_0000..0x0a00_0000, 0x0800_0000).as_device_memory(),
+    /// ];
+    /// ```
+    const MEMORY_MAP: &'static [MemoryMapSection];
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MemoryMapSection {
+    /// The starting physical address. Must be aligned to 2MiB blocks.
+    pub(super) physical_start: u64,
+    /// The starting virtual address. Must be aligned to 2MiB blocks.
+    pub(super) virtual_start: u64,
+    /// The length of the section, measured in bytes. Must be aligned to 2MiB
+    /// blocks.
+    pub(super) len: u64,
+    pub(super) attr: MemoryRegionAttributes,
+}
+
+impl MemoryMapSection {
+    /// Construct a `MemoryMapSection` for normal read/write memory access.
+    ///
+    ///  - All endpoints must be aligned to 2MiB blocks (`0x????_??00_0000`
+    ///    with an even sixth digit).
+    ///
+    ///  - `virtual_range` must not be empty.
+    ///
+    ///  - `virtual_range` must be a subset of `0..0x80_0000_0000` (39 bits).
+    ///
+    ///  - The physical address range must be a subset of
+    ///    `0..0x1_0000_0000_0000` (48 bits).
+    ///
+    /// The memory section is configured as a read/writable (but not
+    /// executable) Normal memory with a Outer and Inner Write-Back,
+    /// Read-Allocate, Write-Allocate attribute.
+    ///
+    /// The startup code maps 1GiB blocks whose virtual and physical addresses
+    /// are both aligned to 1GiB by level 1 block descriptors and the rest by
+    /// level 2 block (2MiB) descriptors. Each 1GiB block containing level 2
+    /// blocks consumes a 4KiB level 2 translation table.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use r3_port_aarch64::MemoryMapSection;
+    ///
+    /// // Map VA `0x2000_0000..0x2800_0000` to PA `0xc000_0000.0xc800_0000`
+    /// MemoryMapSection::new(0x2000_0000..0x2800_0000, 0xc000_0000);
+    ///
+    /// // Map VA `0x40_0000_0000..0x41_0000_0000` to
+    /// // PA `0x1_0000_0000..0x2_0000_0000` (by level 1 blocks)
+    /// MemoryMapSection::new(0x40_0000_0000..0x41_0000_0000, 0x1_0000_0000);
+    /// ```
+    ///
+    /// This function panics if an invalid parameter is supplied.
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_aarch64::MemoryMapSection;
+    /// // Empty range
+    /// MemoryMapSection::new(0x2000_0000..0x2000_0000, 0xc000_0000);
+    /// ```
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_aarch64::MemoryMapSection;
+    /// // VA is not in range `0..0x80_0000_0000`
+    /// MemoryMapSection::new(0x7f_c000_0000..0x80_4000_0000, 0xc000_0000);
+    /// ```
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_aarch64::MemoryMapSection;
+    /// // Not aligned to 2MiB blocks
+    /// MemoryMapSection::new(0x2000_1000..0x2020_1000, 0xc000_1000);
+    /// ```
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_aarch64::MemoryMapSection;
+    /// // PA is not in range `0..0x1_0000_0000_0000`
+    /// MemoryMapSection::new(0x2000_0000..0x3000_0000, 0xffff_f800_0000);
+    /// ```
+    pub const fn new(virtual_range: Range<u64>, physical_start: u64) -> Self {
+        if (virtual_range.start & 0x1f_ffff) != 0
+            || (virtual_range.end & 0x1f_ffff) != 0
+            || (physical_start & 0x1f_ffff) != 0
+        {
+            panic!("all endpoints must be aligned to 2MiB blocks");
+        }
+
+        if virtual_range.start >= virtual_range.end {
+            panic!("`virtual_range` must not be empty");
+        }
+
+        if virtual_range.end > 0x80_0000_0000 {
+            panic!("`virtual_range` must be a subset of `0..0x80_0000_0000`");
+        }
+
+        if physical_start + (virtual_range.end - virtual_range.start) > 0x1_0000_0000_0000 {
+            panic!("the physical address range must be a subset of `0..0x1_0000_0000_0000`");
+        }
+
+        Self {
+            physical_start,
+            virtual_start: virtual_range.start,
+            len: virtual_range.end - virtual_range.start,
+            attr: MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE,
+        }
+    }
+
+    /// Modify the memory attribute for a Device memory, returning the modified
+    /// `self`.
+    pub const fn as_device_memory(self) -> Self {
+        Self {
+            attr: self.attr.as_device_memory(),
+            ..self
+        }
+    }
+
+    /// Change the sharability, returning the modified `self`. This has no
+    /// effect on Device memory, which is always treated as Outer Shareable.
+    pub const fn with_sharable(self, sharable: bool) -> Self {
+        Self {
+            attr: self.attr.with_sharable(sharable),
+            ..self
+        }
+    }
+
+    /// Change the executability, returning the modified `self`.
+    pub const fn with_executable(self, executable: bool) -> Self {
+        Self {
+            attr: self.attr.with_executable(executable),
+            ..self
+        }
+    }
+
+    /// Change the writability, returning the modified `self`.
+    pub const fn with_writable(self, writable: bool) -> Self {
+        Self {
+            attr: self.attr.with_writable(writable),
+            ..self
+        }
+    }
+}
+
+/// The kind of a translation table descriptor mapping a [`Block`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum BlockKind {
+    /// A 1GiB block, mapped by a level 1 descriptor
+    Level1,
+    /// A 2MiB block, mapped by a level 2 descriptor
+    Level2,
+}
+
+impl BlockKind {
+    pub(super) const fn size(self) -> u64 {
+        match self {
+            Self::Level1 => 0x4000_0000,
+            Self::Level2 => 0x20_0000,
+        }
+    }
+
+    /// Choose the largest descriptor kind that can map the beginning of the
+    /// given range.
+    const fn choose(virtual_start: u64, physical_start: u64, len: u64) -> Self {
+        let size = Self::Level1.size();
+        if virtual_start % size == 0 && physical_start % size == 0 && len >= size {
+            Self::Level1
+        } else {
+            Self::Level2
+        }
+    }
+}
+
+/// A block of memory mapped by a single translation table descriptor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) struct Block {
+    pub(super) virtual_start: u64,
+    pub(super) physical_start: u64,
+    pub(super) kind: BlockKind,
+    pub(super) attr: MemoryRegionAttributes,
+}
+
+/// Splits [`MemoryMapSection`]s into [`Block`]s.
+pub(super) struct Blocks<'a> {
+    sections: &'a [MemoryMapSection],
+    /// The offset into `sections[0]`
+    offset: u64,
+}
+
+impl<'a> Blocks<'a> {
+    pub(super) const fn new(sections: &'a [MemoryMapSection]) -> Self {
+        Self {
+            sections,
+            offset: 0,
+        }
+    }
+
+    /// Get the next block. (`Iterator::next` is not `const fn`.)
+    pub(super) const fn next(&mut self) -> Option<Block> {
+        let [section, rest @ ..] = self.sections else {
+            return None;
+        };
+
+        let block = Block {
+            virtual_start: section.virtual_start + self.offset,
+            physical_start: section.physical_start + self.offset,
+            kind: BlockKind::choose(
+                section.virtual_start + self.offset,
+                section.physical_start + self.offset,
+                section.len - self.offset,
+            ),
+            attr: section.attr,
+        };
+
+        self.offset += block.kind.size();
+        if self.offset == section.len {
+            self.sections = rest;
+            self.offset = 0;
+        }
+
+        Some(block)
+    }
+}
+
+/// The memory attribute indices used by the startup code. The startup code
+/// programs `MAIR_EL1` accordingly.
+pub(super) mod mair {
+    /// Normal memory, Outer and Inner Write-Back Non-transient,
+    /// Read-Allocate, Write-Allocate
+    pub const NORMAL_WB_WA: u8 = 0;
+    /// Device-nGnRnE memory
+    pub const DEVICE_NGNRNE: u8 = 1;
+
+    /// The value of `MAIR_EL1` defining the above attribute indices
+    pub const MAIR_VALUE: u64 = 0xff << (NORMAL_WB_WA * 8) | 0x00 << (DEVICE_NGNRNE * 8);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) struct MemoryRegionAttributes {
+    /// An index into `MAIR_EL1` (`AttrIndx`)
+    pub attr_index: u8,
+    /// Shareability (`SH[1:0]`)
+    pub sh: u8,
+    /// Access permissions (`AP[2:1]`)
+    pub ap: u8,
+    /// Privileged Execute Never (`PXN`)
+    pub pxn: bool,
+}
+
+impl MemoryRegionAttributes {
+    pub(super) const NORMAL_WB_WA_SHARABLE_READ_WRITE: Self = Self {
+        attr_index: mair::NORMAL_WB_WA,
+        sh: 0b11,
+        ap: 0b00,
+        pxn: true,
+    };
+
+    const fn as_device_memory(self) -> Self {
+        Self {
+            attr_index: mair::DEVICE_NGNRNE,
+            ..self
+        }
+    }
+
+    pub(super) const fn with_sharable(self, sharable: bool) -> Self {
+        Self {
+            // Inner Shareable or Non-shareable
+            sh: if sharable { 0b11 } else { 0b00 },
+            ..self
+        }
+    }
+
+    pub(super) const fn with_executable(self, executable: bool) -> Self {
+        Self {
+            pxn: !executable,
+            ..self
+        }
+    }
+
+    pub(super) const fn with_writable(self, writable: bool) -> Self {
+        Self {
+            ap: if writable { 0b00 } else { 0b10 },
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn blocks() {
+        let mmap = [
+            // Level 2 blocks followed by a level 1 block
+            MemoryMapSection::new(0x3fe0_0000..0x8000_0000, 0x3fe0_0000),
+            // Level 2 blocks only because of the misalignment of PA
+            MemoryMapSection::new(0x1_0000_0000..0x1_4000_0000, 0x1_0020_0000).as_device_memory(),
+        ];
+
+        let mut blocks = Blocks::new(&mmap);
+        let mut kinds = Vec::new();
+        while let Some(block) = blocks.next() {
+            kinds.push((block.virtual_start, block.physical_start, block.kind));
+        }
+
+        let mut expected = Vec::new();
+        expected.push((0x3fe0_0000, 0x3fe0_0000, BlockKind::Level2));
+        expected.push((0x4000_0000, 0x4000_0000, BlockKind::Level1));
+        expected.extend((0..0x200).map(|i| {
+            (
+                0x1_0000_0000 + i * 0x20_0000,
+                0x1_0020_0000 + i * 0x20_0000,
+                BlockKind::Level2,
+            )
+        }));
+
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn memory_attributes() {
+        assert_eq!(
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE.as_device_memory(),
+            MemoryRegionAttributes {
+                attr_index: mair::DEVICE_NGNRNE,
+                sh: 0b11,
+                ap: 0b00,
+                pxn: true,
+            },
+        );
+
+        // Sharable by default
+        assert_eq!(
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE.with_sharable(true),
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE,
+        );
+
+        // No Execute by default
+        assert_eq!(
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE.with_executable(false),
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE,
+        );
+
+        // Writable by default
+        assert_eq!(
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE.with_writable(true),
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE,
+        );
+
+        // Read-only, executable, Non-shareable Normal memory
+        assert_eq!(
+            MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE
+                .with_sharable(false)
+                .with_executable(true)
+                .with_writable(false),
+            MemoryRegionAttributes {
+                attr_index: mair::NORMAL_WB_WA,
+                sh: 0b00,
+                ap: 0b10,
+                pxn: false,
+            },
+        );
+    }
+
+    #[test]
+    fn mair_value() {
+        assert_eq!(mair::MAIR_VALUE, 0x00ff);
+    }
+}
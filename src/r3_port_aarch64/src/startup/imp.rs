@@ -0,0 +1,383 @@
+//! Provides a standard startup and entry code implementation.
+use r3_portkit::pptext::pp_asm;
+
+use crate::{
+    aarch64,
+    startup::cfg::{mair, BlockKind, Blocks, MemoryRegionAttributes},
+    EntryPoint, StartupOptions,
+};
+
+#[naked]
+pub extern "C" fn start<Traits: EntryPoint + StartupOptions + Level2TranslationTables>() {
+    unsafe {
+        pp_asm!("
+            # Mask all exceptions
+            msr daifset, #0xf
+
+            # Find the current exception level
+            mrs x0, CurrentEL
+            and x0, x0, #0xc
+            cmp x0, #0x4
+            b.eq 1f
+            cmp x0, #0x8
+            b.eq 2f
+
+            # EL3 or EL0 (unsupported)
+        0:
+            wfe
+            b 0b
+
+        2:
+            # EL2: Configure EL2 for running the kernel in EL1 and drop to EL1
+
+            # EL1 uses AArch64 (HCR_EL2.RW = 1)
+            mov x0, #0x80000000
+            msr hcr_el2, x0
+
+            # Allow EL1 to access the physical counter and timer
+            # (CNTHCTL_EL2.EL1PCTEN = CNTHCTL_EL2.EL1PCEN = 1)
+            mov x0, #3
+            msr cnthctl_el2, x0
+            msr cntvoff_el2, xzr
+
+            # Don't trap FP/SIMD instructions (CPTR_EL2.TFP = 0)
+            mov x0, #0x33ff
+            msr cptr_el2, x0
+
+            # If the GIC CPU interface system registers are implemented
+            # (ID_AA64PFR0_EL1.GIC != 0), allow EL1 to use them
+            # (ICC_SRE_EL2.Enable = ICC_SRE_EL2.SRE = 1)
+            mrs x0, id_aa64pfr0_el1
+            ubfx x0, x0, #24, #4
+            cbz x0, 3f
+            mrs x0, s3_4_c12_c9_5
+            mov x1, #0x9
+            orr x0, x0, x1
+            msr s3_4_c12_c9_5, x0
+            isb
+        3:
+
+            # Return to EL1h with all exceptions masked
+            mov x0, #0x3c5
+            msr spsr_el2, x0
+            adr x0, 1f
+            msr elr_el2, x0
+            eret
+
+        1:
+            # EL1: Set the stack pointer (SP_EL1) before calling Rust code
+            adrp x0, _stack_start
+            add x0, x0, :lo12:_stack_start
+            mov sp, x0
+        "   if cfg!(target_feature = "neon") {                                      "
+            # Don't trap FP/SIMD instructions (CPACR_EL1.FPEN = 0b11) because
+            # the compiler may generate them anywhere
+            mov x0, #0x300000
+            msr cpacr_el1, x0
+            isb
+        "   }                                                                       "
+
+            b {reset_handler1}
+            ",
+            reset_handler1 = sym reset_handler1::<Traits>,
+            options(noreturn),
+        );
+    }
+}
+
+extern "C" fn reset_handler1<Traits: EntryPoint + StartupOptions + Level2TranslationTables>() {
+    // Disable MMU, data and unified caches, and instruction caches
+    aarch64::set_SCTLR_EL1(aarch64::SCTLR_RES1);
+    aarch64::isb();
+
+    // Invalidate instruction caches
+    aarch64::ic_iallu();
+
+    // Invalidate data and unified caches
+    //
+    // Level of Coherency: “This field defines the last level of cache that must
+    // be cleaned or invalidated when cleaning or invalidating to the point of
+    // coherency.”
+    let clidr = aarch64::CLIDR_EL1();
+    let level_of_coherency = (clidr >> 24) & 0b111;
+    for level in 0..level_of_coherency {
+        let cache_type = (clidr >> (level * 3)) & 0b111;
+
+        // Does this cache level include a data or unified cache?
+        if cache_type >= 2 {
+            // Level = level, InD = 0
+            // Use `isb` to make sure the change to CSSELR_EL1 takes effect.
+            aarch64::set_CSSELR_EL1(level * 2);
+            aarch64::isb();
+
+            let ccsidr = aarch64::CCSIDR_EL1();
+            let log2_line_size = (ccsidr & 0b111) + 4;
+            let max_way_index = (ccsidr >> 3) & 0x3ff;
+            let max_set_index = (ccsidr >> 13) & 0x7fff;
+
+            let way_offset = (max_way_index as u32).leading_zeros();
+
+            for way in (0..=max_way_index).rev() {
+                for set in (0..=max_set_index).rev() {
+                    let set_way = (level << 1) | (way << way_offset) | (set << log2_line_size);
+
+                    // Invalidate by set/way
+                    aarch64::dc_isw(set_way);
+                }
+            }
+        }
+    }
+
+    // Configure MMU
+    let translation_table_ptr = (&Traits::LEVEL1_TRANSLATION_TABLE) as *const _ as u64;
+    let physical_address_size = (aarch64::ID_AA64MMFR0_EL1() & 0xf).min(0b101);
+    aarch64::set_MAIR_EL1(mair::MAIR_VALUE);
+    aarch64::set_TCR_EL1(
+        // T0SZ = 25: The region size is 2^39 bytes (translation starts at
+        // level 1)
+        25 |
+        // IRGN0 = ORGN0 = 0b01: Table walk is Normal memory, Inner and Outer
+        // Write-Back Read-Allocate Write-Allocate Cacheable
+        (0b01 << 8) | (0b01 << 10) |
+        // SH0 = 0b11: Table walk is Inner Shareable
+        // TG0 = 0b00: 4KiB granule
+        (0b11 << 12) |
+        // EPD1 = 1: Don't use TTBR1_EL1
+        (1 << 23) |
+        // TG1 = 0b10: 4KiB granule (unused)
+        (0b10 << 30) |
+        // IPS: Use all implemented physical address bits (up to 48 bits)
+        (physical_address_size << 32),
+    );
+    aarch64::set_TTBR0_EL1(translation_table_ptr);
+
+    // Invalidate TLB
+    aarch64::tlbi_vmalle1();
+
+    // DSB causes completion of all preceding cache and TLB maintenance
+    // operations. ISB causes the effect to be visible to all subsequent
+    // instructions.
+    aarch64::dsb_sy();
+    aarch64::isb();
+
+    aarch64::set_SCTLR_EL1(
+        aarch64::SCTLR_RES1 |
+        // Enable MMU
+        aarch64::SCTLR_M |
+        // Enable data and unified caches
+        aarch64::SCTLR_C |
+        // Enable SP alignment check
+        aarch64::SCTLR_SA |
+        // Enable instruction caches
+        aarch64::SCTLR_I,
+        // Alignment fault checking (`A`) is left disabled because the
+        // compiler may generate unaligned accesses to Normal memory
+    );
+
+    // Set the exception vector table base address
+    aarch64::set_VBAR_EL1(Traits::EXCEPTION_VECTOR_TABLE as usize as u64);
+
+    // Ensure the changes made to `SCTLR_EL1` and `VBAR_EL1` here take effect
+    // immediately
+    aarch64::isb();
+
+    extern "C" {
+        // These symbols come from `link.x`
+        static mut __sbss: u64;
+        static mut __ebss: u64;
+    }
+
+    // Initialize RAM
+    unsafe {
+        r0::zero_bss(&mut __sbss, &mut __ebss);
+    }
+
+    unsafe { Traits::start() };
+}
+
+// Translation table generation
+// -----------------------------------------------------------------------
+
+/// Provides the storage for level 2 translation tables, whose number depends
+/// on [`StartupOptions::MEMORY_MAP`]. Implemented by [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub trait Level2TranslationTables {
+    const LEVEL2_TRANSLATION_TABLES: &'static [TranslationTable];
+}
+
+/// The extension trait for deriving static data based on `StartupOptions`.
+trait StartupExt {
+    const LEVEL1_TRANSLATION_TABLE: TranslationTable;
+}
+
+impl<T: StartupOptions + Level2TranslationTables> StartupExt for T {
+    const LEVEL1_TRANSLATION_TABLE: TranslationTable = {
+        let mut table = TranslationTable {
+            entries: [TranslationTableEntry::invalid(); 512],
+        };
+        let mut occupied = [false; 512];
+
+        // Create level 1 block entries based on `MEMORY_MAP`
+        let mut blocks = Blocks::new(Self::MEMORY_MAP);
+        while let Some(block) = blocks.next() {
+            let BlockKind::Level1 = block.kind else { continue };
+            let k = (block.virtual_start >> 30) as usize;
+            if occupied[k] {
+                panic!("region overlap; some address ranges are specified more than once");
+            }
+            table.entries[k] = TranslationTableEntry::block(block.physical_start, block.attr);
+            occupied[k] = true;
+        }
+
+        // Link the level 2 translation tables
+        let indices = level2_translation_table_indices::<Self>();
+        // `for` is unusable in `const fn` [ref:const_for]
+        let mut k = 0;
+        while k < 512 {
+            if let Some(i) = indices[k] {
+                if occupied[k] {
+                    panic!("region overlap; some address ranges are specified more than once");
+                }
+                table.entries[k] =
+                    TranslationTableEntry::table(&Self::LEVEL2_TRANSLATION_TABLES[i]);
+            }
+            k += 1;
+        }
+
+        table
+    };
+}
+
+/// Get the index into [`Level2TranslationTables::LEVEL2_TRANSLATION_TABLES`]
+/// for each 1GiB block of the virtual address space that contains level 2
+/// blocks.
+const fn level2_translation_table_indices<T: StartupOptions>() -> [Option<usize>; 512] {
+    let mut needed = [false; 512];
+
+    let mut blocks = Blocks::new(T::MEMORY_MAP);
+    while let Some(block) = blocks.next() {
+        if let BlockKind::Level2 = block.kind {
+            needed[(block.virtual_start >> 30) as usize] = true;
+        }
+    }
+
+    let mut indices = [None; 512];
+    let mut num_tables = 0;
+    // `for` is unusable in `const fn` [ref:const_for]
+    let mut k = 0;
+    while k < 512 {
+        if needed[k] {
+            indices[k] = Some(num_tables);
+            num_tables += 1;
+        }
+        k += 1;
+    }
+
+    indices
+}
+
+/// Get the number of level 2 translation tables required by `T`. Used by
+/// [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub const fn num_level2_translation_tables<T: StartupOptions>() -> usize {
+    let indices = level2_translation_table_indices::<T>();
+    let mut num_tables = 0;
+    // `for` is unusable in `const fn` [ref:const_for]
+    let mut k = 0;
+    while k < 512 {
+        if indices[k].is_some() {
+            num_tables += 1;
+        }
+        k += 1;
+    }
+    num_tables
+}
+
+/// Construct the level 2 translation tables for `T`. `N` must be equal to
+/// [`num_level2_translation_tables`]`::<T>()`. Used by [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub const fn level2_translation_tables<T: StartupOptions, const N: usize>() -> [TranslationTable; N]
+{
+    let indices = level2_translation_table_indices::<T>();
+    let mut tables = [TranslationTable {
+        entries: [TranslationTableEntry::invalid(); 512],
+    }; N];
+    let mut occupied = [[false; 512]; N];
+
+    // Create level 2 block entries based on `MEMORY_MAP`
+    let mut blocks = Blocks::new(T::MEMORY_MAP);
+    while let Some(block) = blocks.next() {
+        let BlockKind::Level2 = block.kind else { continue };
+        let Some(i) = indices[(block.virtual_start >> 30) as usize] else { unreachable!() };
+        let k = ((block.virtual_start >> 21) % 512) as usize;
+        if occupied[i][k] {
+            panic!("region overlap; some address ranges are specified more than once");
+        }
+        tables[i].entries[k] = TranslationTableEntry::block(block.physical_start, block.attr);
+        occupied[i][k] = true;
+    }
+
+    tables
+}
+
+/// A level 1 or 2 translation table for the 4KiB translation granule.
+#[doc(hidden)]
+#[repr(align(4096))]
+#[derive(Clone, Copy)]
+pub struct TranslationTable {
+    entries: [TranslationTableEntry; 512],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union TranslationTableEntry {
+    int: u64,
+    ptr: *mut u8,
+}
+
+impl TranslationTableEntry {
+    /// Construct an invalid entry.
+    const fn invalid() -> Self {
+        Self { int: 0 }
+    }
+
+    /// Construct a table descriptor pointing to a level 2 translation table.
+    const fn table(table: *const TranslationTable) -> Self {
+        Self {
+            // Assuming physical address == virtual address for `table`
+            ptr: (table as *mut u8).wrapping_add(0b11),
+        }
+    }
+
+    /// Construct a block descriptor.
+    const fn block(pa: u64, attr: MemoryRegionAttributes) -> Self {
+        assert!(pa & 0x1f_ffff == 0);
+
+        let MemoryRegionAttributes {
+            attr_index,
+            sh,
+            ap,
+            pxn,
+        } = attr;
+        let ns = false; // Secure access
+        let af = true; // Access Flag (don't generate Access flag faults)
+        let ng = false; // global (not Not-Global)
+        let uxn = true; // EL0 is unused
+
+        Self {
+            int: pa
+                | ((uxn as u64) << 54)
+                | ((pxn as u64) << 53)
+                | ((ng as u64) << 11)
+                | ((af as u64) << 10)
+                | ((sh as u64) << 8)
+                | ((ap as u64) << 6)
+                | ((ns as u64) << 5)
+                | ((attr_index as u64) << 2)
+                | 0b01,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/// The configuration of the port.
+pub trait ThreadingOptions {}
+
+/// An abstract interface to an interrupt controller. Implemented by
+/// [`use_gic!`].
+pub trait InterruptController {
+    /// Initialize the driver. This will be called just before entering
+    /// [`PortToKernel::boot`].
+    ///
+    /// [`PortToKernel::boot`]: r3_kernel::PortToKernel::boot
+    ///
+    /// # Safety
+    ///
+    /// This is only intended to be called by the port.
+    unsafe fn init() {}
+
+    /// Get the currently signaled interrupt and acknowledge it.
+    ///
+    /// # Safety
+    ///
+    /// This is only intended to be called by the port in an IRQ handler.
+    unsafe fn acknowledge_interrupt() -> Option<r3_core::kernel::InterruptNum>;
+
+    /// Notify that the kernel has completed the processing of the specified
+    /// interrupt.
+    ///
+    /// # Safety
+    ///
+    /// This is only intended to be called by the port in an IRQ handler.
+    unsafe fn end_interrupt(num: r3_core::kernel::InterruptNum);
+}
+
+/// An abstract inferface to a port timer driver. Implemented by
+/// [`use_generic_timer!`].
+pub trait Timer {
+    /// Initialize the driver. This will be called just before entering
+    /// [`PortToKernel::boot`].
+    ///
+    /// [`PortToKernel::boot`]: r3_kernel::PortToKernel::boot
+    ///
+    /// # Safety
+    ///
+    /// This is only intended to be called by the port.
+    unsafe fn init() {}
+}
+
+/// Defines the entry points of a port instantiation. Implemented by
+/// [`use_port!`].
+pub trait EntryPoint {
+    /// Proceed with the boot process.
+    ///
+    /// # Safety
+    ///
+    ///  - The processor should be in EL1 with `SPSel == 1` (EL1h).
+    ///  - `VBAR_EL1` should point to [`Self::EXCEPTION_VECTOR_TABLE`].
+    ///  - This method hasn't been entered yet.
+    ///
+    unsafe fn start() -> !;
+
+    /// The exception vector table for EL1. This is not a real function; its
+    /// address, which is aligned to 2KiB, should be loaded to `VBAR_EL1`.
+    ///
+    /// The table routes IRQs to the kernel's interrupt handler. Synchronous
+    /// exceptions and all other exceptions are treated as fatal errors and
+    /// cause a panic.
+    ///
+    /// # Safety
+    ///
+    /// This is not a real function and must not be called.
+    const EXCEPTION_VECTOR_TABLE: unsafe extern "C" fn() -> !;
+}
+
+/// Define a kernel trait type implementing [`PortThreading`] and
+/// [`EntryPoint`]. **Requires [`ThreadingOptions`], [`InterruptController`],
+/// and [`Timer`].**
+///
+/// [`PortThreading`]: r3_kernel::PortThreading
+#[macro_export]
+macro_rules! use_port {
+    (unsafe $vis:vis struct $Traits:ident) => {
+        $vis struct $Traits;
+
+        mod port_aarch64_impl {
+            use super::$Traits;
+            use $crate::r3_kernel::{TaskCb, PortThreading};
+            use $crate::threading::{
+                imp::{State, TaskState, PortInstance},
+                cfg::EntryPoint,
+            };
+
+            static PORT_STATE: State = $crate::r3_core::utils::Init::INIT;
+
+            unsafe impl PortInstance for $Traits {
+                $crate::r3_portkit::sym::sym_static!(
+                    #[sym(p_port_state)] fn port_state() -> &State { &PORT_STATE });
+            }
+
+            impl EntryPoint for $Traits {
+                #[inline]
+                unsafe fn start() -> ! {
+                    unsafe { PORT_STATE.port_boot::<Self>() }
+                }
+
+                const EXCEPTION_VECTOR_TABLE: unsafe extern "C" fn() -> ! =
+                    $crate::threading::imp::exception_vector_table::<Self>;
+            }
+
+            // Assume `$Traits: Kernel`
+            unsafe impl PortThreading for $Traits {
+                type PortTaskState = TaskState;
+                #[allow(clippy::declare_interior_mutable_const)]
+                const PORT_TASK_STATE_INIT: Self::PortTaskState =
+                    $crate::r3_core::utils::Init::INIT;
+
+                // The minimum stack size for all tests to pass. I found debug
+                // formatting to be particularly memory-hungry.
+                const STACK_DEFAULT_SIZE: usize = 4096;
+
+                // AAPCS64: "At any point at which memory is accessed via SP,
+                // the hardware requires that SP mod 16 = 0. The stack must be
+                // quad-word aligned."
+                const STACK_ALIGN: usize = 16;
+
+                #[inline(always)]
+                unsafe fn dispatch_first_task() -> ! {
+                    PORT_STATE.dispatch_first_task::<Self>()
+                }
+
+                #[inline(always)]
+                unsafe fn yield_cpu() {
+                    PORT_STATE.yield_cpu::<Self>()
+                }
+
+                #[inline(always)]
+                unsafe fn exit_and_dispatch(task: &'static TaskCb<Self>) -> ! {
+                    PORT_STATE.exit_and_dispatch::<Self>(task)
+                }
+
+                #[inline(always)]
+                unsafe fn enter_cpu_lock() {
+                    PORT_STATE.enter_cpu_lock::<Self>()
+                }
+
+                #[inline(always)]
+                unsafe fn leave_cpu_lock() {
+                    PORT_STATE.leave_cpu_lock::<Self>()
+                }
+
+                #[inline(always)]
+                unsafe fn initialize_task_state(task: &'static TaskCb<Self>) {
+                    PORT_STATE.initialize_task_state::<Self>(task)
+                }
+
+                #[inline(always)]
+                fn is_cpu_lock_active() -> bool {
+                    PORT_STATE.is_cpu_lock_active::<Self>()
+                }
+
+                #[inline(always)]
+                fn is_task_context() -> bool {
+                    PORT_STATE.is_task_context::<Self>()
+                }
+
+                #[inline(always)]
+                fn is_interrupt_context() -> bool {
+                    PORT_STATE.is_interrupt_context::<Self>()
+                }
+
+                #[inline(always)]
+                fn is_scheduler_active() -> bool {
+                    PORT_STATE.is_scheduler_active::<Self>()
+                }
+            }
+        }
+
+        const _: () = $crate::threading::imp::validate::<$Traits>();
+    };
+}
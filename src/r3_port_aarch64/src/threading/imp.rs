@@ -0,0 +1,789 @@
+use core::{arch::asm, cell::UnsafeCell, mem::MaybeUninit, slice};
+use memoffset::offset_of;
+use r3_core::{kernel::traits, utils::Init};
+use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb};
+use r3_portkit::{pptext::pp_asm, sym::sym_static};
+
+use super::cfg::{InterruptController, ThreadingOptions, Timer};
+
+/// Implemented on a kernel trait type by [`use_port!`].
+///
+/// # Safety
+///
+/// Only meant to be implemented by [`use_port!`].
+pub unsafe trait PortInstance:
+    KernelTraits + Port<PortTaskState = TaskState> + ThreadingOptions + InterruptController + Timer
+{
+    sym_static!(#[sym(p_port_state)] fn port_state() -> &State);
+}
+
+#[repr(C)]
+pub struct State {
+    dispatch_pending: UnsafeCell<bool>,
+    main_stack: UnsafeCell<usize>,
+    /// Stores the value of `System::state().running_task_ptr()` so that it can
+    /// be accessed in naked functions. This field is actually of type
+    /// `*mut Option<&'static TaskCb<System>>`.
+    running_task_ptr: UnsafeCell<*mut ()>,
+}
+
+impl State {
+    const OFFSET_DISPATCH_PENDING: usize = offset_of!(State, dispatch_pending);
+    const OFFSET_MAIN_STACK: usize = offset_of!(State, main_stack);
+    const OFFSET_RUNNING_TASK_PTR: usize = offset_of!(State, running_task_ptr);
+}
+
+unsafe impl Sync for State {}
+
+impl Init for State {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        dispatch_pending: UnsafeCell::new(false),
+        main_stack: UnsafeCell::new(0),
+        running_task_ptr: UnsafeCell::new(core::ptr::null_mut()),
+    };
+}
+
+/// The size of the first-level state, measured in bytes
+const FLS_SIZE: usize = if cfg!(target_feature = "neon") {
+    0x2c0
+} else {
+    0xb0
+};
+/// The offset of `x18` in the first-level state
+const FLS_OFFSET_X18: usize = 0x90;
+/// The offset of `elr` in the first-level state
+const FLS_OFFSET_ELR: usize = 0xa0;
+/// The offset of `fpcr` in the first-level state
+const FLS_OFFSET_FPCR: usize = 0xb0;
+/// The size of the second-level state, measured in bytes
+const SLS_SIZE: usize = 0x60;
+
+/// The initial value of `SPSR_EL1` of a task: EL1t, with debug exceptions,
+/// SErrors, and FIQs masked
+const TASK_INITIAL_SPSR: u64 = 0x344;
+
+/// The assembly code fragments used by `pp_asm!`. Because of a mysterious macro
+/// hygienics behavior, they have to referred to by absolute paths.
+#[rustfmt::skip]
+#[doc(hidden)]
+pub mod asm_inc {
+    // push_first_level_state - pushes the integer portion of the first-level
+    // state to the current stack. Clobbers `x0` and `x1` after saving them.
+    // Requires a `FLS_SIZE` operand.
+    // -----------------------------------------------------------------
+    pub macro push_first_level_state() {r"
+        sub sp, sp, #{FLS_SIZE}
+        stp x0, x1, [sp, #0x00]
+        stp x2, x3, [sp, #0x10]
+        stp x4, x5, [sp, #0x20]
+        stp x6, x7, [sp, #0x30]
+        stp x8, x9, [sp, #0x40]
+        stp x10, x11, [sp, #0x50]
+        stp x12, x13, [sp, #0x60]
+        stp x14, x15, [sp, #0x70]
+        stp x16, x17, [sp, #0x80]
+        stp x18, x30, [sp, #0x90]
+        mrs x0, elr_el1
+        mrs x1, spsr_el1
+        stp x0, x1, [sp, #0xa0]
+    "}
+
+    // pop_first_level_state - the opposite of `push_first_level_state`. This
+    // must be preceded by `pop_first_level_fp_state`.
+    // -----------------------------------------------------------------
+    pub macro pop_first_level_state() {r"
+        ldp x0, x1, [sp, #0xa0]
+        msr elr_el1, x0
+        msr spsr_el1, x1
+        ldp x2, x3, [sp, #0x10]
+        ldp x4, x5, [sp, #0x20]
+        ldp x6, x7, [sp, #0x30]
+        ldp x8, x9, [sp, #0x40]
+        ldp x10, x11, [sp, #0x50]
+        ldp x12, x13, [sp, #0x60]
+        ldp x14, x15, [sp, #0x70]
+        ldp x16, x17, [sp, #0x80]
+        ldp x18, x30, [sp, #0x90]
+        ldp x0, x1, [sp, #0x00]
+        add sp, sp, #{FLS_SIZE}
+    "}
+
+    // push_first_level_fp_state - pushes the SIMD/FP portion of the
+    // first-level state. Must follow `push_first_level_state`. Clobbers `x0`
+    // and `x1`.
+    // -----------------------------------------------------------------
+    #[cfg(target_feature = "neon")]
+    pub macro push_first_level_fp_state() {r"
+        mrs x0, fpcr
+        mrs x1, fpsr
+        stp x0, x1, [sp, #0xb0]
+        stp q0, q1, [sp, #0xc0]
+        stp q2, q3, [sp, #0xe0]
+        stp q4, q5, [sp, #0x100]
+        stp q6, q7, [sp, #0x120]
+        stp q8, q9, [sp, #0x140]
+        stp q10, q11, [sp, #0x160]
+        stp q12, q13, [sp, #0x180]
+        stp q14, q15, [sp, #0x1a0]
+        stp q16, q17, [sp, #0x1c0]
+        stp q18, q19, [sp, #0x1e0]
+        stp q20, q21, [sp, #0x200]
+        stp q22, q23, [sp, #0x220]
+        stp q24, q25, [sp, #0x240]
+        stp q26, q27, [sp, #0x260]
+        stp q28, q29, [sp, #0x280]
+        stp q30, q31, [sp, #0x2a0]
+    "}
+
+    #[cfg(not(target_feature = "neon"))]
+    pub macro push_first_level_fp_state() {""}
+
+    // pop_first_level_fp_state - the opposite of `push_first_level_fp_state`.
+    // Clobbers `x0` and `x1`.
+    // -----------------------------------------------------------------
+    #[cfg(target_feature = "neon")]
+    pub macro pop_first_level_fp_state() {r"
+        ldp q0, q1, [sp, #0xc0]
+        ldp q2, q3, [sp, #0xe0]
+        ldp q4, q5, [sp, #0x100]
+        ldp q6, q7, [sp, #0x120]
+        ldp q8, q9, [sp, #0x140]
+        ldp q10, q11, [sp, #0x160]
+        ldp q12, q13, [sp, #0x180]
+        ldp q14, q15, [sp, #0x1a0]
+        ldp q16, q17, [sp, #0x1c0]
+        ldp q18, q19, [sp, #0x1e0]
+        ldp q20, q21, [sp, #0x200]
+        ldp q22, q23, [sp, #0x220]
+        ldp q24, q25, [sp, #0x240]
+        ldp q26, q27, [sp, #0x260]
+        ldp q28, q29, [sp, #0x280]
+        ldp q30, q31, [sp, #0x2a0]
+        ldp x0, x1, [sp, #0xb0]
+        msr fpcr, x0
+        msr fpsr, x1
+    "}
+
+    #[cfg(not(target_feature = "neon"))]
+    pub macro pop_first_level_fp_state() {""}
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct TaskState {
+    sp: UnsafeCell<u64>,
+}
+
+unsafe impl Sync for TaskState {}
+
+impl Init for TaskState {
+    #[allow(clippy::declare_interior_mutable_const)] // it's intentional
+    const INIT: Self = Self {
+        sp: UnsafeCell::new(0),
+    };
+}
+
+impl State {
+    #[inline(always)]
+    pub unsafe fn port_boot<Traits: PortInstance>(&self) -> ! {
+        unsafe { self.enter_cpu_lock::<Traits>() };
+
+        unsafe { *self.running_task_ptr.get() = Traits::state().running_task_ptr().cast() };
+
+        // Safety: We are the port, so it's okay to call this
+        unsafe { <Traits as InterruptController>::init() };
+
+        // Safety: We are the port, so it's okay to call this
+        unsafe { <Traits as Timer>::init() };
+
+        // Safety: We are the port, so it's okay to call this
+        unsafe { <Traits as PortToKernel>::boot() };
+    }
+
+    #[inline(always)]
+    pub unsafe fn dispatch_first_task<Traits: PortInstance>(&'static self) -> ! {
+        debug_assert!(self.is_cpu_lock_active::<Traits>());
+
+        unsafe {
+            asm!("
+                mov x0, sp
+
+                # Save the stack pointer for later use
+                # [tag:aarch64_main_stack_assigned_in_dft]
+                str x0, [x1]
+
+                # Switch to `SP_EL0` (task context)
+                msr spsel, #0
+
+                # `dispatch` needs stack
+                mov sp, x0
+
+                b {push_second_level_state_and_dispatch}.dispatch
+                ",
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                in("x1") self.main_stack.get(),
+                options(noreturn),
+            );
+        }
+    }
+
+    #[inline(never)] // avoid symbol collision with `YieldReturn`
+    pub unsafe fn yield_cpu<Traits: PortInstance>(&'static self) {
+        if !self.is_task_context::<Traits>() {
+            unsafe { self.dispatch_pending.get().write_volatile(true) };
+            return;
+        }
+
+        unsafe {
+            pp_asm!("
+                # Push the first-level context state. The return address is
+                # set to `YieldReturn`. The value of DAIF is captured before
+                # `msr daifset` so that interrupts are re-enabled when the
+                # current task regains the control.
+                #
+                # All other registers in the first-level state are
+                # caller-saved and declared as clobbered by `clobber_abi`, so
+                # their contents don't have to be saved here. `x18` is saved
+                # in case it's reserved as a platform register.
+                #
+                #   sp_el0 -= FLS_SIZE;
+                #   sp_el0.x18 = x18;
+                #   sp_el0.elr = &YieldReturn;
+                #   sp_el0.spsr = DAIF | EL1t;
+                #   sp_el0.fpcr = FPCR;
+                #   sp_el0.fpsr = FPSR;
+                #
+                sub sp, sp, #{FLS_SIZE}
+                str x18, [sp, #{FLS_OFFSET_X18}]
+                adr x0, 0f
+                mrs x1, daif
+                orr x1, x1, #0x4
+                stp x0, x1, [sp, #{FLS_OFFSET_ELR}]
+            "   if cfg!(target_feature = "neon") {                                  "
+                mrs x0, fpcr
+                mrs x1, fpsr
+                stp x0, x1, [sp, #{FLS_OFFSET_FPCR}]
+            "   } else {                                                            "
+                # unused: {FLS_OFFSET_FPCR}
+            "   }                                                                   "
+
+                msr daifset, #2
+                b {push_second_level_state_and_dispatch}
+
+            0:        # YieldReturn
+                ",
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                FLS_SIZE = const FLS_SIZE,
+                FLS_OFFSET_X18 = const FLS_OFFSET_X18,
+                FLS_OFFSET_ELR = const FLS_OFFSET_ELR,
+                FLS_OFFSET_FPCR = const FLS_OFFSET_FPCR,
+                clobber_abi("C"),
+            );
+        }
+    }
+
+    /// Do the following steps:
+    ///
+    ///  - **Don't** push the first-level state.
+    ///  - If the current task is not an idle task,
+    ///     - Push the second-level state.
+    ///     - Store SP to the current task's `TaskState`.
+    ///  - **`dispatch:`** (alternate entry point)
+    ///     - Call [`r3_kernel::PortToKernel::choose_running_task`].
+    ///     - Restore SP from the next scheduled task's `TaskState`.
+    ///  - If there's no task to schedule, branch to [`Self::idle_task`].
+    ///  - Pop the second-level state of the next scheduled task.
+    ///  - **`pop_first_level_state:`** (alternate entry point)
+    ///     - Pop the first-level state of the next scheduled task.
+    ///
+    /// # Safety
+    ///
+    ///  - The processor should be in EL1t (task context).
+    ///  - IRQs should be masked.
+    ///  - If the current task is an idle task, SP should point to the
+    ///    first-level state on the current task's stack. Otherwise, SP must be
+    ///    zero.
+    ///  - This function may overwrite any contents in the main stack.
+    ///
+    #[naked]
+    unsafe extern "C" fn push_second_level_state_and_dispatch<Traits: PortInstance>() -> ! {
+        extern "C" fn choose_and_get_next_task<Traits: PortInstance>(
+        ) -> Option<&'static TaskCb<Traits>> {
+            // Safety: CPU Lock active
+            unsafe { Traits::choose_running_task() };
+
+            unsafe { *Traits::state().running_task_ptr() }
+        }
+
+        unsafe {
+            pp_asm!("
+                adrp x0, {p_port_state}_
+                ldr x0, [x0, #:lo12:{p_port_state}_]
+
+                # Skip saving the second-level state if the current context
+                # is an idle task. Also, in this case, we don't have a stack,
+                # but `choose_and_get_next_task` needs one. Therefore we borrow
+                # the main stack.
+                #
+                #   if sp_el0 == 0:
+                #       <running_task is None>
+                #       sp_el0 = *main_stack_ptr;
+                #   else:
+                #       /* ... */
+                #
+                #   choose_and_get_next_task();
+                #
+                mov x1, sp
+                cbnz x1, 0f
+                ldr x1, [x0, #{OFFSET_MAIN_STACK}]
+                mov sp, x1
+                b {push_second_level_state_and_dispatch}.dispatch
+            0:
+
+                # Push the second-level context state.
+                sub sp, sp, #{SLS_SIZE}
+                stp x19, x20, [sp, #0x00]
+                stp x21, x22, [sp, #0x10]
+                stp x23, x24, [sp, #0x20]
+                stp x25, x26, [sp, #0x30]
+                stp x27, x28, [sp, #0x40]
+                str x29, [sp, #0x50]
+
+                # Store SP to `TaskState`.
+                #
+                #    <x0 = &port_state>
+                #    x0 = *port_state.running_task_ptr // == running_task
+                #    x0.port_task_state.sp = sp_el0
+                #
+                ldr x0, [x0, #{OFFSET_RUNNING_TASK_PTR}]
+                ldr x0, [x0]
+                mov x1, sp
+                str x1, [x0]
+
+            .global {push_second_level_state_and_dispatch}.dispatch
+            {push_second_level_state_and_dispatch}.dispatch:
+                # Choose the next task to run. `choose_and_get_next_task`
+                # returns the new value of `running_task`.
+                bl {choose_and_get_next_task}
+
+                # Restore SP from `TaskState`
+                #
+                #    <x0 = running_task>
+                #    if x0.is_none():
+                #        goto idle_task;
+                #
+                #    sp_el0 = x0.port_task_state.sp
+                #
+                cbnz x0, 0f
+                b {idle_task}
+            0:
+                ldr x1, [x0]
+                mov sp, x1
+
+                # Pop the second-level context state.
+                ldp x19, x20, [sp, #0x00]
+                ldp x21, x22, [sp, #0x10]
+                ldp x23, x24, [sp, #0x20]
+                ldp x25, x26, [sp, #0x30]
+                ldp x27, x28, [sp, #0x40]
+                ldr x29, [sp, #0x50]
+                add sp, sp, #{SLS_SIZE}
+
+            .global {push_second_level_state_and_dispatch}.pop_first_level_state
+            {push_second_level_state_and_dispatch}.pop_first_level_state:
+                # Reset the local monitor's state (this will cause a
+                # subsequent Store-Exclusive to fail)
+                clrex
+
+                # Resume the next task by restoring the first-level state
+                #
+                #   <[x19-x29, sp_el0] = resumed context>
+                #
+                #   [q0-q31, fpcr, fpsr] = sp_el0.fp_regs;
+                #   [elr_el1, spsr_el1] = [sp_el0.elr, sp_el0.spsr];
+                #   [x0-x18, x30] = sp_el0.x_regs;
+                #   sp_el0 += FLS_SIZE;
+                #   eret;
+                #
+                #   <end of procedure>
+                #
+            "   crate::threading::imp::asm_inc::pop_first_level_fp_state!()         "
+            "   crate::threading::imp::asm_inc::pop_first_level_state!()            "
+                eret
+            ",
+                choose_and_get_next_task = sym choose_and_get_next_task::<Traits>,
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                idle_task = sym Self::idle_task::<Traits>,
+                p_port_state = sym Traits::p_port_state,
+                OFFSET_RUNNING_TASK_PTR = const Self::OFFSET_RUNNING_TASK_PTR,
+                OFFSET_MAIN_STACK = const Self::OFFSET_MAIN_STACK,
+                FLS_SIZE = const FLS_SIZE,
+                SLS_SIZE = const SLS_SIZE,
+                options(noreturn),
+            );
+        }
+    }
+
+    /// Branch to `push_second_level_state_and_dispatch` if `dispatch_pending`
+    /// is set. Otherwise, branch to `pop_first_level_state` (thus skipping the
+    /// saving/restoration of second-level states).
+    #[naked]
+    unsafe extern "C" fn push_second_level_state_and_dispatch_shortcutting<Traits: PortInstance>(
+    ) -> ! {
+        unsafe {
+            asm!("
+                # Read `port_state().dispatch_pending`. If it's set, branch
+                # to `NotShortcutting`
+                adrp x0, {p_port_state}_
+                ldr x0, [x0, #:lo12:{p_port_state}_]
+                ldrb w1, [x0, #{OFFSET_DISPATCH_PENDING}]
+                cbnz w1, 0f
+
+                # `dispatch_pending` is clear, meaning we are returning to the
+                # same task that the current exception has interrupted.
+                #
+                # If we are returning to the idle task, branch to `idle_task`
+                # directly because `pop_first_level_state` can't handle this
+                # case.
+                mov x1, sp
+                cbnz x1, 1f
+                b {idle_task}
+            1:
+                b {push_second_level_state_and_dispatch}.pop_first_level_state
+
+                # `dispatch_pending` is set, meaning `yield_cpu` was called in
+                # an interrupt handler, meaning we might need to return to a
+                # different task. Clear `dispatch_pending` and branch to
+                # `push_second_level_state_and_dispatch`.
+            0:                  # NotShortcutting
+                strb wzr, [x0, #{OFFSET_DISPATCH_PENDING}]
+                b {push_second_level_state_and_dispatch}
+            ",
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                idle_task = sym Self::idle_task::<Traits>,
+                p_port_state = sym Traits::p_port_state,
+                OFFSET_DISPATCH_PENDING = const Self::OFFSET_DISPATCH_PENDING,
+                options(noreturn),
+            );
+        }
+    }
+
+    /// Enters an idle loop with IRQs unmasked.
+    ///
+    /// When context switching to the idle task, you don't need to execute
+    /// `clrex`.
+    ///
+    /// # Safety
+    ///
+    ///  - The processor should be in EL1t (task context).
+    ///  - `*Traits::state().running_task_ptr()` should be `None`.
+    ///
+    #[naked]
+    unsafe extern "C" fn idle_task<Traits: PortInstance>() -> ! {
+        unsafe {
+            asm!(
+                "
+                mov x0, #0
+                mov sp, x0
+                msr daifclr, #2
+            0:
+                # Ensure all outstanding memory transactions are complete before
+                # halting the processor
+                dsb sy
+                wfi
+                b 0b
+            ",
+                options(noreturn),
+            );
+        }
+    }
+
+    #[inline(always)]
+    pub unsafe fn exit_and_dispatch<Traits: PortInstance>(
+        &'static self,
+        _task: &'static TaskCb<Traits>,
+    ) -> ! {
+        unsafe {
+            asm!(
+                "
+                msr daifset, #2
+                b {push_second_level_state_and_dispatch}.dispatch
+                ",
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                options(noreturn),
+            );
+        }
+    }
+
+    #[inline(always)]
+    pub unsafe fn enter_cpu_lock<Traits: PortInstance>(&self) {
+        // TODO: support unmanaged interrupts
+        unsafe { asm!("msr daifset, #2") };
+    }
+
+    #[inline(always)]
+    pub unsafe fn leave_cpu_lock<Traits: PortInstance>(&'static self) {
+        unsafe { asm!("msr daifclr, #2") };
+    }
+
+    pub unsafe fn initialize_task_state<Traits: PortInstance>(
+        &self,
+        task: &'static TaskCb<Traits>,
+    ) {
+        let stack: *mut [u8] = task.attr.stack.as_ptr();
+        let stack_end = stack.as_mut_ptr().wrapping_add(stack.len());
+        // Align the initial SP to 16 bytes (AAPCS64)
+        let mut sp = stack_end
+            .wrapping_sub(stack_end as usize % 16)
+            .cast::<MaybeUninit<u64>>();
+        // TODO: Enforce minimum stack size
+
+        let preload_all = cfg!(feature = "preload-registers");
+
+        // First-level state (always saved and restored as part of our exception
+        // entry/return sequence)
+        let first_level = unsafe {
+            sp = sp.wrapping_sub(FLS_SIZE / 8);
+            slice::from_raw_parts_mut(sp, FLS_SIZE / 8)
+        };
+
+        // X0: Parameter to the entry point
+        first_level[0] = unsafe { core::mem::transmute(task.attr.entry_param) };
+        // X1-X18: Uninitialized
+        if preload_all {
+            for (i, x) in first_level[1..19].iter_mut().enumerate() {
+                *x = MaybeUninit::new(0x0101010101010101 * (i as u64 + 1));
+            }
+        }
+        // X30: The return address
+        first_level[19] =
+            MaybeUninit::new(<System<Traits> as traits::KernelBase>::raw_exit_task as usize as u64);
+        // ELR: The entry point
+        first_level[20] = MaybeUninit::new(task.attr.entry_point as usize as u64);
+        // SPSR: EL1t
+        first_level[21] = MaybeUninit::new(TASK_INITIAL_SPSR);
+
+        // FPCR, FPSR: The default settings. Q0-Q31: Uninitialized
+        if cfg!(target_feature = "neon") {
+            first_level[22] = MaybeUninit::new(0);
+            first_level[23] = MaybeUninit::new(0);
+        }
+
+        // Second-level state (saved and restored only when we are doing context
+        // switching)
+        let extra_ctx = unsafe {
+            sp = sp.wrapping_sub(SLS_SIZE / 8);
+            slice::from_raw_parts_mut(sp, SLS_SIZE / 8)
+        };
+
+        // X19-X29: Uninitialized
+        if preload_all {
+            for (i, x) in extra_ctx[0..11].iter_mut().enumerate() {
+                *x = MaybeUninit::new(0x0101010101010101 * (i as u64 + 0x19));
+            }
+        }
+
+        let task_state = &task.port_task_state;
+        unsafe { *task_state.sp.get() = sp as _ };
+    }
+
+    #[inline(always)]
+    pub fn is_cpu_lock_active<Traits: PortInstance>(&self) -> bool {
+        let daif: u64;
+        unsafe { asm!("mrs {}, daif", out(reg) daif) };
+        (daif & (1 << 7)) != 0
+    }
+
+    #[inline(always)]
+    pub fn is_task_context<Traits: PortInstance>(&self) -> bool {
+        let spsel: u64;
+        unsafe { asm!("mrs {}, spsel", out(reg) spsel) };
+        spsel == 0 // EL1t
+    }
+
+    #[inline]
+    pub fn is_interrupt_context<Traits: PortInstance>(&self) -> bool {
+        self.is_scheduler_active::<Traits>() && !self.is_task_context::<Traits>()
+    }
+
+    #[inline]
+    pub fn is_scheduler_active<Traits: PortInstance>(&self) -> bool {
+        // `main_stack` is assigned by `dispatch_first_task`
+        // [ref:aarch64_main_stack_assigned_in_dft]
+        unsafe { *self.main_stack.get() != 0 }
+    }
+
+    /// The IRQ handler for an interrupted task context (EL1t).
+    #[naked]
+    unsafe extern "C" fn irq_entry_sp0<Traits: PortInstance>() -> ! {
+        unsafe {
+            pp_asm!("
+                # Skip saving the first-level state if the background context
+                # is an idle task. We need a scratch register to check this,
+                # so temporarily save `x0` and `x1` to the main stack.
+                #
+                #   <[x0-x30, sp_el0, ELR, SPSR] = background context>
+                #
+                #   if sp_el0 == 0:
+                #       <running_task is None>
+                #       goto PushFirstLevelStateEnd;
+                #
+                stp x0, x1, [sp, #-16]!
+                mrs x0, sp_el0
+                cbnz x0, 0f
+                add sp, sp, #16
+                b 1f
+            0:
+                ldp x0, x1, [sp], #16
+
+                # Save the first-level state to the background context's stack
+                # (`sp_el0`)
+                msr spsel, #0
+            "   crate::threading::imp::asm_inc::push_first_level_state!()           "
+            "   crate::threading::imp::asm_inc::push_first_level_fp_state!()        "
+                msr spsel, #1
+            1:     # PushFirstLevelStateEnd
+
+                # Call `handle_irq` on the main stack
+                bl {handle_irq}
+
+                # Return to the task context by restoring the first-level and
+                # second-level state of the next task.
+                msr daifset, #2
+                msr spsel, #0
+                b {push_second_level_state_and_dispatch_shortcutting}
+                ",
+                handle_irq = sym Self::handle_irq::<Traits>,
+                push_second_level_state_and_dispatch_shortcutting =
+                    sym Self::push_second_level_state_and_dispatch_shortcutting::<Traits>,
+                FLS_SIZE = const FLS_SIZE,
+                options(noreturn),
+            );
+        }
+    }
+
+    /// The IRQ handler for an interrupted interrupt handler (EL1h).
+    #[naked]
+    unsafe extern "C" fn irq_entry_spx<Traits: PortInstance>() -> ! {
+        unsafe {
+            pp_asm!("
+                # Save the first-level state to the main stack
+            "   crate::threading::imp::asm_inc::push_first_level_state!()           "
+            "   crate::threading::imp::asm_inc::push_first_level_fp_state!()        "
+
+                bl {handle_irq}
+
+                # We are returning to an outer interrupt handler. Finding the
+                # next task to dispatch is unnecessary in this case.
+                msr daifset, #2
+                clrex
+            "   crate::threading::imp::asm_inc::pop_first_level_fp_state!()         "
+            "   crate::threading::imp::asm_inc::pop_first_level_state!()            "
+                eret
+                ",
+                handle_irq = sym Self::handle_irq::<Traits>,
+                FLS_SIZE = const FLS_SIZE,
+                options(noreturn),
+            );
+        }
+    }
+
+    unsafe fn handle_irq<Traits: PortInstance>() {
+        // Safety: We are the port, so it's okay to call this
+        let Some(line) = (unsafe { Traits::acknowledge_interrupt() }) else { return };
+
+        // Now that we have signaled the acknowledgement of the current
+        // exception, we can start accepting nested exceptions.
+        unsafe { asm!("msr daifclr, #2") };
+
+        if let Some(handler) = Traits::INTERRUPT_HANDLERS.get(line) {
+            // Safety: The first-level interrupt handler is the only code
+            //         allowed to call this
+            unsafe { handler() };
+        }
+
+        // Safety: We are the port, so it's okay to call this
+        unsafe { Traits::end_interrupt(line) };
+    }
+}
+
+/// Implements [`crate::EntryPoint::EXCEPTION_VECTOR_TABLE`]
+#[naked]
+#[repr(align(2048))]
+pub unsafe extern "C" fn exception_vector_table<Traits: PortInstance>() -> ! {
+    unsafe {
+        asm!("
+            # Current EL with SP0
+            b {synchronous_exception_handler}
+            .balign 0x80
+            b {irq_entry_sp0}
+            .balign 0x80
+            b {fiq_handler}
+            .balign 0x80
+            b {serror_handler}
+            .balign 0x80
+
+            # Current EL with SPx
+            b {synchronous_exception_handler}
+            .balign 0x80
+            b {irq_entry_spx}
+            .balign 0x80
+            b {fiq_handler}
+            .balign 0x80
+            b {serror_handler}
+            .balign 0x80
+
+            # Lower EL using AArch64, Lower EL using AArch32
+            .rept 8
+            b {lower_el_exception_handler}
+            .balign 0x80
+            .endr
+        ",
+            synchronous_exception_handler = sym synchronous_exception_handler,
+            irq_entry_sp0 = sym State::irq_entry_sp0::<Traits>,
+            irq_entry_spx = sym State::irq_entry_spx::<Traits>,
+            fiq_handler = sym fiq_handler,
+            serror_handler = sym serror_handler,
+            lower_el_exception_handler = sym lower_el_exception_handler,
+            options(noreturn),
+        );
+    }
+}
+
+extern "C" fn synchronous_exception_handler() -> ! {
+    let (esr, elr, far): (u64, u64, u64);
+    unsafe {
+        asm!(
+            "mrs {}, esr_el1",
+            "mrs {}, elr_el1",
+            "mrs {}, far_el1",
+            out(reg) esr,
+            out(reg) elr,
+            out(reg) far,
+        )
+    };
+    panic!("unhandled synchronous exception (ESR_EL1 = {esr:#x}, ELR_EL1 = {elr:#x}, FAR_EL1 = {far:#x})");
+}
+
+extern "C" fn fiq_handler() -> ! {
+    panic!("unexpected fiq");
+}
+
+extern "C" fn serror_handler() -> ! {
+    let esr: u64;
+    unsafe { asm!("mrs {}, esr_el1", out(reg) esr) };
+    panic!("unexpected serror (ESR_EL1 = {esr:#x})");
+}
+
+extern "C" fn lower_el_exception_handler() -> ! {
+    panic!("unexpected exception from a lower exception level");
+}
+
+/// Used by `use_port!`
+pub const fn validate<Traits: PortInstance>() {}
@@ -0,0 +1,184 @@
+//! <div class="distractor"><a style="background-image:
+//! url(https://derpicdn.net/img/2019/6/30/2079083/medium.png);
+//! padding-bottom: 100%" href="http://derpibooru.org/2079083"
+//! title="Screwdriver"></a></div>
+#![doc = include_str!("./common.md")]
+#![feature(const_refs_to_cell)]
+#![feature(const_trait_impl)]
+#![feature(naked_functions)]
+#![feature(const_mut_refs)]
+#![feature(asm_const)]
+#![deny(unsafe_op_in_unsafe_fn)]
+#![cfg_attr(feature = "run", no_std)]
+#![cfg_attr(feature = "run", no_main)]
+#![recursion_limit = "1000"]
+
+#[cfg(feature = "output-semihosting")]
+mod logger_semihosting;
+
+#[cfg(feature = "output-semihosting")]
+mod panic_semihosting;
+
+#[cfg(feature = "kernel_benchmarks")]
+mod pmu;
+
+#[allow(unused_macros)]
+macro_rules! instantiate_test {
+    // If a test case is specified, instantiate the test case
+    ({ path: $path:path, name_ident: $ident:ident, $($tt:tt)* }, $($excess:tt)*) => {
+        // Only one test case can be specified
+        reject_excess!($($excess)*);
+
+        use r3::kernel::{StartupHook, InterruptPriority, InterruptNum};
+        #[cfg(feature = "kernel_benchmarks")]
+        use r3_test_suite::kernel_benchmarks;
+        #[cfg(feature = "kernel_tests")]
+        use r3_test_suite::kernel_tests;
+        use r3_port_aarch64 as port;
+        use $path as test_case;
+
+        fn report_success() {
+            // The test runner will catch this
+            #[cfg(feature = "output-semihosting")]
+            arm_semihosting::hprintln!("!- TEST WAS SUCCESSFUL -!").unwrap();
+
+            loop {}
+        }
+
+        fn report_fail() {
+            panic!("test failed");
+        }
+
+        type System = r3_kernel::System<SystemTraits>;
+        port::use_port!(unsafe struct SystemTraits);
+        port::use_startup!(unsafe SystemTraits);
+        #[cfg(feature = "board-qemu_virt")]
+        port::use_gic!(unsafe impl PortInterrupts for SystemTraits);
+        #[cfg(feature = "board-qemu_virt")]
+        port::use_generic_timer!(unsafe impl PortTimer for SystemTraits);
+
+        impl port::ThreadingOptions for SystemTraits {}
+
+        impl port::StartupOptions for SystemTraits {
+            #[cfg(feature = "board-qemu_virt")]
+            const MEMORY_MAP: &'static [port::MemoryMapSection] = &[
+                port::MemoryMapSection::new(0x4000_0000..0x4040_0000, 0x4000_0000)
+                    .with_executable(true)
+                    .with_writable(false),
+                port::MemoryMapSection::new(0x4040_0000..0x4080_0000, 0x4040_0000),
+                // GIC, UART, etc.
+                port::MemoryMapSection::new(0x0800_0000..0x0a00_0000, 0x0800_0000)
+                    .as_device_memory(),
+            ];
+        }
+
+        #[cfg(all(feature = "board-qemu_virt", not(feature = "gic-v3")))]
+        impl port::GicOptions for SystemTraits {
+            const GIC_DISTRIBUTOR_BASE: usize = 0x08000000;
+            const GIC_CPU_BASE: usize = 0x08010000;
+        }
+
+        #[cfg(all(feature = "board-qemu_virt", feature = "gic-v3"))]
+        impl port::GicOptions for SystemTraits {
+            const GIC_VERSION: port::GicVersion = port::GicVersion::V3;
+            const GIC_DISTRIBUTOR_BASE: usize = 0x08000000;
+            const GIC_REDISTRIBUTOR_BASE: usize = 0x080a0000;
+        }
+
+        #[cfg(feature = "board-qemu_virt")]
+        impl port::GenericTimerOptions for SystemTraits {
+            // The value of `CNTFRQ_EL0` on QEMU's `virt` machine (before 9.0)
+            const FREQUENCY: u64 = 62_500_000;
+            // Non-secure physical timer
+            const INTERRUPT_NUM: InterruptNum = 30;
+        }
+
+        struct Driver;
+
+        #[cfg(feature = "kernel_benchmarks")]
+        impl kernel_benchmarks::Driver<test_case::App<System>> for Driver {
+            fn app() -> &'static test_case::App<System> {
+                &COTTAGE
+            }
+            fn success() {
+                report_success();
+            }
+
+            fn performance_time() -> u32 {
+                pmu::cycle_count() as u32
+            }
+
+            const PERFORMANCE_TIME_UNIT: &'static str = "CPU cycles";
+
+            // Chose PPIs.
+            // SGIs (software-generated interrupts) don't support disabling.
+            const INTERRUPT_LINES: &'static [InterruptNum] = &[16, 17, 18, 19];
+            const INTERRUPT_PRIORITIES: &'static [InterruptPriority] = &[0x20, 0x60];
+        }
+
+        #[cfg(feature = "kernel_tests")]
+        impl kernel_tests::Driver<test_case::App<System>> for Driver {
+            type System = System;
+
+            fn app() -> &'static test_case::App<System> {
+                &COTTAGE
+            }
+            fn success() {
+                report_success();
+            }
+            fn fail() {
+                report_fail();
+            }
+
+            // Chose PPIs.
+            // SGIs (software-generated interrupts) don't support disabling.
+            const INTERRUPT_LINES: &'static [InterruptNum] = &[16, 17, 18, 19];
+            const INTERRUPT_PRIORITIES: &'static [InterruptPriority] = &[0x20, 0x60];
+        }
+
+        static COTTAGE: test_case::App<System> =
+            r3_kernel::build!(SystemTraits, configure_app => test_case::App<System>);
+
+        const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> test_case::App<System> {
+            #[cfg(feature = "board-qemu_virt")]
+            SystemTraits::configure_generic_timer(b);
+
+            // Start PMU cycle counter
+            #[cfg(feature = "kernel_benchmarks")]
+            StartupHook::define().start(|| {
+                pmu::start_cycle_counter();
+            }).finish(b);
+
+            // Redirect the log output to stderr
+            #[cfg(feature = "output-semihosting")]
+            StartupHook::define().start(|| {
+                logger_semihosting::init();
+            }).finish(b);
+
+            test_case::App::new::<_, Driver>(b)
+        }
+    };
+
+    () => {
+        compile_error!("no test is specified");
+    }
+}
+
+#[allow(unused_macros)]
+macro_rules! reject_excess {
+    () => {};
+    ($($tt:tt)*) => {
+        compile_error!("can't specify more than one test");
+    };
+}
+
+// Get the selected test case and instantiate
+#[cfg(feature = "kernel_benchmarks")]
+r3_test_suite::get_selected_kernel_benchmarks!(instantiate_test!());
+#[cfg(feature = "kernel_tests")]
+r3_test_suite::get_selected_kernel_tests!(instantiate_test!());
+
+#[cfg(not(feature = "run"))]
+fn main() {
+    panic!("This executable should not be invoked directly");
+}
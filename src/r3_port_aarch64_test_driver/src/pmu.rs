@@ -0,0 +1,45 @@
+//! Arm PMU
+use core::arch::asm;
+
+/// `PMCR_EL0.E`: Enable
+const PMCR_E: u64 = 1 << 0;
+
+/// `PMCR_EL0.D`: Clock divider (`0` = count every clock cycle)
+const PMCR_D: u64 = 1 << 3;
+
+/// `PMCNTENSET_EL0.C`: `PMCCNTR_EL0` enable bit
+const PMCNTENSET_C: u64 = 1 << 31;
+
+/// Start the cycle counter.
+pub fn start_cycle_counter() {
+    unsafe {
+        asm!(
+            "mrs {tmp}, pmcr_el0",
+            "orr {tmp}, {tmp}, #{PMCR_E}",
+            "bic {tmp}, {tmp}, #{PMCR_D}",
+            "msr pmcr_el0, {tmp}",
+            "mrs {tmp}, pmcntenset_el0",
+            "orr {tmp}, {tmp}, #{PMCNTENSET_C}",
+            "msr pmcntenset_el0, {tmp}",
+            "isb",
+            tmp = out(reg) _,
+            PMCR_E = const PMCR_E,
+            PMCR_D = const PMCR_D,
+            PMCNTENSET_C = const PMCNTENSET_C,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+/// Read the Performance Monitors Cycle Count Register (`PMCCNTR_EL0`).
+pub fn cycle_count() -> u64 {
+    let value;
+    unsafe {
+        asm!(
+            "mrs {}, pmccntr_el0",
+            lateout(reg) value,
+            options(nostack, preserves_flags),
+        )
+    };
+    value
+}
@@ -35,12 +35,23 @@ SECTIONS
     __ebss = .;
   } > RAM
 
+  /* ### .noinit */
+  /* Not initialized by the startup code or the loader. Used for data that
+     should survive a reset, such as crash records. */
+  .noinit (NOLOAD) : ALIGN(4)
+  {
+    __snoinit = .;
+    KEEP(*(.noinit .noinit.*));
+    . = ALIGN(4);
+    __enoinit = .;
+  } > RAM
+
   /* Initial, IRQ, and Abort stack */
   . += 4096;
   . = ALIGN(8);
   _stack_start = .;
 
-  /* Place the heap right after `.uninit` */
+  /* Place the heap right after `.noinit` */
   . = ALIGN(4);
   __sheap = .;
 
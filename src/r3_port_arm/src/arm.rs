@@ -37,9 +37,19 @@ mod bpiall;
 mod ccsidr;
 mod clidr;
 mod csselr;
+mod ctr;
 mod dacr;
+mod dccimvac;
+mod dccisw;
+mod dccmvac;
+mod dccsw;
+mod dcimvac;
 mod dcisw;
+mod dfar;
+mod dfsr;
 mod iciallu;
+mod ifar;
+mod ifsr;
 mod sctlr;
 mod tlbiall;
 mod ttbcr;
@@ -48,9 +58,19 @@ pub use self::bpiall::*;
 pub use self::ccsidr::*;
 pub use self::clidr::*;
 pub use self::csselr::*;
+pub use self::ctr::*;
 pub use self::dacr::*;
+pub use self::dccimvac::*;
+pub use self::dccisw::*;
+pub use self::dccmvac::*;
+pub use self::dccsw::*;
+pub use self::dcimvac::*;
 pub use self::dcisw::*;
+pub use self::dfar::*;
+pub use self::dfsr::*;
 pub use self::iciallu::*;
+pub use self::ifar::*;
+pub use self::ifsr::*;
 pub use self::sctlr::*;
 pub use self::tlbiall::*;
 pub use self::ttbcr::*;
@@ -0,0 +1,20 @@
+tock_registers::register_bitfields! {u32,
+    pub CTR [
+        /// Log2 of the number of words in the smallest cache line of all the
+        /// instruction caches that are controlled by the processor.
+        IminLine OFFSET(0) NUMBITS(4) [],
+        /// Log2 of the number of words in the smallest cache line of all the
+        /// data and unified caches that are controlled by the processor.
+        DminLine OFFSET(16) NUMBITS(4) []
+    ]
+}
+
+/// Cache Type Register
+pub const CTR: CTRAccessor = CTRAccessor;
+pub struct CTRAccessor;
+
+impl tock_registers::interfaces::Readable for CTRAccessor {
+    type T = u32;
+    type R = CTR::Register;
+    sys_coproc_read_raw!(u32, [p15, c0, 0, c0, 1]);
+}
@@ -0,0 +1,9 @@
+/// Data cache clean and invalidate by MVA to PoC
+pub const DCCIMVAC: DCCIMVACAccessor = DCCIMVACAccessor;
+pub struct DCCIMVACAccessor;
+
+impl tock_registers::interfaces::Writeable for DCCIMVACAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_write_raw!(u32, [p15, c7, 0, c14, 1]);
+}
@@ -0,0 +1,9 @@
+/// Data cache clean and invalidate by set/way
+pub const DCCISW: DCCISWAccessor = DCCISWAccessor;
+pub struct DCCISWAccessor;
+
+impl tock_registers::interfaces::Writeable for DCCISWAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_write_raw!(u32, [p15, c7, 0, c14, 2]);
+}
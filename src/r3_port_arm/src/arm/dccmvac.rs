@@ -0,0 +1,9 @@
+/// Data cache clean by MVA to PoC
+pub const DCCMVAC: DCCMVACAccessor = DCCMVACAccessor;
+pub struct DCCMVACAccessor;
+
+impl tock_registers::interfaces::Writeable for DCCMVACAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_write_raw!(u32, [p15, c7, 0, c10, 1]);
+}
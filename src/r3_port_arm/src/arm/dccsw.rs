@@ -0,0 +1,9 @@
+/// Data cache clean by set/way
+pub const DCCSW: DCCSWAccessor = DCCSWAccessor;
+pub struct DCCSWAccessor;
+
+impl tock_registers::interfaces::Writeable for DCCSWAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_write_raw!(u32, [p15, c7, 0, c10, 2]);
+}
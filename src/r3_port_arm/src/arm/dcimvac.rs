@@ -0,0 +1,9 @@
+/// Data cache invalidate by MVA to PoC
+pub const DCIMVAC: DCIMVACAccessor = DCIMVACAccessor;
+pub struct DCIMVACAccessor;
+
+impl tock_registers::interfaces::Writeable for DCIMVACAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_write_raw!(u32, [p15, c7, 0, c6, 1]);
+}
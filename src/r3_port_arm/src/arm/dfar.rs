@@ -0,0 +1,9 @@
+/// Data Fault Address Register
+pub const DFAR: DFARAccessor = DFARAccessor;
+pub struct DFARAccessor;
+
+impl tock_registers::interfaces::Readable for DFARAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_read_raw!(u32, [p15, c6, 0, c0, 0]);
+}
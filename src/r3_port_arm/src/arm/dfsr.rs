@@ -0,0 +1,9 @@
+/// Data Fault Status Register
+pub const DFSR: DFSRAccessor = DFSRAccessor;
+pub struct DFSRAccessor;
+
+impl tock_registers::interfaces::Readable for DFSRAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_read_raw!(u32, [p15, c5, 0, c0, 0]);
+}
@@ -0,0 +1,9 @@
+/// Instruction Fault Address Register
+pub const IFAR: IFARAccessor = IFARAccessor;
+pub struct IFARAccessor;
+
+impl tock_registers::interfaces::Readable for IFARAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_read_raw!(u32, [p15, c6, 0, c0, 2]);
+}
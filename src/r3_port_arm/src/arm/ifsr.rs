@@ -0,0 +1,9 @@
+/// Instruction Fault Status Register
+pub const IFSR: IFSRAccessor = IFSRAccessor;
+pub struct IFSRAccessor;
+
+impl tock_registers::interfaces::Readable for IFSRAccessor {
+    type T = u32;
+    type R = ();
+    sys_coproc_read_raw!(u32, [p15, c5, 0, c0, 1]);
+}
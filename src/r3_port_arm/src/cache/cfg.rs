@@ -0,0 +1,76 @@
+//! The public interface of the cache maintenance operations.
+use core::ops::Range;
+
+/// Cache maintenance operations for the caches of the current processor and,
+/// if [`StartupOptions::PL310_BASE`] is specified, the outer PL310 L2 cache.
+/// Implemented on a kernel trait type by [`use_startup!`].
+///
+/// The range operations take a virtual address range and affect all cache
+/// lines overlapping with the range. They are intended to be used by device
+/// drivers to maintain the coherency between the caches and the memory that
+/// is accessed by DMA:
+///
+///  - Before a DMA controller reads a buffer, call
+///    [`clean_dcache_range`][Self::clean_dcache_range] on the buffer to write
+///    back the data written by the processor.
+///  - After a DMA controller writes a buffer, call
+///    [`invalidate_dcache_range`][Self::invalidate_dcache_range] on the buffer
+///    before reading it so that the processor doesn't observe stale data.
+///
+/// The PL310 is controlled by physical addresses. The range operations
+/// translate virtual addresses by the `ATS1CPR` operation, skipping unmapped
+/// pages.
+///
+/// The whole-cache operations operate by set/way and affect only the caches
+/// controlled by the current processor and the outer L2 cache.
+///
+/// [`StartupOptions::PL310_BASE`]: crate::StartupOptions::PL310_BASE
+/// [`use_startup!`]: crate::use_startup
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use r3_port_arm::CacheMaintenance;
+///
+/// static mut BUFFER: [u8; 512] = [0; 512];
+///
+/// let range = unsafe { BUFFER.as_ptr_range() };
+/// let range = range.start as usize..range.end as usize;
+///
+/// // Write back the buffer contents before starting a transmission
+/// SystemTraits::clean_dcache_range(range.clone());
+/// start_dma_transmission(range);
+/// ```
+pub trait CacheMaintenance {
+    /// Clean the data and unified caches to the point of coherency for the
+    /// specified virtual address range, writing back dirty cache lines to the
+    /// memory.
+    fn clean_dcache_range(range: Range<usize>);
+
+    /// Invalidate the data and unified caches to the point of coherency for
+    /// the specified virtual address range, discarding the cache lines.
+    ///
+    /// The cache lines only partially overlapping with the range are cleaned
+    /// and invalidated so as not to lose the data surrounding the range.
+    ///
+    /// # Safety
+    ///
+    /// The data written to the range by the processor but not written back to
+    /// the memory yet will be lost.
+    unsafe fn invalidate_dcache_range(range: Range<usize>);
+
+    /// Clean and invalidate the data and unified caches to the point of
+    /// coherency for the specified virtual address range.
+    fn clean_invalidate_dcache_range(range: Range<usize>);
+
+    /// Clean all data and unified caches.
+    fn clean_dcache_all();
+
+    /// Clean and invalidate all data and unified caches.
+    fn clean_invalidate_dcache_all();
+
+    /// Invalidate all instruction caches and the branch predictor. This is
+    /// necessary after writing instructions to the memory, e.g., when loading
+    /// a program. The written data should be cleaned first.
+    fn invalidate_icache_all();
+}
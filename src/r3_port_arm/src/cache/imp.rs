@@ -0,0 +1,343 @@
+//! Cache maintenance operations
+use core::{arch::asm, ops::Range};
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    registers::ReadWrite,
+};
+
+use super::pl310_regs;
+use crate::{arm, StartupOptions};
+
+// Processor caches
+// -----------------------------------------------------------------------
+
+/// Get the smallest data cache line size of the processor caches.
+#[inline]
+fn dcache_line_size() -> usize {
+    4 << arm::CTR.read(arm::CTR::DminLine)
+}
+
+/// Call `f` with the set/way value of every line of every data or unified
+/// cache up to the Level of Coherency, from the innermost level to the
+/// outermost.
+///
+/// This part is based on the section “8.9.1. Example code for cache
+/// maintenance operations” of Cortex-A Series Programmers Guide 4.0.
+#[inline]
+fn for_each_dcache_set_way(mut f: impl FnMut(u32)) {
+    // Level of Coherency: “This field defines the last level of cache that must
+    // be cleaned or invalidated when cleaning or invalidating to the point of
+    // coherency.”
+    let clidr = arm::CLIDR.extract();
+    let level_of_coherency = clidr.read(arm::CLIDR::LoC);
+    for level in 0..level_of_coherency {
+        let cache_type = (clidr.get() >> (level * 3)) & 0b111;
+
+        // Does this cache level include a data or unified cache?
+        if cache_type >= 2 {
+            // Level = level, InD = 0
+            // Use `isb` to make sure the change to CSSELR takes effect.
+            arm::CSSELR.set(level * 2);
+            unsafe { asm!("isb") };
+
+            let cssidr = arm::CCSIDR.extract();
+            let log2_line_size = cssidr.read(arm::CCSIDR::LineSize) + 4;
+            let max_way_index = cssidr.read(arm::CCSIDR::Associativity);
+            let max_set_index = cssidr.read(arm::CCSIDR::NumSets);
+
+            let way_offset = max_way_index.leading_zeros();
+
+            for way in (0..=max_way_index).rev() {
+                for set in (0..=max_set_index).rev() {
+                    f((level << 1) | (way << way_offset) | (set << log2_line_size));
+                }
+            }
+        }
+    }
+}
+
+/// Invalidate all data and unified processor caches by set/way. Used by the
+/// startup code.
+pub(crate) fn invalidate_inner_dcache_all() {
+    for_each_dcache_set_way(|set_way| arm::DCISW.set(set_way));
+    unsafe { asm!("dsb") };
+}
+
+/// Call `f` with the address of every processor data cache line overlapping
+/// with `range` and whether the line only partially overlaps with `range`.
+#[inline]
+fn for_each_inner_line(range: Range<usize>, mut f: impl FnMut(usize, bool)) {
+    if range.start >= range.end {
+        return;
+    }
+    let line_size = dcache_line_size();
+    let mut addr = range.start & !(line_size - 1);
+    loop {
+        let partial = addr < range.start || range.end - addr < line_size;
+        f(addr, partial);
+        if range.end - addr <= line_size {
+            break;
+        }
+        addr += line_size;
+    }
+}
+
+// PL310 L2 cache controller
+// -----------------------------------------------------------------------
+
+/// Get the PL310 registers if [`StartupOptions::PL310_BASE`] is specified.
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+#[inline(always)]
+unsafe fn pl310<Traits: StartupOptions>() -> Option<&'static pl310_regs::Pl310> {
+    Traits::PL310_BASE.map(|base| unsafe { &*(base as *const pl310_regs::Pl310) })
+}
+
+/// Get the mask value specifying all ways of the PL310.
+#[inline]
+fn pl310_way_mask(pl310: &pl310_regs::Pl310) -> u32 {
+    if pl310
+        .AuxControl
+        .matches_all(pl310_regs::AuxControl::Associativity::SixteenWay)
+    {
+        0xffff
+    } else {
+        0xff
+    }
+}
+
+/// Drain the PL310's buffers and wait for the completion of the preceding
+/// maintenance operations.
+#[inline]
+fn pl310_sync(pl310: &pl310_regs::Pl310) {
+    pl310.CacheSync.set(0);
+    while pl310.CacheSync.get() & 1 != 0 {}
+}
+
+/// Perform a background operation by way (`reg`) on all ways of the PL310 and
+/// wait for its completion.
+///
+/// No other maintenance operations may be issued while a background operation
+/// is in progress, so this function masks IRQs.
+#[inline]
+fn pl310_all_ways(pl310: &pl310_regs::Pl310, reg: &ReadWrite<u32>) {
+    let way_mask = pl310_way_mask(pl310);
+    with_irq_masked(|| {
+        reg.set(way_mask);
+        while reg.get() & way_mask != 0 {}
+        pl310_sync(pl310);
+    });
+}
+
+/// Initialize the PL310 if [`StartupOptions::PL310_BASE`] is specified. Used
+/// by the startup code.
+///
+/// If the PL310 is already enabled (e.g., by a bootloader), this function
+/// leaves it untouched because it might hold dirty cache lines.
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+pub(crate) unsafe fn init_pl310<Traits: StartupOptions>() {
+    let Some(pl310) = (unsafe { pl310::<Traits>() }) else { return };
+
+    if pl310.Control.is_set(pl310_regs::Control::Enable) {
+        return;
+    }
+
+    if let Some(aux_control) = Traits::PL310_AUX_CONTROL {
+        pl310.AuxControl.set(aux_control);
+    }
+
+    // Invalidate all ways
+    pl310_all_ways(pl310, &pl310.InvWay);
+
+    pl310.Control.modify(pl310_regs::Control::Enable::SET);
+}
+
+/// Translate a virtual address to a physical address by the stage 1
+/// translation for PL1 read (`ATS1CPR`). Returns `None` if the address is not
+/// mapped or is not representable in 32 bits.
+#[inline]
+fn va_to_pa(va: usize) -> Option<u32> {
+    let par: u32;
+    // `PAR` is overwritten by another translation operation, so mask IRQs
+    // while using it
+    unsafe {
+        asm!(
+            "
+                mrs {cpsr}, cpsr
+                cpsid i
+                mcr p15, 0, {va}, c7, c8, 0
+                isb
+                mrc p15, 0, {par}, c7, c4, 0
+                msr cpsr_c, {cpsr}
+            ",
+            va = in(reg) va,
+            par = lateout(reg) par,
+            cpsr = out(reg) _,
+        )
+    };
+
+    if par & 0b1 != 0 {
+        // `PAR.F`: The translation was aborted
+        None
+    } else if par & 0b10 != 0 {
+        // `PAR.SS`: Supersection. `PAR[23:16]` hold `PA[39:32]`.
+        if par & 0x00ff_0000 != 0 {
+            None
+        } else {
+            Some((par & 0xff00_0000) | (va as u32 & 0x00ff_ffff))
+        }
+    } else {
+        Some((par & 0xffff_f000) | (va as u32 & 0xfff))
+    }
+}
+
+/// Call `f` with the physical address of every PL310 cache line overlapping
+/// with `range` and whether the line only partially overlaps with `range`.
+#[inline]
+fn for_each_outer_line(range: Range<usize>, mut f: impl FnMut(u32, bool)) {
+    if range.start >= range.end {
+        return;
+    }
+    let line_size = pl310_regs::LINE_SIZE;
+    let mut addr = range.start & !(line_size - 1);
+    let mut page_pa = va_to_pa(addr & !0xfff);
+    loop {
+        if let Some(page_pa) = page_pa {
+            let partial = addr < range.start || range.end - addr < line_size;
+            f(page_pa | (addr & 0xfff) as u32, partial);
+        }
+        if range.end - addr <= line_size {
+            break;
+        }
+        addr += line_size;
+        if addr & 0xfff == 0 {
+            page_pa = va_to_pa(addr);
+        }
+    }
+}
+
+// Public operations
+// -----------------------------------------------------------------------
+
+/// Implements [`CacheMaintenance::clean_dcache_range`].
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+///
+/// [`CacheMaintenance::clean_dcache_range`]: crate::CacheMaintenance::clean_dcache_range
+pub unsafe fn clean_dcache_range<Traits: StartupOptions>(range: Range<usize>) {
+    for_each_inner_line(range.clone(), |addr, _| arm::DCCMVAC.set(addr as u32));
+    unsafe { asm!("dsb") };
+
+    if let Some(pl310) = unsafe { pl310::<Traits>() } {
+        for_each_outer_line(range, |pa, _| pl310.CleanPa.set(pa));
+        pl310_sync(pl310);
+    }
+}
+
+/// Implements [`CacheMaintenance::invalidate_dcache_range`].
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`] and
+/// [`CacheMaintenance::invalidate_dcache_range`].
+///
+/// [`CacheMaintenance::invalidate_dcache_range`]: crate::CacheMaintenance::invalidate_dcache_range
+pub unsafe fn invalidate_dcache_range<Traits: StartupOptions>(range: Range<usize>) {
+    // Invalidate the outer cache first so that the processor caches aren't
+    // refilled with stale data from the outer cache
+    if let Some(pl310) = unsafe { pl310::<Traits>() } {
+        for_each_outer_line(range.clone(), |pa, partial| {
+            if partial {
+                pl310.CleanInvPa.set(pa);
+            } else {
+                pl310.InvPa.set(pa);
+            }
+        });
+        pl310_sync(pl310);
+    }
+
+    for_each_inner_line(range, |addr, partial| {
+        if partial {
+            arm::DCCIMVAC.set(addr as u32);
+        } else {
+            arm::DCIMVAC.set(addr as u32);
+        }
+    });
+    unsafe { asm!("dsb") };
+}
+
+/// Implements [`CacheMaintenance::clean_invalidate_dcache_range`].
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+///
+/// [`CacheMaintenance::clean_invalidate_dcache_range`]: crate::CacheMaintenance::clean_invalidate_dcache_range
+pub unsafe fn clean_invalidate_dcache_range<Traits: StartupOptions>(range: Range<usize>) {
+    for_each_inner_line(range.clone(), |addr, _| arm::DCCIMVAC.set(addr as u32));
+    unsafe { asm!("dsb") };
+
+    if let Some(pl310) = unsafe { pl310::<Traits>() } {
+        for_each_outer_line(range, |pa, _| pl310.CleanInvPa.set(pa));
+        pl310_sync(pl310);
+    }
+}
+
+/// Implements [`CacheMaintenance::clean_dcache_all`].
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+///
+/// [`CacheMaintenance::clean_dcache_all`]: crate::CacheMaintenance::clean_dcache_all
+pub unsafe fn clean_dcache_all<Traits: StartupOptions>() {
+    for_each_dcache_set_way(|set_way| arm::DCCSW.set(set_way));
+    unsafe { asm!("dsb") };
+
+    if let Some(pl310) = unsafe { pl310::<Traits>() } {
+        pl310_all_ways(pl310, &pl310.CleanWay);
+    }
+}
+
+/// Implements [`CacheMaintenance::clean_invalidate_dcache_all`].
+///
+/// # Safety
+///
+/// See [`StartupOptions::PL310_BASE`].
+///
+/// [`CacheMaintenance::clean_invalidate_dcache_all`]: crate::CacheMaintenance::clean_invalidate_dcache_all
+pub unsafe fn clean_invalidate_dcache_all<Traits: StartupOptions>() {
+    for_each_dcache_set_way(|set_way| arm::DCCISW.set(set_way));
+    unsafe { asm!("dsb") };
+
+    if let Some(pl310) = unsafe { pl310::<Traits>() } {
+        pl310_all_ways(pl310, &pl310.CleanInvWay);
+    }
+}
+
+/// Implements [`CacheMaintenance::invalidate_icache_all`].
+///
+/// [`CacheMaintenance::invalidate_icache_all`]: crate::CacheMaintenance::invalidate_icache_all
+pub fn invalidate_icache_all() {
+    arm::ICIALLU.set(0);
+    arm::BPIALL.set(0);
+    unsafe { asm!("dsb") };
+    unsafe { asm!("isb") };
+}
+
+/// Call `f` with IRQs masked.
+#[inline]
+fn with_irq_masked<R>(f: impl FnOnce() -> R) -> R {
+    let cpsr: u32;
+    unsafe { asm!("mrs {}, cpsr", "cpsid i", out(reg) cpsr) };
+    let ret = f();
+    unsafe { asm!("msr cpsr_c, {}", in(reg) cpsr) };
+    ret
+}
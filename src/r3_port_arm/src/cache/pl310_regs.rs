@@ -0,0 +1,81 @@
+#![allow(non_snake_case)]
+use tock_registers::registers::{ReadOnly, ReadWrite};
+
+#[repr(C)]
+pub struct Pl310 {
+    /// Cache ID Register
+    pub CacheId: ReadOnly<u32>,
+    /// Cache Type Register
+    pub CacheType: ReadOnly<u32>,
+    _reserved1: [u32; 62],
+    /// Control Register
+    pub Control: ReadWrite<u32, Control::Register>,
+    /// Auxiliary Control Register
+    pub AuxControl: ReadWrite<u32, AuxControl::Register>,
+    /// Tag RAM Latency Control Register
+    pub TagRamControl: ReadWrite<u32>,
+    /// Data RAM Latency Control Register
+    pub DataRamControl: ReadWrite<u32>,
+    _reserved2: [u32; 392],
+    /// Cache Synchronization Register
+    pub CacheSync: ReadWrite<u32>,
+    _reserved3: [u32; 15],
+    /// Invalidate Line by PA Register
+    pub InvPa: ReadWrite<u32>,
+    _reserved4: [u32; 2],
+    /// Invalidate by Way Register
+    pub InvWay: ReadWrite<u32>,
+    _reserved5: [u32; 12],
+    /// Clean Line by PA Register
+    pub CleanPa: ReadWrite<u32>,
+    _reserved6: u32,
+    /// Clean Line by Set/Way Register
+    pub CleanIndex: ReadWrite<u32>,
+    /// Clean by Way Register
+    pub CleanWay: ReadWrite<u32>,
+    _reserved7: [u32; 12],
+    /// Clean and Invalidate Line by PA Register
+    pub CleanInvPa: ReadWrite<u32>,
+    _reserved8: u32,
+    /// Clean and Invalidate Line by Set/Way Register
+    pub CleanInvIndex: ReadWrite<u32>,
+    /// Clean and Invalidate by Way Register
+    pub CleanInvWay: ReadWrite<u32>,
+}
+
+tock_registers::register_bitfields! {u32,
+    pub Control [
+        /// L2 Cache enable
+        Enable OFFSET(0) NUMBITS(1) []
+    ],
+
+    pub AuxControl [
+        /// Associativity
+        Associativity OFFSET(16) NUMBITS(1) [
+            EightWay = 0,
+            SixteenWay = 1
+        ]
+    ]
+}
+
+/// The size of a cache line
+pub const LINE_SIZE: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memoffset::offset_of;
+
+    #[test]
+    fn register_offsets() {
+        assert_eq!(offset_of!(Pl310, Control), 0x100);
+        assert_eq!(offset_of!(Pl310, AuxControl), 0x104);
+        assert_eq!(offset_of!(Pl310, CacheSync), 0x730);
+        assert_eq!(offset_of!(Pl310, InvPa), 0x770);
+        assert_eq!(offset_of!(Pl310, InvWay), 0x77c);
+        assert_eq!(offset_of!(Pl310, CleanPa), 0x7b0);
+        assert_eq!(offset_of!(Pl310, CleanWay), 0x7bc);
+        assert_eq!(offset_of!(Pl310, CleanInvPa), 0x7f0);
+        assert_eq!(offset_of!(Pl310, CleanInvWay), 0x7fc);
+    }
+}
@@ -0,0 +1,110 @@
+use r3_portkit::crashdump::{CrashRecord, CrashRecordCell};
+
+/// The storage for the last [`FaultRecord`]. It's placed in `.noinit`, which
+/// the provided linker scripts reserve outside `.bss` so that it survives a
+/// reset. (This isn't a kernel hunk because the hunk pool is zero-initialized
+/// by the startup code.)
+#[cfg_attr(target_os = "none", link_section = ".noinit.r3_port_arm.CRASH_RECORD")]
+pub(crate) static CRASH_RECORD: CrashRecordCell<FaultRecord> = CrashRecordCell::new();
+
+/// Get and remove the [`FaultRecord`] written by the last fault, which may have
+/// happened before the last reset.
+///
+/// Returns `None` if no faults have been recorded since the record was last
+/// removed or since power-on.
+///
+/// # Safety
+///
+/// This function must not be called concurrently with itself.
+pub unsafe fn take_crash_record() -> Option<FaultRecord> {
+    // Safety: Fault handlers don't run concurrently with this function unless
+    //         this function itself causes a fault. The caller is responsible
+    //         for the rest.
+    unsafe { CRASH_RECORD.take() }
+}
+
+/// The exception that reported a fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// Undefined Instruction exception.
+    UndefinedInstruction,
+    /// Prefetch Abort exception, e.g., an instruction fetch from a memory
+    /// region marked as execute-never.
+    PrefetchAbort,
+    /// Data Abort exception, e.g., an unaligned access or an access to an
+    /// unmapped memory region.
+    DataAbort,
+}
+
+impl FaultKind {
+    /// Get the `FaultKind` corresponding to an exception vector index.
+    pub const fn from_exception(exception: u32) -> Option<Self> {
+        match exception {
+            1 => Some(Self::UndefinedInstruction),
+            3 => Some(Self::PrefetchAbort),
+            4 => Some(Self::DataAbort),
+            _ => None,
+        }
+    }
+}
+
+/// A crash record describing a fault. Passed to
+/// [`ThreadingOptions::handle_fault`] and retained across a reset (see
+/// [`take_crash_record`]).
+///
+/// [`ThreadingOptions::handle_fault`]: crate::ThreadingOptions::handle_fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct FaultRecord {
+    /// The exception vector index of the fault handler (`1` = Undefined
+    /// Instruction, `3` = Prefetch Abort, `4` = Data Abort).
+    pub exception: u32,
+    /// `R0`–`R12` of the faulting context.
+    pub regs: [u32; 13],
+    /// The stack pointer value of the faulting context.
+    pub sp: u32,
+    /// The link register value of the faulting context.
+    pub lr: u32,
+    /// The address of the faulting instruction.
+    pub pc: u32,
+    /// The `CPSR` value of the faulting context (taken from `SPSR`).
+    pub cpsr: u32,
+    /// The Data Fault Status Register (`DFSR`).
+    pub dfsr: u32,
+    /// The Data Fault Address Register (`DFAR`).
+    pub dfar: u32,
+    /// The Instruction Fault Status Register (`IFSR`).
+    pub ifsr: u32,
+    /// The Instruction Fault Address Register (`IFAR`).
+    pub ifar: u32,
+    /// The ID of the task that caused the fault, or `0` if the fault was not
+    /// caused by a task.
+    pub task: u32,
+}
+
+// Safety: `FaultRecord` only consists of `u32` fields
+unsafe impl CrashRecord for FaultRecord {
+    const MAGIC: u32 = 0x7233_4101;
+}
+
+impl FaultRecord {
+    /// Get the exception that reported the fault.
+    pub const fn kind(&self) -> Option<FaultKind> {
+        FaultKind::from_exception(self.exception)
+    }
+
+    /// Get the ID of the task that caused the fault.
+    pub fn task(&self) -> Option<r3_kernel::Id> {
+        r3_kernel::Id::new(self.task as usize)
+    }
+
+    /// Get the faulting address. This is `DFAR` for a Data Abort exception and
+    /// `IFAR` for a Prefetch Abort exception.
+    pub const fn address(&self) -> Option<usize> {
+        match self.kind() {
+            Some(FaultKind::DataAbort) => Some(self.dfar as usize),
+            Some(FaultKind::PrefetchAbort) => Some(self.ifar as usize),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Fault handlers
+use core::arch::asm;
+use r3_kernel::TaskCb;
+use r3_portkit::pptext::pp_asm;
+use tock_registers::interfaces::Readable;
+
+use crate::{arm, fault::cfg::CRASH_RECORD, threading::imp::PortInstance, FaultKind, FaultRecord};
+
+/// `CPSR.M` for User mode
+const MODE_USER: u32 = 0x10;
+/// `CPSR.M` for System mode
+const MODE_SYSTEM: u32 = 0x1f;
+/// `CPSR.T`
+const CPSR_T: u32 = 1 << 5;
+
+macro_rules! define_fault_entry {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident = $exception:literal $({ $($prologue:tt)* })?
+    ),* $(,)*) => {$(
+        $(#[$meta])*
+        ///
+        /// # Safety
+        ///
+        /// This method must be registered as the corresponding exception
+        /// handler.
+        #[naked]
+        pub unsafe extern "C" fn $name<Traits: PortInstance>() -> ! {
+            unsafe {
+                pp_asm!(
+                    $($($prologue)*)?
+                    "
+                    # Save the registers of the faulting context. `sp` is still
+                    # 8-byte aligned after this.
+                    push {{r0-r12, lr}}
+                "   if cfg!(target_feature = "vfp2") {                              "
+                    # Enable the VFP for `handle_fault`, which may use it. We
+                    # don't need to preserve the faulting context's VFP state.
+                    vmrs r0, fpexc
+                    orr r0, #0x40000000
+                    vmsr fpexc, r0
+                "   }                                                               "
+                    mov r0, sp
+                    mov r1, #{exception}
+                    b {handle_fault}
+                    ",
+                    exception = const $exception,
+                    handle_fault = sym handle_fault::<Traits>,
+                    options(noreturn),
+                );
+            }
+        }
+    )*};
+}
+
+define_fault_entry! {
+    /// The Undefined Instruction handler.
+    ///
+    /// If the VFP is disabled (`FPEXC.EN == 0`), this handler enables it and
+    /// retries the faulting instruction, which will succeed this time if it
+    /// was a VFP or Advanced SIMD instruction. Tasks start with the VFP
+    /// disabled, so this is where the VFP gets enabled for a task.
+    undefined_instruction_entry = 1 {
+        "
+                # Enable the VFP if it's disabled (`FPEXC.EN == 0`) and retry
+                # the faulting instruction [tag:arm_vfp_lazy_enable]
+        " if cfg!(target_feature = "vfp2") { "
+                push {{r0}}
+                vmrs r0, fpexc
+                tst r0, #0x40000000
+                bne 0f
+                orr r0, #0x40000000
+                vmsr fpexc, r0
+
+                # Retry the faulting instruction. `lr_und` points to the
+                # faulting instruction plus 4 in Arm state or plus 2 in Thumb
+                # state (`SPSR.T == 1`).
+                mrs r0, spsr
+                tst r0, #0x20
+                subeq lr, #4
+                subne lr, #2
+                pop {{r0}}
+                movs pc, lr
+            0:
+                pop {{r0}}
+        " }
+    },
+    /// The Prefetch Abort handler.
+    prefetch_abort_entry = 3,
+    /// The Data Abort handler.
+    data_abort_entry = 4,
+}
+
+/// The body of the fault handlers. Records the fault and panics.
+///
+/// `saved` points to `R0`–`R12` of the faulting context followed by the
+/// exception mode's `LR`.
+extern "C" fn handle_fault<Traits: PortInstance>(saved: &[u32; 14], exception: u32) -> ! {
+    let spsr: u32;
+    // Safety: Reading `SPSR` has no side effects
+    unsafe { asm!("mrs {}, spsr", out(reg) spsr, options(nomem, preserves_flags, nostack)) };
+
+    let (sp, lr) = banked_sp_lr(spsr & 0x1f);
+
+    // Calculate the faulting instruction's address from the exception mode's
+    // `LR`. The offset is different for each exception type.
+    let lr_exc = saved[13];
+    let pc = match FaultKind::from_exception(exception) {
+        Some(FaultKind::UndefinedInstruction) if spsr & CPSR_T != 0 => lr_exc.wrapping_sub(2),
+        Some(FaultKind::DataAbort) => lr_exc.wrapping_sub(8),
+        _ => lr_exc.wrapping_sub(4),
+    };
+
+    // Tasks run in System mode (or User mode). The idle task also runs in
+    // System mode, but `running_task` is `None` while it's running.
+    // Safety: The kernel can't preempt us because IRQs are masked
+    let task = if matches!(spsr & 0x1f, MODE_USER | MODE_SYSTEM) {
+        unsafe { *Traits::state().running_task_ptr() }
+    } else {
+        None
+    };
+
+    let mut regs = [0; 13];
+    regs.copy_from_slice(&saved[..13]);
+
+    let record = FaultRecord {
+        exception,
+        regs,
+        sp,
+        lr,
+        pc,
+        cpsr: spsr,
+        dfsr: arm::DFSR.get(),
+        dfar: arm::DFAR.get(),
+        ifsr: arm::IFSR.get(),
+        ifar: arm::IFAR.get(),
+        task: task.map_or(0, |task| task_id(task).get() as u32),
+    };
+
+    // Safety: Fault handlers don't preempt each other except when a fault
+    // handler itself faults, in which case we are lost anyway
+    unsafe { CRASH_RECORD.store(&record) };
+
+    Traits::handle_fault(&record);
+
+    panic!(
+        "unrecoverable {kind:?} at {pc:#010x} (DFSR = {dfsr:#010x}, \
+        IFSR = {ifsr:#010x}, address = {address:?})",
+        kind = record.kind(),
+        dfsr = record.dfsr,
+        ifsr = record.ifsr,
+        address = record.address(),
+    );
+}
+
+/// Read the banked `SP` and `LR` of the specified processor mode. Returns
+/// zeros if `mode` isn't a mode we can switch to.
+fn banked_sp_lr(mode: u32) -> (u32, u32) {
+    // User mode shares `SP` and `LR` with System mode, and we can't return
+    // from User mode. FIQ mode is excluded because it also banks `R8`–`R12`,
+    // and this port doesn't use FIQs anyway.
+    let mode = match mode {
+        MODE_USER => MODE_SYSTEM,
+        0x12 | 0x13 | 0x17 | 0x1b | MODE_SYSTEM => mode,
+        _ => return (0, 0),
+    };
+
+    let sp: u32;
+    let lr: u32;
+    // Safety: Switching to `mode` and back doesn't affect anything but the
+    // banked registers, which we don't touch. `MSR` doesn't change the
+    // instruction set state.
+    //
+    // The operands are pinned to `R0`–`R3`, which aren't banked in any of
+    // these modes. A generic register operand could be allocated to `LR`,
+    // whose value would change across the mode switch.
+    unsafe {
+        asm!("
+            mrs r1, cpsr
+            bic r2, r1, #0x1f
+            orr r2, r2, r0
+            msr cpsr_c, r2
+            mov r3, sp
+            mov r0, lr
+            msr cpsr_c, r1
+            ",
+            inout("r0") mode => lr,
+            out("r1") _,
+            out("r2") _,
+            out("r3") sp,
+            options(nomem, preserves_flags, nostack),
+        );
+    }
+    (sp, lr)
+}
+
+/// Calculate an `Id` from a task CB reference.
+fn task_id<Traits: PortInstance>(task: &TaskCb<Traits>) -> r3_kernel::Id {
+    // Safety: `task` refers to an element of `Traits::task_cb_pool()`
+    let offset = unsafe { <*const _>::offset_from(task, Traits::task_cb_pool().as_ptr()) };
+    r3_kernel::Id::new(offset as usize + 1).unwrap()
+}
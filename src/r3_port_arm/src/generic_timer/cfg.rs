@@ -0,0 +1,104 @@
+//! The public interface for the Arm Generic Timer driver.
+pub use r3_portkit::arm_generic_timer::GenericTimerOptions;
+
+/// Attach the implementation of [`PortTimer`] that is based on the physical
+/// timer of [Arm Generic Timer] to a given kernel trait type. This macro also
+/// implements [`Timer`] on the kernel trait type.
+/// **Requires [`GenericTimerOptions`].**
+///
+/// [`PortTimer`]: r3_kernel::PortTimer
+/// [`Timer`]: crate::Timer
+/// [Arm Generic Timer]: https://developer.arm.com/documentation/ddi0406/c/System-Level-Architecture/The-Generic-Timer
+///
+/// You should do the following:
+///
+///  - Implement [`GenericTimerOptions`] on the kernel trait type `$Traits`.
+///  - Call `$Traits::configure_generic_timer()` in your configuration
+///    function. See the following example.
+///
+/// ```rust,ignore
+/// r3_port_arm::use_generic_timer!(unsafe impl PortTimer for SystemTraits);
+///
+/// impl r3_port_arm::GenericTimerOptions for SystemTraits {
+///     const FREQUENCY: u64 = 62_500_000;
+///     const INTERRUPT_NUM: InterruptNum = 30;
+/// }
+///
+/// const fn configure_app<C>(b: &mut Cfg<SystemTraits>) -> Objects
+/// where
+///     C: ~const traits::CfgBase<System = System<SystemTraits>>,
+/// {
+///     SystemTraits::configure_generic_timer(b);
+///     /* ... */
+/// }
+/// ```
+///
+/// # Safety
+///
+///  - `GenericTimerOptions` must be configured correctly.
+///  - The physical timer must be accessible from the current mode (e.g.,
+///    `CNTHCTL.PL1PCEN` must be set if the processor implements the
+///    Virtualization Extensions and runs in Non-secure state).
+///
+#[macro_export]
+macro_rules! use_generic_timer {
+    (unsafe impl PortTimer for $Traits:ty) => {
+        const _: () = {
+            use $crate::r3_core::{
+                kernel::{traits, Cfg},
+                utils::Init,
+            };
+            use $crate::r3_kernel::{PortTimer, System, UTicks};
+            use $crate::r3_portkit::{
+                arm_generic_timer::{self as generic_timer, GenericTimerInstance},
+                tickless64,
+            };
+            use $crate::Timer;
+
+            impl PortTimer for $Traits {
+                const MAX_TICK_COUNT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as GenericTimerInstance>::TICKLESS_CFG.max_timeout();
+
+                unsafe fn tick_count() -> UTicks {
+                    // Safety: We are just forwarding the call
+                    unsafe { generic_timer::tick_count::<Self>() }
+                }
+
+                unsafe fn pend_tick() {
+                    // Safety: We are just forwarding the call
+                    unsafe { generic_timer::pend_tick::<Self>() }
+                }
+
+                unsafe fn pend_tick_after(tick_count_delta: UTicks) {
+                    // Safety: We are just forwarding the call
+                    unsafe { generic_timer::pend_tick_after::<Self>(tick_count_delta) }
+                }
+            }
+
+            impl Timer for $Traits {
+                unsafe fn init() {
+                    unsafe { generic_timer::init::<Self>() }
+                }
+            }
+
+            static mut TIMER_STATE: tickless64::Tickless64State = Init::INIT;
+
+            // Safety: Only `use_generic_timer!` is allowed to `impl` this
+            unsafe impl GenericTimerInstance for $Traits {
+                fn tickless_state() -> *mut tickless64::Tickless64State {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
+                }
+            }
+
+            impl $Traits {
+                pub const fn configure_generic_timer<C>(b: &mut Cfg<C>)
+                where
+                    C: ~const traits::CfgInterruptLine<System = System<Self>>,
+                {
+                    generic_timer::configure(b);
+                }
+            }
+        };
+    };
+}
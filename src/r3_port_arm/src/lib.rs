@@ -25,7 +25,7 @@ pub extern crate r3_core_ks as r3_core;
 #[doc(hidden)]
 pub extern crate r3_kernel;
 
-/// Used by `use_sp804!` and `use_port!`
+/// Used by `use_sp804!`, `use_generic_timer!`, and `use_port!`
 #[doc(hidden)]
 pub extern crate r3_portkit;
 
@@ -49,12 +49,28 @@ pub mod threading {
     pub mod imp;
 }
 
+/// Fault handling
+#[doc(hidden)]
+pub mod fault {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
 /// The Arm Generic Interrupt Controller driver.
 #[doc(hidden)]
 pub mod gic {
     pub mod cfg;
-    mod gic_regs;
+}
+
+/// Cache maintenance operations
+#[doc(hidden)]
+pub mod cache {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
     pub mod imp;
+    #[cfg_attr(not(target_os = "none"), allow(dead_code))]
+    mod pl310_regs;
 }
 
 /// The standard startup code.
@@ -66,6 +82,12 @@ pub mod startup {
     pub mod imp;
 }
 
+/// The Arm Generic Timer driver.
+#[doc(hidden)]
+pub mod generic_timer {
+    pub mod cfg;
+}
+
 /// The SP804 Dual Timer driver.
 #[doc(hidden)]
 pub mod sp804 {
@@ -74,6 +96,9 @@ pub mod sp804 {
     mod sp804_regs;
 }
 
+pub use self::cache::cfg::*;
+pub use self::fault::cfg::*;
+pub use self::generic_timer::cfg::*;
 pub use self::gic::cfg::*;
 pub use self::sp804::cfg::*;
 pub use self::startup::cfg::*;
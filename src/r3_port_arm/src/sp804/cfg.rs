@@ -15,6 +15,9 @@ use r3_core::kernel::{InterruptNum, InterruptPriority};
 ///  - Implement [`Sp804Options`] on the kernel trait type `$Traits`.
 ///  - Call `$Traits::configure_sp804()` in your configuration function.
 ///    See the following example.
+///  - Optionally, set [`Sp804Options::RECONFIGURABLE`] to `true` and call
+///    `$Traits::set_timer_frequency()` whenever the timer clock frequency
+///    changes (e.g., because of dynamic voltage and frequency scaling).
 ///
 /// ```rust,ignore
 /// r3_port_arm::use_sp804!(unsafe impl PortTimer for SystemTraits);
@@ -79,6 +82,9 @@ macro_rules! use_sp804 {
             static mut TIMER_STATE: <$Traits as sp804::imp::Sp804Instance>::TicklessState =
                 Init::INIT;
 
+            static mut TIMER_CFG: tickless::TicklessCfg =
+                <$Traits as sp804::imp::Sp804Instance>::TICKLESS_CFG;
+
             // Safety: Only `use_sp804!` is allowed to `impl` this
             unsafe impl sp804::imp::Sp804Instance for $Traits {
                 type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
@@ -86,6 +92,10 @@ macro_rules! use_sp804 {
                 fn tickless_state() -> *mut Self::TicklessState {
                     unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
                 }
+
+                fn tickless_cfg() -> *mut tickless::TicklessCfg {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_CFG) }
+                }
             }
 
             impl $Traits {
@@ -95,6 +105,24 @@ macro_rules! use_sp804 {
                 {
                     sp804::imp::configure(b);
                 }
+
+                /// Inform the SP804 driver that the timer clock frequency
+                /// is changing to `freq_num / freq_denom` Hz. The new
+                /// frequency takes effect when `change_clock` returns.
+                /// Requires `Sp804Options::RECONFIGURABLE`.
+                ///
+                /// `change_clock` is called with CPU Lock active to make the
+                /// actual clock change atomic with respect to the kernel
+                /// timekeeping. The kernel tick count is preserved across the
+                /// change, losing less than one microsecond and the time
+                /// taken by `change_clock` at most.
+                pub fn set_timer_frequency(
+                    freq_num: u64,
+                    freq_denom: u64,
+                    change_clock: impl FnOnce(),
+                ) -> Result<(), tickless::SetTimerFrequencyError> {
+                    sp804::imp::set_timer_frequency::<Self>(freq_num, freq_denom, change_clock)
+                }
             }
         };
     };
@@ -119,6 +147,12 @@ pub trait Sp804Options {
     const HEADROOM: u32 =
         (Self::FREQUENCY as u128 * 60 / Self::FREQUENCY_DENOMINATOR as u128).min(0x40000000) as u32;
 
+    /// Allow changing the timer frequency at runtime by
+    /// `set_timer_frequency` generated by [`use_sp804!`]. This makes the
+    /// driver slightly slower and makes Timer1 always operate in Free-running
+    /// mode. Defaults to `false`.
+    const RECONFIGURABLE: bool = false;
+
     /// The interrupt priority of the timer interrupt line.
     /// Defaults to `0xc0`.
     const INTERRUPT_PRIORITY: InterruptPriority = 0xc0;
@@ -1,7 +1,9 @@
 //! The implementation of the SP804 Dual Timer driver.
-use r3_core::kernel::{traits, Cfg, InterruptLine, StaticInterruptHandler};
+use r3_core::kernel::{raw, traits, Cfg, InterruptLine, StaticInterruptHandler};
 use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
-use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use r3_portkit::tickless::{
+    SetTimerFrequencyError, TicklessCfg, TicklessOptions, TicklessStateTrait,
+};
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 
 use crate::sp804::{cfg::Sp804Options, sp804_regs};
@@ -12,13 +14,11 @@ use crate::sp804::{cfg::Sp804Options, sp804_regs};
 ///
 /// Only meant to be implemented by [`use_sp804!`].
 pub unsafe trait Sp804Instance: KernelTraits + Sp804Options {
-    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
-        hw_freq_num: <Self as Sp804Options>::FREQUENCY,
-        hw_freq_denom: <Self as Sp804Options>::FREQUENCY_DENOMINATOR,
-        hw_headroom_ticks: <Self as Sp804Options>::HEADROOM,
-        force_full_hw_period: false,
-        resettable: false,
-    }) {
+    /// The initial tickless configuration.
+    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(tickless_options::<Self>(
+        <Self as Sp804Options>::FREQUENCY,
+        <Self as Sp804Options>::FREQUENCY_DENOMINATOR,
+    )) {
         Ok(x) => x,
         Err(e) => e.panic(),
     };
@@ -26,6 +26,20 @@ pub unsafe trait Sp804Instance: KernelTraits + Sp804Options {
     type TicklessState: TicklessStateTrait;
 
     fn tickless_state() -> *mut Self::TicklessState;
+
+    /// The current tickless configuration. Only used if
+    /// [`Sp804Options::RECONFIGURABLE`] is `true`.
+    fn tickless_cfg() -> *mut TicklessCfg;
+}
+
+const fn tickless_options<Traits: Sp804Options + ?Sized>(
+    hw_freq_num: u64,
+    hw_freq_denom: u64,
+) -> TicklessOptions {
+    TicklessOptions {
+        reconfigurable: Traits::RECONFIGURABLE,
+        ..TicklessOptions::new(hw_freq_num, hw_freq_denom, Traits::HEADROOM)
+    }
 }
 
 trait Sp804InstanceExt: Sp804Instance {
@@ -33,6 +47,21 @@ trait Sp804InstanceExt: Sp804Instance {
         // Safety: Verified by the user of `use_sp804!`
         unsafe { &*(Self::SP804_BASE as *const sp804_regs::Sp804) }
     }
+
+    /// Get the current tickless configuration.
+    ///
+    /// # Safety
+    ///
+    /// Must not race with [`set_timer_frequency`].
+    #[inline]
+    unsafe fn current_tickless_cfg() -> TicklessCfg {
+        if Self::RECONFIGURABLE {
+            // Safety: CPU Lock protects it from concurrent access
+            unsafe { *Self::tickless_cfg() }
+        } else {
+            Self::TICKLESS_CFG
+        }
+    }
 }
 impl<T: Sp804Instance> Sp804InstanceExt for T {}
 
@@ -108,9 +137,8 @@ pub fn init<Traits: Sp804Instance>() {
     );
 }
 
-fn hw_tick_count<Traits: Sp804Instance>() -> u32 {
+fn hw_tick_count<Traits: Sp804Instance>(tcfg: &TicklessCfg) -> u32 {
     let sp804 = Traits::sp804_regs();
-    let tcfg = Traits::TICKLESS_CFG;
 
     let value = sp804.Timer1Value.get();
 
@@ -131,9 +159,10 @@ fn hw_tick_count<Traits: Sp804Instance>() -> u32 {
 ///
 /// Only meant to be referenced by `use_sp804!`.
 pub unsafe fn tick_count<Traits: Sp804Instance>() -> UTicks {
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
 
-    let hw_tick_count = hw_tick_count::<Traits>();
+    let hw_tick_count = hw_tick_count::<Traits>(tcfg);
 
     // Safety: CPU Lock protects it from concurrent access
     let tstate = unsafe { &mut *Traits::tickless_state() };
@@ -158,11 +187,15 @@ pub unsafe fn pend_tick<Traits: Sp804Instance>() {
 /// Only meant to be referenced by `use_sp804!`.
 pub unsafe fn pend_tick_after<Traits: Sp804Instance>(tick_count_delta: UTicks) {
     let sp804 = Traits::sp804_regs();
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
     // Safety: CPU Lock protects it from concurrent access
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
-    let cur_hw_tick_count = hw_tick_count::<Traits>();
+    // `MAX_TIMEOUT` doesn't reflect the current frequency
+    let tick_count_delta = tick_count_delta.min(tcfg.max_timeout());
+
+    let cur_hw_tick_count = hw_tick_count::<Traits>(tcfg);
     let hw_ticks = tstate
         .mark_reference_and_measure(tcfg, cur_hw_tick_count, tick_count_delta)
         .hw_ticks;
@@ -177,14 +210,54 @@ pub unsafe fn pend_tick_after<Traits: Sp804Instance>(tick_count_delta: UTicks) {
         .modify(sp804_regs::Control::TimerEn::Enable);
 }
 
+/// Implements `set_timer_frequency` generated by `use_sp804!`.
+pub fn set_timer_frequency<Traits: Sp804Instance>(
+    freq_num: u64,
+    freq_denom: u64,
+    change_clock: impl FnOnce(),
+) -> Result<(), SetTimerFrequencyError> {
+    if !Traits::RECONFIGURABLE {
+        return Err(SetTimerFrequencyError::NotReconfigurable);
+    }
+
+    let new_tcfg = TicklessCfg::new(tickless_options::<Traits>(freq_num, freq_denom))
+        .map_err(SetTimerFrequencyError::BadFrequency)?;
+
+    <System<Traits> as raw::KernelBase>::raw_acquire_cpu_lock()
+        .map_err(|_| SetTimerFrequencyError::BadContext)?;
+
+    // Safety: CPU Lock protects them from concurrent access
+    let tcfg = unsafe { &mut *Traits::tickless_cfg() };
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+
+    // Rebase the tickless state at the last hardware tick count measured in
+    // the old frequency. Timer1 keeps counting across the clock change, so the
+    // error is limited to the time taken by `change_clock`.
+    let cur_hw_tick_count = hw_tick_count::<Traits>(tcfg);
+    change_clock();
+    tstate.reconfigure(tcfg, &new_tcfg, cur_hw_tick_count);
+    *tcfg = new_tcfg;
+
+    // Timer2 was programmed in the old frequency. Let the kernel program it
+    // again.
+    // Safety: CPU Lock active
+    unsafe { pend_tick::<Traits>() };
+
+    // Safety: We own the CPU Lock
+    unsafe { <System<Traits> as raw::KernelBase>::raw_release_cpu_lock().unwrap() };
+
+    Ok(())
+}
+
 #[inline]
 fn handle_tick<Traits: Sp804Instance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
 
     // Safety: CPU Lock protects it from concurrent access
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
-    let cur_hw_tick_count = hw_tick_count::<Traits>();
+    let cur_hw_tick_count = hw_tick_count::<Traits>(tcfg);
     tstate.mark_reference(tcfg, cur_hw_tick_count);
 
     // `timer_tick` will call `pend_tick[_after]`, so it's unnecessary to
@@ -7,8 +7,12 @@ use core::ops::Range;
 /// You should specify it as an entry point in your linker script (the provided
 /// linker scripts automatically do this for you).
 ///
+/// This macro also implements [`CacheMaintenance`] on the given kernel trait
+/// type.
+///
 /// [startup code]: crate#startup-code
 /// [`EntryPoint`]: crate::EntryPoint
+/// [`CacheMaintenance`]: crate::CacheMaintenance
 #[macro_export]
 macro_rules! use_startup {
     (unsafe $Traits:ty) => {
@@ -21,6 +25,46 @@ macro_rules! use_startup {
                 options(noreturn),
             );
         }
+
+        impl $crate::CacheMaintenance for $Traits {
+            #[inline]
+            fn clean_dcache_range(range: $crate::core::ops::Range<usize>) {
+                unsafe { $crate::cache::imp::clean_dcache_range::<Self>(range) }
+            }
+
+            #[inline]
+            unsafe fn invalidate_dcache_range(range: $crate::core::ops::Range<usize>) {
+                unsafe { $crate::cache::imp::invalidate_dcache_range::<Self>(range) }
+            }
+
+            #[inline]
+            fn clean_invalidate_dcache_range(range: $crate::core::ops::Range<usize>) {
+                unsafe { $crate::cache::imp::clean_invalidate_dcache_range::<Self>(range) }
+            }
+
+            #[inline]
+            fn clean_dcache_all() {
+                unsafe { $crate::cache::imp::clean_dcache_all::<Self>() }
+            }
+
+            #[inline]
+            fn clean_invalidate_dcache_all() {
+                unsafe { $crate::cache::imp::clean_invalidate_dcache_all::<Self>() }
+            }
+
+            #[inline]
+            fn invalidate_icache_all() {
+                $crate::cache::imp::invalidate_icache_all()
+            }
+        }
+
+        impl $crate::startup::imp::SecondLevelPageTables for $Traits {
+            const SECOND_LEVEL_PAGE_TABLES: &'static [$crate::startup::imp::SecondLevelPageTable] =
+                &$crate::startup::imp::second_level_page_tables::<
+                    $Traits,
+                    { $crate::startup::imp::num_second_level_page_tables::<$Traits>() },
+                >();
+        }
     };
 }
 
@@ -34,6 +78,11 @@ pub trait StartupOptions {
     /// At least one of `0x0000000` and `0xffff0000` must left unmapped so that
     /// an exception vector table can be placed there.
     ///
+    /// The startup code builds translation tables in the short-descriptor
+    /// format, mapping each section by the largest descriptors its alignment
+    /// allows (see [`MemoryMapSection::new`]). Second-level page tables are
+    /// allocated statically based on this memory map.
+    ///
     /// # Examples
     ///
     /// ```
@@ -50,17 +99,59 @@ pub trait StartupOptions {
     ///     MemoryMapSection::new(0xfcf0_0000..0xfd00_0000, 0xfcf0_0000).as_device_memory(),
     /// ];
     /// ```
+    ///
+    /// ```
+    /// use r3_port_arm::MemoryMapSection;
+    ///
+    /// // Arm RealView PBX for Cortex-A9
+    /// const MEMORY_MAP: &'static [MemoryMapSection] = &[
+    ///     // DRAM, leaving the first page unmapped to catch null pointer
+    ///     // dereferences
+    ///     MemoryMapSection::new(0x0000_1000..0x0800_0000, 0x0000_1000).with_executable(true),
+    ///     // UART0 (4KiB)
+    ///     MemoryMapSection::new(0x1000_9000..0x1000_a000, 0x1000_9000).as_device_memory(),
+    /// ];
+    /// ```
     const MEMORY_MAP: &'static [MemoryMapSection];
+
+    /// The base address of the memory-mapped registers of the Arm CoreLink
+    /// Level 2 Cache Controller L2C-310 (PL310), if any.
+    ///
+    /// If this is `Some(_)`, the startup code invalidates and enables the L2
+    /// cache before enabling the MMU, and [`CacheMaintenance`]'s operations
+    /// cover the L2 cache. The startup code leaves the L2 cache untouched if
+    /// it's already enabled.
+    ///
+    /// The register region must be identity-mapped as Device memory by
+    /// [`Self::MEMORY_MAP`]. The L2 cache's configuration registers must be
+    /// writable, which usually means that the processor is in Secure state.
+    ///
+    /// [`CacheMaintenance`]: crate::CacheMaintenance
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Renesas RZ/A1H
+    /// const PL310_BASE: Option<usize> = Some(0x3fff_f000);
+    /// ```
+    const PL310_BASE: Option<usize> = None;
+
+    /// The value written to the PL310's Auxiliary Control Register before the
+    /// startup code enables the L2 cache. The register is left unchanged if
+    /// this is `None`.
+    ///
+    /// This option has no effect if [`Self::PL310_BASE`] is `None`.
+    const PL310_AUX_CONTROL: Option<u32> = None;
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct MemoryMapSection {
-    /// The starting physical address. Must be aligned to 1MiB blocks.
+    /// The starting physical address. Must be aligned to 4KiB pages.
     pub(super) physical_start: u64,
-    /// The starting virtual address. Must be aligned to 1MiB blocks.
+    /// The starting virtual address. Must be aligned to 4KiB pages.
     pub(super) virtual_start: usize,
-    /// The length of the section, measured in bytes. Must be aligned to 1MiB
-    /// blocks.
+    /// The length of the section, measured in bytes. Must be aligned to 4KiB
+    /// pages.
     pub(super) len: usize,
     pub(super) attr: MemoryRegionAttributes,
 }
@@ -68,19 +159,32 @@ pub struct MemoryMapSection {
 impl MemoryMapSection {
     /// Construct a `MemoryMapSection` for normal read/write memory access.
     ///
-    ///  - All endpoints must be aligned to 1MiB blocks (`0x???0_0000`).
+    ///  - All endpoints must be aligned to 4KiB pages (`0x????_?000`).
     ///
     ///  - `virtual_range` must not be empty.
     ///
     ///  - `virtual_range` must be a strict subset of `0..0x1_0000_0000`.
     ///
-    ///  - `physical_start` is of type `u64`, but using a large physical address
-    ///    (> 4GiB) isn't supported yet.
+    ///  - The physical address range must be a subset of
+    ///    `0..0x100_0000_0000` (40 bits).
     ///
     /// The memory section is configured as a read/writable (but not
     /// executable) Normal memory with a Outer and Inner Write-Back,
     /// Write-Allocate attribute.
     ///
+    /// The startup code maps 1MiB blocks whose virtual and physical addresses
+    /// are both aligned to 1MiB by section descriptors and the rest by small
+    /// page (4KiB) descriptors. Each 1MiB block containing small pages
+    /// consumes a 1KiB second-level page table.
+    ///
+    /// Physical addresses above 4GiB can only be mapped by supersection
+    /// (16MiB) descriptors, so the corresponding part of the section must be
+    /// aligned to 16MiB blocks in both address spaces. This requires a
+    /// processor that supports supersections with extended base addresses,
+    /// which is the case for all processors implementing the Large Physical
+    /// Address Extension (e.g., Cortex-A7 and Cortex-A15). The startup code
+    /// panics at compile time if a mapping can't be represented.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -88,6 +192,10 @@ impl MemoryMapSection {
     ///
     /// // Map VA `0x2000_0000..0x2800_0000` to PA `0xc000_0000.0xc800_0000`
     /// MemoryMapSection::new(0x2000_0000..0x2800_0000, 0xc000_0000);
+    ///
+    /// // Map VA `0x4000_0000..0x4100_0000` to PA `0x1_0000_0000..0x1_0100_0000`
+    /// // (by a supersection)
+    /// MemoryMapSection::new(0x4000_0000..0x4100_0000, 0x1_0000_0000);
     /// ```
     ///
     /// This function panics if an invalid parameter is supplied.
@@ -103,12 +211,24 @@ impl MemoryMapSection {
     /// // VA is not in range `0..0x1_0000_0000`
     /// MemoryMapSection::new(0x9000_0000..0x11000_0000, 0xc000_0000);
     /// ```
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_arm::MemoryMapSection;
+    /// // Not aligned to 4KiB pages
+    /// MemoryMapSection::new(0x2000_0800..0x2000_1000, 0xc000_0800);
+    /// ```
+    ///
+    /// ```rust,should_panic
+    /// # use r3_port_arm::MemoryMapSection;
+    /// // PA is not in range `0..0x100_0000_0000`
+    /// MemoryMapSection::new(0x2000_0000..0x3000_0000, 0xff_f800_0000);
+    /// ```
     pub const fn new(virtual_range: Range<u64>, physical_start: u64) -> Self {
-        if (virtual_range.start & 0xfffff) != 0
-            || (virtual_range.end & 0xfffff) != 0
-            || (physical_start & 0xfffff) != 0
+        if (virtual_range.start & 0xfff) != 0
+            || (virtual_range.end & 0xfff) != 0
+            || (physical_start & 0xfff) != 0
         {
-            panic!("all endpoints must be aligned to 1MiB blocks");
+            panic!("all endpoints must be aligned to 4KiB pages");
         }
 
         if virtual_range.start >= virtual_range.end {
@@ -123,6 +243,10 @@ impl MemoryMapSection {
             panic!("`virtual_range` must be a strict subset of `0..0x1_0000_0000`");
         }
 
+        if physical_start + (virtual_range.end - virtual_range.start) > 0x100_0000_0000 {
+            panic!("the physical address range must be a subset of `0..0x100_0000_0000`");
+        }
+
         Self {
             physical_start,
             virtual_start: virtual_range.start as usize,
@@ -165,6 +289,104 @@ impl MemoryMapSection {
     }
 }
 
+/// The kind of a translation table descriptor mapping a [`Block`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum BlockKind {
+    /// A 16MiB supersection, mapped by 16 identical first-level descriptors
+    Supersection,
+    /// A 1MiB section, mapped by a first-level descriptor
+    Section,
+    /// A 4KiB small page, mapped by a second-level descriptor
+    SmallPage,
+}
+
+impl BlockKind {
+    pub(super) const fn size(self) -> usize {
+        match self {
+            Self::Supersection => 0x100_0000,
+            Self::Section => 0x10_0000,
+            Self::SmallPage => 0x1000,
+        }
+    }
+
+    /// Check if a block of this kind can map the beginning of the given
+    /// range.
+    const fn fits(self, virtual_start: usize, physical_start: u64, len: usize) -> bool {
+        let size = self.size();
+        virtual_start % size == 0 && physical_start % size as u64 == 0 && len >= size
+    }
+
+    /// Choose the largest descriptor kind that can map the beginning of the
+    /// given range.
+    const fn choose(virtual_start: usize, physical_start: u64, len: usize) -> Self {
+        if physical_start >= 0x1_0000_0000 {
+            if !Self::Supersection.fits(virtual_start, physical_start, len) {
+                panic!(
+                    "physical addresses above 4GiB can only be mapped by \
+                    supersections, which must be aligned to 16MiB blocks"
+                );
+            }
+            Self::Supersection
+        } else if Self::Section.fits(virtual_start, physical_start, len) {
+            Self::Section
+        } else {
+            Self::SmallPage
+        }
+    }
+}
+
+/// A block of memory mapped by a single translation table descriptor (or
+/// an identical set of descriptors in case of a supersection).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) struct Block {
+    pub(super) virtual_start: usize,
+    pub(super) physical_start: u64,
+    pub(super) kind: BlockKind,
+    pub(super) attr: MemoryRegionAttributes,
+}
+
+/// Splits [`MemoryMapSection`]s into [`Block`]s.
+pub(super) struct Blocks<'a> {
+    sections: &'a [MemoryMapSection],
+    /// The offset into `sections[0]`
+    offset: usize,
+}
+
+impl<'a> Blocks<'a> {
+    pub(super) const fn new(sections: &'a [MemoryMapSection]) -> Self {
+        Self {
+            sections,
+            offset: 0,
+        }
+    }
+
+    /// Get the next block. (`Iterator::next` is not `const fn`.)
+    pub(super) const fn next(&mut self) -> Option<Block> {
+        let [section, rest @ ..] = self.sections else {
+            return None;
+        };
+
+        let block = Block {
+            virtual_start: section.virtual_start + self.offset,
+            physical_start: section.physical_start + self.offset as u64,
+            kind: BlockKind::choose(
+                section.virtual_start + self.offset,
+                section.physical_start + self.offset as u64,
+                section.len - self.offset,
+            ),
+            attr: section.attr,
+        };
+
+        self.offset += block.kind.size();
+        if self.offset == section.len {
+            self.sections = rest;
+            self.offset = 0;
+        }
+
+        Some(block)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub(super) struct MemoryRegionAttributes {
     pub tex: u8,
@@ -251,7 +473,53 @@ impl MemoryRegionAttributes {
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
     use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn blocks() {
+        let mmap = [
+            // Small pages followed by a section
+            MemoryMapSection::new(0x0000_1000..0x0020_0000, 0x0000_1000),
+            // Small pages only because of the misalignment of PA
+            MemoryMapSection::new(0x1000_0000..0x1010_0000, 0x2000_1000).as_device_memory(),
+            // A supersection
+            MemoryMapSection::new(0x4000_0000..0x4100_0000, 0x1_0000_0000),
+        ];
+
+        let mut blocks = Blocks::new(&mmap);
+        let mut kinds = Vec::new();
+        while let Some(block) = blocks.next() {
+            kinds.push((block.virtual_start, block.physical_start, block.kind));
+        }
+
+        let mut expected = Vec::new();
+        expected.extend((1..0x100).map(|i| (i * 0x1000, i as u64 * 0x1000, BlockKind::SmallPage)));
+        expected.push((0x10_0000, 0x10_0000, BlockKind::Section));
+        expected.extend((0..0x100).map(|i| {
+            (
+                0x1000_0000 + i * 0x1000,
+                0x2000_1000 + i as u64 * 0x1000,
+                BlockKind::SmallPage,
+            )
+        }));
+        expected.push((0x4000_0000, 0x1_0000_0000, BlockKind::Supersection));
+
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    #[should_panic = "supersections"]
+    fn blocks_unaligned_large_pa() {
+        let mmap = [MemoryMapSection::new(
+            0x4000_0000..0x4100_0000,
+            0x1_0010_0000,
+        )];
+        let mut blocks = Blocks::new(&mmap);
+        while blocks.next().is_some() {}
+    }
 
     #[test]
     fn memory_attributes() {
@@ -1,8 +1,13 @@
 //! Provides a standard startup and entry code implementation.
 use core::arch::asm;
-use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+use r3_portkit::pptext::pp_asm;
+use tock_registers::interfaces::{ReadWriteable, Writeable};
 
-use crate::{arm, startup::cfg::MemoryRegionAttributes, EntryPoint, StartupOptions};
+use crate::{
+    arm, cache,
+    startup::cfg::{BlockKind, Blocks, MemoryRegionAttributes},
+    EntryPoint, StartupOptions,
+};
 
 #[repr(align(4096), C)]
 struct VectorTable {
@@ -19,10 +24,10 @@ impl VectorTable {
             // trampolines[N]
             _targets: [
                 unhandled_exception_handler,
-                undefined_instruction_handler,
+                Traits::UNDEFINED_INSTRUCTION_ENTRY,
                 supervisor_call_handler,
-                prefetch_abort_handler,
-                data_abort_handler,
+                Traits::PREFETCH_ABORT_ENTRY,
+                Traits::DATA_ABORT_ENTRY,
                 unhandled_exception_handler,
                 Traits::IRQ_ENTRY,
                 fiq_handler,
@@ -32,10 +37,10 @@ impl VectorTable {
 }
 
 #[naked]
-pub extern "C" fn start<Traits: EntryPoint + StartupOptions>() {
+pub extern "C" fn start<Traits: EntryPoint + StartupOptions + SecondLevelPageTables>() {
     unsafe {
         // Set the stack pointer before calling Rust code
-        asm!("
+        pp_asm!("
             ldr r0, =_stack_start
 
             # Set the stack for IRQ mode
@@ -58,6 +63,17 @@ pub extern "C" fn start<Traits: EntryPoint + StartupOptions>() {
             # set) set the stack for Supervisor mode
             msr cpsr_c, #0xd3
             mov sp, r0
+        "   if cfg!(target_feature = "vfp2") {                                      "
+            # Allow access to the VFP (CPACR.cp10 = CPACR.cp11 = full access)
+            # and enable it (FPEXC.EN = 1) because the compiler may generate
+            # VFP instructions anywhere
+            mrc p15, 0, r0, c1, c0, 2
+            orr r0, #0xf00000
+            mcr p15, 0, r0, c1, c0, 2
+            isb
+            mov r0, #0x40000000
+            vmsr fpexc, r0
+        "   }                                                                       "
 
             b {reset_handler1}
             ",
@@ -67,7 +83,7 @@ pub extern "C" fn start<Traits: EntryPoint + StartupOptions>() {
     }
 }
 
-extern "C" fn reset_handler1<Traits: EntryPoint + StartupOptions>() {
+extern "C" fn reset_handler1<Traits: EntryPoint + StartupOptions + SecondLevelPageTables>() {
     arm::SCTLR.modify(
         // Disable data and unified caches
         arm::SCTLR::C::Disable +
@@ -82,41 +98,10 @@ extern "C" fn reset_handler1<Traits: EntryPoint + StartupOptions>() {
     arm::BPIALL.set(0);
 
     // Invalidate data and unified cache
-    // This part is based on the section “8.9.1. Example code for cache
-    // maintenance operations” of Cortex-A Series Programmers Guide 4.0.
-    //
-    // Level of Coherency: “This field defines the last level of cache that must
-    // be cleaned or invalidated when cleaning or invalidating to the point of
-    // coherency.”
-    let clidr = arm::CLIDR.extract();
-    let level_of_coherency = clidr.read(arm::CLIDR::LoC);
-    for level in 0..level_of_coherency {
-        let cache_type = (clidr.get() >> (level * 3)) & 0b111;
-
-        // Does this cache level include a data or unified cache?
-        if cache_type >= 2 {
-            // Level = level, InD = 0
-            // Use `isb` to make sure the change to CSSELR takes effect.
-            arm::CSSELR.set(level * 2);
-            unsafe { asm!("isb") };
-
-            let cssidr = arm::CCSIDR.extract();
-            let log2_line_size = cssidr.read(arm::CCSIDR::LineSize) + 4;
-            let max_way_index = cssidr.read(arm::CCSIDR::Associativity);
-            let max_set_index = cssidr.read(arm::CCSIDR::NumSets);
-
-            let way_offset = max_way_index.leading_zeros();
-
-            for way in (0..=max_way_index).rev() {
-                for set in (0..=max_set_index).rev() {
-                    let set_way = (level << 1) | (way << way_offset) | (set << log2_line_size);
-
-                    // Invalidate by set/way
-                    arm::DCISW.set(set_way);
-                }
-            }
-        }
-    }
+    cache::imp::invalidate_inner_dcache_all();
+
+    // Invalidate and enable the outer L2 cache
+    unsafe { cache::imp::init_pl310::<Traits>() };
 
     // Configure MMU
     let page_table_ptr = (&Traits::PAGE_TABLE) as *const _ as usize;
@@ -197,22 +182,10 @@ extern "C" fn unhandled_exception_handler() -> ! {
     panic!("reserved exception");
 }
 
-extern "C" fn undefined_instruction_handler() -> ! {
-    panic!("undefined instruction");
-}
-
 extern "C" fn supervisor_call_handler() -> ! {
     panic!("unexpected supervisor call");
 }
 
-extern "C" fn prefetch_abort_handler() -> ! {
-    panic!("prefetch abort");
-}
-
-extern "C" fn data_abort_handler() -> ! {
-    panic!("data abort");
-}
-
 extern "C" fn fiq_handler() -> ! {
     panic!("unexpecte fiq");
 }
@@ -220,6 +193,14 @@ extern "C" fn fiq_handler() -> ! {
 // Page table generation
 // -----------------------------------------------------------------------
 
+/// Provides the storage for second-level page tables, whose number depends on
+/// [`StartupOptions::MEMORY_MAP`]. Implemented by [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub trait SecondLevelPageTables {
+    const SECOND_LEVEL_PAGE_TABLES: &'static [SecondLevelPageTable];
+}
+
 /// The extension trait for deriving static data based on `StartupOptions`.
 trait StartupExt {
     const VECTOR_TABLE: VectorTable;
@@ -228,14 +209,10 @@ trait StartupExt {
     /// `0xffff0000`.
     const VECTOR_HIGH: bool;
 
-    /// The page table for `0x000xxxxx` or `0xfffxxxxx` (depending on
-    /// `VECTOR_HIGH`).
-    const VECTOR_PAGE_TABLE: SecondLevelPageTable;
-
     const PAGE_TABLE: FirstLevelPageTable;
 }
 
-impl<T: StartupOptions + EntryPoint> StartupExt for T {
+impl<T: StartupOptions + EntryPoint + SecondLevelPageTables> StartupExt for T {
     const VECTOR_TABLE: VectorTable = VectorTable::new::<Self>();
 
     const VECTOR_HIGH: bool = {
@@ -254,53 +231,27 @@ impl<T: StartupOptions + EntryPoint> StartupExt for T {
         }
     };
 
-    const VECTOR_PAGE_TABLE: SecondLevelPageTable = {
-        let mut table = SecondLevelPageTable {
-            entries: [SecondLevelPageEntry::fault(); 256],
-        };
-
-        let vector_table = <*const _>::cast_mut(&Self::VECTOR_TABLE).cast();
-
-        let attr = MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE
-            .with_sharable(false)
-            .with_writable(false)
-            .with_executable(true);
-
-        if Self::VECTOR_HIGH {
-            // This second-level table is for `0xfffxxxxx`.
-            // Make a page entry for the virtual address `0xffff0xxx`.
-            table.entries[0xf0] = SecondLevelPageEntry::page_ptr(vector_table, attr);
-        } else {
-            // This second-level table is for `0x000xxxxx`.
-            // Make a page entry for the virtual address `0x00000xxx`.
-            table.entries[0x00] = SecondLevelPageEntry::page_ptr(vector_table, attr);
-        }
-
-        table
-    };
-
     const PAGE_TABLE: FirstLevelPageTable = {
         let mut table = FirstLevelPageTable {
             entries: [FirstLevelPageEntry::fault(); 4096],
         };
         let mut occupied = [false; 4096];
 
-        if Self::VECTOR_HIGH {
-            table.entries[0xfff] = FirstLevelPageEntry::page_table(&Self::VECTOR_PAGE_TABLE);
-            occupied[0xfff] = true;
-        } else {
-            table.entries[0x000] = FirstLevelPageEntry::page_table(&Self::VECTOR_PAGE_TABLE);
-            occupied[0x000] = true;
-        }
+        // Create section and supersection entries based on `MEMORY_MAP`
+        let mut blocks = Blocks::new(Self::MEMORY_MAP);
+        while let Some(block) = blocks.next() {
+            let entry = match block.kind {
+                BlockKind::Supersection => {
+                    FirstLevelPageEntry::supersection(block.physical_start, block.attr)
+                }
+                BlockKind::Section => {
+                    FirstLevelPageEntry::section(block.physical_start as u32, block.attr)
+                }
+                BlockKind::SmallPage => continue,
+            };
 
-        // Create section entries based on `MEMORY_MAP`
-        let mmap = Self::MEMORY_MAP;
-        // `for` is unusable in `const fn` [ref:const_for]
-        let mut i = 0;
-        while i < mmap.len() {
-            let section = &mmap[i];
-            let start_i = section.virtual_start / 0x100000;
-            let end_i = start_i + section.len / 0x100000;
+            let start_i = block.virtual_start / 0x100000;
+            let end_i = start_i + block.kind.size() / 0x100000;
 
             // `for` is unusable in `const fn` [ref:const_for]
             let mut k = start_i;
@@ -308,15 +259,25 @@ impl<T: StartupOptions + EntryPoint> StartupExt for T {
                 if occupied[k] {
                     panic!("region overlap; some address ranges are specified more than once");
                 }
-                table.entries[k] = FirstLevelPageEntry::section(
-                    section.physical_start as u32 + ((k - start_i) * 0x100000) as u32,
-                    section.attr,
-                );
+                table.entries[k] = entry;
                 occupied[k] = true;
                 k += 1;
             }
+        }
 
-            i += 1;
+        // Link the second-level page tables
+        let indices = second_level_page_table_indices::<Self>();
+        // `for` is unusable in `const fn` [ref:const_for]
+        let mut k = 0;
+        while k < 4096 {
+            if let Some(i) = indices[k] {
+                if occupied[k] {
+                    panic!("region overlap; some address ranges are specified more than once");
+                }
+                table.entries[k] =
+                    FirstLevelPageEntry::page_table(&Self::SECOND_LEVEL_PAGE_TABLES[i]);
+            }
+            k += 1;
         }
 
         table
@@ -337,6 +298,113 @@ const fn memory_map_maps_va<T: StartupOptions>(va: usize) -> bool {
     false
 }
 
+/// Get the index into [`SecondLevelPageTables::SECOND_LEVEL_PAGE_TABLES`] for
+/// each 1MiB block of the virtual address space that needs a second-level page
+/// table.
+///
+/// A second-level page table is needed for the exception vector table and for
+/// each 1MiB block containing small pages.
+const fn second_level_page_table_indices<T: StartupExt + StartupOptions>() -> [Option<usize>; 4096]
+{
+    let mut needed = [false; 4096];
+
+    if T::VECTOR_HIGH {
+        needed[0xfff] = true;
+    } else {
+        needed[0x000] = true;
+    }
+
+    let mut blocks = Blocks::new(T::MEMORY_MAP);
+    while let Some(block) = blocks.next() {
+        if let BlockKind::SmallPage = block.kind {
+            needed[block.virtual_start / 0x100000] = true;
+        }
+    }
+
+    let mut indices = [None; 4096];
+    let mut num_tables = 0;
+    // `for` is unusable in `const fn` [ref:const_for]
+    let mut k = 0;
+    while k < 4096 {
+        if needed[k] {
+            indices[k] = Some(num_tables);
+            num_tables += 1;
+        }
+        k += 1;
+    }
+
+    indices
+}
+
+/// Get the number of second-level page tables required by `T`. Used by
+/// [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub const fn num_second_level_page_tables<
+    T: StartupOptions + EntryPoint + SecondLevelPageTables,
+>() -> usize {
+    let indices = second_level_page_table_indices::<T>();
+    let mut num_tables = 0;
+    // `for` is unusable in `const fn` [ref:const_for]
+    let mut k = 0;
+    while k < 4096 {
+        if indices[k].is_some() {
+            num_tables += 1;
+        }
+        k += 1;
+    }
+    num_tables
+}
+
+/// Construct the second-level page tables for `T`. `N` must be equal to
+/// [`num_second_level_page_tables`]`::<T>()`. Used by [`use_startup!`].
+///
+/// [`use_startup!`]: crate::use_startup
+pub const fn second_level_page_tables<
+    T: StartupOptions + EntryPoint + SecondLevelPageTables,
+    const N: usize,
+>() -> [SecondLevelPageTable; N] {
+    let indices = second_level_page_table_indices::<T>();
+    let mut tables = [SecondLevelPageTable {
+        entries: [SecondLevelPageEntry::fault(); 256],
+    }; N];
+    let mut occupied = [[false; 256]; N];
+
+    // Map the exception vector table
+    let vector_table = <*const _>::cast_mut(&T::VECTOR_TABLE).cast();
+
+    let attr = MemoryRegionAttributes::NORMAL_WB_WA_SHARABLE_READ_WRITE
+        .with_sharable(false)
+        .with_writable(false)
+        .with_executable(true);
+
+    // Make a page entry for the virtual address `0xffff0xxx` or `0x00000xxx`
+    let (k, page_i) = if T::VECTOR_HIGH {
+        (0xfff, 0xf0)
+    } else {
+        (0x000, 0x00)
+    };
+    let Some(i) = indices[k] else { unreachable!() };
+    tables[i].entries[page_i] = SecondLevelPageEntry::page_ptr(vector_table, attr);
+    occupied[i][page_i] = true;
+
+    // Create small page entries based on `MEMORY_MAP`
+    let mut blocks = Blocks::new(T::MEMORY_MAP);
+    while let Some(block) = blocks.next() {
+        let BlockKind::SmallPage = block.kind else { continue };
+        let Some(i) = indices[block.virtual_start / 0x100000] else { unreachable!() };
+        let page_i = (block.virtual_start / 0x1000) % 256;
+        if occupied[i][page_i] {
+            panic!("region overlap; some address ranges are specified more than once");
+        }
+        tables[i].entries[page_i] =
+            SecondLevelPageEntry::page(block.physical_start as u32, block.attr);
+        occupied[i][page_i] = true;
+    }
+
+    tables
+}
+
 #[repr(align(16384))]
 #[derive(Clone, Copy)]
 struct FirstLevelPageTable {
@@ -372,6 +440,34 @@ impl FirstLevelPageEntry {
 
     /// Construct a section entry.
     const fn section(pa: u32, attr: MemoryRegionAttributes) -> Self {
+        let domain = 0u32;
+
+        assert!(pa & 0xfffff == 0);
+
+        Self {
+            int: pa | (domain << 5) | Self::section_attr_bits(attr),
+        }
+    }
+
+    /// Construct a supersection entry. The same entry must be repeated in 16
+    /// consecutive locations.
+    const fn supersection(pa: u64, attr: MemoryRegionAttributes) -> Self {
+        assert!(pa & 0xffffff == 0);
+
+        Self {
+            int: (pa as u32)
+                // Extended base address, PA[35:32]
+                | (((pa >> 32) as u32 & 0xf) << 20)
+                // Extended base address, PA[39:36]
+                | (((pa >> 36) as u32 & 0xf) << 5)
+                // Supersection
+                | (1 << 18)
+                | Self::section_attr_bits(attr),
+        }
+    }
+
+    /// Get the bits shared by section and supersection entries.
+    const fn section_attr_bits(attr: MemoryRegionAttributes) -> u32 {
         let MemoryRegionAttributes {
             tex,
             c,
@@ -380,34 +476,28 @@ impl FirstLevelPageEntry {
             ap,
             xn,
         } = attr;
-        let domain = 0u32;
         let ns = false; // Secure access
         let ng = false; // global (not Not-Global)
         let pxn = false; // not using Large Physical Address Extension
 
-        assert!(pa & 0xfffff == 0);
-
-        Self {
-            int: pa
-                | ((ns as u32) << 19)
-                | ((ng as u32) << 17)
-                | ((s as u32) << 16)
-                | ((ap as u32 >> 2) << 15)
-                | ((tex as u32) << 12)
-                | ((ap as u32 & 0b11) << 10)
-                | (domain << 5)
-                | ((xn as u32) << 4)
-                | ((c as u32) << 3)
-                | ((b as u32) << 2)
-                | 0b10
-                | (pxn as u32),
-        }
+        ((ns as u32) << 19)
+            | ((ng as u32) << 17)
+            | ((s as u32) << 16)
+            | ((ap as u32 >> 2) << 15)
+            | ((tex as u32) << 12)
+            | ((ap as u32 & 0b11) << 10)
+            | ((xn as u32) << 4)
+            | ((c as u32) << 3)
+            | ((b as u32) << 2)
+            | 0b10
+            | (pxn as u32)
     }
 }
 
+#[doc(hidden)]
 #[repr(align(1024))]
 #[derive(Clone, Copy)]
-struct SecondLevelPageTable {
+pub struct SecondLevelPageTable {
     entries: [SecondLevelPageEntry; 256],
 }
 
@@ -424,11 +514,27 @@ impl SecondLevelPageEntry {
         Self { int: 0 }
     }
 
+    /// Construct a small page entry.
+    const fn page(pa: u32, attr: MemoryRegionAttributes) -> Self {
+        assert!(pa & 0xfff == 0);
+
+        Self {
+            int: pa | Self::page_attr_bits(attr),
+        }
+    }
+
     /// Construct a page entry. Assumes physical address == virtual address for
     /// `ptr`. This method essentially creates an alias for `ptr`.
     ///
     /// The 12 LSBs of `ptr` must be zero.
     const fn page_ptr(ptr: *mut u8, attr: MemoryRegionAttributes) -> Self {
+        Self {
+            ptr: ptr.wrapping_add(Self::page_attr_bits(attr) as usize),
+        }
+    }
+
+    /// Get the attribute bits of a small page entry.
+    const fn page_attr_bits(attr: MemoryRegionAttributes) -> u32 {
         let MemoryRegionAttributes {
             tex,
             c,
@@ -439,18 +545,14 @@ impl SecondLevelPageEntry {
         } = attr;
         let ng = false; // global (not Not-Global)
 
-        Self {
-            ptr: ptr.wrapping_add(
-                (((ng as u32) << 11)
-                    | ((s as u32) << 10)
-                    | ((ap as u32 >> 2) << 9)
-                    | ((tex as u32) << 6)
-                    | ((ap as u32 & 0b11) << 4)
-                    | ((c as u32) << 3)
-                    | ((b as u32) << 2)
-                    | 0b10
-                    | (xn as u32)) as usize,
-            ),
-        }
+        ((ng as u32) << 11)
+            | ((s as u32) << 10)
+            | ((ap as u32 >> 2) << 9)
+            | ((tex as u32) << 6)
+            | ((ap as u32 & 0b11) << 4)
+            | ((c as u32) << 3)
+            | ((b as u32) << 2)
+            | 0b10
+            | (xn as u32)
     }
 }
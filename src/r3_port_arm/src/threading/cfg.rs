@@ -1,5 +1,20 @@
 /// The configuration of the port.
-pub trait ThreadingOptions {}
+pub trait ThreadingOptions {
+    /// Called by the fault handler after a [`FaultRecord`] is written to the
+    /// crash record storage (see [the crate-level documentation]). The fault
+    /// handler panics after this method returns.
+    ///
+    /// This method runs in the fault context (Abort mode or Undefined mode
+    /// with IRQs masked) and must not call kernel services. It may choose not
+    /// to return, e.g., by resetting the system.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// [`FaultRecord`]: crate::FaultRecord
+    /// [the crate-level documentation]: crate#fault-handling
+    #[inline]
+    fn handle_fault(_record: &crate::FaultRecord) {}
+}
 
 /// An abstract interface to an interrupt controller. Implemented by
 /// [`use_gic!`].
@@ -66,6 +81,19 @@ pub trait EntryPoint {
     ///    that the handler can restore it later.
     ///
     const IRQ_ENTRY: unsafe extern "C" fn() -> !;
+
+    /// The Undefined Instruction, Prefetch Abort, and Data Abort handlers.
+    ///
+    /// # Safety
+    ///
+    ///  - Each handler should be registered as the corresponding exception
+    ///    handler.
+    ///
+    const UNDEFINED_INSTRUCTION_ENTRY: unsafe extern "C" fn() -> !;
+    /// See [`Self::UNDEFINED_INSTRUCTION_ENTRY`].
+    const PREFETCH_ABORT_ENTRY: unsafe extern "C" fn() -> !;
+    /// See [`Self::UNDEFINED_INSTRUCTION_ENTRY`].
+    const DATA_ABORT_ENTRY: unsafe extern "C" fn() -> !;
 }
 
 /// Define a kernel trait type implementing [`PortThreading`] and
@@ -107,6 +135,13 @@ macro_rules! use_port {
                 }
 
                 const IRQ_ENTRY: unsafe extern "C" fn() -> ! = State::irq_entry::<Self>;
+
+                const UNDEFINED_INSTRUCTION_ENTRY: unsafe extern "C" fn() -> ! =
+                    $crate::fault::imp::undefined_instruction_entry::<Self>;
+                const PREFETCH_ABORT_ENTRY: unsafe extern "C" fn() -> ! =
+                    $crate::fault::imp::prefetch_abort_entry::<Self>;
+                const DATA_ABORT_ENTRY: unsafe extern "C" fn() -> ! =
+                    $crate::fault::imp::data_abort_entry::<Self>;
             }
 
             // Assume `$Traits: Kernel`
@@ -2,7 +2,7 @@ use core::{arch::asm, cell::UnsafeCell, mem::MaybeUninit, slice};
 use memoffset::offset_of;
 use r3_core::{kernel::traits, utils::Init};
 use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb};
-use r3_portkit::sym::sym_static;
+use r3_portkit::{pptext::pp_asm, sym::sym_static};
 
 use super::cfg::{InterruptController, ThreadingOptions, Timer};
 
@@ -44,6 +44,74 @@ impl Init for State {
     };
 }
 
+/// `FPEXC.EN`
+pub(crate) const FPEXC_EN: u32 = 1 << 30;
+
+/// The assembly code fragments used by `pp_asm!`. Because of a mysterious macro
+/// hygienics behavior, they have to referred to by absolute paths.
+#[rustfmt::skip]
+#[doc(hidden)]
+pub mod asm_inc {
+    // define_vfp_macros - defines the macros for saving/restoring the
+    // first-level VFP state
+    // -----------------------------------------------------------------
+    //
+    // `PUSH_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr` pushes `FPEXC` and, if
+    // `FPEXC.EN` (`1 << 30`) is set, `FPSCR` and the caller-saved VFP
+    // registers. `POP_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr` does the
+    // opposite. Both clobber the specified registers and the condition flags.
+    #[cfg(target_feature = "d32")]
+    pub macro define_vfp_macros() {r"
+        .ifndef vfp_macros_defined
+            .set vfp_macros_defined, 1
+            .macro PUSH_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr
+                vmrs \r_fpexc, fpexc
+                tst \r_fpexc, #0x40000000
+                vmrsne \r_fpscr, fpscr
+                vpushne {{d16-d31}}
+                vpushne {{d0-d7}}
+                pushne {{\r_fpscr}}
+                push {{\r_fpexc}}
+            .endm
+            .macro POP_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr
+                pop {{\r_fpexc}}
+                tst \r_fpexc, #0x40000000
+                popne {{\r_fpscr}}
+                vpopne {{d0-d7}}
+                vpopne {{d16-d31}}
+                vmsrne fpscr, \r_fpscr
+                vmsr fpexc, \r_fpexc
+            .endm
+        .endif
+    "}
+
+    #[cfg(all(target_feature = "vfp2", not(target_feature = "d32")))]
+    pub macro define_vfp_macros() {r"
+        .ifndef vfp_macros_defined
+            .set vfp_macros_defined, 1
+            .macro PUSH_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr
+                vmrs \r_fpexc, fpexc
+                tst \r_fpexc, #0x40000000
+                vmrsne \r_fpscr, fpscr
+                vpushne {{d0-d7}}
+                pushne {{\r_fpscr}}
+                push {{\r_fpexc}}
+            .endm
+            .macro POP_FIRST_LEVEL_VFP_STATE r_fpexc, r_fpscr
+                pop {{\r_fpexc}}
+                tst \r_fpexc, #0x40000000
+                popne {{\r_fpscr}}
+                vpopne {{d0-d7}}
+                vmsrne fpscr, \r_fpscr
+                vmsr fpexc, \r_fpexc
+            .endm
+        .endif
+    "}
+
+    #[cfg(not(target_feature = "vfp2"))]
+    pub macro define_vfp_macros() {""}
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct TaskState {
@@ -112,7 +180,8 @@ impl State {
         }
 
         unsafe {
-            asm!("
+            pp_asm!("
+            "   crate::threading::imp::asm_inc::define_vfp_macros!()                "
                 # Push the first level context state. The return address is
                 # set to `YieldReturn`. The value of CPSR is captured before
                 # `cpsid i` so that interrupts are re-enabled when the current
@@ -132,6 +201,9 @@ impl State {
                 push {{r12, lr}}
                 subs sp, #8
                 push {{r0, r1}}
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                    PUSH_FIRST_LEVEL_VFP_STATE r2, r3
+            "   }                                                                   "
 
                 cpsid i
                 b {push_second_level_state_and_dispatch}
@@ -179,7 +251,8 @@ impl State {
         }
 
         unsafe {
-            asm!("
+            pp_asm!("
+            "   crate::threading::imp::asm_inc::define_vfp_macros!()                "
                 movw r0, :lower16:{p_port_state}_
                 movt r0, :upper16:{p_port_state}_
                 ldr r0, [r0]
@@ -203,6 +276,26 @@ impl State {
 
                 # Push the second-level context state.
                 push {{r4-r11}}
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                # The VFP state is in use if it was in use when the first-level
+                # state was pushed. We can't check `FPEXC` here because it might
+                # have been modified by an interrupt handler.
+                #
+                #   <sp_usr[8] = first-level FPEXC>
+                #   r1 = sp_usr[8];
+                #   if r1.EN:
+                #       sp_usr -= 16;
+                #       sp_usr[0..16] = [d8-d15];
+                #   sp_usr -= 1;
+                #   sp_usr[0] = r1;
+                #
+                ldr r1, [sp, #32]
+                tst r1, #{FPEXC_EN}
+                vpushne {{d8-d15}}
+                push {{r1}}
+            "   } else {                                                            "
+                # unused: {FPEXC_EN}
+            "   }                                                                   "
 
                 # Store SP to `TaskState`.
                 #
@@ -232,7 +325,22 @@ impl State {
                 beq {idle_task}
                 ldr sp, [r0]
 
-                # Pop the second-level context state.
+                # Pop the second-level context state. Restoring `FPEXC` here
+                # enables the VFP if the task was using it, so that the VFP
+                # registers can be restored.
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                #
+                #   FPEXC = sp_usr[0];
+                #   sp_usr += 1;
+                #   if FPEXC.EN:
+                #       [d8-d15] = sp_usr[0..16];
+                #       sp_usr += 16;
+                #
+                pop {{r1}}
+                vmsr fpexc, r1
+                tst r1, #{FPEXC_EN}
+                vpopne {{d8-d15}}
+            "   }                                                                   "
                 pop {{r4-r11}}
 
             .global {push_second_level_state_and_dispatch}.pop_first_level_state
@@ -240,6 +348,9 @@ impl State {
                 # Reset the local monitor's state (this will cause a
                 # subsequent Store-Exclusive to fail)
                 clrex
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                POP_FIRST_LEVEL_VFP_STATE r0, r1
+            "   }                                                                   "
 
                 # Resume the next task by restoring the first-level state
                 #
@@ -264,6 +375,7 @@ impl State {
                 p_port_state = sym Traits::p_port_state,
                 OFFSET_RUNNING_TASK_PTR = const Self::OFFSET_RUNNING_TASK_PTR,
                 OFFSET_MAIN_STACK = const Self::OFFSET_MAIN_STACK,
+                FPEXC_EN = const FPEXC_EN,
                 options(noreturn),
             );
         }
@@ -411,6 +523,13 @@ impl State {
         // CPSR: System mode
         first_level[7] = MaybeUninit::new(0x0000001f);
 
+        // FPEXC: The VFP is disabled until the task uses it
+        // [ref:arm_vfp_lazy_enable]
+        if cfg!(target_feature = "vfp2") {
+            sp = sp.wrapping_sub(1);
+            unsafe { *sp = MaybeUninit::new(0) };
+        }
+
         // Second-level state (saved and restored only when we are doing context
         // switching)
         let extra_ctx = unsafe {
@@ -430,6 +549,12 @@ impl State {
             extra_ctx[7] = MaybeUninit::new(0x11111111);
         }
 
+        // FPEXC: Ditto
+        if cfg!(target_feature = "vfp2") {
+            sp = sp.wrapping_sub(1);
+            unsafe { *sp = MaybeUninit::new(0) };
+        }
+
         let task_state = &task.port_task_state;
         unsafe { *task_state.sp.get() = sp as _ };
     }
@@ -464,7 +589,8 @@ impl State {
     #[naked]
     pub unsafe extern "C" fn irq_entry<Traits: PortInstance>() -> ! {
         unsafe {
-            asm!("
+            pp_asm!("
+            "   crate::threading::imp::asm_inc::define_vfp_macros!()                "
                 # Adjust `lr_irq` to get the preferred return address. (The
                 # required adjustment is different for each exception type.)
                 subs lr, #4
@@ -524,6 +650,9 @@ impl State {
                 subs sp, #8
                 push {{r0-r3, r12, lr}}
                 mov r2, sp
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                PUSH_FIRST_LEVEL_VFP_STATE r0, r1
+            "   }                                                                   "
 
                 # Switch to IRQ mode. Save the return address to the background
                 # context's stack.
@@ -542,6 +671,15 @@ impl State {
 
                 # Switch to Supervisor mode.
                 cps #0x13
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                # Enable the VFP for the interrupt handler. The background
+                # context's VFP state, if any, has been saved by now.
+                vmrs r0, fpexc
+                orr r0, #{FPEXC_EN}
+                vmsr fpexc, r0
+            "   } else {                                                            "
+                # unused: {FPEXC_EN}
+            "   }                                                                   "
 
                 # Align `sp_svc` to 8 bytes and save the original `sp_svc`
                 # (this is required by AAPCS). At the same time, save `spsr_saved`
@@ -595,6 +733,9 @@ impl State {
                 #
                 cpsid i
                 clrex
+            "   if cfg!(target_feature = "vfp2") {                                  "
+                POP_FIRST_LEVEL_VFP_STATE r0, r1
+            "   }                                                                   "
                 pop {{r0-r3, r12, lr}}
                 rfeia sp!
 
@@ -611,6 +752,7 @@ impl State {
                 handle_irq = sym Self::handle_irq::<Traits>,
                 push_second_level_state_and_dispatch_shortcutting =
                     sym Self::push_second_level_state_and_dispatch_shortcutting::<Traits>,
+                FPEXC_EN = const FPEXC_EN,
                 options(noreturn),
             );
         }
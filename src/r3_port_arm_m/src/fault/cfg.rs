@@ -0,0 +1,156 @@
+use r3_portkit::crashdump::{CrashRecord, CrashRecordCell};
+
+/// The storage for the last [`FaultRecord`]. It's placed in `.uninit` so that
+/// it survives a reset.
+#[cfg_attr(
+    target_os = "none",
+    link_section = ".uninit.r3_port_arm_m.CRASH_RECORD"
+)]
+pub(crate) static CRASH_RECORD: CrashRecordCell<FaultRecord> = CrashRecordCell::new();
+
+/// Get and remove the [`FaultRecord`] written by the last fault, which may have
+/// happened before the last reset.
+///
+/// Returns `None` if no faults have been recorded since the record was last
+/// removed or since power-on.
+///
+/// # Safety
+///
+/// This function must not be called concurrently with itself.
+pub unsafe fn take_crash_record() -> Option<FaultRecord> {
+    // Safety: Fault handlers don't run concurrently with this function unless
+    //         this function itself causes a fault. The caller is responsible
+    //         for the rest.
+    unsafe { CRASH_RECORD.take() }
+}
+
+/// The exception that reported a fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// HardFault, e.g., a fault escalated because the corresponding handler
+    /// is disabled or masked.
+    HardFault,
+    /// MemManage, e.g., an MPU violation.
+    MemManage,
+    /// BusFault, e.g., an access to a privileged-only peripheral.
+    BusFault,
+    /// UsageFault, e.g., an undefined instruction.
+    UsageFault,
+}
+
+impl FaultKind {
+    /// Get the `FaultKind` corresponding to an exception number.
+    pub const fn from_exception(exception: u32) -> Option<Self> {
+        match exception {
+            3 => Some(Self::HardFault),
+            4 => Some(Self::MemManage),
+            5 => Some(Self::BusFault),
+            6 => Some(Self::UsageFault),
+            _ => None,
+        }
+    }
+
+    /// Get the `FaultKind` of the fault recorded in the Configurable Fault
+    /// Status Register (`CFSR`). This is useful for finding the original cause
+    /// of an escalated HardFault.
+    pub const fn from_cfsr(cfsr: u32) -> Option<Self> {
+        if cfsr & 0xff != 0 {
+            Some(Self::MemManage)
+        } else if cfsr & 0xff00 != 0 {
+            Some(Self::BusFault)
+        } else if cfsr & 0xffff0000 != 0 {
+            Some(Self::UsageFault)
+        } else {
+            None
+        }
+    }
+}
+
+/// A crash record describing a fault. Passed to
+/// [`ThreadingOptions::handle_fault`] and retained across a reset (see
+/// [`take_crash_record`]).
+///
+/// The fault status registers are read as zero on Armv6-M, which doesn't have
+/// them.
+///
+/// [`ThreadingOptions::handle_fault`]: crate::ThreadingOptions::handle_fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct FaultRecord {
+    /// The exception number of the fault handler (`IPSR.Exception`).
+    pub exception: u32,
+    /// The `EXC_RETURN` value of the fault handler.
+    pub exc_return: u32,
+    /// `R0`–`R3`, `R12`, `LR`, `PC`, and `xPSR` stacked on exception entry.
+    /// All zero if the stacking caused a fault.
+    pub frame: [u32; 8],
+    /// The stack pointer value of the faulting context.
+    pub sp: u32,
+    /// The Configurable Fault Status Register (`CFSR`).
+    pub cfsr: u32,
+    /// The HardFault Status Register (`HFSR`).
+    pub hfsr: u32,
+    /// The MemManage Fault Address Register (`MMFAR`).
+    pub mmfar: u32,
+    /// The BusFault Address Register (`BFAR`).
+    pub bfar: u32,
+    /// The ID of the task that caused the fault, or `0` if the fault was not
+    /// caused by a task.
+    pub task: u32,
+}
+
+// Safety: `FaultRecord` only consists of `u32` fields
+unsafe impl CrashRecord for FaultRecord {
+    const MAGIC: u32 = 0x7233_4d01;
+}
+
+/// `CFSR.MMARVALID`
+pub(crate) const CFSR_MMARVALID: u32 = 1 << 7;
+/// `CFSR.BFARVALID`
+pub(crate) const CFSR_BFARVALID: u32 = 1 << 15;
+
+impl FaultRecord {
+    /// Get the exception that reported the fault.
+    pub const fn kind(&self) -> Option<FaultKind> {
+        FaultKind::from_exception(self.exception)
+    }
+
+    /// Get the address of the faulting instruction. This is the stacked `PC`
+    /// value for a synchronous fault.
+    pub const fn pc(&self) -> u32 {
+        self.frame[6]
+    }
+
+    /// Get the ID of the task that caused the fault.
+    pub fn task(&self) -> Option<r3_kernel::Id> {
+        r3_kernel::Id::new(self.task as usize)
+    }
+
+    /// Get the faulting data address if it's valid.
+    pub const fn address(&self) -> Option<usize> {
+        if self.cfsr & CFSR_MMARVALID != 0 {
+            Some(self.mmfar as usize)
+        } else if self.cfsr & CFSR_BFARVALID != 0 {
+            Some(self.bfar as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Describes a fault that caused a task to be terminated. Passed to
+/// [`ThreadingOptions::handle_task_fault`].
+///
+/// [`ThreadingOptions::handle_task_fault`]: crate::ThreadingOptions::handle_task_fault
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct TaskFault {
+    /// The ID of the terminated task.
+    pub task: r3_kernel::Id,
+    /// The exception that reported the fault.
+    pub kind: FaultKind,
+    /// The value of the Configurable Fault Status Register (`CFSR`).
+    pub status: u32,
+    /// The faulting address (`MMFAR` or `BFAR`) if it's valid.
+    pub address: Option<usize>,
+}
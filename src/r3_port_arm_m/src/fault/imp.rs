@@ -0,0 +1,236 @@
+//! Fault handlers
+use r3_core::kernel::traits;
+use r3_kernel::{System, TaskCb};
+use r3_portkit::pptext::pp_asm;
+
+use crate::{
+    fault::cfg::{CFSR_BFARVALID, CFSR_MMARVALID, CRASH_RECORD},
+    mpu::imp::task_stack_region,
+    threading::imp::{read_control, PortInstance, CONTROL_NPRIV},
+    FaultKind, FaultRecord, TaskFault,
+};
+
+/// Configurable Fault Status Register
+#[cfg(not(armv6m))]
+const SCB_CFSR: *mut u32 = 0xe000_ed28usize as _;
+/// `CFSR.MSTKERR | CFSR.STKERR`
+const CFSR_STKERR: u32 = 1 << 4 | 1 << 12;
+/// HardFault Status Register
+#[cfg(not(armv6m))]
+const SCB_HFSR: *mut u32 = 0xe000_ed2cusize as _;
+/// `HFSR.FORCED`
+const HFSR_FORCED: u32 = 1 << 30;
+/// MemManage Fault Address Register
+#[cfg(not(armv6m))]
+const SCB_MMFAR: *const u32 = 0xe000_ed34usize as _;
+/// BusFault Address Register
+#[cfg(not(armv6m))]
+const SCB_BFAR: *const u32 = 0xe000_ed38usize as _;
+/// Floating-Point Context Control Register
+#[cfg(has_fpu)]
+const FPU_FPCCR: *mut u32 = 0xe000_ef34usize as _;
+#[cfg(has_fpu)]
+const FPU_FPCCR_LSPACT: u32 = 1;
+
+/// The handler for HardFault, MemManage, BusFault, and UsageFault.
+///
+/// # Safety
+///
+///  - This method must be registered as a HardFault, MemManage, BusFault, and
+///    UsageFault handler.
+///
+#[naked]
+pub unsafe extern "C" fn handle_fault<Traits: PortInstance>() {
+    pp_asm!(
+        "
+            # Pass EXC_RETURN and the exception frame's location in case it's
+            # in the main stack. Receive a new EXC_RETURN.
+            mov r0, lr
+            mrs r1, msp
+            bl {handle_fault_inner}
+            bx r0
+        ",
+        handle_fault_inner = sym handle_fault_inner::<Traits>,
+        options(noreturn),
+    );
+}
+
+/// The body of [`handle_fault`]. Records the fault, terminates the running
+/// task if the fault was caused by the task and is recoverable, and returns
+/// the `EXC_RETURN` value to use.
+extern "C" fn handle_fault_inner<Traits: PortInstance>(exc_return: u32, msp: *const u32) -> u32 {
+    // Tasks run in Thread mode and use PSP (`EXC_RETURN.Mode == 1 &&
+    // EXC_RETURN.SPSEL == 1`). A fault handler can't be preempted by the
+    // dispatcher, so `running_task` still refers to the faulting task.
+    // Safety: We don't race with the kernel for the above reason
+    let running_task = unsafe { *Traits::state().running_task_ptr() };
+    let task = running_task.filter(|_| exc_return & 0b1100 == 0b1100);
+
+    let record = capture_fault::<Traits>(exc_return, msp, task);
+
+    // Safety: Fault handlers don't preempt each other except when a fault
+    // handler itself faults, in which case we are lost anyway
+    unsafe { CRASH_RECORD.store(&record) };
+
+    Traits::handle_fault(&record);
+
+    let mut kind = record.kind().unwrap_or(FaultKind::HardFault);
+
+    // CPU Lock masks the other fault handlers, so a fault caused by an
+    // unprivileged task holding CPU Lock is escalated to HardFault. `CFSR`
+    // still tells the original cause. Kernel code runs in privileged Thread
+    // mode, so such a fault can't come from the middle of a kernel service.
+    let escalated = Traits::USE_MPU
+        && kind == FaultKind::HardFault
+        && task.is_some()
+        && record.hfsr & HFSR_FORCED != 0
+        && read_control() & CONTROL_NPRIV != 0;
+    if escalated {
+        kind = FaultKind::from_cfsr(record.cfsr).unwrap_or(FaultKind::HardFault);
+    }
+
+    let task = match task {
+        // A HardFault might be an escalated fault that happened in a fault
+        // handler
+        Some(task) if Traits::USE_MPU && kind != FaultKind::HardFault => task,
+        _ => panic!(
+            "unrecoverable {kind:?} at {pc:#010x} (CFSR = {cfsr:#010x}, \
+            HFSR = {hfsr:#010x}, address = {address:?})",
+            pc = record.pc(),
+            cfsr = record.cfsr,
+            hfsr = record.hfsr,
+            address = record.address(),
+        ),
+    };
+
+    let fault = TaskFault {
+        task: task_id(task),
+        kind,
+        status: record.cfsr,
+        address: record.address(),
+    };
+
+    // Discard the task's context and create a new one that exits the task.
+    // We don't reuse the current one because the fault might have been caused
+    // by a stack overflow.
+    let stack_top = task_stack_region(task).end as *mut u32;
+    let exc_frame = stack_top.wrapping_sub(8);
+    // Safety: The stack is owned by the task, which won't run until we return
+    unsafe {
+        // R0-R3, R12, LR
+        for i in 0..6 {
+            exc_frame.add(i).write(0);
+        }
+        // PC: See `initialize_task_state`
+        exc_frame
+            .add(6)
+            .write(<System<Traits> as traits::KernelBase>::raw_exit_task as usize as u32 & !1);
+        // xPSR
+        exc_frame.add(7).write(0x01000000);
+
+        pp_asm!(
+            "msr psp, {}",
+            in(reg) exc_frame,
+            options(nomem, preserves_flags, nostack),
+        );
+
+        // Cancel the pending lazy preservation of the discarded FP context
+        #[cfg(has_fpu)]
+        FPU_FPCCR.write_volatile(FPU_FPCCR.read_volatile() & !FPU_FPCCR_LSPACT);
+    }
+
+    if escalated {
+        // Release the CPU Lock held by the task so that `handle_task_fault`
+        // can call kernel services. The new context exits the task without
+        // it.
+        // Safety: CPU Lock active
+        unsafe { Traits::port_state().leave_cpu_lock::<Traits>() };
+    }
+
+    Traits::handle_task_fault(&fault);
+
+    // Return with a basic frame (`EXC_RETURN.FType == 1`)
+    exc_return | 0x10
+}
+
+/// Collect the information about the current fault and clear the fault status.
+fn capture_fault<Traits: PortInstance>(
+    exc_return: u32,
+    msp: *const u32,
+    task: Option<&TaskCb<Traits>>,
+) -> FaultRecord {
+    let exception: u32;
+    let psp: *const u32;
+    // Safety: Reading these registers has no side effects
+    unsafe {
+        pp_asm!(
+            "mrs {}, ipsr",
+            out(reg) exception,
+            options(nomem, preserves_flags, nostack),
+        );
+        pp_asm!(
+            "mrs {}, psp",
+            out(reg) psp,
+            options(nomem, preserves_flags, nostack),
+        );
+    }
+
+    // Safety: These registers are accessible in privileged mode
+    #[cfg(not(armv6m))]
+    let (cfsr, hfsr, mmfar, bfar) = unsafe {
+        let cfsr = SCB_CFSR.read_volatile();
+        let hfsr = SCB_HFSR.read_volatile();
+        let fault_regs = (
+            cfsr,
+            hfsr,
+            SCB_MMFAR.read_volatile(),
+            SCB_BFAR.read_volatile(),
+        );
+
+        // Clear the fault status (write-one-to-clear)
+        SCB_CFSR.write_volatile(cfsr);
+        SCB_HFSR.write_volatile(hfsr);
+
+        fault_regs
+    };
+    #[cfg(armv6m)]
+    let (cfsr, hfsr, mmfar, bfar) = (0, 0, 0, 0);
+
+    // Choose the stack the exception frame was pushed to (`EXC_RETURN.SPSEL`)
+    let frame_ptr = if exc_return & 0b100 != 0 { psp } else { msp };
+
+    let frame = if cfsr & CFSR_STKERR != 0 {
+        // The exception frame is missing or incomplete
+        [0; 8]
+    } else {
+        // Safety: The exception frame was pushed here
+        unsafe { frame_ptr.cast::<[u32; 8]>().read_volatile() }
+    };
+
+    // Calculate the stack pointer before the exception entry. The frame
+    // includes the FP context if `EXC_RETURN.FType == 0`, and padding if
+    // `xPSR[9]` is set.
+    let mut sp = frame_ptr as u32 + if exc_return & 0x10 == 0 { 0x68 } else { 0x20 };
+    if frame[7] & (1 << 9) != 0 {
+        sp += 4;
+    }
+
+    FaultRecord {
+        exception: exception & 0x1ff,
+        exc_return,
+        frame,
+        sp,
+        cfsr,
+        hfsr,
+        mmfar: if cfsr & CFSR_MMARVALID != 0 { mmfar } else { 0 },
+        bfar: if cfsr & CFSR_BFARVALID != 0 { bfar } else { 0 },
+        task: task.map_or(0, |task| task_id(task).get() as u32),
+    }
+}
+
+/// Calculate an `Id` from a task CB reference.
+fn task_id<Traits: PortInstance>(task: &TaskCb<Traits>) -> r3_kernel::Id {
+    // Safety: `task` refers to an element of `Traits::task_cb_pool()`
+    let offset = unsafe { <*const _>::offset_from(task, Traits::task_cb_pool().as_ptr()) };
+    r3_kernel::Id::new(offset as usize + 1).unwrap()
+}
@@ -43,7 +43,32 @@ pub mod systick_tickful {
     pub mod imp;
 }
 
-pub use self::{systick_tickful::cfg::*, threading::cfg::*};
+/// The tickless [`r3_kernel::PortTimer`] implementation based on SysTick.
+#[doc(hidden)]
+pub mod systick_tickless {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+/// Memory protection
+#[doc(hidden)]
+pub mod mpu {
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+/// Fault handling
+#[doc(hidden)]
+pub mod fault {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+pub use self::{
+    fault::cfg::*, systick_tickful::cfg::*, systick_tickless::cfg::*, threading::cfg::*,
+};
 
 /// Used by `use_port!`
 #[doc(hidden)]
@@ -0,0 +1,337 @@
+use core::ops::Range;
+use r3_core::kernel::{
+    task::{MemoryAccess, MemoryRegion},
+    traits,
+};
+use r3_kernel::TaskCb;
+
+use crate::threading::imp::PortInstance;
+
+/// MPU Type Register
+const MPU_TYPE: *const u32 = 0xe000_ed90usize as _;
+/// MPU Control Register
+const MPU_CTRL: *mut u32 = 0xe000_ed94usize as _;
+/// MPU Region Number Register
+const MPU_RNR: *mut u32 = 0xe000_ed98usize as _;
+/// MPU Region Base Address Register
+const MPU_RBAR: *mut u32 = 0xe000_ed9cusize as _;
+/// MPU Region Attribute and Size Register (PMSAv7) or MPU Region Limit Address
+/// Register (PMSAv8)
+const MPU_RASR_RLAR: *mut u32 = 0xe000_eda0usize as _;
+/// MPU Memory Attribute Indirection Register 0 (PMSAv8)
+#[cfg(armv8m)]
+const MPU_MAIR0: *mut u32 = 0xe000_edc0usize as _;
+
+const MPU_CTRL_ENABLE: u32 = 1;
+/// Use the default memory map as a background region for privileged accesses
+const MPU_CTRL_PRIVDEFENA: u32 = 1 << 2;
+
+/// System Handler Priority Register 1 (MemManage, BusFault, UsageFault, -)
+const SCB_SHPR1: *mut u8 = 0xe000_ed18usize as _;
+/// System Handler Control and State Register
+const SCB_SHCSR: *mut u32 = 0xe000_ed24usize as _;
+/// `MEMFAULTENA | BUSFAULTENA | USGFAULTENA`
+const SCB_SHCSR_FAULTENA: u32 = 0b111 << 16;
+
+/// The minimum size and alignment of an MPU region
+const MIN_REGION_SIZE: usize = 32;
+
+/// The minimum size of the stack region of an unprivileged task. The initial
+/// context alone takes 72 bytes.
+const MIN_STACK_REGION_SIZE: usize = 128;
+
+/// The register values for an MPU region.
+#[derive(Clone, Copy)]
+struct RegionRegs {
+    rbar: u32,
+    rasr_rlar: u32,
+}
+
+impl RegionRegs {
+    const DISABLED: Self = Self {
+        rbar: 0,
+        rasr_rlar: 0,
+    };
+}
+
+/// Get a flag indicating whether the specified task runs in unprivileged
+/// Thread mode.
+#[inline]
+pub fn is_unprivileged_task<Traits: PortInstance>(task: &TaskCb<Traits>) -> bool {
+    Traits::USE_MPU && !task.attr.memory_protection.is_privileged()
+}
+
+/// Get the portion of the task's stack used by the task. This is the whole
+/// stack for a privileged task. For an unprivileged task, this is the largest
+/// portion that can be described by a single MPU region.
+pub fn task_stack_region<Traits: PortInstance>(task: &TaskCb<Traits>) -> Range<usize> {
+    let stack = task.attr.stack.as_ptr();
+    let start = stack.as_mut_ptr() as usize;
+    let range = start..start + stack.len();
+    if is_unprivileged_task(task) {
+        inner_region(range)
+    } else {
+        range
+    }
+}
+
+/// Find the largest region in `range` that satisfies PMSAv8's alignment
+/// requirements.
+#[cfg(armv8m)]
+fn inner_region(range: Range<usize>) -> Range<usize> {
+    let start = range.start.saturating_add(MIN_REGION_SIZE - 1) & !(MIN_REGION_SIZE - 1);
+    let end = range.end & !(MIN_REGION_SIZE - 1);
+    start..end.max(start)
+}
+
+/// Find the largest region in `range` that satisfies PMSAv7's size and
+/// alignment requirements, i.e., the largest naturally aligned power-of-two
+/// block.
+#[cfg(not(armv8m))]
+fn inner_region(range: Range<usize>) -> Range<usize> {
+    let len = range.end - range.start;
+    if len < MIN_REGION_SIZE {
+        return range.start..range.start;
+    }
+
+    // Any range of length `2 * size` contains an aligned block of `size`
+    // bytes, so this loop finishes in at most two iterations
+    let mut size = 1 << (usize::BITS - 1 - len.leading_zeros());
+    while size >= MIN_REGION_SIZE {
+        if let Some(start) = range.start.checked_add(size - 1) {
+            let start = start & !(size - 1);
+            if range.end - start >= size {
+                return start..start + size;
+            }
+        }
+        size /= 2;
+    }
+
+    range.start..range.start
+}
+
+/// Get the address range of a memory region.
+fn region_range<System: traits::KernelStatic>(region: MemoryRegion<System>) -> Range<usize> {
+    let ptr = region.as_ptr();
+    let start = ptr.as_mut_ptr() as usize;
+    start..start + ptr.len()
+}
+
+/// Enumerate the MPU regions to use while the specified unprivileged task is
+/// running.
+fn task_regions<Traits: PortInstance>(
+    task: &TaskCb<Traits>,
+) -> impl Iterator<Item = (Range<usize>, MemoryAccess)> + '_ {
+    let memory_protection = &task.attr.memory_protection;
+    let stack = task_stack_region(task);
+
+    // A writable region covering the whole stack supersedes the stack region.
+    // This is required on PMSAv8, which doesn't allow regions to overlap.
+    let stack_covered = memory_protection.regions().any(|region| {
+        let range = region_range(region);
+        region.access().is_writable() && range.start <= stack.start && stack.end <= range.end
+    });
+
+    memory_protection
+        .regions()
+        .map(|region| (region_range(region), region.access()))
+        // On PMSAv7, the stack region must come last to take precedence
+        // over other regions
+        .chain((!stack_covered).then_some((stack, MemoryAccess::ReadWrite)))
+}
+
+/// Get the `TEX`, `S`, `C`, and `B` fields representing the default memory
+/// map's memory attributes at `address`.
+#[cfg(not(armv8m))]
+fn memory_attributes(address: usize) -> u32 {
+    const B: u32 = 1 << 16;
+    const C: u32 = 1 << 17;
+    const S: u32 = 1 << 18;
+    const TEX_1: u32 = 1 << 19;
+    match address >> 29 {
+        // Code, RAM (`0x80000000..0xa0000000`): Normal, Write-Through
+        0 | 4 => C,
+        // SRAM, RAM (`0x60000000..0x80000000`): Normal, Write-Back,
+        // Write-Allocate
+        1 | 3 => TEX_1 | C | B,
+        // Peripheral, Device: Shareable Device
+        2 | 5 | 6 => S | B,
+        // System: Strongly-ordered
+        _ => 0,
+    }
+}
+
+/// The memory attributes referenced by [`memory_attributes`]. `Attr3` is
+/// Device-nGnRnE (`0x00`).
+#[cfg(armv8m)]
+const MAIR0: u32 = 0x04 // [0]: Device-nGnRE
+    | 0xaa << 8 // [1]: Normal, Write-Through, Read-Allocate
+    | 0xff << 16; // [2]: Normal, Write-Back, Read/Write-Allocate
+
+/// Get the `AttrIndx` field representing the default memory map's memory
+/// attributes at `address`.
+#[cfg(armv8m)]
+fn memory_attributes(address: usize) -> u32 {
+    match address >> 29 {
+        // Code, RAM (`0x80000000..0xa0000000`): Normal, Write-Through
+        0 | 4 => 1,
+        // SRAM, RAM (`0x60000000..0x80000000`): Normal, Write-Back,
+        // Write-Allocate
+        1 | 3 => 2,
+        // Peripheral, Device
+        2 | 5 | 6 => 0,
+        // System
+        _ => 3,
+    }
+}
+
+/// Encode an MPU region for PMSAv7. Returns `None` if the region doesn't
+/// satisfy the architectural requirements.
+#[cfg(not(armv8m))]
+fn encode_region(range: Range<usize>, access: MemoryAccess) -> Option<RegionRegs> {
+    let size = range.end.checked_sub(range.start)?;
+    if !size.is_power_of_two() || size < MIN_REGION_SIZE || range.start & (size - 1) != 0 {
+        return None;
+    }
+
+    // Privileged code always has write access
+    let ap = if access.is_writable() { 0b011 } else { 0b010 };
+    let xn = !access.is_executable() as u32;
+
+    Some(RegionRegs {
+        rbar: range.start as u32,
+        rasr_rlar: xn << 28
+            | ap << 24
+            | memory_attributes(range.start)
+            | (size.trailing_zeros() - 1) << 1
+            | 1,
+    })
+}
+
+/// Encode an MPU region for PMSAv8. Returns `None` if the region doesn't
+/// satisfy the architectural requirements.
+#[cfg(armv8m)]
+fn encode_region(range: Range<usize>, access: MemoryAccess) -> Option<RegionRegs> {
+    if range.start >= range.end
+        || range.start % MIN_REGION_SIZE != 0
+        || range.end % MIN_REGION_SIZE != 0
+    {
+        return None;
+    }
+
+    let ap = if access.is_writable() { 0b01 } else { 0b11 };
+    let xn = !access.is_executable() as u32;
+
+    Some(RegionRegs {
+        rbar: range.start as u32 | ap << 1 | xn,
+        rasr_rlar: (range.end - MIN_REGION_SIZE) as u32 | memory_attributes(range.start) << 1 | 1,
+    })
+}
+
+/// Get the number of MPU regions.
+#[inline]
+fn num_regions() -> usize {
+    // Safety: `MPU_TYPE` is always accessible in privileged mode
+    (unsafe { MPU_TYPE.read_volatile() } as usize >> 8) & 0xff
+}
+
+/// Initialize the MPU and the fault handlers. Called by `port_boot` if
+/// `USE_MPU` is `true`.
+///
+/// # Safety
+///
+/// Privileged mode, CPU Lock active
+pub unsafe fn init<Traits: PortInstance>() {
+    let num_regions = num_regions();
+    assert!(
+        num_regions > 0,
+        "`USE_MPU` is set, but the processor doesn't have an MPU"
+    );
+
+    // Validate the memory regions of all unprivileged tasks now so that we
+    // don't have to do so in every context switch
+    for task in Traits::task_cb_pool() {
+        if !is_unprivileged_task(task) {
+            continue;
+        }
+
+        assert!(
+            task_stack_region(task).len() >= MIN_STACK_REGION_SIZE,
+            "the usable part of an unprivileged task's stack is too small"
+        );
+
+        let mut count = 0;
+        for (range, access) in task_regions(task) {
+            assert!(
+                encode_region(range, access).is_some(),
+                "a memory region of an unprivileged task doesn't satisfy the \
+                MPU's size and alignment requirements"
+            );
+            count += 1;
+        }
+        assert!(
+            count <= num_regions,
+            "an unprivileged task has more memory regions than the MPU supports"
+        );
+    }
+
+    // Safety: These registers are accessible in privileged mode
+    unsafe {
+        #[cfg(armv8m)]
+        MPU_MAIR0.write_volatile(MAIR0);
+
+        // Set the fault handlers' priorities to the lowest so that they can
+        // call kernel services
+        for i in 0..3 {
+            SCB_SHPR1.add(i).write_volatile(0xff);
+        }
+
+        // Enable MemManage, BusFault, and UsageFault. Otherwise, they would be
+        // escalated to HardFault.
+        SCB_SHCSR.write_volatile(SCB_SHCSR_FAULTENA | SCB_SHCSR.read_volatile());
+
+        // Enable the MPU with no task regions
+        switch_task::<Traits>();
+    }
+}
+
+/// Program the MPU for the current running task. Called by the PendSV handler
+/// after choosing the next task to run.
+///
+/// # Safety
+///
+/// Privileged mode, CPU Lock active
+pub unsafe fn switch_task<Traits: PortInstance>() {
+    // Safety: CPU Lock active
+    let running_task = unsafe { *Traits::state().running_task_ptr() };
+
+    // Safety: These registers are accessible in privileged mode
+    unsafe {
+        // Disable the MPU while updating the regions so that we don't observe
+        // an inconsistent state
+        MPU_CTRL.write_volatile(0);
+
+        let mut i = 0;
+        if let Some(task) = running_task.filter(|task| is_unprivileged_task(task)) {
+            for (range, access) in task_regions(task) {
+                // The regions were validated by `init`
+                let regs = encode_region(range, access).unwrap_or(RegionRegs::DISABLED);
+                MPU_RNR.write_volatile(i as u32);
+                MPU_RBAR.write_volatile(regs.rbar);
+                MPU_RASR_RLAR.write_volatile(regs.rasr_rlar);
+                i += 1;
+            }
+        }
+
+        // Disable the remaining regions
+        for i in i..num_regions() {
+            MPU_RNR.write_volatile(i as u32);
+            MPU_RASR_RLAR.write_volatile(0);
+        }
+
+        MPU_CTRL.write_volatile(MPU_CTRL_ENABLE | MPU_CTRL_PRIVDEFENA);
+    }
+
+    cortex_m::asm::dsb();
+    cortex_m::asm::isb();
+}
@@ -9,6 +9,10 @@
 ///  - The entry function (`#[cortex_m_rt::entry]`).
 ///  - The SysTick handler (`SysTick` global symbol).
 ///  - The PendSV handler (`PendSV` global symbol).
+///  - The SVCall handler (`SVCall` global symbol).
+///  - The HardFault, MemManage, BusFault, and UsageFault handlers
+///    (`HardFault`, `MemoryManagement`, `BusFault`, and `UsageFault` global
+///    symbols).
 ///  - Interrupt handlers and the vector table (`__INTERRUPTS` global symbol).
 ///
 #[macro_export]
@@ -41,6 +45,27 @@ macro_rules! use_rt {
                     );
                 }
 
+                // Likewise, register `HANDLE_SVC` and `HANDLE_FAULT`
+                unsafe {
+                    ::core::arch::asm!(
+                        "
+                            .global SVCall
+                            SVCall = {} + 1
+                            .global MemoryManagement
+                            MemoryManagement = {fault} + 1
+                            .global BusFault
+                            BusFault = {fault} + 1
+                            .global UsageFault
+                            UsageFault = {fault} + 1
+                            .global HardFault
+                            HardFault = {hard_fault} + 1
+                        ",
+                        sym SVC_TRAMPOLINE,
+                        fault = sym FAULT_TRAMPOLINE,
+                        hard_fault = sym HARD_FAULT_TRAMPOLINE,
+                    );
+                }
+
                 // `<$Traits as EntryPoint>::HANDLE_PEND_SV` contains the address of the PendSV
                 // handler. Ideally we would like to simply assign the symbol address like the
                 // following:
@@ -58,6 +83,18 @@ macro_rules! use_rt {
                 #[link_section = ".text"]
                 static PEND_SV_TRAMPOLINE: ExceptionTrampoline =
                     ExceptionTrampoline::new(<$Traits as EntryPoint>::HANDLE_PEND_SV);
+                #[link_section = ".text"]
+                static SVC_TRAMPOLINE: ExceptionTrampoline =
+                    ExceptionTrampoline::new(<$Traits as EntryPoint>::HANDLE_SVC);
+                #[link_section = ".text"]
+                static FAULT_TRAMPOLINE: ExceptionTrampoline =
+                    ExceptionTrampoline::new(<$Traits as EntryPoint>::HANDLE_FAULT);
+                // `cortex_m_rt`'s `HardFaultTrampoline` jumps to `HardFault` by a
+                // `b` instruction, whose range is limited on Armv6-M. `link.x`
+                // places `.HardFault.*` right after `HardFaultTrampoline`.
+                #[link_section = ".HardFault.r3_port_arm_m"]
+                static HARD_FAULT_TRAMPOLINE: ExceptionTrampoline =
+                    ExceptionTrampoline::new(<$Traits as EntryPoint>::HANDLE_FAULT);
 
                 unsafe { <$Traits as EntryPoint>::start() };
             }
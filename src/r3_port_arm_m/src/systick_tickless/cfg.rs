@@ -0,0 +1,178 @@
+/// The configuration for the implementation of `PortTimer` based on SysTick
+/// ([tickless]). This is used in addition to [`SysTickOptions`], from which
+/// only [`FREQUENCY`], [`FREQUENCY_DENOMINATOR`], and [`INTERRUPT_PRIORITY`]
+/// are used.
+///
+/// [tickless]: crate::use_systick_tickless
+/// [`SysTickOptions`]: crate::SysTickOptions
+/// [`FREQUENCY`]: crate::SysTickOptions::FREQUENCY
+/// [`FREQUENCY_DENOMINATOR`]: crate::SysTickOptions::FREQUENCY_DENOMINATOR
+/// [`INTERRUPT_PRIORITY`]: crate::SysTickOptions::INTERRUPT_PRIORITY
+pub trait SysTickTicklessOptions {
+    /// The maximum permissible timer interrupt latency, measured in cycles of
+    /// the time source (SysTick or [`FreeRunningTimer`]).
+    ///
+    /// Defaults to `None`, which means `min(FREQUENCY * 60 /
+    /// FREQUENCY_DENOMINATOR, 0x40000000)`, where `FREQUENCY` and
+    /// `FREQUENCY_DENOMINATOR` are those of the time source.
+    const HEADROOM: Option<u32> = None;
+
+    /// The number of SysTick cycles that elapse between reading SysTick's
+    /// current value and resetting it when SysTick is reprogrammed for a new
+    /// timeout. Only used when SysTick is the time source. Must be less than
+    /// `0x1000`.
+    ///
+    /// Every reprogramming loses this number of cycles from the time source
+    /// unless compensated by this option. The exact value depends on the
+    /// processor, memory wait states, and the ratio between the processor
+    /// clock and SysTick's input clock. Defaults to `0`.
+    const RELOAD_COMPENSATION: u32 = 0;
+}
+
+/// An application-supplied free-running timer that is used as the time source
+/// of [the tickless SysTick driver][tickless].
+///
+/// [tickless]: crate::use_systick_tickless
+pub trait FreeRunningTimer {
+    /// The numerator of the counting frequency of the timer.
+    const FREQUENCY: u64;
+
+    /// The denominator of the counting frequency of the timer.
+    /// Defaults to `1`.
+    const FREQUENCY_DENOMINATOR: u64 = 1;
+
+    /// Start the timer. Called by the driver with CPU Lock active before the
+    /// first call to [`Self::count`].
+    fn init();
+
+    /// Get the current counter value of the timer.
+    ///
+    /// The counter must be an up-counter covering the full 32-bit range, i.e.,
+    /// it must count up by one at the frequency specified by
+    /// [`Self::FREQUENCY`] and wrap around to `0` after reaching `u32::MAX`.
+    /// A down-counter can be adapted by returning the bitwise complement of
+    /// its current value.
+    fn count() -> u32;
+}
+
+/// Attach the tickless implementation of [`PortTimer`] that is based on
+/// SysTick to a given kernel trait type.
+///
+/// [`PortTimer`]: r3_kernel::PortTimer
+/// [a tickless scheme]: crate#tickless-systick
+///
+/// This macro has two forms:
+///
+///  - `use_systick_tickless!(unsafe impl PortTimer for $Traits)` uses SysTick
+///    both to track time and to generate timer interrupts.
+///
+///  - `use_systick_tickless!(unsafe impl PortTimer for $Traits, time_source =
+///    $Timer)` uses an application-supplied free-running timer `$Timer`
+///    implementing [`FreeRunningTimer`] to track time and SysTick to generate
+///    timer interrupts.
+///
+/// You should also do the following:
+///
+///  - Implement [`SysTickOptions`] and [`SysTickTicklessOptions`] manually.
+///  - Call `$Traits::configure_systick()` in your configuration function.
+///    See the following example.
+///
+/// [`SysTickOptions`]: crate::SysTickOptions
+///
+/// ```rust,ignore
+/// r3_port_arm_m::use_systick_tickless!(unsafe impl PortTimer for SystemTraits);
+///
+/// impl r3_port_arm_m::SysTickOptions for SystemTraits {
+///    // SysTick = AHB/8, AHB = HSI (internal 16-MHz RC oscillator)
+///     const FREQUENCY: u64 = 2_000_000;
+/// }
+///
+/// impl r3_port_arm_m::SysTickTicklessOptions for SystemTraits {}
+///
+/// const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+///     SystemTraits::configure_systick(b);
+///     /* ... */
+/// }
+/// ```
+///
+/// # Safety
+///
+///  - The target must really be a bare-metal Arm-M environment.
+///  - `$Timer` must implement [`FreeRunningTimer`] correctly.
+///
+#[macro_export]
+macro_rules! use_systick_tickless {
+    (unsafe impl PortTimer for $Traits:ty) => {
+        $crate::use_systick_tickless!(
+            @imp $Traits, $crate::systick_tickless::imp::SysTickSource
+        );
+    };
+
+    (unsafe impl PortTimer for $Traits:ty, time_source = $Timer:ty) => {
+        $crate::use_systick_tickless!(
+            @imp $Traits, $crate::systick_tickless::imp::FreeRunningSource<$Timer>
+        );
+    };
+
+    (@imp $Traits:ty, $TimeSource:ty) => {
+        const _: () = {
+            use $crate::r3_core::{
+                kernel::{traits, Cfg},
+                utils::Init,
+            };
+            use $crate::r3_kernel::{PortTimer, System, UTicks};
+            use $crate::r3_portkit::tickless;
+            use $crate::systick_tickless::imp;
+
+            impl PortTimer for $Traits {
+                const MAX_TICK_COUNT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as imp::SysTickTicklessInstance>::TICKLESS_CFG.max_timeout();
+
+                unsafe fn tick_count() -> UTicks {
+                    // Safety: We are just forwarding the call
+                    unsafe { imp::tick_count::<Self>() }
+                }
+
+                unsafe fn pend_tick() {
+                    // Safety: We are just forwarding the call
+                    unsafe { imp::pend_tick::<Self>() }
+                }
+
+                unsafe fn pend_tick_after(tick_count_delta: UTicks) {
+                    // Safety: We are just forwarding the call
+                    unsafe { imp::pend_tick_after::<Self>(tick_count_delta) }
+                }
+            }
+
+            static mut TIMER_STATE: <$Traits as imp::SysTickTicklessInstance>::TicklessState =
+                Init::INIT;
+
+            static mut DRIVER_STATE: imp::DriverState = Init::INIT;
+
+            // Safety: Only `use_systick_tickless!` is allowed to `impl` this
+            unsafe impl imp::SysTickTicklessInstance for $Traits {
+                type TimeSource = $TimeSource;
+
+                type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
+
+                fn tickless_state() -> *mut Self::TicklessState {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
+                }
+
+                fn driver_state() -> *mut imp::DriverState {
+                    unsafe { core::ptr::addr_of_mut!(DRIVER_STATE) }
+                }
+            }
+
+            impl $Traits {
+                pub const fn configure_systick<C>(b: &mut Cfg<C>)
+                where
+                    C: ~const traits::CfgInterruptLine<System = System<Self>>,
+                {
+                    imp::configure(b);
+                }
+            }
+        };
+    };
+}
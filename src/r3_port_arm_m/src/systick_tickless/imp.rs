@@ -0,0 +1,467 @@
+//! The tickless `PortTimer` implementation based on SysTick.
+use core::marker::PhantomData;
+use cortex_m::peripheral::{SCB, SYST};
+use r3_core::{
+    kernel::{raw, traits, Cfg, InterruptLine, StaticInterruptHandler},
+    utils::Init,
+};
+use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
+use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+
+use crate::{FreeRunningTimer, SysTickOptions, SysTickTicklessOptions, INTERRUPT_SYSTICK};
+
+/// The maximum value of SysTick's 24-bit reload value register.
+const LOAD_MAX: u32 = 0xff_ffff;
+
+/// The minimum number of SysTick cycles that must remain in the current
+/// SysTick period (in addition to [`SysTickTicklessOptions::RELOAD_COMPENSATION`])
+/// for [`SysTickCounter::pend_tick_after`] to proceed with reprogramming
+/// SysTick. Otherwise, it waits for the period to end so that the
+/// wrap-around is not lost.
+const RELOAD_GUARD: u32 = 0x100;
+
+/// The minimum timeout programmed into SysTick, measured in SysTick cycles.
+/// [`SysTickCounter::pend_tick_after`] must be able to observe SysTick
+/// reloading the timeout and restore the reload value before the timeout ends.
+/// Shorter timeouts are implemented by pending a SysTick interrupt
+/// immediately.
+const MIN_DELAY: u32 = 0x100;
+
+/// Implemented on a kernel trait type by [`use_systick_tickless!`].
+///
+/// # Safety
+///
+/// Only meant to be implemented by [`use_systick_tickless!`].
+pub unsafe trait SysTickTicklessInstance:
+    KernelTraits + SysTickOptions + SysTickTicklessOptions
+{
+    /// The source of the hardware tick count.
+    type TimeSource: TimeSource<Self>;
+
+    const TICKLESS_CFG: TicklessCfg = if Self::RELOAD_COMPENSATION >= 0x1000 {
+        panic!("`RELOAD_COMPENSATION` must be less than `0x1000`");
+    } else {
+        match TicklessCfg::new(TicklessOptions {
+            force_full_hw_period: true,
+            resettable: true,
+            ..TicklessOptions::new(
+                <Self::TimeSource as TimeSource<Self>>::FREQUENCY,
+                <Self::TimeSource as TimeSource<Self>>::FREQUENCY_DENOMINATOR,
+                if let Some(x) = Self::HEADROOM {
+                    x
+                } else {
+                    (<Self::TimeSource as TimeSource<Self>>::FREQUENCY as u128 * 60
+                        / <Self::TimeSource as TimeSource<Self>>::FREQUENCY_DENOMINATOR as u128)
+                        .min(0x40000000) as u32
+                },
+            )
+        }) {
+            Ok(x) => x,
+            Err(e) => e.panic(),
+        }
+    };
+
+    type TicklessState: TicklessStateTrait;
+
+    fn tickless_state() -> *mut Self::TicklessState;
+
+    fn driver_state() -> *mut DriverState;
+}
+
+/// The driver state other than the tickless state.
+#[derive(Debug, Clone, Copy)]
+pub struct DriverState {
+    /// Indicates whether the time source has been started by
+    /// [`ensure_started`].
+    started: bool,
+    /// Only used by [`SysTickSource`].
+    counter: SysTickCounter,
+}
+
+impl Init for DriverState {
+    const INIT: Self = Self {
+        started: false,
+        counter: Init::INIT,
+    };
+}
+
+/// The source of the hardware tick count, implemented by [`SysTickSource`] and
+/// [`FreeRunningSource`].
+///
+/// All methods must be called with CPU Lock active.
+pub trait TimeSource<Traits: ?Sized> {
+    /// The numerator of the frequency of the hardware tick count.
+    const FREQUENCY: u64;
+
+    /// The denominator of the frequency of the hardware tick count.
+    const FREQUENCY_DENOMINATOR: u64;
+
+    /// Start the time source and SysTick. CPU Lock active.
+    fn init();
+
+    /// Get the current hardware tick count.
+    unsafe fn hw_tick_count() -> u32;
+
+    /// Program SysTick to generate an interrupt `hw_ticks` hardware ticks
+    /// after `cur_hw_tick_count` or earlier.
+    unsafe fn pend_tick_after(cur_hw_tick_count: u32, hw_ticks: u32);
+
+    /// Called by the SysTick interrupt handler.
+    unsafe fn handle_tick();
+}
+
+/// Steal `SYST`.
+///
+/// # Safety
+///
+/// The caller must have the control of SysTick.
+#[inline]
+unsafe fn syst() -> SYST {
+    // Safety: Upheld by the caller
+    unsafe { cortex_m::Peripherals::steal() }.SYST
+}
+
+/// A [`TimeSource`] that uses SysTick alone. The hardware tick count is
+/// maintained by [`SysTickCounter`].
+pub struct SysTickSource;
+
+impl<Traits: SysTickTicklessInstance + ?Sized> TimeSource<Traits> for SysTickSource {
+    const FREQUENCY: u64 = <Traits as SysTickOptions>::FREQUENCY;
+    const FREQUENCY_DENOMINATOR: u64 = <Traits as SysTickOptions>::FREQUENCY_DENOMINATOR;
+
+    fn init() {
+        // Safety: We have the control of SysTick
+        let mut syst = unsafe { syst() };
+        syst.set_reload(LOAD_MAX);
+        syst.clear_current();
+        syst.enable_interrupt();
+        syst.enable_counter();
+    }
+
+    #[inline]
+    unsafe fn hw_tick_count() -> u32 {
+        // Safety: CPU Lock protects it from concurrent access
+        let counter = unsafe { &mut (*Traits::driver_state()).counter };
+        // Safety: We have the control of SysTick
+        counter.hw_tick_count(&mut unsafe { syst() })
+    }
+
+    #[inline]
+    unsafe fn pend_tick_after(cur_hw_tick_count: u32, hw_ticks: u32) {
+        // Safety: CPU Lock protects it from concurrent access
+        let counter = unsafe { &mut (*Traits::driver_state()).counter };
+        counter.pend_tick_after(
+            // Safety: We have the control of SysTick
+            &mut unsafe { syst() },
+            Traits::RELOAD_COMPENSATION,
+            cur_hw_tick_count,
+            hw_ticks,
+        );
+    }
+
+    #[inline]
+    unsafe fn handle_tick() {
+        // SysTick keeps running. The wrap-around is accounted for by the next
+        // call to `hw_tick_count`.
+    }
+}
+
+/// A [`TimeSource`] that uses an application-supplied [`FreeRunningTimer`].
+/// SysTick is used as a one-shot timer to generate timer interrupts.
+pub struct FreeRunningSource<T>(PhantomData<T>);
+
+impl<Traits: SysTickTicklessInstance + ?Sized, T: FreeRunningTimer> TimeSource<Traits>
+    for FreeRunningSource<T>
+{
+    const FREQUENCY: u64 = T::FREQUENCY;
+    const FREQUENCY_DENOMINATOR: u64 = T::FREQUENCY_DENOMINATOR;
+
+    fn init() {
+        T::init();
+
+        // SysTick is started by `pend_tick_after`
+        // Safety: We have the control of SysTick
+        let mut syst = unsafe { syst() };
+        syst.disable_counter();
+        syst.enable_interrupt();
+    }
+
+    #[inline]
+    unsafe fn hw_tick_count() -> u32 {
+        T::count()
+    }
+
+    unsafe fn pend_tick_after(cur_hw_tick_count: u32, hw_ticks: u32) {
+        // Subtract the time elapsed since `cur_hw_tick_count`
+        let hw_ticks = hw_ticks.saturating_sub(T::count().wrapping_sub(cur_hw_tick_count));
+
+        // Convert `hw_ticks` to SysTick cycles, rounding up. If the result
+        // exceeds the range of SysTick, the interrupt will be generated
+        // early, and the kernel will call `pend_tick_after` again.
+        let num = <Traits as SysTickOptions>::FREQUENCY as u128 * T::FREQUENCY_DENOMINATOR as u128;
+        let denom =
+            <Traits as SysTickOptions>::FREQUENCY_DENOMINATOR as u128 * T::FREQUENCY as u128;
+        let delay = ((hw_ticks as u128 * num + denom - 1) / denom).min(LOAD_MAX as u128 + 1) as u32;
+
+        // Safety: We have the control of SysTick
+        let mut syst = unsafe { syst() };
+        syst.disable_counter();
+
+        if delay <= 1 {
+            // SysTick can't count such a short period
+            SCB::set_pendst();
+            return;
+        }
+
+        syst.set_reload(delay - 1);
+        syst.clear_current();
+        syst.enable_counter();
+    }
+
+    #[inline]
+    unsafe fn handle_tick() {
+        // Stop SysTick. `timer_tick` will call `pend_tick_after` to start it
+        // again.
+        // Safety: We have the control of SysTick
+        unsafe { syst() }.disable_counter();
+    }
+}
+
+/// Extends SysTick's 24-bit down-counter to a 32-bit hardware tick count.
+///
+/// SysTick is normally left running with the maximum reload value
+/// (`0xffffff`), generating an interrupt in every period. Wrap-arounds are
+/// detected by `COUNTFLAG`, which is cleared by every read of SysTick's
+/// control and status register. Therefore, nothing else may read or
+/// read-modify-write the register while SysTick is used as the time source.
+///
+/// To generate an interrupt at a specific moment, `pend_tick_after`
+/// resets SysTick's current value with a shorter reload value. The SysTick
+/// cycles spent between reading and resetting the current value are accounted
+/// for by [`SysTickTicklessOptions::RELOAD_COMPENSATION`].
+#[derive(Debug, Clone, Copy)]
+pub struct SysTickCounter {
+    /// The hardware tick count when SysTick loaded the reload value of the
+    /// current period.
+    base: u32,
+    /// The reload value of the current period.
+    load: u32,
+}
+
+impl Init for SysTickCounter {
+    const INIT: Self = Self {
+        base: 0,
+        load: LOAD_MAX,
+    };
+}
+
+impl SysTickCounter {
+    /// Account for a wrap-around (if any) and get SysTick's current value.
+    #[inline]
+    fn sync(&mut self, syst: &mut SYST) -> u32 {
+        let value = SYST::get_current();
+        if syst.has_wrapped() {
+            // SysTick has reached zero and started a new period with
+            // `LOAD_MAX`
+            self.base = self.base.wrapping_add(self.load + 1);
+            self.load = LOAD_MAX;
+
+            // `value` might have been read before the wrap-around
+            SYST::get_current()
+        } else {
+            value
+        }
+    }
+
+    /// Convert SysTick's current value to a hardware tick count.
+    #[inline]
+    fn hw_tick_count_at(&self, value: u32) -> u32 {
+        if value == 0 {
+            // SysTick has reached zero and will load the reload value in the
+            // next cycle. `sync` has already advanced `base` because
+            // `COUNTFLAG` is set at the same time.
+            self.base.wrapping_sub(1)
+        } else {
+            self.base.wrapping_add(self.load - value)
+        }
+    }
+
+    /// Get the current hardware tick count. Must be called at least once in
+    /// every SysTick period, which is ensured by the SysTick interrupt
+    /// handler.
+    #[inline]
+    fn hw_tick_count(&mut self, syst: &mut SYST) -> u32 {
+        let value = self.sync(syst);
+        self.hw_tick_count_at(value)
+    }
+
+    /// Reprogram SysTick to generate an interrupt `hw_ticks` cycles after
+    /// `cur_hw_tick_count` or earlier.
+    fn pend_tick_after(
+        &mut self,
+        syst: &mut SYST,
+        compensation: u32,
+        cur_hw_tick_count: u32,
+        hw_ticks: u32,
+    ) {
+        // The hardware tick count at which SysTick's current value is reset
+        let reset_hw_tick_count = loop {
+            let value0 = self.sync(syst);
+            let value = SYST::get_current();
+
+            // Retry if SysTick has wrapped around since `sync` or is about to
+            // wrap around before we reset it
+            if value <= value0 && value > RELOAD_GUARD + compensation {
+                break self.hw_tick_count_at(value).wrapping_add(compensation);
+            }
+        };
+
+        let delay = hw_ticks.saturating_sub(reset_hw_tick_count.wrapping_sub(cur_hw_tick_count));
+        if delay < MIN_DELAY {
+            // Too short to be programmed reliably
+            SCB::set_pendst();
+            return;
+        }
+
+        // If `delay` exceeds the range of SysTick, the interrupt will be
+        // generated early, and the kernel will call `pend_tick_after` again
+        let delay = delay.min(LOAD_MAX + 1);
+
+        // Writing to the current value register clears it and `COUNTFLAG`.
+        // SysTick loads `delay - 1` in the next cycle and reaches zero `delay`
+        // cycles after the reset.
+        syst.set_reload(delay - 1);
+        syst.clear_current();
+        self.base = reset_hw_tick_count.wrapping_add(1);
+        self.load = delay - 1;
+
+        // Restore the reload value after SysTick has loaded `delay - 1` so
+        // that the subsequent periods are as long as possible
+        while SYST::get_current() == 0 {}
+        syst.set_reload(LOAD_MAX);
+    }
+}
+
+/// The configuration function.
+pub const fn configure<C, Traits: SysTickTicklessInstance>(b: &mut Cfg<C>)
+where
+    C: ~const traits::CfgInterruptLine<System = System<Traits>>,
+{
+    InterruptLine::define()
+        .line(INTERRUPT_SYSTICK)
+        .priority(Traits::INTERRUPT_PRIORITY)
+        .finish(b);
+    StaticInterruptHandler::define()
+        .line(INTERRUPT_SYSTICK)
+        .start(handle_tick::<Traits>)
+        .finish(b);
+}
+
+/// Start the time source and SysTick if they haven't been started yet.
+///
+/// The kernel calls `tick_count` and `pend_tick_after` during boot before
+/// calling startup hooks, so this is done on the first call to them.
+///
+/// # Safety
+///
+/// CPU Lock active
+#[inline]
+unsafe fn ensure_started<Traits: SysTickTicklessInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let started = unsafe { &mut (*Traits::driver_state()).started };
+    if !*started {
+        *started = true;
+        // Safety: CPU Lock active
+        unsafe { start::<Traits>() };
+    }
+}
+
+/// Start the time source and SysTick, and mark the current hardware tick count
+/// as the origin.
+///
+/// # Safety
+///
+/// CPU Lock active
+#[cold]
+unsafe fn start<Traits: SysTickTicklessInstance>() {
+    <Traits::TimeSource as TimeSource<Traits>>::init();
+
+    // Safety: CPU Lock active
+    let hw_tick_count = unsafe { <Traits::TimeSource as TimeSource<Traits>>::hw_tick_count() };
+
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+    tstate.reset(&Traits::TICKLESS_CFG, hw_tick_count);
+}
+
+/// Implements [`r3_kernel::PortTimer::tick_count`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_systick_tickless!`.
+pub unsafe fn tick_count<Traits: SysTickTicklessInstance>() -> UTicks {
+    // Safety: CPU Lock active
+    unsafe { ensure_started::<Traits>() };
+
+    // Safety: CPU Lock active
+    let hw_tick_count = unsafe { <Traits::TimeSource as TimeSource<Traits>>::hw_tick_count() };
+
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+    tstate.tick_count(&Traits::TICKLESS_CFG, hw_tick_count)
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_systick_tickless!`.
+pub unsafe fn pend_tick<Traits: SysTickTicklessInstance>() {
+    SCB::set_pendst();
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick_after`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_systick_tickless!`.
+pub unsafe fn pend_tick_after<Traits: SysTickTicklessInstance>(tick_count_delta: UTicks) {
+    // Safety: CPU Lock active
+    unsafe { ensure_started::<Traits>() };
+
+    // Safety: CPU Lock active
+    let cur_hw_tick_count = unsafe { <Traits::TimeSource as TimeSource<Traits>>::hw_tick_count() };
+
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+    let hw_ticks = tstate
+        .mark_reference_and_measure(&Traits::TICKLESS_CFG, cur_hw_tick_count, tick_count_delta)
+        .hw_ticks;
+
+    // Safety: CPU Lock active
+    unsafe {
+        <Traits::TimeSource as TimeSource<Traits>>::pend_tick_after(cur_hw_tick_count, hw_ticks)
+    };
+}
+
+/// Handle a SysTick interrupt.
+#[inline]
+fn handle_tick<Traits: SysTickTicklessInstance>() {
+    <System<Traits> as raw::KernelBase>::raw_acquire_cpu_lock().unwrap();
+
+    // Safety: CPU Lock active
+    unsafe { <Traits::TimeSource as TimeSource<Traits>>::handle_tick() };
+
+    // Safety: CPU Lock active
+    let cur_hw_tick_count = unsafe { <Traits::TimeSource as TimeSource<Traits>>::hw_tick_count() };
+
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+    tstate.mark_reference(&Traits::TICKLESS_CFG, cur_hw_tick_count);
+
+    // Safety: We own the CPU Lock, we are not in a boot context
+    unsafe { <System<Traits> as raw::KernelBase>::raw_release_cpu_lock().unwrap() };
+
+    // Safety: CPU Lock inactive, an interrupt context
+    unsafe { Traits::timer_tick() };
+}
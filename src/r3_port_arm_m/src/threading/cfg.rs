@@ -30,6 +30,54 @@ pub trait ThreadingOptions {
     /// Defaults to `true`.
     const USE_WFI: bool = true;
 
+    /// Enables memory protection using the MPU. Defaults to `false`.
+    ///
+    /// See [the crate-level documentation](crate#memory-protection) for
+    /// details. Must be `false` on Armv6-M and Armv8-M Baseline. Requires a
+    /// non-zero [`CPU_LOCK_PRIORITY_MASK`].
+    ///
+    /// [`CPU_LOCK_PRIORITY_MASK`]: Self::CPU_LOCK_PRIORITY_MASK
+    const USE_MPU: bool = false;
+
+    /// The amount of stack space (in bytes) that an unprivileged task must
+    /// have left when it calls a kernel service. Only used when [`USE_MPU`]
+    /// is `true`. Defaults to `1024`.
+    ///
+    /// Kernel services called by an unprivileged task run with privileges on
+    /// the task's stack, where the MPU doesn't stop them from overflowing it.
+    /// The call is rejected with `BadContext` unless the task's stack pointer
+    /// is at least this many bytes above the bottom of the task's stack
+    /// region. This must cover the worst-case stack usage of the kernel
+    /// services plus the exception frame and the context state saved if the
+    /// task is preempted in a kernel service.
+    ///
+    /// [`USE_MPU`]: Self::USE_MPU
+    const SYSCALL_STACK_SIZE: usize = 1024;
+
+    /// Called when a task is terminated because of a fault. Only used when
+    /// [`USE_MPU`] is `true`. Defaults to doing nothing.
+    ///
+    /// This method is called in an interrupt context, where kernel services
+    /// such as [`Task::activate`] can be used to handle the failure.
+    ///
+    /// [`USE_MPU`]: Self::USE_MPU
+    /// [`Task::activate`]: r3_core::kernel::task::TaskMethods::activate
+    fn handle_task_fault(_fault: &crate::TaskFault) {}
+
+    /// Called when a fault exception (HardFault, MemManage, BusFault, or
+    /// UsageFault) is taken, after [the crash record] is written. Defaults to
+    /// doing nothing.
+    ///
+    /// This method is called in a fault handler, where the kernel state might
+    /// be inconsistent. It must not call kernel services. After this method
+    /// returns, the port terminates the faulting task if the fault is
+    /// recoverable (see [`handle_task_fault`]) or panics otherwise. This method
+    /// may choose not to return, e.g., by resetting the system.
+    ///
+    /// [the crash record]: crate#fault-handling
+    /// [`handle_task_fault`]: Self::handle_task_fault
+    fn handle_fault(_record: &crate::FaultRecord) {}
+
     /// Get the top of the interrupt stack. Defaults to
     /// `*(SCB.VTOR as *const u32)`.
     ///
@@ -75,6 +123,23 @@ pub unsafe trait EntryPoint {
     ///    registers must contain the values from the background context.
     ///
     const HANDLE_PEND_SV: unsafe extern "C" fn();
+
+    /// The SVCall handler.
+    ///
+    /// # Safety
+    ///
+    ///  - This method must be registered as an SVCall handler.
+    ///
+    const HANDLE_SVC: unsafe extern "C" fn();
+
+    /// The handler for HardFault, MemManage, BusFault, and UsageFault.
+    ///
+    /// # Safety
+    ///
+    ///  - This method must be registered as a HardFault, MemManage, BusFault,
+    ///    and UsageFault handler.
+    ///
+    const HANDLE_FAULT: unsafe extern "C" fn();
 }
 
 /// Instantiate the port. Implements the port traits ([`PortThreading`], etc.)
@@ -199,6 +264,11 @@ macro_rules! use_port {
                 fn is_scheduler_active() -> bool {
                     PORT_STATE.is_scheduler_active::<Self>()
                 }
+
+                #[inline(always)]
+                fn syscall_gate() -> Option<$crate::r3_kernel::syscall::Gate> {
+                    PORT_STATE.syscall_gate::<Self>()
+                }
             }
 
             unsafe impl PortInterrupts for $Traits {
@@ -243,6 +313,12 @@ macro_rules! use_port {
 
                 const HANDLE_PEND_SV: unsafe extern "C" fn() =
                     State::handle_pend_sv::<$Traits>;
+
+                const HANDLE_SVC: unsafe extern "C" fn() =
+                    State::handle_svc::<$Traits>;
+
+                const HANDLE_FAULT: unsafe extern "C" fn() =
+                    $crate::fault::imp::handle_fault::<$Traits>;
             }
         }
 
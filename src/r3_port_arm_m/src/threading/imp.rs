@@ -8,11 +8,11 @@ use memoffset::offset_of;
 use r3_core::{
     kernel::{
         traits, ClearInterruptLineError, EnableInterruptLineError, InterruptNum, InterruptPriority,
-        PendInterruptLineError, QueryInterruptLineError, SetInterruptLinePriorityError,
+        PendInterruptLineError, QueryInterruptLineError, ResultCode, SetInterruptLinePriorityError,
     },
     utils::Init,
 };
-use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb};
+use r3_kernel::{syscall, KernelTraits, Port, PortToKernel, System, TaskCb};
 use r3_portkit::{pptext::pp_asm, sym::sym_static};
 
 use crate::{
@@ -84,17 +84,25 @@ impl State {
         // Claim the ownership of `Peripherals`
         let mut peripherals = unsafe { cortex_m::Peripherals::steal() };
 
-        // Set the priorities of SVCall and PendSV
+        // Set the priorities of SVCall and PendSV. Unprivileged tasks execute
+        // `svc` to call kernel services, which they may do while holding CPU
+        // Lock, so SVCall must not be masked by CPU Lock.
         // Safety: We don't make "priority-based critical sections"
         unsafe {
-            peripherals
-                .SCB
-                .set_priority(cortex_m::peripheral::scb::SystemHandler::SVCall, 0xff);
+            peripherals.SCB.set_priority(
+                cortex_m::peripheral::scb::SystemHandler::SVCall,
+                if Traits::USE_MPU { 0 } else { 0xff },
+            );
             peripherals
                 .SCB
                 .set_priority(cortex_m::peripheral::scb::SystemHandler::PendSV, 0xff);
         }
 
+        if Traits::USE_MPU {
+            // Safety: Privileged mode, CPU Lock active
+            unsafe { crate::mpu::imp::init::<Traits>() };
+        }
+
         // Safety: We are a port, so it's okay to call this
         unsafe {
             <Traits as PortToKernel>::boot();
@@ -249,6 +257,11 @@ impl State {
             // Safety: CPU Lock active
             unsafe { Traits::choose_running_task() };
 
+            if Traits::USE_MPU {
+                // Safety: Handler mode, CPU Lock active
+                unsafe { crate::mpu::imp::switch_task::<Traits>() };
+            }
+
             unsafe { State::leave_cpu_lock_inner::<Traits>() };
         }
 
@@ -446,6 +459,69 @@ impl State {
         unsafe { Self::leave_cpu_lock_inner::<Traits>() };
     }
 
+    /// Implements [`r3_kernel::PortThreading::syscall_gate`]. Unprivileged
+    /// tasks call kernel services through [`kernel_call`].
+    #[inline(always)]
+    pub fn syscall_gate<Traits: PortInstance>(&self) -> Option<syscall::Gate> {
+        (Traits::USE_MPU && is_unprivileged_thread()).then_some(kernel_call as syscall::Gate)
+    }
+
+    /// The SVCall handler. Unprivileged tasks execute `svc` in
+    /// [`kernel_call`] to call kernel services.
+    ///
+    /// # Safety
+    ///
+    ///  - This method must be registered as an SVCall handler.
+    ///
+    pub unsafe extern "C" fn handle_svc<Traits: PortInstance>() {
+        // `CONTROL.nPRIV` reflects the privilege level of Thread mode.
+        // Privileged code doesn't use `svc`.
+        if !Traits::USE_MPU || read_control() & CONTROL_NPRIV == 0 {
+            return;
+        }
+
+        // Unprivileged code only runs in tasks, which use PSP. Find the
+        // exception frame `[r0, r1, r2, r3, r12, lr, pc, xPSR]`.
+        let psp: *mut u32;
+        unsafe {
+            pp_asm!(
+                "mrs {}, psp",
+                out(reg) psp,
+                options(nomem, preserves_flags, nostack),
+            )
+        };
+
+        // `kernel_trampoline` runs on the task's stack with privileges, where
+        // the MPU doesn't catch a stack overflow. Reject the call unless the
+        // task's stack pointer leaves `SYSCALL_STACK_SIZE` bytes within its
+        // stack so that the task can't make the kernel write anywhere else.
+        // Safety: SVCall can't be preempted by the dispatcher, so we don't race
+        // with the kernel
+        let task = unsafe { *Traits::state().running_task_ptr() }.unwrap();
+        let stack = crate::mpu::imp::task_stack_region(task);
+        let psp_min = stack.start.saturating_add(Traits::SYSCALL_STACK_SIZE);
+        if !(psp_min..stack.end).contains(&(psp as usize)) {
+            // Safety: The exception frame was just pushed to PSP
+            unsafe { psp.write_volatile(ResultCode::BadContext as i8 as i32 as u32) };
+            return;
+        }
+
+        // Return to `kernel_trampoline` in privileged Thread mode. It finds
+        // the kernel service number and the arguments in `r12` and `r0`-`r3`
+        // and returns to the instruction following `svc` in `lr`. Clear the
+        // `IT` execution state in case `svc` was in an `IT` block.
+        // Safety: The exception frame was just pushed to PSP
+        unsafe {
+            let pc = psp.add(6).read_volatile();
+            psp.add(5).write_volatile(pc | 1);
+            psp.add(6)
+                .write_volatile(kernel_trampoline::<Traits> as usize as u32 & !1);
+            psp.add(7)
+                .write_volatile(psp.add(7).read_volatile() & !XPSR_IT_MASK);
+            write_control(read_control() & !CONTROL_NPRIV);
+        }
+    }
+
     #[inline(always)]
     unsafe fn leave_cpu_lock_inner<Traits: PortInstance>() {
         #[cfg(not(any(armv6m, armv8m_base)))]
@@ -467,9 +543,12 @@ impl State {
         task: &'static TaskCb<Traits>,
     ) {
         let stack: *mut [u8] = task.attr.stack.as_ptr();
+        // An unprivileged task can only use the part of the stack covered by
+        // an MPU region
+        let stack_top = crate::mpu::imp::task_stack_region(task).end;
         let mut sp = stack
             .as_mut_ptr()
-            .wrapping_add(stack.len())
+            .wrapping_add(stack_top - stack.as_mut_ptr() as usize)
             .cast::<MaybeUninit<u32>>();
         // TODO: Enforce minimum stack size
 
@@ -518,8 +597,12 @@ impl State {
         // TODO: This differs for Armv8-M
         // TODO: Plus, we shouldn't hard-code this here
         extra_ctx[0] = MaybeUninit::new(0xfffffffd);
-        // CONTROL: SPSEL = 1 (Use PSP)
-        extra_ctx[1] = MaybeUninit::new(0x00000002);
+        // CONTROL: SPSEL = 1 (Use PSP), nPRIV = 1 if the task is unprivileged
+        extra_ctx[1] = MaybeUninit::new(if crate::mpu::imp::is_unprivileged_task(task) {
+            CONTROL_SPSEL | CONTROL_NPRIV
+        } else {
+            CONTROL_SPSEL
+        });
         // TODO: Secure context (Armv8-M)
         // TODO: PSPLIM
 
@@ -558,15 +641,7 @@ impl State {
     #[inline]
     pub fn is_interrupt_context<Traits: PortInstance>(&self) -> bool {
         // `IPSR.Exception != 0`
-        unsafe {
-            let ipsr: u32;
-            pp_asm!(
-                "mrs {}, ipsr",
-                out(reg) ipsr,
-                options(nomem, preserves_flags, nostack),
-            );
-            (ipsr & ((1u32 << 9) - 1)) != 0
-        }
+        ipsr() != 0
     }
 
     #[inline]
@@ -696,4 +771,136 @@ pub const fn validate<Traits: PortInstance>() {
         "`CPU_LOCK_PRIORITY_MASK` must be zero because the target architecture \
          does not have a BASEPRI register"
     );
+    #[cfg(any(armv6m, armv8m_base))]
+    assert!(
+        !Traits::USE_MPU,
+        "`USE_MPU` is not supported on the target architecture"
+    );
+    // Unprivileged tasks call kernel services through SVCall, which `PRIMASK`
+    // would mask while they hold CPU Lock
+    assert!(
+        !Traits::USE_MPU || Traits::CPU_LOCK_PRIORITY_MASK > 0,
+        "`USE_MPU` requires a non-zero `CPU_LOCK_PRIORITY_MASK`"
+    );
+}
+
+/// `CONTROL.nPRIV`: Thread mode is unprivileged
+pub(crate) const CONTROL_NPRIV: u32 = 1 << 0;
+/// `CONTROL.SPSEL`: Thread mode uses PSP
+const CONTROL_SPSEL: u32 = 1 << 1;
+/// `xPSR.IT`: The execution state of an `IT` block
+const XPSR_IT_MASK: u32 = 0x0600_fc00;
+
+/// Get the exception number of the current context (`IPSR.Exception`).
+#[inline(always)]
+fn ipsr() -> u32 {
+    let ipsr: u32;
+    unsafe {
+        pp_asm!(
+            "mrs {}, ipsr",
+            out(reg) ipsr,
+            options(nomem, preserves_flags, nostack),
+        )
+    };
+    ipsr & ((1u32 << 9) - 1)
+}
+
+#[inline(always)]
+pub(crate) fn read_control() -> u32 {
+    let control: u32;
+    unsafe {
+        pp_asm!(
+            "mrs {}, control",
+            out(reg) control,
+            options(nomem, preserves_flags, nostack),
+        )
+    };
+    control
+}
+
+/// # Safety
+///
+/// The caller must be in privileged mode, and the new value must not break
+/// the current execution context.
+#[inline(always)]
+unsafe fn write_control(control: u32) {
+    unsafe {
+        pp_asm!(
+            "msr control, {}",
+            "isb",
+            in(reg) control,
+            options(nostack, preserves_flags),
+        )
+    };
+}
+
+/// Get a flag indicating whether the current context is unprivileged Thread
+/// mode, i.e., an unprivileged task.
+#[inline(always)]
+fn is_unprivileged_thread() -> bool {
+    read_control() & CONTROL_NPRIV != 0 && ipsr() == 0
+}
+
+/// The gate through which an unprivileged task calls a kernel service. Executes
+/// `svc` with the kernel service number in `r12` and `regs` in `r0`-`r3`.
+/// [`State::handle_svc`] resumes the task at [`kernel_trampoline`] in
+/// privileged Thread mode, which returns here in unprivileged Thread mode with
+/// the result in `r0`-`r3`.
+///
+/// # Safety
+///
+/// The caller must be an unprivileged task.
+#[inline(never)]
+unsafe fn kernel_call(num: usize, regs: &mut syscall::Regs) {
+    let [mut r0, mut r1, mut r2, mut r3] = *regs;
+    unsafe {
+        pp_asm!(
+            "svc #0",
+            inout("r0") r0,
+            inout("r1") r1,
+            inout("r2") r2,
+            inout("r3") r3,
+            in("r12") num,
+            clobber_abi("C"),
+        )
+    };
+    *regs = [r0, r1, r2, r3];
+}
+
+/// Calls [`syscall::dispatch`] in privileged Thread mode on behalf of
+/// [`kernel_call`], drops the privilege, and returns to `kernel_call`. Only
+/// entered by an exception return from [`State::handle_svc`].
+///
+/// `r12` = the kernel service number, `r0`-`r3` = the arguments, `lr` = the
+/// return address
+#[naked]
+unsafe extern "C" fn kernel_trampoline<Traits: PortInstance>() {
+    unsafe {
+        pp_asm!(
+            "
+            # Pass the arguments in memory and get the result from there
+            #
+            #   let mut regs = [r0, r1, r2, r3];
+            #   dispatch(r12, &mut regs);
+            #   [r0, r1, r2, r3] = regs;
+            #
+            push {{r4, lr}}
+            push {{r0-r3}}
+            mov r0, r12
+            mov r1, sp
+            bl {dispatch}
+
+            # Return to unprivileged Thread mode (`CONTROL.nPRIV = 1`)
+            mrs r0, control
+            movs r1, #1
+            orrs r0, r1
+            msr control, r0
+            isb
+            pop {{r0-r3}}
+            pop {{r4, pc}}
+            ",
+            dispatch = sym syscall::dispatch::<Traits>,
+            options(noreturn),
+        )
+    };
 }
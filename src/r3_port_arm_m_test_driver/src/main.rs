@@ -13,6 +13,11 @@ mod board_rp2040;
 mod logger_rtt;
 #[cfg(feature = "output-semihosting")]
 mod logger_semihosting;
+#[cfg(any(
+    feature = "timer-tickless-cmsdk-an385",
+    feature = "timer-tickless-cmsdk-an505"
+))]
+mod timer_cmsdk;
 
 #[allow(unused_macros)]
 macro_rules! instantiate_test {
@@ -63,7 +68,24 @@ macro_rules! instantiate_test {
         type System = r3_kernel::System<SystemTraits>;
         port::use_port!(unsafe struct SystemTraits);
         port::use_rt!(unsafe SystemTraits);
+        #[cfg(not(feature = "timer-tickless"))]
         port::use_systick_tickful!(unsafe impl PortTimer for SystemTraits);
+        #[cfg(all(
+            feature = "timer-tickless",
+            not(any(
+                feature = "timer-tickless-cmsdk-an385",
+                feature = "timer-tickless-cmsdk-an505"
+            ))
+        ))]
+        port::use_systick_tickless!(unsafe impl PortTimer for SystemTraits);
+        #[cfg(any(
+            feature = "timer-tickless-cmsdk-an385",
+            feature = "timer-tickless-cmsdk-an505"
+        ))]
+        port::use_systick_tickless!(
+            unsafe impl PortTimer for SystemTraits,
+            time_source = timer_cmsdk::CmsdkTimer
+        );
 
         impl port::ThreadingOptions for SystemTraits {
             // On some chips, RTT stops working when the processor is suspended
@@ -73,18 +95,41 @@ macro_rules! instantiate_test {
 
             #[cfg(feature = "cpu-lock-by-basepri")]
             const CPU_LOCK_PRIORITY_MASK: u8 = 0x20;
+
+            #[cfg(feature = "mpu")]
+            const USE_MPU: bool = true;
         }
 
         impl port::SysTickOptions for SystemTraits {
             #[cfg(feature = "board-rp_pico")]
             const FREQUENCY: u64 = board_rp2040::SYSTICK_FREQUENCY;
 
+            // SysTick is switched to the processor clock by
+            // `timer_cmsdk::CmsdkTimer::init`
+            #[cfg(any(
+                feature = "timer-tickless-cmsdk-an385",
+                feature = "timer-tickless-cmsdk-an505"
+            ))]
+            const FREQUENCY: u64 = timer_cmsdk::SYSCLK_FREQUENCY;
+
             // STM32F401
             // SysTick = AHB/8, AHB = HSI (internal 16-MHz RC oscillator)
-            #[cfg(not(feature = "board-rp_pico"))]
+            #[cfg(not(any(
+                feature = "board-rp_pico",
+                feature = "timer-tickless-cmsdk-an385",
+                feature = "timer-tickless-cmsdk-an505"
+            )))]
             const FREQUENCY: u64 = 2_000_000;
         }
 
+        #[cfg(feature = "timer-tickless")]
+        impl port::SysTickTicklessOptions for SystemTraits {
+            // Exercise the compensation code path. The value is arbitrary
+            // because QEMU's SysTick doesn't count processor cycles.
+            #[cfg(feature = "timer-tickless-compensated")]
+            const RELOAD_COMPENSATION: u32 = 8;
+        }
+
         struct Driver;
 
         #[cfg(feature = "kernel_benchmarks")]
@@ -133,6 +178,29 @@ macro_rules! instantiate_test {
 
             #[cfg(feature = "cpu-lock-by-basepri")]
             const UNMANAGED_INTERRUPT_PRIORITIES: &'static [InterruptPriority] = &[0x00];
+
+            // Code (ZBT SSRAM1) and data (ZBT SSRAM2 and 3)
+            #[cfg(feature = "mpu-an385")]
+            const UNPRIVILEGED_MEMORY_REGIONS:
+                &'static [(core::ops::Range<usize>, r3::kernel::task::MemoryAccess)] = &[
+                (0x0000_0000..0x0040_0000, r3::kernel::task::MemoryAccess::ReadExecute),
+                (0x2000_0000..0x2040_0000, r3::kernel::task::MemoryAccess::ReadWrite),
+            ];
+
+            // Code (SSRAM1, Non-Secure alias) and data (SSRAM2 and 3,
+            // Secure alias)
+            #[cfg(feature = "mpu-an505")]
+            const UNPRIVILEGED_MEMORY_REGIONS:
+                &'static [(core::ops::Range<usize>, r3::kernel::task::MemoryAccess)] = &[
+                (0x1000_0000..0x1040_0000, r3::kernel::task::MemoryAccess::ReadExecute),
+                (0x3800_0000..0x3840_0000, r3::kernel::task::MemoryAccess::ReadWrite),
+            ];
+
+            // APB peripherals are inaccessible to unprivileged tasks
+            #[cfg(feature = "mpu-an385")]
+            const UNPRIVILEGED_FAULT_ADDRESS: Option<usize> = Some(0x4000_0000);
+            #[cfg(feature = "mpu-an505")]
+            const UNPRIVILEGED_FAULT_ADDRESS: Option<usize> = Some(0x5000_0000);
         }
 
         static COTTAGE: test_case::App<System> =
@@ -0,0 +1,50 @@
+//! CMSDK APB Timer 0 of Arm MPS2+ AN385/AN505, used as the time source of
+//! `use_systick_tickless!`
+use cortex_m::peripheral::syst::SystClkSource;
+
+/// The base address of CMSDK APB Timer 0
+#[cfg(feature = "timer-tickless-cmsdk-an385")]
+const TIMER0_BASE: usize = 0x4000_0000;
+/// The base address of CMSDK APB Timer 0 (Secure alias)
+#[cfg(feature = "timer-tickless-cmsdk-an505")]
+const TIMER0_BASE: usize = 0x5000_0000;
+
+/// The frequency of the processor clock and the APB clock
+#[cfg(feature = "timer-tickless-cmsdk-an385")]
+pub const SYSCLK_FREQUENCY: u64 = 25_000_000;
+/// The frequency of the processor clock and the APB clock
+#[cfg(feature = "timer-tickless-cmsdk-an505")]
+pub const SYSCLK_FREQUENCY: u64 = 20_000_000;
+
+const CTRL: *mut u32 = TIMER0_BASE as _;
+const VALUE: *mut u32 = (TIMER0_BASE + 0x4) as _;
+const RELOAD: *mut u32 = (TIMER0_BASE + 0x8) as _;
+
+const CTRL_ENABLE: u32 = 1;
+
+pub struct CmsdkTimer;
+
+impl r3_port_arm_m::FreeRunningTimer for CmsdkTimer {
+    const FREQUENCY: u64 = SYSCLK_FREQUENCY;
+
+    fn init() {
+        unsafe {
+            // Count down from `u32::MAX` repeatedly
+            CTRL.write_volatile(0);
+            RELOAD.write_volatile(u32::MAX);
+            VALUE.write_volatile(u32::MAX);
+            CTRL.write_volatile(CTRL_ENABLE);
+
+            // The frequency of SysTick's reference clock differs between
+            // the boards, so clock SysTick by the processor clock instead
+            let mut peripherals = cortex_m::Peripherals::steal();
+            peripherals.SYST.set_clock_source(SystClkSource::Core);
+        }
+    }
+
+    #[inline]
+    fn count() -> u32 {
+        // Convert the down-counter to an up-counter
+        !unsafe { VALUE.read_volatile() }
+    }
+}
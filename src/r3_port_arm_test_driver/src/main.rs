@@ -55,10 +55,16 @@ macro_rules! instantiate_test {
         type System = r3_kernel::System<SystemTraits>;
         port::use_port!(unsafe struct SystemTraits);
         port::use_startup!(unsafe SystemTraits);
-        #[cfg(any(feature = "board-realview_pbx_a9", feature = "board-rza1"))]
+        #[cfg(any(
+            feature = "board-realview_pbx_a9",
+            feature = "board-qemu_virt_a15",
+            feature = "board-rza1",
+        ))]
         port::use_gic!(unsafe impl PortInterrupts for SystemTraits);
         #[cfg(feature = "board-realview_pbx_a9")]
         port::use_sp804!(unsafe impl PortTimer for SystemTraits);
+        #[cfg(feature = "board-qemu_virt_a15")]
+        port::use_generic_timer!(unsafe impl PortTimer for SystemTraits);
         #[cfg(feature = "board-rza1")]
         r3_support_rza1::use_os_timer!(unsafe impl PortTimer for SystemTraits);
 
@@ -77,6 +83,20 @@ macro_rules! instantiate_test {
                     .as_device_memory(),
             ];
 
+            #[cfg(feature = "board-realview_pbx_a9")]
+            const PL310_BASE: Option<usize> = Some(0x1f00_2000);
+
+            #[cfg(feature = "board-qemu_virt_a15")]
+            const MEMORY_MAP: &'static [port::MemoryMapSection] = &[
+                port::MemoryMapSection::new(0x4000_0000..0x4040_0000, 0x4000_0000)
+                    .with_executable(true)
+                    .with_writable(false),
+                port::MemoryMapSection::new(0x4040_0000..0x4080_0000, 0x4040_0000),
+                // GIC, UART, etc.
+                port::MemoryMapSection::new(0x0800_0000..0x0a00_0000, 0x0800_0000)
+                    .as_device_memory(),
+            ];
+
             #[cfg(feature = "board-rza1")]
             const MEMORY_MAP: &'static [port::MemoryMapSection] = &[
                 // On-chip RAM (10MB)
@@ -92,6 +112,9 @@ macro_rules! instantiate_test {
                 port::MemoryMapSection::new(0xfc00_0000..0xfc10_0000, 0xfc00_0000).as_device_memory(),
                 port::MemoryMapSection::new(0xfcf0_0000..0xfd00_0000, 0xfcf0_0000).as_device_memory(),
             ];
+
+            #[cfg(feature = "board-rza1")]
+            const PL310_BASE: Option<usize> = Some(0x3fff_f000);
         }
 
         #[cfg(feature = "board-realview_pbx_a9")]
@@ -100,6 +123,19 @@ macro_rules! instantiate_test {
             const GIC_CPU_BASE: usize = 0x1f000100;
         }
 
+        #[cfg(all(feature = "board-qemu_virt_a15", not(feature = "gic-v3")))]
+        impl port::GicOptions for SystemTraits {
+            const GIC_DISTRIBUTOR_BASE: usize = 0x08000000;
+            const GIC_CPU_BASE: usize = 0x08010000;
+        }
+
+        #[cfg(all(feature = "board-qemu_virt_a15", feature = "gic-v3"))]
+        impl port::GicOptions for SystemTraits {
+            const GIC_VERSION: port::GicVersion = port::GicVersion::V3;
+            const GIC_DISTRIBUTOR_BASE: usize = 0x08000000;
+            const GIC_REDISTRIBUTOR_BASE: usize = 0x080a0000;
+        }
+
         #[cfg(feature = "board-rza1")]
         impl port::GicOptions for SystemTraits {
             const GIC_DISTRIBUTOR_BASE: usize = 0xe8201000;
@@ -113,6 +149,14 @@ macro_rules! instantiate_test {
             const INTERRUPT_NUM: InterruptNum = 36;
         }
 
+        #[cfg(feature = "board-qemu_virt_a15")]
+        impl port::GenericTimerOptions for SystemTraits {
+            // The value of `CNTFRQ` on QEMU's `virt` machine (before 9.0)
+            const FREQUENCY: u64 = 62_500_000;
+            // Non-secure physical timer
+            const INTERRUPT_NUM: InterruptNum = 30;
+        }
+
         #[cfg(feature = "board-rza1")]
         impl r3_support_rza1::OsTimerOptions for SystemTraits {
             const FREQUENCY: u64 = 33_333_000;
@@ -168,6 +212,8 @@ macro_rules! instantiate_test {
         const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> test_case::App<System> {
             #[cfg(feature = "board-realview_pbx_a9")]
             SystemTraits::configure_sp804(b);
+            #[cfg(feature = "board-qemu_virt_a15")]
+            SystemTraits::configure_generic_timer(b);
             #[cfg(feature = "board-rza1")]
             SystemTraits::configure_os_timer(b);
 
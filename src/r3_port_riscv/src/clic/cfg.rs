@@ -0,0 +1,192 @@
+/// The public interface of the Core-Local Interrupt Controller driver.
+use r3_core::kernel::InterruptNum;
+use tock_registers::interfaces::ReadWriteable;
+
+use super::{clic_regs, imp};
+
+/// Implement [`InterruptController`] and [`Clic`] on the given kernel trait
+/// type using the Core-Local Interrupt Controller (CLIC) on the target.
+/// **Requires [`ClicOptions`] and [`ThreadingOptions`].**
+///
+/// [`InterruptController`]: crate::InterruptController
+/// [`ThreadingOptions`]: crate::ThreadingOptions
+///
+/// This macro puts the hart in CLIC mode ([`USE_CLIC_MODE`]). Unlike
+/// [`use_plic!`], the interrupts are taken and nested by the hardware, so no
+/// configuration function is needed. See the following example:
+///
+/// [`USE_CLIC_MODE`]: crate::InterruptController::USE_CLIC_MODE
+/// [`use_plic!`]: crate::use_plic
+///
+/// ```rust,ignore
+/// r3_port_riscv::use_clic!(unsafe impl InterruptController for SystemTraits);
+///
+/// impl r3_port_riscv::ClicOptions for SystemTraits {
+///     // GD32VF103
+///     const CLIC_BASE: usize = 0xd200_0000;
+///     const MAX_NUM: InterruptNum = 86;
+///     const CLICINTCTLBITS: u8 = 4;
+/// }
+/// ```
+///
+/// # Safety
+///
+///  - The target must really include a CLIC.
+///  - `ClicOptions` should be configured correctly and the memory-mapped
+///    registers should be accessible.
+///
+#[macro_export]
+macro_rules! use_clic {
+    (unsafe impl InterruptController for $Traits:ty) => {
+        const _: () = {
+            use $crate::{
+                clic::{clic_regs, imp},
+                core::ops::Range,
+                r3_core::kernel::{
+                    ClearInterruptLineError, EnableInterruptLineError, InterruptNum,
+                    InterruptPriority, PendInterruptLineError, QueryInterruptLineError,
+                    SetInterruptLinePriorityError,
+                },
+                Clic, ClicOptions, InterruptController,
+            };
+
+            unsafe impl Clic for $Traits {
+                fn clic_regs() -> &'static clic_regs::Clic {
+                    unsafe { &*(<$Traits as ClicOptions>::CLIC_BASE as *const clic_regs::Clic) }
+                }
+            }
+
+            impl InterruptController for $Traits {
+                #[inline]
+                unsafe fn init() {
+                    imp::init::<Self>()
+                }
+
+                const MANAGED_INTERRUPT_PRIORITY_RANGE: Range<InterruptPriority> =
+                    imp::MIN_PRIORITY..imp::max_priority::<Self>() + 1;
+
+                const USE_CLIC_MODE: bool = true;
+
+                #[inline]
+                unsafe fn acknowledge_clic_interrupt(id: usize) -> Option<InterruptNum> {
+                    imp::acknowledge_interrupt::<Self>(id)
+                }
+
+                #[inline]
+                unsafe fn set_interrupt_line_priority(
+                    line: InterruptNum,
+                    priority: InterruptPriority,
+                ) -> Result<(), SetInterruptLinePriorityError> {
+                    imp::set_interrupt_line_priority::<Self>(line, priority)
+                }
+
+                #[inline]
+                unsafe fn enable_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), EnableInterruptLineError> {
+                    imp::enable_interrupt_line::<Self>(line)
+                }
+
+                #[inline]
+                unsafe fn disable_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), EnableInterruptLineError> {
+                    imp::disable_interrupt_line::<Self>(line)
+                }
+
+                #[inline]
+                unsafe fn pend_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), PendInterruptLineError> {
+                    imp::pend_interrupt_line::<Self>(line)
+                }
+
+                #[inline]
+                unsafe fn clear_interrupt_line(
+                    line: InterruptNum,
+                ) -> Result<(), ClearInterruptLineError> {
+                    imp::clear_interrupt_line::<Self>(line)
+                }
+
+                #[inline]
+                unsafe fn is_interrupt_line_pending(
+                    line: InterruptNum,
+                ) -> Result<bool, QueryInterruptLineError> {
+                    imp::is_interrupt_line_pending::<Self>(line)
+                }
+            }
+        };
+    };
+}
+
+/// The options for [`use_clic!`].
+pub trait ClicOptions {
+    /// The base address of CLIC's memory-mapped registers.
+    const CLIC_BASE: usize;
+
+    /// The last interrupt ID supported by the CLIC implementation. Must be in
+    /// range `16..=4095`.
+    const MAX_NUM: InterruptNum;
+
+    /// The number of implemented bits in `clicintctl`. Must be in range
+    /// `1..=8`.
+    ///
+    /// The driver uses all implemented bits to encode interrupt levels, so
+    /// the interrupt priority values range from `1` (lowest) to
+    /// `2.pow(CLICINTCTLBITS) - 1` (highest).
+    const CLICINTCTLBITS: u8;
+}
+
+/// Specifies the type of signal transition that pends an interrupt.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum InterruptLineTriggerMode {
+    /// Asserts an interrupt while the interrupt signal is high.
+    PositiveLevel = 0b00,
+    /// Asserts an interrupt upon detection of a rising edge of an interrupt
+    /// signal.
+    PositiveEdge = 0b01,
+    /// Asserts an interrupt while the interrupt signal is low.
+    NegativeLevel = 0b10,
+    /// Asserts an interrupt upon detection of a falling edge of an interrupt
+    /// signal.
+    NegativeEdge = 0b11,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SetInterruptLineTriggerModeError {
+    /// The interrupt number is out of range.
+    BadParam,
+}
+
+/// Provides access to a system-global CLIC instance. Implemented by
+/// [`use_clic!`].
+///
+/// # Safety
+///
+/// This trait is not intended to be implemented in any other means.
+pub unsafe trait Clic: ClicOptions + crate::ThreadingOptions {
+    #[doc(hidden)]
+    /// Get [`clic_regs::Clic`] representing the memory-mapped interface for the
+    /// CLIC instance.
+    fn clic_regs() -> &'static clic_regs::Clic;
+
+    /// Set the trigger mode of the specified interrupt line.
+    ///
+    /// The CLIC implementation may hardwire the trigger mode of some or all
+    /// interrupts, in which case this method has no effect.
+    fn set_interrupt_line_trigger_mode(
+        num: InterruptNum,
+        mode: InterruptLineTriggerMode,
+    ) -> Result<(), SetInterruptLineTriggerModeError>
+    where
+        Self: Sized,
+    {
+        let id = imp::line_to_id::<Self>(num).ok_or(SetInterruptLineTriggerModeError::BadParam)?;
+
+        Self::clic_regs().clicint[id]
+            .clicintattr
+            .modify(clic_regs::CLICINTATTR::TRIG.val(mode as u8));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+#![allow(non_snake_case)]
+use tock_registers::{
+    register_bitfields,
+    registers::{ReadOnly, ReadWrite},
+};
+
+/// RISC-V Core-Local Interrupt Controller
+///
+/// This follows the memory map of the original CLIC draft specification,
+/// which is implemented by Nuclei ECLIC and its derivatives such as
+/// GD32VF103.
+///
+/// <https://github.com/riscv/riscv-fast-interrupt/blob/master/clic.adoc>
+#[repr(C)]
+pub struct Clic {
+    // +0x0000
+    /// CLIC configuration.
+    ///
+    /// The number of bits actually used to encode the interrupt level is the
+    /// minimum of `nlbits` and `CLICINTCTLBITS`.
+    pub cliccfg: ReadWrite<u8, CLICCFG::Register>,
+
+    _reserved1: [u8; 3],
+
+    // +0x0004
+    /// CLIC information.
+    pub clicinfo: ReadOnly<u32, CLICINFO::Register>,
+
+    _reserved2: [u8; 0x1000 - 0x8],
+
+    // +0x1000
+    /// The per-interrupt registers of each interrupt ID.
+    pub clicint: [ClicInt; 4096],
+}
+
+#[repr(C)]
+pub struct ClicInt {
+    /// The interrupt pending bit.
+    ///
+    /// This bit is read-only for a level-triggered interrupt. For an
+    /// edge-triggered interrupt, this bit can be written by software, and it
+    /// must be cleared by software when the interrupt is taken in
+    /// non-vectored mode.
+    pub clicintip: ReadWrite<u8, CLICINTIP::Register>,
+
+    /// The interrupt enable bit.
+    pub clicintie: ReadWrite<u8, CLICINTIE::Register>,
+
+    /// The interrupt attributes.
+    pub clicintattr: ReadWrite<u8, CLICINTATTR::Register>,
+
+    /// The interrupt level and priority.
+    ///
+    /// The interrupt level is held in the upper `nlbits` bits. Only the upper
+    /// `CLICINTCTLBITS` bits are implemented, and the remaining bits read as
+    /// 1.
+    pub clicintctl: ReadWrite<u8, ()>,
+}
+
+register_bitfields! {u8,
+    pub CLICCFG [
+        /// Selective hardware vectoring supported
+        NVBITS OFFSET(0) NUMBITS(1) [],
+        /// The number of bits used to encode the interrupt level
+        NLBITS OFFSET(1) NUMBITS(4) [],
+        /// The number of bits used to encode the privilege mode
+        NMBITS OFFSET(5) NUMBITS(2) []
+    ],
+
+    pub CLICINTIP [
+        IP OFFSET(0) NUMBITS(1) []
+    ],
+
+    pub CLICINTIE [
+        IE OFFSET(0) NUMBITS(1) []
+    ],
+
+    pub CLICINTATTR [
+        /// Selective hardware vectoring
+        SHV OFFSET(0) NUMBITS(1) [],
+        /// Trigger type and polarity
+        TRIG OFFSET(1) NUMBITS(2) [
+            PositiveLevel = 0b00,
+            PositiveEdge = 0b01,
+            NegativeLevel = 0b10,
+            NegativeEdge = 0b11
+        ],
+        /// The privilege mode of the interrupt
+        MODE OFFSET(6) NUMBITS(2) []
+    ]
+}
+
+register_bitfields! {u32,
+    pub CLICINFO [
+        /// The number of interrupt inputs
+        NUM_INTERRUPT OFFSET(0) NUMBITS(13) [],
+        /// The implementation version
+        VERSION OFFSET(13) NUMBITS(8) [],
+        /// The number of implemented bits in `clicintctl`
+        CLICINTCTLBITS OFFSET(21) NUMBITS(4) []
+    ]
+}
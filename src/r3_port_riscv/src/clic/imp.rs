@@ -0,0 +1,193 @@
+/// The implementation of the Core-Local Interrupt Controller driver.
+use r3_core::kernel::{
+    ClearInterruptLineError, EnableInterruptLineError, InterruptNum, InterruptPriority,
+    PendInterruptLineError, QueryInterruptLineError, SetInterruptLinePriorityError,
+};
+use r3_kernel::KernelTraits;
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+
+use super::clic_regs::{self, CLICCFG, CLICINTATTR};
+use crate::{
+    Clic, ClicOptions, INTERRUPT_EXTERNAL, INTERRUPT_PLATFORM_START, INTERRUPT_SOFTWARE,
+    INTERRUPT_TIMER,
+};
+
+/// The first interrupt ID that is not reserved for the interrupts defined by
+/// the RISC-V privileged architecture. Interrupt IDs `FIRST_PLATFORM_ID..`
+/// are mapped to interrupt numbers `INTERRUPT_PLATFORM_START +
+/// FIRST_PLATFORM_ID..`.
+const FIRST_PLATFORM_ID: usize = 16;
+
+/// The lowest interrupt priority. The priority value `0` is not used because
+/// it would be encoded as interrupt level `0` when `CLICINTCTLBITS == 8`,
+/// which never preempts tasks.
+pub const MIN_PRIORITY: InterruptPriority = 1;
+
+/// Get the highest interrupt priority.
+pub const fn max_priority<Traits: ClicOptions>() -> InterruptPriority {
+    assert!(
+        Traits::CLICINTCTLBITS >= 1 && Traits::CLICINTCTLBITS <= 8,
+        "`CLICINTCTLBITS` must be in range `1..=8`"
+    );
+    (1 << Traits::CLICINTCTLBITS) - 1
+}
+
+/// Encode the specified priority as a `clicintctl` value.
+#[inline]
+fn priority_to_clicintctl<Traits: Clic>(priority: InterruptPriority) -> u8 {
+    let unused_bits = 8 - Traits::CLICINTCTLBITS as u32;
+    // The unimplemented bits read as 1
+    ((priority as u32) << unused_bits | ((1 << unused_bits) - 1)) as u8
+}
+
+/// Get the interrupt ID for the specified interrupt number.
+///
+/// The local interrupt lines are mapped to the standard interrupt IDs for
+/// [`ThreadingOptions::PRIVILEGE_LEVEL`] (e.g., [`INTERRUPT_TIMER`] to
+/// `7` (machine timer interrupt) in M-mode).
+///
+/// [`ThreadingOptions::PRIVILEGE_LEVEL`]: crate::ThreadingOptions::PRIVILEGE_LEVEL
+#[inline]
+pub fn line_to_id<Traits: Clic>(line: InterruptNum) -> Option<usize> {
+    match line {
+        INTERRUPT_SOFTWARE | INTERRUPT_TIMER | INTERRUPT_EXTERNAL => {
+            Some(line * 4 + Traits::PRIVILEGE_LEVEL as usize)
+        }
+        _ => {
+            let id = line - INTERRUPT_PLATFORM_START;
+            (FIRST_PLATFORM_ID..=Traits::MAX_NUM)
+                .contains(&id)
+                .then_some(id)
+        }
+    }
+}
+
+/// Get the interrupt number for the specified interrupt ID. The inverse of
+/// [`line_to_id`].
+#[inline]
+fn id_to_line<Traits: Clic>(id: usize) -> Option<InterruptNum> {
+    if id >= FIRST_PLATFORM_ID {
+        Some(id + INTERRUPT_PLATFORM_START)
+    } else if id < INTERRUPT_PLATFORM_START * 4 && id % 4 == Traits::PRIVILEGE_LEVEL as usize {
+        Some(id / 4)
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn is_edge_triggered(int: &clic_regs::ClicInt) -> bool {
+    // `TRIG[0]`: 0 = level-triggered, 1 = edge-triggered
+    (int.clicintattr.read(CLICINTATTR::TRIG) & 1) != 0
+}
+
+/// Implements [`crate::InterruptController::init`].
+pub fn init<Traits: Clic + KernelTraits>() {
+    let clic_regs = Traits::clic_regs();
+
+    // Use all implemented bits of `clicintctl` to encode interrupt levels
+    clic_regs.cliccfg.modify(CLICCFG::NLBITS.val(8));
+
+    // Disable all interrupts, configure them as non-vectored, and assign the
+    // lowest priority to them
+    let clicintctl = priority_to_clicintctl::<Traits>(MIN_PRIORITY);
+    for int in &clic_regs.clicint[..=Traits::MAX_NUM] {
+        int.clicintie.set(0);
+        int.clicintattr.modify(CLICINTATTR::SHV::CLEAR);
+        int.clicintctl.set(clicintctl);
+    }
+
+    // The local interrupts with a registered handler are enabled by default,
+    // as they are in the basic interrupt handling model
+    for line in [INTERRUPT_SOFTWARE, INTERRUPT_TIMER, INTERRUPT_EXTERNAL] {
+        if Traits::INTERRUPT_HANDLERS.get(line).is_some() {
+            let id = line_to_id::<Traits>(line).unwrap();
+            clic_regs.clicint[id].clicintie.set(1);
+        }
+    }
+}
+
+/// Implements [`crate::InterruptController::acknowledge_clic_interrupt`].
+#[inline]
+pub fn acknowledge_interrupt<Traits: Clic>(id: usize) -> Option<InterruptNum> {
+    let int = Traits::clic_regs().clicint.get(id)?;
+
+    // In non-vectored mode, the hardware doesn't clear the pending bit of an
+    // edge-triggered interrupt
+    if is_edge_triggered(int) {
+        int.clicintip.set(0);
+    }
+
+    id_to_line::<Traits>(id)
+}
+
+/// Implements [`crate::InterruptController::set_interrupt_line_priority`].
+pub fn set_interrupt_line_priority<Traits: Clic>(
+    line: InterruptNum,
+    priority: InterruptPriority,
+) -> Result<(), SetInterruptLinePriorityError> {
+    let id = line_to_id::<Traits>(line).ok_or(SetInterruptLinePriorityError::BadParam)?;
+
+    if !(MIN_PRIORITY..=max_priority::<Traits>()).contains(&priority) {
+        return Err(SetInterruptLinePriorityError::BadParam);
+    }
+
+    Traits::clic_regs().clicint[id]
+        .clicintctl
+        .set(priority_to_clicintctl::<Traits>(priority));
+    Ok(())
+}
+
+/// Implements [`crate::InterruptController::enable_interrupt_line`].
+pub fn enable_interrupt_line<Traits: Clic>(
+    line: InterruptNum,
+) -> Result<(), EnableInterruptLineError> {
+    let id = line_to_id::<Traits>(line).ok_or(EnableInterruptLineError::BadParam)?;
+    Traits::clic_regs().clicint[id].clicintie.set(1);
+    Ok(())
+}
+
+/// Implements [`crate::InterruptController::disable_interrupt_line`].
+pub fn disable_interrupt_line<Traits: Clic>(
+    line: InterruptNum,
+) -> Result<(), EnableInterruptLineError> {
+    let id = line_to_id::<Traits>(line).ok_or(EnableInterruptLineError::BadParam)?;
+    Traits::clic_regs().clicint[id].clicintie.set(0);
+    Ok(())
+}
+
+/// Implements [`crate::InterruptController::pend_interrupt_line`].
+pub fn pend_interrupt_line<Traits: Clic>(line: InterruptNum) -> Result<(), PendInterruptLineError> {
+    let id = line_to_id::<Traits>(line).ok_or(PendInterruptLineError::BadParam)?;
+    let int = &Traits::clic_regs().clicint[id];
+
+    if !is_edge_triggered(int) {
+        return Err(PendInterruptLineError::BadObjectState);
+    }
+
+    int.clicintip.set(1);
+    Ok(())
+}
+
+/// Implements [`crate::InterruptController::clear_interrupt_line`].
+pub fn clear_interrupt_line<Traits: Clic>(
+    line: InterruptNum,
+) -> Result<(), ClearInterruptLineError> {
+    let id = line_to_id::<Traits>(line).ok_or(ClearInterruptLineError::BadParam)?;
+    let int = &Traits::clic_regs().clicint[id];
+
+    if !is_edge_triggered(int) {
+        return Err(ClearInterruptLineError::BadObjectState);
+    }
+
+    int.clicintip.set(0);
+    Ok(())
+}
+
+/// Implements [`crate::InterruptController::is_interrupt_line_pending`].
+pub fn is_interrupt_line_pending<Traits: Clic>(
+    line: InterruptNum,
+) -> Result<bool, QueryInterruptLineError> {
+    let id = line_to_id::<Traits>(line).ok_or(QueryInterruptLineError::BadParam)?;
+    Ok(Traits::clic_regs().clicint[id].clicintip.get() & 1 != 0)
+}
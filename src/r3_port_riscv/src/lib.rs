@@ -9,6 +9,7 @@
 #![feature(decl_macro)]
 #![feature(raw_ref_op)]
 #![feature(asm_const)]
+#![feature(fn_align)]
 #![feature(doc_cfg)]
 #![feature(linkage)]
 #![deny(unsafe_op_in_unsafe_fn)]
@@ -55,6 +56,14 @@ pub mod plic {
     pub mod plic_regs;
 }
 
+/// The Core-Local Interrupt Controller driver.
+#[doc(hidden)]
+pub mod clic {
+    pub mod cfg;
+    pub mod clic_regs;
+    pub mod imp;
+}
+
 /// The binding for [`::riscv_rt`].
 #[doc(hidden)]
 #[cfg(feature = "riscv-rt")]
@@ -80,6 +89,14 @@ pub mod mtime {
     pub mod imp;
 }
 
+/// Physical Memory Protection
+#[doc(hidden)]
+pub mod pmp {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
 /// The SBI-based timer driver.
 #[doc(hidden)]
 pub mod sbi_timer {
@@ -87,8 +104,10 @@ pub mod sbi_timer {
     pub mod imp;
 }
 
+pub use self::clic::cfg::*;
 pub use self::mtime::cfg::*;
 pub use self::plic::cfg::*;
+pub use self::pmp::cfg::*;
 #[cfg(feature = "riscv-rt")]
 pub use self::rt::cfg::*;
 pub use self::sbi_timer::cfg::*;
@@ -138,7 +157,7 @@ pub trait Timer {
 }
 
 /// An abstract interface to an interrupt controller. Implemented by
-/// [`use_plic!`].
+/// [`use_plic!`] and [`use_clic!`].
 ///
 /// # Safety
 ///
@@ -162,11 +181,41 @@ pub trait InterruptController {
     #[allow(clippy::reversed_empty_ranges)] // on purpose
     const MANAGED_INTERRUPT_PRIORITY_RANGE: Range<InterruptPriority> = 0..0;
 
+    /// Operate the hart in CLIC mode (`xtvec.MODE = 0b11`), in which
+    /// interrupts are taken through the [RISC-V Core-Local Interrupt
+    /// Controller] instead of `xie` and `xip`.
+    ///
+    /// When this is `true`, the port installs its CLIC trap vector to `xtvec`
+    /// on boot, calls [`Self::acknowledge_clic_interrupt`] to determine the
+    /// interrupt number of a taken interrupt, and delegates the
+    /// [`PortInterrupts`] method calls for local interrupt lines (<
+    /// [`INTERRUPT_PLATFORM_START`]) to the interrupt controller driver as
+    /// well.
+    ///
+    /// Defaults to `false` when unspecified.
+    ///
+    /// [RISC-V Core-Local Interrupt Controller]: https://github.com/riscv/riscv-fast-interrupt
+    /// [`PortInterrupts`]: r3_kernel::PortInterrupts
+    const USE_CLIC_MODE: bool = false;
+
+    /// Acknowledge the interrupt with the specified CLIC interrupt ID
+    /// (`xcause.Exccode`), which has just been taken, and get its interrupt
+    /// number.
+    ///
+    /// This method is only called when [`Self::USE_CLIC_MODE`] is `true`.
+    ///
+    /// # Safety
+    ///
+    /// See this trait's documentation.
+    unsafe fn acknowledge_clic_interrupt(_id: usize) -> Option<InterruptNum> {
+        None
+    }
+
     /// Handle the call to [`PortInterrupts::set_interrupt_line_priority`] for a
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::set_interrupt_line_priority`]: r3_kernel::PortInterrupts::set_interrupt_line_priority
     ///
@@ -184,7 +233,7 @@ pub trait InterruptController {
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::enable_interrupt_line`]: r3_kernel::PortInterrupts::enable_interrupt_line
     ///
@@ -199,7 +248,7 @@ pub trait InterruptController {
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::disable_interrupt_line`]: r3_kernel::PortInterrupts::disable_interrupt_line
     ///
@@ -214,7 +263,7 @@ pub trait InterruptController {
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::pend_interrupt_line`]: r3_kernel::PortInterrupts::pend_interrupt_line
     ///
@@ -229,7 +278,7 @@ pub trait InterruptController {
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::clear_interrupt_line`]: r3_kernel::PortInterrupts::clear_interrupt_line
     ///
@@ -244,7 +293,7 @@ pub trait InterruptController {
     /// platform interrupt line.
     ///
     /// The provided interrupt number must be greater than or equal to
-    /// [`INTERRUPT_PLATFORM_START`].
+    /// [`INTERRUPT_PLATFORM_START`] unless [`Self::USE_CLIC_MODE`] is `true`.
     ///
     /// [`PortInterrupts::is_interrupt_line_pending`]: r3_kernel::PortInterrupts::is_interrupt_line_pending
     ///
@@ -14,6 +14,9 @@ use r3_core::kernel::InterruptNum;
 ///  - Implement [`MtimeOptions`] on the kernel trait type `$Traits`.
 ///  - Call `$Traits::configure_timer()` in your configuration function.
 ///    See the following example.
+///  - Optionally, set [`MtimeOptions::RECONFIGURABLE`] to `true` and call
+///    `$Traits::set_timer_frequency()` whenever the timer clock frequency
+///    changes (e.g., because of dynamic voltage and frequency scaling).
 ///
 /// ```rust,ignore
 /// r3_port_riscv::use_mtime!(unsafe impl PortTimer for SystemTraits);
@@ -43,12 +46,13 @@ macro_rules! use_mtime {
                 utils::Init,
             };
             use $crate::r3_kernel::{PortTimer, System, UTicks};
-            use $crate::r3_portkit::tickless;
+            use $crate::r3_portkit::{tickless, tickless64};
             use $crate::{mtime, MtimeOptions, Timer};
 
             impl PortTimer for $Traits {
                 const MAX_TICK_COUNT: UTicks = u32::MAX;
-                const MAX_TIMEOUT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as mtime::imp::TimerInstance>::TICKLESS_CFG.max_timeout();
 
                 unsafe fn tick_count() -> UTicks {
                     // Safety: We are just forwarding the call
@@ -72,16 +76,20 @@ macro_rules! use_mtime {
                 }
             }
 
-            static mut TIMER_STATE: <$Traits as mtime::imp::TimerInstance>::TicklessState =
-                Init::INIT;
+            static mut TIMER_STATE: tickless64::Tickless64State = Init::INIT;
+
+            static mut TIMER_CFG: tickless64::Tickless64Cfg =
+                <$Traits as mtime::imp::TimerInstance>::TICKLESS_CFG;
 
             // Safety: Only `use_mtime!` is allowed to `impl` this
             unsafe impl mtime::imp::TimerInstance for $Traits {
-                type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
-
-                fn tickless_state() -> *mut Self::TicklessState {
+                fn tickless_state() -> *mut tickless64::Tickless64State {
                     unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
                 }
+
+                fn tickless_cfg() -> *mut tickless64::Tickless64Cfg {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_CFG) }
+                }
             }
 
             impl $Traits {
@@ -91,6 +99,24 @@ macro_rules! use_mtime {
                 {
                     mtime::imp::configure(b);
                 }
+
+                /// Inform the `mtime`-based timer driver that the timer
+                /// clock frequency is changing to `freq_num / freq_denom` Hz.
+                /// The new frequency takes effect when `change_clock`
+                /// returns. Requires `MtimeOptions::RECONFIGURABLE`.
+                ///
+                /// `change_clock` is called with CPU Lock active to make the
+                /// actual clock change atomic with respect to the kernel
+                /// timekeeping. The kernel tick count is preserved across the
+                /// change, losing less than one microsecond and the time
+                /// taken by `change_clock` at most.
+                pub fn set_timer_frequency(
+                    freq_num: u64,
+                    freq_denom: u64,
+                    change_clock: impl FnOnce(),
+                ) -> Result<(), tickless::SetTimerFrequencyError> {
+                    mtime::imp::set_timer_frequency::<Self>(freq_num, freq_denom, change_clock)
+                }
             }
         };
     };
@@ -107,8 +133,9 @@ pub trait MtimeOptions {
     /// When set to `true`, the driver clears the lower 32 bits of the `mtime`
     /// register on boot.
     ///
-    /// Disabling this might increase the runtime overhead of the driver.
-    /// Nevertheless, the need to disable this might arise for numerous reasons
+    /// The driver records the starting value of `mtime` regardless of this
+    /// option, so disabling this has no effect on the runtime overhead of the
+    /// driver. The need to disable this might arise for numerous reasons
     /// including:
     ///
     ///  - Updating the `mtime` register [is not supported by QEMU] at this time.
@@ -133,6 +160,11 @@ pub trait MtimeOptions {
     const HEADROOM: u32 =
         (Self::FREQUENCY as u128 * 60 / Self::FREQUENCY_DENOMINATOR as u128).min(0x40000000) as u32;
 
+    /// Allow changing the timer frequency at runtime by
+    /// `set_timer_frequency` generated by [`use_mtime!`]. This makes the
+    /// driver slightly slower. Defaults to `false`.
+    const RECONFIGURABLE: bool = false;
+
     /// The timer's interrupt number. Defaults to [`INTERRUPT_TIMER`].
     ///
     /// [`INTERRUPT_TIMER`]: crate::INTERRUPT_TIMER
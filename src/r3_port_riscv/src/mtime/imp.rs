@@ -1,7 +1,10 @@
 //! The implementation of the `mtime`-based timer driver.
-use r3_core::kernel::{traits, Cfg, StaticInterruptHandler};
+use r3_core::kernel::{raw, traits, Cfg, StaticInterruptHandler};
 use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
-use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use r3_portkit::{
+    tickless::SetTimerFrequencyError,
+    tickless64::{Tickless64Cfg, Tickless64Options, Tickless64State},
+};
 use tock_registers::{
     interfaces::{Readable, Writeable},
     registers::ReadWrite,
@@ -15,25 +18,31 @@ use crate::mtime::cfg::MtimeOptions;
 ///
 /// Only meant to be implemented by [`use_mtime!`].
 pub unsafe trait TimerInstance: KernelTraits + MtimeOptions {
-    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
-        hw_freq_num: <Self as MtimeOptions>::FREQUENCY,
-        hw_freq_denom: <Self as MtimeOptions>::FREQUENCY_DENOMINATOR,
-        hw_headroom_ticks: <Self as MtimeOptions>::HEADROOM,
-        // `mtime` is a 64-bit free-running counter and it is
-        // expensive to create a 32-bit timer with an arbitrary
-        // period out of it.
-        force_full_hw_period: true,
-        // If clearing `mtime` is not allowed, we must record the
-        // starting value of `mtime` by calling `reset`.
-        resettable: !<Self as MtimeOptions>::RESET_MTIME,
-    }) {
+    /// The initial tickless configuration.
+    const TICKLESS_CFG: Tickless64Cfg = match Tickless64Cfg::new(tickless_options::<Self>(
+        <Self as MtimeOptions>::FREQUENCY,
+        <Self as MtimeOptions>::FREQUENCY_DENOMINATOR,
+    )) {
         Ok(x) => x,
         Err(e) => e.panic(),
     };
 
-    type TicklessState: TicklessStateTrait;
+    fn tickless_state() -> *mut Tickless64State;
+
+    /// The current tickless configuration. Only used if
+    /// [`MtimeOptions::RECONFIGURABLE`] is `true`.
+    fn tickless_cfg() -> *mut Tickless64Cfg;
+}
 
-    fn tickless_state() -> *mut Self::TicklessState;
+const fn tickless_options<Traits: MtimeOptions + ?Sized>(
+    hw_freq_num: u64,
+    hw_freq_denom: u64,
+) -> Tickless64Options {
+    Tickless64Options {
+        hw_freq_num,
+        hw_freq_denom,
+        hw_headroom_ticks: Traits::HEADROOM,
+    }
 }
 
 trait TimerInstanceExt: TimerInstance {
@@ -73,6 +82,21 @@ trait TimerInstanceExt: TimerInstance {
             }
         }
     }
+
+    /// Get the current tickless configuration.
+    ///
+    /// # Safety
+    ///
+    /// Must not race with [`set_timer_frequency`].
+    #[inline]
+    unsafe fn current_tickless_cfg() -> Tickless64Cfg {
+        if Self::RECONFIGURABLE {
+            // Safety: CPU Lock protects it from concurrent access
+            unsafe { *Self::tickless_cfg() }
+        } else {
+            Self::TICKLESS_CFG
+        }
+    }
 }
 impl<T: TimerInstance> TimerInstanceExt for T {}
 
@@ -90,16 +114,15 @@ where
 /// Implements [`crate::Timer::init`]
 #[inline]
 pub fn init<Traits: TimerInstance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
-
     // Safety: No context switching during boot
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
     if Traits::RESET_MTIME {
         Traits::mtime_reg32()[0].set(0);
-    } else {
-        tstate.reset(tcfg, Traits::mtime_reg32()[0].get());
     }
+
+    // Record the starting value of `mtime`
+    tstate.reset(Traits::mtime());
 }
 
 /// Implements [`r3_kernel::PortTimer::tick_count`]
@@ -108,12 +131,13 @@ pub fn init<Traits: TimerInstance>() {
 ///
 /// Only meant to be referenced by `use_mtime!`.
 pub unsafe fn tick_count<Traits: TimerInstance>() -> UTicks {
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
 
-    let hw_tick_count = Traits::mtime_reg32()[0].get();
+    let hw_tick_count = Traits::mtime();
 
     // Safety: CPU Lock protects it from concurrent access
-    let tstate = unsafe { &mut *Traits::tickless_state() };
+    let tstate = unsafe { &*Traits::tickless_state() };
     tstate.tick_count(tcfg, hw_tick_count)
 }
 
@@ -133,16 +157,18 @@ pub unsafe fn pend_tick<Traits: TimerInstance>() {
 ///
 /// Only meant to be referenced by `use_mtime!`.
 pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
-    let tcfg = &Traits::TICKLESS_CFG;
     // Safety: CPU Lock protects it from concurrent access
-    let tstate = unsafe { &mut *Traits::tickless_state() };
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &*Traits::tickless_state() };
 
-    let cur_hw_tick_count = Traits::mtime();
-    let hw_ticks = tstate
-        .mark_reference_and_measure(tcfg, cur_hw_tick_count as u32, tick_count_delta)
-        .hw_ticks;
+    // `MAX_TIMEOUT` doesn't reflect the current frequency
+    let tick_count_delta = tick_count_delta.min(tcfg.max_timeout());
 
-    let next_hw_tick_count = cur_hw_tick_count + hw_ticks as u64;
+    let cur_hw_tick_count = Traits::mtime();
+    let next_hw_tick_count = tstate
+        .measure(tcfg, cur_hw_tick_count, tick_count_delta)
+        .end_hw_tick_count;
 
     // Since we have CPU Lock, spurious timer interrupts while non-atomically
     // updating `mtimecmp` are acceptable
@@ -150,15 +176,49 @@ pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
     Traits::mtimecmp_reg32()[1].set((next_hw_tick_count >> 32) as u32);
 }
 
-#[inline]
-fn handle_tick<Traits: TimerInstance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
+/// Implements `set_timer_frequency` generated by `use_mtime!`.
+pub fn set_timer_frequency<Traits: TimerInstance>(
+    freq_num: u64,
+    freq_denom: u64,
+    change_clock: impl FnOnce(),
+) -> Result<(), SetTimerFrequencyError> {
+    if !Traits::RECONFIGURABLE {
+        return Err(SetTimerFrequencyError::NotReconfigurable);
+    }
 
-    // Safety: CPU Lock protects it from concurrent access
+    let new_tcfg = Tickless64Cfg::new(tickless_options::<Traits>(freq_num, freq_denom))
+        .map_err(SetTimerFrequencyError::BadFrequency)?;
+
+    <System<Traits> as raw::KernelBase>::raw_acquire_cpu_lock()
+        .map_err(|_| SetTimerFrequencyError::BadContext)?;
+
+    // Safety: CPU Lock protects them from concurrent access
+    let tcfg = unsafe { &mut *Traits::tickless_cfg() };
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
-    let cur_hw_tick_count = Traits::mtime_reg32()[0].get();
-    tstate.mark_reference(tcfg, cur_hw_tick_count);
+    // Rebase the tickless state at the last `mtime` value measured in the old
+    // frequency. `mtime` keeps counting across the clock change, so the error
+    // is limited to the time taken by `change_clock`.
+    let cur_hw_tick_count = Traits::mtime();
+    change_clock();
+    tstate.reconfigure(tcfg, &new_tcfg, cur_hw_tick_count);
+    *tcfg = new_tcfg;
+
+    // `mtimecmp` was programmed in the old frequency. Let the kernel program
+    // it again.
+    // Safety: CPU Lock active
+    unsafe { pend_tick::<Traits>() };
+
+    // Safety: We own the CPU Lock
+    unsafe { <System<Traits> as raw::KernelBase>::raw_release_cpu_lock().unwrap() };
+
+    Ok(())
+}
+
+#[inline]
+fn handle_tick<Traits: TimerInstance>() {
+    // `Tickless64State` derives the tick count from the full `mtime` value, so
+    // there's no need to mark a reference point here
 
     // Safety: CPU Lock inactive, an interrupt context
     unsafe { Traits::timer_tick() };
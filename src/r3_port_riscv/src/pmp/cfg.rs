@@ -0,0 +1,19 @@
+/// Describes a fault that caused a task to be terminated. Passed to
+/// [`ThreadingOptions::handle_task_fault`].
+///
+/// [`ThreadingOptions::handle_task_fault`]: crate::ThreadingOptions::handle_task_fault
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct TaskFault {
+    /// The ID of the terminated task.
+    pub task: r3_kernel::Id,
+    /// The exception code (`mcause[11:0]`) of the exception that reported the
+    /// fault, e.g., `5` for a load access fault.
+    pub cause: usize,
+    /// The address of the instruction that caused the fault (`mepc`).
+    pub pc: usize,
+    /// The exception-specific information (`mtval`), e.g., the faulting
+    /// address of an access fault. This is zero if the hardware doesn't
+    /// provide one.
+    pub tval: usize,
+}
@@ -0,0 +1,730 @@
+//! Physical Memory Protection and unprivileged (U-mode) tasks
+use core::{arch::asm, ops::Range};
+use r3_core::kernel::{task::MemoryRegion, traits, ResultCode};
+use r3_kernel::{syscall, System, TaskCb};
+use r3_portkit::pptext::pp_asm;
+
+use crate::{
+    threading::imp::{PortInstance, FLSF_SIZE, F_SIZE, X_SIZE},
+    TaskFault,
+};
+
+/// The maximum number of PMP entries used by the port. Entries beyond this
+/// number are left disabled.
+const MAX_ENTRIES: usize = 16;
+
+/// `pmpcfg0`
+const CSR_PMPCFG0: usize = 0x3a0;
+/// `pmpaddr0`
+const CSR_PMPADDR0: usize = 0x3b0;
+
+/// `pmpNcfg.R`
+const PMPCFG_R: u8 = 1 << 0;
+/// `pmpNcfg.W`
+const PMPCFG_W: u8 = 1 << 1;
+/// `pmpNcfg.X`
+const PMPCFG_X: u8 = 1 << 2;
+/// `pmpNcfg.A = OFF`
+const PMPCFG_A_OFF: u8 = 0 << 3;
+/// `pmpNcfg.A = TOR`
+const PMPCFG_A_TOR: u8 = 1 << 3;
+/// `pmpNcfg.A = NAPOT`
+const PMPCFG_A_NAPOT: u8 = 3 << 3;
+
+/// `mstatus.MIE`
+const MSTATUS_MIE: usize = 1 << 3;
+/// `mstatus.MPIE`
+const MSTATUS_MPIE: usize = 1 << 7;
+/// `mstatus.MPP`
+const MSTATUS_MPP: usize = 0b11 << 11;
+
+/// `misa.S`
+const MISA_S: usize = 1 << (b'S' - b'A');
+
+/// Environment call from U-mode
+const EXCEPTION_ECALL_U: usize = 8;
+
+/// The maximum size of the context state (FLS and SLS) that the port stores
+/// on a task's stack.
+const MAX_CONTEXT_SIZE: usize = if cfg!(target_feature = "f") {
+    // FLS.X + FLS.F + SLS.X + SLS.F + SLS.HDR
+    17 * X_SIZE + FLSF_SIZE + 12 * X_SIZE + 12 * F_SIZE + X_SIZE
+} else if cfg!(target_feature = "v") {
+    // FLS.X + SLS.X + SLS.HDR
+    17 * X_SIZE + 12 * X_SIZE + X_SIZE
+} else {
+    // FLS.X + SLS.X
+    17 * X_SIZE + 12 * X_SIZE
+};
+
+/// The size of the inaccessible region at the bottom of an unprivileged
+/// task's stack. When an unprivileged task is preempted or overflows its stack,
+/// the port pushes the task's context state below the task's stack pointer,
+/// which might be in this region. The extra 128 bytes are for the stack frame
+/// of the function that caused the overflow.
+const STACK_GUARD_SIZE: usize = (MAX_CONTEXT_SIZE + 15) / 16 * 16 + 128;
+
+/// The minimum size of the usable part of an unprivileged task's stack.
+const MIN_STACK_REGION_SIZE: usize = 256;
+
+/// The lowest valid stack pointer of the running unprivileged task. Set by
+/// [`switch_task`]. The trap handler replaces an invalid stack pointer of a
+/// U-mode context with this value before pushing the context state.
+pub(crate) static mut USER_SP_MIN: usize = 0;
+
+/// The highest valid stack pointer of the running unprivileged task. Set by
+/// [`switch_task`].
+pub(crate) static mut USER_SP_MAX: usize = 0;
+
+/// The value of `mie` saved by [`kernel_trampoline`] when returning to an
+/// unprivileged task holding CPU Lock.
+static mut SAVED_MIE: usize = 0;
+
+/// The number of implemented PMP entries (up to [`MAX_ENTRIES`]). Set by
+/// [`init`].
+static mut NUM_ENTRIES: usize = 0;
+
+/// The PMP granularity in bytes. Set by [`init`].
+static mut GRANULE: usize = 4;
+
+/// Indicates whether `sfence.vma` is required to synchronize PMP updates.
+/// Set by [`init`].
+static mut NEEDS_SFENCE_VMA: bool = false;
+
+/// The task whose PMP entries are currently loaded, or zero.
+static mut LOADED_TASK: usize = 0;
+
+/// The error type of [`Entries`]'s methods.
+#[derive(Clone, Copy)]
+enum EncodeError {
+    /// A memory region doesn't satisfy the PMP's alignment requirements.
+    Misaligned,
+    /// There aren't enough PMP entries.
+    TooManyEntries,
+}
+
+/// The register values for a set of PMP entries.
+struct Entries {
+    cfg: [u8; MAX_ENTRIES],
+    addr: [usize; MAX_ENTRIES],
+    len: usize,
+    limit: usize,
+}
+
+impl Entries {
+    const fn new(limit: usize) -> Self {
+        Self {
+            cfg: [0; MAX_ENTRIES],
+            addr: [0; MAX_ENTRIES],
+            len: 0,
+            limit,
+        }
+    }
+
+    fn push(&mut self, cfg: u8, addr: usize) -> Result<(), EncodeError> {
+        if self.len >= self.limit {
+            return Err(EncodeError::TooManyEntries);
+        }
+        self.cfg[self.len] = cfg;
+        self.addr[self.len] = addr;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Get the end address of the region described by the last entry if it's
+    /// usable as the start address of a TOR region.
+    fn top(&self) -> Option<usize> {
+        let i = self.len.checked_sub(1)?;
+        (self.cfg[i] & PMPCFG_A_NAPOT != PMPCFG_A_NAPOT).then_some(self.addr[i] << 2)
+    }
+
+    /// Push one or two entries describing `range` using the TOR addressing
+    /// mode.
+    fn push_tor(&mut self, range: Range<usize>, perm: u8) -> Result<(), EncodeError> {
+        if self.top() != Some(range.start) {
+            self.push(PMPCFG_A_OFF, range.start >> 2)?;
+        }
+        self.push(PMPCFG_A_TOR | perm, range.end >> 2)
+    }
+
+    /// Push one or two entries describing `range`.
+    fn push_region(&mut self, range: Range<usize>, perm: u8) -> Result<(), EncodeError> {
+        let granule = granule();
+        let size = range.end.saturating_sub(range.start);
+        if size == 0 || range.start % granule != 0 || range.end % granule != 0 {
+            return Err(EncodeError::Misaligned);
+        }
+
+        if size.is_power_of_two() && size >= 8 && range.start % size == 0 {
+            self.push(
+                PMPCFG_A_NAPOT | perm,
+                (range.start >> 2) | ((size >> 3) - 1),
+            )
+        } else {
+            self.push_tor(range, perm)
+        }
+    }
+}
+
+/// Get a flag indicating whether the specified task runs in U-mode.
+#[inline]
+pub fn is_unprivileged_task<Traits: PortInstance>(task: &TaskCb<Traits>) -> bool {
+    Traits::USE_PMP && !task.attr.memory_protection.is_privileged()
+}
+
+/// Get a flag indicating whether the hart is currently in U-mode, i.e., the
+/// current context is an unprivileged task outside [`kernel_trampoline`].
+///
+/// `tp` is set on every return to U-mode and cleared on every trap entry from
+/// U-mode [ref:riscv_pmp_pc_bit0]. Unlike a variable in memory, it can be read
+/// in U-mode without granting access to the port's state.
+#[inline(always)]
+pub fn is_user_mode<Traits: PortInstance>() -> bool {
+    if !Traits::USE_PMP {
+        return false;
+    }
+    let tp: usize;
+    // Safety: Reading `tp` has no side effects
+    unsafe {
+        asm!(
+            "mv {}, tp",
+            out(reg) tp,
+            options(nomem, preserves_flags, nostack),
+        )
+    };
+    tp != 0
+}
+
+/// Implements [`r3_kernel::PortThreading::syscall_gate`]. Unprivileged tasks
+/// call kernel services through [`kernel_call`].
+#[inline(always)]
+pub fn syscall_gate<Traits: PortInstance>() -> Option<syscall::Gate> {
+    is_user_mode::<Traits>().then_some(kernel_call as syscall::Gate)
+}
+
+#[inline(always)]
+fn granule() -> usize {
+    // Safety: Only written by `init`
+    unsafe { GRANULE }
+}
+
+/// Get the guard region of an unprivileged task's stack.
+fn stack_guard_region<Traits: PortInstance>(task: &TaskCb<Traits>) -> Range<usize> {
+    let stack = task.attr.stack.as_ptr();
+    let start = stack.as_mut_ptr() as usize;
+    let mask = granule() - 1;
+    let guard_start = (start + mask) & !mask;
+    let guard_end = (start + STACK_GUARD_SIZE + mask) & !mask;
+    guard_start..guard_end
+}
+
+/// Get the portion of the task's stack used by the task. This is the whole
+/// stack for a privileged task. For an unprivileged task, this excludes the
+/// guard region and is aligned to the PMP granularity.
+pub fn task_stack_region<Traits: PortInstance>(task: &TaskCb<Traits>) -> Range<usize> {
+    let stack = task.attr.stack.as_ptr();
+    let start = stack.as_mut_ptr() as usize;
+    let end = start + stack.len();
+    if is_unprivileged_task(task) {
+        let start = stack_guard_region(task).end;
+        let end = end & !(granule() - 1);
+        start..end.max(start)
+    } else {
+        start..end
+    }
+}
+
+/// Get the address range of a memory region.
+fn region_range<System: traits::KernelStatic>(region: MemoryRegion<System>) -> Range<usize> {
+    let ptr = region.as_ptr();
+    let start = ptr.as_mut_ptr() as usize;
+    start..start + ptr.len()
+}
+
+/// Calculate the PMP entries to use while the specified unprivileged task is
+/// running.
+fn task_entries<Traits: PortInstance>(task: &TaskCb<Traits>) -> Result<Entries, EncodeError> {
+    // Safety: Only written by `init`
+    let mut entries = Entries::new(unsafe { NUM_ENTRIES });
+    let memory_protection = &task.attr.memory_protection;
+    let stack = task_stack_region(task);
+
+    // The stack guard comes first to take precedence over other entries
+    entries.push_tor(stack_guard_region(task), 0)?;
+
+    // A writable region covering the whole stack supersedes the stack region
+    let stack_covered = memory_protection.regions().any(|region| {
+        let range = region_range(region);
+        region.access().is_writable() && range.start <= stack.start && stack.end <= range.end
+    });
+    if !stack_covered {
+        entries.push_tor(stack, PMPCFG_R | PMPCFG_W)?;
+    }
+
+    for region in memory_protection.regions() {
+        let access = region.access();
+        let perm = PMPCFG_R
+            | [0, PMPCFG_W][access.is_writable() as usize]
+            | [0, PMPCFG_X][access.is_executable() as usize];
+        entries.push_region(region_range(region), perm)?;
+    }
+
+    Ok(entries)
+}
+
+/// Write `pmpaddrN`.
+#[inline]
+#[allow(clippy::identity_op)] // `N == 0`
+unsafe fn write_pmpaddr(n: usize, value: usize) {
+    seq_macro::seq!(N in 0..16 {
+        match n {
+            #(
+                N => unsafe {
+                    asm!(
+                        "csrw {csr}, {value}",
+                        csr = const CSR_PMPADDR0 + N,
+                        value = in(reg) value,
+                        options(nostack),
+                    )
+                },
+            )*
+            _ => unreachable!(),
+        }
+    });
+}
+
+/// Read `pmpaddrN`.
+#[inline]
+#[allow(clippy::identity_op)] // `N == 0`
+fn read_pmpaddr(n: usize) -> usize {
+    let value: usize;
+    seq_macro::seq!(N in 0..16 {
+        match n {
+            #(
+                N => unsafe {
+                    asm!(
+                        "csrr {value}, {csr}",
+                        csr = const CSR_PMPADDR0 + N,
+                        value = out(reg) value,
+                        options(nomem, nostack),
+                    )
+                },
+            )*
+            _ => unreachable!(),
+        }
+    });
+    value
+}
+
+/// Write the `pmpcfg` register containing `pmp{X_SIZE * n}cfg`.
+#[inline]
+#[allow(clippy::identity_op)] // `N == 0`
+unsafe fn write_pmpcfg(n: usize, value: usize) {
+    // On RV64, only even-numbered `pmpcfg` registers exist
+    seq_macro::seq!(N in 0..4 {
+        match n * (X_SIZE / 4) {
+            #(
+                N => unsafe {
+                    asm!(
+                        "csrw {csr}, {value}",
+                        csr = const CSR_PMPCFG0 + N,
+                        value = in(reg) value,
+                        options(nostack),
+                    )
+                },
+            )*
+            _ => unreachable!(),
+        }
+    });
+}
+
+/// Load the specified PMP entries and disable the remaining ones.
+///
+/// # Safety
+///
+/// M-mode
+unsafe fn load_entries(entries: &Entries) {
+    // Safety: Only written by `init`
+    let num_entries = unsafe { NUM_ENTRIES };
+
+    // Safety: M-mode. The entries don't affect M-mode.
+    unsafe {
+        for i in 0..entries.len {
+            write_pmpaddr(i, entries.addr[i]);
+        }
+
+        for (i, cfg) in entries.cfg[..(num_entries + X_SIZE - 1) / X_SIZE * X_SIZE]
+            .chunks_exact(X_SIZE)
+            .enumerate()
+        {
+            write_pmpcfg(i, usize::from_le_bytes(cfg.try_into().unwrap()));
+        }
+
+        if NEEDS_SFENCE_VMA {
+            asm!("sfence.vma", options(nostack));
+        }
+    }
+}
+
+/// Initialize the PMP. Called by `port_boot` if `USE_PMP` is `true`.
+///
+/// # Safety
+///
+/// M-mode, CPU Lock active
+pub unsafe fn init<Traits: PortInstance>() {
+    // Find the number of implemented entries. Unimplemented entries are
+    // hardwired to zero, and the lowest-numbered entries are implemented
+    // first.
+    // Safety: M-mode. All entries are disabled.
+    unsafe { load_entries(&Entries::new(0)) };
+    let num_entries = (0..MAX_ENTRIES)
+        .take_while(|&i| {
+            // Safety: M-mode. The entry is disabled.
+            unsafe { write_pmpaddr(i, usize::MAX) };
+            read_pmpaddr(i) != 0
+        })
+        .count();
+    assert!(
+        num_entries > 0,
+        "`USE_PMP` is set, but the processor doesn't implement PMP"
+    );
+
+    // When `pmp0cfg.A` is OFF, `pmpaddr0[G-1:0]` reads as zero, where the
+    // granularity is `2^(G + 2)` bytes
+    let granule = 4 << read_pmpaddr(0).trailing_zeros();
+
+    let misa: usize;
+    // Safety: Reading `misa` has no side effects
+    unsafe { asm!("csrr {}, misa", out(reg) misa, options(nomem, nostack)) };
+
+    // Safety: Boot context, so no one is reading them. The compiler doesn't
+    // use `tp`.
+    unsafe {
+        asm!("mv tp, zero", options(nomem, preserves_flags, nostack));
+        NUM_ENTRIES = num_entries;
+        GRANULE = granule;
+        // Page-based virtual memory requires `sfence.vma` after updating PMP
+        // entries
+        NEEDS_SFENCE_VMA = misa & MISA_S != 0;
+        LOADED_TASK = 0;
+    }
+
+    // Validate the memory regions of all unprivileged tasks now so that we
+    // don't have to do so in every context switch
+    for task in Traits::task_cb_pool() {
+        if !is_unprivileged_task(task) {
+            continue;
+        }
+
+        let stack = task.attr.stack.as_ptr();
+        assert!(
+            stack.len() >= STACK_GUARD_SIZE
+                && task_stack_region(task).len() >= MIN_STACK_REGION_SIZE,
+            "the usable part of an unprivileged task's stack is too small"
+        );
+
+        match task_entries(task) {
+            Ok(_) => {}
+            Err(EncodeError::Misaligned) => panic!(
+                "a memory region of an unprivileged task doesn't satisfy the \
+                PMP's alignment requirements"
+            ),
+            Err(EncodeError::TooManyEntries) => panic!(
+                "an unprivileged task has more memory regions than the PMP \
+                supports"
+            ),
+        }
+    }
+}
+
+/// Program the PMP for the current running task. Called by the dispatcher
+/// after choosing the next task to run.
+///
+/// # Safety
+///
+/// M-mode, CPU Lock active
+pub unsafe fn switch_task<Traits: PortInstance>() {
+    // Safety: CPU Lock active
+    let running_task = unsafe { *Traits::state().running_task_ptr() };
+
+    // The PMP entries don't affect M-mode, so privileged tasks can run with
+    // any entries loaded
+    let Some(task) = running_task.filter(|task| is_unprivileged_task(task)) else {
+        return;
+    };
+
+    // Safety: CPU Lock active
+    unsafe {
+        let stack = task_stack_region(task);
+        USER_SP_MIN = stack.start;
+        USER_SP_MAX = stack.end;
+
+        if LOADED_TASK == task as *const _ as usize {
+            return;
+        }
+        LOADED_TASK = task as *const _ as usize;
+
+        // The entries were validated by `init`
+        let entries = task_entries(task).unwrap_or(Entries::new(0));
+        load_entries(&entries);
+    }
+}
+
+/// The gate through which an unprivileged task calls a kernel service. Executes
+/// `ecall` with the kernel service number in `a7` and `regs` in `a0`-`a3`.
+/// [`handle_exception`] resumes the task at [`kernel_trampoline`] in M-mode,
+/// which returns here in U-mode with the result in `a0`-`a3`.
+///
+/// # Safety
+///
+/// The caller must be an unprivileged task in U-mode.
+#[inline(never)]
+unsafe fn kernel_call(num: usize, regs: &mut syscall::Regs) {
+    let [mut a0, mut a1, mut a2, mut a3] = *regs;
+    unsafe {
+        asm!(
+            "ecall",
+            inout("a0") a0,
+            inout("a1") a1,
+            inout("a2") a2,
+            inout("a3") a3,
+            in("a7") num,
+            clobber_abi("C"),
+        )
+    };
+    *regs = [a0, a1, a2, a3];
+}
+
+/// Call [`syscall::dispatch`] in M-mode and return to `ra` in U-mode. Entered
+/// by [`handle_exception`] on behalf of [`kernel_call`].
+///
+/// `mstatus.MIE` doesn't mask M-mode interrupts in U-mode. If CPU Lock is
+/// active when returning to U-mode, all interrupts are masked by clearing
+/// `mie` instead, and the original value is saved in [`SAVED_MIE`], which
+/// `handle_exception` restores on the next trap from U-mode.
+#[naked]
+unsafe extern "C" fn kernel_trampoline<Traits: PortInstance>() -> ! {
+    unsafe {
+        pp_asm!(
+            "
+            "   crate::threading::imp::asm_inc::define_load_store!()              "
+
+                # Call the kernel service
+                #
+                #   <a0-a3 = regs, a7 = num, ra = return address, M-mode>
+                #   let mut regs = [a0, a1, a2, a3];
+                #   dispatch(a7, &mut regs);
+                #   [a0, a1, a2, a3] = regs;
+                #
+                addi sp, sp, -{FRAME_SIZE}
+                STORE a0, ({X_SIZE} * 0)(sp)
+                STORE a1, ({X_SIZE} * 1)(sp)
+                STORE a2, ({X_SIZE} * 2)(sp)
+                STORE a3, ({X_SIZE} * 3)(sp)
+                STORE ra, ({X_SIZE} * 4)(sp)
+                mv a0, a7
+                mv a1, sp
+                call {dispatch}
+                LOAD a0, ({X_SIZE} * 0)(sp)
+                LOAD a1, ({X_SIZE} * 1)(sp)
+                LOAD a2, ({X_SIZE} * 2)(sp)
+                LOAD a3, ({X_SIZE} * 3)(sp)
+                LOAD ra, ({X_SIZE} * 4)(sp)
+                addi sp, sp, {FRAME_SIZE}
+
+                # Disable interrupts while we are updating `mepc` and
+                # `mstatus.MPP`, and keep the CPU Lock state in
+                # `mstatus.MPIE`
+                #
+                #   let cpu_lock_inactive = mstatus.MIE;
+                #   mstatus.MIE = 0;
+                #   if cpu_lock_inactive:
+                #       mstatus.MPIE = 1;
+                #   else:
+                #       SAVED_MIE = mie;
+                #       mie = 0;
+                #       mstatus.MPIE = 0;
+                #
+                csrrci t0, mstatus, {MSTATUS_MIE}
+                andi t0, t0, {MSTATUS_MIE}
+                li t1, {MSTATUS_MPIE}
+                bnez t0, 1f
+                csrrw t2, mie, zero
+                STORE t2, ({SAVED_MIE}), t3
+                csrc mstatus, t1
+                j 2f
+            1:
+                csrs mstatus, t1
+            2:
+
+                # Return to the caller of `kernel_call` in U-mode
+                #
+                #   mepc = ra;
+                #   mstatus.MPP = U;
+                #   tp = 1;
+                #
+                csrw mepc, ra
+                li t1, {MSTATUS_MPP}
+                csrc mstatus, t1
+                li tp, 1
+                mret
+            ",
+            dispatch = sym syscall::dispatch::<Traits>,
+            FRAME_SIZE = const (X_SIZE * 5 + 15) / 16 * 16,
+            X_SIZE = const X_SIZE,
+            MSTATUS_MIE = const MSTATUS_MIE,
+            MSTATUS_MPP = const MSTATUS_MPP,
+            MSTATUS_MPIE = const MSTATUS_MPIE,
+            SAVED_MIE = sym SAVED_MIE,
+            options(noreturn),
+        )
+    };
+}
+
+/// Handle an exception taken from U-mode. Called by the trap handler before
+/// attempting instruction emulation. Returns `false` if the exception was
+/// taken from M-mode, in which case it's left to the caller.
+///
+/// An `ecall` from U-mode resumes the calling task at [`kernel_trampoline`] in
+/// M-mode, which calls the kernel service specified by `a7`. Only the
+/// trampoline's address is used as a code address, so the task can only
+/// choose from the kernel services defined by [`syscall::dispatch`]. Any other
+/// exception taken from U-mode terminates the running task, releasing CPU Lock
+/// if the task held it.
+///
+/// # Safety
+///
+///  - `fl_state` must point to the background context's FLS.X.
+///  - `frame` must point to the trap handler's frame
+///    `[background_sp, xstatus_part]`.
+///
+pub(crate) unsafe extern "C" fn handle_exception<Traits: PortInstance>(
+    fl_state: *mut usize,
+    cause: usize,
+    frame: *mut usize,
+) -> bool {
+    // Safety: Upheld by the caller
+    let pc = unsafe { fl_state.add(16).read() };
+
+    // `pc[0]` indicates the background context was in U-mode
+    // [ref:riscv_pmp_pc_bit0]
+    if pc & 1 == 0 {
+        return false;
+    }
+    let pc = pc & !1;
+
+    // `mstatus.MPIE` is `mstatus.MIE` of the background context. If it's
+    // clear, the task holds CPU Lock, and `kernel_trampoline` masked all
+    // interrupts by clearing `mie`. Unmask them.
+    let mstatus: usize;
+    // Safety: Reading `mstatus` has no side effects
+    unsafe { asm!("csrr {}, mstatus", out(reg) mstatus, options(nomem, nostack)) };
+    let cpu_lock_active = mstatus & MSTATUS_MPIE == 0;
+    if cpu_lock_active {
+        // Safety: `SAVED_MIE` was written by `kernel_trampoline`, and
+        // interrupts are disabled
+        unsafe { asm!("csrw mie, {}", in(reg) SAVED_MIE, options(nomem, nostack)) };
+    }
+
+    // U-mode code only runs in unprivileged tasks. An exception handler can't
+    // be preempted by the dispatcher, so `running_task` still refers to the
+    // faulting task.
+    // Safety: We don't race with the kernel for the above reason
+    let task = unsafe { *Traits::state().running_task_ptr() }.unwrap();
+
+    if cause == EXCEPTION_ECALL_U {
+        // `kernel_trampoline` runs on the task's stack in M-mode, where the
+        // PMP entries don't apply, so a stack overflow would go unnoticed.
+        // Reject the call unless the task's stack pointer leaves
+        // `SYSCALL_STACK_SIZE` bytes within its stack so that the task can't
+        // make the kernel write anywhere else.
+        // Safety: Upheld by the caller
+        let background_sp = unsafe { frame.read() };
+        let stack = task_stack_region(task);
+        let sp_min = stack.start.saturating_add(Traits::SYSCALL_STACK_SIZE);
+        if !(sp_min..stack.end).contains(&background_sp) {
+            // Return to the task in U-mode (`pc[0] == 1`) with an error
+            // Safety: Upheld by the caller
+            unsafe {
+                fl_state
+                    .add(4)
+                    .write(ResultCode::BadContext as i8 as isize as usize);
+                fl_state.add(16).write((pc + 4) | 1);
+                if cpu_lock_active {
+                    asm!("csrw mie, zero", options(nomem, nostack));
+                }
+            }
+            return true;
+        }
+
+        // Resume the task at `kernel_trampoline` in M-mode (`pc[0] == 0`),
+        // which returns to the instruction following `ecall` in `ra`. `a0`-`a3`
+        // and `a7` are passed to it as is. The CPU Lock state is restored from
+        // `mstatus.MPIE` on return.
+        // Safety: Upheld by the caller
+        unsafe {
+            fl_state.write(pc + 4);
+            fl_state.add(16).write(kernel_trampoline::<Traits> as usize);
+        }
+        return true;
+    }
+
+    let tval: usize;
+    // Safety: Reading `mtval` has no side effects
+    unsafe { asm!("csrr {}, mtval", out(reg) tval, options(nomem, nostack)) };
+
+    let fault = TaskFault {
+        task: task_id(task),
+        cause,
+        pc,
+        tval,
+    };
+
+    // Discard the task's context and create a new one that exits the task.
+    // We don't reuse the current one because the fault might have been caused
+    // by a stack overflow.
+    let stack_top = task_stack_region(task).end as *mut usize;
+    let fl_state = stack_top.wrapping_sub(17);
+    // Safety: The stack is owned by the task, which won't run until we return
+    unsafe {
+        // ra, t0-t2, a0-a7, t3-t6
+        for i in 0..16 {
+            fl_state.add(i).write(0);
+        }
+        // pc: `raw_exit_task` in M-mode (`pc[0] == 0`)
+        fl_state
+            .add(16)
+            .write(<System<Traits> as traits::KernelBase>::raw_exit_task as usize);
+
+        frame.write(fl_state as usize);
+        // xstatus_part: FS[1] = 0, VS = Off
+        if cfg!(any(target_feature = "f", target_feature = "v")) {
+            frame.add(1).write(0);
+        }
+    }
+
+    // Enable interrupts so that `handle_task_fault` can call kernel services
+    // like an interrupt handler
+    // Safety: The background context was saved
+    unsafe { asm!("csrsi mstatus, {}", const MSTATUS_MIE, options(nostack)) };
+
+    Traits::handle_task_fault(&fault);
+
+    // Safety: We are about to return from the trap handler. The next context
+    // to run doesn't hold CPU Lock.
+    unsafe {
+        asm!("csrci mstatus, {}", const MSTATUS_MIE, options(nostack));
+        if cpu_lock_active {
+            asm!("csrs mstatus, {}", in(reg) MSTATUS_MPIE, options(nostack));
+        }
+    }
+
+    true
+}
+
+/// Calculate an `Id` from a task CB reference.
+fn task_id<Traits: PortInstance>(task: &TaskCb<Traits>) -> r3_kernel::Id {
+    // Safety: `task` refers to an element of `Traits::task_cb_pool()`
+    let offset = unsafe { <*const _>::offset_from(task, Traits::task_cb_pool().as_ptr()) };
+    r3_kernel::Id::new(offset as usize + 1).unwrap()
+}
@@ -1,10 +1,18 @@
 #[doc(hidden)]
 pub extern crate riscv_rt;
 
-/// Generate entry points using [`::riscv_rt`]. **Requires [`EntryPoint`] to
-/// be implemented.**
+/// Generate entry points using [`::riscv_rt`]. **Requires [`EntryPoint`] and
+/// [`ThreadingOptions`] to be implemented.**
+///
+/// This macro also defines `_mp_hook`, which `riscv_rt` calls on every hart.
+/// Hart 0 initializes the memory and boots the kernel. If
+/// [`ThreadingOptions::NUM_HARTS`] is greater than `1`, the harts
+/// `1..NUM_HARTS` wait until hart 0 releases them and then join the kernel.
+/// The other harts stay idle forever.
 ///
 /// [`EntryPoint`]: crate::EntryPoint
+/// [`ThreadingOptions`]: crate::ThreadingOptions
+/// [`ThreadingOptions::NUM_HARTS`]: crate::ThreadingOptions::NUM_HARTS
 #[macro_export]
 macro_rules! use_rt {
     (unsafe $Traits:ty) => {
@@ -16,6 +24,11 @@ macro_rules! use_rt {
                     <$Traits as $crate::EntryPoint>::start();
                 }
             }
+
+            #[export_name = "_mp_hook"]
+            fn mp_hook() -> bool {
+                unsafe { $crate::rt::imp::mp_hook::<$Traits>() }
+            }
         };
     };
 }
@@ -1,7 +1,40 @@
-use crate::EntryPoint;
+use crate::{threading::imp::msip_ptr, EntryPoint, ThreadingOptions};
 
 pub unsafe fn setup_interrupt_handler<System: EntryPoint>() {
     unsafe {
         core::arch::asm!("csrw mtvec, {}", in(reg) System::TRAP_HANDLER);
     }
 }
+
+/// Implements `_mp_hook`. Returns `true` if the current hart should initialize
+/// the memory.
+pub unsafe fn mp_hook<System: ThreadingOptions>() -> bool {
+    let hart: usize;
+    unsafe { core::arch::asm!("csrr {}, mhartid", out(reg) hart) };
+
+    if hart == 0 {
+        return true;
+    }
+
+    if hart >= System::NUM_HARTS {
+        loop {
+            unsafe { core::arch::asm!("wfi") };
+        }
+    }
+
+    // Wait until hart 0 sets our `msip` after initializing the memory
+    // [tag:riscv_mp_hook_wait]. `mie.MSIE` lets `wfi` return when `msip` is
+    // set while `mstatus.MIE` is still clear.
+    unsafe { core::arch::asm!("csrs mie, {}", in(reg) 1 << 3) };
+    let msip = msip_ptr::<System>(hart);
+    // Safety: `MSIP_PTR` points to the `msip` registers
+    while unsafe { msip.read_volatile() } == 0 {
+        unsafe { core::arch::asm!("wfi") };
+    }
+    unsafe { msip.write_volatile(0) };
+
+    // Make sure the memory initialized by hart 0 is visible
+    unsafe { core::arch::asm!("fence") };
+
+    false
+}
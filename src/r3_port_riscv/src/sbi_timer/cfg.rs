@@ -43,12 +43,13 @@ macro_rules! use_sbi_timer {
                 utils::Init,
             };
             use $crate::r3_kernel::{PortTimer, System, UTicks};
-            use $crate::r3_portkit::tickless;
+            use $crate::r3_portkit::tickless64;
             use $crate::{sbi_timer, SbiTimerOptions, Timer};
 
             impl PortTimer for $Traits {
                 const MAX_TICK_COUNT: UTicks = u32::MAX;
-                const MAX_TIMEOUT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as sbi_timer::imp::TimerInstance>::TICKLESS_CFG.max_timeout();
 
                 unsafe fn tick_count() -> UTicks {
                     // Safety: We are just forwarding the call
@@ -72,14 +73,11 @@ macro_rules! use_sbi_timer {
                 }
             }
 
-            static mut TIMER_STATE: <$Traits as sbi_timer::imp::TimerInstance>::TicklessState =
-                Init::INIT;
+            static mut TIMER_STATE: tickless64::Tickless64State = Init::INIT;
 
             // Safety: Only `use_sbi_timer!` is allowed to `impl` this
             unsafe impl sbi_timer::imp::TimerInstance for $Traits {
-                type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
-
-                fn tickless_state() -> *mut Self::TicklessState {
+                fn tickless_state() -> *mut tickless64::Tickless64State {
                     unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
                 }
             }
@@ -7,7 +7,7 @@
 use core::arch::asm;
 use r3_core::kernel::{traits, Cfg, StaticInterruptHandler};
 use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
-use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use r3_portkit::tickless64::{Tickless64Cfg, Tickless64Options, Tickless64State};
 
 use crate::sbi_timer::cfg::SbiTimerOptions;
 
@@ -17,25 +17,16 @@ use crate::sbi_timer::cfg::SbiTimerOptions;
 ///
 /// Only meant to be implemented by [`use_sbi_timer!`].
 pub unsafe trait TimerInstance: KernelTraits + SbiTimerOptions {
-    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
+    const TICKLESS_CFG: Tickless64Cfg = match Tickless64Cfg::new(Tickless64Options {
         hw_freq_num: <Self as SbiTimerOptions>::FREQUENCY,
         hw_freq_denom: <Self as SbiTimerOptions>::FREQUENCY_DENOMINATOR,
         hw_headroom_ticks: <Self as SbiTimerOptions>::HEADROOM,
-        // `stime` is a 64-bit free-running counter and it is
-        // expensive to create a 32-bit timer with an arbitrary
-        // period out of it.
-        force_full_hw_period: true,
-        // Clearing `stime` is not possible, so we must record the
-        // starting value of `stime` by calling `reset`.
-        resettable: true,
     }) {
         Ok(x) => x,
         Err(e) => e.panic(),
     };
 
-    type TicklessState: TicklessStateTrait;
-
-    fn tickless_state() -> *mut Self::TicklessState;
+    fn tickless_state() -> *mut Tickless64State;
 }
 
 #[cfg(any(
@@ -120,10 +111,6 @@ trait TimerInstanceExt: TimerInstance {
     target_arch = "riscv128"
 )))]
 trait TimerInstanceExt: TimerInstance {
-    fn time_lo() -> usize {
-        unimplemented!("target mismatch")
-    }
-
     fn set_timecmp(_value: u64) {
         unimplemented!("target mismatch")
     }
@@ -148,12 +135,12 @@ where
 /// Implements [`crate::Timer::init`]
 #[inline]
 pub fn init<Traits: TimerInstance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
-
     // Safety: No context switching during boot
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
-    tstate.reset(tcfg, Traits::time_lo() as u32);
+    // Clearing `time` is not possible, so we must record the starting value
+    // of `time` by calling `reset`.
+    tstate.reset(Traits::time());
 }
 
 /// Implements [`r3_kernel::PortTimer::tick_count`]
@@ -164,10 +151,10 @@ pub fn init<Traits: TimerInstance>() {
 pub unsafe fn tick_count<Traits: TimerInstance>() -> UTicks {
     let tcfg = &Traits::TICKLESS_CFG;
 
-    let hw_tick_count = Traits::time_lo() as u32;
+    let hw_tick_count = Traits::time();
 
     // Safety: CPU Lock protects it from concurrent access
-    let tstate = unsafe { &mut *Traits::tickless_state() };
+    let tstate = unsafe { &*Traits::tickless_state() };
     tstate.tick_count(tcfg, hw_tick_count)
 }
 
@@ -188,27 +175,20 @@ pub unsafe fn pend_tick<Traits: TimerInstance>() {
 pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
     let tcfg = &Traits::TICKLESS_CFG;
     // Safety: CPU Lock protects it from concurrent access
-    let tstate = unsafe { &mut *Traits::tickless_state() };
+    let tstate = unsafe { &*Traits::tickless_state() };
 
     let cur_hw_tick_count = Traits::time();
-    let hw_ticks = tstate
-        .mark_reference_and_measure(tcfg, cur_hw_tick_count as u32, tick_count_delta)
-        .hw_ticks;
-
-    let next_hw_tick_count = cur_hw_tick_count + hw_ticks as u64;
+    let next_hw_tick_count = tstate
+        .measure(tcfg, cur_hw_tick_count, tick_count_delta)
+        .end_hw_tick_count;
 
     Traits::set_timecmp(next_hw_tick_count);
 }
 
 #[inline]
 fn handle_tick<Traits: TimerInstance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
-
-    // Safety: CPU Lock protects it from concurrent access
-    let tstate = unsafe { &mut *Traits::tickless_state() };
-
-    let cur_hw_tick_count = Traits::time_lo() as u32;
-    tstate.mark_reference(tcfg, cur_hw_tick_count);
+    // `Tickless64State` derives the tick count from the full `time` value, so
+    // there's no need to mark a reference point here
 
     // Safety: CPU Lock inactive, an interrupt context
     unsafe { Traits::timer_tick() };
@@ -38,6 +38,58 @@ pub trait ThreadingOptions {
     /// [`EntryPoint`]: crate::EntryPoint
     /// [CPU Lock]: r3_core#system-states
     const PRIVILEGE_LEVEL: u8 = PRIVILEGE_LEVEL_MACHINE;
+
+    /// Enables memory protection using Physical Memory Protection (PMP).
+    /// Defaults to `false`.
+    ///
+    /// See [the crate-level documentation](crate#memory-protection) for
+    /// details. Requires [`PRIVILEGE_LEVEL`] to be
+    /// [`PRIVILEGE_LEVEL_MACHINE`]. Not supported in CLIC mode.
+    ///
+    /// [`PRIVILEGE_LEVEL`]: Self::PRIVILEGE_LEVEL
+    const USE_PMP: bool = false;
+
+    /// The amount of stack space (in bytes) that an unprivileged task must
+    /// have left when it calls a kernel service. Only used when [`USE_PMP`]
+    /// is `true`. Defaults to `1024`.
+    ///
+    /// Kernel services called by an unprivileged task run in M-mode on the
+    /// task's stack, where the PMP entries don't apply. The call is rejected
+    /// with `BadContext` unless the task's stack pointer is at least this many
+    /// bytes above the bottom of the task's usable stack. This must cover the
+    /// worst-case stack usage of the kernel services plus the context state
+    /// saved if an interrupt is taken or the task is preempted in a kernel
+    /// service.
+    ///
+    /// [`USE_PMP`]: Self::USE_PMP
+    const SYSCALL_STACK_SIZE: usize = 1024;
+
+    /// Called when a task is terminated because of a fault. Only used when
+    /// [`USE_PMP`] is `true`. Defaults to doing nothing.
+    ///
+    /// This method is called in an interrupt context, where kernel services
+    /// such as [`Task::activate`] can be used to handle the failure.
+    ///
+    /// [`USE_PMP`]: Self::USE_PMP
+    /// [`Task::activate`]: r3_core::kernel::task::TaskMethods::activate
+    fn handle_task_fault(_fault: &crate::TaskFault) {}
+
+    /// The number of harts that run the kernel. Defaults to `1`.
+    ///
+    /// If this is greater than `1`, the kernel runs in [the symmetric
+    /// multiprocessing mode][1] on the harts `0..NUM_HARTS`. See [the
+    /// crate-level documentation](crate#multiprocessing) for the requirements.
+    ///
+    /// [1]: r3_kernel#symmetric-multiprocessing
+    const NUM_HARTS: usize = 1;
+
+    /// The address of hart 0's `msip` register (i.e., the base address of
+    /// CLINT or ACLINT MSWI). Hart `i`'s `msip` register is expected to be
+    /// located at `MSIP_PTR + i * 4`. Only used if [`NUM_HARTS`] is greater
+    /// than `1`.
+    ///
+    /// [`NUM_HARTS`]: Self::NUM_HARTS
+    const MSIP_PTR: usize = 0;
 }
 
 /// The RISC-V privilege level encoding for the machine level.
@@ -75,13 +127,18 @@ macro_rules! use_port {
             };
             use $crate::core::ops::Range;
             use $crate::{
-                threading::imp::{State, TaskState, PortInstance, CsrSet, NumTy},
+                threading::imp::{State, TaskState, HartState, PortInstance, CsrSet, NumTy},
                 ThreadingOptions, EntryPoint, InterruptController,
                 InterruptControllerToPort,
             };
 
             pub(super) static PORT_STATE: State = State::new();
 
+            static SECONDARY_HART_STATES:
+                [HartState; <$Traits as ThreadingOptions>::NUM_HARTS - 1] =
+                [<HartState as $crate::r3_core::utils::Init>::INIT;
+                    <$Traits as ThreadingOptions>::NUM_HARTS - 1];
+
             unsafe impl PortInstance for $Traits {
                 #[inline(always)]
                 fn port_state() -> &'static State {
@@ -97,6 +154,13 @@ macro_rules! use_port {
 
                 type Csr = CsrSet<$Traits>;
                 type Priv = NumTy<{ <$Traits as ThreadingOptions>::PRIVILEGE_LEVEL as usize }>;
+                type UsePmp = NumTy<{ <$Traits as ThreadingOptions>::USE_PMP as usize }>;
+                type Smp = NumTy<{ (<$Traits as ThreadingOptions>::NUM_HARTS > 1) as usize }>;
+
+                #[inline(always)]
+                fn secondary_hart_states() -> &'static [HartState] {
+                    &SECONDARY_HART_STATES
+                }
             }
 
             impl EntryPoint for $Traits {
@@ -137,6 +201,8 @@ macro_rules! use_port {
                 // aligned to a word boundary.
                 const STACK_ALIGN: usize = 16;
 
+                const NUM_CPUS: usize = <$Traits as ThreadingOptions>::NUM_HARTS;
+
                 #[inline(always)]
                 unsafe fn dispatch_first_task() -> ! {
                     PORT_STATE.dispatch_first_task::<Self>()
@@ -147,6 +213,16 @@ macro_rules! use_port {
                     PORT_STATE.yield_cpu::<Self>()
                 }
 
+                #[inline(always)]
+                fn current_cpu() -> usize {
+                    PORT_STATE.current_cpu::<Self>()
+                }
+
+                #[inline(always)]
+                unsafe fn yield_cpu_on(cpu: usize) {
+                    PORT_STATE.yield_cpu_on::<Self>(cpu)
+                }
+
                 #[inline(always)]
                 unsafe fn exit_and_dispatch(task: &'static TaskCb<Self>) -> ! {
                     PORT_STATE.exit_and_dispatch::<Self>(task);
@@ -172,6 +248,11 @@ macro_rules! use_port {
                     PORT_STATE.initialize_task_state::<Self>(task)
                 }
 
+                #[inline(always)]
+                fn syscall_gate() -> Option<$crate::r3_kernel::syscall::Gate> {
+                    PORT_STATE.syscall_gate::<Self>()
+                }
+
                 #[inline(always)]
                 fn is_cpu_lock_active() -> bool {
                     PORT_STATE.is_cpu_lock_active::<Self>()
@@ -7,18 +7,19 @@ use r3_core::{
     },
     utils::Init,
 };
-use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb};
+use r3_kernel::{syscall, KernelTraits, Port, PortToKernel, System, TaskCb};
 use r3_portkit::pptext::pp_asm;
 
 use crate::{
-    InterruptController, ThreadingOptions, Timer, INTERRUPT_PLATFORM_START, INTERRUPT_SOFTWARE,
+    pmp, InterruptController, ThreadingOptions, Timer, INTERRUPT_PLATFORM_START,
+    INTERRUPT_SOFTWARE, PRIVILEGE_LEVEL_MACHINE,
 };
 
 /// `XLEN / 8`
-const X_SIZE: usize = core::mem::size_of::<usize>();
+pub(crate) const X_SIZE: usize = core::mem::size_of::<usize>();
 
 /// `FLEN / 8`
-const F_SIZE: usize = if cfg!(target_feature = "q") {
+pub(crate) const F_SIZE: usize = if cfg!(target_feature = "q") {
     16
 } else if cfg!(target_feature = "d") {
     8
@@ -30,7 +31,11 @@ const F_SIZE: usize = if cfg!(target_feature = "q") {
 const REG_ALIGN: usize = if F_SIZE > X_SIZE { F_SIZE } else { X_SIZE };
 
 /// The size of FLS.F
-const FLSF_SIZE: usize = 20 * F_SIZE + REG_ALIGN;
+pub(crate) const FLSF_SIZE: usize = 20 * F_SIZE + REG_ALIGN;
+
+/// The size of the header of a vector context save area, which holds `vl`,
+/// `vtype`, `vstart`, and `vcsr`.
+const VCTX_HDR_SIZE: usize = (4 * X_SIZE + 15) / 16 * 16;
 
 /// The assembly code fragments used by `pp_asm!`. Because of a mysterious macro
 /// hygienics behavior, they have to referred to by absolute paths.
@@ -151,10 +156,10 @@ pub use csr::{CsrSet, NumTy};
 
 /// The part of `xstatus` which is specific to each thread.
 ///
-/// `xstatus_part` is only used if `cfg!(target_feature = "f")`. `xstatus_part`
-/// is undefined otherwise.
+/// `xstatus_part` is only used if `cfg!(target_feature = "f")` or
+/// `cfg!(target_feature = "v")`. `xstatus_part` is undefined otherwise.
 #[allow(dead_code)]
-const XSTATUS_PART_MASK: usize = csr::XSTATUS_FS_1;
+const XSTATUS_PART_MASK: usize = csr::XSTATUS_FS_1 | csr::XSTATUS_VS;
 
 /// Implemented on a kernel trait type by [`use_port!`].
 ///
@@ -170,7 +175,10 @@ pub unsafe trait PortInstance:
     const INTERRUPT_TIMER_HANDLER: Option<InterruptHandlerFn>;
     const INTERRUPT_EXTERNAL_HANDLER: Option<InterruptHandlerFn>;
 
-    const USE_INTERRUPT_SOFTWARE: bool = Self::INTERRUPT_SOFTWARE_HANDLER.is_some();
+    /// The software interrupt is used for inter-processor interrupts if
+    /// `NUM_HARTS > 1`.
+    const USE_INTERRUPT_SOFTWARE: bool =
+        Self::INTERRUPT_SOFTWARE_HANDLER.is_some() || Self::NUM_HARTS > 1;
     const USE_INTERRUPT_TIMER: bool = Self::INTERRUPT_TIMER_HANDLER.is_some();
     const USE_INTERRUPT_EXTERNAL: bool = Self::INTERRUPT_EXTERNAL_HANDLER.is_some();
 
@@ -178,23 +186,111 @@ pub unsafe trait PortInstance:
 
     /// Validated privilege level encoding.
     type Priv: csr::Num;
-}
 
-static mut DISPATCH_PENDING: bool = false;
+    /// [`ThreadingOptions::USE_PMP`] as a number.
+    type UsePmp: csr::Num;
+
+    /// `NUM_HARTS > 1` as a number.
+    type Smp: csr::Num;
 
-static mut MAIN_STACK: usize = 0;
+    /// The [`HartState`]s of the harts `1..NUM_HARTS`.
+    fn secondary_hart_states() -> &'static [HartState];
+}
 
-/// The current nesting level minus one.
+/// The per-hart state of the port.
 ///
-/// The valid range is `-1..=isize::MAX`. The current context is a task
-/// context iff `INTERRUPT_NESTING == -1`.
+/// Hart 0 uses `PRIMARY_HART_STATE`, and the other harts use
+/// [`PortInstance::secondary_hart_states`]. If `NUM_HARTS > 1`, `mscratch`
+/// points to the current hart's `HartState` [ref:riscv_mscratch_hart_state].
+/// Otherwise, the assembly code accesses `PRIMARY_HART_STATE` directly.
+#[doc(hidden)] // used by macro
+#[repr(C)]
+pub struct HartState {
+    /// The stack pointer of the boot context, which is used as the main stack
+    /// after `dispatch_first_task`. Accessed at offset `HS_MAIN_STACK`.
+    main_stack: UnsafeCell<usize>,
+
+    /// The current nesting level minus one. Accessed at offset
+    /// `HS_INTERRUPT_NESTING`.
+    ///
+    /// The valid range is `-1..=i32::MAX`. The current context is a task
+    /// context iff `interrupt_nesting == -1`.
+    ///
+    /// `is_task_context` is supposed to return `false` in the main
+    /// thread (which is a boot context and not a task context). For
+    /// this reason, `interrupt_nesting` is initialized as `0`. This
+    /// doesn't reflect the actual nesting level, but it doesn't matter
+    /// because interrupts are disabled during booting.
+    interrupt_nesting: UnsafeCell<i32>,
+
+    /// Set by `yield_cpu` in an interrupt context. Accessed at offset
+    /// `HS_DISPATCH_PENDING`.
+    dispatch_pending: UnsafeCell<bool>,
+}
+
+unsafe impl Sync for HartState {}
+
+impl Init for HartState {
+    #[allow(clippy::declare_interior_mutable_const)] // it's intentional
+    const INIT: Self = Self {
+        main_stack: UnsafeCell::new(0),
+        interrupt_nesting: UnsafeCell::new(0),
+        dispatch_pending: UnsafeCell::new(false),
+    };
+}
+
+/// The offset of [`HartState::main_stack`].
+const HS_MAIN_STACK: usize = 0;
+/// The offset of [`HartState::interrupt_nesting`].
+const HS_INTERRUPT_NESTING: usize = X_SIZE;
+/// The offset of [`HartState::dispatch_pending`].
+const HS_DISPATCH_PENDING: usize = X_SIZE + 4;
+
+/// The [`HartState`] of hart 0.
+static PRIMARY_HART_STATE: HartState = Init::INIT;
+
+/// Get the current hart's ID. Always returns `0` if `NUM_HARTS == 1`.
+#[inline]
+fn current_hart<Traits: PortInstance>() -> usize {
+    if Traits::NUM_HARTS > 1 {
+        let hart: usize;
+        // Safety: `NUM_HARTS > 1` requires M-mode
+        unsafe { asm!("csrr {}, mhartid", out(reg) hart, options(nomem, nostack)) };
+        hart
+    } else {
+        0
+    }
+}
+
+/// Get the current hart's [`HartState`].
+#[inline]
+fn hart_state<Traits: PortInstance>() -> &'static HartState {
+    if Traits::NUM_HARTS > 1 {
+        let hart_state: *const HartState;
+        // Safety: `NUM_HARTS > 1` requires M-mode
+        unsafe { asm!("csrr {}, mscratch", out(reg) hart_state, options(nomem, nostack)) };
+        // Safety: `mscratch` was set by `port_boot`
+        // [ref:riscv_mscratch_hart_state]
+        unsafe { &*hart_state }
+    } else {
+        &PRIMARY_HART_STATE
+    }
+}
+
+/// Get a pointer to the specified hart's `msip` register.
+pub(crate) fn msip_ptr<Traits: ThreadingOptions>(hart: usize) -> *mut u32 {
+    (Traits::MSIP_PTR + hart * 4) as *mut u32
+}
+
+/// `VLEN / 8`. Only valid if `cfg!(target_feature = "v")`. Set by `port_boot`.
+static mut VLENB: usize = 0;
+
+/// The task (`*const TaskCb`) whose vector context is currently loaded to the
+/// vector registers, or zero. Only used if `cfg!(target_feature = "v")`.
 ///
-/// `is_task_context` is supposed to return `false` in the main
-/// thread (which is a boot context and not a task context). For
-/// this reason, `INTERRUPT_NESTING` is initialized as `0`. This
-/// doesn't reflect the actual nesting level, but it doesn't matter
-/// because interrupts are disabled during booting.
-static mut INTERRUPT_NESTING: i32 = 0;
+/// The vector registers hold the owner's vector context as of its last save
+/// unless the owner is running or its `xstatus_part.VS` is Initial.
+static mut VECTOR_OWNER: usize = 0;
 
 pub struct State {}
 
@@ -204,6 +300,12 @@ unsafe impl Sync for State {}
 #[repr(C)]
 pub struct TaskState {
     sp: UnsafeCell<usize>,
+    /// The vector context save area. Only valid if the task uses the vector
+    /// extension ([`TaskAttr::extended_context`]).
+    ///
+    /// [`TaskAttr::extended_context`]: r3_kernel::TaskAttr::extended_context
+    #[cfg(target_feature = "v")]
+    vctx: UnsafeCell<usize>,
 }
 
 unsafe impl Sync for TaskState {}
@@ -218,12 +320,28 @@ impl Init for TaskState {
     #[allow(clippy::declare_interior_mutable_const)] // it's intentional
     const INIT: Self = Self {
         sp: UnsafeCell::new(0),
+        #[cfg(target_feature = "v")]
+        vctx: UnsafeCell::new(0),
     };
 }
 
 impl State {
     #[inline(always)]
     pub unsafe fn port_boot<Traits: PortInstance>(&self) -> ! {
+        let hart = current_hart::<Traits>();
+
+        if Traits::NUM_HARTS > 1 {
+            // Let the assembly code find the current hart's `HartState`
+            // [tag:riscv_mscratch_hart_state]
+            let hart_state = if hart == 0 {
+                &PRIMARY_HART_STATE
+            } else {
+                &Traits::secondary_hart_states()[hart - 1]
+            };
+            // Safety: `NUM_HARTS > 1` requires M-mode
+            unsafe { asm!("csrw mscratch, {}", in(reg) hart_state, options(nomem, nostack)) };
+        }
+
         unsafe { self.enter_cpu_lock::<Traits>() };
 
         // Enable FPU
@@ -232,14 +350,50 @@ impl State {
             Traits::Csr::xstatus().set(csr::XSTATUS_FS_0);
         }
 
+        if hart != 0 {
+            // The other harts only take inter-processor interrupts. Timer and
+            // external interrupts are handled by hart 0.
+            // [tag:riscv_secondary_hart_interrupts]
+            Traits::Csr::xie().clear(Traits::Csr::XIE_XTIE | Traits::Csr::XIE_XEIE);
+            Traits::Csr::xie().set(Traits::Csr::XIE_XSIE);
+
+            // Safety: We are the port, so it's okay to call this
+            unsafe { <Traits as PortToKernel>::boot_secondary() };
+        }
+
+        // Disable the vector unit. It's only enabled while running a task
+        // using the vector extension.
+        #[cfg(target_feature = "v")]
+        {
+            Traits::Csr::xstatus().set(csr::XSTATUS_VS_0);
+            let vlenb: usize;
+            // Safety: `vlenb` is accessible while the vector unit is enabled
+            unsafe { asm!("csrr {}, vlenb", out(reg) vlenb, options(nomem, nostack)) };
+            // Safety: CPU Lock active, no other contexts access `VLENB` yet
+            unsafe { VLENB = vlenb };
+            Traits::Csr::xstatus().clear(csr::XSTATUS_VS);
+        }
+
+        // Install the CLIC trap vector before the interrupt controller driver
+        // puts the hart in CLIC mode
+        if Traits::USE_CLIC_MODE {
+            Traits::Csr::xtvec().write(clic_trap_vector::<Traits> as usize | csr::XTVEC_MODE_CLIC);
+        }
+
         // Safety: We are the port, so it's okay to call this
         unsafe { <Traits as InterruptController>::init() };
 
         // Safety: We are the port, so it's okay to call this
         unsafe { <Traits as Timer>::init() };
 
-        // Enable local interrupts
-        {
+        if Traits::USE_PMP {
+            // Safety: M-mode, CPU Lock active
+            unsafe { pmp::imp::init::<Traits>() };
+        }
+
+        // Enable local interrupts. In CLIC mode, `xie` is not used, and the
+        // interrupt controller driver is responsible for this.
+        if !Traits::USE_CLIC_MODE {
             let mut clear_set = [0usize; 2];
             clear_set[Traits::USE_INTERRUPT_SOFTWARE as usize] |= Traits::Csr::XIE_XSIE;
             clear_set[Traits::USE_INTERRUPT_TIMER as usize] |= Traits::Csr::XIE_XTIE;
@@ -252,6 +406,18 @@ impl State {
             }
         }
 
+        // Release the other harts waiting in `_mp_hook`. They wait further in
+        // `boot_secondary` until the kernel is initialized.
+        // [ref:riscv_mp_hook_wait]
+        if Traits::NUM_HARTS > 1 {
+            // Make the memory initialized so far visible to them
+            unsafe { asm!("fence", options(nostack)) };
+        }
+        for hart in 1..Traits::NUM_HARTS {
+            // Safety: `MSIP_PTR` points to the `msip` registers
+            unsafe { msip_ptr::<Traits>(hart).write_volatile(1) };
+        }
+
         // Safety: We are the port, so it's okay to call this
         unsafe { <Traits as PortToKernel>::boot() };
     }
@@ -261,16 +427,29 @@ impl State {
         debug_assert!(self.is_cpu_lock_active::<Traits>());
 
         // We are going to dispatch the first task and enable interrupts, so
-        // set `INTERRUPT_NESTING` to `-1`, indicating that there are no active
+        // set `interrupt_nesting` to `-1`, indicating that there are no active
         // interrupts and we are in a task context.
-        unsafe { INTERRUPT_NESTING = -1 };
+        unsafe { *hart_state::<Traits>().interrupt_nesting.get() = -1 };
+
+        // In CLIC mode, `xret` sets the current interrupt level to
+        // `xcause.xpil`, which must be zero (the interrupt level of tasks)
+        // when returning to a task. This invariant is maintained by
+        // `handle_interrupt` after this point.
+        if Traits::USE_CLIC_MODE {
+            Traits::Csr::xcause().write(0);
+        }
 
         unsafe {
             pp_asm!("
             "   crate::threading::imp::asm_inc::define_load_store!()              "
                 # Save the stack pointer for later use
                 # [tag:riscv_main_stack_assigned_in_dft]
-                STORE sp, ({MAIN_STACK}), a0
+                .if /*{SMP}*/
+                    csrr a0, mscratch
+                .else
+                    la a0, {PRIMARY_HART_STATE}
+                .endif
+                STORE sp, {HS_MAIN_STACK}(a0)
 
                 # `xstatus.XPIE` will be `1` all the time except in a software
                 # exception handler
@@ -279,7 +458,9 @@ impl State {
 
                 tail {push_second_level_state_and_dispatch}.dispatch
                 ",
-                MAIN_STACK = sym MAIN_STACK,
+                PRIMARY_HART_STATE = sym PRIMARY_HART_STATE,
+                HS_MAIN_STACK = const HS_MAIN_STACK,
+                SMP = sym <<Traits as PortInstance>::Smp as csr::Num>::value,
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
                 PRIV = sym <<Traits as PortInstance>::Priv as csr::Num>::value,
@@ -291,7 +472,7 @@ impl State {
     #[inline]
     pub unsafe fn yield_cpu<Traits: PortInstance>(&'static self) {
         if !self.is_task_context::<Traits>() {
-            unsafe { DISPATCH_PENDING = true };
+            unsafe { *hart_state::<Traits>().dispatch_pending.get() = true };
         } else {
             // `yield_cpu_in_task` does not clobber any registers except
             // for `ra`
@@ -306,6 +487,38 @@ impl State {
         }
     }
 
+    /// Implements [`r3_kernel::PortThreading::yield_cpu_on`].
+    #[inline]
+    pub unsafe fn yield_cpu_on<Traits: PortInstance>(&'static self, hart: usize) {
+        // The target hart calls `yield_cpu` in its software interrupt handler
+        // [ref:riscv_ipi]
+        // Safety: `MSIP_PTR` points to the `msip` registers
+        unsafe { msip_ptr::<Traits>(hart).write_volatile(1) };
+    }
+
+    /// Implements [`r3_kernel::PortThreading::current_cpu`].
+    #[inline]
+    pub fn current_cpu<Traits: PortInstance>(&self) -> usize {
+        current_hart::<Traits>()
+    }
+
+    /// Handle an inter-processor interrupt sent by [`Self::yield_cpu_on`].
+    /// [tag:riscv_ipi]
+    ///
+    /// # Safety
+    ///
+    /// An interrupt context, CPU Lock inactive, `NUM_HARTS > 1`
+    #[inline]
+    unsafe fn handle_ipi<Traits: PortInstance>() {
+        // Clear the request first so that a request made after this point
+        // isn't lost
+        // Safety: `MSIP_PTR` points to the `msip` registers
+        unsafe { msip_ptr::<Traits>(current_hart::<Traits>()).write_volatile(0) };
+
+        // Safety: Upheld by the caller
+        unsafe { Traits::port_state().yield_cpu::<Traits>() };
+    }
+
     #[naked]
     unsafe extern "C" fn yield_cpu_in_task<Traits: PortInstance>() {
         unsafe {
@@ -409,7 +622,7 @@ impl State {
     /// The procedure does the following:
     ///
     ///  - **Don't** push the first-level state.
-    ///  - If `DISPATCH_PENDING == 0`,
+    ///  - If `hart_state.dispatch_pending == 0`,
     ///     - If the current task is not the idle task, go to
     ///       `pop_first_level_state`.
     ///     - Otherwise, branch to the idle task loop.
@@ -417,10 +630,9 @@ impl State {
     ///  - If the current task is not the idle task,
     ///     - Push the second-level state.
     ///     - Store SP to the current task's `TaskState`.
-    ///  - If the current task is the idle task,
-    ///     - Update SP to point to the main stack. In this case, **this
-    ///       procedure may overwrite any contents in the main stack.**
     ///  - **`dispatch:`** (alternate entry point)
+    ///  - Update SP to point to the main stack. **This procedure may overwrite
+    ///    any contents in the main stack.**
     ///  - Call [`r3_kernel::PortToKernel::choose_running_task`].
     ///  - Restore SP from the next scheduled task's `TaskState`.
     ///  - If there's no task to schedule, branch to the idle task loop.
@@ -445,7 +657,7 @@ impl State {
     ///
     /// `dispatch`:
     ///
-    ///  - SP must point to a valid stack.
+    ///  - The main stack must not be in use.
     ///
     /// `pop_first_level_state`:
     ///
@@ -461,6 +673,11 @@ impl State {
             // Safety: CPU Lock active
             unsafe { Traits::choose_running_task() };
 
+            if Traits::USE_PMP {
+                // Safety: M-mode, CPU Lock active
+                unsafe { crate::pmp::imp::switch_task::<Traits>() };
+            }
+
             A0A1(MaybeUninit::uninit(), unsafe {
                 *Traits::state().running_task_ptr()
             })
@@ -481,11 +698,16 @@ impl State {
             "   crate::threading::imp::asm_inc::define_fload_fstore!()              "
 
                 # <a0 = xstatus_part>
-                # Take a shortcut only if `DISPATCH_PENDING == 0`
-                lb a1, ({DISPATCH_PENDING})
+                # Take a shortcut only if `hart_state.dispatch_pending == 0`
+                .if /*{SMP}*/
+                    csrr a2, mscratch
+                .else
+                    la a2, {PRIMARY_HART_STATE}
+                .endif
+                lb a1, {HS_DISPATCH_PENDING}(a2)
                 bnez a1, 0f
 
-                # `DISPATCH_PENDING` is clear, meaning we are returning to the
+                # `dispatch_pending` is clear, meaning we are returning to the
                 # same task that the current exception has interrupted.
                 #
                 # If we are returning to the idle task, branch to `idle_task`
@@ -495,28 +717,22 @@ impl State {
                 j {push_second_level_state_and_dispatch}.pop_first_level_state
 
             0:
-                # `DISPATCH_PENDING` is set, meaning `yield_cpu` was called in
+                # `dispatch_pending` is set, meaning `yield_cpu` was called in
                 # an interrupt handler, meaning we might need to return to a
-                # different task. Clear `DISPATCH_PENDING` and proceeed to
+                # different task. Clear `dispatch_pending` and proceeed to
                 # `not_shortcutting`.
-                sb zero, ({DISPATCH_PENDING}), a2
+                sb zero, {HS_DISPATCH_PENDING}(a2)
 
             .global {push_second_level_state_and_dispatch}.not_shortcutting
             {push_second_level_state_and_dispatch}.not_shortcutting:
                 # <a0 = xstatus_part>
 
                 # Skip saving the second-level state if the current context
-                # is an idle task. Also, in this case, we don't have a stack,
-                # but `choose_and_get_next_task` needs one. Therefore we borrow
-                # the main stack.
+                # is an idle task.
                 #
                 #   if sp == 0:
                 #       <running_task is None>
-                #       sp = *main_stack_ptr;
-                #   else:
-                #       /* ... */
-                #
-                #   choose_and_get_next_task();
+                #       goto dispatch;
                 #
                 beqz sp, 1f
 
@@ -538,12 +754,6 @@ impl State {
                 STORE s10, ({X_SIZE} * 10)(sp)
                 STORE s11, ({X_SIZE} * 11)(sp)
 
-                # The following branch checks the following conditions, which
-                # are coincidentally identical, at the same time
-                #
-                #  - Is it possible for FP registers to be in use?
-                #  - Do we use `xstatus_part`?
-                #
             "   if cfg!(target_feature = "f") {                                     "
                     # If FP registers are in use, push SLS.F.
                     #
@@ -572,12 +782,64 @@ impl State {
                     FSTORE fs10, ({F_SIZE} * 10)(sp)
                     FSTORE fs11, ({F_SIZE} * 11)(sp)
                 0:      # PushSLSFEnd
+            "   } else {                                                            "
+                    # unused: {F_SIZE} {FS_1}
+            "   }                                                                   "
 
+            "   if cfg!(target_feature = "v") {                                     "
+                    # If vector registers were modified, save them to the
+                    # task's vector context save area. The trap handler might
+                    # have disabled the vector unit, so enable it first.
+                    #
+                    #   <a0 = xstatus_part, a1 = running_task>
+                    #   if xstatus_part.VS == Dirty:
+                    #       xstatus.VS = Dirty;
+                    #       let vctx = a1.port_task_state.vctx;
+                    #       vctx.header = (vl, vtype, vstart, vcsr);
+                    #       vstart = 0;
+                    #       vctx.v = [v0-v31];
+                    #       xstatus_part.VS = Clean;
+                    #       VECTOR_OWNER = a1;
+                    #   <a0 = xstatus_part>
+                    #
+                    li a2, {VS}
+                    and a3, a0, a2
+                    bne a3, a2, 0f      # → PushVctxEnd
+
+                    csrs " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a2
+                    LOAD a3, {X_SIZE}(a1)
+                    csrr a4, vl
+                    csrr a5, vtype
+                    csrr a6, vstart
+                    csrr a7, vcsr
+                    STORE a4, ({X_SIZE} * 0)(a3)
+                    STORE a5, ({X_SIZE} * 1)(a3)
+                    STORE a6, ({X_SIZE} * 2)(a3)
+                    STORE a7, ({X_SIZE} * 3)(a3)
+                    csrw vstart, zero
+
+                    csrr a4, vlenb
+                    slli a4, a4, 3
+                    addi a3, a3, {VCTX_HDR_SIZE}
+                    vs8r.v v0, (a3)
+                    add a3, a3, a4
+                    vs8r.v v8, (a3)
+                    add a3, a3, a4
+                    vs8r.v v16, (a3)
+                    add a3, a3, a4
+                    vs8r.v v24, (a3)
+
+                    xori a0, a0, {VS_0}
+                    STORE a1, ({VECTOR_OWNER}), a2
+                0:      # PushVctxEnd
+            "   } else {                                                            "
+                    # unused: {VS} {VS_0} {VS_1} {VCTX_HDR_SIZE} {VECTOR_OWNER}
+            "   }                                                                   "
+
+            "   if cfg!(any(target_feature = "f", target_feature = "v")) {          "
                     # Push `xstatus_part`
                     addi sp, sp, -{X_SIZE}
                     STORE a0, (sp)
-            "   } else {                                                            "
-                    # unused: {F_SIZE} {FS_1}
             "   }                                                                   "
 
                 # Store SP to `TaskState`.
@@ -587,13 +849,24 @@ impl State {
                 #
                 STORE sp, (a1)
 
-                j {push_second_level_state_and_dispatch}.dispatch
-
             1:
-                LOAD sp, ({MAIN_STACK})
-
             .global {push_second_level_state_and_dispatch}.dispatch
             {push_second_level_state_and_dispatch}.dispatch:
+                # Switch to the main stack. The idle task doesn't have a stack,
+                # and in a multiprocessor system, another hart may resume the
+                # current task as soon as `choose_running_task` releases the
+                # kernel's spinlock, so we must stop using the task's stack by
+                # then.
+                #
+                #   sp = hart_state.main_stack;
+                #
+                .if /*{SMP}*/
+                    csrr a0, mscratch
+                .else
+                    la a0, {PRIMARY_HART_STATE}
+                .endif
+                LOAD sp, {HS_MAIN_STACK}(a0)
+
                 # Choose the next task to run. `choose_and_get_next_task`
                 # returns the new value of `running_task`.
                 call {choose_and_get_next_task}
@@ -610,17 +883,13 @@ impl State {
                 beqz a1, {push_second_level_state_and_dispatch}.idle_task
                 LOAD sp, (a1)
 
-                # The following branch checks the following conditions, which
-                # are coincidentally identical, at the same time
-                #
-                #  - Is it possible for FP registers to be in use?
-                #  - Do we use `xstatus_part`?
-                #
-            "   if cfg!(target_feature = "f") {                                     "
+            "   if cfg!(any(target_feature = "f", target_feature = "v")) {          "
                     # Pop `xstatus_part`
                     LOAD a0, (sp)
                     addi sp, sp, {X_SIZE}
+            "   }                                                                   "
 
+            "   if cfg!(target_feature = "f") {                                     "
                     # If FP registers are in use, pop SLS.F.
                     #
                     #   <a0 = xstatus_part>
@@ -650,6 +919,58 @@ impl State {
                 0:      # PopSLSFEnd
             "   }                                                                   "
 
+            "   if cfg!(target_feature = "v") {                                     "
+                    # If the task uses the vector unit, take the ownership of
+                    # the vector registers. If the task has a saved vector
+                    # context that is not loaded to the vector registers,
+                    # restore it.
+                    #
+                    #   <a0 = xstatus_part, a1 = running_task>
+                    #   if xstatus_part.VS != Off:
+                    #       if xstatus_part.VS == Clean && VECTOR_OWNER != a1:
+                    #           xstatus.VS = Dirty;
+                    #           let vctx = a1.port_task_state.vctx;
+                    #           vstart = 0;
+                    #           [v0-v31] = vctx.v;
+                    #           (vl, vtype, vstart, vcsr) = vctx.header;
+                    #       VECTOR_OWNER = a1;
+                    #   <a0 = xstatus_part>
+                    #
+                    li a2, {VS}
+                    and a3, a0, a2
+                    beqz a3, 0f         # → PopVctxEnd
+                    li a4, {VS_1}
+                    bne a3, a4, 1f      # → PopVctxSetOwner
+                    LOAD a4, ({VECTOR_OWNER})
+                    beq a4, a1, 1f      # → PopVctxSetOwner
+
+                    csrs " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a2
+                    LOAD a3, {X_SIZE}(a1)
+                    csrw vstart, zero
+
+                    csrr a4, vlenb
+                    slli a4, a4, 3
+                    addi a5, a3, {VCTX_HDR_SIZE}
+                    vl8re8.v v0, (a5)
+                    add a5, a5, a4
+                    vl8re8.v v8, (a5)
+                    add a5, a5, a4
+                    vl8re8.v v16, (a5)
+                    add a5, a5, a4
+                    vl8re8.v v24, (a5)
+
+                    LOAD a4, ({X_SIZE} * 0)(a3)
+                    LOAD a5, ({X_SIZE} * 1)(a3)
+                    LOAD a6, ({X_SIZE} * 2)(a3)
+                    LOAD a7, ({X_SIZE} * 3)(a3)
+                    vsetvl zero, a4, a5
+                    csrw vstart, a6
+                    csrw vcsr, a7
+                1:      # PopVctxSetOwner
+                    STORE a1, ({VECTOR_OWNER}), a2
+                0:      # PopVctxEnd
+            "   }                                                                   "
+
                 # Pop the second-level context state.
                 LOAD s0, ({X_SIZE} * 0)(sp)
                 LOAD s1, ({X_SIZE} * 1)(sp)
@@ -669,6 +990,19 @@ impl State {
             {push_second_level_state_and_dispatch}.pop_first_level_state:
                 # <a0 = xstatus_part>
 
+            "   if cfg!(target_feature = "v") {                                     "
+                    # Restore `xstatus.VS`. This re-enables the vector unit if
+                    # it was disabled by the trap handler.
+                    #
+                    #   <a0 = xstatus_part>
+                    #   xstatus.VS = xstatus_part.VS;
+                    #
+                    li a1, {VS}
+                    csrc " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a1
+                    and a1, a1, a0
+                    csrs " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a1
+            "   }                                                                   "
+
             "   if cfg!(target_feature = "f") {                                     "
                     # If FP registers were in use, pop FLS.F. Loading FP regs
                     # will implicitly set `xstatus.FS[1]`.
@@ -761,6 +1095,22 @@ impl State {
                 #   <end of procedure>
                 #
                 LOAD a7, ({X_SIZE} * 16)(sp)
+
+                # If `pc[0]` is set, return to U-mode [ref:riscv_pmp_pc_bit0]
+                #
+                #   tp = pc[0];
+                #   if pc[0] != 0:
+                #       mstatus.MPP = U;
+                #
+                .if /*{USE_PMP}*/
+                    andi a0, a7, 1
+                    mv tp, a0
+                    beqz a0, 4f
+                    li a0, {MPP_M}
+                    csrc mstatus, a0
+                4:
+                .endif
+
                 LOAD ra, ({X_SIZE} * 0)(sp)
                 LOAD t0, ({X_SIZE} * 1)(sp)
                 LOAD t1, ({X_SIZE} * 2)(sp)
@@ -810,15 +1160,23 @@ impl State {
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
                 choose_and_get_next_task = sym choose_and_get_next_task::<Traits>,
                 get_running_task = sym get_running_task::<Traits>,
-                MAIN_STACK = sym MAIN_STACK,
-                DISPATCH_PENDING = sym DISPATCH_PENDING,
+                PRIMARY_HART_STATE = sym PRIMARY_HART_STATE,
+                HS_MAIN_STACK = const HS_MAIN_STACK,
+                HS_DISPATCH_PENDING = const HS_DISPATCH_PENDING,
+                SMP = sym <<Traits as PortInstance>::Smp as csr::Num>::value,
                 MPP_M = const csr::XSTATUS_MPP_M,
                 SPP_S = const csr::XSTATUS_SPP_S,
                 PRIV = sym <<Traits as PortInstance>::Priv as csr::Num>::value,
+                USE_PMP = sym <<Traits as PortInstance>::UsePmp as csr::Num>::value,
                 FS_1 = const csr::XSTATUS_FS_1,
+                VS = const csr::XSTATUS_VS,
+                VS_0 = const csr::XSTATUS_VS_0,
+                VS_1 = const csr::XSTATUS_VS_1,
+                VECTOR_OWNER = sym VECTOR_OWNER,
                 X_SIZE = const X_SIZE,
                 F_SIZE = const F_SIZE,
                 FLSF_SIZE = const FLSF_SIZE,
+                VCTX_HDR_SIZE = const VCTX_HDR_SIZE,
                 options(noreturn)
             );
         }
@@ -860,14 +1218,36 @@ impl State {
         Traits::Csr::xstatus_set_xie();
     }
 
+    /// Implements [`r3_kernel::PortThreading::syscall_gate`].
+    #[inline(always)]
+    pub fn syscall_gate<Traits: PortInstance>(&self) -> Option<syscall::Gate> {
+        pmp::imp::syscall_gate::<Traits>()
+    }
+
     pub unsafe fn initialize_task_state<Traits: PortInstance>(
         &self,
         task: &'static TaskCb<Traits>,
     ) {
         let stack: *mut [u8] = task.attr.stack.as_ptr();
+        // An unprivileged task can only use the part of the stack above the
+        // stack guard
+        let stack_top = pmp::imp::task_stack_region(task).end;
+
+        // Reserve the vector context save area at the top of the stack
+        #[cfg(target_feature = "v")]
+        let stack_top = if task.attr.extended_context.is_enabled() {
+            // Safety: Only written by `port_boot`
+            let vctx_size = VCTX_HDR_SIZE + 32 * unsafe { VLENB };
+            let vctx = (stack_top - vctx_size) & !15;
+            unsafe { *task.port_task_state.vctx.get() = vctx };
+            vctx
+        } else {
+            stack_top
+        };
+
         let mut sp = stack
             .as_mut_ptr()
-            .wrapping_add(stack.len())
+            .wrapping_add(stack_top - stack.as_mut_ptr() as usize)
             .cast::<MaybeUninit<usize>>();
         // TODO: Enforce minimum stack size
 
@@ -920,8 +1300,11 @@ impl State {
             first_level[14] = preload_val(0x30);
             first_level[15] = preload_val(0x31);
         }
-        // pc: The entry point
-        first_level[16] = MaybeUninit::new(task.attr.entry_point as usize);
+        // pc: The entry point. `pc[0]` indicates the task runs in U-mode
+        // [ref:riscv_pmp_pc_bit0].
+        first_level[16] = MaybeUninit::new(
+            task.attr.entry_point as usize | pmp::imp::is_unprivileged_task(task) as usize,
+        );
 
         // Second-level state (saved and restored only when we are doing context
         // switching)
@@ -950,11 +1333,14 @@ impl State {
         // SLS.F is non-existent when `xstatus.FS[1] == 0`
 
         // SLS.HDR
-        if cfg!(target_feature = "f") {
+        if cfg!(any(target_feature = "f", target_feature = "v")) {
             // xstatus
             //  - FS[1] = 0
+            //  - VS = Initial if the task uses the vector extension, Off
+            //    otherwise
+            let vs_initial = cfg!(target_feature = "v") && task.attr.extended_context.is_enabled();
             sp = sp.wrapping_sub(1);
-            unsafe { *sp = MaybeUninit::new(0) };
+            unsafe { *sp = MaybeUninit::new([0, csr::XSTATUS_VS_0][vs_initial as usize]) };
         }
 
         let task_state = &task.port_task_state;
@@ -968,7 +1354,7 @@ impl State {
 
     #[inline]
     pub fn is_task_context<Traits: PortInstance>(&self) -> bool {
-        unsafe { INTERRUPT_NESTING < 0 }
+        unsafe { *hart_state::<Traits>().interrupt_nesting.get() < 0 }
     }
 
     #[inline]
@@ -978,9 +1364,9 @@ impl State {
 
     #[inline]
     pub fn is_scheduler_active<Traits: PortInstance>(&self) -> bool {
-        // `MAIN_STACK` is assigned by `dispatch_first_task`
+        // `main_stack` is assigned by `dispatch_first_task`
         // [ref:riscv_main_stack_assigned_in_dft]
-        unsafe { MAIN_STACK != 0 }
+        unsafe { *hart_state::<Traits>().main_stack.get() != 0 }
     }
 
     pub fn set_interrupt_line_priority<Traits: PortInstance>(
@@ -988,7 +1374,7 @@ impl State {
         num: InterruptNum,
         priority: InterruptPriority,
     ) -> Result<(), SetInterruptLinePriorityError> {
-        if num < INTERRUPT_PLATFORM_START {
+        if num < INTERRUPT_PLATFORM_START && !Traits::USE_CLIC_MODE {
             Err(SetInterruptLinePriorityError::BadParam)
         } else {
             // Safety: We are delegating the call in the intended way
@@ -1001,7 +1387,7 @@ impl State {
         &'static self,
         num: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        if num < INTERRUPT_PLATFORM_START {
+        if num < INTERRUPT_PLATFORM_START && !Traits::USE_CLIC_MODE {
             // Enabling or disabling local interrupt lines is not supported
             Err(EnableInterruptLineError::BadParam)
         } else {
@@ -1015,7 +1401,7 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        if num < INTERRUPT_PLATFORM_START {
+        if num < INTERRUPT_PLATFORM_START && !Traits::USE_CLIC_MODE {
             // Enabling or disabling local interrupt lines is not supported
             Err(EnableInterruptLineError::BadParam)
         } else {
@@ -1029,7 +1415,10 @@ impl State {
         &'static self,
         num: InterruptNum,
     ) -> Result<(), PendInterruptLineError> {
-        if num == INTERRUPT_SOFTWARE {
+        if Traits::USE_CLIC_MODE {
+            // Safety: We are delegating the call in the intended way
+            unsafe { <Traits as InterruptController>::pend_interrupt_line(num) }
+        } else if num == INTERRUPT_SOFTWARE && Traits::NUM_HARTS == 1 {
             Traits::Csr::xip().set(Traits::Csr::XIP_XSIP);
             Ok(())
         } else if num < INTERRUPT_PLATFORM_START {
@@ -1045,7 +1434,10 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<(), ClearInterruptLineError> {
-        if num == INTERRUPT_SOFTWARE {
+        if Traits::USE_CLIC_MODE {
+            // Safety: We are delegating the call in the intended way
+            unsafe { <Traits as InterruptController>::clear_interrupt_line(num) }
+        } else if num == INTERRUPT_SOFTWARE && Traits::NUM_HARTS == 1 {
             Traits::Csr::xip().clear(Traits::Csr::XIP_XSIP);
             Ok(())
         } else if num < INTERRUPT_PLATFORM_START {
@@ -1061,7 +1453,7 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<bool, QueryInterruptLineError> {
-        if num < INTERRUPT_PLATFORM_START {
+        if num < INTERRUPT_PLATFORM_START && !Traits::USE_CLIC_MODE {
             Ok((Traits::Csr::xip().read() & (Traits::Csr::XIP_XSIP << (num * 4))) != 0)
         } else {
             // Safety: We are delegating the call in the intended way
@@ -1082,7 +1474,7 @@ impl State {
     /// Implements [`crate::EntryPoint::exception_handler`].
     #[naked]
     pub unsafe extern "C" fn exception_handler<Traits: PortInstance>() -> ! {
-        const FRAME_SIZE: usize = if cfg!(target_feature = "f") {
+        const FRAME_SIZE: usize = if cfg!(any(target_feature = "f", target_feature = "v")) {
             // [background_sp, xstatus]
             X_SIZE * 2
         } else {
@@ -1103,6 +1495,40 @@ impl State {
                 # Align the handler to a 4-byte boundary
                 .align 2
 
+                # If the background context is in U-mode, make sure `sp` points
+                # to the running task's stack before pushing anything to it. The
+                # task controls `sp`, so it might point to the kernel's memory.
+                # An invalid `sp` is replaced with the bottom of the task's
+                # usable stack, below which the stack guard has room for the
+                # context state. `mscratch` is used as a scratch register
+                # because all general-purpose registers belong to the
+                # background context.
+                #
+                #   if mstatus.MPP == U:
+                #       if sp % 16 != 0 || !(USER_SP_MIN..=USER_SP_MAX).contains(sp):
+                #           sp = USER_SP_MIN;
+                #
+                .if /*{USE_PMP}*/
+                    csrw mscratch, a0
+                    csrr a0, mstatus
+                    srli a0, a0, {MPP_SHIFT}
+                    andi a0, a0, 3
+                    bnez a0, 6f
+                    andi a0, sp, 15
+                    bnez a0, 7f
+                    la a0, {USER_SP_MIN}
+                    LOAD a0, (a0)
+                    bltu sp, a0, 7f
+                    la a0, {USER_SP_MAX}
+                    LOAD a0, (a0)
+                    bgeu a0, sp, 6f
+                7:
+                    la a0, {USER_SP_MIN}
+                    LOAD sp, (a0)
+                6:
+                    csrr a0, mscratch
+                .endif
+
                 # Skip the stacking of FLS if the background context is the idle
                 # task.
                 #
@@ -1111,7 +1537,7 @@ impl State {
                 #   if sp == 0:
                 #       xstatus_part = 0;
                 #       <background context ∈ [idle task], a2 == xstatus_part>
-                #       INTERRUPT_NESTING += 1;
+                #       hart_state.interrupt_nesting += 1;
                 #       goto SwitchToMainStack;
                 #
                 beqz sp, 3f     # → EntryFromIdleTask
@@ -1139,12 +1565,16 @@ impl State {
                 STORE a1, ({X_SIZE} * 5)(sp)
                                                 # Increment the nesting count.
                                                 #
-                                                #   <INTERRUPT_NESTING ≥ -1>
-                                                #   INTERRUPT_NESTING += 1;
-                                                #   <INTERRUPT_NESTING ≥ 0>
+                                                #   <interrupt_nesting ≥ -1>
+                                                #   interrupt_nesting += 1;
+                                                #   <interrupt_nesting ≥ 0>
                                                 #
-                                                la a1, {INTERRUPT_NESTING}
-                                                lw a0, (a1)
+                                                .if /*{SMP}*/
+                                                    csrr a1, mscratch
+                                                .else
+                                                    la a1, {PRIMARY_HART_STATE}
+                                                .endif
+                                                lw a0, {HS_INTERRUPT_NESTING}(a1)
                 STORE a2, ({X_SIZE} * 6)(sp)
                 csrr a2, " crate::threading::imp::csr::csrexpr!(XEPC) "
                 STORE a3, ({X_SIZE} * 7)(sp)
@@ -1156,12 +1586,40 @@ impl State {
                 STORE t4, ({X_SIZE} * 13)(sp)
                 STORE t5, ({X_SIZE} * 14)(sp)
                 STORE t6, ({X_SIZE} * 15)(sp)
+
+                # If the background context is in U-mode, set `pc[0]` to
+                # remember that. `xepc[0]` is always zero, so this bit is
+                # otherwise unused. [tag:riscv_pmp_pc_bit0]
+                #
+                #   if mstatus.MPP == U:
+                #       pc[0] = 1;
+                #       tp = 0;
+                #
+                .if /*{USE_PMP}*/
+                    csrr a3, mstatus
+                    srli a3, a3, {MPP_SHIFT}
+                    andi a3, a3, 3
+                    bnez a3, 5f
+                    ori a2, a2, 1
+                    mv tp, zero
+                5:
+                .endif
+
                 STORE a2, ({X_SIZE} * 16)(sp)
-            "   if cfg!(target_feature = "f") {                                     "
+            "   if cfg!(any(target_feature = "f", target_feature = "v")) {          "
                     csrr a2, " crate::threading::imp::csr::csrexpr!(XSTATUS) "
+            "   }                                                                   "
+            "   if cfg!(target_feature = "v") {                                     "
+                    # Disable the vector unit while running the trap handler.
+                    # The background context's `xstatus.VS` is restored from
+                    # `xstatus_part` on return.
+                    li a3, {VS}
+                    csrc " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a3
+            "   } else {                                                            "
+                    # unused: {VS}
             "   }                                                                   "
                                                 addi a0, a0, 1
-                                                sw a0, (a1)
+                                                sw a0, {HS_INTERRUPT_NESTING}(a1)
 
             "   if cfg!(target_feature = "f") {                                     "
                     # If FP registers are in use, push FLS.F to the background
@@ -1220,10 +1678,10 @@ impl State {
                 # have to switch stacks. However, we still need to re-align
                 # `sp`.
                 #
-                # Note: The minimum value of `INTERRUPT_NESTING` is `-1`. Thus
+                # Note: The minimum value of `interrupt_nesting` is `-1`. Thus
                 # at this point, the minimum value we expect to see is `0`.
                 #
-                #   if INTERRUPT_NESTING > 0:
+                #   if interrupt_nesting > 0:
                 #       <background context ∈ [interrupt]>
                 #       goto RealignStack;
                 #   else:
@@ -1234,18 +1692,24 @@ impl State {
 
             4:      # SwitchToMainStack
                 # If the background context is a task context, we should switch
-                # to `MAIN_STACK`. Meanwhile, push the original `sp` to
-                # `MAIN_STACK`.
+                # to the main stack. Meanwhile, push the original `sp` to
+                # the main stack.
                 #
-                #   <INTERRUPT_NESTING == 0, background context ∈ [task, idle task],
+                #   <interrupt_nesting == 0, background context ∈ [task, idle task],
                 #    a2 == xstatus_part>
-                #   *(MAIN_STACK - ceil(FRAME_SIZE, 16)) = sp;
-                #   sp = MAIN_STACK - ceil(FRAME_SIZE, 16);
+                #   let main_stack = hart_state.main_stack;
+                #   *(main_stack - ceil(FRAME_SIZE, 16)) = sp;
+                #   sp = main_stack - ceil(FRAME_SIZE, 16);
                 #   <sp[0] == background_sp, sp & 15 == 0, sp != 0,
                 #    a0 == background_sp, a2 == xstatus_part>
                 #
                 mv a0, sp
-                LOAD sp, ({MAIN_STACK})
+                .if /*{SMP}*/
+                    csrr a1, mscratch
+                .else
+                    la a1, {PRIMARY_HART_STATE}
+                .endif
+                LOAD sp, {HS_MAIN_STACK}(a1)
                 addi sp, sp, -(({FRAME_SIZE} + 15) / 16 * 16)
                 STORE a0, (sp)
 
@@ -1263,7 +1727,7 @@ impl State {
                 # (applicable to RV32E), where `sp` is only required to be
                 # aligned to a word boundary.
                 #
-                #   <INTERRUPT_NESTING > 0, background context ∈ [interrupt],
+                #   <interrupt_nesting > 0, background context ∈ [interrupt],
                 #    a2 == xstatus_part>
                 #   *((sp - FRAME_SIZE) & !15) = sp
                 #   sp = (sp - FRAME_SIZE) & !15
@@ -1276,7 +1740,7 @@ impl State {
                 STORE a0, (sp)
 
             1:      # RealignStackEnd
-            "   if cfg!(target_feature = "f") {                                     "
+            "   if cfg!(any(target_feature = "f", target_feature = "v")) {          "
                     # Save `xstatus_part`.
                     STORE a2, {X_SIZE}(sp)
             "   }                                                                   "
@@ -1311,6 +1775,13 @@ impl State {
                 j 2f
             1:
                 # If the cause is a software trap, call `handle_exception`
+                #
+                # In CLIC mode, `xcause` includes other fields (e.g., `xpil`)
+                # in addition to the exception code. Extract the exception
+                # code (`xcause[11:0]`), which is sufficient to represent all
+                # standard exception codes in either mode.
+                slli a1, a1, {X_SIZE} * 8 - 12
+                srli a1, a1, {X_SIZE} * 8 - 12
             "   if cfg!(target_feature = "f") {                                     "
                     #
                     #   <a0 == background_sp, a1 == xcause, a2 = xstatus_part>
@@ -1324,6 +1795,28 @@ impl State {
             "   } else {                                                            "
                     # unused: {FS_1_SHIFT}
             "   }                                                                   "
+
+                # If the background context is in U-mode, let
+                # `pmp::imp::handle_exception` handle the exception
+                #
+                #   <a0 == background_flsx, a1 == xcause>
+                #   if handle_exception_pmp(a0, a1, sp):
+                #       goto 2;
+                #   <a0 == background_flsx, a1 == xcause>
+                #
+                .if /*{USE_PMP}*/
+                    mv a2, sp
+                    addi sp, sp, -16
+                    STORE a0, (sp)
+                    STORE a1, {X_SIZE}(sp)
+                    call {handle_exception_pmp}
+                    mv a2, a0
+                    LOAD a0, (sp)
+                    LOAD a1, {X_SIZE}(sp)
+                    addi sp, sp, 16
+                    bnez a2, 2f
+                .endif
+
                 #
                 #   <a0 == background_flsx, a1 == xcause>
                 #   handle_exception(a0, a1);
@@ -1333,14 +1826,18 @@ impl State {
 
                                             # Decrement the nesting count.
                                             #
-                                            #   <INTERRUPT_NESTING ≥ 0>
-                                            #   INTERRUPT_NESTING -= 1;
-                                            #   <INTERRUPT_NESTING ≥ -1>
+                                            #   <interrupt_nesting ≥ 0>
+                                            #   interrupt_nesting -= 1;
+                                            #   <interrupt_nesting ≥ -1>
                                             #
-                                            la a2, {INTERRUPT_NESTING}
-                                            lw a1, (a2)
-
-            "   if cfg!(target_feature = "f") {                                     "
+                                            .if /*{SMP}*/
+                                                csrr a2, mscratch
+                                            .else
+                                                la a2, {PRIMARY_HART_STATE}
+                                            .endif
+                                            lw a1, {HS_INTERRUPT_NESTING}(a2)
+
+            "   if cfg!(any(target_feature = "f", target_feature = "v")) {          "
                     # Restore `xstatus_part`
                     LOAD a0, {X_SIZE}(sp)
             "   }                                                                   "
@@ -1349,7 +1846,7 @@ impl State {
                 LOAD sp, (sp)
 
                                             addi a1, a1, -1
-                                            sw a1, (a2)
+                                            sw a1, {HS_INTERRUPT_NESTING}(a2)
 
                 # Are we returning to an interrupt context?
                 #
@@ -1357,8 +1854,8 @@ impl State {
                 # next task to dispatch is unnecessary, so we can jump straight
                 # to `pop_first_level_state`.
                 #
-                #   <INTERRUPT_NESTING ≥ 0>
-                #   if INTERRUPT_NESTING > 0:
+                #   <interrupt_nesting ≥ 0>
+                #   if interrupt_nesting > 0:
                 #       goto pop_first_level_state;
                 #
                 bgez a1, 2f
@@ -1373,21 +1870,35 @@ impl State {
             3:      # EntryFromIdleTask
                 # Increment the nesting count.
                 #
-                #   <INTERRUPT_NESTING == -1, background context ∈ [idle task]>
-                #   INTERRUPT_NESTING += 1;
-                #   <INTERRUPT_NESTING == 0>
+                #   <interrupt_nesting == -1, background context ∈ [idle task]>
+                #   interrupt_nesting += 1;
+                #   <interrupt_nesting == 0>
                 #
-                sw x0, ({INTERRUPT_NESTING}), a1
+                .if /*{SMP}*/
+                    csrr a1, mscratch
+                .else
+                    la a1, {PRIMARY_HART_STATE}
+                .endif
+                sw x0, {HS_INTERRUPT_NESTING}(a1)
                 mv a2, x0
+            "   if cfg!(target_feature = "v") {                                     "
+                    li a1, {VS}
+                    csrc " crate::threading::imp::csr::csrexpr!(XSTATUS) ", a1
+            "   }                                                                   "
                 j 4b        # → SwitchToMainStack
                 ",
                 handle_interrupt = sym Self::handle_interrupt::<Traits>,
                 handle_exception = sym instemu::handle_exception,
+                handle_exception_pmp = sym pmp::imp::handle_exception::<Traits>,
+                USER_SP_MIN = sym pmp::imp::USER_SP_MIN,
+                USER_SP_MAX = sym pmp::imp::USER_SP_MAX,
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
-                INTERRUPT_NESTING = sym INTERRUPT_NESTING,
                 RESERVATION_ADDR_VALUE = sym instemu::RESERVATION_ADDR_VALUE,
-                MAIN_STACK = sym MAIN_STACK,
+                PRIMARY_HART_STATE = sym PRIMARY_HART_STATE,
+                HS_MAIN_STACK = const HS_MAIN_STACK,
+                HS_INTERRUPT_NESTING = const HS_INTERRUPT_NESTING,
+                SMP = sym <<Traits as PortInstance>::Smp as csr::Num>::value,
                 X_SIZE = const X_SIZE,
                 F_SIZE = const F_SIZE,
                 FLSF_SIZE = const FLSF_SIZE,
@@ -1395,16 +1906,31 @@ impl State {
                 PRIV = sym <<Traits as PortInstance>::Priv as csr::Num>::value,
                 FS_1 = const csr::XSTATUS_FS_1,
                 FS_1_SHIFT = const csr::XSTATUS_FS_1.trailing_zeros(),
+                VS = const csr::XSTATUS_VS,
+                MPP_SHIFT = const csr::XSTATUS_MPP_M.trailing_zeros(),
+                USE_PMP = sym <<Traits as PortInstance>::UsePmp as csr::Num>::value,
                 options(noreturn)
             );
         }
     }
 
     unsafe fn handle_interrupt<Traits: PortInstance>() {
+        if Traits::USE_CLIC_MODE {
+            // Safety: Upheld by the caller
+            unsafe { Self::handle_interrupt_clic::<Traits>() };
+            return;
+        }
+
+        // The harts other than hart 0 only take inter-processor interrupts
+        // [ref:riscv_secondary_hart_interrupts]
+        let is_primary_hart = current_hart::<Traits>() == 0;
+        let use_interrupt_timer = Traits::USE_INTERRUPT_TIMER && is_primary_hart;
+        let use_interrupt_external = Traits::USE_INTERRUPT_EXTERNAL && is_primary_hart;
+
         let all_local_interrupts = [0, Traits::Csr::XIE_XSIE]
             [Traits::USE_INTERRUPT_SOFTWARE as usize]
-            | [0, Traits::Csr::XIE_XTIE][Traits::USE_INTERRUPT_TIMER as usize]
-            | [0, Traits::Csr::XIE_XEIE][Traits::USE_INTERRUPT_EXTERNAL as usize];
+            | [0, Traits::Csr::XIE_XTIE][use_interrupt_timer as usize]
+            | [0, Traits::Csr::XIE_XEIE][use_interrupt_external as usize];
 
         // `M[EST]IE` is used to simulate execution priority levels.
         //
@@ -1462,7 +1988,7 @@ impl State {
 
         // Check the pending flags and call the respective handlers in the
         // descending order of priority.
-        if Traits::USE_INTERRUPT_EXTERNAL && (old_mie & Traits::Csr::XIE_XEIE) != 0 {
+        if use_interrupt_external && (old_mie & Traits::Csr::XIE_XEIE) != 0 {
             // Safety: `USE_INTERRUPT_EXTERNAL == true`
             let handler = Traits::INTERRUPT_EXTERNAL_HANDLER
                 .unwrap_or_else(|| unsafe { unreachable_unchecked() });
@@ -1479,11 +2005,7 @@ impl State {
         }
 
         if Traits::USE_INTERRUPT_SOFTWARE && (old_mie & Traits::Csr::XIE_XSIE) != 0 {
-            // Safety: `USE_INTERRUPT_SOFTWARE == true`
-            let handler = Traits::INTERRUPT_SOFTWARE_HANDLER
-                .unwrap_or_else(|| unsafe { unreachable_unchecked() });
-
-            if Traits::USE_INTERRUPT_EXTERNAL {
+            if use_interrupt_external {
                 debug_assert_eq!(xie_pending, Traits::Csr::XIE_XEIE);
                 Traits::Csr::xie().set(Traits::Csr::XIE_XEIE);
             } else {
@@ -1491,9 +2013,20 @@ impl State {
             }
 
             while (xip & Traits::Csr::XIP_XSIP) != 0 {
-                // Safety: The first-level interrupt handler is allowed to call
-                //         a second-level interrupt handler
-                unsafe { handler() };
+                if Traits::NUM_HARTS > 1 {
+                    // Safety: An interrupt context, CPU Lock inactive
+                    unsafe { Self::handle_ipi::<Traits>() };
+                } else {
+                    // Safety: `USE_INTERRUPT_SOFTWARE == true`, and the
+                    // software interrupt isn't used for inter-processor
+                    // interrupts
+                    let handler = Traits::INTERRUPT_SOFTWARE_HANDLER
+                        .unwrap_or_else(|| unsafe { unreachable_unchecked() });
+
+                    // Safety: The first-level interrupt handler is allowed to
+                    //         call a second-level interrupt handler
+                    unsafe { handler() };
+                }
 
                 xip = Traits::Csr::xip().read();
             }
@@ -1501,7 +2034,7 @@ impl State {
             xie_pending = Traits::Csr::XIE_XSIE;
         }
 
-        if Traits::USE_INTERRUPT_TIMER && (old_mie & Traits::Csr::XIE_XTIE) != 0 {
+        if use_interrupt_timer && (old_mie & Traits::Csr::XIE_XTIE) != 0 {
             // Safety: `USE_INTERRUPT_TIMER == true`
             let handler = Traits::INTERRUPT_TIMER_HANDLER
                 .unwrap_or_else(|| unsafe { unreachable_unchecked() });
@@ -1509,7 +2042,7 @@ impl State {
             if Traits::USE_INTERRUPT_SOFTWARE {
                 debug_assert_eq!(xie_pending, Traits::Csr::XIE_XSIE);
                 Traits::Csr::xie().set(Traits::Csr::XIE_XSIE);
-            } else if Traits::USE_INTERRUPT_EXTERNAL {
+            } else if use_interrupt_external {
                 debug_assert_eq!(xie_pending, Traits::Csr::XIE_XEIE);
                 Traits::Csr::xie().set(Traits::Csr::XIE_XEIE);
             } else {
@@ -1533,7 +2066,99 @@ impl State {
         debug_assert_ne!(xie_pending, 0);
         Traits::Csr::xie().set(xie_pending);
     }
+
+    /// The CLIC mode counterpart of [`Self::handle_interrupt`].
+    #[inline]
+    unsafe fn handle_interrupt_clic<Traits: PortInstance>() {
+        // In CLIC mode, the hardware raises the current interrupt level to
+        // the taken interrupt's level, saving the previous one to
+        // `xcause.xpil`. Nested interrupts are preempted by the hardware based
+        // on their levels, so all we have to do is to re-enable interrupts
+        // globally while the handler is running.
+        //
+        // A nested trap overwrites `xcause`, so save it here and restore it
+        // before returning so that `xret` restores the correct interrupt
+        // level. This also ensures `xcause.xpil == 0` when we are returning to
+        // a task, which `pop_first_level_state` relies on when it's switching
+        // to another task.
+        let xcause = Traits::Csr::xcause().read();
+
+        // Safety: We are the port, so it's okay to call this
+        let num = unsafe {
+            <Traits as InterruptController>::acknowledge_clic_interrupt(
+                xcause & csr::XCAUSE_CLIC_EXCCODE_MASK,
+            )
+        };
+
+        if let Some(handler) = num.and_then(|num| Traits::INTERRUPT_HANDLERS.get(num)) {
+            // Re-enable interrupts globally.
+            Traits::Csr::xstatus_set_xie();
+
+            // Safety: The first-level interrupt handler is allowed to call
+            //         a second-level interrupt handler
+            unsafe { handler() };
+
+            // Disable interrupts globally before returning.
+            Traits::Csr::xstatus_clear_xie();
+
+            Traits::Csr::xcause().write(xcause);
+        }
+    }
+}
+
+/// The trap vector for CLIC mode, which is installed to `xtvec` by
+/// [`State::port_boot`] if [`InterruptController::USE_CLIC_MODE`] is `true`.
+///
+/// In CLIC mode, the trap vector base address must be aligned to a 64-byte
+/// boundary. Because `#[repr(align(_))]` doesn't work on
+/// [`State::exception_handler`] (see the comment in it), this function is
+/// defined as a free function that jumps to it.
+#[naked]
+#[repr(align(64))]
+unsafe extern "C" fn clic_trap_vector<Traits: PortInstance>() -> ! {
+    unsafe {
+        asm!("
+            j {exception_handler}
+            ",
+            exception_handler = sym State::exception_handler::<Traits>,
+            options(noreturn),
+        );
+    }
 }
 
 /// Used by `use_port!`
-pub const fn validate<Traits: PortInstance>() {}
+pub const fn validate<Traits: PortInstance>() {
+    assert!(
+        !Traits::USE_PMP || Traits::PRIVILEGE_LEVEL == PRIVILEGE_LEVEL_MACHINE,
+        "`USE_PMP` requires `PRIVILEGE_LEVEL_MACHINE`"
+    );
+    assert!(
+        !Traits::USE_PMP || !Traits::USE_CLIC_MODE,
+        "`USE_PMP` is not supported in CLIC mode"
+    );
+    assert!(Traits::NUM_HARTS >= 1, "`NUM_HARTS` must be at least `1`");
+    if Traits::NUM_HARTS > 1 {
+        assert!(
+            Traits::PRIVILEGE_LEVEL == PRIVILEGE_LEVEL_MACHINE,
+            "`NUM_HARTS > 1` requires `PRIVILEGE_LEVEL_MACHINE`"
+        );
+        assert!(!Traits::USE_PMP, "`NUM_HARTS > 1` doesn't support `USE_PMP`");
+        assert!(
+            !Traits::USE_CLIC_MODE,
+            "`NUM_HARTS > 1` is not supported in CLIC mode"
+        );
+        assert!(
+            !cfg!(target_feature = "v"),
+            "`NUM_HARTS > 1` doesn't support the \"V\" extension"
+        );
+        assert!(
+            !cfg!(feature = "emulate-lr-sc"),
+            "`NUM_HARTS > 1` doesn't support `emulate-lr-sc`"
+        );
+        assert!(
+            Traits::INTERRUPT_SOFTWARE_HANDLER.is_none(),
+            "`NUM_HARTS > 1` uses the software interrupt for inter-processor \
+            interrupts, so it can't have an interrupt handler"
+        );
+    }
+}
@@ -76,6 +76,7 @@ pub trait CsrAccessor: Copy {
     const NUM: usize;
 
     fn read(&self) -> usize;
+    fn write(&self, value: usize);
     fn set(&self, value: usize);
     fn set_i<const VALUE: usize>(&self);
     fn clear(&self, value: usize);
@@ -94,6 +95,11 @@ impl<const NUM: usize> CsrAccessor for Csr<NUM> {
         read
     }
 
+    #[inline(always)]
+    fn write(&self, value: usize) {
+        unsafe { asm!("csrw {NUM}, {value}", NUM = const NUM, value = in(reg) value) };
+    }
+
     #[inline(always)]
     fn set(&self, value: usize) {
         unsafe { asm!("csrs {NUM}, {value}", NUM = const NUM, value = in(reg) value) };
@@ -262,9 +268,15 @@ pub const XSTATUS_MPP_M: usize = 0b11 << 11;
 pub const XSTATUS_SPP_S: usize = 1 << 8;
 pub const XSTATUS_FS_0: usize = 1 << 13;
 pub const XSTATUS_FS_1: usize = 1 << 14;
+pub const XSTATUS_VS_0: usize = 1 << 9;
+pub const XSTATUS_VS_1: usize = 1 << 10;
+pub const XSTATUS_VS: usize = XSTATUS_VS_0 | XSTATUS_VS_1;
 
 pub const XCAUSE_INTERRUPT: usize = usize::MAX - usize::MAX / 2;
 pub const XCAUSE_EXCEPTIONCODE_MASK: usize = usize::MAX / 2;
+pub const XCAUSE_CLIC_EXCCODE_MASK: usize = 0xfff;
+
+pub const XTVEC_MODE_CLIC: usize = 0b11;
 
 #[macropol::macropol(concat = "concat!($parts_comma_sep)")]
 define_set! {
@@ -279,6 +291,7 @@ define_set! {
         // CSRs
         (XSTATUS) => { "$PRIV * 0x100" },
         (XIE) => { "$PRIV * 0x100 + 0x04" },
+        (XTVEC) => { "$PRIV * 0x100 + 0x05" },
         (XEPC) => { "$PRIV * 0x100 + 0x41" },
         (XCAUSE) => { "$PRIV * 0x100 + 0x42" },
         (XIP) => { "$PRIV * 0x100 + 0x44" },
@@ -313,6 +326,10 @@ define_set! {
         /// `λie` (Machine/Supervisor/... Interrupt Enable)
         fn xie() -> Csr<{ Self::XIE }>;
 
+        #[csr_accessor(ty = Xtvec)]
+        /// `λtvec` (Machine/Supervisor/... Trap-Vector Base-Address Register)
+        fn xtvec() -> Csr<{ Self::XTVEC }>;
+
         #[csr_accessor(ty = Xcause)]
         /// `λcause` (Machine/Supervisor/... Cause Register)
         fn xcause() -> Csr<{ Self::XCAUSE }>;
@@ -21,12 +21,22 @@ fn main() {
         }
     };
 
+    println!("cargo:rerun-if-env-changed=CARGO_FEATURE_SMP");
+    let smp = env::var_os("CARGO_FEATURE_SMP").is_some();
+
     if let Some(name) = selected_test.strip_prefix("kernel_tests::") {
+        // Multiprocessor tests run on two harts if supported by the target
+        let num_harts = if smp && name.starts_with("smp_") {
+            "num_harts: 2,"
+        } else {
+            ""
+        };
         writeln!(
             generated_code,
             r#"
             instantiate_test!({{
                 path: crate::driver_kernel_tests::{name},
+                {num_harts}
             }},);
             "#,
         )
@@ -0,0 +1,140 @@
+//! Checks that activating a task preempts a lower-priority task running on
+//! another hart. This exercises the inter-processor interrupts
+//! (`ThreadingOptions::MSIP_PTR`).
+//!
+//! 1. `task1` (pinned to the hart 0) and `task2` (pinned to the hart 1) start
+//!    running. `task2` busy-waits until `DONE` is set.
+//!
+//! 2. `task1` activates `task3`, which has a higher priority and is pinned to
+//!    the hart 1. `task3` can only run by preempting `task2`, which never
+//!    yields the hart by itself. `task1` keeps running on the hart 0 and
+//!    busy-waits until `DONE` is set.
+//!
+//! 3. `task3` sets `DONE` and exits. `task1` completes the test.
+//!
+//! Without the `smp` feature, the system only has one hart, and this test
+//! does nothing.
+use r3::kernel::{traits, Cfg, StaticTask};
+use r3_test_suite::kernel_tests::Driver;
+
+pub trait SupportedSystem: traits::KernelBase + traits::KernelStatic {}
+impl<T: traits::KernelBase + traits::KernelStatic> SupportedSystem for T {}
+
+#[cfg(feature = "smp")]
+pub struct App<System: SupportedSystem> {
+    task3: StaticTask<System>,
+}
+
+#[cfg(feature = "smp")]
+impl<System: SupportedSystem> App<System> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System>,
+    {
+        use r3::kernel::task::CpuAffinity;
+
+        StaticTask::define()
+            .start(smp::task1_body::<System, D>)
+            .priority(2)
+            .active(true)
+            .affinity(CpuAffinity::only(0))
+            .finish(b);
+        StaticTask::define()
+            .start(smp::task2_body::<System, D>)
+            .priority(2)
+            .active(true)
+            .affinity(CpuAffinity::only(1))
+            .finish(b);
+        let task3 = StaticTask::define()
+            .start(smp::task3_body::<System, D>)
+            .priority(1)
+            .affinity(CpuAffinity::only(1))
+            .finish(b);
+
+        App { task3 }
+    }
+}
+
+#[cfg(feature = "smp")]
+mod smp {
+    use super::*;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use r3::kernel::prelude::*;
+
+    static TASK2_STARTED: AtomicBool = AtomicBool::new(false);
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    pub(super) fn task1_body<System: SupportedSystem, D: Driver<App<System>>>() {
+        assert_eq!(current_hart::<System>(), 0);
+
+        while !TASK2_STARTED.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+
+        // `task3` preempts `task2` on the hart 1
+        D::app().task3.activate().unwrap();
+
+        while !DONE.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+
+        assert_eq!(current_hart::<System>(), 0);
+        D::success();
+    }
+
+    pub(super) fn task2_body<System: SupportedSystem, D: Driver<App<System>>>() {
+        assert_eq!(current_hart::<System>(), 1);
+
+        TASK2_STARTED.store(true, Ordering::Relaxed);
+
+        while !DONE.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+    }
+
+    pub(super) fn task3_body<System: SupportedSystem, D: Driver<App<System>>>() {
+        assert_eq!(current_hart::<System>(), 1);
+
+        DONE.store(true, Ordering::Relaxed);
+    }
+
+    /// Get the current hart. CPU Lock prevents the calling task from
+    /// migrating to another hart during the call. (The multiprocessor mode
+    /// requires M-mode, so `mhartid` is accessible.)
+    fn current_hart<System: SupportedSystem>() -> usize {
+        System::acquire_cpu_lock().unwrap();
+        let hart: usize;
+        unsafe { core::arch::asm!("csrr {}, mhartid", out(reg) hart) };
+        unsafe { System::release_cpu_lock().unwrap() };
+        hart
+    }
+}
+
+#[cfg(not(feature = "smp"))]
+pub struct App<System> {
+    _phantom: core::marker::PhantomData<System>,
+}
+
+#[cfg(not(feature = "smp"))]
+impl<System: SupportedSystem> App<System> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System>,
+    {
+        StaticTask::define()
+            .start(skipped_task_body::<System, D>)
+            .priority(0)
+            .active(true)
+            .finish(b);
+
+        App {
+            _phantom: core::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(not(feature = "smp"))]
+fn skipped_task_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    log::warn!("the target doesn't support multiprocessing; skipping the test");
+    D::success();
+}
@@ -0,0 +1,207 @@
+//! Checks that the vector registers and CSRs (`vl` and `vtype`) of tasks that
+//! opted in to the extended context ([`TaskDefiner::extended_context`]) are
+//! preserved across context switches.
+//!
+//! 1. (`SEQ`: 0 → 1) `task2` fills the vector registers with `0x40..0x60` and
+//!    sets `vl = 2`. `task2` parks itself, and `task1` starts running.
+//!
+//! 2. (`SEQ`: 1 → 2) `task1` fills the vector registers with `0x10..0x30` and
+//!    sets `vl = 3`. `task1` unparks `task2`, which has a higher priority.
+//!
+//! 3. (`SEQ`: 2 → 3) `task2` checks its vector context, which has to be
+//!    restored from memory because `task1` overwrote the registers. `task2`
+//!    unparks `task3`, which doesn't use the extended context and parks
+//!    itself immediately.
+//!
+//! 4. (`SEQ`: 3 → 4) `task2` checks its vector context again, which this time
+//!    is still in the registers. `task2` parks itself.
+//!
+//! 5. (`SEQ`: 4 → 5) `task1` checks its vector context and completes the test.
+//!
+//! This test relies on the compiler not generating vector instructions, which
+//! would clobber the vector registers between the steps. If the "V" extension
+//! is disabled, only the task sequence is checked.
+//!
+//! [`TaskDefiner::extended_context`]: r3::kernel::task::TaskDefiner::extended_context
+use core::sync::atomic::{AtomicUsize, Ordering};
+use r3::kernel::{prelude::*, traits, Cfg, StaticTask};
+use r3_test_suite::kernel_tests::Driver;
+
+pub trait SupportedSystem: traits::KernelBase + traits::KernelStatic {}
+impl<T: traits::KernelBase + traits::KernelStatic> SupportedSystem for T {}
+
+static SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// Assert that `SEQ == old` and replace it with `new`.
+fn expect_and_replace(old: usize, new: usize) {
+    assert_eq!(SEQ.load(Ordering::Relaxed), old);
+    SEQ.store(new, Ordering::Relaxed);
+}
+
+pub struct App<System: SupportedSystem> {
+    task2: StaticTask<System>,
+    task3: StaticTask<System>,
+}
+
+impl<System: SupportedSystem> App<System> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System>,
+    {
+        StaticTask::define()
+            .start(task1_body::<System, D>)
+            .priority(2)
+            .active(true)
+            .extended_context(true)
+            .finish(b);
+        let task2 = StaticTask::define()
+            .start(task2_body::<System, D>)
+            .priority(1)
+            .active(true)
+            .extended_context(true)
+            .finish(b);
+        let task3 = StaticTask::define()
+            .start(task3_body::<System, D>)
+            .priority(0)
+            .active(true)
+            .finish(b);
+
+        App { task2, task3 }
+    }
+}
+
+fn task1_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    expect_and_replace(1, 2);
+
+    fill(0x10, 3);
+
+    // Switches to `task2`
+    D::app().task2.unpark_exact().unwrap();
+
+    expect_and_replace(4, 5);
+
+    check(0x10, 3);
+
+    D::success();
+}
+
+fn task2_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    if !cfg!(target_feature = "v") {
+        log::warn!("The 'V' extension is disabled, only checking the task sequence");
+    }
+
+    expect_and_replace(0, 1);
+
+    fill(0x40, 2);
+
+    System::park().unwrap(); // blocks, switching to `task1`
+
+    expect_and_replace(2, 3);
+
+    check(0x40, 2);
+
+    // Switches to `task3` and back
+    D::app().task3.unpark_exact().unwrap();
+
+    expect_and_replace(3, 4);
+
+    check(0x40, 2);
+
+    System::park().unwrap(); // blocks, switching to `task1`
+
+    unreachable!();
+}
+
+fn task3_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    loop {
+        System::park().unwrap();
+    }
+}
+
+/// The maximum value of `vlenb` supported by this test
+#[cfg(target_feature = "v")]
+const MAX_VLENB: usize = 64;
+
+/// The expected value of `vtype` after [`fill`] (`e32, m1, ta, ma`)
+#[cfg(target_feature = "v")]
+const VTYPE: usize = 0xd0;
+
+/// Fill the byte elements of `v{i}` with `seed + i` and set `vl = avl` with
+/// `vtype = `[`VTYPE`].
+#[cfg(target_feature = "v")]
+#[inline(never)]
+fn fill(seed: u8, avl: usize) {
+    log::trace!("fill({seed:#x}, {avl})");
+    // The vector registers aren't listed as clobbered; leaving them modified
+    // is the whole point of this function.
+    unsafe {
+        core::arch::asm!(
+            "
+                vsetvli {tmp}, zero, e8, m1, ta, ma
+                .irp n, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31
+                    vmv.v.x v\\n, {seed}
+                    addi {seed}, {seed}, 1
+                .endr
+                vsetvli zero, {avl}, e32, m1, ta, ma
+            ",
+            seed = inout(reg) seed as usize => _,
+            avl = in(reg) avl,
+            tmp = out(reg) _,
+            options(nostack),
+        );
+    }
+}
+
+/// Check the vector context set up by [`fill`].
+#[cfg(target_feature = "v")]
+#[inline(never)]
+fn check(seed: u8, avl: usize) {
+    log::trace!("check({seed:#x}, {avl})");
+
+    static mut DUMP: [u8; 32 * MAX_VLENB] = [0; 32 * MAX_VLENB];
+
+    let vlenb: usize;
+    unsafe { core::arch::asm!("csrr {}, vlenb", out(reg) vlenb, options(nomem, nostack)) };
+    assert!(vlenb <= MAX_VLENB, "vlenb = {vlenb} is too large");
+
+    let (vl, vtype): (usize, usize);
+    let dump = unsafe { &mut *core::ptr::addr_of_mut!(DUMP) };
+    unsafe {
+        core::arch::asm!(
+            "
+                csrr {vl}, vl
+                csrr {vtype}, vtype
+                slli {stride}, {vlenb}, 3
+                vs8r.v v0, ({ptr})
+                add {ptr}, {ptr}, {stride}
+                vs8r.v v8, ({ptr})
+                add {ptr}, {ptr}, {stride}
+                vs8r.v v16, ({ptr})
+                add {ptr}, {ptr}, {stride}
+                vs8r.v v24, ({ptr})
+            ",
+            vl = out(reg) vl,
+            vtype = out(reg) vtype,
+            vlenb = in(reg) vlenb,
+            stride = out(reg) _,
+            ptr = inout(reg) dump.as_mut_ptr() => _,
+            options(nostack),
+        );
+    }
+
+    assert_eq!(vl, avl, "vl");
+    assert_eq!(vtype, VTYPE, "vtype");
+
+    for (i, reg) in dump[..32 * vlenb].chunks_exact(vlenb).enumerate() {
+        let expected = seed.wrapping_add(i as u8);
+        if let Some(got) = reg.iter().find(|&&b| b != expected) {
+            panic!("v{i}: expected {expected:#x} in all elements, got {got:#x}");
+        }
+    }
+}
+
+#[cfg(not(target_feature = "v"))]
+fn fill(_seed: u8, _avl: usize) {}
+
+#[cfg(not(target_feature = "v"))]
+fn check(_seed: u8, _avl: usize) {}
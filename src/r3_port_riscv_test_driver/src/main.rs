@@ -27,6 +27,9 @@ mod uart;
 #[cfg(feature = "output-k210-uart")]
 #[path = "uart_k210.rs"]
 mod uart;
+#[cfg(feature = "output-ns16550a-uart")]
+#[path = "uart_ns16550a.rs"]
+mod uart;
 
 #[cfg(feature = "interrupt-e310x")]
 mod interrupt_e310x;
@@ -37,11 +40,20 @@ mod e310x;
 mod k210;
 #[cfg(feature = "board-u540-qemu")]
 mod u540;
+#[cfg(feature = "board-virt-qemu")]
+mod virt;
 
 #[allow(unused_macros)]
 macro_rules! instantiate_test {
     // If a test case is specified, instantiate the test case
-    ({ path: $path:path, $($tt:tt)* }, $($excess:tt)*) => {
+    (
+        {
+            path: $path:path,
+            $( name_ident: $name_ident:ident, name_str: $name_str:expr, )?
+            $( num_harts: $num_harts:expr, )?
+        },
+        $($excess:tt)*
+    ) => {
         // Only one test case can be specified
         reject_excess!($($excess)*);
 
@@ -79,6 +91,14 @@ macro_rules! instantiate_test {
         impl port::ThreadingOptions for SystemTraits {
             #[cfg(feature = "boot-minimal-s")]
             const PRIVILEGE_LEVEL: u8 = port::PRIVILEGE_LEVEL_SUPERVISOR;
+
+            #[cfg(feature = "pmp")]
+            const USE_PMP: bool = true;
+
+            $( const NUM_HARTS: usize = $num_harts; )?
+
+            #[cfg(feature = "smp-virt-qemu")]
+            const MSIP_PTR: usize = 0x0200_0000;
         }
 
         #[cfg(feature = "boot-rt")]
@@ -132,6 +152,20 @@ macro_rules! instantiate_test {
             const CONTEXT: usize = 1;
         }
 
+        #[cfg(feature = "interrupt-virt-qemu")]
+        port::use_plic!(unsafe impl InterruptController for SystemTraits);
+        #[cfg(feature = "interrupt-virt-qemu")]
+        impl port::PlicOptions for SystemTraits {
+            const MAX_PRIORITY: InterruptPriority = 7;
+            const MAX_NUM: InterruptNum = 95;
+            const PLIC_BASE: usize = 0x0c00_0000;
+            // Hart 0, M-mode or S-mode
+            #[cfg(not(feature = "boot-minimal-s"))]
+            const CONTEXT: usize = 0;
+            #[cfg(feature = "boot-minimal-s")]
+            const CONTEXT: usize = 1;
+        }
+
         #[cfg(feature = "interrupt-k210")]
         port::use_plic!(unsafe impl InterruptController for SystemTraits);
         #[cfg(feature = "interrupt-k210")]
@@ -149,7 +183,8 @@ macro_rules! instantiate_test {
             #[cfg(any(
                 feature = "board-e310x-red-v",
                 feature = "board-e310x-qemu",
-                feature = "board-maix"
+                feature = "board-maix",
+                feature = "board-virt-qemu",
             ))]
             const MTIMECMP_PTR: usize = 0x0200_4000;
             #[cfg(feature = "board-u540-qemu")]
@@ -161,6 +196,8 @@ macro_rules! instantiate_test {
             const FREQUENCY: u64 = u540::MTIME_FREQUENCY;
             #[cfg(feature = "board-maix")]
             const FREQUENCY: u64 = k210::MTIME_FREQUENCY;
+            #[cfg(feature = "board-virt-qemu")]
+            const FREQUENCY: u64 = virt::MTIME_FREQUENCY;
 
             // Updating `mtime` is not supported by QEMU.
             const RESET_MTIME: bool = false;
@@ -170,6 +207,8 @@ macro_rules! instantiate_test {
         impl port::SbiTimerOptions for SystemTraits {
             #[cfg(feature = "board-u540-qemu")]
             const FREQUENCY: u64 = u540::MTIME_FREQUENCY;
+            #[cfg(feature = "board-virt-qemu")]
+            const FREQUENCY: u64 = virt::MTIME_FREQUENCY;
         }
 
         struct Driver;
@@ -223,6 +262,19 @@ macro_rules! instantiate_test {
                 crate::interrupt_e310x::INTERRUPT_GPIO1,
             ];
             const INTERRUPT_PRIORITIES: &'static [InterruptPriority] = &[6, 2];
+
+            // Code and data (see the linker script provided by the test
+            // runner)
+            #[cfg(feature = "pmp-u540-qemu")]
+            const UNPRIVILEGED_MEMORY_REGIONS:
+                &'static [(core::ops::Range<usize>, r3::kernel::task::MemoryAccess)] = &[
+                (0x8000_0000..0x8080_0000, r3::kernel::task::MemoryAccess::ReadExecute),
+                (0x8080_0000..0x8100_0000, r3::kernel::task::MemoryAccess::ReadWrite),
+            ];
+
+            // UART0 is inaccessible to unprivileged tasks
+            #[cfg(feature = "pmp-u540-qemu")]
+            const UNPRIVILEGED_FAULT_ADDRESS: Option<usize> = Some(0x1001_0000);
         }
 
         static COTTAGE: test_case::App<System> =
@@ -241,6 +293,9 @@ macro_rules! instantiate_test {
             #[cfg(feature = "interrupt-u540-qemu")]
             SystemTraits::configure_plic(b);
 
+            #[cfg(feature = "interrupt-virt-qemu")]
+            SystemTraits::configure_plic(b);
+
             #[cfg(feature = "interrupt-k210")]
             SystemTraits::configure_plic(b);
 
@@ -320,6 +375,8 @@ include!(concat!(env!("OUT_DIR"), "/gen.rs"));
 #[cfg(feature = "kernel_tests")]
 mod driver_kernel_tests {
     pub mod execute_lr_sc;
+    pub mod smp_preemption;
+    pub mod vector_context;
 }
 
 #[cfg(not(feature = "run"))]
@@ -0,0 +1,56 @@
+//! The UART driver compatible with NS16550A, which is found in QEMU `virt`
+//! machine (RISC-V VirtIO board).
+//!
+//! The machine has only one UART, so standard output and standard error share
+//! the same output.
+use core::fmt::{self, Write};
+
+/// The base address of UART0
+const UART0_BASE: usize = 0x1000_0000;
+
+/// Transmitter Holding Register
+const THR: usize = 0;
+/// Line Status Register
+const LSR: usize = 5;
+/// `LSR.THRE` (Transmitter Holding Register Empty)
+const LSR_THRE: u8 = 1 << 5;
+
+pub fn stdout_write_str(s: &str) {
+    crate::with_cpu_lock(|| {
+        let _ = SerialWrapper(UART0_BASE as *mut u8).write_str(s);
+    });
+}
+
+pub fn stdout_write_fmt(args: fmt::Arguments<'_>) {
+    crate::with_cpu_lock(|| {
+        let _ = SerialWrapper(UART0_BASE as *mut u8).write_fmt(args);
+    });
+}
+
+pub fn stderr_write_fmt(args: fmt::Arguments<'_>) {
+    stdout_write_fmt(args);
+}
+
+struct SerialWrapper(*mut u8);
+
+impl SerialWrapper {
+    fn write_u8(&self, x: u8) {
+        unsafe {
+            while self.0.wrapping_add(LSR).read_volatile() & LSR_THRE == 0 {}
+            self.0.wrapping_add(THR).write_volatile(x);
+        }
+    }
+}
+
+impl Write for SerialWrapper {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.as_bytes() {
+            if *byte == b'\n' {
+                self.write_u8(b'\r');
+            }
+
+            self.write_u8(*byte);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1 @@
+pub const MTIME_FREQUENCY: u64 = 10_000_000;
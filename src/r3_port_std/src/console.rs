@@ -0,0 +1,457 @@
+//! Host-side inspection console
+//!
+//! The console is a line-based text interface served by a host thread. It's
+//! enabled by the environment variable [`CONSOLE_ENV_VAR`] or
+//! `State::set_console`.
+//!
+//! While the simulated system is paused, the worker threads are stopped at
+//! arbitrary points and might be holding the allocator's lock, so the console
+//! serves commands without allocating memory. Buffers are allocated when a
+//! session starts.
+use r3_core::kernel::{task::TaskHandle, InterruptNum, TaskRef};
+use r3_kernel::{Id, System, TaskInfo, TaskInfoError};
+use std::{
+    fmt::{self, Write as _},
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use crate::{pend_interrupt_line, sched, shutdown, ums, PortInstance};
+
+/// The environment variable that enables the console. The value is either
+/// `stdin` or `unix:` followed by the path of a Unix domain socket to listen
+/// on.
+pub const CONSOLE_ENV_VAR: &str = "R3_PORT_STD_CONSOLE";
+
+const HELP: &str = "\
+commands:
+  tasks        list the tasks with their states, priorities, and wait targets
+  pend LINE    pend the interrupt line LINE
+  pause        stop the simulated system
+  resume       resume the simulated system
+  shutdown     shut down the simulated system
+  help         show this message
+";
+
+/// The maximum length of a command line, in bytes. Longer lines are rejected.
+const MAX_LINE_LEN: usize = 256;
+
+/// The capacity of the response buffer per task, in bytes. A response that
+/// doesn't fit in the buffer is truncated.
+const RESPONSE_LEN_PER_TASK: usize = 160;
+
+/// Specifies where the inspection console accepts commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleEndpoint {
+    /// Read commands from the standard input and write responses to the
+    /// standard output.
+    Stdin,
+    /// Listen on a Unix domain socket at the specified path. Clients are
+    /// served one at a time.
+    UnixSocket(PathBuf),
+}
+
+impl ConsoleEndpoint {
+    /// Get the endpoint specified by [`CONSOLE_ENV_VAR`]. Returns `None` if the
+    /// variable is not set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the variable has an invalid value.
+    pub(crate) fn from_env() -> Option<Self> {
+        let value = std::env::var_os(CONSOLE_ENV_VAR)?;
+        let value = value.to_str().unwrap_or("");
+        if value == "stdin" {
+            Some(Self::Stdin)
+        } else if let Some(path) = value.strip_prefix("unix:") {
+            Some(Self::UnixSocket(path.into()))
+        } else {
+            panic!("`{CONSOLE_ENV_VAR}` must be `stdin` or `unix:PATH`, not {value:?}");
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Help,
+    Tasks,
+    Pend(InterruptNum),
+    Pause,
+    Resume,
+    Shutdown,
+}
+
+/// An error returned by [`Command::parse`]. Borrows the command line so that
+/// it can be reported without allocating memory.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError<'a> {
+    PendUsage,
+    BadInterruptLine(&'a str),
+    UnknownCommand(&'a str),
+    TooManyArguments(&'a str),
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PendUsage => write!(f, "usage: pend LINE"),
+            Self::BadInterruptLine(line) => write!(f, "invalid interrupt line: {line:?}"),
+            Self::UnknownCommand(name) => write!(f, "unknown command: {name:?}; try `help`"),
+            Self::TooManyArguments(name) => write!(f, "too many arguments for `{name}`"),
+        }
+    }
+}
+
+impl Command {
+    /// Parse a command line. Returns `Ok(None)` if the line is empty.
+    fn parse(line: &str) -> Result<Option<Self>, ParseError<'_>> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else { return Ok(None) };
+        let command = match name {
+            "help" => Self::Help,
+            "tasks" => Self::Tasks,
+            "pend" => {
+                let line = words.next().ok_or(ParseError::PendUsage)?;
+                let line = line
+                    .parse()
+                    .map_err(|_| ParseError::BadInterruptLine(line))?;
+                Self::Pend(line)
+            }
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            "shutdown" => Self::Shutdown,
+            _ => return Err(ParseError::UnknownCommand(name)),
+        };
+        if words.next().is_some() {
+            return Err(ParseError::TooManyArguments(name));
+        }
+        Ok(Some(command))
+    }
+}
+
+/// Start a host thread serving the console.
+pub(crate) fn spawn<Traits: PortInstance>(endpoint: ConsoleEndpoint) {
+    log::debug!("starting the console on {endpoint:?}");
+    std::thread::spawn(move || {
+        let num_tasks = Traits::task_cb_pool().len();
+        let mut session = Session::<Traits> {
+            paused: None,
+            response: ResponseBuf::with_capacity(
+                HELP.len() + RESPONSE_LEN_PER_TASK * (num_tasks + Traits::NUM_CPUS),
+            ),
+            task_infos: Vec::with_capacity(num_tasks),
+            _phantom: core::marker::PhantomData,
+        };
+        let result = match endpoint {
+            ConsoleEndpoint::Stdin => unlocked_stdout()
+                .and_then(|stdout| session.serve(io::stdin().lock(), &stdout)),
+            ConsoleEndpoint::UnixSocket(path) => session.serve_unix_socket(path),
+        };
+        if let Err(e) = result {
+            log::warn!("the console stopped because of an I/O error: {e}");
+        }
+    });
+}
+
+/// Get a handle to the standard output that doesn't go through
+/// [`io::Stdout`]'s lock. A worker thread might be stopped while holding the
+/// lock (e.g., in the middle of `println!`), which would block the console
+/// forever if it used [`io::Stdout`].
+fn unlocked_stdout() -> io::Result<std::fs::File> {
+    #[cfg(unix)]
+    let handle = std::os::fd::AsFd::as_fd(&io::stdout()).try_clone_to_owned()?;
+    #[cfg(windows)]
+    let handle = std::os::windows::io::AsHandle::as_handle(&io::stdout()).try_clone_to_owned()?;
+    Ok(handle.into())
+}
+
+/// The state of the console, which outlives client connections.
+struct Session<Traits: PortInstance> {
+    /// Stops the simulated system while it's paused.
+    paused: Option<Paused>,
+    /// The response to the current command.
+    response: ResponseBuf,
+    /// The buffer used by [`task_report`].
+    task_infos: Vec<(Id, Result<TaskInfo, TaskInfoError>)>,
+    _phantom: core::marker::PhantomData<Traits>,
+}
+
+/// A fixed-capacity text buffer. The output is truncated if it doesn't fit.
+struct ResponseBuf(Vec<u8>);
+
+impl ResponseBuf {
+    fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Write for ResponseBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let num_bytes = s.len().min(self.0.capacity() - self.0.len());
+        // This doesn't reallocate the buffer because it fits in the capacity
+        self.0.extend_from_slice(&s.as_bytes()[..num_bytes]);
+        if num_bytes < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Holds the scheduler lock and keeps the worker threads stopped until
+/// dropped.
+///
+/// The stopped threads might be holding any lock, including the allocator's.
+/// While this exists, [`Session`] must not allocate memory or log anything.
+struct Paused(ums::ThreadGroupLockGuard<'static, sched::SchedState>);
+
+impl Paused {
+    fn new(mut lock: ums::ThreadGroupLockGuard<'static, sched::SchedState>) -> Self {
+        lock.stop_threads();
+        Self(lock)
+    }
+}
+
+impl Drop for Paused {
+    fn drop(&mut self) {
+        self.0.resume_threads();
+    }
+}
+
+impl<Traits: PortInstance> Session<Traits> {
+    #[cfg(unix)]
+    fn serve_unix_socket(&mut self, path: PathBuf) -> io::Result<()> {
+        use std::os::unix::net::UnixListener;
+
+        // Remove a stale socket left by a previous run
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        log::info!("the console is listening on {}", path.display());
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if let Err(e) = self.serve(&stream, &stream) {
+                // Logging might allocate memory
+                if self.paused.is_none() {
+                    log::debug!("a console client disconnected: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn serve_unix_socket(&mut self, _path: PathBuf) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix domain sockets are not supported on this platform",
+        ))
+    }
+
+    /// Process commands until `input` reaches EOF.
+    fn serve(&mut self, mut input: impl Read, mut output: impl Write) -> io::Result<()> {
+        let mut line = [0u8; MAX_LINE_LEN];
+        while let Some(len) = read_line(&mut input, &mut line)? {
+            self.response.clear();
+            let line = line.get(..len).map(std::str::from_utf8);
+            let _ = match line {
+                Some(Ok(line)) => match Command::parse(line) {
+                    Ok(Some(command)) => self.execute(command),
+                    Ok(None) => continue,
+                    Err(e) => writeln!(self.response, "error: {e}"),
+                },
+                Some(Err(_)) => writeln!(self.response, "error: invalid UTF-8"),
+                None => writeln!(self.response, "error: the line is too long"),
+            };
+            output.write_all(self.response.as_bytes())?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: Command) -> fmt::Result {
+        let state = Traits::port_state();
+        let out = &mut self.response;
+        match command {
+            Command::Help => out.write_str(HELP),
+            Command::Tasks => {
+                if let Some(Paused(lock)) = &mut self.paused {
+                    task_report::<Traits>(lock, false, &mut self.task_infos, out)
+                } else {
+                    task_report::<Traits>(
+                        &mut state.thread_group.get().unwrap().lock(),
+                        true,
+                        &mut self.task_infos,
+                        out,
+                    )
+                }
+            }
+            Command::Pend(line) => {
+                if self.paused.is_some() {
+                    return writeln!(out, "error: the system is paused");
+                }
+                match pend_interrupt_line::<Traits>(line) {
+                    Ok(()) => writeln!(out, "ok"),
+                    Err(e) => writeln!(out, "error: {e:?}"),
+                }
+            }
+            Command::Pause => {
+                if self.paused.is_none() {
+                    self.paused = Some(Paused::new(state.thread_group.get().unwrap().lock()));
+                }
+                writeln!(out, "paused")
+            }
+            Command::Resume => {
+                self.paused = None;
+                writeln!(out, "resumed")
+            }
+            Command::Shutdown => {
+                self.paused = None;
+                shutdown::<Traits>();
+                writeln!(out, "shutting down")
+            }
+        }
+    }
+}
+
+/// Read a line from `input` into `buf` without allocating memory. Returns the
+/// length of the line excluding the line terminator, or `None` if `input`
+/// reached EOF. If the line doesn't fit in `buf`, it's truncated, and the
+/// returned length is greater than `buf.len()`.
+///
+/// `input` is read byte by byte, which is fine for an interactive console.
+fn read_line(input: &mut impl Read, buf: &mut [u8]) -> io::Result<Option<usize>> {
+    let mut len = 0;
+    let mut byte = 0;
+    loop {
+        match input.read(std::slice::from_mut(&mut byte)) {
+            Ok(0) => return Ok((len > 0).then_some(len)),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+        match byte {
+            b'\n' => break,
+            // Accept CRLF line endings
+            b'\r' => {}
+            _ => {
+                if let Some(x) = buf.get_mut(len) {
+                    *x = byte;
+                }
+                len += 1;
+            }
+        }
+    }
+    Ok(Some(len))
+}
+
+/// Get a report of the tasks. The worker threads are stopped while the kernel
+/// state is examined, so this works even if the simulated system is stuck in a
+/// CPU Lock state.
+///
+/// `stop` specifies whether to stop the worker threads. It must be `false` if
+/// they are already stopped. `infos` must have enough capacity to hold all
+/// tasks because this function must not allocate memory while the worker
+/// threads are stopped.
+fn task_report<Traits: PortInstance>(
+    lock: &mut ums::ThreadGroupLockGuard<'_, sched::SchedState>,
+    stop: bool,
+    infos: &mut Vec<(Id, Result<TaskInfo, TaskInfoError>)>,
+    out: &mut impl fmt::Write,
+) -> fmt::Result {
+    let num_tasks = Traits::task_cb_pool().len();
+    assert!(infos.capacity() >= num_tasks);
+    infos.clear();
+
+    let mut collect = || {
+        for i in 0..num_tasks {
+            let id = Id::new(i + 1).unwrap();
+            // Safety: `id` refers to an element of `task_cb_pool`, and we are
+            // the port, so it's okay to create an object handle out of thin
+            // air
+            let task = unsafe { TaskRef::<System<Traits>>::from_id(id) };
+            // Safety: The worker threads are stopped, so no one else is
+            // accessing the kernel state
+            infos.push((id, unsafe { System::<Traits>::task_info_unchecked(task) }));
+        }
+    };
+    if stop {
+        lock.with_threads_stopped(collect);
+    } else {
+        collect();
+    }
+
+    for (i, cpu) in lock.scheduler().cpus.iter().enumerate() {
+        if cpu.cpu_lock {
+            writeln!(
+                out,
+                "cpu {i}: CPU Lock active; the following information might be inconsistent"
+            )?;
+        }
+    }
+    for (id, info) in infos.iter() {
+        match info {
+            Ok(info) => {
+                write!(
+                    out,
+                    "task {id}: {:?}, priority {} (effective {})",
+                    info.state, info.base_priority, info.effective_priority
+                )?;
+                if let Some(wait_target) = &info.wait_target {
+                    write!(out, ", waiting for {wait_target:?}")?;
+                }
+                out.write_char('\n')?;
+            }
+            Err(e) => writeln!(out, "task {id}: error: {e:?}")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Command::parse(""), Ok(None));
+        assert_eq!(Command::parse("  tasks "), Ok(Some(Command::Tasks)));
+        assert_eq!(Command::parse("pend 42"), Ok(Some(Command::Pend(42))));
+        assert!(Command::parse("pend").is_err());
+        assert!(Command::parse("pend x").is_err());
+        assert!(Command::parse("pause now").is_err());
+        assert!(Command::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn read_line() {
+        let mut input = &b"tasks\r\n\npend 1\nlong line\nunterminated"[..];
+        let mut buf = [0u8; 6];
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), Some(5));
+        assert_eq!(&buf[..5], b"tasks");
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), Some(0));
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), Some(6));
+        assert_eq!(&buf, b"pend 1");
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), Some(9));
+        assert_eq!(&buf, b"long l");
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), Some(12));
+        assert_eq!(super::read_line(&mut input, &mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn response_buf_truncate() {
+        let mut out = ResponseBuf::with_capacity(4);
+        let capacity = out.0.capacity();
+        assert!(write!(out, "{}", "x".repeat(capacity + 1)).is_err());
+        assert_eq!(out.as_bytes().len(), capacity);
+        assert_eq!(out.0.capacity(), capacity);
+    }
+}
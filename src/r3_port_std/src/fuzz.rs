@@ -0,0 +1,144 @@
+//! Randomized schedule exploration ("schedule fuzzing")
+//!
+//! When enabled, the simulated hardware makes some of the decisions that real
+//! hardware would leave to timing at random, driven by a seeded pseudo-random
+//! number generator:
+//!
+//!  - When two or more interrupt lines with the same priority are pending, the
+//!    line to service first is chosen at random.
+//!
+//!  - Preemption points are injected into the port functions called by the
+//!    kernel. At each of them, with a probability of
+//!    [`PREEMPTION_PROBABILITY`], the port takes pending interrupts and
+//!    yields the processor, letting another simulated processor run in a
+//!    multiprocessor system. With [`TimeSource::Virtual`], the port also
+//!    pretends that the code executed since the last preemption point took up
+//!    to [`MAX_ELAPSED_MICROS`] microseconds, firing the timer interrupt if
+//!    its deadline is reached. (The host time can't be skipped.)
+//!
+//! With [`TimeSource::Virtual`] and no external sources of interrupts, the
+//! execution is fully determined by the seed, so a failing seed can be
+//! replayed by setting the environment variable [`SEED_ENV_VAR`].
+//!
+//! [`TimeSource::Virtual`]: crate::TimeSource::Virtual
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// The environment variable that enables schedule fuzzing. The value is either
+/// a seed (a decimal integer) or `random`.
+pub const SEED_ENV_VAR: &str = "R3_PORT_STD_FUZZ_SEED";
+
+/// The probability of each injected preemption point taking effect.
+pub const PREEMPTION_PROBABILITY: (u32, u32) = (1, 8);
+
+/// The maximum amount of virtual time that can elapse at an injected
+/// preemption point, measured in microseconds.
+pub const MAX_ELAPSED_MICROS: u64 = 1000;
+
+/// The state of schedule fuzzing.
+#[derive(Debug)]
+pub struct SchedFuzzer {
+    /// The state of the xorshift64* generator. Never zero.
+    rng_state: u64,
+}
+
+impl SchedFuzzer {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed so that similar seeds don't produce similar
+        // sequences. Replace zero, which is a fixed point of xorshift.
+        let rng_state = splitmix64(seed);
+        Self {
+            rng_state: if rng_state == 0 { 1 } else { rng_state },
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        x.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Choose a number in range `0..len`.
+    pub fn gen_index(&mut self, len: usize) -> usize {
+        assert_ne!(len, 0);
+        (self.next_u64() % len as u64) as usize
+    }
+
+    /// Decide whether an injected preemption point should take effect.
+    pub fn gen_preemption(&mut self) -> bool {
+        let (num, den) = PREEMPTION_PROBABILITY;
+        self.next_u64() % u64::from(den) < u64::from(num)
+    }
+
+    /// Choose the amount of virtual time elapsed at an injected preemption
+    /// point.
+    pub fn gen_elapsed_micros(&mut self) -> u64 {
+        self.next_u64() % (MAX_ELAPSED_MICROS + 1)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Get the seed specified by [`SEED_ENV_VAR`]. Returns `None` if the variable
+/// is not set.
+///
+/// # Panics
+///
+/// Panics if the variable has an invalid value.
+pub fn seed_from_env() -> Option<u64> {
+    let value = std::env::var_os(SEED_ENV_VAR)?;
+    let value = value.to_str().unwrap_or("");
+    if value == "random" {
+        Some(random_seed())
+    } else if let Ok(seed) = value.parse() {
+        Some(seed)
+    } else {
+        panic!("`{SEED_ENV_VAR}` must be an integer or `random`, not {value:?}");
+    }
+}
+
+/// Generate a random seed from the process-local random state used by
+/// `HashMap`.
+fn random_seed() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let mut a = SchedFuzzer::new(42);
+        let mut b = SchedFuzzer::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn seed_zero() {
+        let mut fuzzer = SchedFuzzer::new(0);
+        assert_ne!(fuzzer.next_u64(), fuzzer.next_u64());
+    }
+
+    #[test]
+    fn gen_index_covers_range() {
+        let mut fuzzer = SchedFuzzer::new(1);
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            seen[fuzzer.gen_index(seen.len())] = true;
+        }
+        assert!(seen.iter().all(|&x| x));
+    }
+}
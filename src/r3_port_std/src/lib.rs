@@ -22,7 +22,7 @@ use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb, UTicks};
 use spin::Mutex as SpinMutex;
 use std::{
     cell::Cell,
-    sync::{mpsc, OnceLock},
+    sync::{atomic::AtomicU64, mpsc, OnceLock},
     time::{Duration, Instant},
 };
 
@@ -43,6 +43,9 @@ mod threading;
 #[cfg(test)]
 mod threading_test;
 
+mod console;
+mod fuzz;
+pub mod peripheral;
 mod sched;
 mod ums;
 mod utils;
@@ -76,6 +79,12 @@ pub const INTERRUPT_LINE_TIMER: InterruptNum = 1022;
 /// The default interrupt priority for [`INTERRUPT_LINE_TIMER`].
 pub const INTERRUPT_PRIORITY_TIMER: InterruptPriority = 16383;
 
+/// The interval at which the running thread is preempted in a multiprocessor
+/// system to let the other processors run.
+const TIME_SLICE: Duration = Duration::from_millis(1);
+
+pub use console::ConsoleEndpoint;
+
 /// Implemented on a kernel trait type by [`use_port!`].
 ///
 /// # Safety
@@ -98,7 +107,34 @@ pub unsafe trait PortInstance:
 pub struct State {
     thread_group: OnceLock<ums::ThreadGroup<sched::SchedState>>,
     timer_cmd_send: SpinMutex<Option<mpsc::Sender<TimerCmd>>>,
+    /// `true` iff the time source is [`TimeSource::Virtual`]. This is not a
+    /// `SpinMutex` because worker threads can be preempted while holding it.
+    virtual_time: AtomicBool,
+    /// The origin point of [`TimeSource::Host`].
     origin: AtomicRef<'static, Instant>,
+    /// The current time of [`TimeSource::Virtual`], measured in microseconds.
+    virtual_now: AtomicU64,
+    /// The seed for schedule fuzzing set by [`Self::set_sched_fuzz_seed`].
+    sched_fuzz_seed: SpinMutex<Option<u64>>,
+    /// The console endpoint set by [`Self::set_console`].
+    console: SpinMutex<Option<ConsoleEndpoint>>,
+}
+
+/// Specifies the time source of the simulated system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The host's monotonic clock ([`Instant`]). This is the default.
+    Host,
+    /// Virtual time that stands still while the simulated system is running
+    /// and advances only when all simulated threads are idle. When this
+    /// happens, virtual time jumps straight to the deadline set by the last
+    /// call to `pend_tick_after`.
+    ///
+    /// This makes time-based tests complete instantly and reproducibly. On the
+    /// other hand, a system that never becomes idle (e.g., a task that
+    /// busy-waits for the system time to reach some value) will never see the
+    /// time advance.
+    Virtual,
 }
 
 #[derive(Debug)]
@@ -132,7 +168,13 @@ enum Tsm {
 }
 
 enum TimerCmd {
+    /// Used by [`TimeSource::Host`].
     SetTimeout { at: Instant },
+    /// Sent when a device event is scheduled. Used by [`TimeSource::Host`].
+    DeviceEvent,
+    /// Sent by [`sched::SchedState`] when the system goes idle. Used by
+    /// [`TimeSource::Virtual`].
+    Idle,
 }
 
 /// The role of a thread.
@@ -172,14 +214,21 @@ impl TaskState {
         log::trace!("exit_and_dispatch({self:p}) enter");
         self.assert_current_thread();
 
-        let mut lock = state.thread_group.get().unwrap().lock();
-
         // Dissociate this thread from the task.
         let thread_id = match std::mem::replace(&mut *self.tsm.lock(), Tsm::Uninit) {
             Tsm::Running(thread_id) => thread_id,
             _ => unreachable!(),
         };
 
+        // Invoke the dispatcher. This is done without leaving CPU Lock
+        // because, in a multiprocessor system, the kernel hands over the
+        // ownership of its spinlock to the next call to `choose_running_task`
+        // on this processor.
+        unsafe { Traits::choose_running_task() };
+        state.update_task_thread::<Traits>();
+
+        let mut lock = state.thread_group.get().unwrap().lock();
+
         // Make sure this thread will run to completion.
         //
         // Running all threads to completion is a prerequisite for a clean
@@ -189,13 +238,12 @@ impl TaskState {
         // the kernel will never choose this task again. However, the underlying
         // UMS thread is still alive. Thus, we need to temporarily override the
         // normal scheduling to ensure this thread will run to completion.
-        lock.scheduler().recycle_thread(thread_id);
-        lock.scheduler().cpu_lock = false;
+        let cpu = lock.scheduler().current_cpu;
+        lock.scheduler().recycle_thread(thread_id, cpu);
+        lock.scheduler().cpu().cpu_lock = false;
+        let _ = sched::check_preemption_by_interrupt(state.thread_group.get().unwrap(), &mut lock);
         drop(lock);
 
-        // Invoke the dispatcher
-        unsafe { state.yield_cpu::<Traits>() };
-
         log::trace!("exit_and_dispatch({self:p}) calling exit_thread");
         unsafe { ums::exit_thread() };
     }
@@ -207,10 +255,66 @@ impl State {
         Self {
             thread_group: OnceLock::new(),
             timer_cmd_send: SpinMutex::new(None),
+            virtual_time: AtomicBool::new(false),
             origin: AtomicRef::new(None),
+            virtual_now: AtomicU64::new(0),
+            sched_fuzz_seed: SpinMutex::new(None),
+            console: SpinMutex::new(None),
         }
     }
 
+    /// Select the time source. Must be called before [`Self::port_boot`].
+    pub fn set_time_source(&self, time_source: TimeSource) {
+        assert!(
+            self.thread_group.get().is_none(),
+            "the time source can't be changed after boot"
+        );
+        self.virtual_time
+            .store(time_source == TimeSource::Virtual, Ordering::Relaxed);
+    }
+
+    fn time_source(&self) -> TimeSource {
+        if self.virtual_time.load(Ordering::Relaxed) {
+            TimeSource::Virtual
+        } else {
+            TimeSource::Host
+        }
+    }
+
+    /// Enable schedule fuzzing with the specified seed. Must be called before
+    /// [`Self::port_boot`].
+    ///
+    /// Schedule fuzzing can also be enabled by setting the environment
+    /// variable `R3_PORT_STD_FUZZ_SEED`, which takes precedence over this
+    /// method.
+    pub fn set_sched_fuzz_seed(&self, seed: Option<u64>) {
+        assert!(
+            self.thread_group.get().is_none(),
+            "the schedule fuzzing seed can't be changed after boot"
+        );
+        *self.sched_fuzz_seed.lock() = seed;
+    }
+
+    /// Get the seed used for schedule fuzzing, or `None` if schedule fuzzing
+    /// is disabled. After [`Self::port_boot`], this reflects the seed taken
+    /// from the environment variable `R3_PORT_STD_FUZZ_SEED`, if any.
+    pub fn sched_fuzz_seed(&self) -> Option<u64> {
+        *self.sched_fuzz_seed.lock()
+    }
+
+    /// Enable the inspection console. Must be called before
+    /// [`Self::port_boot`].
+    ///
+    /// The console can also be enabled by setting the environment variable
+    /// `R3_PORT_STD_CONSOLE`, which takes precedence over this method.
+    pub fn set_console(&self, endpoint: Option<ConsoleEndpoint>) {
+        assert!(
+            self.thread_group.get().is_none(),
+            "the console can't be enabled after boot"
+        );
+        *self.console.lock() = endpoint;
+    }
+
     /// Initialize the user-mode scheduling system and boot the kernel.
     ///
     /// Returns when the shutdown initiated by [`shutdown`] completes.
@@ -220,35 +324,49 @@ impl State {
 
         self.thread_group.set(thread_group).ok().unwrap();
 
+        // Enable schedule fuzzing
+        let seed = fuzz::seed_from_env().or(*self.sched_fuzz_seed.lock());
+        *self.sched_fuzz_seed.lock() = seed;
+        if let Some(seed) = seed {
+            log::info!(
+                "schedule fuzzing enabled (seed = {seed}); set `{}={seed}` to replay",
+                fuzz::SEED_ENV_VAR,
+            );
+            self.thread_group.get().unwrap().lock().scheduler().fuzz =
+                Some(fuzz::SchedFuzzer::new(seed));
+        }
+
         // Start a timer thread
         let (timer_cmd_send, timer_cmd_recv) = mpsc::channel();
         log::trace!("starting the timer thread");
-        let timer_join_handle = std::thread::spawn(move || {
-            let mut next_deadline = None;
-            loop {
-                let recv_result = if let Some(next_deadline) = next_deadline {
-                    timer_cmd_recv.recv_deadline(next_deadline)
-                } else {
-                    timer_cmd_recv
-                        .recv()
-                        .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
-                };
-                match recv_result {
-                    Err(mpsc::RecvTimeoutError::Disconnected) => {
-                        break;
-                    }
-                    Err(mpsc::RecvTimeoutError::Timeout) => {
-                        pend_interrupt_line::<Traits>(INTERRUPT_LINE_TIMER).unwrap();
-                        next_deadline = None;
-                    }
-                    Ok(TimerCmd::SetTimeout { at }) => {
-                        next_deadline = Some(at);
-                    }
-                }
+        let timer_join_handle = match self.time_source() {
+            TimeSource::Host => {
+                std::thread::spawn(move || Self::host_timer_thread::<Traits>(timer_cmd_recv))
             }
-        });
+            TimeSource::Virtual => {
+                // Let the scheduler tell the timer thread when the system goes
+                // idle
+                self.thread_group
+                    .get()
+                    .unwrap()
+                    .lock()
+                    .scheduler()
+                    .idle_send = Some(timer_cmd_send.clone());
+
+                std::thread::spawn(move || {
+                    Traits::port_state().virtual_timer_thread::<Traits>(timer_cmd_recv)
+                })
+            }
+        };
         *self.timer_cmd_send.lock() = Some(timer_cmd_send);
 
+        // Start a time-slicing thread if there are multiple processors
+        let (time_slice_stop_send, time_slice_stop_recv) = mpsc::channel();
+        let time_slice_join_handle = (Traits::NUM_CPUS > 1).then(|| {
+            log::trace!("starting the time-slicing thread");
+            std::thread::spawn(move || Self::time_slice_thread::<Traits>(time_slice_stop_recv))
+        });
+
         // Create the initial UMS worker thread, where the boot phase of the
         // kernel runs
         let mut lock = self.thread_group.get().unwrap().lock();
@@ -261,8 +379,8 @@ impl State {
             }
         });
         log::trace!("startup thread = {thread_id:?}");
-        lock.scheduler().task_thread = Some(thread_id);
-        lock.scheduler().recycle_thread(thread_id);
+        lock.scheduler().cpus[0].task_thread = Some(thread_id);
+        lock.scheduler().recycle_thread(thread_id, 0);
         lock.preempt();
 
         // Configure timer interrupt
@@ -275,6 +393,11 @@ impl State {
             .ok()
             .unwrap();
 
+        // Start the inspection console
+        if let Some(endpoint) = ConsoleEndpoint::from_env().or_else(|| self.console.lock().take()) {
+            console::spawn::<Traits>(endpoint);
+        }
+
         drop(lock);
 
         // Wait until the thread group shuts down
@@ -285,15 +408,178 @@ impl State {
         // corresponding sender (`timer_cmd_send`).
         log::trace!("stopping the timer thread");
         *self.timer_cmd_send.lock() = None;
+        self.thread_group
+            .get()
+            .unwrap()
+            .lock()
+            .scheduler()
+            .idle_send = None;
         timer_join_handle.join().unwrap();
         log::trace!("stopped the timer thread");
 
+        // Stop the time-slicing thread
+        drop(time_slice_stop_send);
+        if let Some(join_handle) = time_slice_join_handle {
+            join_handle.join().unwrap();
+        }
+
         // Propagate any panic that occured in a worker thread
         if let Err(e) = result {
             std::panic::resume_unwind(e);
         }
     }
 
+    /// The timer thread for [`TimeSource::Host`]. Pends a timer interrupt when
+    /// the host time reaches the deadline.
+    fn host_timer_thread<Traits: PortInstance>(timer_cmd_recv: mpsc::Receiver<TimerCmd>) {
+        let state = Traits::port_state();
+        let thread_group = state.thread_group.get().unwrap();
+        let mut next_deadline = None;
+        loop {
+            // Wake up at the deadline or when the next device event is due,
+            // whichever comes first
+            let next_device_event = (thread_group.lock().scheduler())
+                .devices
+                .next_event_time()
+                .map(|micros| state.host_instant(micros));
+            let wake_at = next_deadline.into_iter().chain(next_device_event).min();
+
+            let recv_result = if let Some(wake_at) = wake_at {
+                timer_cmd_recv.recv_deadline(wake_at)
+            } else {
+                timer_cmd_recv
+                    .recv()
+                    .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+            };
+            match recv_result {
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let mut lock = thread_group.lock();
+
+                    if next_deadline.map_or(false, |at| at <= Instant::now()) {
+                        lock.scheduler()
+                            .update_line(INTERRUPT_LINE_TIMER, |line| line.pended = true)
+                            .ok()
+                            .unwrap();
+                        next_deadline = None;
+                    }
+
+                    peripheral::run_due_events(state, lock.scheduler(), state.host_micros());
+
+                    if sched::check_preemption_by_interrupt(thread_group, &mut lock) {
+                        lock.preempt();
+                    }
+                }
+                Ok(TimerCmd::SetTimeout { at }) => {
+                    next_deadline = Some(at);
+                }
+                Ok(TimerCmd::DeviceEvent) => {}
+                Ok(TimerCmd::Idle) => unreachable!(),
+            }
+        }
+    }
+
+    /// The time-slicing thread for a multiprocessor system. Periodically
+    /// preempts the running thread so that all simulated processors make
+    /// progress even if some of them are busy-waiting.
+    fn time_slice_thread<Traits: PortInstance>(stop_recv: mpsc::Receiver<()>) {
+        let thread_group = Traits::port_state().thread_group.get().unwrap();
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stop_recv.recv_timeout(TIME_SLICE) {
+            let mut lock = thread_group.lock();
+            if lock.scheduler().num_busy_cpus() > 1 {
+                lock.preempt();
+            }
+        }
+    }
+
+    /// The timer thread for [`TimeSource::Virtual`]. Advances virtual time to
+    /// the deadline or the next device event when the system goes idle.
+    fn virtual_timer_thread<Traits: PortInstance>(
+        &'static self,
+        timer_cmd_recv: mpsc::Receiver<TimerCmd>,
+    ) {
+        let thread_group = self.thread_group.get().unwrap();
+
+        while let Ok(cmd) = timer_cmd_recv.recv() {
+            match cmd {
+                TimerCmd::Idle => {}
+                TimerCmd::SetTimeout { .. } | TimerCmd::DeviceEvent => unreachable!(),
+            }
+
+            loop {
+                let mut lock = thread_group.lock();
+
+                // The system might have woken up by an external interrupt since
+                // the idle notification was sent
+                if !lock.scheduler().is_idle() {
+                    break;
+                }
+
+                if !self.advance_virtual_time(&mut lock) {
+                    // Nothing will happen until an external interrupt
+                    break;
+                }
+
+                if sched::check_preemption_by_interrupt(thread_group, &mut lock) {
+                    lock.preempt();
+                    break;
+                }
+
+                // The system is still idle (e.g., a device event didn't pend
+                // any interrupts). Release the lock so that external threads
+                // can make progress, and try again.
+            }
+        }
+    }
+
+    /// Advance virtual time to the deadline set by `pend_tick_after` or the
+    /// next device event, whichever comes first, and fire the events that are
+    /// due. Returns `false` if there's nothing to advance to.
+    fn advance_virtual_time(
+        &'static self,
+        lock: &mut ums::ThreadGroupLockGuard<sched::SchedState>,
+    ) -> bool {
+        let sched_state = lock.scheduler();
+        let Some(at) = (sched_state.virtual_deadline.into_iter())
+            .chain(sched_state.devices.next_event_time())
+            .min()
+        else {
+            return false;
+        };
+
+        log::trace!("advancing virtual time to {at}us");
+        let now = self.virtual_now.fetch_max(at, Ordering::Relaxed).max(at);
+
+        self.fire_virtual_events(lock, now)
+    }
+
+    /// Pend a timer interrupt if virtual time has reached the deadline set by
+    /// `pend_tick_after`, and run device events that are due. Returns `true`
+    /// if anything happened.
+    fn fire_virtual_events(
+        &'static self,
+        lock: &mut ums::ThreadGroupLockGuard<sched::SchedState>,
+        now: u64,
+    ) -> bool {
+        let sched_state = lock.scheduler();
+        let mut fired = false;
+
+        if sched_state.virtual_deadline.map_or(false, |at| at <= now) {
+            sched_state.virtual_deadline = None;
+            sched_state
+                .update_line(INTERRUPT_LINE_TIMER, |line| line.pended = true)
+                .ok()
+                .unwrap();
+            fired = true;
+        }
+
+        fired |= peripheral::run_due_events(self, sched_state, now);
+
+        fired
+    }
+
     pub unsafe fn dispatch_first_task<Traits: PortInstance>(&'static self) -> ! {
         log::trace!("dispatch_first_task");
         assert_eq!(expect_worker_thread::<Traits>(), ThreadRole::Boot);
@@ -302,6 +588,27 @@ impl State {
         // Create a UMS worker thread for the dispatcher
         let mut lock = self.thread_group.get().unwrap().lock();
 
+        // Start the other processors. They don't access the kernel state
+        // until `boot` completes, which is why they can start in a CPU Lock
+        // state at the same time.
+        if lock.scheduler().current_cpu == 0 {
+            for cpu in 1..Traits::NUM_CPUS {
+                let thread_id = lock.spawn(|_| {
+                    THREAD_ROLE.with(|role| role.set(ThreadRole::Boot));
+
+                    // Safety: We are a port, so it's okay to call this
+                    unsafe {
+                        <Traits as PortToKernel>::boot_secondary();
+                    }
+                });
+                log::trace!("startup thread for processor {cpu} = {thread_id:?}");
+                let sched_state = lock.scheduler();
+                sched_state.cpus[cpu].task_thread = Some(thread_id);
+                sched_state.cpus[cpu].cpu_lock = true;
+                sched_state.recycle_thread(thread_id, cpu);
+            }
+        }
+
         // Configure PendSV
         // TODO: move this (except for `pended = true`) to `port_boot`
         lock.scheduler()
@@ -314,7 +621,7 @@ impl State {
             .ok()
             .unwrap();
 
-        lock.scheduler().cpu_lock = false;
+        lock.scheduler().cpu().cpu_lock = false;
 
         // Start scheduling
         assert!(sched::check_preemption_by_interrupt(
@@ -337,15 +644,21 @@ impl State {
 
         unsafe { self.enter_cpu_lock::<Traits>() };
         unsafe { Traits::choose_running_task() };
+        // Do this before leaving CPU Lock so that the other processors never
+        // observe `task_thread` disagreeing with `running_task`
+        self.update_task_thread::<Traits>();
         unsafe { self.leave_cpu_lock::<Traits>() };
+    }
 
-        let mut lock = self.thread_group.get().unwrap().lock();
-
-        // Tell the scheduler which task to run next
+    /// Tell the scheduler which task to run next on the current processor.
+    fn update_task_thread<Traits: PortInstance>(&'static self) {
         // Safety: `running_task` is only modified by `choose_running_task`, so
         //         there's no data race
         let running_task = unsafe { *Traits::state().running_task_ptr() };
-        lock.scheduler().task_thread = if let Some(task) = running_task {
+
+        let mut lock = self.thread_group.get().unwrap().lock();
+
+        lock.scheduler().cpu().task_thread = if let Some(task) = running_task {
             log::trace!("dispatching task {task:p}");
 
             let mut tsm = task.port_task_state.tsm.lock();
@@ -395,6 +708,30 @@ impl State {
             .unwrap();
     }
 
+    pub unsafe fn yield_cpu_on<Traits: PortInstance>(&'static self, cpu: usize) {
+        log::trace!("yield_cpu_on({cpu})");
+        expect_worker_thread::<Traits>();
+        assert!(self.is_cpu_lock_active::<Traits>());
+
+        // The interrupt handler will start running when the current processor
+        // leaves CPU Lock
+        let mut lock = self.thread_group.get().unwrap().lock();
+        lock.scheduler().pend_dispatch(cpu);
+        let _ = sched::check_preemption_by_interrupt(self.thread_group.get().unwrap(), &mut lock);
+    }
+
+    pub fn current_cpu<Traits: PortInstance>(&self) -> usize {
+        if Traits::NUM_CPUS == 1 {
+            return 0;
+        }
+
+        expect_worker_thread::<Traits>();
+
+        (self.thread_group.get().unwrap().lock())
+            .scheduler()
+            .current_cpu
+    }
+
     pub unsafe fn exit_and_dispatch<Traits: PortInstance>(
         &'static self,
         task: &'static TaskCb<Traits>,
@@ -408,13 +745,15 @@ impl State {
         }
     }
 
-    pub unsafe fn enter_cpu_lock<Traits: PortInstance>(&self) {
+    pub unsafe fn enter_cpu_lock<Traits: PortInstance>(&'static self) {
         log::trace!("enter_cpu_lock");
         expect_worker_thread::<Traits>();
 
+        self.fuzz_preemption_point();
+
         let mut lock = self.thread_group.get().unwrap().lock();
-        assert!(!lock.scheduler().cpu_lock);
-        lock.scheduler().cpu_lock = true;
+        assert!(!lock.scheduler().cpu().cpu_lock);
+        lock.scheduler().cpu().cpu_lock = true;
     }
 
     pub unsafe fn leave_cpu_lock<Traits: PortInstance>(&'static self) {
@@ -422,13 +761,50 @@ impl State {
         expect_worker_thread::<Traits>();
 
         let mut lock = self.thread_group.get().unwrap().lock();
-        assert!(lock.scheduler().cpu_lock);
-        lock.scheduler().cpu_lock = false;
-
-        if sched::check_preemption_by_interrupt(self.thread_group.get().unwrap(), &mut lock) {
+        assert!(lock.scheduler().cpu().cpu_lock);
+        lock.scheduler().cpu().cpu_lock = false;
+
+        // In a multiprocessor system, this is also a good opportunity to let
+        // the other processors run
+        if sched::check_preemption_by_interrupt(self.thread_group.get().unwrap(), &mut lock)
+            || Traits::NUM_CPUS > 1
+        {
             drop(lock);
             ums::yield_now();
+        } else {
+            drop(lock);
         }
+
+        self.fuzz_preemption_point();
+    }
+
+    /// A preemption point injected by schedule fuzzing. Randomly takes pending
+    /// interrupts and yields the processor to let the scheduler choose the
+    /// next thread to run (which may be another processor's). With
+    /// [`TimeSource::Virtual`], it also pretends that the code executed so far
+    /// took some time, firing the timer interrupt and device events if this
+    /// makes virtual time reach them.
+    fn fuzz_preemption_point(&'static self) {
+        let thread_group = self.thread_group.get().unwrap();
+        let mut lock = thread_group.lock();
+        let Some(fuzz) = &mut lock.scheduler().fuzz else { return };
+        if !fuzz.gen_preemption() {
+            return;
+        }
+
+        // Host time can't be skipped
+        if self.time_source() == TimeSource::Virtual {
+            let elapsed = fuzz.gen_elapsed_micros();
+            let now = self.virtual_now.fetch_add(elapsed, Ordering::Relaxed) + elapsed;
+            log::trace!("injecting a preemption point at {now}us");
+            self.fire_virtual_events(&mut lock, now);
+        } else {
+            log::trace!("injecting a preemption point");
+        }
+
+        let _ = sched::check_preemption_by_interrupt(thread_group, &mut lock);
+        drop(lock);
+        ums::yield_now();
     }
 
     pub unsafe fn initialize_task_state<Traits: PortInstance>(
@@ -457,6 +833,7 @@ impl State {
 
         (self.thread_group.get().unwrap().lock())
             .scheduler()
+            .cpu()
             .cpu_lock
     }
 
@@ -596,10 +973,47 @@ impl State {
     pub const MAX_TICK_COUNT: UTicks = UTicks::MAX;
     pub const MAX_TIMEOUT: UTicks = UTicks::MAX / 2;
 
-    pub fn tick_count<Traits: PortInstance>(&self) -> UTicks {
+    pub fn tick_count<Traits: PortInstance>(&'static self) -> UTicks {
         expect_worker_thread::<Traits>();
 
-        let origin = if let Some(x) = self.origin.load(Ordering::Acquire) {
+        self.fuzz_preemption_point();
+
+        let micros = self.now_micros();
+
+        /// Implementation of <https://xkcd.com/221/> with a different magic
+        /// number
+        fn get_random_number() -> UTicks {
+            0x00c0ffee
+        }
+
+        // Calculate `micros % MAX_TICK_COUNT + 1` by truncating upper bits. Add
+        // some random number so that the kernel doesn't depend on zero-start.
+        (micros as UTicks).wrapping_add(get_random_number())
+    }
+
+    /// Get the current system time in microseconds.
+    fn now_micros(&self) -> u64 {
+        match self.time_source() {
+            TimeSource::Host => self.host_micros(),
+            TimeSource::Virtual => self.virtual_now.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the host time elapsed since the origin point in microseconds.
+    fn host_micros(&self) -> u64 {
+        Instant::now()
+            .duration_since(*self.host_origin())
+            .as_micros() as u64
+    }
+
+    /// Convert a value returned by [`Self::host_micros`] to [`Instant`].
+    fn host_instant(&self, micros: u64) -> Instant {
+        *self.host_origin() + Duration::from_micros(micros)
+    }
+
+    /// Get the origin point of [`TimeSource::Host`].
+    fn host_origin(&self) -> &'static Instant {
+        if let Some(x) = self.origin.load(Ordering::Acquire) {
             x
         } else {
             // Establish an origin point.
@@ -630,37 +1044,30 @@ impl State {
                 Ok(_) => origin,      // case 2
                 Err(x) => x.unwrap(), // case 1
             }
-        };
-
-        let micros = Instant::now().duration_since(*origin).as_micros();
-
-        /// Implementation of <https://xkcd.com/221/> with a different magic
-        /// number
-        fn get_random_number() -> UTicks {
-            0x00c0ffee
         }
-
-        // Calculate `micros % MAX_TICK_COUNT + 1` by truncating upper bits. Add
-        // some random number so that the kernel doesn't depend on zero-start.
-        (micros as UTicks).wrapping_add(get_random_number())
     }
 
     pub fn pend_tick_after<Traits: PortInstance>(&self, tick_count_delta: UTicks) {
         expect_worker_thread::<Traits>();
         log::trace!("pend_tick_after({tick_count_delta:?})");
 
-        // Calculate when `timer_tick` should be called
-        let now = Instant::now() + Duration::from_micros(tick_count_delta.into());
-
         // Lock the scheduler because we aren't sure what would happen if
         // `Sender::send` was interrupted
-        let _sched_lock = lock_scheduler::<Traits>();
+        let mut lock = self.thread_group.get().unwrap().lock();
 
-        let timer_cmd_send = self.timer_cmd_send.lock();
-        let timer_cmd_send = timer_cmd_send.as_ref().unwrap();
-        timer_cmd_send
-            .send(TimerCmd::SetTimeout { at: now })
-            .unwrap();
+        // Calculate when `timer_tick` should be called
+        match self.time_source() {
+            TimeSource::Host => {
+                let at = Instant::now() + Duration::from_micros(tick_count_delta.into());
+                let timer_cmd_send = self.timer_cmd_send.lock();
+                let timer_cmd_send = timer_cmd_send.as_ref().unwrap();
+                timer_cmd_send.send(TimerCmd::SetTimeout { at }).unwrap();
+            }
+            TimeSource::Virtual => {
+                let at = self.virtual_now.load(Ordering::Relaxed) + u64::from(tick_count_delta);
+                lock.scheduler().virtual_deadline = Some(at);
+            }
+        }
     }
 
     pub fn pend_tick<Traits: PortInstance>(&'static self) {
@@ -747,6 +1154,9 @@ pub fn lock_scheduler<Traits: PortInstance>() -> impl Sized {
 #[macro_export]
 macro_rules! use_port {
     (unsafe $vis:vis struct $SystemTraits:ident) => {
+        $crate::use_port!(unsafe $vis struct $SystemTraits, num_cpus = 1);
+    };
+    (unsafe $vis:vis struct $SystemTraits:ident, num_cpus = $num_cpus:expr) => {
         $vis struct $SystemTraits;
 
         mod port_std_impl {
@@ -774,6 +1184,7 @@ macro_rules! use_port {
                 type PortTaskState = TaskState;
                 #[allow(clippy::declare_interior_mutable_const)]
                 const PORT_TASK_STATE_INIT: Self::PortTaskState = TaskState::new();
+                const NUM_CPUS: usize = $num_cpus;
 
                 unsafe fn dispatch_first_task() -> ! {
                     PORT_STATE.dispatch_first_task::<Self>()
@@ -814,6 +1225,14 @@ macro_rules! use_port {
                 fn is_scheduler_active() -> bool {
                     PORT_STATE.is_scheduler_active::<Self>()
                 }
+
+                fn current_cpu() -> usize {
+                    PORT_STATE.current_cpu::<Self>()
+                }
+
+                unsafe fn yield_cpu_on(cpu: usize) {
+                    PORT_STATE.yield_cpu_on::<Self>(cpu)
+                }
             }
 
             unsafe impl PortInterrupts for $SystemTraits {
@@ -0,0 +1,514 @@
+//! Simulated peripheral devices
+//!
+//! A [`Device`] is a model of a hardware peripheral attached to a port
+//! instance by [`attach_device`]. Devices can
+//!
+//!  - own interrupt lines ([`DeviceContext::claim_interrupt_line`]) and pend
+//!    them ([`DeviceContext::pend_interrupt_line`]),
+//!
+//!  - schedule events at points in the simulated time
+//!    ([`DeviceContext::schedule`]), which advances according to the port's
+//!    [`TimeSource`], and
+//!
+//!  - exchange data with the firmware through typed [`Register`]s, optionally
+//!    getting notified when the firmware accesses them
+//!    ([`DeviceContext::watched_register`]).
+//!
+//! All device code runs while the simulated hardware scheduler is locked, so
+//! devices don't need any synchronization of their own and never observe the
+//! firmware in the middle of being preempted.
+//!
+//! This module comes with reference models of a [UART](uart) and
+//! [GPIO](gpio) controller.
+//!
+//! [`TimeSource`]: crate::TimeSource
+use r3_core::kernel::{ClearInterruptLineError, InterruptNum, PendInterruptLineError};
+use spin::Mutex as SpinMutex;
+use std::{
+    any::Any,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use crate::{
+    sched, ums, PortInstance, State, ThreadRole, TimeSource, TimerCmd, INTERRUPT_LINE_DISPATCH,
+    INTERRUPT_LINE_TIMER, NUM_INTERRUPT_LINES, THREAD_ROLE,
+};
+
+pub mod gpio;
+pub mod uart;
+
+/// A model of a hardware peripheral.
+pub trait Device: Send + 'static {
+    /// The firmware-facing interface of the device, usually a set of
+    /// [`Register`]s. Returned by [`DeviceHandle::registers`].
+    type Registers: Clone + Send + Sync + 'static;
+
+    /// Called by [`attach_device`] to claim interrupt lines, create registers,
+    /// and schedule initial events.
+    fn attach(&mut self, cx: &mut DeviceContext<'_>) -> Result<Self::Registers, AttachDeviceError>;
+
+    /// Handle an event.
+    fn handle_event(&mut self, cx: &mut DeviceContext<'_>, event: Event);
+}
+
+/// An event delivered to [`Device::handle_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// An event scheduled by [`DeviceContext::schedule`] with the contained
+    /// token is due.
+    Scheduled(u64),
+    /// The firmware has read a register created by
+    /// [`DeviceContext::watched_register`] with the contained token.
+    RegisterRead(u64),
+    /// The firmware has written a register created by
+    /// [`DeviceContext::watched_register`] with the contained token.
+    RegisterWritten(u64),
+}
+
+/// Error type for [`attach_device`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachDeviceError {
+    /// The interrupt line number is out of range or reserved by the port.
+    BadInterruptLine,
+    /// The interrupt line is already owned by another device.
+    InterruptLineInUse,
+}
+
+/// Identifies a device attached to a port instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceId(usize);
+
+/// The object-safe subset of [`Device`].
+trait DynDevice: Send {
+    fn handle_event(&mut self, cx: &mut DeviceContext<'_>, event: Event);
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<D: Device> DynDevice for D {
+    fn handle_event(&mut self, cx: &mut DeviceContext<'_>, event: Event) {
+        Device::handle_event(self, cx, event)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The devices attached to a port instance. Owned by [`sched::SchedState`].
+pub(crate) struct Devices {
+    /// Indexed by [`DeviceId`]. An element is `None` while the device is
+    /// handling an event or if attaching the device has failed.
+    slots: Vec<Option<Box<dyn DynDevice>>>,
+    line_owners: HashMap<InterruptNum, DeviceId>,
+    /// Scheduled events, ordered by `(time, sequence number)`.
+    events: BinaryHeap<Reverse<(u64, u64, DeviceId, u64)>>,
+    next_seq: u64,
+}
+
+impl Devices {
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            line_owners: HashMap::new(),
+            events: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Get the time of the earliest scheduled event in microseconds.
+    pub(crate) fn next_event_time(&self) -> Option<u64> {
+        self.events.peek().map(|&Reverse((at, ..))| at)
+    }
+}
+
+/// Run the device events due at or before `now`. Returns `true` if any events
+/// were run.
+pub(crate) fn run_due_events(
+    state: &'static State,
+    sched_state: &mut sched::SchedState,
+    now: u64,
+) -> bool {
+    let mut ran_any = false;
+
+    while let Some(&Reverse((at, _, device, token))) = sched_state.devices.events.peek() {
+        if at > now {
+            break;
+        }
+        sched_state.devices.events.pop();
+
+        if sched_state.devices.slots[device.0].is_none() {
+            // The device failed to attach
+            continue;
+        }
+
+        log::trace!("running a device event {token} of {device:?} (due at {at}us)");
+        with_device(state, sched_state, device, now, |device, cx| {
+            device.handle_event(cx, Event::Scheduled(token))
+        });
+        ran_any = true;
+    }
+
+    ran_any
+}
+
+/// Call `f` with the specified device temporarily taken out of `sched_state`.
+fn with_device<R>(
+    state: &'static State,
+    sched_state: &mut sched::SchedState,
+    device: DeviceId,
+    now: u64,
+    f: impl FnOnce(&mut dyn DynDevice, &mut DeviceContext<'_>) -> R,
+) -> R {
+    let mut device_obj = sched_state.devices.slots[device.0]
+        .take()
+        .expect("a device can't be accessed while it's handling an event");
+
+    let result = f(
+        &mut *device_obj,
+        &mut DeviceContext {
+            state,
+            sched_state,
+            device,
+            now,
+        },
+    );
+
+    sched_state.devices.slots[device.0] = Some(device_obj);
+    result
+}
+
+/// Lock the scheduler and call `f`, and then take any interrupts pended by
+/// `f`.
+///
+/// This can be called from any thread except for a device event handler,
+/// which already holds the lock.
+fn with_sched_state<R>(
+    state: &'static State,
+    f: impl FnOnce(&mut sched::SchedState, u64) -> R,
+) -> R {
+    let thread_group = state
+        .thread_group
+        .get()
+        .expect("the port hasn't started yet");
+    let mut lock = thread_group.lock();
+    let now = state.now_micros();
+
+    let result = f(lock.scheduler(), now);
+
+    if sched::check_preemption_by_interrupt(thread_group, &mut lock) {
+        if THREAD_ROLE.with(|role| role.get()) == ThreadRole::Unknown {
+            // We are an external thread
+            lock.preempt();
+        } else {
+            drop(lock);
+            ums::yield_now();
+        }
+    } else if lock.scheduler().is_idle() {
+        // `f` might have scheduled a device event. Let the timer thread of
+        // `TimeSource::Virtual` know that it can advance time to it.
+        lock.scheduler().notify_idle();
+    }
+
+    result
+}
+
+/// Attach a device to the port instance.
+///
+/// This can be called from a startup hook, a task, an interrupt handler, or an
+/// external thread, provided that the port has already started.
+pub fn attach_device<Traits: PortInstance, D: Device>(
+    mut device: D,
+) -> Result<DeviceHandle<D>, AttachDeviceError> {
+    let state = Traits::port_state();
+
+    with_sched_state(state, |sched_state, now| {
+        let id = DeviceId(sched_state.devices.slots.len());
+
+        let result = device.attach(&mut DeviceContext {
+            state,
+            sched_state,
+            device: id,
+            now,
+        });
+
+        let registers = match result {
+            Ok(registers) => {
+                sched_state.devices.slots.push(Some(Box::new(device)));
+                registers
+            }
+            Err(e) => {
+                // Leave a tombstone so that the events scheduled by the
+                // failed `attach` are discarded
+                sched_state.devices.slots.push(None);
+                sched_state
+                    .devices
+                    .line_owners
+                    .retain(|_, owner| *owner != id);
+                return Err(e);
+            }
+        };
+
+        log::trace!("attached {id:?}");
+
+        Ok(DeviceHandle {
+            state,
+            id,
+            registers,
+            _phantom: PhantomData,
+        })
+    })
+}
+
+/// A handle to a device attached by [`attach_device`].
+pub struct DeviceHandle<D: Device> {
+    state: &'static State,
+    id: DeviceId,
+    registers: D::Registers,
+    _phantom: PhantomData<fn() -> D>,
+}
+
+impl<D: Device> Clone for DeviceHandle<D> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state,
+            id: self.id,
+            registers: self.registers.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<D: Device> fmt::Debug for DeviceHandle<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DeviceHandle").field(&self.id).finish()
+    }
+}
+
+impl<D: Device> DeviceHandle<D> {
+    /// Get the device's identifier.
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    /// Get the firmware-facing interface of the device.
+    pub fn registers(&self) -> &D::Registers {
+        &self.registers
+    }
+
+    /// Access the device model directly, e.g., to inject input to the device
+    /// or inspect its output from a test harness.
+    ///
+    /// This can be called from any thread except for a device event handler.
+    pub fn with<R>(&self, f: impl FnOnce(&mut D, &mut DeviceContext<'_>) -> R) -> R {
+        with_sched_state(self.state, |sched_state, now| {
+            with_device(self.state, sched_state, self.id, now, |device, cx| {
+                f(device.as_any_mut().downcast_mut().unwrap(), cx)
+            })
+        })
+    }
+}
+
+/// The interface through which a device interacts with the simulated system.
+pub struct DeviceContext<'a> {
+    state: &'static State,
+    sched_state: &'a mut sched::SchedState,
+    device: DeviceId,
+    now: u64,
+}
+
+impl DeviceContext<'_> {
+    /// Get the identifier of the device.
+    pub fn device_id(&self) -> DeviceId {
+        self.device
+    }
+
+    /// Get the current simulated time in microseconds.
+    pub fn now_micros(&self) -> u64 {
+        self.now
+    }
+
+    /// Schedule an event to be delivered to the device as
+    /// [`Event::Scheduled`]`(token)` after `delay_micros` microseconds.
+    ///
+    /// Events scheduled for the same point of time are delivered in the order
+    /// in which they were scheduled.
+    pub fn schedule(&mut self, delay_micros: u64, token: u64) {
+        let devices = &mut self.sched_state.devices;
+        let at = self.now.saturating_add(delay_micros);
+        devices
+            .events
+            .push(Reverse((at, devices.next_seq, self.device, token)));
+        devices.next_seq += 1;
+
+        if self.state.time_source() == TimeSource::Host {
+            // Wake up the timer thread so that it can reschedule itself
+            if let Some(timer_cmd_send) = &*self.state.timer_cmd_send.lock() {
+                let _ = timer_cmd_send.send(TimerCmd::DeviceEvent);
+            }
+        }
+    }
+
+    /// Claim the ownership of an interrupt line.
+    pub fn claim_interrupt_line(&mut self, num: InterruptNum) -> Result<(), AttachDeviceError> {
+        if num >= NUM_INTERRUPT_LINES
+            || num == INTERRUPT_LINE_DISPATCH
+            || num == INTERRUPT_LINE_TIMER
+        {
+            return Err(AttachDeviceError::BadInterruptLine);
+        }
+
+        match self.sched_state.devices.line_owners.get(&num) {
+            Some(&owner) if owner == self.device => Ok(()),
+            Some(_) => Err(AttachDeviceError::InterruptLineInUse),
+            None => {
+                self.sched_state
+                    .devices
+                    .line_owners
+                    .insert(num, self.device);
+                Ok(())
+            }
+        }
+    }
+
+    fn expect_owned_line(&self, num: InterruptNum) -> bool {
+        self.sched_state.devices.line_owners.get(&num) == Some(&self.device)
+    }
+
+    /// Set the pending flag of an interrupt line owned by the device.
+    ///
+    /// The interrupt handler will start running after the device returns
+    /// control to the simulator.
+    pub fn pend_interrupt_line(&mut self, num: InterruptNum) -> Result<(), PendInterruptLineError> {
+        if !self.expect_owned_line(num) {
+            return Err(PendInterruptLineError::BadParam);
+        }
+        self.sched_state
+            .update_line(num, |line| line.pended = true)
+            .map_err(|sched::BadIntLineError| PendInterruptLineError::BadParam)
+    }
+
+    /// Clear the pending flag of an interrupt line owned by the device.
+    pub fn clear_interrupt_line(
+        &mut self,
+        num: InterruptNum,
+    ) -> Result<(), ClearInterruptLineError> {
+        if !self.expect_owned_line(num) {
+            return Err(ClearInterruptLineError::BadParam);
+        }
+        self.sched_state
+            .update_line(num, |line| line.pended = false)
+            .map_err(|sched::BadIntLineError| ClearInterruptLineError::BadParam)
+    }
+
+    /// Create a register owned by the device.
+    pub fn register<T: Send + 'static>(&mut self, init: T) -> Register<T> {
+        self.new_register(init, None)
+    }
+
+    /// Create a register owned by the device. Firmware accesses to the
+    /// register are reported to the device as [`Event::RegisterRead`]`(token)`
+    /// and [`Event::RegisterWritten`]`(token)`.
+    pub fn watched_register<T: Send + 'static>(&mut self, init: T, token: u64) -> Register<T> {
+        self.new_register(init, Some(token))
+    }
+
+    fn new_register<T: Send + 'static>(&mut self, init: T, token: Option<u64>) -> Register<T> {
+        Register {
+            inner: Arc::new(RegisterInner {
+                value: SpinMutex::new(init),
+                state: self.state,
+                device: self.device,
+                token,
+            }),
+        }
+    }
+
+    /// Read a register without notifying its owner.
+    pub fn read<T: Clone>(&self, reg: &Register<T>) -> T {
+        reg.inner.value.lock().clone()
+    }
+
+    /// Write a register without notifying its owner.
+    pub fn write<T>(&mut self, reg: &Register<T>, value: T) {
+        *reg.inner.value.lock() = value;
+    }
+}
+
+/// A typed register-like channel between a device and the firmware.
+///
+/// The firmware accesses the register by [`Self::read`] and [`Self::write`].
+/// The device accesses it by [`DeviceContext::read`] and
+/// [`DeviceContext::write`].
+pub struct Register<T> {
+    inner: Arc<RegisterInner<T>>,
+}
+
+struct RegisterInner<T> {
+    /// The register value. This is only accessed while the scheduler is
+    /// locked, so the spinlock is never contended.
+    value: SpinMutex<T>,
+    state: &'static State,
+    device: DeviceId,
+    /// The token for [`Event::RegisterRead`] and [`Event::RegisterWritten`].
+    token: Option<u64>,
+}
+
+impl<T> Clone for Register<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Register<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Register")
+            .field("device", &self.inner.device)
+            .field("token", &self.inner.token)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + 'static> Register<T> {
+    /// Read the register from the firmware. The device is notified after the
+    /// value is read, so it can implement read-to-clear semantics.
+    ///
+    /// This can be called from any thread except for a device event handler,
+    /// which should use [`DeviceContext::read`] instead.
+    pub fn read(&self) -> T
+    where
+        T: Clone,
+    {
+        let inner = &*self.inner;
+        with_sched_state(inner.state, |sched_state, now| {
+            let value = inner.value.lock().clone();
+            if let Some(token) = inner.token {
+                with_device(inner.state, sched_state, inner.device, now, |device, cx| {
+                    device.handle_event(cx, Event::RegisterRead(token))
+                });
+            }
+            value
+        })
+    }
+
+    /// Write the register from the firmware.
+    ///
+    /// This can be called from any thread except for a device event handler,
+    /// which should use [`DeviceContext::write`] instead.
+    pub fn write(&self, value: T) {
+        let inner = &*self.inner;
+        with_sched_state(inner.state, |sched_state, now| {
+            *inner.value.lock() = value;
+            if let Some(token) = inner.token {
+                with_device(inner.state, sched_state, inner.device, now, |device, cx| {
+                    device.handle_event(cx, Event::RegisterWritten(token))
+                });
+            }
+        })
+    }
+}
@@ -0,0 +1,159 @@
+//! A reference model of a 32-pin GPIO controller
+//!
+//! # Registers
+//!
+//! | Register                              | Access | Description            |
+//! | ------------------------------------- | ------ | ---------------------- |
+//! | [`GpioRegisters::input`]              | R      | The input levels of the pins. |
+//! | [`GpioRegisters::output`]             | RW     | The output levels of the pins. |
+//! | [`GpioRegisters::interrupt_enable`]   | RW     | The pins whose input level changes are latched in `interrupt_status`. |
+//! | [`GpioRegisters::interrupt_status`]   | RW     | The pins whose input level has changed. Writing `1` to a bit clears it. |
+//!
+//! The interrupt line is pended whenever `interrupt_status` becomes or remains
+//! non-zero after a change.
+use r3_core::kernel::InterruptNum;
+use std::collections::HashMap;
+
+use super::{AttachDeviceError, Device, DeviceContext, Event, Register};
+
+/// The registers of [`Gpio`].
+#[derive(Debug, Clone)]
+pub struct GpioRegisters {
+    pub input: Register<u32>,
+    pub output: Register<u32>,
+    pub interrupt_enable: Register<u32>,
+    pub interrupt_status: Register<u32>,
+}
+
+const REG_OUTPUT: u64 = 0;
+const REG_INTERRUPT_ENABLE: u64 = 1;
+const REG_INTERRUPT_STATUS: u64 = 2;
+/// Tokens for input changes scheduled by [`Gpio::schedule_input`] start from
+/// this value.
+const EVENT_INPUT_BASE: u64 = 1 << 32;
+
+/// A model of a 32-pin GPIO controller.
+///
+/// The host side drives input pins by [`Self::set_input`] or
+/// [`Self::schedule_input`] and observes output pins by [`Self::output`] or
+/// [`Self::output_history`], all of which are accessible through
+/// [`DeviceHandle::with`](super::DeviceHandle::with).
+#[derive(Debug)]
+pub struct Gpio {
+    interrupt_line: InterruptNum,
+    registers: Option<GpioRegisters>,
+    input: u32,
+    interrupt_status: u32,
+    /// The output level changes, each of which is represented by a tuple of
+    /// the time (in microseconds) and the new output levels.
+    output_history: Vec<(u64, u32)>,
+    scheduled_inputs: HashMap<u64, (u32, bool)>,
+    next_input_token: u64,
+}
+
+impl Gpio {
+    pub fn new(interrupt_line: InterruptNum) -> Self {
+        Self {
+            interrupt_line,
+            registers: None,
+            input: 0,
+            interrupt_status: 0,
+            output_history: Vec::new(),
+            scheduled_inputs: HashMap::new(),
+            next_input_token: EVENT_INPUT_BASE,
+        }
+    }
+
+    /// Set the input level of a pin.
+    pub fn set_input(&mut self, cx: &mut DeviceContext<'_>, pin: u32, level: bool) {
+        assert!(pin < 32, "pin number out of range");
+
+        let new_input = (self.input & !(1 << pin)) | (u32::from(level) << pin);
+        let changed = self.input ^ new_input;
+        self.input = new_input;
+
+        let regs = self.registers.as_ref().unwrap();
+        cx.write(&regs.input, new_input);
+        self.interrupt_status |= changed & cx.read(&regs.interrupt_enable);
+        self.update_interrupt(cx);
+    }
+
+    /// Set the input level of a pin after the specified delay.
+    pub fn schedule_input(
+        &mut self,
+        cx: &mut DeviceContext<'_>,
+        delay_micros: u64,
+        pin: u32,
+        level: bool,
+    ) {
+        assert!(pin < 32, "pin number out of range");
+
+        let token = self.next_input_token;
+        self.next_input_token += 1;
+        self.scheduled_inputs.insert(token, (pin, level));
+        cx.schedule(delay_micros, token);
+    }
+
+    /// Get the current output levels.
+    pub fn output(&self, cx: &DeviceContext<'_>) -> u32 {
+        cx.read(&self.registers.as_ref().unwrap().output)
+    }
+
+    /// Get the output level changes, each of which is represented by a tuple
+    /// of the time (in microseconds) and the new output levels.
+    pub fn output_history(&self) -> &[(u64, u32)] {
+        &self.output_history
+    }
+
+    /// Reflect `interrupt_status` to the register and pend an interrupt if
+    /// it's non-zero.
+    fn update_interrupt(&mut self, cx: &mut DeviceContext<'_>) {
+        let regs = self.registers.as_ref().unwrap();
+        cx.write(&regs.interrupt_status, self.interrupt_status);
+        if self.interrupt_status != 0 {
+            cx.pend_interrupt_line(self.interrupt_line).unwrap();
+        }
+    }
+}
+
+impl Device for Gpio {
+    type Registers = GpioRegisters;
+
+    fn attach(&mut self, cx: &mut DeviceContext<'_>) -> Result<GpioRegisters, AttachDeviceError> {
+        cx.claim_interrupt_line(self.interrupt_line)?;
+
+        let registers = GpioRegisters {
+            input: cx.register(0),
+            output: cx.watched_register(0, REG_OUTPUT),
+            interrupt_enable: cx.watched_register(0, REG_INTERRUPT_ENABLE),
+            interrupt_status: cx.watched_register(0, REG_INTERRUPT_STATUS),
+        };
+        self.registers = Some(registers.clone());
+        Ok(registers)
+    }
+
+    fn handle_event(&mut self, cx: &mut DeviceContext<'_>, event: Event) {
+        let regs = self.registers.as_ref().unwrap();
+        match event {
+            Event::Scheduled(token) => {
+                let (pin, level) = self.scheduled_inputs.remove(&token).unwrap();
+                self.set_input(cx, pin, level);
+            }
+            Event::RegisterWritten(REG_OUTPUT) => {
+                let output = cx.read(&regs.output);
+                if self.output_history.last().map(|&(_, x)| x) != Some(output) {
+                    self.output_history.push((cx.now_micros(), output));
+                }
+            }
+            Event::RegisterWritten(REG_INTERRUPT_ENABLE) => {
+                // Disabling a pin doesn't clear its latched status
+            }
+            Event::RegisterWritten(REG_INTERRUPT_STATUS) => {
+                // Write-1-to-clear
+                self.interrupt_status &= !cx.read(&regs.interrupt_status);
+                self.update_interrupt(cx);
+            }
+            Event::RegisterRead(_) | Event::RegisterWritten(_) => {}
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! A reference model of a UART
+//!
+//! # Registers
+//!
+//! | Register                  | Access | Description                       |
+//! | ------------------------- | ------ | --------------------------------- |
+//! | [`UartRegisters::tx_data`] | W     | Writing starts transmitting a byte. Ignored unless [`UartStatus::tx_ready`] is set. |
+//! | [`UartRegisters::rx_data`] | R     | The oldest byte in the receive FIFO. Reading removes the byte from the FIFO. |
+//! | [`UartRegisters::status`]  | R     | [`UartStatus`]                    |
+//! | [`UartRegisters::control`] | RW    | [`UartControl`]                   |
+//!
+//! The interrupt line is pended whenever an event that is enabled by
+//! [`UartControl`] occurs or is found active after a register access.
+use r3_core::kernel::InterruptNum;
+use std::collections::VecDeque;
+
+use super::{AttachDeviceError, Device, DeviceContext, Event, Register};
+
+/// The configuration of [`Uart`].
+#[derive(Debug, Clone, Copy)]
+pub struct UartConfig {
+    /// The interrupt line used by the UART.
+    pub interrupt_line: InterruptNum,
+    /// The time it takes to transmit or receive one byte, measured in
+    /// microseconds.
+    pub byte_time_micros: u64,
+    /// The capacity of the receive FIFO. Received bytes that don't fit in the
+    /// FIFO are discarded.
+    pub rx_fifo_len: usize,
+}
+
+impl UartConfig {
+    /// Construct `UartConfig` with a byte time equivalent to 115200 baud (8N1)
+    /// and a 16-byte receive FIFO.
+    pub const fn new(interrupt_line: InterruptNum) -> Self {
+        Self {
+            interrupt_line,
+            byte_time_micros: 87,
+            rx_fifo_len: 16,
+        }
+    }
+}
+
+/// The registers of [`Uart`].
+#[derive(Debug, Clone)]
+pub struct UartRegisters {
+    pub tx_data: Register<u8>,
+    pub rx_data: Register<u8>,
+    pub status: Register<UartStatus>,
+    pub control: Register<UartControl>,
+}
+
+/// The value of [`UartRegisters::status`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UartStatus {
+    /// The transmitter is ready to accept a byte.
+    pub tx_ready: bool,
+    /// The receive FIFO is not empty.
+    pub rx_ready: bool,
+    /// Received bytes have been discarded because the receive FIFO was full.
+    /// Cleared by reading [`UartRegisters::rx_data`].
+    pub rx_overrun: bool,
+}
+
+/// The value of [`UartRegisters::control`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UartControl {
+    /// Pend the interrupt line when [`UartStatus::tx_ready`] is set.
+    pub tx_interrupt: bool,
+    /// Pend the interrupt line when [`UartStatus::rx_ready`] is set.
+    pub rx_interrupt: bool,
+}
+
+const EVENT_TX_DONE: u64 = 0;
+const EVENT_RX_BYTE: u64 = 1;
+const REG_TX_DATA: u64 = 2;
+const REG_RX_DATA: u64 = 3;
+const REG_CONTROL: u64 = 4;
+
+/// A model of a UART.
+///
+/// The host side feeds input by [`Self::receive`] and collects output by
+/// [`Self::take_output`], both of which are accessible through
+/// [`DeviceHandle::with`](super::DeviceHandle::with).
+#[derive(Debug)]
+pub struct Uart {
+    config: UartConfig,
+    registers: Option<UartRegisters>,
+    /// Bytes being sent to the UART, one of which is moved to `rx_fifo` per
+    /// byte time
+    rx_line: VecDeque<u8>,
+    rx_fifo: VecDeque<u8>,
+    tx_busy: bool,
+    output: Vec<u8>,
+}
+
+impl Uart {
+    pub fn new(config: UartConfig) -> Self {
+        Self {
+            config,
+            registers: None,
+            rx_line: VecDeque::new(),
+            rx_fifo: VecDeque::new(),
+            tx_busy: false,
+            output: Vec::new(),
+        }
+    }
+
+    /// Start sending bytes to the UART. The bytes will arrive at the receive
+    /// FIFO one by one at the configured rate.
+    pub fn receive(&mut self, cx: &mut DeviceContext<'_>, bytes: &[u8]) {
+        if self.rx_line.is_empty() && !bytes.is_empty() {
+            cx.schedule(self.config.byte_time_micros, EVENT_RX_BYTE);
+        }
+        self.rx_line.extend(bytes);
+    }
+
+    /// Take the bytes transmitted by the UART so far.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn registers(&self) -> &UartRegisters {
+        self.registers.as_ref().unwrap()
+    }
+
+    /// Update the status register and pend an interrupt if it's enabled.
+    fn update(&mut self, cx: &mut DeviceContext<'_>) {
+        let regs = self.registers();
+        let mut status = cx.read(&regs.status);
+        status.tx_ready = !self.tx_busy;
+        status.rx_ready = !self.rx_fifo.is_empty();
+        cx.write(&regs.status, status);
+        cx.write(&regs.rx_data, self.rx_fifo.front().copied().unwrap_or(0));
+
+        let control = cx.read(&regs.control);
+        if (control.tx_interrupt && status.tx_ready) || (control.rx_interrupt && status.rx_ready) {
+            cx.pend_interrupt_line(self.config.interrupt_line).unwrap();
+        }
+    }
+}
+
+impl Device for Uart {
+    type Registers = UartRegisters;
+
+    fn attach(&mut self, cx: &mut DeviceContext<'_>) -> Result<UartRegisters, AttachDeviceError> {
+        cx.claim_interrupt_line(self.config.interrupt_line)?;
+
+        let registers = UartRegisters {
+            tx_data: cx.watched_register(0, REG_TX_DATA),
+            rx_data: cx.watched_register(0, REG_RX_DATA),
+            status: cx.register(UartStatus {
+                tx_ready: true,
+                ..UartStatus::default()
+            }),
+            control: cx.watched_register(UartControl::default(), REG_CONTROL),
+        };
+        self.registers = Some(registers.clone());
+        Ok(registers)
+    }
+
+    fn handle_event(&mut self, cx: &mut DeviceContext<'_>, event: Event) {
+        match event {
+            Event::Scheduled(EVENT_TX_DONE) => {
+                self.tx_busy = false;
+            }
+            Event::Scheduled(EVENT_RX_BYTE) => {
+                let byte = self.rx_line.pop_front().unwrap();
+                if self.rx_fifo.len() < self.config.rx_fifo_len {
+                    self.rx_fifo.push_back(byte);
+                } else {
+                    log::warn!("UART receive FIFO overrun");
+                    let regs = self.registers();
+                    let mut status = cx.read(&regs.status);
+                    status.rx_overrun = true;
+                    cx.write(&regs.status, status);
+                }
+
+                if !self.rx_line.is_empty() {
+                    cx.schedule(self.config.byte_time_micros, EVENT_RX_BYTE);
+                }
+            }
+            Event::RegisterWritten(REG_TX_DATA) => {
+                if self.tx_busy {
+                    log::warn!("UART transmitter is busy; discarding a byte");
+                    return;
+                }
+                let byte = cx.read(&self.registers().tx_data);
+                self.output.push(byte);
+                self.tx_busy = true;
+                cx.schedule(self.config.byte_time_micros, EVENT_TX_DONE);
+            }
+            Event::RegisterRead(REG_RX_DATA) => {
+                self.rx_fifo.pop_front();
+                let regs = self.registers();
+                let mut status = cx.read(&regs.status);
+                status.rx_overrun = false;
+                cx.write(&regs.status, status);
+            }
+            Event::RegisterWritten(REG_CONTROL) => {}
+            Event::RegisterRead(_) | Event::RegisterWritten(_) => return,
+            Event::Scheduled(_) => unreachable!(),
+        }
+
+        self.update(cx);
+    }
+}
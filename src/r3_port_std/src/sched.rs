@@ -4,25 +4,45 @@ use r3_core::{
     utils::Init,
 };
 use r3_kernel::KernelTraits;
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::mpsc,
+};
 
-use crate::{ums, ThreadRole, NUM_INTERRUPT_LINES, THREAD_ROLE};
+use crate::{
+    fuzz, peripheral, ums, ThreadRole, TimerCmd, INTERRUPT_LINE_DISPATCH, NUM_INTERRUPT_LINES,
+    THREAD_ROLE,
+};
 
 /// The state of the simulated hardware scheduler.
 pub struct SchedState {
     /// Interrupt lines.
     int_lines: HashMap<InterruptNum, IntLine>,
-    /// `int_lines.iter().filter(|_,a| a.pended && a.enable)
-    /// .map(|i,a| (a.priority, i)).collect()`.
-    pended_lines: BTreeSet<(InterruptPriority, InterruptNum)>,
-    active_int_handlers: Vec<(InterruptPriority, ums::ThreadId)>,
-    pub cpu_lock: bool,
 
-    /// The currently-selected task thread.
-    pub task_thread: Option<ums::ThreadId>,
+    /// The simulated processors. The element at index `i` represents the
+    /// processor `i`.
+    pub cpus: Vec<Cpu>,
+
+    /// The processor the currently-running thread belongs to.
+    pub current_cpu: usize,
+
+    /// Garbage can. Each thread is associated with the processor it was
+    /// running on.
+    zombies: Vec<(ums::ThreadId, usize)>,
 
-    /// Garbage can
-    zombies: Vec<ums::ThreadId>,
+    /// Used to send [`TimerCmd::Idle`] to the timer thread when the system
+    /// goes idle.
+    pub(crate) idle_send: Option<mpsc::Sender<TimerCmd>>,
+
+    /// The deadline of [`crate::TimeSource::Virtual`], measured in
+    /// microseconds.
+    pub virtual_deadline: Option<u64>,
+
+    /// The state of schedule fuzzing. `None` if it's disabled.
+    pub fuzz: Option<fuzz::SchedFuzzer>,
+
+    /// Simulated peripheral devices.
+    pub(crate) devices: peripheral::Devices,
 }
 
 /// The configuration of an interrupt line.
@@ -43,17 +63,66 @@ impl Init for IntLine {
     };
 }
 
+/// The state of a simulated processor.
+///
+/// Interrupt lines are routed to the processor `0`, except for
+/// [`INTERRUPT_LINE_DISPATCH`], which is banked (each processor has its own
+/// pending flag) so that a processor can pend it for another processor.
+pub struct Cpu {
+    /// The pended lines that can be taken by this processor.
+    /// `int_lines.iter().filter(|_,a| a.pended && a.enable)
+    /// .map(|i,a| (a.priority, i)).collect()` for the processor `0`, plus
+    /// `INTERRUPT_LINE_DISPATCH` if `dispatch_pended`.
+    pended_lines: BTreeSet<(InterruptPriority, InterruptNum)>,
+    /// The banked pending flag of [`INTERRUPT_LINE_DISPATCH`].
+    dispatch_pended: bool,
+    active_int_handlers: Vec<(InterruptPriority, ums::ThreadId)>,
+    pub cpu_lock: bool,
+
+    /// The currently-selected task thread.
+    pub task_thread: Option<ums::ThreadId>,
+}
+
+impl Init for Cpu {
+    const INIT: Self = Cpu {
+        pended_lines: BTreeSet::new(),
+        dispatch_pended: false,
+        active_int_handlers: Vec::new(),
+        cpu_lock: false,
+        task_thread: None,
+    };
+}
+
+impl Cpu {
+    /// Get the thread the processor is currently running, if any.
+    fn thread(&self) -> Option<ums::ThreadId> {
+        if let Some(&(_, thread_id)) = self.active_int_handlers.last() {
+            Some(thread_id)
+        } else {
+            self.task_thread
+        }
+    }
+}
+
 pub struct BadIntLineError;
 
 impl SchedState {
     pub fn new<Traits: KernelTraits>() -> Self {
+        let mut cpus: Vec<Cpu> = (0..Traits::NUM_CPUS).map(|_| Cpu::INIT).collect();
+
+        // The processor `0` boots the kernel. The other processors are
+        // started by `State::dispatch_first_task`.
+        cpus[0].cpu_lock = true;
+
         let mut this = Self {
             int_lines: HashMap::new(),
-            pended_lines: BTreeSet::new(),
-            active_int_handlers: Vec::new(),
-            cpu_lock: true,
-            task_thread: None,
+            cpus,
+            current_cpu: 0,
             zombies: Vec::new(),
+            idle_send: None,
+            virtual_deadline: None,
+            fuzz: None,
+            devices: peripheral::Devices::new(),
         };
 
         for i in 0..NUM_INTERRUPT_LINES {
@@ -80,49 +149,122 @@ impl SchedState {
             return Err(BadIntLineError);
         }
         let line = self.int_lines.entry(i).or_insert_with(|| IntLine::INIT);
-        self.pended_lines.remove(&(line.priority, i));
+        let old_priority = line.priority;
         f(line);
-        if line.enable && line.pended {
-            self.pended_lines.insert((line.priority, i));
+
+        if i == INTERRUPT_LINE_DISPATCH && std::mem::take(&mut line.pended) {
+            // The line is banked. Pend it for the current processor.
+            self.cpus[self.current_cpu].dispatch_pended = true;
+        }
+
+        for (cpu_i, cpu) in self.cpus.iter_mut().enumerate() {
+            cpu.pended_lines.remove(&(old_priority, i));
+            let pended = if i == INTERRUPT_LINE_DISPATCH {
+                cpu.dispatch_pended
+            } else {
+                cpu_i == 0 && line.pended
+            };
+            if line.enable && pended {
+                cpu.pended_lines.insert((line.priority, i));
+            }
         }
         Ok(())
     }
 
+    /// Pend [`INTERRUPT_LINE_DISPATCH`] for the processor `cpu`.
+    pub fn pend_dispatch(&mut self, cpu: usize) {
+        self.cpus[cpu].dispatch_pended = true;
+        self.update_line(INTERRUPT_LINE_DISPATCH, |_| {})
+            .ok()
+            .unwrap();
+    }
+
+    /// Get the state of the current processor.
+    pub fn cpu(&mut self) -> &mut Cpu {
+        &mut self.cpus[self.current_cpu]
+    }
+
     pub fn is_line_pended(&self, i: InterruptNum) -> Result<bool, BadIntLineError> {
         if i >= NUM_INTERRUPT_LINES {
             return Err(BadIntLineError);
         }
 
-        if let Some(line) = self.int_lines.get(&i) {
+        if i == INTERRUPT_LINE_DISPATCH {
+            Ok(self.cpus[self.current_cpu].dispatch_pended)
+        } else if let Some(line) = self.int_lines.get(&i) {
             Ok(line.pended)
         } else {
             Ok(false)
         }
     }
 
-    /// Schedule the specified thread until it naturally exits.
-    pub fn recycle_thread(&mut self, thread_id: ums::ThreadId) {
-        self.zombies.push(thread_id);
+    /// Get a flag indicating whether there are no threads to run.
+    pub fn is_idle(&self) -> bool {
+        self.zombies.is_empty() && self.cpus.iter().all(|cpu| cpu.thread().is_none())
+    }
+
+    /// Get the number of processors that have a thread to run.
+    pub fn num_busy_cpus(&self) -> usize {
+        self.cpus
+            .iter()
+            .filter(|cpu| cpu.thread().is_some())
+            .count()
+    }
+
+    /// Tell the timer thread that the system is idle.
+    pub fn notify_idle(&self) {
+        if let Some(idle_send) = &self.idle_send {
+            // Ignore if the timer thread has already stopped
+            let _ = idle_send.send(TimerCmd::Idle);
+        }
+    }
+
+    /// Schedule the specified thread, which belongs to the processor `cpu`,
+    /// until it naturally exits.
+    pub fn recycle_thread(&mut self, thread_id: ums::ThreadId, cpu: usize) {
+        self.zombies.push((thread_id, cpu));
     }
 }
 
 impl ums::Scheduler for SchedState {
     fn choose_next_thread(&mut self) -> Option<ums::ThreadId> {
-        if let Some(&thread_id) = self.zombies.first() {
+        if let Some(&(thread_id, cpu)) = self.zombies.first() {
             // Clean up zombie threads as soon as possible
-            Some(thread_id)
-        } else if let Some(&(_, thread_id)) = self.active_int_handlers.last() {
-            Some(thread_id)
-        } else if self.cpu_lock {
-            // CPU Lock owned by a task thread
-            Some(self.task_thread.unwrap())
-        } else {
-            self.task_thread
+            self.current_cpu = cpu;
+            return Some(thread_id);
+        }
+
+        // A processor in a CPU Lock state runs exclusively. This makes kernel
+        // operations appear atomic to the other processors, so the kernel's
+        // spinlock is never contended.
+        if let Some(cpu) = self.cpus.iter().position(|cpu| cpu.cpu_lock) {
+            self.current_cpu = cpu;
+            // CPU Lock owned by an interrupt or task thread
+            return Some(self.cpus[cpu].thread().unwrap());
         }
+
+        // Otherwise, the processors take turns (or are chosen randomly if
+        // schedule fuzzing is enabled)
+        let num_cpus = self.cpus.len();
+        let start = match &mut self.fuzz {
+            Some(fuzz) if num_cpus > 1 => fuzz.gen_index(num_cpus),
+            _ => self.current_cpu + 1,
+        };
+        for i in 0..num_cpus {
+            let cpu = (start + i) % num_cpus;
+            if let Some(thread_id) = self.cpus[cpu].thread() {
+                self.current_cpu = cpu;
+                return Some(thread_id);
+            }
+        }
+
+        // The system is idle
+        self.notify_idle();
+        None
     }
 
     fn thread_exited(&mut self, thread_id: ums::ThreadId) {
-        let Some(i) = self.zombies.iter().position(|id| *id == thread_id)
+        let Some(i) = self.zombies.iter().position(|(id, _)| *id == thread_id)
         else {
             log::warn!("thread_exited: unexpected thread {thread_id:?}");
             return;
@@ -134,7 +276,8 @@ impl ums::Scheduler for SchedState {
 }
 
 /// Check for any pending interrupts that can be activated under the current
-/// condition. If there are one or more of them, activate them and return
+/// condition on any processor. If there are one or more of them, activate them
+/// and return
 /// `true`, in which case the caller should call
 /// [`ums::ThreadGroupLockGuard::preempt`], [`ums::yield_now`],
 /// [`ums::exit_thread`].
@@ -149,25 +292,41 @@ pub fn check_preemption_by_interrupt(
 ) -> bool {
     let mut activated_any = false;
 
+    for cpu_i in 0..lock.scheduler().cpus.len() {
+        activated_any |= check_preemption_by_interrupt_on(thread_group, lock, cpu_i);
+    }
+
+    activated_any
+}
+
+/// [`check_preemption_by_interrupt`] for the processor `cpu_i`.
+fn check_preemption_by_interrupt_on(
+    thread_group: &'static ums::ThreadGroup<SchedState>,
+    lock: &mut ums::ThreadGroupLockGuard<SchedState>,
+    cpu_i: usize,
+) -> bool {
+    let mut activated_any = false;
+
     // Check pending interrupts
     loop {
         let sched_state = lock.scheduler();
+        let cpu = &mut sched_state.cpus[cpu_i];
 
         // Find the highest pended priority
-        let Some(&(pri, num)) = sched_state.pended_lines.iter().next()
+        let Some(&(pri, num)) = cpu.pended_lines.iter().next()
         else {
             // No interrupt is pended
             break;
         };
 
         // Masking by CPU Lock
-        if sched_state.cpu_lock && is_interrupt_priority_managed(pri) {
+        if cpu.cpu_lock && is_interrupt_priority_managed(pri) {
             log::trace!("not handling an interrupt with priority {pri} because of CPU Lock");
             break;
         }
 
         // Masking by an already active interrupt
-        if let Some(&(existing_pri, _)) = sched_state.active_int_handlers.last() {
+        if let Some(&(existing_pri, _)) = cpu.active_int_handlers.last() {
             if existing_pri < pri {
                 log::trace!(
                     "not handling an interrupt with priority {pri} because of \
@@ -177,8 +336,23 @@ pub fn check_preemption_by_interrupt(
             }
         }
 
+        // If schedule fuzzing is enabled, choose randomly from the pended
+        // interrupts with the same priority
+        let num = if let Some(fuzz) = &mut sched_state.fuzz {
+            let mut candidates = cpu
+                .pended_lines
+                .range((pri, InterruptNum::MIN)..=(pri, InterruptNum::MAX));
+            let num_candidates = candidates.clone().count();
+            candidates.nth(fuzz.gen_index(num_candidates)).unwrap().1
+        } else {
+            num
+        };
+
         // Take the interrupt
-        sched_state.pended_lines.remove(&(pri, num));
+        cpu.pended_lines.remove(&(pri, num));
+        if num == INTERRUPT_LINE_DISPATCH {
+            cpu.dispatch_pended = false;
+        }
 
         // Find the interrupt handler for `num`. Return
         // `default_interrupt_handler` if there's none.
@@ -197,19 +371,27 @@ pub fn check_preemption_by_interrupt(
             let mut lock = thread_group.lock();
 
             // Make this interrupt handler inactive
-            let (_, popped_thread_id) = lock.scheduler().active_int_handlers.pop().unwrap();
+            let (_, popped_thread_id) = lock.scheduler().cpus[cpu_i]
+                .active_int_handlers
+                .pop()
+                .unwrap();
             assert_eq!(thread_id, popped_thread_id);
             log::trace!("an interrupt handler for an interrupt {num} (priority = {pri}) exited");
 
             // Make sure this thread will run to completion
-            lock.scheduler().zombies.push(thread_id);
+            lock.scheduler().recycle_thread(thread_id, cpu_i);
 
             let _ = check_preemption_by_interrupt(thread_group, &mut lock);
         });
 
-        log::trace!("handling an interrupt {num} (priority = {pri}) with thread {thread_id:?}");
+        log::trace!(
+            "handling an interrupt {num} (priority = {pri}) on processor {cpu_i} \
+            with thread {thread_id:?}"
+        );
 
-        lock.scheduler().active_int_handlers.push((pri, thread_id));
+        lock.scheduler().cpus[cpu_i]
+            .active_int_handlers
+            .push((pri, thread_id));
 
         activated_any = true;
     }
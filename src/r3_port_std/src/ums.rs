@@ -191,14 +191,51 @@ impl<'a, Sched: Scheduler> ThreadGroupLockGuard<'a, Sched> {
         // Preeempt the current thread
         let guard = &mut *self.guard;
         log::trace!("preempting {:?}", guard.cur_thread_id);
-        if let Some(thread_id) = guard.cur_thread_id {
-            let join_handle = guard.threads[thread_id.0].join_handle.as_ref().unwrap();
-            join_handle.thread().park();
+        if let Some(thread) = guard.cur_thread() {
+            thread.park();
+        } else if guard.cur_thread_id.is_some() {
+            // The current thread has exited by panicking, in which case the
+            // other threads are left hanging (see `finalize_thread`)
+            return;
         }
 
         guard.unpark_next_thread();
     }
 
+    /// Remote-park the current thread so that no worker threads run until
+    /// [`Self::resume_threads`] is called. The calls to these methods must be
+    /// balanced and must be made while holding the same guard.
+    ///
+    /// Calling this method from a worker thread is not allowed.
+    pub fn stop_threads(&mut self) {
+        assert!(
+            TLB.with(|cell| cell.get().is_none()),
+            "this method cannot be called from a worker thread"
+        );
+        if let Some(thread) = self.guard.cur_thread() {
+            thread.park();
+        }
+    }
+
+    /// Undo [`Self::stop_threads`].
+    pub fn resume_threads(&mut self) {
+        if let Some(thread) = self.guard.cur_thread() {
+            thread.unpark();
+        }
+    }
+
+    /// Call the specified closure with the worker threads stopped by
+    /// [`Self::stop_threads`].
+    ///
+    /// The closure must not allocate memory or take locks that a worker thread
+    /// might be holding, or it might deadlock.
+    pub fn with_threads_stopped<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        self.stop_threads();
+        let result = f();
+        self.resume_threads();
+        result
+    }
+
     /// Initiate graceful shutdown for the thread group.
     ///
     /// The shutdown completes when all threads complete execution. After this
@@ -239,6 +276,15 @@ impl<Sched: Scheduler> State<Sched> {
     }
 }
 
+impl<Sched: ?Sized> State<Sched> {
+    /// Get the currently-scheduled thread. Returns `None` if there's no such
+    /// thread or it has exited by panicking (see `finalize_thread`).
+    fn cur_thread(&self) -> Option<&threading::Thread> {
+        let thread = self.threads.get(self.cur_thread_id?.0)?;
+        Some(thread.join_handle.as_ref().unwrap().thread())
+    }
+}
+
 impl State<dyn Scheduler> {
     /// Find the next thread to run and unpark that thread.
     fn unpark_next_thread(&mut self) {
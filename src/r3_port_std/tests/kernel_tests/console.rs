@@ -0,0 +1,205 @@
+//! Drives the inspection console through a Unix domain socket.
+//!
+//! 1. `task1` enters a CPU Lock state. The client issues `tasks`, which should
+//!    still report the tasks, and lets `task1` proceed.
+//! 2. `task1` keeps incrementing a counter. The client issues `pause` and
+//!    checks that the counter stops. `tasks` should work in this state, too.
+//!    The client issues `resume` and checks that the counter starts moving
+//!    again.
+//! 3. The client issues `pend` to pend an interrupt line, which is detected
+//!    by the interrupt handler.
+use r3_core::kernel::{prelude::*, traits, Cfg, InterruptLine, StaticInterruptHandler, StaticTask};
+use r3_kernel::System;
+use r3_test_suite::kernel_tests::Driver;
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+use r3_port_std::PortInstance;
+
+pub trait SupportedSystemTraits: PortInstance {}
+impl<T: PortInstance> SupportedSystemTraits for T {}
+
+pub struct App<System: traits::KernelBase + traits::KernelInterruptLine + traits::KernelStatic> {
+    int: Option<InterruptLine<System>>,
+}
+
+/// The path of the socket the console listens on.
+pub fn socket_path() -> PathBuf {
+    std::env::temp_dir().join(format!("r3_port_std_console_{}.sock", std::process::id()))
+}
+
+/// The step the test is in. Advanced by the client.
+static STAGE: AtomicUsize = AtomicUsize::new(0);
+/// Set by the client on failure.
+static FAILED: AtomicBool = AtomicBool::new(false);
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+static PENDED: AtomicBool = AtomicBool::new(false);
+
+impl<Traits: SupportedSystemTraits> App<System<Traits>> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System<Traits>> + ~const traits::CfgInterruptLine,
+    {
+        StaticTask::define()
+            .start(task1_body::<Traits, D>)
+            .priority(2)
+            .active(true)
+            .finish(b);
+
+        StaticTask::define()
+            .start(|| unreachable!())
+            .priority(3)
+            .finish(b);
+
+        let int = if let (&[int_line, ..], &[int_pri, ..]) =
+            (D::INTERRUPT_LINES, D::INTERRUPT_PRIORITIES)
+        {
+            StaticInterruptHandler::define()
+                .line(int_line)
+                .start(isr)
+                .finish(b);
+
+            Some(
+                InterruptLine::define()
+                    .line(int_line)
+                    .priority(int_pri)
+                    .enabled(true)
+                    .finish(b),
+            )
+        } else {
+            None
+        };
+
+        App { int }
+    }
+}
+
+fn task1_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    let Some(int) = D::app().int
+    else {
+        log::warn!("No interrupt lines defined, skipping the test");
+        D::success();
+        return;
+    };
+
+    if cfg!(not(unix)) {
+        log::warn!("Unix domain sockets are unavailable, skipping the test");
+        D::success();
+        return;
+    }
+
+    spawn(move || {
+        let result = std::panic::catch_unwind(|| client(int.num()));
+        if result.is_err() {
+            FAILED.store(true, Ordering::Relaxed);
+        }
+    });
+
+    // Stage 1
+    System::<Traits>::acquire_cpu_lock().unwrap();
+    STAGE.store(1, Ordering::Relaxed);
+    while STAGE.load(Ordering::Relaxed) < 2 {
+        check_client::<Traits, D>();
+        core::hint::spin_loop();
+    }
+    unsafe { System::<Traits>::release_cpu_lock().unwrap() };
+
+    // Stage 2
+    while STAGE.load(Ordering::Relaxed) < 3 {
+        check_client::<Traits, D>();
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Stage 3
+    while !PENDED.load(Ordering::Relaxed) {
+        check_client::<Traits, D>();
+    }
+
+    D::success();
+}
+
+fn check_client<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    if FAILED.load(Ordering::Relaxed) {
+        D::fail();
+    }
+}
+
+fn isr() {
+    PENDED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+fn client(int_num: r3_core::kernel::InterruptNum) {
+    use std::os::unix::net::UnixStream;
+
+    let stream = loop {
+        match UnixStream::connect(socket_path()) {
+            Ok(stream) => break stream,
+            Err(_) => sleep(Duration::from_millis(10)),
+        }
+    };
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    // Send `command` and receive a response of `num_lines` lines
+    let mut command = |command: &str, num_lines: usize| -> Vec<String> {
+        log::debug!("sending {command:?}");
+        writeln!(writer, "{command}").unwrap();
+        let lines: Vec<String> = (0..num_lines)
+            .map(|_| {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                line.trim_end().to_owned()
+            })
+            .collect();
+        log::debug!("received {lines:#?}");
+        lines
+    };
+
+    // Stage 1: CPU Lock
+    while STAGE.load(Ordering::Relaxed) < 1 {
+        sleep(Duration::from_millis(10));
+    }
+    let lines = command("tasks", 3);
+    assert!(lines[0].starts_with("cpu 0: CPU Lock active"), "{lines:?}");
+    assert!(lines[1].starts_with("task 1: Running"), "{lines:?}");
+    assert!(lines[2].starts_with("task 2: Dormant"), "{lines:?}");
+    STAGE.store(2, Ordering::Relaxed);
+
+    // Stage 2: Pause
+    let wait_for_counter = || {
+        let start = COUNTER.load(Ordering::Relaxed);
+        while COUNTER.load(Ordering::Relaxed) == start {
+            sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for_counter();
+    assert_eq!(command("pause", 1), ["paused"]);
+    let count = COUNTER.load(Ordering::Relaxed);
+    sleep(Duration::from_millis(100));
+    assert_eq!(COUNTER.load(Ordering::Relaxed), count);
+
+    let lines = command("tasks", 2);
+    assert!(lines[0].starts_with("task 1: Running"), "{lines:?}");
+    assert!(lines[1].starts_with("task 2: Dormant"), "{lines:?}");
+
+    assert_eq!(
+        command(&format!("pend {int_num}"), 1),
+        ["error: the system is paused"]
+    );
+
+    assert_eq!(command("resume", 1), ["resumed"]);
+    wait_for_counter();
+    STAGE.store(3, Ordering::Relaxed);
+
+    // Stage 3: Pend
+    assert_eq!(command(&format!("pend {int_num}"), 1), ["ok"]);
+}
+
+#[cfg(not(unix))]
+fn client(_: r3_core::kernel::InterruptNum) {}
@@ -0,0 +1,187 @@
+//! Runs the reference models of simulated peripherals.
+//!
+//! 1. `task1` attaches a UART and a GPIO controller.
+//! 2. The host side sends `hello` to the UART. The UART interrupt handler
+//!    collects the received bytes.
+//! 3. `task1` transmits `world` through the UART by polling its status
+//!    register.
+//! 4. The host side schedules a level change of a GPIO input pin, which is
+//!    detected by the GPIO interrupt handler.
+//! 5. `task1` changes the levels of GPIO output pins, which are observed by the
+//!    host side.
+use r3_core::{
+    kernel::{prelude::*, traits, Cfg, InterruptLine, StaticInterruptHandler, StaticTask},
+    time::Duration,
+};
+use r3_kernel::System;
+use r3_port_std::{
+    peripheral::{
+        attach_device,
+        gpio::Gpio,
+        uart::{Uart, UartConfig, UartControl},
+        AttachDeviceError, DeviceHandle,
+    },
+    PortInstance,
+};
+use r3_test_suite::kernel_tests::Driver;
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    OnceLock,
+};
+
+pub trait SupportedSystemTraits: PortInstance {}
+impl<T: PortInstance> SupportedSystemTraits for T {}
+
+pub struct App<System: traits::KernelBase + traits::KernelInterruptLine> {
+    int: Option<[InterruptLine<System>; 2]>,
+}
+
+static UART: OnceLock<DeviceHandle<Uart>> = OnceLock::new();
+static GPIO: OnceLock<DeviceHandle<Gpio>> = OnceLock::new();
+
+/// The received bytes in little endian
+static RECEIVED: AtomicU64 = AtomicU64::new(0);
+static RECEIVED_LEN: AtomicUsize = AtomicUsize::new(0);
+static GPIO_STATUS: AtomicU32 = AtomicU32::new(0);
+
+impl<Traits: SupportedSystemTraits> App<System<Traits>> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System<Traits>> + ~const traits::CfgInterruptLine,
+    {
+        StaticTask::define()
+            .start(task1_body::<Traits, D>)
+            .priority(1)
+            .active(true)
+            .finish(b);
+
+        let int = if let (&[uart_line, gpio_line, ..], &[uart_pri, gpio_pri, ..]) =
+            (D::INTERRUPT_LINES, D::INTERRUPT_PRIORITIES)
+        {
+            StaticInterruptHandler::define()
+                .line(uart_line)
+                .start(uart_isr::<Traits>)
+                .finish(b);
+            StaticInterruptHandler::define()
+                .line(gpio_line)
+                .start(gpio_isr)
+                .finish(b);
+
+            Some([
+                InterruptLine::define()
+                    .line(uart_line)
+                    .priority(uart_pri)
+                    .enabled(true)
+                    .finish(b),
+                InterruptLine::define()
+                    .line(gpio_line)
+                    .priority(gpio_pri)
+                    .enabled(true)
+                    .finish(b),
+            ])
+        } else {
+            None
+        };
+
+        App { int }
+    }
+}
+
+fn task1_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    let Some([uart_line, gpio_line]) = D::app().int
+    else {
+        log::warn!("Not enough interrupt lines defined, skipping the test");
+        D::success();
+        return;
+    };
+
+    let sleep = || System::<Traits>::sleep(Duration::from_millis(1)).unwrap();
+
+    // Attach devices
+    let uart = attach_device::<Traits, _>(Uart::new(UartConfig {
+        byte_time_micros: 100,
+        ..UartConfig::new(uart_line.num())
+    }))
+    .unwrap();
+    let gpio = attach_device::<Traits, _>(Gpio::new(gpio_line.num())).unwrap();
+    assert_eq!(
+        attach_device::<Traits, _>(Gpio::new(uart_line.num())).unwrap_err(),
+        AttachDeviceError::InterruptLineInUse
+    );
+    UART.set(uart.clone()).unwrap();
+    GPIO.set(gpio.clone()).unwrap();
+
+    // UART reception
+    let uart_regs = uart.registers();
+    uart_regs.control.write(UartControl {
+        rx_interrupt: true,
+        ..UartControl::default()
+    });
+    uart.with(|uart, cx| uart.receive(cx, b"hello"));
+
+    log::debug!("waiting for the UART to receive bytes...");
+    while RECEIVED_LEN.load(Ordering::Relaxed) < 5 {
+        sleep();
+    }
+    assert_eq!(
+        RECEIVED.load(Ordering::Relaxed).to_le_bytes()[..5],
+        *b"hello"
+    );
+
+    // UART transmission
+    for &byte in b"world" {
+        while !uart_regs.status.read().tx_ready {
+            sleep();
+        }
+        uart_regs.tx_data.write(byte);
+    }
+    assert_eq!(uart.with(|uart, _| uart.take_output()), b"world");
+
+    // GPIO input
+    let gpio_regs = gpio.registers();
+    gpio_regs.interrupt_enable.write(1 << 3);
+    gpio.with(|gpio, cx| gpio.schedule_input(cx, 5_000, 3, true));
+
+    log::debug!("waiting for the GPIO input to change...");
+    while GPIO_STATUS.load(Ordering::Relaxed) == 0 {
+        sleep();
+    }
+    assert_eq!(GPIO_STATUS.load(Ordering::Relaxed), 1 << 3);
+    assert_eq!(gpio_regs.input.read(), 1 << 3);
+    assert_eq!(gpio_regs.interrupt_status.read(), 0);
+
+    // GPIO output
+    gpio_regs.output.write(0b101);
+    gpio_regs.output.write(0b101);
+    gpio_regs.output.write(0b100);
+    let history: Vec<u32> =
+        gpio.with(|gpio, _| gpio.output_history().iter().map(|&(_, x)| x).collect());
+    assert_eq!(history, [0b101, 0b100]);
+
+    D::success();
+}
+
+fn uart_isr<Traits: SupportedSystemTraits>() {
+    // Reading `rx_data` might re-pend the interrupt line, and this port lets
+    // an interrupt handler preempt another one with the same priority. Use
+    // CPU Lock to keep the received bytes in order.
+    System::<Traits>::acquire_cpu_lock().unwrap();
+
+    let regs = UART.get().unwrap().registers();
+    while regs.status.read().rx_ready {
+        let byte = regs.rx_data.read();
+        let i = RECEIVED_LEN.load(Ordering::Relaxed);
+        RECEIVED.fetch_or(u64::from(byte) << (i * 8), Ordering::Relaxed);
+        RECEIVED_LEN.store(i + 1, Ordering::Relaxed);
+    }
+
+    // Safety: CPU Lock was acquired by us
+    unsafe { System::<Traits>::release_cpu_lock() }.unwrap();
+}
+
+fn gpio_isr() {
+    let regs = GPIO.get().unwrap().registers();
+    let status = regs.interrupt_status.read();
+    GPIO_STATUS.fetch_or(status, Ordering::Relaxed);
+    regs.interrupt_status.write(status);
+}
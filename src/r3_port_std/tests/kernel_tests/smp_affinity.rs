@@ -0,0 +1,79 @@
+//! Checks that tasks only run on the processors allowed by their affinity and
+//! that tasks pinned to different processors run in parallel.
+//!
+//! Each task is pinned to a different processor. The tasks have the same
+//! priority, and each of them busy-waits until all tasks have started, which
+//! would never happen if they couldn't run in parallel. The last task to
+//! finish completes the test.
+//!
+//! Must be instantiated with at least [`NUM_TASKS`] processors.
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use r3_core::kernel::{prelude::*, task::CpuAffinity, traits, Cfg, StaticTask};
+use r3_kernel::{PortThreading, System};
+use r3_test_suite::kernel_tests::Driver;
+
+use r3_port_std::PortInstance;
+
+pub trait SupportedSystemTraits: PortInstance {}
+impl<T: PortInstance> SupportedSystemTraits for T {}
+
+/// The number of tasks, each of which is pinned to the processor with the same
+/// index.
+const NUM_TASKS: usize = 3;
+
+static NUM_STARTED: AtomicUsize = AtomicUsize::new(0);
+static NUM_FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+pub struct App<System> {
+    _phantom: PhantomData<System>,
+}
+
+impl<Traits: SupportedSystemTraits> App<System<Traits>> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System<Traits>>,
+    {
+        let mut i = 0;
+        while i < NUM_TASKS {
+            StaticTask::define()
+                .start((i, task_body::<Traits, D>))
+                .priority(1)
+                .active(true)
+                .affinity(CpuAffinity::only(i))
+                .finish(b);
+            i += 1;
+        }
+
+        App {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+fn task_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>(i: usize) {
+    log::debug!("task {i} started");
+    assert_eq!(current_cpu::<Traits>(), i);
+
+    NUM_STARTED.fetch_add(1, Ordering::Relaxed);
+    while NUM_STARTED.load(Ordering::Relaxed) < NUM_TASKS {
+        // The task must not migrate to another processor
+        assert_eq!(current_cpu::<Traits>(), i);
+    }
+
+    log::debug!("task {i} finished");
+    if NUM_FINISHED.fetch_add(1, Ordering::Relaxed) + 1 == NUM_TASKS {
+        D::success();
+    }
+}
+
+/// Get the current processor. CPU Lock prevents the calling task from
+/// migrating to another processor during the call.
+fn current_cpu<Traits: SupportedSystemTraits>() -> usize {
+    System::<Traits>::acquire_cpu_lock().unwrap();
+    let cpu = <Traits as PortThreading>::current_cpu();
+    unsafe { System::<Traits>::release_cpu_lock().unwrap() };
+    cpu
+}
@@ -0,0 +1,100 @@
+//! Checks that activating a task preempts a lower-priority task running on
+//! another processor.
+//!
+//! 1. `task1` (pinned to the processor 0) and `task2` (pinned to the processor
+//!    1) start running. `task2` busy-waits until `DONE` is set.
+//!
+//! 2. `task1` activates `task3`, which has a higher priority and is pinned to
+//!    the processor 1. `task3` can only run by preempting `task2`, which never
+//!    yields the processor by itself. `task1` keeps running on the processor
+//!    0 and busy-waits until `DONE` is set.
+//!
+//! 3. `task3` sets `DONE` and exits. `task1` completes the test.
+//!
+//! Must be instantiated with exactly two processors.
+use core::sync::atomic::{AtomicBool, Ordering};
+use r3_core::kernel::{prelude::*, task::CpuAffinity, traits, Cfg, StaticTask};
+use r3_kernel::{PortThreading, System};
+use r3_test_suite::kernel_tests::Driver;
+
+use r3_port_std::PortInstance;
+
+pub trait SupportedSystemTraits: PortInstance {}
+impl<T: PortInstance> SupportedSystemTraits for T {}
+
+static TASK2_STARTED: AtomicBool = AtomicBool::new(false);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+pub struct App<System: traits::KernelBase + traits::KernelStatic> {
+    task3: StaticTask<System>,
+}
+
+impl<Traits: SupportedSystemTraits> App<System<Traits>> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System<Traits>>,
+    {
+        StaticTask::define()
+            .start(task1_body::<Traits, D>)
+            .priority(2)
+            .active(true)
+            .affinity(CpuAffinity::only(0))
+            .finish(b);
+        StaticTask::define()
+            .start(task2_body::<Traits, D>)
+            .priority(2)
+            .active(true)
+            .affinity(CpuAffinity::only(1))
+            .finish(b);
+        let task3 = StaticTask::define()
+            .start(task3_body::<Traits, D>)
+            .priority(1)
+            .affinity(CpuAffinity::only(1))
+            .finish(b);
+
+        App { task3 }
+    }
+}
+
+fn task1_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    assert_eq!(current_cpu::<Traits>(), 0);
+
+    while !TASK2_STARTED.load(Ordering::Relaxed) {
+        core::hint::spin_loop();
+    }
+
+    // `task3` preempts `task2` on the processor 1
+    D::app().task3.activate().unwrap();
+
+    while !DONE.load(Ordering::Relaxed) {
+        core::hint::spin_loop();
+    }
+
+    assert_eq!(current_cpu::<Traits>(), 0);
+    D::success();
+}
+
+fn task2_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    assert_eq!(current_cpu::<Traits>(), 1);
+
+    TASK2_STARTED.store(true, Ordering::Relaxed);
+
+    while !DONE.load(Ordering::Relaxed) {
+        core::hint::spin_loop();
+    }
+}
+
+fn task3_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    assert_eq!(current_cpu::<Traits>(), 1);
+
+    DONE.store(true, Ordering::Relaxed);
+}
+
+/// Get the current processor. CPU Lock prevents the calling task from
+/// migrating to another processor during the call.
+fn current_cpu<Traits: SupportedSystemTraits>() -> usize {
+    System::<Traits>::acquire_cpu_lock().unwrap();
+    let cpu = <Traits as PortThreading>::current_cpu();
+    unsafe { System::<Traits>::release_cpu_lock().unwrap() };
+    cpu
+}
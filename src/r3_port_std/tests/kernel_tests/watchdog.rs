@@ -0,0 +1,108 @@
+//! Checks that `r3_kernel::watchdog` reports a task that stopped feeding the
+//! watchdog, along with the condition the task is waiting for.
+//!
+//! 1. `task1` registers itself to the watchdog and feeds it several times.
+//! 2. `task1` starts waiting for a semaphore that is never signaled.
+//! 3. The watchdog detects the missed deadline and reports `task1` in the
+//!    Waiting state, waiting for the semaphore. The handler signals the
+//!    semaphore.
+//! 4. `task1` wakes up, unregisters itself, and completes the test.
+use r3_core::{
+    kernel::{prelude::*, traits, Cfg, StaticSemaphore, StaticTask},
+    time::Duration,
+};
+use r3_kernel::{
+    watchdog::{MissedDeadline, StaticWatchdog},
+    System, TaskState, WaitTarget,
+};
+use r3_test_suite::kernel_tests::Driver;
+
+use r3_port_std::PortInstance;
+
+pub trait SupportedSystemTraits: PortInstance {}
+impl<T: PortInstance> SupportedSystemTraits for T {}
+
+/// Maps `System<Traits>` back to `Traits`.
+pub trait SystemOf: traits::KernelBase + traits::KernelSemaphore {
+    type Traits: SupportedSystemTraits;
+}
+impl<Traits: SupportedSystemTraits> SystemOf for System<Traits> {
+    type Traits = Traits;
+}
+
+pub struct App<System: SystemOf> {
+    task1: StaticTask<System>,
+    sem: StaticSemaphore<System>,
+    watchdog: StaticWatchdog<System::Traits>,
+}
+
+impl<Traits: SupportedSystemTraits> App<System<Traits>> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System<Traits>>
+            + ~const traits::CfgSemaphore
+            + ~const traits::CfgTimer,
+    {
+        let task1 = StaticTask::define()
+            .start(task1_body::<Traits, D>)
+            .priority(1)
+            .active(true)
+            .finish(b);
+        let sem = StaticSemaphore::define().initial(0).maximum(1).finish(b);
+        let watchdog = StaticWatchdog::define()
+            .period(Duration::from_millis(10))
+            .on_missed_deadline(on_missed_deadline::<Traits, D>)
+            .capacity(1)
+            .finish(b);
+
+        App {
+            task1,
+            sem,
+            watchdog,
+        }
+    }
+}
+
+fn task1_body<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>() {
+    let App { sem, watchdog, .. } = D::app();
+
+    assert_eq!(
+        watchdog.feed(),
+        Err(r3_kernel::watchdog::FeedError::BadObjectState)
+    );
+    watchdog.register(Duration::from_millis(100)).unwrap();
+
+    for _ in 0..5 {
+        System::<Traits>::sleep(Duration::from_millis(50)).unwrap();
+        watchdog.feed().unwrap();
+    }
+
+    // Stop feeding the watchdog. `on_missed_deadline` will wake us up.
+    sem.wait_one().unwrap();
+
+    watchdog.unregister().unwrap();
+    assert_eq!(
+        watchdog.unregister(),
+        Err(r3_kernel::watchdog::FeedError::BadObjectState)
+    );
+    D::success();
+}
+
+fn on_missed_deadline<Traits: SupportedSystemTraits, D: Driver<App<System<Traits>>>>(
+    missed: &MissedDeadline<Traits>,
+) {
+    let App { task1, sem, .. } = D::app();
+    log::trace!("{missed:?}");
+
+    assert_eq!(missed.task, *task1);
+    assert_eq!(missed.deadline, Duration::from_millis(100));
+    assert_eq!(missed.info.state, TaskState::Waiting);
+    assert_eq!(missed.info.base_priority, 1);
+    assert_eq!(missed.info.effective_priority, 1);
+    assert_eq!(
+        missed.info.wait_target,
+        Some(WaitTarget::Semaphore(sem.id()))
+    );
+
+    sem.signal_one().unwrap();
+}
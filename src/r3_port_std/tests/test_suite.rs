@@ -5,6 +5,7 @@
 #![feature(slice_ptr_get)]
 #![feature(slice_ptr_len)]
 #![feature(never_type)]
+#![feature(once_cell)]
 
 extern crate r3_core_ks as r3_core;
 
@@ -31,25 +32,53 @@ impl KernelTestUtil {
         panic!("test failed");
     }
 
-    fn run(&self, func: impl FnOnce()) {
+    fn run(&self, port_state: &r3_port_std::State, func: impl FnOnce()) {
         let _ = env_logger::try_init();
 
-        if let Err(panic_info) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(func)) {
-            std::panic::resume_unwind(panic_info);
-        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(func));
 
-        if self.is_successful.load(Ordering::Relaxed) {
+        if result.is_ok() && self.is_successful.load(Ordering::Relaxed) {
             return;
         }
 
+        // Print the schedule fuzzing seed so that the failure can be
+        // reproduced
+        if let Some(seed) = port_state.sched_fuzz_seed() {
+            eprintln!(
+                "The test failed with schedule fuzzing enabled; \
+                set `R3_PORT_STD_FUZZ_SEED={seed}` to replay"
+            );
+        }
+
+        if let Err(panic_info) = result {
+            std::panic::resume_unwind(panic_info);
+        }
+
         panic!("The program deadlocked without calling `success`");
     }
 }
 
+/// Expands to the [`r3_port_std::TimeSource`] specified by a test entry.
+/// Tests use the host time unless they opt in to virtual time by
+/// `time_source: Virtual`.
+macro_rules! time_source {
+    () => {
+        r3_port_std::TimeSource::Host
+    };
+    ($time_source:ident) => {
+        r3_port_std::TimeSource::$time_source
+    };
+}
+
 mod kernel_tests {
+    pub mod console;
     pub mod external_interrupt;
     pub mod interrupt_table_sparsity;
+    pub mod peripheral;
+    pub mod smp_affinity;
+    pub mod smp_preemption;
     pub mod stack_align;
+    pub mod watchdog;
 }
 
 macro_rules! instantiate_kernel_tests {
@@ -61,13 +90,36 @@ macro_rules! instantiate_kernel_tests {
 
             // Port-specific tests, which cover `r3` and `r3_kernel` as
             // well as `r3_port_std`
+            { path: crate::kernel_tests::console, name_ident: console, console: crate::kernel_tests::console::socket_path(), },
             { path: crate::kernel_tests::external_interrupt, name_ident: external_interrupt, },
             { path: crate::kernel_tests::interrupt_table_sparsity, name_ident: interrupt_table_sparsity, },
+            { path: crate::kernel_tests::peripheral, name_ident: peripheral, time_source: Virtual, },
+            { path: crate::kernel_tests::smp_affinity, name_ident: smp_affinity, num_cpus: 3, },
+            { path: crate::kernel_tests::smp_preemption, name_ident: smp_preemption, num_cpus: 2, },
             { path: crate::kernel_tests::stack_align, name_ident: stack_align, },
+            { path: crate::kernel_tests::watchdog, name_ident: watchdog, time_source: Virtual, },
+
+            // Timing-sensitive tests from `r3_test_suite`, run again with
+            // `TimeSource::Virtual` to check them deterministically
+            { path: r3_test_suite::kernel_tests::semaphore_timeout, name_ident: semaphore_timeout_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::task_park, name_ident: task_park_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::time_adjust_event, name_ident: time_adjust_event_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::time_misc, name_ident: time_misc_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::time_stress, name_ident: time_stress_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::timer_misc, name_ident: timer_misc_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::timer_overdue, name_ident: timer_overdue_virtual_time, time_source: Virtual, },
+            { path: r3_test_suite::kernel_tests::timer_periodic, name_ident: timer_periodic_virtual_time, time_source: Virtual, },
         );
     };
     ( @inner $(
-        { path: $path:path, name_ident: $name_ident:ident, $($rest:tt)* },
+        {
+            path: $path:path,
+            name_ident: $name_ident:ident,
+            $( name_str: $name_str:expr, )?
+            $( num_cpus: $num_cpus:expr, )?
+            $( time_source: $time_source:ident, )?
+            $( console: $console:expr, )?
+        },
     )*) => {$(
         mod $name_ident {
             use r3_core::kernel::{InterruptNum, InterruptPriority};
@@ -75,7 +127,7 @@ macro_rules! instantiate_kernel_tests {
             use $path as test_case;
 
             type System = r3_kernel::System<SystemTraits>;
-            r3_port_std::use_port!(unsafe struct SystemTraits);
+            r3_port_std::use_port!(unsafe struct SystemTraits $(, num_cpus = $num_cpus)?);
 
             struct Driver;
             static TEST_UTIL: super::KernelTestUtil = super::KernelTestUtil::new();
@@ -105,7 +157,13 @@ macro_rules! instantiate_kernel_tests {
 
             #[test]
             fn run() {
-                TEST_UTIL.run(|| {
+                TEST_UTIL.run(&port_std_impl::PORT_STATE, || {
+                    port_std_impl::PORT_STATE.set_time_source(time_source!($($time_source)?));
+                    $(
+                        port_std_impl::PORT_STATE.set_console(
+                            Some(r3_port_std::ConsoleEndpoint::UnixSocket($console)),
+                        );
+                    )?
                     port_std_impl::PORT_STATE.port_boot::<SystemTraits>();
                 });
             }
@@ -0,0 +1,248 @@
+//! The Arm Generic Timer driver shared by the Arm ports. Implements a tickless
+//! port timer based on the physical timer and [`tickless64`](crate::tickless64).
+//!
+//! A port implements `PortTimer` by forwarding the calls to the functions in
+//! this module. The timer registers are accessed as CP15 registers on AArch32
+//! and as system registers on AArch64.
+use r3_core::kernel::{
+    traits, Cfg, InterruptLine, InterruptNum, InterruptPriority, StaticInterruptHandler,
+};
+use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
+
+use crate::tickless64::{Tickless64Cfg, Tickless64Options, Tickless64State};
+
+/// The options for a port's `use_generic_timer!`.
+pub trait GenericTimerOptions {
+    /// The numerator of the frequency of the system counter. This should match
+    /// the value of `CNTFRQ` (`CNTFRQ_EL0` on AArch64).
+    const FREQUENCY: u64;
+
+    /// The denominator of the frequency of the system counter.
+    /// Defaults to `1`.
+    const FREQUENCY_DENOMINATOR: u64 = 1;
+
+    /// The maximum permissible timer interrupt latency, measured in hardware
+    /// timer cycles.
+    ///
+    /// Defaults to `min(FREQUENCY * 60 / FREQUENCY_DENOMINATOR, 0x40000000)`.
+    const HEADROOM: u32 =
+        (Self::FREQUENCY as u128 * 60 / Self::FREQUENCY_DENOMINATOR as u128).min(0x40000000) as u32;
+
+    /// The interrupt priority of the timer interrupt line.
+    /// Defaults to `0xc0`.
+    const INTERRUPT_PRIORITY: InterruptPriority = 0xc0;
+
+    /// The interrupt number (PPI) of the physical timer. This is usually `30`
+    /// (Non-secure physical timer) or `29` (Secure physical timer), depending
+    /// on the Security state the kernel runs in.
+    const INTERRUPT_NUM: InterruptNum;
+}
+
+/// Implemented on a kernel trait type by a port's `use_generic_timer!`.
+///
+/// # Safety
+///
+/// Only meant to be implemented by a port's `use_generic_timer!`.
+pub unsafe trait GenericTimerInstance: KernelTraits + GenericTimerOptions {
+    const TICKLESS_CFG: Tickless64Cfg = match Tickless64Cfg::new(Tickless64Options {
+        hw_freq_num: <Self as GenericTimerOptions>::FREQUENCY,
+        hw_freq_denom: <Self as GenericTimerOptions>::FREQUENCY_DENOMINATOR,
+        hw_headroom_ticks: <Self as GenericTimerOptions>::HEADROOM,
+    }) {
+        Ok(x) => x,
+        Err(e) => e.panic(),
+    };
+
+    fn tickless_state() -> *mut Tickless64State;
+}
+
+/// The physical timer registers
+mod regs {
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    use core::arch::asm;
+
+    /// `CNTP_CTL.ENABLE`
+    pub const CNTP_CTL_ENABLE: u32 = 1 << 0;
+
+    /// Read the Physical Count register (`CNTPCT`).
+    #[inline(always)]
+    pub fn cntpct() -> u64 {
+        #[cfg(target_arch = "arm")]
+        {
+            let (lo, hi): (u32, u32);
+            unsafe {
+                asm!(
+                    // Prevent the read from being performed out of order
+                    "isb",
+                    "mrrc p15, 0, {lo}, {hi}, c14",
+                    lo = lateout(reg) lo,
+                    hi = lateout(reg) hi,
+                    options(nostack, preserves_flags),
+                )
+            };
+            lo as u64 | ((hi as u64) << 32)
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            let value;
+            unsafe {
+                asm!(
+                    // Prevent the read from being performed out of order
+                    "isb",
+                    "mrs {value}, cntpct_el0",
+                    value = lateout(reg) value,
+                    options(nostack, preserves_flags),
+                )
+            };
+            value
+        }
+        #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+        unimplemented!("target mismatch")
+    }
+
+    /// Write the Physical Timer CompareValue register (`CNTP_CVAL`).
+    #[inline(always)]
+    pub fn set_cntp_cval(value: u64) {
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            asm!(
+                "mcrr p15, 2, {lo}, {hi}, c14",
+                "isb",
+                lo = in(reg) value as u32,
+                hi = in(reg) (value >> 32) as u32,
+                options(nostack, preserves_flags),
+            )
+        };
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!(
+                "msr cntp_cval_el0, {value}",
+                "isb",
+                value = in(reg) value,
+                options(nostack, preserves_flags),
+            )
+        };
+        #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+        {
+            let _ = value;
+            unimplemented!("target mismatch")
+        }
+    }
+
+    /// Write the Physical Timer Control register (`CNTP_CTL`).
+    #[inline(always)]
+    pub fn set_cntp_ctl(value: u32) {
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            asm!(
+                "mcr p15, 0, {value}, c14, c2, 1",
+                "isb",
+                value = in(reg) value,
+                options(nostack, preserves_flags),
+            )
+        };
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!(
+                "msr cntp_ctl_el0, {value}",
+                "isb",
+                value = in(reg) value as u64,
+                options(nostack, preserves_flags),
+            )
+        };
+        #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+        {
+            let _ = value;
+            unimplemented!("target mismatch")
+        }
+    }
+}
+
+/// The configuration function.
+pub const fn configure<C, Traits: GenericTimerInstance>(b: &mut Cfg<C>)
+where
+    C: ~const traits::CfgInterruptLine<System = System<Traits>>,
+{
+    InterruptLine::define()
+        .line(Traits::INTERRUPT_NUM)
+        .priority(Traits::INTERRUPT_PRIORITY)
+        .enabled(true)
+        .finish(b);
+    StaticInterruptHandler::define()
+        .line(Traits::INTERRUPT_NUM)
+        .start(handle_tick::<Traits>)
+        .finish(b);
+}
+
+/// Initialize the timer. Implements a port's `Timer::init`.
+#[inline]
+pub fn init<Traits: GenericTimerInstance>() {
+    // Safety: No context switching during boot
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+
+    // The system counter can't be cleared (it's shared by all processors and
+    // may have been running since power-on), so we must record the starting
+    // value of `CNTPCT` by calling `reset`.
+    tstate.reset(regs::cntpct());
+
+    // Don't fire the timer interrupt until the kernel requests it
+    regs::set_cntp_cval(u64::MAX);
+
+    // Enable the timer and unmask the interrupt (`IMASK = 0`)
+    regs::set_cntp_ctl(regs::CNTP_CTL_ENABLE);
+}
+
+/// Implements [`r3_kernel::PortTimer::tick_count`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by a port's `use_generic_timer!`.
+pub unsafe fn tick_count<Traits: GenericTimerInstance>() -> UTicks {
+    let tcfg = &Traits::TICKLESS_CFG;
+
+    let hw_tick_count = regs::cntpct();
+
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &*Traits::tickless_state() };
+    tstate.tick_count(tcfg, hw_tick_count)
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by a port's `use_generic_timer!`.
+pub unsafe fn pend_tick<Traits: GenericTimerInstance>() {
+    // The timer condition is met as soon as `CNTPCT >= CNTP_CVAL`
+    regs::set_cntp_cval(0);
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick_after`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by a port's `use_generic_timer!`.
+pub unsafe fn pend_tick_after<Traits: GenericTimerInstance>(tick_count_delta: UTicks) {
+    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &*Traits::tickless_state() };
+
+    let cur_hw_tick_count = regs::cntpct();
+    let next_hw_tick_count = tstate
+        .measure(tcfg, cur_hw_tick_count, tick_count_delta)
+        .end_hw_tick_count;
+
+    regs::set_cntp_cval(next_hw_tick_count);
+}
+
+#[inline]
+fn handle_tick<Traits: GenericTimerInstance>() {
+    // The interrupt is level-sensitive. It will be deasserted when the kernel
+    // calls `pend_tick_after`, which moves `CNTP_CVAL` to the future.
+    //
+    // `Tickless64State` derives the tick count from the full `CNTPCT` value,
+    // so there's no need to mark a reference point here.
+
+    // Safety: CPU Lock inactive, an interrupt context
+    unsafe { Traits::timer_tick() };
+}
@@ -0,0 +1,483 @@
+//! The Arm Generic Interrupt Controller (GIC) driver shared by the Arm
+//! ports. Supports GICv2 and GICv3 (see [`GicOptions::GIC_VERSION`]).
+//!
+//! A port implements [`Gic`] and its interrupt-related kernel traits by
+//! forwarding the calls to the methods of [`GicRegs`]. The GICv3 CPU interface
+//! system registers are accessed as CP15 registers on AArch32 and as system
+//! registers on AArch64.
+use r3_core::kernel::{
+    ClearInterruptLineError, EnableInterruptLineError, InterruptNum, InterruptPriority,
+    PendInterruptLineError, QueryInterruptLineError, SetInterruptLinePriorityError,
+};
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{ReadWriteable, Readable, Writeable},
+};
+
+mod icc_regs;
+mod regs;
+
+/// Specifies the type of signal transition that pends an interrupt.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum InterruptLineTriggerMode {
+    /// Asserts an interrupt whenever the interrupt signal level is active and
+    /// deasserts whenever the level is not active.
+    Level = 0,
+    /// Asserts an interrupt upon detection of a rising edge of an interrupt
+    /// signal.
+    RisingEdge = 1,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SetInterruptLineTriggerModeError {
+    /// The interrupt number is out of range.
+    BadParam,
+}
+
+/// The architecture version of a GIC.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum GicVersion {
+    /// GICv1 or GICv2, which has a memory-mapped CPU interface.
+    V2,
+    /// GICv3 or GICv4, which has a Redistributor for each processor and a CPU
+    /// interface accessed through system registers.
+    ///
+    /// The driver configures all interrupts as Group 1 interrupts and enables
+    /// affinity routing. This assumes that the kernel runs in Non-secure state
+    /// or the GIC doesn't implement two Security states
+    /// (`GICD_CTLR.DS == 1`). If the processor implements EL2 or EL3, they
+    /// must allow the kernel (PL1 or EL1) to access the CPU interface system
+    /// registers (`ICC_SRE_EL2.Enable` and `ICC_SRE_EL3.Enable`). The standard
+    /// startup code of the AArch64 port sets `ICC_SRE_EL2` when entered in
+    /// EL2.
+    V3,
+}
+
+/// The options for a port's `use_gic!`.
+pub trait GicOptions {
+    /// The architecture version of the GIC. Defaults to [`GicVersion::V2`].
+    const GIC_VERSION: GicVersion = GicVersion::V2;
+
+    /// The base address of GIC distributor registers.
+    const GIC_DISTRIBUTOR_BASE: usize;
+
+    /// The base address of GIC CPU interface registers. Must be specified for
+    /// [`GicVersion::V2`].
+    const GIC_CPU_BASE: usize = 0;
+
+    /// The base address of the Redistributor registers (`RD_base`) of the
+    /// processor running the kernel. Must be specified for [`GicVersion::V3`].
+    const GIC_REDISTRIBUTOR_BASE: usize = 0;
+}
+
+/// Check the [`GicOptions`] implemented by `Traits`. Used by a port's
+/// `use_gic!`.
+pub const fn validate_options<Traits: GicOptions>() {
+    match Traits::GIC_VERSION {
+        GicVersion::V2 => {
+            if Traits::GIC_CPU_BASE == 0 {
+                panic!("`GicOptions::GIC_CPU_BASE` must be specified for GICv2");
+            }
+        }
+        GicVersion::V3 => {
+            if Traits::GIC_REDISTRIBUTOR_BASE == 0 {
+                panic!("`GicOptions::GIC_REDISTRIBUTOR_BASE` must be specified for GICv3");
+            }
+        }
+    }
+}
+
+/// Provides access to a system-global GIC instance. Implemented by a port's
+/// `use_gic!`.
+///
+/// # Safety
+///
+/// This trait is not intended to be implemented in any other means.
+pub unsafe trait Gic {
+    #[doc(hidden)]
+    /// Get `GicRegs` representing the system-global GIC instance.
+    fn gic_regs() -> GicRegs;
+
+    /// Get the number of supported interrupt lines.
+    fn num_interrupt_lines() -> InterruptNum {
+        Self::gic_regs().num_interrupt_lines()
+    }
+
+    /// Set the trigger mode of the specified interrupt line.
+    fn set_interrupt_line_trigger_mode(
+        num: InterruptNum,
+        mode: InterruptLineTriggerMode,
+    ) -> Result<(), SetInterruptLineTriggerModeError> {
+        Self::gic_regs().set_interrupt_line_trigger_mode(num, mode)
+    }
+}
+
+#[doc(hidden)]
+/// Represents a GIC instance.
+#[derive(Clone, Copy)]
+pub struct GicRegs {
+    distributor: &'static regs::GicDistributor,
+    cpu_interface: GicCpuInterfaceRegs,
+}
+
+/// The registers used to access the CPU interface and the banked interrupt
+/// lines (SGIs and PPIs).
+#[derive(Clone, Copy)]
+enum GicCpuInterfaceRegs {
+    /// GICv2: The memory-mapped CPU interface. SGIs and PPIs are controlled
+    /// through the distributor.
+    V2(&'static regs::GicCpuInterface),
+    /// GICv3: The CPU interface is accessed through system registers. SGIs and
+    /// PPIs are controlled through the processor's Redistributor.
+    V3 {
+        redistributor: &'static regs::GicRedistributor,
+        routing: &'static regs::GicDistributorRouting,
+    },
+}
+
+impl GicRegs {
+    /// Construct a `GicRegs`.
+    ///
+    /// # Safety
+    ///
+    /// `GicOptions` should be configured correctly and the memory-mapped
+    /// registers should be accessible.
+    #[inline(always)]
+    pub unsafe fn from_system_traits<Traits: GicOptions>() -> Self {
+        Self {
+            distributor: unsafe { &*(Traits::GIC_DISTRIBUTOR_BASE as *const regs::GicDistributor) },
+            cpu_interface: match Traits::GIC_VERSION {
+                GicVersion::V2 => GicCpuInterfaceRegs::V2(unsafe {
+                    &*(Traits::GIC_CPU_BASE as *const regs::GicCpuInterface)
+                }),
+                GicVersion::V3 => GicCpuInterfaceRegs::V3 {
+                    redistributor: unsafe {
+                        &*(Traits::GIC_REDISTRIBUTOR_BASE as *const regs::GicRedistributor)
+                    },
+                    routing: unsafe {
+                        &*((Traits::GIC_DISTRIBUTOR_BASE + regs::GICD_ROUTING_OFFSET)
+                            as *const regs::GicDistributorRouting)
+                    },
+                },
+            },
+        }
+    }
+
+    /// Get the number of supported interrupt lines.
+    pub fn num_interrupt_lines(self) -> InterruptNum {
+        let raw = self.distributor.TYPER.read(regs::GICD_TYPER::ITLinesNumber);
+        let num_lines = (raw as usize + 1) * 32;
+        match self.cpu_interface {
+            GicCpuInterfaceRegs::V2(_) => num_lines,
+            // INTIDs `1020..1024` are reserved
+            GicCpuInterfaceRegs::V3 { .. } => num_lines.min(1020),
+        }
+    }
+
+    /// Set the trigger mode of the specified interrupt line.
+    pub fn set_interrupt_line_trigger_mode(
+        self,
+        num: InterruptNum,
+        mode: InterruptLineTriggerMode,
+    ) -> Result<(), SetInterruptLineTriggerModeError> {
+        // SGI (num = `0..16`) doesn't support changing trigger mode
+        if num < 16 || num >= self.num_interrupt_lines() {
+            return Err(SetInterruptLineTriggerModeError::BadParam);
+        }
+
+        let int_config = mode as u32 * 2;
+        let field = FieldValue::<u32, ()>::new(0b10, (num % 16) * 2, int_config);
+        match self.cpu_interface {
+            // GICv3: PPIs are configured through the Redistributor
+            GicCpuInterfaceRegs::V3 { redistributor, .. } if num < 32 => {
+                redistributor.ICFGR1.modify(field);
+            }
+            _ => self.distributor.ICFGR[num / 16].modify(field),
+        }
+
+        Ok(())
+    }
+
+    /// Initialize the GIC. Implements a port's
+    /// `InterruptController::init`.
+    pub fn init(self) {
+        match self.cpu_interface {
+            GicCpuInterfaceRegs::V2(cpu_interface) => self.init_v2(cpu_interface),
+            GicCpuInterfaceRegs::V3 {
+                redistributor,
+                routing,
+            } => self.init_v3(redistributor, routing),
+        }
+    }
+
+    fn init_v2(self, cpu_interface: &regs::GicCpuInterface) {
+        let distributor = self.distributor;
+
+        // Disable the distributor
+        distributor.CTLR.modify(regs::GICD_CTLR::Enable::Disable);
+
+        let num_lines = self.num_interrupt_lines();
+
+        // Disable all interrupt lines
+        for r in &distributor.ICENABLE[0..(num_lines + 31) / 32] {
+            r.set(0xffffffff);
+        }
+
+        // Clear all interrupt lines
+        for r in &distributor.ICPEND[0..(num_lines + 31) / 32] {
+            r.set(0xffffffff);
+        }
+
+        // Configure all interrupt lines as level-triggered
+        for r in &distributor.ICFGR[0..(num_lines + 15) / 16] {
+            r.set(0);
+        }
+
+        // Configure all interrupt lines to target CPU interface 0
+        for r in &distributor.ITARGETS[0..(num_lines + 3) / 4] {
+            r.set(0x01010101);
+        }
+
+        // Unmask all priorities in range `0..255`
+        cpu_interface.PMR.set(0xff);
+
+        // Deactivate any active interrupts
+        while let Some(x) = self.acknowledge_interrupt() {
+            self.end_interrupt(x);
+        }
+
+        // Allocate all priority bits for group priority
+        cpu_interface.BPR.set(0);
+
+        // Enable the distributor
+        distributor.CTLR.modify(regs::GICD_CTLR::Enable::Enable);
+
+        // Enable the CPU interface
+        cpu_interface.CTLR.modify(regs::GICC_CTLR::Enable::Enable);
+    }
+
+    fn init_v3(
+        self,
+        redistributor: &regs::GicRedistributor,
+        routing: &regs::GicDistributorRouting,
+    ) {
+        let distributor = self.distributor;
+        let wait_distributor = || while distributor.CTLR.is_set(regs::GICD_CTLR::RWP) {};
+
+        // Disable the distributor
+        distributor.CTLR.set(0);
+        wait_distributor();
+
+        let num_lines = self.num_interrupt_lines();
+
+        // Shared peripheral interrupts (SPI, line `32..`) are configured
+        // through the distributor. SGIs and PPIs (line `0..32`) are configured
+        // through the Redistributor below.
+        for r in &distributor.ICENABLE[1..(num_lines + 31) / 32] {
+            r.set(0xffffffff);
+        }
+        for r in &distributor.ICPEND[1..(num_lines + 31) / 32] {
+            r.set(0xffffffff);
+        }
+        for r in &distributor.IGROUPR[1..(num_lines + 31) / 32] {
+            r.set(0xffffffff);
+        }
+        for r in &distributor.ICFGR[2..(num_lines + 15) / 16] {
+            r.set(0);
+        }
+        wait_distributor();
+
+        // Enable affinity routing and route all SPIs to this processor
+        distributor.CTLR.write(regs::GICD_CTLR::ARE::Enable);
+        wait_distributor();
+
+        let affinity = icc_regs::affinity();
+        for r in &routing.IROUTER[32..num_lines] {
+            r.set(affinity);
+        }
+
+        // Enable the distributor
+        distributor
+            .CTLR
+            .write(regs::GICD_CTLR::ARE::Enable + regs::GICD_CTLR::EnableGrp1::Enable);
+        wait_distributor();
+
+        // Wake up the Redistributor
+        redistributor
+            .WAKER
+            .modify(regs::GICR_WAKER::ProcessorSleep::Awake);
+        while redistributor.WAKER.is_set(regs::GICR_WAKER::ChildrenAsleep) {}
+
+        // Disable and clear all SGIs and PPIs, make them Group 1 interrupts,
+        // and configure PPIs as level-triggered
+        redistributor.ICENABLER0.set(0xffffffff);
+        while redistributor.CTLR.is_set(regs::GICR_CTLR::RWP) {}
+        redistributor.ICPENDR0.set(0xffffffff);
+        redistributor.IGROUPR0.set(0xffffffff);
+        redistributor.ICFGR1.set(0);
+
+        // Enable the system register interface of the CPU interface
+        icc_regs::set_ICC_SRE(icc_regs::ICC_SRE() | icc_regs::ICC_SRE_SRE);
+        icc_regs::isb();
+
+        // Unmask all priorities in range `0..255`
+        icc_regs::set_ICC_PMR(0xff);
+
+        // Deactivate any active interrupts
+        while let Some(x) = self.acknowledge_interrupt() {
+            self.end_interrupt(x);
+        }
+
+        // Allocate all priority bits for group priority
+        icc_regs::set_ICC_BPR1(0);
+
+        // Enable Group 1 interrupts in the CPU interface
+        icc_regs::set_ICC_IGRPEN1(1);
+    }
+
+    /// Acknowledge the highest-priority pending interrupt. Implements a port's
+    /// `InterruptController::acknowledge_interrupt`.
+    #[inline]
+    pub fn acknowledge_interrupt(self) -> Option<InterruptNum> {
+        match self.cpu_interface {
+            GicCpuInterfaceRegs::V2(cpu_interface) => {
+                let raw = cpu_interface.IAR.get();
+                let interrupt_id = raw & 0x3ff;
+                if interrupt_id == 0x3ff {
+                    None
+                } else {
+                    Some(interrupt_id as _)
+                }
+            }
+            GicCpuInterfaceRegs::V3 { .. } => {
+                let interrupt_id = icc_regs::ICC_IAR1() & 0xffffff;
+                // INTIDs `1020..1024` are special (`1023` = spurious)
+                if (1020..1024).contains(&interrupt_id) {
+                    None
+                } else {
+                    Some(interrupt_id as _)
+                }
+            }
+        }
+    }
+
+    /// Signal the completion of an interrupt. Implements a port's
+    /// `InterruptController::end_interrupt`.
+    #[inline]
+    pub fn end_interrupt(self, num: InterruptNum) {
+        match self.cpu_interface {
+            GicCpuInterfaceRegs::V2(cpu_interface) => cpu_interface.EOIR.set(num as _),
+            GicCpuInterfaceRegs::V3 { .. } => icc_regs::set_ICC_EOIR1(num as _),
+        }
+    }
+
+    /// Get the Redistributor if `line` is an SGI or PPI that has to be
+    /// controlled through it.
+    #[inline]
+    fn banked_redistributor(self, line: InterruptNum) -> Option<&'static regs::GicRedistributor> {
+        match self.cpu_interface {
+            GicCpuInterfaceRegs::V3 { redistributor, .. } if line < 32 => Some(redistributor),
+            _ => None,
+        }
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::set_interrupt_line_priority`].
+    pub fn set_interrupt_line_priority(
+        self,
+        line: InterruptNum,
+        priority: InterruptPriority,
+    ) -> Result<(), SetInterruptLinePriorityError> {
+        if line >= self.num_interrupt_lines() || !(0..=255).contains(&priority) {
+            return Err(SetInterruptLinePriorityError::BadParam);
+        }
+
+        if let Some(redistributor) = self.banked_redistributor(line) {
+            redistributor.IPRIORITYR[line].set(priority as u8);
+        } else {
+            self.distributor.IPRIORITY[line].set(priority as u8);
+        }
+
+        Ok(())
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::enable_interrupt_line`].
+    pub fn enable_interrupt_line(self, line: InterruptNum) -> Result<(), EnableInterruptLineError> {
+        // SGI (line `0..16`) does not support enabling/disabling.
+        if line < 16 || line >= self.num_interrupt_lines() {
+            return Err(EnableInterruptLineError::BadParam);
+        }
+
+        if let Some(redistributor) = self.banked_redistributor(line) {
+            redistributor.ISENABLER0.set(1 << line);
+        } else {
+            self.distributor.ISENABLE[line / 32].set(1 << (line % 32));
+        }
+
+        Ok(())
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::disable_interrupt_line`].
+    pub fn disable_interrupt_line(
+        self,
+        line: InterruptNum,
+    ) -> Result<(), EnableInterruptLineError> {
+        // SGI (line `0..16`) does not support enabling/disabling.
+        if line < 16 || line >= self.num_interrupt_lines() {
+            return Err(EnableInterruptLineError::BadParam);
+        }
+
+        if let Some(redistributor) = self.banked_redistributor(line) {
+            redistributor.ICENABLER0.set(1 << line);
+        } else {
+            self.distributor.ICENABLE[line / 32].set(1 << (line % 32));
+        }
+
+        Ok(())
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::pend_interrupt_line`].
+    pub fn pend_interrupt_line(self, line: InterruptNum) -> Result<(), PendInterruptLineError> {
+        if line >= self.num_interrupt_lines() {
+            return Err(PendInterruptLineError::BadParam);
+        } else if let Some(redistributor) = self.banked_redistributor(line) {
+            redistributor.ISPENDR0.set(1 << line);
+        } else if line < 16 {
+            self.distributor.SPENDSGIR[line].set(1);
+        } else {
+            self.distributor.ISPEND[line / 32].set(1 << (line % 32));
+        }
+
+        Ok(())
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::clear_interrupt_line`].
+    pub fn clear_interrupt_line(self, line: InterruptNum) -> Result<(), ClearInterruptLineError> {
+        if line >= self.num_interrupt_lines() {
+            return Err(ClearInterruptLineError::BadParam);
+        } else if let Some(redistributor) = self.banked_redistributor(line) {
+            redistributor.ICPENDR0.set(1 << line);
+        } else if line < 16 {
+            self.distributor.CPENDSGIR[line].set(1);
+        } else {
+            self.distributor.ICPEND[line / 32].set(1 << (line % 32));
+        }
+
+        Ok(())
+    }
+
+    /// Implements [`r3_kernel::PortInterrupts::is_interrupt_line_pending`].
+    pub fn is_interrupt_line_pending(
+        self,
+        line: InterruptNum,
+    ) -> Result<bool, QueryInterruptLineError> {
+        if line >= self.num_interrupt_lines() {
+            return Err(QueryInterruptLineError::BadParam);
+        }
+
+        if let Some(redistributor) = self.banked_redistributor(line) {
+            Ok((redistributor.ISPENDR0.get() & (1 << line)) != 0)
+        } else {
+            Ok((self.distributor.ISPEND[line / 32].get() & (1 << (line % 32))) != 0)
+        }
+    }
+}
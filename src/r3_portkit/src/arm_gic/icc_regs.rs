@@ -0,0 +1,145 @@
+//! The GICv3 CPU interface system registers
+//!
+//! The registers are accessed as CP15 registers on AArch32 and as system
+//! registers on AArch64. Either way, their values are represented as `u64`.
+#![allow(non_snake_case)]
+
+/// Define a function to read a CPU interface system register.
+macro_rules! icc_read {
+    (
+        $(#[$meta:meta])*
+        fn $name:ident() = [$crn:ident, $opc1:literal, $crm:ident, $opc2:literal], $reg:literal
+    ) => {
+        $(#[$meta])*
+        #[inline(always)]
+        pub fn $name() -> u64 {
+            #[cfg(target_arch = "arm")]
+            {
+                let value: u32;
+                unsafe {
+                    core::arch::asm!(
+                        concat!(
+                            "mrc p15, ", stringify!($opc1), ", {}, ", stringify!($crn), ", ",
+                            stringify!($crm), ", ", stringify!($opc2),
+                        ),
+                        lateout(reg) value,
+                        options(nostack, preserves_flags),
+                    )
+                };
+                value as u64
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                let value;
+                unsafe {
+                    core::arch::asm!(
+                        concat!("mrs {}, ", $reg),
+                        lateout(reg) value,
+                        options(nostack, preserves_flags),
+                    )
+                };
+                value
+            }
+            #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+            unimplemented!("target mismatch")
+        }
+    };
+}
+
+/// Define a function to write a CPU interface system register.
+macro_rules! icc_write {
+    (
+        $(#[$meta:meta])*
+        fn $name:ident() = [$crn:ident, $opc1:literal, $crm:ident, $opc2:literal], $reg:literal
+    ) => {
+        $(#[$meta])*
+        #[inline(always)]
+        pub fn $name(value: u64) {
+            #[cfg(target_arch = "arm")]
+            unsafe {
+                core::arch::asm!(
+                    concat!(
+                        "mcr p15, ", stringify!($opc1), ", {}, ", stringify!($crn), ", ",
+                        stringify!($crm), ", ", stringify!($opc2),
+                    ),
+                    in(reg) value as u32,
+                    options(nostack, preserves_flags),
+                )
+            };
+            #[cfg(target_arch = "aarch64")]
+            unsafe {
+                core::arch::asm!(
+                    concat!("msr ", $reg, ", {}"),
+                    in(reg) value,
+                    options(nostack, preserves_flags),
+                )
+            };
+            #[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+            {
+                let _ = value;
+                unimplemented!("target mismatch")
+            }
+        }
+    };
+}
+
+// The AArch64 registers are specified by their encodings
+// (`S<op0>_<op1>_<Cn>_<Cm>_<op2>`) so that the assembler accepts them without
+// any target features.
+
+icc_write!(
+    /// Write Interrupt Controller Priority Mask Register (`ICC_PMR`).
+    fn set_ICC_PMR() = [c4, 0, c6, 0], "s3_0_c4_c6_0"
+);
+icc_read!(
+    /// Read Interrupt Controller Interrupt Acknowledge Register 1
+    /// (`ICC_IAR1`).
+    fn ICC_IAR1() = [c12, 0, c12, 0], "s3_0_c12_c12_0"
+);
+icc_write!(
+    /// Write Interrupt Controller End Of Interrupt Register 1 (`ICC_EOIR1`).
+    fn set_ICC_EOIR1() = [c12, 0, c12, 1], "s3_0_c12_c12_1"
+);
+icc_write!(
+    /// Write Interrupt Controller Binary Point Register 1 (`ICC_BPR1`).
+    fn set_ICC_BPR1() = [c12, 0, c12, 3], "s3_0_c12_c12_3"
+);
+icc_read!(
+    /// Read Interrupt Controller System Register Enable register (`ICC_SRE`).
+    fn ICC_SRE() = [c12, 0, c12, 5], "s3_0_c12_c12_5"
+);
+icc_write!(
+    /// Write Interrupt Controller System Register Enable register (`ICC_SRE`).
+    fn set_ICC_SRE() = [c12, 0, c12, 5], "s3_0_c12_c12_5"
+);
+icc_write!(
+    /// Write Interrupt Controller Interrupt Group 1 Enable register
+    /// (`ICC_IGRPEN1`).
+    fn set_ICC_IGRPEN1() = [c12, 0, c12, 7], "s3_0_c12_c12_7"
+);
+
+icc_read!(
+    /// Read Multiprocessor Affinity Register (`MPIDR`).
+    fn MPIDR() = [c0, 0, c0, 5], "mpidr_el1"
+);
+
+/// `ICC_SRE.SRE`: System Register Enable
+pub const ICC_SRE_SRE: u64 = 1 << 0;
+
+/// Get the affinity of the current processor in the format of
+/// `GICD_IROUTER<n>` (`Aff3:Aff2:Aff1:Aff0`). AArch32 `MPIDR` doesn't have
+/// `Aff3`, which is treated as zero.
+#[inline]
+pub fn affinity() -> u64 {
+    MPIDR() & 0xff_00ff_ffff
+}
+
+/// Execute an Instruction Synchronization Barrier so that a preceding write
+/// to `ICC_SRE` takes effect.
+#[inline(always)]
+pub fn isb() {
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    unsafe {
+        core::arch::asm!("isb", options(nostack, preserves_flags))
+    };
+}
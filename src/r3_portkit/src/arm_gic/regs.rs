@@ -13,8 +13,7 @@ pub struct GicDistributor {
     _implementation_defined1: [u32; 8],
     _reserved2: [u32; 16],
     /// Interrupt Group Registers
-    pub IGROUPR: ReadWrite<u32>,
-    _reserved3: [u32; 31],
+    pub IGROUPR: [ReadWrite<u32>; 32],
     /// Interrupt Set-Enable Registers
     pub ISENABLE: [ReadWrite<u32>; 32],
     /// Interrupt Clear-Enable Registers
@@ -48,6 +47,67 @@ pub struct GicDistributor {
     _implementation_defined3: [u32; 12],
 }
 
+/// The GICv3 Distributor registers located at `GICD_BASE + 0x6000`
+#[repr(C)]
+pub struct GicDistributorRouting {
+    /// Interrupt Routing Registers. The elements for SGIs and PPIs
+    /// (`0..32`) are reserved.
+    pub IROUTER: [ReadWrite<u64>; 1020],
+}
+
+/// The offset of [`GicDistributorRouting`] from the Distributor base address
+pub const GICD_ROUTING_OFFSET: usize = 0x6000;
+
+/// The GICv3 Redistributor registers of a single processor
+#[repr(C)]
+pub struct GicRedistributor {
+    // `RD_base` frame
+    // ------------------------------------------------------------------------
+    /// Redistributor Control Register
+    pub CTLR: ReadWrite<u32, GICR_CTLR::Register>,
+    /// Redistributor Implementer Identification Register
+    pub IIDR: ReadOnly<u32>,
+    /// Redistributor Type Register
+    pub TYPER: ReadOnly<u64>,
+    /// Error Reporting Status Register, optional
+    pub STATUSR: ReadWrite<u32>,
+    /// Redistributor Wake Register
+    pub WAKER: ReadWrite<u32, GICR_WAKER::Register>,
+    _reserved1: [u32; 0x3ffa],
+
+    // `SGI_base` frame
+    // ------------------------------------------------------------------------
+    _reserved2: [u32; 32],
+    /// Interrupt Group Register 0
+    pub IGROUPR0: ReadWrite<u32>,
+    _reserved3: [u32; 31],
+    /// Interrupt Set-Enable Register 0
+    pub ISENABLER0: ReadWrite<u32>,
+    _reserved4: [u32; 31],
+    /// Interrupt Clear-Enable Register 0
+    pub ICENABLER0: ReadWrite<u32>,
+    _reserved5: [u32; 31],
+    /// Interrupt Set-Pending Register 0
+    pub ISPENDR0: ReadWrite<u32>,
+    _reserved6: [u32; 31],
+    /// Interrupt Clear-Pending Register 0
+    pub ICPENDR0: ReadWrite<u32>,
+    _reserved7: [u32; 31],
+    /// Interrupt Set-Active Register 0
+    pub ISACTIVER0: ReadWrite<u32>,
+    _reserved8: [u32; 31],
+    /// Interrupt Clear-Active Register 0
+    pub ICACTIVER0: ReadWrite<u32>,
+    _reserved9: [u32; 31],
+    /// Interrupt Priority Registers
+    pub IPRIORITYR: [ReadWrite<u8>; 32],
+    _reserved10: [u32; 504],
+    /// SGI Configuration Register
+    pub ICFGR0: ReadOnly<u32>,
+    /// PPI Configuration Register
+    pub ICFGR1: ReadWrite<u32>,
+}
+
 #[repr(C)]
 pub struct GicCpuInterface {
     /// CPU Interface Control Register
@@ -92,6 +152,44 @@ tock_registers::register_bitfields! {u32,
         Enable OFFSET(0) NUMBITS(1) [
             Disable = 0,
             Enable = 1
+        ],
+
+        /// GICv3: Enable Group 1 interrupts (`EnableGrp1` if
+        /// `GICD_CTLR.DS == 1`, `EnableGrp1A` for Non-secure accesses)
+        EnableGrp1 OFFSET(1) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1
+        ],
+
+        /// GICv3: Affinity Routing Enable (`ARE` if `GICD_CTLR.DS == 1`,
+        /// `ARE_NS` for Non-secure accesses)
+        ARE OFFSET(4) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1
+        ],
+
+        /// GICv3: Register Write Pending
+        RWP OFFSET(31) NUMBITS(1) []
+    ]
+}
+
+tock_registers::register_bitfields! {u32,
+    pub GICR_CTLR [
+        /// Register Write Pending
+        RWP OFFSET(3) NUMBITS(1) []
+    ]
+}
+
+tock_registers::register_bitfields! {u32,
+    pub GICR_WAKER [
+        /// Indicates whether the connected processor is quiescent.
+        ChildrenAsleep OFFSET(2) NUMBITS(1) [],
+
+        /// Indicates whether the Redistributor can assert the `WakeRequest`
+        /// signal.
+        ProcessorSleep OFFSET(1) NUMBITS(1) [
+            Awake = 0,
+            Asleep = 1
         ]
     ]
 }
@@ -117,3 +215,26 @@ tock_registers::register_bitfields! {u32,
         ITLinesNumber OFFSET(0) NUMBITS(5) []
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memoffset::offset_of;
+
+    #[test]
+    fn redistributor_layout() {
+        assert_eq!(offset_of!(GicRedistributor, WAKER), 0x14);
+        assert_eq!(offset_of!(GicRedistributor, IGROUPR0), 0x10080);
+        assert_eq!(offset_of!(GicRedistributor, ISENABLER0), 0x10100);
+        assert_eq!(offset_of!(GicRedistributor, ICPENDR0), 0x10280);
+        assert_eq!(offset_of!(GicRedistributor, IPRIORITYR), 0x10400);
+        assert_eq!(offset_of!(GicRedistributor, ICFGR1), 0x10c04);
+    }
+
+    #[test]
+    fn distributor_layout() {
+        assert_eq!(offset_of!(GicDistributor, IGROUPR), 0x80);
+        assert_eq!(offset_of!(GicDistributor, ISENABLE), 0x100);
+        assert_eq!(offset_of!(GicDistributor, ICFGR), 0xc00);
+    }
+}
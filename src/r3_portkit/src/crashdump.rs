@@ -0,0 +1,220 @@
+//! Crash records that survive a reset
+//!
+//! A port writes a crash record to a [`CrashRecordCell`] when it takes a fault
+//! exception. The cell is meant to be placed in a section that is left
+//! uninitialized by the startup code (e.g., `.uninit`) so that the application
+//! can retrieve the record after a reset and upload it for post-mortem
+//! analysis.
+//!
+//! The record is stored with a magic number and a checksum, which are used to
+//! tell a valid record from the garbage found in RAM after a power-on reset.
+use core::{
+    cell::UnsafeCell,
+    mem::{size_of, MaybeUninit},
+};
+
+/// A plain-old-data type that can be stored in a [`CrashRecordCell`].
+///
+/// # Safety
+///
+/// The type must only consist of `u32` fields (possibly nested in arrays or
+/// `#[repr(C)]` structs) and must be valid for any bit pattern.
+pub unsafe trait CrashRecord: Copy {
+    /// Identifies the record format. Should be updated whenever the layout of
+    /// the type changes.
+    const MAGIC: u32;
+}
+
+/// A storage for a [`CrashRecord`].
+#[repr(C)]
+pub struct CrashRecordCell<T> {
+    magic: UnsafeCell<u32>,
+    checksum: UnsafeCell<u32>,
+    record: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Safety: The methods accessing the contents are `unsafe`
+unsafe impl<T: Send> Sync for CrashRecordCell<T> {}
+
+impl<T: CrashRecord> Default for CrashRecordCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: CrashRecord> CrashRecordCell<T> {
+    /// Construct an empty `CrashRecordCell`.
+    ///
+    /// The initial value doesn't matter if the cell is placed in an
+    /// uninitialized section.
+    pub const fn new() -> Self {
+        Self {
+            magic: UnsafeCell::new(0),
+            checksum: UnsafeCell::new(0),
+            record: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Get the number of `u32` words in `T`.
+    #[inline]
+    fn num_words() -> usize {
+        assert!(size_of::<T>() % 4 == 0);
+        size_of::<T>() / 4
+    }
+
+    /// Get a pointer to the record's words.
+    #[inline]
+    fn words(&self) -> *mut u32 {
+        self.record.get().cast()
+    }
+
+    /// Calculate the checksum of the stored record.
+    ///
+    /// # Safety
+    ///
+    /// No concurrent writes to `self`.
+    unsafe fn calculate_checksum(&self) -> u32 {
+        // FNV-1a, applied on words
+        (0..Self::num_words()).fold(0x811c_9dc5 ^ T::MAGIC, |hash, i| {
+            // Safety: In-bounds, and any bit pattern is valid for `u32`
+            let word = unsafe { self.words().add(i).read_volatile() };
+            (hash ^ word).wrapping_mul(0x0100_0193)
+        })
+    }
+
+    /// Store a record, overwriting the existing one.
+    ///
+    /// # Safety
+    ///
+    /// No concurrent accesses to `self`.
+    pub unsafe fn store(&self, record: &T) {
+        // Safety: Any bit pattern is valid for `T`. The caller guarantees the
+        // absence of concurrent accesses.
+        unsafe {
+            // Invalidate the old record first so that an interrupted write
+            // doesn't leave a valid-looking record
+            self.magic.get().write_volatile(0);
+
+            let words: *const u32 = (record as *const T).cast();
+            for i in 0..Self::num_words() {
+                self.words().add(i).write_volatile(words.add(i).read());
+            }
+
+            self.checksum
+                .get()
+                .write_volatile(self.calculate_checksum());
+            self.magic.get().write_volatile(T::MAGIC);
+        }
+    }
+
+    /// Get the stored record if it's valid.
+    ///
+    /// # Safety
+    ///
+    /// No concurrent writes to `self`.
+    pub unsafe fn load(&self) -> Option<T> {
+        // Safety: The caller guarantees the absence of concurrent writes
+        unsafe {
+            if self.magic.get().read_volatile() != T::MAGIC
+                || self.checksum.get().read_volatile() != self.calculate_checksum()
+            {
+                return None;
+            }
+
+            // Safety: Any bit pattern is valid for `T`
+            Some(self.record.get().read_volatile().assume_init())
+        }
+    }
+
+    /// Remove the stored record.
+    ///
+    /// # Safety
+    ///
+    /// No concurrent accesses to `self`.
+    pub unsafe fn clear(&self) {
+        // Safety: The caller guarantees the absence of concurrent accesses
+        unsafe { self.magic.get().write_volatile(0) };
+    }
+
+    /// Get and remove the stored record if it's valid.
+    ///
+    /// # Safety
+    ///
+    /// No concurrent accesses to `self`.
+    pub unsafe fn take(&self) -> Option<T> {
+        // Safety: Upheld by the caller
+        unsafe {
+            let record = self.load();
+            self.clear();
+            record
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[repr(C)]
+    struct Record {
+        a: u32,
+        b: [u32; 3],
+    }
+
+    unsafe impl CrashRecord for Record {
+        const MAGIC: u32 = 0x1234_5678;
+    }
+
+    const RECORD: Record = Record {
+        a: 42,
+        b: [1, 2, 0xffff_ffff],
+    };
+
+    #[test]
+    fn store_and_take() {
+        let cell = CrashRecordCell::<Record>::new();
+        unsafe {
+            assert_eq!(cell.load(), None);
+            cell.store(&RECORD);
+            assert_eq!(cell.load(), Some(RECORD));
+            assert_eq!(cell.take(), Some(RECORD));
+            assert_eq!(cell.take(), None);
+        }
+    }
+
+    #[test]
+    fn overwrite() {
+        let cell = CrashRecordCell::<Record>::new();
+        let record2 = Record { a: 43, ..RECORD };
+        unsafe {
+            cell.store(&RECORD);
+            cell.store(&record2);
+            assert_eq!(cell.take(), Some(record2));
+        }
+    }
+
+    #[test]
+    fn reject_corrupted() {
+        let cell = CrashRecordCell::<Record>::new();
+        unsafe {
+            cell.store(&RECORD);
+            cell.words().add(2).write(3);
+            assert_eq!(cell.load(), None);
+        }
+    }
+
+    #[test]
+    fn reject_garbage() {
+        // Simulate the garbage found in RAM after a power-on reset
+        let cell = CrashRecordCell::<Record>::new();
+        unsafe {
+            cell.magic.get().write(Record::MAGIC);
+            cell.checksum.get().write(0xdead_beef);
+            for i in 0..4 {
+                cell.words().add(i).write(0x5555_5555);
+            }
+            assert_eq!(cell.load(), None);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 //! R3 PortKit
+#![feature(const_refs_to_cell)]
 #![feature(generic_const_exprs)]
+#![feature(const_trait_impl)]
+#![feature(const_mut_refs)]
 #![feature(adt_const_params)]
 #![feature(naked_functions)]
 #![feature(core_panic)]
@@ -21,8 +24,12 @@ pub mod _changelog_ {}
 #[macro_use]
 pub mod utils;
 
+pub mod arm_generic_timer;
+pub mod arm_gic;
+pub mod crashdump;
 pub mod num;
 pub mod pptext;
 pub mod sym;
 pub mod tickful;
 pub mod tickless;
+pub mod tickless64;
@@ -21,6 +21,20 @@ pub struct TicklessOptions {
     pub hw_freq_denom: u64,
     /// The headroom for interrupt latency, measured in hardware timer cycles.
     pub hw_headroom_ticks: u32,
+    /// The width of the hardware counter, measured in bits. Must be in range
+    /// `1..=32`.
+    ///
+    /// If this is less than `32`, the hardware counter must be extended to 32
+    /// bits by [`HwCounterExtender`], and [`hw_max_tick_count`] is forced to
+    /// be `u32::MAX`. `hw_headroom_ticks` must be less than the half period
+    /// of the hardware counter in this case.
+    ///
+    /// A prescaled counter is represented by the effective frequency after
+    /// prescaling (e.g., `hw_freq_num: 32_768, hw_freq_denom: 16` for a
+    /// 32.768kHz clock divided by 16).
+    ///
+    /// [`hw_max_tick_count`]: TicklessCfg::hw_max_tick_count
+    pub hw_counter_bits: u32,
     /// Forces [`hw_max_tick_count`] to be `u32::MAX`. This might require the
     /// use of a less-efficient algorithm.
     ///
@@ -28,6 +42,40 @@ pub struct TicklessOptions {
     pub force_full_hw_period: bool,
     /// Allow the use of [`TicklessStateTrait::reset`].
     pub resettable: bool,
+    /// Allow the use of [`TicklessStateTrait::reconfigure`]. This forces
+    /// [`hw_max_tick_count`] and [`max_tick_count`] to be `u32::MAX` and
+    /// might require the use of a less-efficient algorithm.
+    ///
+    /// [`hw_max_tick_count`]: TicklessCfg::hw_max_tick_count
+    /// [`max_tick_count`]: TicklessCfg::max_tick_count
+    pub reconfigurable: bool,
+}
+
+impl TicklessOptions {
+    /// Construct `TicklessOptions` with the specified hardware timer frequency
+    /// and headroom. The other fields are set to their defaults: a 32-bit
+    /// hardware counter and all flags cleared. Use the struct update syntax to
+    /// override them.
+    ///
+    /// ```rust
+    /// use r3_portkit::tickless::TicklessOptions;
+    /// const OPTIONS: TicklessOptions = TicklessOptions {
+    ///     resettable: true,
+    ///     ..TicklessOptions::new(32_768, 1, 1024)
+    /// };
+    /// assert_eq!(OPTIONS.hw_counter_bits, 32);
+    /// ```
+    pub const fn new(hw_freq_num: u64, hw_freq_denom: u64, hw_headroom_ticks: u32) -> Self {
+        Self {
+            hw_freq_num,
+            hw_freq_denom,
+            hw_headroom_ticks,
+            hw_counter_bits: 32,
+            force_full_hw_period: false,
+            resettable: false,
+            reconfigurable: false,
+        }
+    }
 }
 
 /// Error type for [`TicklessCfg::new`].
@@ -44,6 +92,11 @@ pub enum CfgError {
     InternalOverflow,
     /// The calculated value of [`TicklessCfg::max_timeout`] is too low.
     OSMaxTimeoutTooLow,
+    /// [`TicklessOptions::hw_counter_bits`] is out of range.
+    BadHwCounterBits,
+    /// [`TicklessOptions::hw_counter_bits`] is too low for
+    /// [`TicklessOptions::hw_headroom_ticks`].
+    HwCounterTooNarrow,
 }
 
 impl CfgError {
@@ -60,6 +113,11 @@ impl CfgError {
                 "the calculated maximum OS timeout is too low. lowering the \
                  interrupt latency headroom might help"
             }
+            Self::BadHwCounterBits => "the hardware counter width must be in range `1..=32`",
+            Self::HwCounterTooNarrow => {
+                "the interrupt latency headroom must be less than the half \
+                 period of the hardware counter"
+            }
         }
     }
 
@@ -74,6 +132,20 @@ impl fmt::Display for CfgError {
     }
 }
 
+/// Error type for the `set_timer_frequency` methods provided by the timer
+/// drivers built on [`TicklessStateTrait::reconfigure`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SetTimerFrequencyError {
+    /// The timer driver was not configured to allow changing the timer
+    /// frequency.
+    NotReconfigurable,
+    /// CPU Lock is active, or the current context is not a task or interrupt
+    /// context.
+    BadContext,
+    /// [`TicklessCfg::new`] rejected the new timer frequency.
+    BadFrequency(CfgError),
+}
+
 /// The precomputed parameters for the tickless implementation of
 /// [`r3_kernel::PortTimer`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -90,6 +162,8 @@ pub struct TicklessCfg {
     /// The maximum interval (measured in microseconds) that can be reliably
     /// measured.
     max_timeout: u32,
+    /// The width of the hardware counter.
+    hw_counter_bits: u32,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -103,6 +177,8 @@ enum TicklessAlgorithm {
     },
     /// See [`TicklessStateCore`].
     Stateful,
+    /// See [`TicklessDynStateCore`].
+    Reconfigurable,
 }
 
 impl TicklessCfg {
@@ -113,8 +189,10 @@ impl TicklessCfg {
             hw_freq_num,
             hw_freq_denom,
             hw_headroom_ticks,
+            hw_counter_bits,
             force_full_hw_period,
             resettable,
+            reconfigurable,
         }: TicklessOptions,
     ) -> Result<Self, CfgError> {
         if hw_freq_denom == 0 {
@@ -123,6 +201,21 @@ impl TicklessCfg {
             return Err(CfgError::FreqNumZero);
         }
 
+        if hw_counter_bits == 0 || hw_counter_bits > 32 {
+            return Err(CfgError::BadHwCounterBits);
+        }
+
+        // A narrow hardware counter is extended to 32 bits by
+        // `HwCounterExtender`, which wraps around at `u32::MAX`. The overflow
+        // interrupt must be handled within the half period of the counter for
+        // `HwCounterExtender` to tell whether an overflow happened before or
+        // after reading the counter. The overflow interrupt is usually subject
+        // to the same latency as the timer interrupt.
+        if hw_counter_bits < 32 && hw_headroom_ticks as u64 >= 1 << (hw_counter_bits - 1) {
+            return Err(CfgError::HwCounterTooNarrow);
+        }
+        let force_full_hw_period = force_full_hw_period || hw_counter_bits < 32;
+
         // `hw_ticks_per_micro = freq_num / freq_denom / 1_000_000`
         let hw_ticks_per_micro =
             Ratio::new_raw(hw_freq_num as u128, hw_freq_denom as u128 * 1_000_000);
@@ -187,6 +280,7 @@ impl TicklessCfg {
                 (0x1_0000_0000 % hw_global_period == 0
                  && hw_global_period >= global_period))
             && !resettable
+            && !reconfigurable
         {
             // If the period is measurable without wrap-around in both ticks,
             // the stateless algorithm is applicable.
@@ -295,7 +389,13 @@ impl TicklessCfg {
             }
             assert!(max_timeout <= u32::MAX as u128);
 
-            (TicklessAlgorithm::Stateful, max_timeout as u32)
+            let algorithm = if reconfigurable {
+                TicklessAlgorithm::Reconfigurable
+            } else {
+                TicklessAlgorithm::Stateful
+            };
+
+            (algorithm, max_timeout as u32)
         };
 
         Ok(Self {
@@ -304,6 +404,7 @@ impl TicklessCfg {
             algorithm,
             division: *hw_ticks_per_micro.denom() as u64,
             max_timeout,
+            hw_counter_bits,
         })
     }
 
@@ -314,7 +415,7 @@ impl TicklessCfg {
             TicklessAlgorithm::Stateless {
                 hw_max_tick_count, ..
             } => hw_max_tick_count,
-            TicklessAlgorithm::Stateful => u32::MAX,
+            TicklessAlgorithm::Stateful | TicklessAlgorithm::Reconfigurable => u32::MAX,
         }
     }
 
@@ -323,7 +424,7 @@ impl TicklessCfg {
     pub const fn max_tick_count(&self) -> u32 {
         match self.algorithm {
             TicklessAlgorithm::Stateless { max_tick_count, .. } => max_tick_count,
-            TicklessAlgorithm::Stateful => u32::MAX,
+            TicklessAlgorithm::Stateful | TicklessAlgorithm::Reconfigurable => u32::MAX,
         }
     }
 
@@ -334,6 +435,19 @@ impl TicklessCfg {
         self.max_timeout
     }
 
+    /// Get the width of the hardware counter, measured in bits.
+    #[inline]
+    pub const fn hw_counter_bits(&self) -> u32 {
+        self.hw_counter_bits
+    }
+
+    /// Get the maximum raw value of the hardware counter, i.e.,
+    /// `2.pow(hw_counter_bits) - 1`.
+    #[inline]
+    pub const fn hw_counter_max(&self) -> u32 {
+        u32::MAX >> (32 - self.hw_counter_bits)
+    }
+
     /// Get the subtick division.
     #[inline]
     pub const fn division(&self) -> u64 {
@@ -353,7 +467,9 @@ impl TicklessCfg {
 /// given [`TicklessCfg`]. All instances implement [`TicklessStateTrait`].
 pub type TicklessState<const CFG: TicklessCfg> = If! {
     |CFG: TicklessCfg|
-    if (matches!(CFG.algorithm, TicklessAlgorithm::Stateful)) {
+    if (matches!(CFG.algorithm, TicklessAlgorithm::Reconfigurable)) {
+        TicklessDynStateCore
+    } else if (matches!(CFG.algorithm, TicklessAlgorithm::Stateful)) {
         TicklessStateCore<Wrapping<{ CFG.take_division() - 1 }>>
     } else {
         TicklessStatelessCore
@@ -402,6 +518,24 @@ pub struct TicklessStateCore<Subticks> {
     ref_hw_subtick_count: Subticks,
 }
 
+/// The internal state of the tickless implementation of
+/// [`r3_kernel::PortTimer`] that supports switching [`TicklessCfg`] at runtime
+/// by [`TicklessStateTrait::reconfigure`].
+///
+/// This is a variant of [`TicklessStateCore`] that doesn't depend on a
+/// particular value of [`TicklessCfg::division`].
+#[derive(Debug, Copy, Clone)]
+pub struct TicklessDynStateCore {
+    /// The OS tick count at the reference point.
+    ref_tick_count: u32,
+    /// The hardware tick count at the reference point.
+    ref_hw_tick_count: u32,
+    /// The fractional part of the hardware tick count at the reference point.
+    /// Must be in range `0..cfg.division` for the current `cfg: `
+    /// [`TicklessCfg`].
+    ref_hw_subtick_count: u64,
+}
+
 /// Operations implemented by all valid instantiations of [`TicklessState`].
 #[doc = include_str!("./common.md")]
 pub trait TicklessStateTrait: Init + Copy + core::fmt::Debug {
@@ -415,6 +549,23 @@ pub trait TicklessStateTrait: Init + Copy + core::fmt::Debug {
     /// called.
     fn reset(&mut self, cfg: &TicklessCfg, hw_tick_count: u32);
 
+    /// Switch to a different [`TicklessCfg`] at the given hardware tick count
+    /// without disrupting the OS tick count. Returns the OS tick count at the
+    /// moment of the switch.
+    ///
+    /// This is intended to be used when the hardware timer frequency changes.
+    /// Hardware ticks before `hw_tick_count` are measured by `cfg`, and those
+    /// after `hw_tick_count` are measured by `new_cfg`. The reference point is
+    /// moved to `hw_tick_count`, discarding the fractional part of the OS
+    /// tick count (less than one microsecond) at this point.
+    ///
+    /// To use this method, [`TicklessOptions::reconfigurable`] must be set to
+    /// `true` when constructing both of `cfg` and `new_cfg`. `hw_tick_count`
+    /// must satisfy the requirements of [`TicklessStateTrait::tick_count`] for
+    /// `cfg`. After calling this method, `new_cfg` must be passed to all
+    /// methods in place of `cfg`.
+    fn reconfigure(&mut self, cfg: &TicklessCfg, new_cfg: &TicklessCfg, hw_tick_count: u32) -> u32;
+
     /// Mark a reference point. Returns the reference point's OS tick count
     /// (in range `0..=cfg.`[`max_tick_count`]`()`).
     ///
@@ -628,6 +779,14 @@ impl<Subticks: Init> Init for TicklessStateCore<Subticks> {
     };
 }
 
+impl Init for TicklessDynStateCore {
+    const INIT: Self = Self {
+        ref_tick_count: Init::INIT,
+        ref_hw_tick_count: Init::INIT,
+        ref_hw_subtick_count: Init::INIT,
+    };
+}
+
 impl TicklessStateTrait for TicklessStatelessCore {
     fn reset(&mut self, _cfg: &TicklessCfg, _hw_tick_count: u32) {
         // `TicklessStatelessCore` can be chosen only if
@@ -635,6 +794,17 @@ impl TicklessStateTrait for TicklessStatelessCore {
         unreachable!()
     }
 
+    fn reconfigure(
+        &mut self,
+        _cfg: &TicklessCfg,
+        _new_cfg: &TicklessCfg,
+        _hw_tick_count: u32,
+    ) -> u32 {
+        // `TicklessStatelessCore` can be chosen only if
+        // `TicklessOptions::reconfigurable` was set to `false`
+        unreachable!()
+    }
+
     #[inline]
     fn mark_reference(&mut self, cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
         self.tick_count(cfg, hw_tick_count)
@@ -683,6 +853,17 @@ impl<Subticks: WrappingTrait> TicklessStateTrait for TicklessStateCore<Subticks>
         self.ref_hw_tick_count = hw_tick_count;
     }
 
+    fn reconfigure(
+        &mut self,
+        _cfg: &TicklessCfg,
+        _new_cfg: &TicklessCfg,
+        _hw_tick_count: u32,
+    ) -> u32 {
+        // `TicklessStateCore` can be chosen only if
+        // `TicklessOptions::reconfigurable` was set to `false`
+        unreachable!()
+    }
+
     #[inline]
     fn mark_reference(&mut self, cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
         // Calculate the tick count
@@ -706,48 +887,246 @@ impl<Subticks: WrappingTrait> TicklessStateTrait for TicklessStateCore<Subticks>
     #[inline]
     fn tick_count_to_hw_tick_count(&self, cfg: &TicklessCfg, tick_count: u32) -> u32 {
         debug_assert_ne!(tick_count, self.ref_tick_count);
+        stateful_tick_count_to_hw_tick_count(
+            cfg,
+            self.ref_tick_count,
+            self.ref_hw_tick_count,
+            self.ref_hw_subtick_count.to_u128(),
+            tick_count,
+        )
+    }
 
-        let micros = tick_count.wrapping_sub(self.ref_tick_count);
-        // ceil(ref_hw_tick_count + ref_hw_subtick_count / division +
-        //      micros * (hw_ticks_per_micro + hw_subticks_per_micro / division))
-        //  = ceil(ref_hw_subtick_count / division +
-        //        micros * (hw_ticks_per_micro + hw_subticks_per_micro / division))
-        //     + ref_hw_tick_count
-        //  = ceil((
-        //       ref_hw_subtick_count +
-        //       micros * (hw_ticks_per_micro * division + hw_subticks_per_micro)
-        //    ) / division) + ref_hw_tick_count
+    #[inline]
+    fn tick_count(&self, cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
+        stateful_tick_count(
+            cfg,
+            self.ref_tick_count,
+            self.ref_hw_tick_count,
+            self.ref_hw_subtick_count.to_u128(),
+            hw_tick_count,
+        )
+    }
+}
+
+impl TicklessStateTrait for TicklessDynStateCore {
+    #[inline]
+    fn reset(&mut self, _cfg: &TicklessCfg, hw_tick_count: u32) {
+        debug_assert_eq!(self.ref_tick_count, 0);
+        self.ref_hw_tick_count = hw_tick_count;
+    }
+
+    fn reconfigure(&mut self, cfg: &TicklessCfg, new_cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
+        debug_assert!(matches!(cfg.algorithm, TicklessAlgorithm::Reconfigurable));
+        debug_assert!(matches!(
+            new_cfg.algorithm,
+            TicklessAlgorithm::Reconfigurable
+        ));
+
+        // The reference point is now exactly aligned to the OS tick
+        // `ref_tick_count`, which is at or before `hw_tick_count`. Move it
+        // forward to `hw_tick_count`, which has no fractional part in either
+        // configuration. The portion of the OS tick elapsed between the two
+        // points is lost, but `tick_count` never decreases.
+        let ref_tick_count = self.mark_reference(cfg, hw_tick_count);
+        self.ref_hw_tick_count = hw_tick_count;
+        self.ref_hw_subtick_count = 0;
+
+        debug_assert_eq!(self.tick_count(new_cfg, hw_tick_count), ref_tick_count);
+        ref_tick_count
+    }
+
+    #[inline]
+    fn mark_reference(&mut self, cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
+        // Calculate the tick count
+        let new_ref_tick_count = self.tick_count(cfg, hw_tick_count);
+
+        let advance_micros = new_ref_tick_count.wrapping_sub(self.ref_tick_count);
+        self.ref_tick_count = new_ref_tick_count;
+
+        // Unlike `TicklessStateCore`, the subtick counter wraps around at the
+        // boundary specified by `cfg`
         let division = cfg.division as u128;
-        let ref_hw_tick_count = self.ref_hw_tick_count;
-        let ref_hw_subtick_count = self.ref_hw_subtick_count.to_u128();
-        let hw_ticks_per_micro = cfg.hw_ticks_per_micro as u128;
-        let hw_subticks_per_micro = cfg.hw_subticks_per_micro as u128;
-        ref_hw_tick_count.wrapping_add(ceil_div128(
-            ref_hw_subtick_count
-                + micros as u128 * (hw_ticks_per_micro * division + hw_subticks_per_micro),
-            division,
-        ) as u32)
+        let new_ref_hw_subtick_count = self.ref_hw_subtick_count as u128
+            + cfg.hw_subticks_per_micro as u128 * advance_micros as u128;
+        let overflow = (new_ref_hw_subtick_count / division) as u32;
+        self.ref_hw_subtick_count = (new_ref_hw_subtick_count % division) as u64;
+
+        self.ref_hw_tick_count = self
+            .ref_hw_tick_count
+            .wrapping_add(advance_micros.wrapping_mul(cfg.hw_ticks_per_micro))
+            .wrapping_add(overflow);
+
+        new_ref_tick_count
+    }
+
+    #[inline]
+    fn tick_count_to_hw_tick_count(&self, cfg: &TicklessCfg, tick_count: u32) -> u32 {
+        debug_assert_ne!(tick_count, self.ref_tick_count);
+        stateful_tick_count_to_hw_tick_count(
+            cfg,
+            self.ref_tick_count,
+            self.ref_hw_tick_count,
+            self.ref_hw_subtick_count as u128,
+            tick_count,
+        )
     }
 
     #[inline]
     fn tick_count(&self, cfg: &TicklessCfg, hw_tick_count: u32) -> u32 {
-        // (hw_tick_count - (ref_hw_tick_count + ref_hw_subtick_count / division))
-        //      / (hw_ticks_per_micro + hw_subticks_per_micro / division) + ref_tick_count
-        //  = ((hw_tick_count - ref_hw_tick_count) * division - ref_hw_subtick_count)
-        //         / (hw_ticks_per_micro * division + hw_subticks_per_micro) + ref_tick_count
-        let division = cfg.division as u128;
-        let ref_hw_tick_count = self.ref_hw_tick_count;
-        let ref_hw_subtick_count = self.ref_hw_subtick_count.to_u128();
-        let hw_ticks_per_micro = cfg.hw_ticks_per_micro as u128;
-        let hw_subticks_per_micro = cfg.hw_subticks_per_micro as u128;
-        self.ref_tick_count.wrapping_add(
-            ((hw_tick_count.wrapping_sub(ref_hw_tick_count) as u128 * division
-                - ref_hw_subtick_count)
-                / (hw_ticks_per_micro * division + hw_subticks_per_micro)) as u32,
+        stateful_tick_count(
+            cfg,
+            self.ref_tick_count,
+            self.ref_hw_tick_count,
+            self.ref_hw_subtick_count as u128,
+            hw_tick_count,
         )
     }
 }
 
+/// The implementation of [`TicklessStateTrait::tick_count_to_hw_tick_count`]
+/// shared by [`TicklessStateCore`] and [`TicklessDynStateCore`].
+#[inline]
+fn stateful_tick_count_to_hw_tick_count(
+    cfg: &TicklessCfg,
+    ref_tick_count: u32,
+    ref_hw_tick_count: u32,
+    ref_hw_subtick_count: u128,
+    tick_count: u32,
+) -> u32 {
+    let micros = tick_count.wrapping_sub(ref_tick_count);
+    // ceil(ref_hw_tick_count + ref_hw_subtick_count / division +
+    //      micros * (hw_ticks_per_micro + hw_subticks_per_micro / division))
+    //  = ceil(ref_hw_subtick_count / division +
+    //        micros * (hw_ticks_per_micro + hw_subticks_per_micro / division))
+    //     + ref_hw_tick_count
+    //  = ceil((
+    //       ref_hw_subtick_count +
+    //       micros * (hw_ticks_per_micro * division + hw_subticks_per_micro)
+    //    ) / division) + ref_hw_tick_count
+    let division = cfg.division as u128;
+    let hw_ticks_per_micro = cfg.hw_ticks_per_micro as u128;
+    let hw_subticks_per_micro = cfg.hw_subticks_per_micro as u128;
+    ref_hw_tick_count.wrapping_add(ceil_div128(
+        ref_hw_subtick_count
+            + micros as u128 * (hw_ticks_per_micro * division + hw_subticks_per_micro),
+        division,
+    ) as u32)
+}
+
+/// The implementation of [`TicklessStateTrait::tick_count`] shared by
+/// [`TicklessStateCore`] and [`TicklessDynStateCore`].
+#[inline]
+fn stateful_tick_count(
+    cfg: &TicklessCfg,
+    ref_tick_count: u32,
+    ref_hw_tick_count: u32,
+    ref_hw_subtick_count: u128,
+    hw_tick_count: u32,
+) -> u32 {
+    // (hw_tick_count - (ref_hw_tick_count + ref_hw_subtick_count / division))
+    //      / (hw_ticks_per_micro + hw_subticks_per_micro / division) + ref_tick_count
+    //  = ((hw_tick_count - ref_hw_tick_count) * division - ref_hw_subtick_count)
+    //         / (hw_ticks_per_micro * division + hw_subticks_per_micro) + ref_tick_count
+    let division = cfg.division as u128;
+    let hw_ticks_per_micro = cfg.hw_ticks_per_micro as u128;
+    let hw_subticks_per_micro = cfg.hw_subticks_per_micro as u128;
+    ref_tick_count.wrapping_add(
+        ((hw_tick_count.wrapping_sub(ref_hw_tick_count) as u128 * division - ref_hw_subtick_count)
+            / (hw_ticks_per_micro * division + hw_subticks_per_micro)) as u32,
+    )
+}
+
+/// Extends a hardware counter narrower than 32 bits to the 32-bit hardware
+/// tick count used by [`TicklessStateTrait`].
+///
+/// This is used when [`TicklessOptions::hw_counter_bits`] is less than `32`.
+/// The upper bits of the hardware tick count are maintained in software and
+/// advanced by [`Self::handle_overflow`], which the port should call from the
+/// hardware counter's overflow interrupt handler. The overflow interrupt
+/// handler must run within `2.pow(hw_counter_bits - 1)` hardware ticks after
+/// the overflow for the extended hardware tick count to be correct.
+///
+/// All methods must be called with CPU Lock active.
+#[derive(Debug, Copy, Clone)]
+pub struct HwCounterExtender {
+    /// The upper bits of the hardware tick count. The lower
+    /// [`TicklessCfg::hw_counter_bits`] bits are always zero.
+    hw_tick_count_high: u32,
+}
+
+impl Init for HwCounterExtender {
+    const INIT: Self = Self {
+        hw_tick_count_high: Init::INIT,
+    };
+}
+
+impl HwCounterExtender {
+    /// Get the extended hardware tick count.
+    ///
+    /// `raw_hw_tick_count` is the current value of the hardware counter.
+    /// `overflow_pending` indicates whether the hardware counter has
+    /// overflowed and the overflow hasn't been processed by
+    /// [`Self::handle_overflow`] yet. `overflow_pending` must be read *after*
+    /// `raw_hw_tick_count`.
+    #[inline]
+    pub fn hw_tick_count(
+        &self,
+        cfg: &TicklessCfg,
+        raw_hw_tick_count: u32,
+        overflow_pending: bool,
+    ) -> u32 {
+        let max = cfg.hw_counter_max();
+        debug_assert!(raw_hw_tick_count <= max);
+
+        // If the overflow flag is set, the overflow might have happened
+        // either before or after reading the counter. The overflow interrupt
+        // is serviced in less than a half period, so a value in the first half
+        // indicates the former.
+        let hw_tick_count_high = if overflow_pending && raw_hw_tick_count <= max / 2 {
+            self.hw_tick_count_high.wrapping_add(max).wrapping_add(1)
+        } else {
+            self.hw_tick_count_high
+        };
+
+        hw_tick_count_high | raw_hw_tick_count
+    }
+
+    /// Process the overflow of the hardware counter. This method should be
+    /// called by the overflow interrupt handler before clearing the overflow
+    /// flag.
+    #[inline]
+    pub fn handle_overflow(&mut self, cfg: &TicklessCfg) {
+        self.hw_tick_count_high = self
+            .hw_tick_count_high
+            .wrapping_add(cfg.hw_counter_max())
+            .wrapping_add(1);
+    }
+
+    /// Get the value to be programmed into the hardware counter's compare
+    /// register to generate an interrupt at the end of `measurement`, which is
+    /// a result of [`TicklessStateTrait::mark_reference_and_measure`] called
+    /// with the extended hardware tick count `hw_tick_count`.
+    ///
+    /// Returns `None` if the hardware counter will overflow before the end of
+    /// `measurement`. In this case, the port should rely on the overflow
+    /// interrupt handler to call [`r3_kernel::PortToKernel::timer_tick`],
+    /// which will calculate the next deadline again.
+    #[inline]
+    pub fn compare_value(
+        &self,
+        cfg: &TicklessCfg,
+        hw_tick_count: u32,
+        measurement: &Measurement,
+    ) -> Option<u32> {
+        let max = cfg.hw_counter_max();
+        if measurement.hw_ticks <= max - (hw_tick_count & max) {
+            Some(measurement.end_hw_tick_count & max)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate std;
@@ -765,8 +1144,10 @@ mod tests {
                 hw_freq_num: 1,
                 hw_freq_denom: 1,
                 hw_headroom_ticks: 1,
+                hw_counter_bits: 32,
                 force_full_hw_period: false,
                 resettable: false,
+                reconfigurable: false,
             })
             .unwrap(),
             TicklessCfg {
@@ -778,6 +1159,7 @@ mod tests {
                 },
                 division: 1_000_000,
                 max_timeout: 4_292_000_000,
+                hw_counter_bits: 32,
             },
         );
 
@@ -788,8 +1170,10 @@ mod tests {
                 hw_freq_num: 1,
                 hw_freq_denom: 1,
                 hw_headroom_ticks: 1,
+                hw_counter_bits: 32,
                 force_full_hw_period: true,
                 resettable: false,
+                reconfigurable: false,
             })
             .unwrap(),
             TicklessCfg {
@@ -798,6 +1182,7 @@ mod tests {
                 algorithm: TicklessAlgorithm::Stateful,
                 division: 1_000_000,
                 max_timeout: 4_292_967_296,
+                hw_counter_bits: 32,
             },
         );
     }
@@ -810,8 +1195,10 @@ mod tests {
                 hw_freq_num: 0,
                 hw_freq_denom: 1,
                 hw_headroom_ticks: 1,
+                hw_counter_bits: 32,
                 force_full_hw_period: false,
                 resettable: false,
+                reconfigurable: false,
             }),
             Err(CfgError::FreqNumZero)
         );
@@ -826,8 +1213,10 @@ mod tests {
                 hw_freq_num: 1,
                 hw_freq_denom: 0,
                 hw_headroom_ticks: 1,
+                hw_counter_bits: 32,
                 force_full_hw_period: false,
                 resettable: false,
+                reconfigurable: false,
             }),
             Err(CfgError::FreqDenomZero)
         );
@@ -842,8 +1231,10 @@ mod tests {
                 hw_freq_num: 1_000_000 * 0x1_0000_0000,
                 hw_freq_denom: 1,
                 hw_headroom_ticks: 0,
+                hw_counter_bits: 32,
                 force_full_hw_period: false,
                 resettable: false,
+                reconfigurable: false,
             }),
             Err(CfgError::FreqTooHigh)
         );
@@ -859,37 +1250,203 @@ mod tests {
                 hw_freq_num: 0x1fffffffffffffff,
                 hw_freq_denom: 0x1ffffffffffffffe,
                 hw_headroom_ticks: 0,
+                hw_counter_bits: 32,
                 force_full_hw_period: false,
                 resettable: false,
+                reconfigurable: false,
             }),
             Err(CfgError::InternalOverflow)
         );
     }
 
+    /// The hardware counter width given to `TicklessCfg` must be in range
+    /// `1..=32`.
+    #[test]
+    fn tickless_bad_counter_bits() {
+        for hw_counter_bits in [0, 33] {
+            assert_eq!(
+                TicklessCfg::new(TicklessOptions {
+                    hw_freq_num: 32_768,
+                    hw_freq_denom: 1,
+                    hw_headroom_ticks: 1,
+                    hw_counter_bits,
+                    force_full_hw_period: false,
+                    resettable: false,
+                    reconfigurable: false,
+                }),
+                Err(CfgError::BadHwCounterBits)
+            );
+        }
+    }
+
+    /// `TicklessCfg` should reject a hardware counter that overflows too
+    /// quickly for the given interrupt latency.
+    #[test]
+    fn tickless_counter_too_narrow() {
+        assert_eq!(
+            TicklessCfg::new(TicklessOptions {
+                hw_freq_num: 32_768,
+                hw_freq_denom: 1,
+                hw_headroom_ticks: 128,
+                hw_counter_bits: 8,
+                force_full_hw_period: false,
+                resettable: false,
+                reconfigurable: false,
+            }),
+            Err(CfgError::HwCounterTooNarrow)
+        );
+    }
+
     #[quickcheck_macros::quickcheck]
     fn quickcheck_cfg(
         hw_freq_num: u64,
         hw_freq_denom: u64,
         hw_headroom_ticks: u32,
+        hw_counter_bits: u8,
         force_full_hw_period: bool,
         resettable: bool,
+        reconfigurable: bool,
     ) {
+        // Out-of-range values are rejected (see `tickless_bad_counter_bits`);
+        // map the input to `1..=32` so that the valid space is explored
+        let hw_counter_bits = hw_counter_bits as u32 % 32 + 1;
+
         // `TicklessCfg::new` includes various integrity checks
         let _ = TicklessCfg::new(TicklessOptions {
             hw_freq_num,
             hw_freq_denom,
             hw_headroom_ticks,
+            hw_counter_bits,
             force_full_hw_period,
             resettable,
+            reconfigurable,
         });
     }
 
+    /// The timer frequencies (`hw_freq_num`, `hw_freq_denom`,
+    /// `hw_headroom_ticks`) used by the reconfiguration tests.
+    const RECONF_FREQS: &[(u64, u64, u32)] = &[
+        (125_000_000, 1, 125),
+        (125_000_000, 3, 125),
+        (10_000_000, 1, 1),
+        (32_768, 1, 32),
+        (375, 1, 250),
+        (1, 260, 10),
+        (0x501e_e2c2_9a0f, 0xb79a_14f3, 0x64),
+    ];
+
+    fn reconf_cfg(i: usize) -> TicklessCfg {
+        let (hw_freq_num, hw_freq_denom, hw_headroom_ticks) = RECONF_FREQS[i % RECONF_FREQS.len()];
+        TicklessCfg::new(TicklessOptions {
+            hw_freq_num,
+            hw_freq_denom,
+            hw_headroom_ticks,
+            hw_counter_bits: 32,
+            force_full_hw_period: false,
+            resettable: false,
+            reconfigurable: true,
+        })
+        .unwrap()
+    }
+
+    /// Simulate a timer whose frequency changes between timer interrupts.
+    /// Each element of `ops` specifies the new frequency (an index into
+    /// `RECONF_FREQS`) and the timeout and interrupt latency of the
+    /// following timer interrupt.
+    fn do_test_reconfigure(ops: impl IntoIterator<Item = (usize, u32, u32)>) {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let mut cfg = reconf_cfg(0);
+        let mut state: TicklessDynStateCore = Init::INIT;
+        let mut hw_tick_count: u32 = 0xfff0_0000;
+        state.reset(&cfg, hw_tick_count);
+        let mut tick_count = state.mark_reference(&cfg, hw_tick_count);
+        assert_eq!(tick_count, 0);
+
+        for (freq_i, timeout, latency) in ops {
+            let new_cfg = reconf_cfg(freq_i);
+            log::debug!("  switching to {new_cfg:?} at HW = {hw_tick_count}");
+
+            // The OS tick count must be continuous at the moment of switch
+            let switch_tick_count = state.reconfigure(&cfg, &new_cfg, hw_tick_count);
+            assert_eq!(switch_tick_count, tick_count);
+            cfg = new_cfg;
+            assert_eq!(state.tick_count(&cfg, hw_tick_count), switch_tick_count);
+
+            let timeout = timeout % cfg.max_timeout() + 1;
+            let latency = latency % (RECONF_FREQS[freq_i % RECONF_FREQS.len()].2 + 1);
+            log::debug!("    timeout = {timeout}, latency = {latency}");
+
+            let measurement = state.mark_reference_and_measure(&cfg, hw_tick_count, timeout);
+            assert_eq!(
+                measurement.end_hw_tick_count,
+                hw_tick_count.wrapping_add(measurement.hw_ticks)
+            );
+
+            // Elapsed OS ticks after `hw_elapsed` HW ticks from the switch
+            let elapsed_at = |hw_elapsed: u32| {
+                state
+                    .tick_count(&cfg, hw_tick_count.wrapping_add(hw_elapsed))
+                    .wrapping_sub(switch_tick_count)
+            };
+
+            // The new frequency must be in effect immediately after the switch
+            let expected_elapsed_at = |hw_elapsed: u32| {
+                (hw_elapsed as u128 * cfg.division as u128
+                    / (cfg.hw_ticks_per_micro as u128 * cfg.division as u128
+                        + cfg.hw_subticks_per_micro as u128)) as u32
+            };
+
+            if measurement.hw_ticks > 0 {
+                assert!(elapsed_at(measurement.hw_ticks - 1) < timeout);
+            }
+            assert!(elapsed_at(measurement.hw_ticks) >= timeout);
+
+            let late_hw_ticks = measurement.hw_ticks + latency;
+            for hw_elapsed in choose_values_from_range(0..=late_hw_ticks) {
+                assert_eq!(elapsed_at(hw_elapsed), expected_elapsed_at(hw_elapsed));
+            }
+
+            // The timer interrupt handler marks a reference point
+            hw_tick_count = hw_tick_count.wrapping_add(late_hw_ticks);
+            tick_count = state.mark_reference(&cfg, hw_tick_count);
+            assert_eq!(
+                tick_count.wrapping_sub(switch_tick_count),
+                expected_elapsed_at(late_hw_ticks)
+            );
+        }
+    }
+
+    #[test]
+    fn reconfigure_all_pairs() {
+        let n = RECONF_FREQS.len();
+        do_test_reconfigure(
+            (0..n)
+                .flat_map(|i| (0..n).map(move |k| (i, k)))
+                .flat_map(|(i, k)| [(i, 0, 0), (k, u32::MAX, u32::MAX)]),
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn reconfigure_quickcheck(ops: Vec<(usize, u32, u32)>) {
+        do_test_reconfigure(ops);
+    }
+
     #[derive(Debug, Copy, Clone)]
     struct Op {
         timeout: u32,
         latency: u32,
     }
 
+    #[derive(Debug, Copy, Clone)]
+    struct NarrowOp {
+        timeout: u32,
+        latency: u32,
+        /// The latency of the overflow interrupts taken while processing
+        /// this operation.
+        overflow_latency: u32,
+    }
+
     /// Choose some values from `x`. The returned values are sorted in an
     /// ascending order and always include the endpoints.
     fn choose_values_from_range(x: std::ops::RangeInclusive<u32>) -> Box<dyn Iterator<Item = u32>> {
@@ -933,7 +1490,8 @@ mod tests {
         $freq_denom:expr,
         $hw_headroom_ticks:expr,
         $force_full_hw_period:expr,
-        $resettable:expr $(,)*
+        $resettable:expr,
+        $reconfigurable:expr $(,)*
     ) {
         mod $ident {
             use super::*;
@@ -942,8 +1500,10 @@ mod tests {
                 hw_freq_num: $freq_num,
                 hw_freq_denom: $freq_denom,
                 hw_headroom_ticks: $hw_headroom_ticks,
+                hw_counter_bits: 32,
                 force_full_hw_period: $force_full_hw_period,
                 resettable: $resettable,
+                reconfigurable: $reconfigurable,
             }) {
                 Ok(x) => x,
                 Err(e) => e.panic(),
@@ -1143,38 +1703,88 @@ mod tests {
         }
     }
 
-    tickless_simulate!(mod sim1 {}, 1, 1, 1, false, false);
-    tickless_simulate!(mod sim2 {}, 125_000_000, 1, 125, false, false);
-    tickless_simulate!(mod sim3 {}, 375_000_000, 1, 1250, false, false);
-    tickless_simulate!(mod sim4 {}, 125_000_000, 3, 0, false, false);
-    tickless_simulate!(mod sim5 {}, 125_000_000, 3, 125, false, false);
-    tickless_simulate!(mod sim6 {}, 125_000_000, 3, 125_000_000, false, false);
-    tickless_simulate!(mod sim7 {}, 125_000_000, 3, 0xffff_ffa7, false, false);
-    tickless_simulate!(mod sim8 {}, 10_000_000, 1, 1, false, false);
-    tickless_simulate!(mod sim9 {}, 375, 1, 250_000, false, false);
-    tickless_simulate!(mod sim10 {}, 1, 260, 0, false, false);
-    tickless_simulate!(mod sim11 {}, 1, 260, 1, false, false);
-    tickless_simulate!(mod sim12 {}, 1, 260, 10, false, false);
-    tickless_simulate!(mod sim13 {}, 0x501e_e2c2_9a0f, 0xb79a, 0, false, false);
-    tickless_simulate!(mod sim14 {}, 0x501e_e2c2_9a0f, 0xb79a, 0x64, false, false);
+    tickless_simulate!(mod sim1 {}, 1, 1, 1, false, false, false);
+    tickless_simulate!(mod sim2 {}, 125_000_000, 1, 125, false, false, false);
+    tickless_simulate!(mod sim3 {}, 375_000_000, 1, 1250, false, false, false);
+    tickless_simulate!(mod sim4 {}, 125_000_000, 3, 0, false, false, false);
+    tickless_simulate!(mod sim5 {}, 125_000_000, 3, 125, false, false, false);
+    tickless_simulate!(
+        mod sim6 {},
+        125_000_000,
+        3,
+        125_000_000,
+        false,
+        false,
+        false
+    );
+    tickless_simulate!(
+        mod sim7 {},
+        125_000_000,
+        3,
+        0xffff_ffa7,
+        false,
+        false,
+        false
+    );
+    tickless_simulate!(mod sim8 {}, 10_000_000, 1, 1, false, false, false);
+    tickless_simulate!(mod sim9 {}, 375, 1, 250_000, false, false, false);
+    tickless_simulate!(mod sim10 {}, 1, 260, 0, false, false, false);
+    tickless_simulate!(mod sim11 {}, 1, 260, 1, false, false, false);
+    tickless_simulate!(mod sim12 {}, 1, 260, 10, false, false, false);
+    tickless_simulate!(
+        mod sim13 {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0,
+        false,
+        false,
+        false
+    );
+    tickless_simulate!(
+        mod sim14 {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0x64,
+        false,
+        false,
+        false
+    );
     tickless_simulate!(
         mod sim15 {},
         0x501e_e2c2_9a0f,
         0xb79a,
         0x1_0000,
         false,
+        false,
+        false
+    );
+    tickless_simulate!(
+        mod sim16 {},
+        0x501e_e2c2_9a0f,
+        0xb79a_14f3,
+        0,
+        false,
+        false,
         false
     );
-    tickless_simulate!(mod sim16 {}, 0x501e_e2c2_9a0f, 0xb79a_14f3, 0, false, false);
     tickless_simulate!(
         mod sim17 {},
         0x501e_e2c2_9a0f,
         0xb79a_14f3,
         0x64,
         false,
+        false,
+        false
+    );
+    tickless_simulate!(
+        mod sim18 {},
+        0xb79a_14f3,
+        0x1e_e2c2_9a0f,
+        1,
+        false,
+        false,
         false
     );
-    tickless_simulate!(mod sim18 {}, 0xb79a_14f3, 0x1e_e2c2_9a0f, 1, false, false);
     tickless_simulate!(
         mod sim19 {},
         0xff_ffff_ffff_ffff,
@@ -1182,27 +1792,53 @@ mod tests {
         0x41,
         false,
         false,
+        false,
     );
 
-    tickless_simulate!(mod sim1_full {}, 1, 1, 1, true, false);
-    tickless_simulate!(mod sim2_full {}, 125_000_000, 1, 125, true, false);
-    tickless_simulate!(mod sim3_full {}, 375_000_000, 1, 1250, true, false);
-    tickless_simulate!(mod sim4_full {}, 125_000_000, 3, 0, true, false);
-    tickless_simulate!(mod sim5_full {}, 125_000_000, 3, 125, true, false);
-    tickless_simulate!(mod sim6_full {}, 125_000_000, 3, 125_000_000, true, false);
-    tickless_simulate!(mod sim7_full {}, 125_000_000, 3, 0xffff_ffa7, true, false);
-    tickless_simulate!(mod sim8_full {}, 10_000_000, 1, 1, true, false);
-    tickless_simulate!(mod sim9_full {}, 375, 1, 250_000, true, false);
-    tickless_simulate!(mod sim10_full {}, 1, 260, 0, true, false);
-    tickless_simulate!(mod sim11_full {}, 1, 260, 1, true, false);
-    tickless_simulate!(mod sim12_full {}, 1, 260, 10, true, false);
-    tickless_simulate!(mod sim13_full {}, 0x501e_e2c2_9a0f, 0xb79a, 0, true, false);
+    tickless_simulate!(mod sim1_full {}, 1, 1, 1, true, false, false);
+    tickless_simulate!(mod sim2_full {}, 125_000_000, 1, 125, true, false, false);
+    tickless_simulate!(mod sim3_full {}, 375_000_000, 1, 1250, true, false, false);
+    tickless_simulate!(mod sim4_full {}, 125_000_000, 3, 0, true, false, false);
+    tickless_simulate!(mod sim5_full {}, 125_000_000, 3, 125, true, false, false);
+    tickless_simulate!(
+        mod sim6_full {},
+        125_000_000,
+        3,
+        125_000_000,
+        true,
+        false,
+        false
+    );
+    tickless_simulate!(
+        mod sim7_full {},
+        125_000_000,
+        3,
+        0xffff_ffa7,
+        true,
+        false,
+        false
+    );
+    tickless_simulate!(mod sim8_full {}, 10_000_000, 1, 1, true, false, false);
+    tickless_simulate!(mod sim9_full {}, 375, 1, 250_000, true, false, false);
+    tickless_simulate!(mod sim10_full {}, 1, 260, 0, true, false, false);
+    tickless_simulate!(mod sim11_full {}, 1, 260, 1, true, false, false);
+    tickless_simulate!(mod sim12_full {}, 1, 260, 10, true, false, false);
+    tickless_simulate!(
+        mod sim13_full {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0,
+        true,
+        false,
+        false
+    );
     tickless_simulate!(
         mod sim14_full {},
         0x501e_e2c2_9a0f,
         0xb79a,
         0x64,
         true,
+        false,
         false
     );
     tickless_simulate!(
@@ -1211,6 +1847,7 @@ mod tests {
         0xb79a,
         0x1_0000,
         true,
+        false,
         false
     );
     tickless_simulate!(
@@ -1219,6 +1856,7 @@ mod tests {
         0xb79a_14f3,
         0,
         true,
+        false,
         false
     );
     tickless_simulate!(
@@ -1227,6 +1865,7 @@ mod tests {
         0xb79a_14f3,
         0x64,
         true,
+        false,
         false
     );
     tickless_simulate!(
@@ -1235,6 +1874,7 @@ mod tests {
         0x1e_e2c2_9a0f,
         1,
         true,
+        false,
         false
     );
     tickless_simulate!(
@@ -1244,28 +1884,54 @@ mod tests {
         0x41,
         true,
         false,
+        false,
     );
 
-    tickless_simulate!(mod sim1_reset {}, 1, 1, 1, false, true);
-    tickless_simulate!(mod sim2_reset {}, 125_000_000, 1, 125, false, true);
-    tickless_simulate!(mod sim3_reset {}, 375_000_000, 1, 1250, false, true);
-    tickless_simulate!(mod sim4_reset {}, 125_000_000, 3, 0, false, true);
-    tickless_simulate!(mod sim5_reset {}, 125_000_000, 3, 125, false, true);
-    tickless_simulate!(mod sim6_reset {}, 125_000_000, 3, 125_000_000, false, true);
-    tickless_simulate!(mod sim7_reset {}, 125_000_000, 3, 0xffff_ffa7, false, true);
-    tickless_simulate!(mod sim8_reset {}, 10_000_000, 1, 1, false, true);
-    tickless_simulate!(mod sim9_reset {}, 375, 1, 250_000, false, true);
-    tickless_simulate!(mod sim10_reset {}, 1, 260, 0, false, true);
-    tickless_simulate!(mod sim11_reset {}, 1, 260, 1, false, true);
-    tickless_simulate!(mod sim12_reset {}, 1, 260, 10, false, true);
-    tickless_simulate!(mod sim13_reset {}, 0x501e_e2c2_9a0f, 0xb79a, 0, false, true);
+    tickless_simulate!(mod sim1_reset {}, 1, 1, 1, false, true, false);
+    tickless_simulate!(mod sim2_reset {}, 125_000_000, 1, 125, false, true, false);
+    tickless_simulate!(mod sim3_reset {}, 375_000_000, 1, 1250, false, true, false);
+    tickless_simulate!(mod sim4_reset {}, 125_000_000, 3, 0, false, true, false);
+    tickless_simulate!(mod sim5_reset {}, 125_000_000, 3, 125, false, true, false);
+    tickless_simulate!(
+        mod sim6_reset {},
+        125_000_000,
+        3,
+        125_000_000,
+        false,
+        true,
+        false
+    );
+    tickless_simulate!(
+        mod sim7_reset {},
+        125_000_000,
+        3,
+        0xffff_ffa7,
+        false,
+        true,
+        false
+    );
+    tickless_simulate!(mod sim8_reset {}, 10_000_000, 1, 1, false, true, false);
+    tickless_simulate!(mod sim9_reset {}, 375, 1, 250_000, false, true, false);
+    tickless_simulate!(mod sim10_reset {}, 1, 260, 0, false, true, false);
+    tickless_simulate!(mod sim11_reset {}, 1, 260, 1, false, true, false);
+    tickless_simulate!(mod sim12_reset {}, 1, 260, 10, false, true, false);
+    tickless_simulate!(
+        mod sim13_reset {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0,
+        false,
+        true,
+        false
+    );
     tickless_simulate!(
         mod sim14_reset {},
         0x501e_e2c2_9a0f,
         0xb79a,
         0x64,
         false,
-        true
+        true,
+        false
     );
     tickless_simulate!(
         mod sim15_reset {},
@@ -1273,7 +1939,8 @@ mod tests {
         0xb79a,
         0x1_0000,
         false,
-        true
+        true,
+        false
     );
     tickless_simulate!(
         mod sim16_reset {},
@@ -1281,7 +1948,8 @@ mod tests {
         0xb79a_14f3,
         0,
         false,
-        true
+        true,
+        false
     );
     tickless_simulate!(
         mod sim17_reset {},
@@ -1289,7 +1957,8 @@ mod tests {
         0xb79a_14f3,
         0x64,
         false,
-        true
+        true,
+        false
     );
     tickless_simulate!(
         mod sim18_reset {},
@@ -1297,7 +1966,8 @@ mod tests {
         0x1e_e2c2_9a0f,
         1,
         false,
-        true
+        true,
+        false
     );
     tickless_simulate!(
         mod sim19_reset {},
@@ -1306,5 +1976,340 @@ mod tests {
         0x41,
         false,
         true,
+        false,
+    );
+
+    tickless_simulate!(mod sim1_reconf {}, 1, 1, 1, false, false, true);
+    tickless_simulate!(mod sim2_reconf {}, 125_000_000, 1, 125, false, false, true);
+    tickless_simulate!(mod sim3_reconf {}, 375_000_000, 1, 1250, false, false, true);
+    tickless_simulate!(mod sim4_reconf {}, 125_000_000, 3, 0, false, false, true);
+    tickless_simulate!(mod sim5_reconf {}, 125_000_000, 3, 125, false, false, true);
+    tickless_simulate!(
+        mod sim6_reconf {},
+        125_000_000,
+        3,
+        125_000_000,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim7_reconf {},
+        125_000_000,
+        3,
+        0xffff_ffa7,
+        false,
+        false,
+        true
     );
+    tickless_simulate!(mod sim8_reconf {}, 10_000_000, 1, 1, false, false, true);
+    tickless_simulate!(mod sim9_reconf {}, 375, 1, 250_000, false, false, true);
+    tickless_simulate!(mod sim10_reconf {}, 1, 260, 0, false, false, true);
+    tickless_simulate!(mod sim11_reconf {}, 1, 260, 1, false, false, true);
+    tickless_simulate!(mod sim12_reconf {}, 1, 260, 10, false, false, true);
+    tickless_simulate!(
+        mod sim13_reconf {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim14_reconf {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0x64,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim15_reconf {},
+        0x501e_e2c2_9a0f,
+        0xb79a,
+        0x1_0000,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim16_reconf {},
+        0x501e_e2c2_9a0f,
+        0xb79a_14f3,
+        0,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim17_reconf {},
+        0x501e_e2c2_9a0f,
+        0xb79a_14f3,
+        0x64,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim18_reconf {},
+        0xb79a_14f3,
+        0x1e_e2c2_9a0f,
+        1,
+        false,
+        false,
+        true
+    );
+    tickless_simulate!(
+        mod sim19_reconf {},
+        0xff_ffff_ffff_ffff,
+        0xff_ffff_fffe,
+        0x41,
+        false,
+        false,
+        true
+    );
+
+    /// Simulate a narrow hardware counter extended by `HwCounterExtender`.
+    macro narrow_simulate(
+        mod $ident:ident {},
+        $freq_num:expr,
+        $freq_denom:expr,
+        $hw_headroom_ticks:expr,
+        $hw_counter_bits:expr $(,)*
+    ) {
+        mod $ident {
+            use super::*;
+
+            const CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
+                hw_freq_num: $freq_num,
+                hw_freq_denom: $freq_denom,
+                hw_headroom_ticks: $hw_headroom_ticks,
+                hw_counter_bits: $hw_counter_bits,
+                force_full_hw_period: false,
+                resettable: false,
+                reconfigurable: false,
+            }) {
+                Ok(x) => x,
+                Err(e) => e.panic(),
+            };
+            const MAX_TIMEOUT: u32 = CFG.max_timeout();
+            const PERIOD: u64 = CFG.max_tick_count() as u64 + 1;
+            const RAW_PERIOD: u64 = CFG.hw_counter_max() as u64 + 1;
+
+            // Work-around for [ref:false_unconstrained_generic_const_on_type_alias]
+            type TheTicklessState = TicklessState<CFG>;
+
+            /// Get the expected OS tick count at the given time.
+            fn expected_tick_count(now: u64) -> u32 {
+                (now as u128 * $freq_denom as u128 * 1_000_000 / $freq_num as u128 % PERIOD as u128)
+                    as u32
+            }
+
+            struct Sim {
+                /// The current time measured in hardware ticks. Unlike the
+                /// extended hardware tick count, this doesn't wrap around.
+                now: u64,
+                extender: HwCounterExtender,
+                state: TheTicklessState,
+                overflow_pending: bool,
+                overflow_isr_at: Option<u64>,
+            }
+
+            impl Sim {
+                fn hw_tick_count(&self) -> u32 {
+                    let raw_hw_tick_count = (self.now % RAW_PERIOD) as u32;
+                    let hw_tick_count =
+                        self.extender
+                            .hw_tick_count(&CFG, raw_hw_tick_count, self.overflow_pending);
+                    log::trace!(
+                        "    HW = {hw_tick_count} (raw = {raw_hw_tick_count}, \
+                        overflow_pending = {})",
+                        self.overflow_pending
+                    );
+                    assert_eq!(hw_tick_count, self.now as u32);
+                    hw_tick_count
+                }
+
+                fn mark_reference(&mut self) -> u32 {
+                    let hw_tick_count = self.hw_tick_count();
+                    let tick_count = self.state.mark_reference(&CFG, hw_tick_count);
+                    log::trace!("    OS = {tick_count}");
+                    assert_eq!(tick_count, expected_tick_count(self.now));
+                    tick_count
+                }
+
+                /// Arm the timer to wait for `timeout` OS ticks. Returns the
+                /// time of the timer interrupt.
+                fn pend_tick_after(&mut self, timeout: u32, latency: u32) -> Option<u64> {
+                    let hw_tick_count = self.hw_tick_count();
+                    let measurement =
+                        self.state
+                            .mark_reference_and_measure(&CFG, hw_tick_count, timeout);
+                    let deadline = self.now + measurement.hw_ticks as u64;
+                    let next_overflow = (self.now / RAW_PERIOD + 1) * RAW_PERIOD;
+                    log::trace!("    Should wait until {deadline} (timeout = {timeout})");
+
+                    assert!(
+                        sub_mod(
+                            expected_tick_count(deadline),
+                            expected_tick_count(self.now),
+                            PERIOD
+                        ) >= timeout
+                    );
+
+                    match self
+                        .extender
+                        .compare_value(&CFG, hw_tick_count, &measurement)
+                    {
+                        Some(compare_value) => {
+                            // The counter must reach `compare_value` at
+                            // `deadline` for the first time
+                            assert!(deadline < next_overflow);
+                            assert_eq!(compare_value as u64, deadline % RAW_PERIOD);
+                            Some(deadline + latency as u64)
+                        }
+                        None => {
+                            // Wait for an overflow interrupt instead
+                            assert!(deadline >= next_overflow);
+                            None
+                        }
+                    }
+                }
+            }
+
+            fn do_test(start: u32, ops: impl IntoIterator<Item = NarrowOp>) {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                log::info!("CFG = {CFG:?}");
+                log::info!("MAX_TIMEOUT = {MAX_TIMEOUT:?}");
+                log::info!("RAW_PERIOD = {RAW_PERIOD:?}");
+
+                assert_eq!(CFG.hw_max_tick_count(), u32::MAX);
+
+                let mut sim = Sim {
+                    now: start as u64 % RAW_PERIOD,
+                    extender: Init::INIT,
+                    state: Init::INIT,
+                    overflow_pending: false,
+                    overflow_isr_at: None,
+                };
+
+                let mut ops = ops.into_iter();
+                let Some(mut op) = ops.next() else { return };
+                log::debug!("  {op:?}");
+                let mut start_tick_count = sim.mark_reference();
+                let mut timer_isr_at = sim.pend_tick_after(op.timeout, op.latency);
+
+                loop {
+                    let next_overflow = (sim.now / RAW_PERIOD + 1) * RAW_PERIOD;
+                    let next_isr = timer_isr_at.into_iter().chain(sim.overflow_isr_at).min();
+
+                    if next_isr.map_or(true, |t| next_overflow <= t) {
+                        // The hardware counter overflows
+                        sim.now = next_overflow;
+                        log::trace!("    Overflow at {next_overflow}");
+                        assert!(!sim.overflow_pending);
+                        sim.overflow_pending = true;
+                        sim.overflow_isr_at = Some(sim.now + op.overflow_latency as u64);
+                        continue;
+                    }
+
+                    sim.now = next_isr.unwrap();
+
+                    let tick_count = if timer_isr_at == Some(sim.now) {
+                        log::trace!("    Timer interrupt at {}", sim.now);
+                        let tick_count = sim.mark_reference();
+
+                        // The timer interrupt must not be taken too early
+                        assert!(sub_mod(tick_count, start_tick_count, PERIOD) >= op.timeout);
+                        tick_count
+                    } else {
+                        log::trace!("    Overflow interrupt at {}", sim.now);
+                        let tick_count = sim.mark_reference();
+                        sim.extender.handle_overflow(&CFG);
+                        sim.overflow_pending = false;
+                        sim.overflow_isr_at = None;
+
+                        let elapsed = sub_mod(tick_count, start_tick_count, PERIOD);
+                        if elapsed < op.timeout {
+                            // Recalculate the deadline
+                            timer_isr_at = sim.pend_tick_after(op.timeout - elapsed, op.latency);
+                            continue;
+                        }
+                        tick_count
+                    };
+
+                    // The current operation is complete; start the next one
+                    let Some(next_op) = ops.next() else { break };
+                    op = next_op;
+                    log::debug!("  {op:?}");
+                    start_tick_count = tick_count;
+                    timer_isr_at = sim.pend_tick_after(op.timeout, op.latency);
+                }
+            }
+
+            #[test]
+            fn ones() {
+                do_test(
+                    0,
+                    std::iter::repeat(NarrowOp {
+                        timeout: 1,
+                        latency: 0,
+                        overflow_latency: 0,
+                    })
+                    .take(10),
+                );
+            }
+
+            #[test]
+            fn max_timeout() {
+                do_test(
+                    0,
+                    std::iter::repeat(NarrowOp {
+                        timeout: MAX_TIMEOUT,
+                        latency: 0,
+                        overflow_latency: 0,
+                    })
+                    .take(10),
+                );
+            }
+
+            #[test]
+            fn max_timeout_max_latency() {
+                do_test(
+                    u32::MAX,
+                    std::iter::repeat(NarrowOp {
+                        timeout: MAX_TIMEOUT,
+                        latency: $hw_headroom_ticks,
+                        overflow_latency: (RAW_PERIOD / 2 - 1) as u32,
+                    })
+                    .take(10),
+                );
+            }
+
+            #[quickcheck_macros::quickcheck]
+            fn quickcheck(start: u32, values: Vec<(u32, u32, u32)>) {
+                do_test(
+                    start,
+                    values
+                        .into_iter()
+                        .map(|(timeout, latency, overflow_latency)| NarrowOp {
+                            timeout: timeout % MAX_TIMEOUT + 1,
+                            latency: latency % ($hw_headroom_ticks + 1),
+                            overflow_latency: (overflow_latency as u64 % (RAW_PERIOD / 2)) as u32,
+                        })
+                        .take(10),
+                );
+            }
+        }
+    }
+
+    narrow_simulate!(mod narrow1 {}, 32_768, 1, 32, 16);
+    // 32.768kHz clock divided by a prescaler
+    narrow_simulate!(mod narrow2 {}, 32_768, 16, 4, 16);
+    narrow_simulate!(mod narrow3 {}, 32_768, 128, 1, 8);
+    narrow_simulate!(mod narrow4 {}, 10_000_000, 1, 100, 24);
+    narrow_simulate!(mod narrow5 {}, 125_000_000, 3, 125, 16);
 }
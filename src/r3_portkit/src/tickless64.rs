@@ -0,0 +1,507 @@
+//! Implements the core algorithm for tickless timing based on a 64-bit
+//! free-running hardware counter.
+//!
+//! Unlike [`tickless`](crate::tickless), which works on the lower 32 bits of
+//! the hardware counter and has to mark reference points periodically to keep
+//! track of wrap-arounds, this algorithm derives the OS tick count directly
+//! from the full 64-bit hardware tick count. A 64-bit counter doesn't wrap
+//! around in any practical time frame, so the state only changes when the
+//! timer is reset or reconfigured.
+use num_rational::Ratio;
+
+use crate::{
+    num::{floor_ratio128, reduce_ratio128},
+    tickless::CfgError,
+    utils::Init,
+};
+
+/// The parameters of the 64-bit tickless timing algorithm.
+///
+/// It can be passed to [`Tickless64Cfg::new`] to construct [`Tickless64Cfg`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Tickless64Options {
+    /// The numerator of the hardware timer frequency.
+    pub hw_freq_num: u64,
+    /// The denominator of the hardware timer frequency.
+    pub hw_freq_denom: u64,
+    /// The headroom for interrupt latency, measured in hardware timer cycles.
+    pub hw_headroom_ticks: u32,
+}
+
+/// The precomputed parameters for the 64-bit tickless implementation of
+/// [`r3_kernel::PortTimer`].
+///
+/// The OS tick count wraps around at `u32::MAX`, i.e.,
+/// [`r3_kernel::PortTimer::MAX_TICK_COUNT`] should be `u32::MAX`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tickless64Cfg {
+    /// The numerator of the number of hardware ticks per microsecond.
+    hw_ticks_per_micro_numer: u64,
+    /// The denominator of the number of hardware ticks per microsecond.
+    hw_ticks_per_micro_denom: u64,
+    /// The maximum interval (measured in microseconds) that can be reliably
+    /// measured.
+    max_timeout: u32,
+}
+
+impl Tickless64Cfg {
+    /// Construct a `Tickless64Cfg`.
+    pub const fn new(
+        Tickless64Options {
+            hw_freq_num,
+            hw_freq_denom,
+            hw_headroom_ticks,
+        }: Tickless64Options,
+    ) -> Result<Self, CfgError> {
+        if hw_freq_denom == 0 {
+            return Err(CfgError::FreqDenomZero);
+        } else if hw_freq_num == 0 {
+            return Err(CfgError::FreqNumZero);
+        }
+
+        // `hw_ticks_per_micro = freq_num / freq_denom / 1_000_000`
+        let hw_ticks_per_micro =
+            Ratio::new_raw(hw_freq_num as u128, hw_freq_denom as u128 * 1_000_000);
+        let hw_ticks_per_micro = reduce_ratio128(hw_ticks_per_micro);
+        assert!(*hw_ticks_per_micro.numer() >= 1);
+        assert!(*hw_ticks_per_micro.numer() <= 0xffff_ffff_ffff_ffff);
+        assert!(*hw_ticks_per_micro.denom() >= 1);
+
+        if floor_ratio128(hw_ticks_per_micro) > u32::MAX as u128 {
+            return Err(CfgError::FreqTooHigh);
+        }
+
+        // `hw_tick_count * denom` must fit in `u128`
+        if *hw_ticks_per_micro.denom() > u64::MAX as u128 {
+            return Err(CfgError::InternalOverflow);
+        }
+
+        // Find the maximum value of `max_timeout` such that:
+        //
+        //  // For every possible reference point...
+        //  ∀ref_hw_tick_count ∈ 0..2⁶⁴:
+        //    let ref_tick_count = floor(ref_hw_tick_count / hw_ticks_per_micro);
+        //
+        //    // Timeout is set to maximum
+        //    let next_tick_count = ref_tick_count + max_timeout;
+        //    let next_hw_tick_count = ceil(next_tick_count * hw_ticks_per_micro);
+        //
+        //    // Take an interrupt latency into account
+        //    let late_hw_tick_count = next_hw_tick_count + hw_headroom_ticks;
+        //
+        //    // Convert it back to OS tick count
+        //    let late_tick_count = floor(late_hw_tick_count / hw_ticks_per_micro);
+        //
+        //    // The tick count of the next tick shouldn't completely
+        //    // "revolve" around
+        //    late_tick_count <= ref_tick_count + u32::MAX
+        //
+        let max_timeout = (u32::MAX as u128 * *hw_ticks_per_micro.numer()
+            + *hw_ticks_per_micro.numer()
+            - 1)
+        .saturating_sub(
+            *hw_ticks_per_micro.denom() - 1
+                + hw_headroom_ticks as u128 * *hw_ticks_per_micro.denom(),
+        ) / *hw_ticks_per_micro.numer();
+
+        if max_timeout == 0 {
+            return Err(CfgError::OSMaxTimeoutTooLow);
+        }
+        assert!(max_timeout <= u32::MAX as u128);
+
+        Ok(Self {
+            hw_ticks_per_micro_numer: *hw_ticks_per_micro.numer() as u64,
+            hw_ticks_per_micro_denom: *hw_ticks_per_micro.denom() as u64,
+            max_timeout: max_timeout as u32,
+        })
+    }
+
+    /// Get the maximum interval (measured in microseconds) that can be
+    /// reliably measured. This value can be used as
+    /// [`r3_kernel::PortTimer::MAX_TIMEOUT`].
+    #[inline]
+    pub const fn max_timeout(&self) -> u32 {
+        self.max_timeout
+    }
+
+    /// Convert the given number of hardware ticks to OS ticks. Returns the
+    /// quotient (truncated to 32 bits) and the remainder (measured in
+    /// `1 / hw_ticks_per_micro_denom` hardware ticks).
+    #[inline]
+    fn hw_ticks_to_ticks(&self, hw_ticks: u64) -> (u32, u64) {
+        if self.hw_ticks_per_micro_denom == 1 {
+            // Avoid 128-bit arithmetics if possible
+            (
+                (hw_ticks / self.hw_ticks_per_micro_numer) as u32,
+                hw_ticks % self.hw_ticks_per_micro_numer,
+            )
+        } else {
+            let x = hw_ticks as u128 * self.hw_ticks_per_micro_denom as u128;
+            let numer = self.hw_ticks_per_micro_numer as u128;
+            ((x / numer) as u32, (x % numer) as u64)
+        }
+    }
+}
+
+/// Result type of [`Tickless64State::measure`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Measurement64 {
+    /// The hardware tick count at which the measurement ends.
+    ///
+    /// This value is equal to `hw_tick_count.wrapping_add(self.hw_ticks)`.
+    pub end_hw_tick_count: u64,
+    /// The number of hardware ticks in the measured interval.
+    pub hw_ticks: u64,
+}
+
+/// The internal state of the 64-bit tickless implementation of
+/// [`r3_kernel::PortTimer`].
+#[derive(Debug, Copy, Clone)]
+pub struct Tickless64State {
+    /// The OS tick count at the reference point.
+    ref_tick_count: u32,
+    /// The hardware tick count at the reference point.
+    ref_hw_tick_count: u64,
+}
+
+impl Init for Tickless64State {
+    const INIT: Self = Self {
+        ref_tick_count: Init::INIT,
+        ref_hw_tick_count: Init::INIT,
+    };
+}
+
+impl Tickless64State {
+    /// Mark the given hardware tick count as the origin (where
+    /// OS tick count is exactly zero).
+    #[inline]
+    pub fn reset(&mut self, hw_tick_count: u64) {
+        *self = Self {
+            ref_tick_count: 0,
+            ref_hw_tick_count: hw_tick_count,
+        };
+    }
+
+    /// Switch to a different [`Tickless64Cfg`] at the given hardware tick
+    /// count without disrupting the OS tick count. Returns the OS tick count
+    /// at the moment of the switch.
+    ///
+    /// This is the counterpart of
+    /// [`TicklessStateTrait::reconfigure`][1]. The fractional part of the OS
+    /// tick count (less than one microsecond) at this point is discarded.
+    /// After calling this method, `new_cfg` must be passed to all methods in
+    /// place of `cfg`.
+    ///
+    /// [1]: crate::tickless::TicklessStateTrait::reconfigure
+    #[inline]
+    pub fn reconfigure(
+        &mut self,
+        cfg: &Tickless64Cfg,
+        new_cfg: &Tickless64Cfg,
+        hw_tick_count: u64,
+    ) -> u32 {
+        let _ = new_cfg;
+        let tick_count = self.tick_count(cfg, hw_tick_count);
+        *self = Self {
+            ref_tick_count: tick_count,
+            ref_hw_tick_count: hw_tick_count,
+        };
+        tick_count
+    }
+
+    /// Calculate the earliest hardware tick count at which the OS tick count
+    /// reaches `tick_count(hw_tick_count) + tick_count_delta`.
+    ///
+    /// `tick_count_delta` must be in range `1..=cfg.`[`max_timeout`]`()`.
+    ///
+    /// Unlike [`TicklessStateTrait::mark_reference_and_measure`][1], this
+    /// method doesn't update the internal state.
+    ///
+    /// [`max_timeout`]: Tickless64Cfg::max_timeout
+    /// [1]: crate::tickless::TicklessStateTrait::mark_reference_and_measure
+    #[inline]
+    pub fn measure(
+        &self,
+        cfg: &Tickless64Cfg,
+        hw_tick_count: u64,
+        tick_count_delta: u32,
+    ) -> Measurement64 {
+        debug_assert_ne!(tick_count_delta, 0);
+        debug_assert!(tick_count_delta <= cfg.max_timeout);
+
+        let (_, remainder) =
+            cfg.hw_ticks_to_ticks(hw_tick_count.wrapping_sub(self.ref_hw_tick_count));
+
+        // Let `x = (hw_tick_count - ref_hw_tick_count) * denom`. The current
+        // OS tick count is `floor(x / numer)`, and `remainder = x % numer`.
+        // Find the minimum `hw_ticks` such that
+        //
+        //    (x + hw_ticks * denom) / numer >= floor(x / numer) + tick_count_delta
+        //  ⇔ hw_ticks * denom >= tick_count_delta * numer - remainder
+        //
+        let numer = cfg.hw_ticks_per_micro_numer as u128;
+        let denom = cfg.hw_ticks_per_micro_denom as u128;
+        let hw_ticks =
+            ((tick_count_delta as u128 * numer - remainder as u128 + denom - 1) / denom) as u64;
+
+        Measurement64 {
+            end_hw_tick_count: hw_tick_count.wrapping_add(hw_ticks),
+            hw_ticks,
+        }
+    }
+
+    /// Get the OS tick count.
+    #[inline]
+    pub fn tick_count(&self, cfg: &Tickless64Cfg, hw_tick_count: u64) -> u32 {
+        let (ticks, _) = cfg.hw_ticks_to_ticks(hw_tick_count.wrapping_sub(self.ref_hw_tick_count));
+        self.ref_tick_count.wrapping_add(ticks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::tickless::{TicklessCfg, TicklessDynStateCore, TicklessOptions, TicklessStateTrait};
+    use std::prelude::v1::*;
+
+    /// The timer frequencies (`hw_freq_num`, `hw_freq_denom`,
+    /// `hw_headroom_ticks`) used by the tests.
+    const FREQS: &[(u64, u64, u32)] = &[
+        (1, 1, 0),
+        (125_000_000, 1, 125),
+        (125_000_000, 3, 125),
+        (10_000_000, 1, 600_000_000),
+        (32_768, 1, 32),
+        (375, 1, 250),
+        (1, 260, 10),
+        (0x501e_e2c2_9a0f, 0xb79a_14f3, 0x64),
+        (0xff_ffff_ffff_ffff, 0xff_ffff_fffe, 0x41),
+    ];
+
+    fn cfg64(i: usize) -> Tickless64Cfg {
+        let (hw_freq_num, hw_freq_denom, hw_headroom_ticks) = FREQS[i % FREQS.len()];
+        Tickless64Cfg::new(Tickless64Options {
+            hw_freq_num,
+            hw_freq_denom,
+            hw_headroom_ticks,
+        })
+        .unwrap()
+    }
+
+    /// The 32-bit configuration equivalent to `cfg64(i)`
+    fn cfg32(i: usize) -> TicklessCfg {
+        let (hw_freq_num, hw_freq_denom, hw_headroom_ticks) = FREQS[i % FREQS.len()];
+        TicklessCfg::new(TicklessOptions {
+            force_full_hw_period: true,
+            resettable: true,
+            reconfigurable: true,
+            ..TicklessOptions::new(hw_freq_num, hw_freq_denom, hw_headroom_ticks)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn tickless64_known_values() {
+        assert_eq!(
+            Tickless64Cfg::new(Tickless64Options {
+                hw_freq_num: 125_000_000,
+                hw_freq_denom: 3,
+                hw_headroom_ticks: 125,
+            }),
+            Ok(Tickless64Cfg {
+                hw_ticks_per_micro_numer: 125,
+                hw_ticks_per_micro_denom: 3,
+                max_timeout: 4_294_967_292,
+            }),
+        );
+    }
+
+    /// `Tickless64Cfg` should reject invalid frequencies in the same way as
+    /// `TicklessCfg`.
+    #[test]
+    fn tickless64_bad_freqs() {
+        for (hw_freq_num, hw_freq_denom, hw_headroom_ticks, expected) in [
+            (0, 1, 1, CfgError::FreqNumZero),
+            (1, 0, 1, CfgError::FreqDenomZero),
+            (1_000_000 * 0x1_0000_0000, 1, 0, CfgError::FreqTooHigh),
+            (
+                0x1fffffffffffffff,
+                0x1ffffffffffffffe,
+                0,
+                CfgError::InternalOverflow,
+            ),
+        ] {
+            assert_eq!(
+                Tickless64Cfg::new(Tickless64Options {
+                    hw_freq_num,
+                    hw_freq_denom,
+                    hw_headroom_ticks,
+                }),
+                Err(expected)
+            );
+        }
+    }
+
+    /// The 64-bit algorithm should allow a longer timeout than the 32-bit one
+    /// when the hardware timer is fast.
+    #[test]
+    fn tickless64_longer_max_timeout() {
+        for i in 0..FREQS.len() {
+            assert!(cfg64(i).max_timeout() >= cfg32(i).max_timeout());
+        }
+
+        // 10MHz: 2³² hardware ticks ≈ 429 seconds
+        let i = 3;
+        assert!(cfg32(i).max_timeout() < 430_000_000);
+        assert!(cfg64(i).max_timeout() > 3_600_000_000);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn quickcheck_cfg(hw_freq_num: u64, hw_freq_denom: u64, hw_headroom_ticks: u32) {
+        // `Tickless64Cfg::new` includes various integrity checks
+        let _ = Tickless64Cfg::new(Tickless64Options {
+            hw_freq_num,
+            hw_freq_denom,
+            hw_headroom_ticks,
+        });
+    }
+
+    /// Run `Tickless64State` side by side with `TicklessDynStateCore` and
+    /// check that they produce identical results. Each element of `ops`
+    /// specifies the new frequency (an index into `FREQS`) and the timeout and
+    /// interrupt latency of the following timer interrupt.
+    fn do_test_compare(start: u64, ops: impl IntoIterator<Item = (usize, u32, u32)>) {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let (mut i, mut tcfg64, mut tcfg32) = (0, cfg64(0), cfg32(0));
+        let mut state64: Tickless64State = Init::INIT;
+        let mut state32: TicklessDynStateCore = Init::INIT;
+        let mut hw_tick_count = start;
+        state64.reset(hw_tick_count);
+        state32.reset(&tcfg32, hw_tick_count as u32);
+
+        for (new_i, timeout, latency) in ops {
+            if new_i % FREQS.len() != i {
+                i = new_i % FREQS.len();
+                let (new_tcfg64, new_tcfg32) = (cfg64(i), cfg32(i));
+                log::debug!("  switching to {new_tcfg64:?} at HW = {hw_tick_count}");
+                assert_eq!(
+                    state64.reconfigure(&tcfg64, &new_tcfg64, hw_tick_count),
+                    state32.reconfigure(&tcfg32, &new_tcfg32, hw_tick_count as u32),
+                );
+                (tcfg64, tcfg32) = (new_tcfg64, new_tcfg32);
+            }
+
+            let tick_count = state64.tick_count(&tcfg64, hw_tick_count);
+            assert_eq!(
+                state32.mark_reference(&tcfg32, hw_tick_count as u32),
+                tick_count
+            );
+
+            let timeout = timeout % tcfg32.max_timeout() + 1;
+            let latency = latency % (FREQS[i].2 + 1);
+            log::debug!("    HW = {hw_tick_count}, timeout = {timeout}, latency = {latency}");
+
+            let measurement64 = state64.measure(&tcfg64, hw_tick_count, timeout);
+            let measurement32 =
+                state32.mark_reference_and_measure(&tcfg32, hw_tick_count as u32, timeout);
+            assert_eq!(measurement64.hw_ticks, measurement32.hw_ticks as u64);
+            assert_eq!(
+                measurement64.end_hw_tick_count,
+                hw_tick_count.wrapping_add(measurement64.hw_ticks)
+            );
+
+            // `hw_ticks` must be the minimum amount of waiting required to
+            // fulfill the request
+            let end = measurement64.end_hw_tick_count;
+            assert_eq!(
+                state64.tick_count(&tcfg64, end).wrapping_sub(tick_count),
+                state32
+                    .tick_count(&tcfg32, end as u32)
+                    .wrapping_sub(tick_count)
+            );
+            assert!(state64.tick_count(&tcfg64, end).wrapping_sub(tick_count) >= timeout);
+            assert!(
+                state64
+                    .tick_count(&tcfg64, end.wrapping_sub(1))
+                    .wrapping_sub(tick_count)
+                    < timeout
+            );
+
+            hw_tick_count = end.wrapping_add(latency as u64);
+            assert_eq!(
+                state64.tick_count(&tcfg64, hw_tick_count),
+                state32.tick_count(&tcfg32, hw_tick_count as u32)
+            );
+        }
+    }
+
+    #[test]
+    fn compare_max_timeout() {
+        let n = FREQS.len();
+        do_test_compare(
+            0,
+            (0..n).flat_map(|i| [(i, u32::MAX, u32::MAX), (i, u32::MAX, 0), (i, 0, 0)]),
+        );
+    }
+
+    #[test]
+    fn compare_counter_wrap_around() {
+        let n = FREQS.len();
+        do_test_compare(
+            u64::MAX - 0x1_0000_0000,
+            (0..n).flat_map(|i| [(i, u32::MAX, u32::MAX); 4]),
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn compare_quickcheck(start: u64, ops: Vec<(usize, u32, u32)>) {
+        do_test_compare(start, ops);
+    }
+
+    /// A timeout longer than `TicklessCfg::max_timeout` should still be
+    /// handled correctly.
+    #[quickcheck_macros::quickcheck]
+    fn long_timeout_quickcheck(start: u64, ops: Vec<(usize, u32, u32)>) {
+        let mut state: Tickless64State = Init::INIT;
+        state.reset(start);
+        let mut hw_tick_count = start.wrapping_add(1);
+        let mut cfg = cfg64(0);
+
+        for (i, timeout, latency) in ops {
+            let new_cfg = cfg64(i);
+            let tick_count = state.reconfigure(&cfg, &new_cfg, hw_tick_count);
+            cfg = new_cfg;
+
+            let timeout = timeout % cfg.max_timeout() + 1;
+            let latency = latency % (FREQS[i % FREQS.len()].2 + 1);
+
+            let end = state
+                .measure(&cfg, hw_tick_count, timeout)
+                .end_hw_tick_count;
+            assert!(state.tick_count(&cfg, end).wrapping_sub(tick_count) >= timeout);
+            assert!(
+                state
+                    .tick_count(&cfg, end.wrapping_sub(1))
+                    .wrapping_sub(tick_count)
+                    < timeout
+            );
+
+            // The OS tick count must not revolve around even if the timer
+            // interrupt is late
+            let (hw_freq_num, hw_freq_denom, _) = FREQS[i % FREQS.len()];
+            let hw_elapsed = end.wrapping_add(latency as u64).wrapping_sub(hw_tick_count);
+            let expected_elapsed =
+                hw_elapsed as u128 * hw_freq_denom as u128 * 1_000_000 / hw_freq_num as u128;
+            assert!(expected_elapsed <= u32::MAX as u128);
+
+            hw_tick_count = end.wrapping_add(latency as u64);
+            let elapsed = state
+                .tick_count(&cfg, hw_tick_count)
+                .wrapping_sub(tick_count);
+            assert_eq!(elapsed as u128, expected_elapsed);
+        }
+    }
+}
@@ -15,6 +15,9 @@ use r3::kernel::{InterruptNum, InterruptPriority};
 ///  - Implement [`OsTimerOptions`] on the kernel trait type `$Traits`.
 ///  - Call `$Traits::configure_os_timer()` in your configuration function.
 ///    See the following example.
+///  - Optionally, set [`OsTimerOptions::RECONFIGURABLE`] to `true` and call
+///    `$Traits::set_timer_frequency()` whenever the timer clock frequency
+///    changes (e.g., because of dynamic voltage and frequency scaling).
 ///
 /// ```rust,ignore
 /// r3_support_rza1::use_os_timer!(unsafe impl PortTimer for SystemTraits);
@@ -78,6 +81,9 @@ macro_rules! use_os_timer {
             static mut TIMER_STATE: <$Traits as os_timer::imp::OsTimerInstance>::TicklessState =
                 Init::INIT;
 
+            static mut TIMER_CFG: tickless::TicklessCfg =
+                <$Traits as os_timer::imp::OsTimerInstance>::TICKLESS_CFG;
+
             // Safety: Only `use_os_timer!` is allowed to `impl` this
             unsafe impl os_timer::imp::OsTimerInstance for $Traits {
                 type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
@@ -85,6 +91,10 @@ macro_rules! use_os_timer {
                 fn tickless_state() -> *mut Self::TicklessState {
                     unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
                 }
+
+                fn tickless_cfg() -> *mut tickless::TicklessCfg {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_CFG) }
+                }
             }
 
             impl $Traits {
@@ -94,6 +104,24 @@ macro_rules! use_os_timer {
                 {
                     os_timer::imp::configure(b);
                 }
+
+                /// Inform the OS Timer driver that the timer clock
+                /// frequency is changing to `freq_num / freq_denom` Hz. The
+                /// new frequency takes effect when `change_clock` returns.
+                /// Requires `OsTimerOptions::RECONFIGURABLE`.
+                ///
+                /// `change_clock` is called with CPU Lock active to make the
+                /// actual clock change atomic with respect to the kernel
+                /// timekeeping. The kernel tick count is preserved across the
+                /// change, losing less than one microsecond and the time
+                /// taken by `change_clock` at most.
+                pub fn set_timer_frequency(
+                    freq_num: u64,
+                    freq_denom: u64,
+                    change_clock: impl FnOnce(),
+                ) -> Result<(), tickless::SetTimerFrequencyError> {
+                    os_timer::imp::set_timer_frequency::<Self>(freq_num, freq_denom, change_clock)
+                }
             }
         };
     };
@@ -124,6 +152,11 @@ pub trait OsTimerOptions {
     const HEADROOM: u32 =
         (Self::FREQUENCY as u128 * 60 / Self::FREQUENCY_DENOMINATOR as u128).min(0x40000000) as u32;
 
+    /// Allow changing the timer frequency at runtime by
+    /// `set_timer_frequency` generated by [`use_os_timer!`]. This makes the
+    /// driver slightly slower. Defaults to `false`.
+    const RECONFIGURABLE: bool = false;
+
     /// The interrupt priority of the timer interrupt line.
     /// Defaults to `0xc0`.
     const INTERRUPT_OSTM_PRIORITY: InterruptPriority = 0xc0;
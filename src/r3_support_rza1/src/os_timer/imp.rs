@@ -1,8 +1,10 @@
 //! The implementation of the RZ/A1 OS Timer driver.
-use r3::kernel::{traits, Cfg, InterruptLine, StaticInterruptHandler};
+use r3::kernel::{raw, traits, Cfg, InterruptLine, StaticInterruptHandler};
 use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
 use r3_port_arm::Gic;
-use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use r3_portkit::tickless::{
+    SetTimerFrequencyError, TicklessCfg, TicklessOptions, TicklessStateTrait,
+};
 use rza1::ostm0 as ostm;
 
 use crate::os_timer::cfg::OsTimerOptions;
@@ -13,13 +15,11 @@ use crate::os_timer::cfg::OsTimerOptions;
 ///
 /// Only meant to be implemented by [`use_os_timer!`].
 pub unsafe trait OsTimerInstance: KernelTraits + OsTimerOptions + Gic {
-    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
-        hw_freq_num: <Self as OsTimerOptions>::FREQUENCY,
-        hw_freq_denom: <Self as OsTimerOptions>::FREQUENCY_DENOMINATOR,
-        hw_headroom_ticks: <Self as OsTimerOptions>::HEADROOM,
-        force_full_hw_period: true,
-        resettable: false,
-    }) {
+    /// The initial tickless configuration.
+    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(tickless_options::<Self>(
+        <Self as OsTimerOptions>::FREQUENCY,
+        <Self as OsTimerOptions>::FREQUENCY_DENOMINATOR,
+    )) {
         Ok(x) => x,
         Err(e) => e.panic(),
     };
@@ -27,6 +27,21 @@ pub unsafe trait OsTimerInstance: KernelTraits + OsTimerOptions + Gic {
     type TicklessState: TicklessStateTrait;
 
     fn tickless_state() -> *mut Self::TicklessState;
+
+    /// The current tickless configuration. Only used if
+    /// [`OsTimerOptions::RECONFIGURABLE`] is `true`.
+    fn tickless_cfg() -> *mut TicklessCfg;
+}
+
+const fn tickless_options<Traits: OsTimerOptions + ?Sized>(
+    hw_freq_num: u64,
+    hw_freq_denom: u64,
+) -> TicklessOptions {
+    TicklessOptions {
+        force_full_hw_period: true,
+        reconfigurable: Traits::RECONFIGURABLE,
+        ..TicklessOptions::new(hw_freq_num, hw_freq_denom, Traits::HEADROOM)
+    }
 }
 
 trait OsTimerInstanceExt: OsTimerInstance {
@@ -34,6 +49,21 @@ trait OsTimerInstanceExt: OsTimerInstance {
         // Safety: Verified by the user of `use_os_timer!`
         unsafe { &*(Self::OSTM_BASE as *const ostm::RegisterBlock) }
     }
+
+    /// Get the current tickless configuration.
+    ///
+    /// # Safety
+    ///
+    /// Must not race with [`set_timer_frequency`].
+    #[inline]
+    unsafe fn current_tickless_cfg() -> TicklessCfg {
+        if Self::RECONFIGURABLE {
+            // Safety: CPU Lock protects it from concurrent access
+            unsafe { *Self::tickless_cfg() }
+        } else {
+            Self::TICKLESS_CFG
+        }
+    }
 }
 impl<T: OsTimerInstance> OsTimerInstanceExt for T {}
 
@@ -106,7 +136,8 @@ fn hw_tick_count<System: OsTimerInstance>() -> u32 {
 ///
 /// Only meant to be referenced by `use_os_timer!`.
 pub unsafe fn tick_count<System: OsTimerInstance>() -> UTicks {
-    let tcfg = &System::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { System::current_tickless_cfg() };
 
     let hw_tick_count = hw_tick_count::<System>();
 
@@ -132,10 +163,14 @@ pub unsafe fn pend_tick<Traits: OsTimerInstance>() {
 /// Only meant to be referenced by `use_os_timer!`.
 pub unsafe fn pend_tick_after<Traits: OsTimerInstance>(tick_count_delta: UTicks) {
     let ostm = Traits::ostm_regs();
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
     // Safety: CPU Lock protects it from concurrent access
     let tstate = unsafe { &mut *Traits::tickless_state() };
 
+    // `MAX_TIMEOUT` doesn't reflect the current frequency
+    let tick_count_delta = tick_count_delta.min(tcfg.max_timeout());
+
     let cur_hw_tick_count = hw_tick_count::<Traits>();
     let measurement = tstate.mark_reference_and_measure(tcfg, cur_hw_tick_count, tick_count_delta);
 
@@ -150,9 +185,49 @@ pub unsafe fn pend_tick_after<Traits: OsTimerInstance>(tick_count_delta: UTicks)
     }
 }
 
+/// Implements `set_timer_frequency` generated by `use_os_timer!`.
+pub fn set_timer_frequency<Traits: OsTimerInstance>(
+    freq_num: u64,
+    freq_denom: u64,
+    change_clock: impl FnOnce(),
+) -> Result<(), SetTimerFrequencyError> {
+    if !Traits::RECONFIGURABLE {
+        return Err(SetTimerFrequencyError::NotReconfigurable);
+    }
+
+    let new_tcfg = TicklessCfg::new(tickless_options::<Traits>(freq_num, freq_denom))
+        .map_err(SetTimerFrequencyError::BadFrequency)?;
+
+    <System<Traits> as raw::KernelBase>::raw_acquire_cpu_lock()
+        .map_err(|_| SetTimerFrequencyError::BadContext)?;
+
+    // Safety: CPU Lock protects them from concurrent access
+    let tcfg = unsafe { &mut *Traits::tickless_cfg() };
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+
+    // Rebase the tickless state at the last hardware tick count measured in
+    // the old frequency. The counter keeps counting across the clock change,
+    // so the error is limited to the time taken by `change_clock`.
+    let cur_hw_tick_count = hw_tick_count::<Traits>();
+    change_clock();
+    tstate.reconfigure(tcfg, &new_tcfg, cur_hw_tick_count);
+    *tcfg = new_tcfg;
+
+    // `OSTMnCMP` was programmed in the old frequency. Let the kernel program
+    // it again.
+    // Safety: CPU Lock active
+    unsafe { pend_tick::<Traits>() };
+
+    // Safety: We own the CPU Lock
+    unsafe { <System<Traits> as raw::KernelBase>::raw_release_cpu_lock().unwrap() };
+
+    Ok(())
+}
+
 #[inline]
 fn handle_tick<Traits: OsTimerInstance>() {
-    let tcfg = &Traits::TICKLESS_CFG;
+    // Safety: CPU Lock protects it from concurrent access
+    let tcfg = &unsafe { Traits::current_tickless_cfg() };
 
     // Safety: CPU Lock protects it from concurrent access
     let tstate = unsafe { &mut *Traits::tickless_state() };
@@ -0,0 +1,286 @@
+//! Decodes [`defmt`] log frames in a test driver's output.
+//!
+//! A test driver using `defmt` emits binary frames that only make sense with
+//! the interned strings stored in the `.defmt` section of its executable.
+//! [`DefmtDecode`] converts them to plain text lines so that the rest of the
+//! test runner can look for markers such as `!- TEST WAS SUCCESSFUL -!`.
+//!
+//! [`defmt`]: https://defmt.ferrous-systems.com/
+use defmt_decoder::{DecodeError, StreamDecoder, Table};
+use futures_core::ready;
+use std::{
+    io::{self, Write},
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Load the `defmt` table from the specified executable. Returns `Ok(None)` if
+/// the executable doesn't use `defmt`.
+pub fn load_table(exe: &Path) -> anyhow::Result<Option<Table>> {
+    let elf = std::fs::read(exe)?;
+    Table::parse(&elf)
+}
+
+/// The decoding adapter for [`AsyncRead`] types. Each decoded frame is output
+/// as a single line.
+pub struct DefmtDecode<'a> {
+    inner: Pin<Box<dyn AsyncRead + 'a>>,
+    decoder: Box<dyn StreamDecoder + 'a>,
+    /// `true` if the decoder can resynchronize after encountering a malformed
+    /// frame.
+    can_recover: bool,
+    /// Decoded text that hasn't been read yet.
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+}
+
+impl<'a> DefmtDecode<'a> {
+    pub fn new(inner: Pin<Box<dyn AsyncRead + 'a>>, table: &'a Table) -> Self {
+        Self {
+            inner,
+            decoder: table.new_stream_decoder(),
+            can_recover: table.encoding().can_recover(),
+            decoded: Vec::new(),
+            decoded_pos: 0,
+        }
+    }
+}
+
+impl AsyncRead for DefmtDecode<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = Pin::into_inner(self);
+        loop {
+            if this.decoded_pos < this.decoded.len() {
+                let decoded = &this.decoded[this.decoded_pos..];
+                let num_bytes_read = decoded.len().min(buf.remaining());
+                buf.put_slice(&decoded[..num_bytes_read]);
+                this.decoded_pos += num_bytes_read;
+                return Poll::Ready(Ok(()));
+            }
+
+            this.decoded.clear();
+            this.decoded_pos = 0;
+
+            let mut raw = [0u8; 1024];
+            let mut raw_buf = ReadBuf::new(&mut raw);
+            ready!(this.inner.as_mut().poll_read(cx, &mut raw_buf))?;
+            if raw_buf.filled().is_empty() {
+                // End of stream. Any incomplete frame is discarded.
+                return Poll::Ready(Ok(()));
+            }
+
+            this.decoder.received(raw_buf.filled());
+
+            loop {
+                match this.decoder.decode() {
+                    Ok(frame) => {
+                        writeln!(this.decoded, "{}", frame.display(false)).unwrap();
+                    }
+                    Err(DecodeError::UnexpectedEof) => break,
+                    Err(DecodeError::Malformed) if this.can_recover => {
+                        log::warn!("Skipping a malformed `defmt` frame");
+                    }
+                    Err(DecodeError::Malformed) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "received a malformed `defmt` frame",
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    /// Build a minimal ELF64 file containing a `.defmt` section with the
+    /// specified log messages (`(index, level, format)`), mimicking the output
+    /// of `defmt`'s linker script. `encoding` is `None` to build a file that
+    /// doesn't use `defmt`, in which case the section is named `.rodata`
+    /// instead.
+    fn build_elf(encoding: Option<&str>, messages: &[(u64, &str, &str)]) -> Vec<u8> {
+        const SHN_ABS: u16 = 0xfff1;
+
+        // (name, section index, value)
+        let mut symbols = Vec::new();
+        if let Some(encoding) = encoding {
+            symbols.push(("_defmt_version_ = 3".to_owned(), SHN_ABS, 1));
+            symbols.push((format!("_defmt_encoding_ = {encoding}"), SHN_ABS, 1));
+        }
+        for &(index, level, format) in messages {
+            let name = serde_json::json!({
+                "package": "test",
+                "tag": format!("defmt_{level}"),
+                "data": format,
+                "disambiguator": index.to_string(),
+            });
+            symbols.push((name.to_string(), 1, index));
+        }
+
+        let defmt = vec![0u8; 16];
+
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; 24];
+        for (name, shndx, value) in &symbols {
+            symtab.extend((strtab.len() as u32).to_le_bytes());
+            symtab.push(0x11); // STB_GLOBAL, STT_OBJECT
+            symtab.push(0);
+            symtab.extend(shndx.to_le_bytes());
+            symtab.extend(value.to_le_bytes());
+            symtab.extend(1u64.to_le_bytes());
+            strtab.extend(name.as_bytes());
+            strtab.push(0);
+        }
+
+        let shstrtab = b"\0.defmt\0.strtab\0.symtab\0.shstrtab\0.rodata\0";
+        let data_section_name = if encoding.is_some() { 1 } else { 34 };
+
+        // (name offset, type, link, info, entry size, contents)
+        let sections: [(u32, u32, u32, u32, u64, &[u8]); 4] = [
+            (data_section_name, 1, 0, 0, 0, &defmt),
+            (8, 3, 0, 0, 0, &strtab),
+            (16, 2, 2, 1, 24, &symtab),
+            (24, 3, 0, 0, 0, shstrtab),
+        ];
+
+        let mut data = Vec::new();
+        let mut headers = vec![0u8; 64];
+        for (name, ty, link, info, entsize, contents) in sections {
+            while data.len() % 8 != 0 {
+                data.push(0);
+            }
+            let offset = 64 + data.len() as u64;
+            data.extend(contents);
+
+            headers.extend(name.to_le_bytes());
+            headers.extend(ty.to_le_bytes());
+            headers.extend(0u64.to_le_bytes()); // sh_flags
+            headers.extend(0u64.to_le_bytes()); // sh_addr
+            headers.extend(offset.to_le_bytes());
+            headers.extend((contents.len() as u64).to_le_bytes());
+            headers.extend(link.to_le_bytes());
+            headers.extend(info.to_le_bytes());
+            headers.extend(8u64.to_le_bytes()); // sh_addralign
+            headers.extend(entsize.to_le_bytes());
+        }
+        while data.len() % 8 != 0 {
+            data.push(0);
+        }
+
+        let mut elf = Vec::new();
+        elf.extend(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        elf.extend(2u16.to_le_bytes()); // ET_EXEC
+        elf.extend(62u16.to_le_bytes()); // EM_X86_64
+        elf.extend(1u32.to_le_bytes()); // e_version
+        elf.extend(0u64.to_le_bytes()); // e_entry
+        elf.extend(0u64.to_le_bytes()); // e_phoff
+        elf.extend((64 + data.len() as u64).to_le_bytes()); // e_shoff
+        elf.extend(0u32.to_le_bytes()); // e_flags
+        elf.extend(64u16.to_le_bytes()); // e_ehsize
+        elf.extend(56u16.to_le_bytes()); // e_phentsize
+        elf.extend(0u16.to_le_bytes()); // e_phnum
+        elf.extend(64u16.to_le_bytes()); // e_shentsize
+        elf.extend(5u16.to_le_bytes()); // e_shnum
+        elf.extend(4u16.to_le_bytes()); // e_shstrndx
+        assert_eq!(elf.len(), 64);
+        elf.extend(data);
+        elf.extend(headers);
+        elf
+    }
+
+    const MESSAGES: &[(u64, &str, &str)] = &[
+        (1, "info", "Hello, world!"),
+        (2, "debug", "The answer is {=u8}!"),
+    ];
+
+    /// Write an ELF file built by [`build_elf`] and load the `defmt` table from
+    /// it.
+    fn load_test_table(encoding: Option<&str>) -> Option<Table> {
+        let dir = tempdir::TempDir::new("r3_test_runner").unwrap();
+        let exe = dir.path().join("test.elf");
+        let messages = if encoding.is_some() { MESSAGES } else { &[] };
+        std::fs::write(&exe, build_elf(encoding, messages)).unwrap();
+        load_table(&exe).unwrap()
+    }
+
+    /// An [`AsyncRead`] delivering the specified chunks one by one.
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl AsyncRead for Chunks {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if !self.0.is_empty() {
+                buf.put_slice(&self.0.remove(0));
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn decode(table: &Table, chunks: Vec<Vec<u8>>) -> io::Result<String> {
+        let mut output = String::new();
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(
+                DefmtDecode::new(Box::pin(Chunks(chunks)), table).read_to_string(&mut output),
+            )?;
+        Ok(output)
+    }
+
+    #[test]
+    fn load_table_without_defmt() {
+        assert!(load_test_table(None).is_none());
+    }
+
+    #[test]
+    fn decode_raw() {
+        let table = load_test_table(Some("raw")).unwrap();
+
+        // Frames split across chunks
+        let stream = [1, 0, 2, 0, 42, 1, 0];
+        let chunks = stream.iter().map(|&b| vec![b]).collect();
+        assert_eq!(
+            decode(&table, chunks).unwrap(),
+            "INFO Hello, world!\nDEBUG The answer is 42!\nINFO Hello, world!\n"
+        );
+    }
+
+    #[test]
+    fn decode_raw_malformed() {
+        let table = load_test_table(Some("raw")).unwrap();
+
+        // The raw encoding can't resynchronize after an unknown index
+        let error = decode(&table, vec![vec![1, 0, 9, 0]]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_rzcobs() {
+        let table = load_test_table(Some("rzcobs")).unwrap();
+
+        // rzCOBS-encoded frames, each terminated by `0x00`. The second frame
+        // is truncated and skipped.
+        let stream = vec![
+            vec![0x01, 0x7e, 0x00, 0x7e],
+            vec![0x00, 0x02, 0x2a],
+            vec![0x7a, 0x00],
+        ];
+        assert_eq!(
+            decode(&table, stream).unwrap(),
+            "INFO Hello, world!\nDEBUG The answer is 42!\n"
+        );
+    }
+}
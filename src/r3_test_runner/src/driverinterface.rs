@@ -8,7 +8,7 @@ use std::{
 };
 use tokio::io::AsyncReadExt;
 
-use crate::{selection, subprocess, targets};
+use crate::{defmtdecode, selection, subprocess, targets};
 
 /// Interface to a test driver, encompassing the identity of a test driver crate
 /// as well as a reference to its build output directory.
@@ -105,7 +105,8 @@ impl TestDriver {
     ) -> impl Future<Output = Result<Self, TestDriverNewError>> {
         // Choose the right test driver for the given target architecture
         let crate_name = match target_arch {
-            targets::Arch::Armv7A => "r3_port_arm_test_driver",
+            targets::Arch::Armv7A { .. } => "r3_port_arm_test_driver",
+            targets::Arch::Aarch64 { .. } => "r3_port_aarch64_test_driver",
             targets::Arch::ArmM { .. } => "r3_port_arm_m_test_driver",
             targets::Arch::Riscv { .. } => "r3_port_riscv_test_driver",
         };
@@ -410,12 +411,23 @@ async fn debug_probe_program_and_get_output_until<P: AsRef<[u8]>>(
     exe: &Path,
     markers: impl IntoIterator<Item = P>,
 ) -> Result<Vec<u8>, RunError> {
+    // If the executable uses `defmt`, decode the frames into text. The table
+    // must outlive `stream`.
+    let defmt_table = defmtdecode::load_table(exe).map_err(RunError::Other)?;
+    if defmt_table.is_some() {
+        log::debug!("The executable uses `defmt`; decoding its output");
+    }
+
     let mut stream = debug_probe
         .program_and_get_output(exe)
         .await
         .map_err(RunError::Other)?;
     log::trace!("debug_probe_program_and_get_output_until: Got a stream");
 
+    if let Some(defmt_table) = &defmt_table {
+        stream = Box::pin(defmtdecode::DefmtDecode::new(stream, defmt_table));
+    }
+
     let matcher = aho_corasick::AhoCorasickBuilder::new().build(markers);
 
     let mut output = Vec::new();
@@ -10,6 +10,7 @@ use anyhow::{bail, Context};
 use clap::Parser;
 use std::{env, path::Path};
 
+mod defmtdecode;
 mod driverinterface;
 mod selection;
 mod subprocess;
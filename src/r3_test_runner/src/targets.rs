@@ -39,7 +39,7 @@ pub struct LinkerScripts {
 
 impl LinkerScripts {
     /// Create `LinkerScripts` to use the `link_ram_harvard.x` provided by
-    /// `r3_port_arm`. The specified string is written to `memory.x`, which will
+    /// `r3_port_arm` or `r3_port_aarch64`. The specified string is written to `memory.x`, which will
     /// be imported by `link_ram_harvard.x`.
     fn arm_harvard(memory_definition: String) -> Self {
         Self {
@@ -134,14 +134,114 @@ type DynAsyncRead<'a> = Pin<Box<dyn AsyncRead + 'a>>;
 
 pub static TARGETS: &[(&str, &dyn Target)] = &[
     ("nucleo_f401re", &probe_rs::NucleoF401re),
-    ("qemu_mps2_an385", &qemu::arm::QemuMps2An385),
-    ("qemu_mps2_an505", &qemu::arm::QemuMps2An505),
+    (
+        "qemu_mps2_an385",
+        &qemu::arm::QemuMps2An385 {
+            timer: qemu::arm::ArmMTimer::Tickful,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an385_tickless",
+        &qemu::arm::QemuMps2An385 {
+            timer: qemu::arm::ArmMTimer::Tickless,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an385_tickless_compensated",
+        &qemu::arm::QemuMps2An385 {
+            timer: qemu::arm::ArmMTimer::TicklessCompensated,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an385_tickless_cmsdk",
+        &qemu::arm::QemuMps2An385 {
+            timer: qemu::arm::ArmMTimer::TicklessCmsdk,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an385_mpu",
+        &qemu::arm::QemuMps2An385 {
+            timer: qemu::arm::ArmMTimer::Tickful,
+            mpu: true,
+        },
+    ),
+    (
+        "qemu_mps2_an505",
+        &qemu::arm::QemuMps2An505 {
+            timer: qemu::arm::ArmMTimer::Tickful,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an505_tickless",
+        &qemu::arm::QemuMps2An505 {
+            timer: qemu::arm::ArmMTimer::Tickless,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an505_tickless_cmsdk",
+        &qemu::arm::QemuMps2An505 {
+            timer: qemu::arm::ArmMTimer::TicklessCmsdk,
+            mpu: false,
+        },
+    ),
+    (
+        "qemu_mps2_an505_mpu",
+        &qemu::arm::QemuMps2An505 {
+            timer: qemu::arm::ArmMTimer::Tickful,
+            mpu: true,
+        },
+    ),
     ("qemu_realview_pbx_a9", &qemu::arm::QemuRealviewPbxA9),
+    ("qemu_virt_a15", &qemu::arm::QemuVirtA15 { gic_v3: false }),
+    (
+        "qemu_virt_a15_gicv3",
+        &qemu::arm::QemuVirtA15 { gic_v3: true },
+    ),
+    (
+        "qemu_virt_aarch64",
+        &qemu::arm::QemuVirtAarch64 { gic_v3: false },
+    ),
+    (
+        "qemu_virt_aarch64_gicv3",
+        &qemu::arm::QemuVirtAarch64 { gic_v3: true },
+    ),
     ("gr_peach", &openocd::GrPeach),
     ("qemu_sifive_e_rv32", &qemu::riscv::QemuSiFiveE(Xlen::_32)),
     ("qemu_sifive_e_rv64", &qemu::riscv::QemuSiFiveE(Xlen::_64)),
-    ("qemu_sifive_u_rv32", &qemu::riscv::QemuSiFiveU(Xlen::_32)),
-    ("qemu_sifive_u_rv64", &qemu::riscv::QemuSiFiveU(Xlen::_64)),
+    (
+        "qemu_sifive_u_rv32",
+        &qemu::riscv::QemuSiFiveU {
+            xlen: Xlen::_32,
+            pmp: false,
+        },
+    ),
+    (
+        "qemu_sifive_u_rv64",
+        &qemu::riscv::QemuSiFiveU {
+            xlen: Xlen::_64,
+            pmp: false,
+        },
+    ),
+    (
+        "qemu_sifive_u_pmp_rv32",
+        &qemu::riscv::QemuSiFiveU {
+            xlen: Xlen::_32,
+            pmp: true,
+        },
+    ),
+    (
+        "qemu_sifive_u_pmp_rv64",
+        &qemu::riscv::QemuSiFiveU {
+            xlen: Xlen::_64,
+            pmp: true,
+        },
+    ),
     (
         "qemu_sifive_u_s_rv32",
         &qemu::riscv::QemuSiFiveUModeS(Xlen::_32),
@@ -150,6 +250,51 @@ pub static TARGETS: &[(&str, &dyn Target)] = &[
         "qemu_sifive_u_s_rv64",
         &qemu::riscv::QemuSiFiveUModeS(Xlen::_64),
     ),
+    (
+        "qemu_virt_rv32",
+        &qemu::riscv::QemuVirt {
+            xlen: Xlen::_32,
+            s_mode: false,
+            vector: false,
+            smp: true,
+        },
+    ),
+    (
+        "qemu_virt_rv64",
+        &qemu::riscv::QemuVirt {
+            xlen: Xlen::_64,
+            s_mode: false,
+            vector: false,
+            smp: true,
+        },
+    ),
+    (
+        "qemu_virt_s_rv32",
+        &qemu::riscv::QemuVirt {
+            xlen: Xlen::_32,
+            s_mode: true,
+            vector: false,
+            smp: false,
+        },
+    ),
+    (
+        "qemu_virt_s_rv64",
+        &qemu::riscv::QemuVirt {
+            xlen: Xlen::_64,
+            s_mode: true,
+            vector: false,
+            smp: false,
+        },
+    ),
+    (
+        "qemu_virt_v_rv64",
+        &qemu::riscv::QemuVirt {
+            xlen: Xlen::_64,
+            s_mode: false,
+            vector: true,
+            smp: false,
+        },
+    ),
     ("red_v", &jlink::RedV),
     ("maix", &kflash::Maix),
     ("rp_pico", &rp_pico::RaspberryPiPico),
@@ -178,7 +323,18 @@ impl<T: Target> Target for OverrideTargetArch<T> {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Arch {
     /// Armv7-A
-    Armv7A,
+    Armv7A {
+        /// The Floating-point extension (VFPv3-D16).
+        fpu: bool,
+        /// The Advanced SIMD extension (NEON). Implies VFPv3-D32. Requires
+        /// `fpu`.
+        neon: bool,
+    },
+    /// Armv8-A in AArch64 state
+    Aarch64 {
+        /// The FP/SIMD registers. Disabling this selects a soft-float target.
+        fp: bool,
+    },
     /// Arm M-Profile
     ArmM {
         /// Specifies the architecture version to use.
@@ -203,6 +359,8 @@ pub enum Arch {
         f: bool,
         /// The "D" extension (double-precision floating point numbers)
         d: bool,
+        /// The "V" extension (vectors)
+        v: bool,
     },
 }
 
@@ -231,6 +389,7 @@ pub struct BuildOpt {
 impl Arch {
     const NAMED_ARCHS: &'static [(&'static str, Self)] = &[
         ("cortex_a9", Self::CORTEX_A9),
+        ("cortex_a53", Self::CORTEX_A53),
         ("cortex_m0", Self::CORTEX_M0),
         ("cortex_m3", Self::CORTEX_M3),
         ("cortex_m4", Self::CORTEX_M4),
@@ -247,6 +406,7 @@ impl Arch {
                 c: false,
                 f: false,
                 d: false,
+                v: false,
             },
         ),
         (
@@ -259,6 +419,7 @@ impl Arch {
                 c: false,
                 f: false,
                 d: false,
+                v: false,
             },
         ),
         (
@@ -271,11 +432,17 @@ impl Arch {
                 c: false,
                 f: false,
                 d: false,
+                v: false,
             },
         ),
     ];
 
-    const CORTEX_A9: Self = Self::Armv7A;
+    const CORTEX_A9: Self = Self::Armv7A {
+        fpu: false,
+        neon: false,
+    };
+
+    const CORTEX_A53: Self = Self::Aarch64 { fp: true };
 
     const CORTEX_M0: Self = Self::ArmM {
         version: ArmMVersion::Armv6M,
@@ -321,6 +488,7 @@ impl Arch {
         c: true,
         f: false,
         d: false,
+        v: false,
     };
 
     const RV64IMAC: Self = Self::Riscv {
@@ -331,6 +499,7 @@ impl Arch {
         c: true,
         f: false,
         d: false,
+        v: false,
     };
 
     const RV32GC: Self = Self::Riscv {
@@ -341,6 +510,7 @@ impl Arch {
         c: true,
         f: true,
         d: true,
+        v: false,
     };
 
     const RV64GC: Self = Self::Riscv {
@@ -351,13 +521,48 @@ impl Arch {
         c: true,
         f: true,
         d: true,
+        v: false,
     };
 
     pub fn build_opt(&self) -> Option<BuildOpt> {
         match self {
             // Arm A-Profile
             // -------------------------------------------------------------
-            Self::Armv7A => Some(BuildOpt::from_target_triple("armv7a-none-eabi")),
+            Self::Armv7A {
+                fpu: false,
+                neon: false,
+            } => Some(BuildOpt::from_target_triple("armv7a-none-eabi")),
+
+            // `armv7a-none-eabihf` is a tier 3 target, so `core` has to be
+            // built from source. Specifying target features ensures that.
+            Self::Armv7A {
+                fpu: true,
+                neon: false,
+            } => Some(
+                BuildOpt::from_target_triple("armv7a-none-eabihf")
+                    .with_target_features(&[Some("+vfp3d16")]),
+            ),
+
+            Self::Armv7A {
+                fpu: true,
+                neon: true,
+            } => Some(
+                BuildOpt::from_target_triple("armv7a-none-eabihf")
+                    .with_target_features(&[Some("+neon")]),
+            ),
+
+            Self::Armv7A {
+                fpu: false,
+                neon: true,
+            } => None,
+
+            Self::Aarch64 { fp: true } => {
+                Some(BuildOpt::from_target_triple("aarch64-unknown-none"))
+            }
+
+            Self::Aarch64 { fp: false } => Some(BuildOpt::from_target_triple(
+                "aarch64-unknown-none-softfloat",
+            )),
 
             // Arm M-Profile
             // -------------------------------------------------------------
@@ -431,6 +636,7 @@ impl Arch {
                 c: false,
                 f: false,
                 d: false,
+                v: false,
             } => Some(BuildOpt::from_target_triple("riscv32i-unknown-none-elf")),
 
             Self::Riscv {
@@ -441,6 +647,7 @@ impl Arch {
                 c: true,
                 f: false,
                 d: false,
+                v: false,
             } => Some(BuildOpt::from_target_triple("riscv32imc-unknown-none-elf")),
 
             Self::Riscv {
@@ -451,6 +658,7 @@ impl Arch {
                 c: true,
                 f: false,
                 d: false,
+                v: false,
             } => Some(BuildOpt::from_target_triple("riscv32imac-unknown-none-elf")),
 
             Self::Riscv {
@@ -461,6 +669,7 @@ impl Arch {
                 c: true,
                 f: false,
                 d: false,
+                v: false,
             } => Some(BuildOpt::from_target_triple("riscv64imac-unknown-none-elf")),
 
             Self::Riscv {
@@ -471,6 +680,7 @@ impl Arch {
                 c: true,
                 f: true,
                 d: true,
+                v: false,
             } => Some(BuildOpt::from_target_triple("riscv64gc-unknown-none-elf")),
 
             &Self::Riscv {
@@ -481,6 +691,7 @@ impl Arch {
                 c,
                 f,
                 d,
+                v,
             } => Some(
                 BuildOpt::from_target_triple(match xlen {
                     Xlen::_32 => "riscv32imac-unknown-none-elf",
@@ -493,6 +704,7 @@ impl Arch {
                     (!c).then_some("-c"),
                     f.then_some("+f"),
                     d.then_some("+d"),
+                    v.then_some("+v"),
                 ]),
             ),
         }
@@ -517,7 +729,8 @@ impl Arch {
             Some(Self::$variant { $($feat,)* $($extra,)* })
         }}
         match self {
-            Self::Armv7A => None,
+            Self::Armv7A { fpu, neon } => features!(Self::Armv7A { fpu, neon; }),
+            Self::Aarch64 { fp } => features!(Self::Aarch64 { fp; }),
             Self::ArmM { fpu, dsp, version } => features!(Self::ArmM { fpu, dsp; version }),
             Self::Riscv {
                 e,
@@ -526,8 +739,9 @@ impl Arch {
                 c,
                 f,
                 d,
+                v,
                 xlen,
-            } => features!(Self::Riscv { e, m, a, c, f, d; xlen }),
+            } => features!(Self::Riscv { e, m, a, c, f, d, v; xlen }),
         }
     }
 }
@@ -552,7 +766,23 @@ impl BuildOpt {
 impl fmt::Display for Arch {
     fn fmt(&self, fm: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Armv7A => write!(fm, "cortex_a9"),
+            Self::Armv7A { fpu, neon } => {
+                write!(fm, "cortex_a9")?;
+                if *fpu {
+                    write!(fm, "+fpu")?;
+                }
+                if *neon {
+                    write!(fm, "+neon")?;
+                }
+                Ok(())
+            }
+            Self::Aarch64 { fp } => {
+                write!(fm, "cortex_a53")?;
+                if !*fp {
+                    write!(fm, "-fp")?;
+                }
+                Ok(())
+            }
             Self::ArmM {
                 mut fpu,
                 mut dsp,
@@ -588,6 +818,7 @@ impl fmt::Display for Arch {
                 c,
                 f,
                 d,
+                v,
                 xlen,
             } => {
                 if *e {
@@ -610,6 +841,9 @@ impl fmt::Display for Arch {
                 if *d {
                     write!(fm, "+d")?;
                 }
+                if *v {
+                    write!(fm, "+v")?;
+                }
                 Ok(())
             }
         }
@@ -684,4 +918,54 @@ mod tests {
             assert_eq!(*arch, arch2);
         }
     }
+
+    #[test]
+    fn arch_parse_armv7a_features() {
+        let arch: Arch = "cortex_a9+fpu+neon".parse().unwrap();
+        assert_eq!(
+            arch,
+            Arch::Armv7A {
+                fpu: true,
+                neon: true
+            }
+        );
+        assert_eq!(arch.to_string(), "cortex_a9+fpu+neon");
+        assert_eq!(
+            arch.build_opt().unwrap().target_triple,
+            "armv7a-none-eabihf"
+        );
+    }
+
+    #[test]
+    fn arch_parse_aarch64_features() {
+        let arch: Arch = "cortex_a53-fp".parse().unwrap();
+        assert_eq!(arch, Arch::Aarch64 { fp: false });
+        assert_eq!(arch.to_string(), "cortex_a53-fp");
+        assert_eq!(
+            arch.build_opt().unwrap().target_triple,
+            "aarch64-unknown-none-softfloat"
+        );
+    }
+
+    #[test]
+    fn arch_parse_riscv_vector() {
+        let arch: Arch = "rv64i+m+a+c+f+d+v".parse().unwrap();
+        assert_eq!(
+            arch,
+            Arch::Riscv {
+                xlen: Xlen::_64,
+                e: false,
+                m: true,
+                a: true,
+                c: true,
+                f: true,
+                d: true,
+                v: true,
+            }
+        );
+        assert_eq!(arch.to_string(), "rv64i+m+a+c+f+d+v");
+        let build_opt = arch.build_opt().unwrap();
+        assert_eq!(build_opt.target_triple, "riscv64imac-unknown-none-elf");
+        assert_eq!(build_opt.target_features, "+f,+d,+v");
+    }
 }
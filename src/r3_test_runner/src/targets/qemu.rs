@@ -19,14 +19,14 @@ pub mod riscv;
 
 struct QemuDebugProbe {
     qemu_cmd: &'static str,
-    qemu_args: &'static [&'static str],
+    qemu_args: Vec<&'static str>,
 }
 
 impl QemuDebugProbe {
-    fn new(qemu_cmd: &'static str, qemu_args: &'static [&'static str]) -> Self {
+    fn new(qemu_cmd: &'static str, qemu_args: &[&'static str]) -> Self {
         Self {
             qemu_cmd,
-            qemu_args,
+            qemu_args: qemu_args.to_vec(),
         }
     }
 }
@@ -39,7 +39,7 @@ impl DebugProbe for QemuDebugProbe {
         let result = subprocess::CmdBuilder::new(self.qemu_cmd)
             .arg("-kernel")
             .arg(exe)
-            .args(self.qemu_args)
+            .args(&self.qemu_args)
             .args([
                 "-nographic",
                 "-d",
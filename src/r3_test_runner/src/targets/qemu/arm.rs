@@ -4,7 +4,36 @@ use std::{future::Future, pin::Pin};
 use super::super::{Arch, DebugProbe, LinkerScripts, Target};
 use super::QemuDebugProbe;
 
-pub struct QemuMps2An385;
+/// The timer driver used by the Arm-M test driver
+#[derive(Debug, Clone, Copy)]
+pub enum ArmMTimer {
+    /// `use_systick_tickful!`
+    Tickful,
+    /// `use_systick_tickless!` with SysTick as the time source
+    Tickless,
+    /// `use_systick_tickless!` with SysTick as the time source and a non-zero
+    /// `SysTickTicklessOptions::RELOAD_COMPENSATION`
+    TicklessCompensated,
+    /// `use_systick_tickless!` with CMSDK APB Timer 0 as the time source
+    TicklessCmsdk,
+}
+
+impl ArmMTimer {
+    fn cargo_features(self, board: &str) -> Vec<String> {
+        match self {
+            Self::Tickful => vec![],
+            Self::Tickless => vec!["timer-tickless".to_owned()],
+            Self::TicklessCompensated => vec!["timer-tickless-compensated".to_owned()],
+            Self::TicklessCmsdk => vec![format!("timer-tickless-cmsdk-{board}")],
+        }
+    }
+}
+
+pub struct QemuMps2An385 {
+    pub timer: ArmMTimer,
+    /// Enable the MPU and run the tests of unprivileged tasks
+    pub mpu: bool,
+}
 
 impl Target for QemuMps2An385 {
     fn target_arch(&self) -> Arch {
@@ -12,7 +41,12 @@ impl Target for QemuMps2An385 {
     }
 
     fn cargo_features(&self) -> Vec<String> {
-        vec!["output-semihosting".to_owned()]
+        let mut features = vec!["output-semihosting".to_owned()];
+        features.extend(self.timer.cargo_features("an385"));
+        if self.mpu {
+            features.push("mpu-an385".to_owned());
+        }
+        features
     }
 
     fn linker_scripts(&self) -> LinkerScripts {
@@ -46,7 +80,11 @@ impl Target for QemuMps2An385 {
     }
 }
 
-pub struct QemuMps2An505;
+pub struct QemuMps2An505 {
+    pub timer: ArmMTimer,
+    /// Enable the MPU and run the tests of unprivileged tasks
+    pub mpu: bool,
+}
 
 impl Target for QemuMps2An505 {
     fn target_arch(&self) -> Arch {
@@ -54,7 +92,12 @@ impl Target for QemuMps2An505 {
     }
 
     fn cargo_features(&self) -> Vec<String> {
-        vec!["output-semihosting".to_owned()]
+        let mut features = vec!["output-semihosting".to_owned()];
+        features.extend(self.timer.cargo_features("an505"));
+        if self.mpu {
+            features.push("mpu-an505".to_owned());
+        }
+        features
     }
 
     fn linker_scripts(&self) -> LinkerScripts {
@@ -130,3 +173,126 @@ impl Target for QemuRealviewPbxA9 {
         })
     }
 }
+
+/// QEMU `virt` machine with Cortex-A15
+pub struct QemuVirtA15 {
+    /// Use GICv3 instead of GICv2
+    pub gic_v3: bool,
+}
+
+impl Target for QemuVirtA15 {
+    fn target_arch(&self) -> Arch {
+        Arch::CORTEX_A9
+    }
+
+    fn cargo_features(&self) -> Vec<String> {
+        let mut features = vec!["board-qemu_virt_a15".to_owned()];
+        if self.gic_v3 {
+            features.push("gic-v3".to_owned());
+        }
+        features
+    }
+
+    fn linker_scripts(&self) -> LinkerScripts {
+        LinkerScripts::arm_harvard(
+            "
+            MEMORY
+            {
+              RAM_CODE : ORIGIN = 0x40000000, LENGTH = 4096K
+              RAM_DATA : ORIGIN = 0x40400000, LENGTH = 4096K
+            }
+            "
+            .to_owned(),
+        )
+    }
+
+    fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Box<dyn DebugProbe>>>>> {
+        let qemu_args: &'static [&'static str] = if self.gic_v3 {
+            &[
+                "-machine",
+                "virt,gic-version=3",
+                "-cpu",
+                "cortex-a15",
+                "-semihosting",
+                "-semihosting-config",
+                "target=native",
+            ]
+        } else {
+            &[
+                "-machine",
+                "virt,gic-version=2",
+                "-cpu",
+                "cortex-a15",
+                "-semihosting",
+                "-semihosting-config",
+                "target=native",
+            ]
+        };
+        Box::pin(async move {
+            Ok(Box::new(QemuDebugProbe::new("qemu-system-arm", qemu_args)) as Box<dyn DebugProbe>)
+        })
+    }
+}
+
+/// QEMU `virt` machine with Cortex-A53 (AArch64)
+pub struct QemuVirtAarch64 {
+    /// Use GICv3 instead of GICv2
+    pub gic_v3: bool,
+}
+
+impl Target for QemuVirtAarch64 {
+    fn target_arch(&self) -> Arch {
+        Arch::CORTEX_A53
+    }
+
+    fn cargo_features(&self) -> Vec<String> {
+        let mut features = vec!["board-qemu_virt".to_owned()];
+        if self.gic_v3 {
+            features.push("gic-v3".to_owned());
+        }
+        features
+    }
+
+    fn linker_scripts(&self) -> LinkerScripts {
+        LinkerScripts::arm_harvard(
+            "
+            MEMORY
+            {
+              RAM_CODE : ORIGIN = 0x40000000, LENGTH = 4096K
+              RAM_DATA : ORIGIN = 0x40400000, LENGTH = 4096K
+            }
+            "
+            .to_owned(),
+        )
+    }
+
+    fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Box<dyn DebugProbe>>>>> {
+        let qemu_args: &'static [&'static str] = if self.gic_v3 {
+            &[
+                "-machine",
+                "virt,gic-version=3",
+                "-cpu",
+                "cortex-a53",
+                "-semihosting",
+                "-semihosting-config",
+                "target=native",
+            ]
+        } else {
+            &[
+                "-machine",
+                "virt,gic-version=2",
+                "-cpu",
+                "cortex-a53",
+                "-semihosting",
+                "-semihosting-config",
+                "target=native",
+            ]
+        };
+        Box::pin(async move {
+            Ok(
+                Box::new(QemuDebugProbe::new("qemu-system-aarch64", qemu_args))
+                    as Box<dyn DebugProbe>,
+            )
+        })
+    }
+}
@@ -78,36 +78,56 @@ impl Target for QemuSiFiveE {
 }
 
 /// The RISC-V board compatible with SiFive U SDK on QEMU
-pub struct QemuSiFiveU(pub Xlen);
+pub struct QemuSiFiveU {
+    pub xlen: Xlen,
+    /// Enable PMP and run the tests of unprivileged tasks
+    pub pmp: bool,
+}
 
 impl Target for QemuSiFiveU {
     fn target_arch(&self) -> Arch {
-        match self.0 {
+        match self.xlen {
             Xlen::_32 => Arch::RV32GC,
             Xlen::_64 => Arch::RV64GC,
         }
     }
 
     fn cargo_features(&self) -> Vec<String> {
-        vec![
+        let mut features = vec![
             "boot-rt".to_owned(),
             "output-u540-uart".to_owned(),
             "interrupt-u540-qemu".to_owned(),
             "timer-clint".to_owned(),
             "board-u540-qemu".to_owned(),
-        ]
+        ];
+        if self.pmp {
+            features.push("pmp-u540-qemu".to_owned());
+        }
+        features
     }
 
     fn linker_scripts(&self) -> LinkerScripts {
-        LinkerScripts::riscv_rt(
+        let (memory, code_region) = if self.pmp {
+            // Separate code and data so that unprivileged tasks can be given
+            // access to each of them by a single PMP entry
+            (
+                "RAM_CODE : ORIGIN = 0x80000000, LENGTH = 8M
+                RAM : ORIGIN = 0x80800000, LENGTH = 8M",
+                "RAM_CODE",
+            )
+        } else {
+            ("RAM : ORIGIN = 0x80000000, LENGTH = 16M", "RAM")
+        };
+
+        LinkerScripts::riscv_rt(format!(
             r#"
             MEMORY
-            {
-                RAM : ORIGIN = 0x80000000, LENGTH = 16M
-            }
+            {{
+                {memory}
+            }}
 
-            REGION_ALIAS("REGION_TEXT", RAM);
-            REGION_ALIAS("REGION_RODATA", RAM);
+            REGION_ALIAS("REGION_TEXT", {code_region});
+            REGION_ALIAS("REGION_RODATA", {code_region});
             REGION_ALIAS("REGION_DATA", RAM);
             REGION_ALIAS("REGION_BSS", RAM);
             REGION_ALIAS("REGION_HEAP", RAM);
@@ -116,12 +136,11 @@ impl Target for QemuSiFiveU {
             _hart_stack_size = 1K;
             _max_hart_id = 1;
         "#
-            .to_owned(),
-        )
+        ))
     }
 
     fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Box<dyn DebugProbe>>>>> {
-        let xlen = self.0;
+        let xlen = self.xlen;
         Box::pin(async move {
             Ok(Box::new(QemuDebugProbe::new(
                 match xlen {
@@ -201,3 +220,118 @@ impl Target for QemuSiFiveUModeS {
         })
     }
 }
+
+/// QEMU `virt` machine (RISC-V VirtIO board)
+pub struct QemuVirt {
+    pub xlen: Xlen,
+    /// Use a bootloader (OpenSBI) to run the kernel in S-mode. Otherwise, the
+    /// kernel runs in M-mode and uses ACLINT (MSWI and MTIMER) directly.
+    pub s_mode: bool,
+    /// Enable the "V" extension (requires QEMU 7.1 or later)
+    pub vector: bool,
+    /// Start two harts and run the driver-defined multiprocessor tests
+    /// (`smp_*`) on both of them. Requires `!s_mode`.
+    pub smp: bool,
+}
+
+impl Target for QemuVirt {
+    fn target_arch(&self) -> Arch {
+        let arch = match self.xlen {
+            Xlen::_32 => Arch::RV32GC,
+            Xlen::_64 => Arch::RV64GC,
+        };
+        if self.vector {
+            arch.with_feature_by_name("v", true).unwrap()
+        } else {
+            arch
+        }
+    }
+
+    fn cargo_features(&self) -> Vec<String> {
+        let mut features = vec![
+            "output-ns16550a-uart".to_owned(),
+            "interrupt-virt-qemu".to_owned(),
+            "board-virt-qemu".to_owned(),
+        ];
+        if self.s_mode {
+            features.push("boot-minimal-s".to_owned());
+            features.push("timer-sbi".to_owned());
+        } else {
+            features.push("boot-rt".to_owned());
+            features.push("timer-clint".to_owned());
+        }
+        if self.smp {
+            features.push("smp-virt-qemu".to_owned());
+        }
+        features
+    }
+
+    fn linker_scripts(&self) -> LinkerScripts {
+        if self.s_mode {
+            // The bootloader occupies the first 2MiB of RAM
+            LinkerScripts::standard(0x80200000)
+        } else {
+            // `riscv-rt` places the stack of hart `i` at `_stack_start -
+            // i * _hart_stack_size`. Hart 0's stack must not overflow into
+            // hart 1's.
+            let (max_hart_id, hart_stack_size) = if self.smp { (1, "8K") } else { (0, "1K") };
+            LinkerScripts::riscv_rt(format!(
+                r#"
+                MEMORY
+                {{
+                    RAM : ORIGIN = 0x80000000, LENGTH = 16M
+                }}
+
+                REGION_ALIAS("REGION_TEXT", RAM);
+                REGION_ALIAS("REGION_RODATA", RAM);
+                REGION_ALIAS("REGION_DATA", RAM);
+                REGION_ALIAS("REGION_BSS", RAM);
+                REGION_ALIAS("REGION_HEAP", RAM);
+                REGION_ALIAS("REGION_STACK", RAM);
+
+                _max_hart_id = {max_hart_id};
+                _hart_stack_size = {hart_stack_size};
+            "#
+            ))
+        }
+    }
+
+    fn connect(&self) -> Pin<Box<dyn Future<Output = Result<Box<dyn DebugProbe>>>>> {
+        let xlen = self.xlen;
+        let mut qemu_args = if self.s_mode {
+            // OpenSBI, the default bootloader, provides the SBI timer
+            vec!["-machine", "virt"]
+        } else {
+            vec!["-machine", "virt,aclint=on", "-bios", "none"]
+        };
+        if self.vector {
+            qemu_args.extend([
+                "-cpu",
+                match xlen {
+                    Xlen::_32 => "rv32,v=true,vlen=128",
+                    Xlen::_64 => "rv64,v=true,vlen=128",
+                },
+            ]);
+        }
+        if self.smp {
+            qemu_args.extend(["-smp", "2"]);
+        }
+        qemu_args.extend([
+            // UART0 → stdout
+            "-serial",
+            "file:/dev/stdout",
+            // Disable monitor
+            "-monitor",
+            "none",
+        ]);
+        Box::pin(async move {
+            Ok(Box::new(QemuDebugProbe::new(
+                match xlen {
+                    Xlen::_32 => "qemu-system-riscv32",
+                    Xlen::_64 => "qemu-system-riscv64",
+                },
+                &qemu_args,
+            )) as Box<dyn DebugProbe>)
+        })
+    }
+}
@@ -0,0 +1,142 @@
+//! Runs unprivileged tasks. Checks that they can use kernel services and are
+//! terminated when they access memory outside their memory regions, even
+//! while holding CPU Lock.
+use r3::{
+    hunk::Hunk,
+    kernel::{
+        prelude::*,
+        task::{MemoryRegion, TaskDefiner},
+        traits, Cfg, StaticTask,
+    },
+};
+
+use super::Driver;
+use crate::utils::SeqTracker;
+
+pub trait SupportedSystem: traits::KernelBase + traits::KernelStatic {}
+impl<T: traits::KernelBase + traits::KernelStatic> SupportedSystem for T {}
+
+pub struct App<System: SupportedSystem> {
+    task2: Option<StaticTask<System>>,
+    task3: Option<StaticTask<System>>,
+    seq: Hunk<System, SeqTracker>,
+}
+
+impl<System: SupportedSystem> App<System> {
+    pub const fn new<C, D: Driver<Self>>(b: &mut Cfg<C>) -> Self
+    where
+        C: ~const traits::CfgTask<System = System>,
+    {
+        StaticTask::define()
+            .start(task1_body::<System, D>)
+            .priority(2)
+            .active(true)
+            .finish(b);
+
+        let (task2, task3) = if D::UNPRIVILEGED_MEMORY_REGIONS.is_empty() {
+            (None, None)
+        } else {
+            let task2 = unprivileged_task::<System, D>(task2_body::<System, D>);
+            let task3 = if D::UNPRIVILEGED_FAULT_ADDRESS.is_some() {
+                Some(unprivileged_task::<System, D>(task3_body::<System, D>).finish(b))
+            } else {
+                None
+            };
+            (Some(task2.finish(b)), task3)
+        };
+
+        let seq = Hunk::<_, SeqTracker>::define().finish(b);
+
+        App { task2, task3, seq }
+    }
+}
+
+const fn unprivileged_task<System: SupportedSystem, D: Driver<App<System>>>(
+    start: fn(),
+) -> TaskDefiner<System> {
+    let mut definer = StaticTask::define()
+        .start(start)
+        .priority(1)
+        .privileged(false);
+
+    let regions = D::UNPRIVILEGED_MEMORY_REGIONS;
+    let mut i = 0;
+    // `for` is unusable in `const fn` [ref:const_for]
+    while i < regions.len() {
+        let (range, access) = &regions[i];
+        definer = definer.memory_region(MemoryRegion::new(
+            range.start,
+            range.end - range.start,
+            *access,
+        ));
+        i += 1;
+    }
+
+    definer
+}
+
+fn task1_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    let (Some(task2), task3) = (D::app().task2, D::app().task3) else {
+        log::warn!("No memory regions defined, skipping the test");
+        D::success();
+        return;
+    };
+
+    D::app().seq.expect_and_replace(0, 1);
+
+    task2.activate().unwrap(); // switching to `task2`
+
+    D::app().seq.expect_and_replace(2, 3);
+
+    task2.unpark_exact().unwrap(); // switching to `task2`
+
+    // `task2` completed or was terminated by the fault
+    D::app().seq.expect_and_replace(4, 5);
+
+    if let Some(task3) = task3 {
+        task3.activate().unwrap(); // switching to `task3`
+
+        // `task3` was terminated by the fault
+        D::app().seq.expect_and_replace(6, 7);
+    } else {
+        log::warn!("No fault address defined, skipping the fault check");
+    }
+
+    D::success();
+}
+
+fn task2_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    D::app().seq.expect_and_replace(1, 2);
+
+    System::park().unwrap(); // blocks, switching to `task1`
+
+    D::app().seq.expect_and_replace(3, 4);
+
+    // CPU Lock can be acquired and released by an unprivileged task
+    System::acquire_cpu_lock().unwrap();
+    assert!(System::has_cpu_lock());
+    unsafe { System::release_cpu_lock().unwrap() };
+    assert!(!System::has_cpu_lock());
+
+    let Some(address) = D::UNPRIVILEGED_FAULT_ADDRESS else { return };
+
+    // Holding CPU Lock doesn't elevate the task. This should cause a fault,
+    // terminating the task.
+    System::acquire_cpu_lock().unwrap();
+    // Safety: The driver guarantees that the access faults
+    let _ = unsafe { (address as *const u32).read_volatile() };
+
+    D::fail();
+}
+
+fn task3_body<System: SupportedSystem, D: Driver<App<System>>>() {
+    D::app().seq.expect_and_replace(5, 6);
+
+    let address = D::UNPRIVILEGED_FAULT_ADDRESS.unwrap();
+
+    // This should cause a fault, terminating the task
+    // Safety: The driver guarantees that the access faults
+    let _ = unsafe { (address as *const u32).read_volatile() };
+
+    D::fail();
+}
@@ -27,7 +27,8 @@ include!(concat!(env!("OUT_DIR"), "/selective_tests.rs"));
 
 /// Kernel tests
 pub mod kernel_tests {
-    use r3::kernel::{raw, InterruptNum, InterruptPriority};
+    use core::ops::Range;
+    use r3::kernel::{raw, task::MemoryAccess, InterruptNum, InterruptPriority};
     /// Instantiation parameters of a test case.
     ///
     /// This trait has two purposes: (1) It serves as an interface to a test driver.
@@ -118,6 +119,21 @@ pub mod kernel_tests {
         ///
         const UNMANAGED_INTERRUPT_PRIORITIES: &'static [InterruptPriority] = &[];
 
+        /// The memory regions to assign to unprivileged tasks.
+        ///
+        ///  - The regions must cover the code and data of the test program
+        ///    except for task stacks, and they must be accepted by the port.
+        ///
+        ///  - The list can be empty if the port doesn't support unprivileged
+        ///    tasks. Some tests will be silently skipped in this case.
+        ///
+        const UNPRIVILEGED_MEMORY_REGIONS: &'static [(Range<usize>, MemoryAccess)] = &[];
+
+        /// An address that causes a fault when an unprivileged task reads from
+        /// it, terminating the task. Some tests will be silently skipped if
+        /// it's `None`.
+        const UNPRIVILEGED_FAULT_ADDRESS: Option<usize> = None;
+
         // TODO: Add instantiations of test suites that provide the following
         //       items once there appears a kernel supporting `NoAccess`:
         /// Create a `RawEventGroupId` for which the kernel functions will
@@ -293,6 +309,7 @@ pub mod kernel_tests {
         (mod task_queue_fifo {}, "task_queue_fifo"),
         (mod task_set_priority {}, "task_set_priority"),
         (mod task_take_interrupt_at_return {}, "task_take_interrupt_at_return"),
+        (mod task_unprivileged {}, "task_unprivileged"),
         (mod time_adjust_event {}, "time_adjust_event"),
         #[cfg(feature = "priority_boost")]
         (mod time_adjust_limits {}, "time_adjust_limits"),